@@ -3,7 +3,8 @@ use std::rc::Rc;
 use common::ref_static_mut;
 
 use crate::{
-  managed_adsr::ManagedAdsr, Adsr, AdsrLengthMode, AdsrStep, RampFn, RENDERED_BUFFER_SIZE,
+  managed_adsr::ManagedAdsr, Adsr, AdsrLengthMode, AdsrLoop, AdsrStep, LoopMode, NoteScalingConfig,
+  RampFn, StepRetrigger, RENDERED_BUFFER_SIZE,
 };
 
 #[cfg(target_arch = "wasm32")]
@@ -62,6 +63,18 @@ fn decode_steps(encoded_steps: &[f32]) -> Vec<AdsrStep> {
             x2: ramp_fn_param_2,
             y2: ramp_fn_param_3,
           },
+          x if x == 4. => RampFn::Sine,
+          x if x == 5. => RampFn::Logarithmic {
+            curvature: ramp_fn_param_0,
+          },
+          x if x == 6. => RampFn::Stairs {
+            step_count: ramp_fn_param_0 as u32,
+          },
+          x if x == 7. => RampFn::Random {
+            point_count: ramp_fn_param_0 as u32,
+            amount: ramp_fn_param_1,
+            seed: ramp_fn_param_2 as u32,
+          },
           other => unreachable!("Invalid ramp fn type val: {other}"),
         };
         AdsrStep {
@@ -89,6 +102,34 @@ pub unsafe extern "C" fn get_encoded_adsr_step_buf_ptr(step_count: usize) -> *mu
   encoded_step_buf.as_mut_ptr()
 }
 
+/// Number of `f32`s needed to encode one loop: `(id, start, end, mode, repeat_count)`.  A negative
+/// `repeat_count` loops until released.
+const LOOP_F32_COUNT: usize = 5;
+
+fn decode_loop(id: f32, start: f32, end: f32, mode: f32, repeat_count: f32) -> AdsrLoop {
+  AdsrLoop {
+    id: id as u32,
+    start,
+    end,
+    mode: LoopMode::from_u32(mode as u32),
+    repeat_count: if repeat_count < 0. {
+      None
+    } else {
+      Some(repeat_count as u32)
+    },
+  }
+}
+
+static mut ENCODED_ADSR_LOOP_BUF: Vec<f32> = Vec::new();
+
+/// Resizes the loop buffer to hold exactly `loop_count` loops
+#[no_mangle]
+pub unsafe extern "C" fn get_encoded_adsr_loop_buf_ptr(loop_count: usize) -> *mut f32 {
+  let encoded_loop_buf = ref_static_mut!(ENCODED_ADSR_LOOP_BUF);
+  encoded_loop_buf.resize(loop_count * LOOP_F32_COUNT, 0.);
+  encoded_loop_buf.as_mut_ptr()
+}
+
 impl AdsrLengthMode {
   pub fn from_u32(val: u32) -> Self {
     match val {
@@ -136,7 +177,7 @@ pub unsafe extern "C" fn create_adsr_ctx(
 
   let length_mode = AdsrLengthMode::from_u32(length_mode);
 
-  let rendered: Rc<[f32; RENDERED_BUFFER_SIZE]> = Rc::new([0.0f32; RENDERED_BUFFER_SIZE]);
+  let rendered: Rc<Vec<f32>> = Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
   let decoded_steps = decode_steps(ref_static_mut!(ENCODED_ADSR_STEP_BUF).as_slice());
   assert!(adsr_count > 0);
 
@@ -199,10 +240,46 @@ pub unsafe extern "C" fn update_adsr_len_ms(
 #[no_mangle]
 pub unsafe extern "C" fn gate_adsr(ctx: *mut AdsrContext, index: usize, cur_beat: f32) {
   let ctx = &mut *ctx;
-  ctx.adsrs[index].adsr.gate(cur_beat);
+  ctx.adsrs[index].adsr.gate(cur_beat, None, None);
   ctx.most_recent_gated_ix = index;
 }
 
+/// Like `gate_adsr`, but with the velocity and MIDI number of the note that triggered the gate so
+/// that the ADSR's note scaling settings can take effect
+#[no_mangle]
+pub unsafe extern "C" fn gate_adsr_with_note(
+  ctx: *mut AdsrContext,
+  index: usize,
+  cur_beat: f32,
+  velocity: u8,
+  midi_number: u8,
+) {
+  let ctx = &mut *ctx;
+  ctx.adsrs[index]
+    .adsr
+    .gate(cur_beat, Some(velocity), Some(midi_number));
+  ctx.most_recent_gated_ix = index;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn adsr_set_note_scaling(
+  ctx: *mut AdsrContext,
+  velocity_to_level: f32,
+  velocity_to_attack_time: f32,
+  key_tracking: f32,
+  key_tracking_center: u8,
+) {
+  let note_scaling = NoteScalingConfig {
+    velocity_to_level,
+    velocity_to_attack_time,
+    key_tracking,
+    key_tracking_center,
+  };
+  for adsr in &mut (*ctx).adsrs {
+    adsr.adsr.note_scaling = note_scaling;
+  }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn ungate_adsr(ctx: *mut AdsrContext, index: usize) {
   let ctx = &mut *ctx;
@@ -240,6 +317,77 @@ pub unsafe extern "C" fn adsr_set_loop_point(ctx: *mut AdsrContext, new_loop_poi
   }
 }
 
+/// Sets the sustain loops for all ADSRs from the encoded loop buffer
+#[no_mangle]
+pub unsafe extern "C" fn adsr_set_loops(ctx: *mut AdsrContext) {
+  let loops: Vec<AdsrLoop> = ref_static_mut!(ENCODED_ADSR_LOOP_BUF)
+    .as_chunks::<LOOP_F32_COUNT>()
+    .0
+    .iter()
+    .map(|&[id, start, end, mode, repeat_count]| decode_loop(id, start, end, mode, repeat_count))
+    .collect();
+  for adsr in &mut (*ctx).adsrs {
+    adsr.adsr.set_loops(loops.clone());
+  }
+}
+
+/// Sets the release loop for all ADSRs.  A negative `start` clears it.
+#[no_mangle]
+pub unsafe extern "C" fn adsr_set_release_loop(
+  ctx: *mut AdsrContext,
+  id: u32,
+  start: f32,
+  end: f32,
+  mode: u32,
+  repeat_count: i32,
+) {
+  let release_loop = if start < 0. {
+    None
+  } else {
+    Some(decode_loop(
+      id as f32,
+      start,
+      end,
+      mode as f32,
+      repeat_count as f32,
+    ))
+  };
+  for adsr in &mut (*ctx).adsrs {
+    adsr.adsr.set_release_loop(release_loop);
+  }
+}
+
+/// Returns the id of the loop being played by the most recently gated ADSR, or -1 if it isn't
+/// in a loop
+#[no_mangle]
+pub unsafe extern "C" fn adsr_get_active_loop_id(ctx: *mut AdsrContext) -> i32 {
+  let ctx = &*ctx;
+  match ctx.adsrs[ctx.most_recent_gated_ix]
+    .adsr
+    .get_active_loop_id()
+  {
+    Some(id) => id as i32,
+    None => -1,
+  }
+}
+
+/// `continue_from_current` selects `StepRetrigger::Continue` for the step at `step_ix`
+#[no_mangle]
+pub unsafe extern "C" fn adsr_set_step_retrigger(
+  ctx: *mut AdsrContext,
+  step_ix: usize,
+  continue_from_current: bool,
+) {
+  let retrigger = if continue_from_current {
+    StepRetrigger::Continue
+  } else {
+    StepRetrigger::Reset
+  };
+  for adsr in &mut (*ctx).adsrs {
+    adsr.adsr.set_step_retrigger(step_ix, retrigger);
+  }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn adsr_set_release_start_phase(
   ctx: *mut AdsrContext,
@@ -17,9 +17,31 @@ extern "C" {
 
 /// Samples per second
 const SAMPLE_RATE: usize = 44_100;
+/// Minimum size of the rendered buffer.  Longer envelopes use larger buffers; see
+/// `rendered_buffer_size_for_len`.
 pub const RENDERED_BUFFER_SIZE: usize = SAMPLE_RATE;
+/// Upper bound on the size of the rendered buffer to keep memory usage and re-render time in check
+/// for extremely long envelopes
+const MAX_RENDERED_BUFFER_SIZE: usize = RENDERED_BUFFER_SIZE * 32;
+/// Target for the maximum number of output samples covered by each entry in the rendered buffer
+const MAX_SAMPLES_PER_RENDERED_ENTRY: f32 = 4.;
 const FRAME_SIZE: usize = 128;
 
+/// Returns the size of the rendered buffer to use for an envelope that is `len_samples` samples
+/// long.  Sizes are quantized to power-of-two multiples of `RENDERED_BUFFER_SIZE` so that only a
+/// few distinct sizes exist.  `Adsr::set_len` only ever grows the buffer, so lengths changing
+/// across a size boundary (from tempo changes in beats length mode, for example) re-render at
+/// most once per size.
+pub fn rendered_buffer_size_for_len(len_samples: f32) -> usize {
+  let mut size = RENDERED_BUFFER_SIZE;
+  while size < MAX_RENDERED_BUFFER_SIZE
+    && len_samples / size as f32 > MAX_SAMPLES_PER_RENDERED_ENTRY
+  {
+    size *= 2;
+  }
+  size
+}
+
 #[derive(Clone, Copy)]
 #[repr(u32)]
 pub enum AdsrLengthMode {
@@ -33,6 +55,26 @@ pub enum RampFn {
   Linear,
   Exponential { exponent: f32 },
   Bezier { x1: f32, y1: f32, x2: f32, y2: f32 },
+  /// Half-cosine S-curve that eases in and out of both ends
+  Sine,
+  /// Logarithmic curve that moves quickly at the start and slows down towards the end.  Higher
+  /// `curvature` values produce a more extreme curve.
+  Logarithmic {
+    curvature: f32,
+  },
+  /// Moves from the previous step's value to the next step's value through `step_count` equally
+  /// spaced levels, including both ends.  That's `step_count - 1` equal jumps.
+  Stairs {
+    step_count: u32,
+  },
+  /// Piecewise-linear random walk between the previous and next step's values.  `point_count`
+  /// random points are placed evenly through the segment, each offset from the linear ramp by up
+  /// to `amount`.  The same `seed` always produces the same curve.
+  Random {
+    point_count: u32,
+    amount: f32,
+    seed: u32,
+  },
 }
 
 impl RampFn {
@@ -47,6 +89,16 @@ impl RampFn {
         x2: param2,
         y2: param3,
       },
+      4 => Self::Sine,
+      5 => Self::Logarithmic { curvature: param0 },
+      6 => Self::Stairs {
+        step_count: param0 as u32,
+      },
+      7 => Self::Random {
+        point_count: param0 as u32,
+        amount: param1,
+        seed: param2 as u32,
+      },
       _ => panic!("Invlaid ramper fn type: {}", type_val),
     }
   }
@@ -57,6 +109,17 @@ fn eval_cubic_bezier(y0: f32, y1: f32, y2: f32, y3: f32, t: f32) -> f32 {
   y0 * mt.powi(3) + 3. * y1 * mt.powi(2) * t + 3. * y2 * mt * t.powi(2) + y3 * t.powi(3)
 }
 
+/// Deterministic hash of `(seed, ix)` mapped to [-1, 1]
+fn hash_noise(seed: u32, ix: u32) -> f32 {
+  let mut x = seed.wrapping_mul(0x9E37_79B9) ^ ix.wrapping_mul(0x85EB_CA6B);
+  x ^= x >> 16;
+  x = x.wrapping_mul(0x7FEB_352D);
+  x ^= x >> 15;
+  x = x.wrapping_mul(0x846C_A68B);
+  x ^= x >> 16;
+  (x as f32 / u32::MAX as f32) * 2. - 1.
+}
+
 fn compute_pos(prev_step: &AdsrStep, next_step: &AdsrStep, phase: f32) -> f32 {
   let distance = next_step.x - prev_step.x;
   debug_assert!(distance > 0.);
@@ -80,6 +143,46 @@ fn compute_pos(prev_step: &AdsrStep, next_step: &AdsrStep, phase: f32) -> f32 {
       let t = (phase - prev_step.x) / (next_step.x - prev_step.x);
       eval_cubic_bezier(y0, y1, y2, y3, t)
     },
+    RampFn::Sine => {
+      let x = (phase - prev_step.x) / distance;
+      let eased = 0.5 - 0.5 * (x * std::f32::consts::PI).cos();
+      prev_step.y + eased * (next_step.y - prev_step.y)
+    },
+    RampFn::Logarithmic { curvature } => {
+      let x = (phase - prev_step.x) / distance;
+      let curvature = curvature.max(0.0001);
+      let curved = (1. + curvature * x).ln() / (1. + curvature).ln();
+      prev_step.y + curved * (next_step.y - prev_step.y)
+    },
+    RampFn::Stairs { step_count } => {
+      let x = (phase - prev_step.x) / distance;
+      let stepped = if step_count <= 1 {
+        0.
+      } else {
+        ((x * step_count as f32).floor() / (step_count - 1) as f32).min(1.)
+      };
+      prev_step.y + stepped * (next_step.y - prev_step.y)
+    },
+    RampFn::Random {
+      point_count,
+      amount,
+      seed,
+    } => {
+      let x = (phase - prev_step.x) / distance;
+      let linear = prev_step.y + x * (next_step.y - prev_step.y);
+      // The endpoints are pinned to zero offset so the curve joins its neighbors cleanly
+      let segment_count = point_count + 1;
+      let pos = x * segment_count as f32;
+      let ix = (pos.floor() as u32).min(segment_count - 1);
+      let offset_at = |ix: u32| {
+        if ix == 0 || ix >= segment_count {
+          0.
+        } else {
+          hash_noise(seed, ix) * amount
+        }
+      };
+      linear + dsp::mix(pos - ix as f32, offset_at(ix + 1), offset_at(ix))
+    },
   }
 }
 
@@ -90,6 +193,164 @@ pub struct AdsrStep {
   pub ramper: RampFn,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoopMode {
+  /// Jump back to the start of the loop when the end is reached
+  Forward,
+  /// Reverse direction at the end of the loop and play back to the start before going forward
+  /// again
+  PingPong,
+}
+
+impl LoopMode {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => LoopMode::Forward,
+      1 => LoopMode::PingPong,
+      _ => panic!("Invalid loop mode: {}", val),
+    }
+  }
+}
+
+/// A region of the envelope, in phase, that is played repeatedly
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AdsrLoop {
+  /// Identifies the loop to the UI, which owns its name.  The id of the loop currently being
+  /// played is reported by `Adsr::get_active_loop_id`.
+  pub id: u32,
+  pub start: f32,
+  pub end: f32,
+  pub mode: LoopMode,
+  /// Number of times the loop is repeated before the envelope continues past its end.  If `None`,
+  /// the loop repeats until the ADSR is released (or forever for release loops).
+  pub repeat_count: Option<u32>,
+}
+
+/// Determines what happens to the output when the envelope jumps to a segment, either because a
+/// loop wrapped around or because the ADSR was re-gated.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StepRetrigger {
+  /// The segment starts from the envelope's value at the jump destination, producing a
+  /// discontinuity if that differs from the current output
+  Reset,
+  /// The segment ramps from the current output value to the step's value, avoiding any jumps
+  Continue,
+}
+
+/// Tracks progress through the loops of the currently active loop set
+#[derive(Clone, Copy, Default, Debug)]
+struct LoopState {
+  /// Index of the first loop that hasn't yet been exhausted
+  next_loop_ix: usize,
+  /// Number of times the current loop has repeated
+  iterations: u32,
+  /// `true` if we're in the reverse half of a ping-pong loop
+  reversed: bool,
+}
+
+/// Offset added to the rendered envelope to implement `StepRetrigger::Continue`.  It decays
+/// linearly to zero over the course of the segment.
+#[derive(Clone, Copy, Debug)]
+struct ContinueOffset {
+  offset: f32,
+  start_phase: f32,
+  end_phase: f32,
+}
+
+impl ContinueOffset {
+  fn get(&self, phase: f32) -> f32 {
+    if phase < self.start_phase || phase >= self.end_phase {
+      return 0.;
+    }
+
+    let progress = (phase - self.start_phase) / (self.end_phase - self.start_phase);
+    self.offset * (1. - progress)
+  }
+}
+
+/// Handles the case where `phase` has crossed a boundary of the loop at `state.next_loop_ix`,
+/// wrapping or reflecting it.  `prev_phase` is the phase before the current sample's advance.
+/// Returns `true` if the phase jumped discontinuously.
+fn apply_loops(
+  loops: &[AdsrLoop],
+  state: &mut LoopState,
+  prev_phase: f32,
+  phase: &mut f32,
+) -> bool {
+  // A loop only applies if the phase reaches its end by advancing through the envelope.  If the
+  // phase was already past the end before this sample, it was moved there from outside (gating
+  // into the middle of the envelope in beats length mode, for example) and the loop is skipped.
+  while !state.reversed
+    && loops
+      .get(state.next_loop_ix)
+      .is_some_and(|lp| prev_phase >= lp.end)
+  {
+    state.next_loop_ix += 1;
+    state.iterations = 0;
+  }
+
+  let Some(lp) = loops.get(state.next_loop_ix) else {
+    return false;
+  };
+  let loop_len = lp.end - lp.start;
+
+  if state.reversed {
+    if *phase > lp.start {
+      return false;
+    }
+
+    // Bounced off the start of a ping-pong loop; that completes one iteration
+    state.reversed = false;
+    state.iterations += 1;
+    *phase = (lp.start + (lp.start - *phase)).min(lp.end);
+    return false;
+  }
+
+  if *phase < lp.end {
+    return false;
+  }
+
+  if lp
+    .repeat_count
+    .is_some_and(|repeat_count| state.iterations >= repeat_count)
+  {
+    state.next_loop_ix += 1;
+    state.iterations = 0;
+    return false;
+  }
+
+  let overflow = *phase - lp.end;
+  match lp.mode {
+    LoopMode::Forward => {
+      state.iterations += 1;
+      *phase = lp.start + (overflow / loop_len).fract() * loop_len;
+      true
+    },
+    LoopMode::PingPong => {
+      state.reversed = true;
+      *phase = (lp.end - overflow).max(lp.start);
+      false
+    },
+  }
+}
+
+/// Sorts loops by start, clamps them into `[0, max_end]`, and drops any that are empty or that
+/// overlap a previous loop.
+fn sanitize_loops(mut loops: Vec<AdsrLoop>, max_end: f32) -> Vec<AdsrLoop> {
+  loops.sort_by(|a, b| a.start.total_cmp(&b.start));
+  let mut last_end = 0f32;
+  loops.retain_mut(|lp| {
+    lp.start = dsp::clamp(last_end, max_end, lp.start);
+    lp.end = dsp::clamp(0., max_end, lp.end);
+    if lp.end <= lp.start {
+      return false;
+    }
+    last_end = lp.end;
+    true
+  });
+  loops
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum GateStatus {
   Gated,
@@ -137,6 +398,68 @@ pub enum EarlyReleaseStrategy {
   ScanToMatchThenFollow,
 }
 
+/// Controls how the velocity and key of the note that gated the ADSR affect its output level and
+/// timing.  The defaults leave the envelope unchanged for all notes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoteScalingConfig {
+  /// From 0 to 1.  At 0, velocity has no effect on level.  At 1, the envelope's level is scaled
+  /// linearly by `velocity / 127`.
+  pub velocity_to_level: f32,
+  /// From 0 to 1.  Higher velocities shorten the attack, which is the portion of the envelope
+  /// leading up to its highest point before the release.  At 1, a velocity of 127 makes the attack
+  /// almost instant.
+  pub velocity_to_attack_time: f32,
+  /// How much the overall envelope length follows the key, in octaves of length per octave of
+  /// pitch.  At 1, each octave above `key_tracking_center` halves the envelope's length and each
+  /// octave below doubles it.  Negative values lengthen envelopes for higher notes.
+  pub key_tracking: f32,
+  /// MIDI number at which key tracking has no effect
+  pub key_tracking_center: u8,
+}
+
+impl Default for NoteScalingConfig {
+  fn default() -> Self {
+    NoteScalingConfig {
+      velocity_to_level: 0.,
+      velocity_to_attack_time: 0.,
+      key_tracking: 0.,
+      key_tracking_center: 60,
+    }
+  }
+}
+
+/// Minimum multiplier for attack time to avoid dividing by zero when `velocity_to_attack_time` is
+/// maxed out
+const MIN_ATTACK_TIME_MULTIPLIER: f32 = 0.02;
+
+impl NoteScalingConfig {
+  pub fn level_multiplier(&self, velocity: Option<u8>) -> f32 {
+    match velocity {
+      Some(velocity) =>
+        1. - self.velocity_to_level + self.velocity_to_level * (velocity as f32 / 127.),
+      None => 1.,
+    }
+  }
+
+  pub fn attack_time_multiplier(&self, velocity: Option<u8>) -> f32 {
+    match velocity {
+      Some(velocity) => (1. - self.velocity_to_attack_time * (velocity as f32 / 127.))
+        .max(MIN_ATTACK_TIME_MULTIPLIER),
+      None => 1.,
+    }
+  }
+
+  pub fn length_multiplier(&self, key: Option<u8>) -> f32 {
+    match key {
+      Some(key) => {
+        let octaves = (key as f32 - self.key_tracking_center as f32) / 12.;
+        2f32.powf(-self.key_tracking * octaves)
+      },
+      None => 1.,
+    }
+  }
+}
+
 /// Determines how we handle releasing if the ADSR is ungated before the release point
 #[derive(Clone)]
 pub struct EarlyReleaseConfig {
@@ -196,8 +519,9 @@ pub struct Adsr {
   /// `loop_start_point` until it is released.
   loop_start_point: Option<f32>,
   /// Contains the rendered waveform the for ADSR from start to end, used as an optimization to
-  /// avoid having to compute ramp points every sample
-  pub rendered: Rc<[f32; RENDERED_BUFFER_SIZE]>,
+  /// avoid having to compute ramp points every sample.  Its size depends on `len_samples`; see
+  /// `rendered_buffer_size_for_len`.
+  pub rendered: Rc<Vec<f32>>,
   /// A buffer into which the current output for the ADSR is rendered each frame
   cur_frame_output: Box<[f32; FRAME_SIZE]>,
   pub len_samples: f32,
@@ -215,6 +539,27 @@ pub struct Adsr {
   /// containing the current phase of all active ADSRs.
   pub store_phase_to: Option<*mut f32>,
   pub log_scale: bool,
+  pub note_scaling: NoteScalingConfig,
+  /// Output level multiplier computed from the velocity of the current gate
+  level_multiplier: f32,
+  /// Multiplier for the rate at which phase advances, computed from the key of the current gate
+  length_rate_multiplier: f32,
+  /// Multiplier for the rate at which phase advances during the attack, computed from the velocity
+  /// of the current gate
+  attack_rate_multiplier: f32,
+  /// Phase of the highest step before the release point; the end of the attack
+  attack_end_phase: f32,
+  /// Loops that are played while the ADSR is gated, sorted by start phase and non-overlapping.
+  /// They must lie before `release_start_phase`.  These are independent from `loop_start_point`,
+  /// which is applied after them.
+  loops: Vec<AdsrLoop>,
+  /// Loop that is played after the ADSR has been released.  Must lie after `release_start_phase`.
+  release_loop: Option<AdsrLoop>,
+  loop_state: LoopState,
+  /// Retrigger behavior for each step, indexed by step index.  Steps without an entry use
+  /// `StepRetrigger::Reset`.
+  step_retrigger: Vec<StepRetrigger>,
+  continue_offset: Option<ContinueOffset>,
 }
 
 const DEFAULT_FIRST_STEP: AdsrStep = AdsrStep {
@@ -230,7 +575,7 @@ impl Adsr {
     len_samples: f32,
     len_beats: Option<f32>,
     release_start_phase: f32,
-    rendered: Rc<[f32; RENDERED_BUFFER_SIZE]>,
+    rendered: Rc<Vec<f32>>,
     early_release_config: EarlyReleaseConfig,
     log_scale: bool,
   ) -> Self {
@@ -252,6 +597,76 @@ impl Adsr {
       cached_phase_diff_per_sample: (1. / len_samples),
       store_phase_to: None,
       log_scale,
+      note_scaling: NoteScalingConfig::default(),
+      level_multiplier: 1.,
+      length_rate_multiplier: 1.,
+      attack_rate_multiplier: 1.,
+      attack_end_phase: 0.,
+      loops: Vec::new(),
+      release_loop: None,
+      loop_state: LoopState::default(),
+      step_retrigger: Vec::new(),
+      continue_offset: None,
+    }
+  }
+
+  fn has_multi_segment_loops(&self) -> bool {
+    !self.loops.is_empty() || self.release_loop.is_some()
+  }
+
+  /// Beat sync can't be used if anything makes the envelope's phase diverge from the global beat
+  fn can_beat_sync(&self) -> bool {
+    !self.has_multi_segment_loops()
+      && self.length_rate_multiplier == 1.
+      && self.attack_rate_multiplier == 1.
+  }
+
+  fn compute_attack_end_phase(&self) -> f32 {
+    let mut attack_end = (0., f32::NEG_INFINITY);
+    for step in self
+      .steps
+      .iter()
+      .take_while(|step| step.x <= self.release_start_phase)
+    {
+      if step.y > attack_end.1 {
+        attack_end = (step.x, step.y);
+      }
+    }
+    attack_end.0
+  }
+
+  /// Returns the current output value of the envelope, before scaling
+  fn read_value(&self) -> f32 {
+    let rendered_val = dsp::read_interpolated(
+      &*self.rendered,
+      self.phase * (self.rendered.len() - 2) as f32,
+    );
+    match self.continue_offset {
+      Some(continue_offset) => rendered_val + continue_offset.get(self.phase),
+      None => rendered_val,
+    }
+  }
+
+  /// Called after the phase has jumped discontinuously to set up the offset for
+  /// `StepRetrigger::Continue` if the segment we landed in is configured to use it.  `prev_value`
+  /// is the output before the jump including the level multiplier.
+  fn handle_retrigger(&mut self, prev_value: f32) {
+    self.continue_offset = None;
+
+    let Some(step_ix) = self.steps.iter().position(|step| step.x > self.phase) else {
+      return;
+    };
+    if self.step_retrigger.get(step_ix).copied() != Some(StepRetrigger::Continue) {
+      return;
+    }
+
+    let offset = prev_value / self.level_multiplier.max(f32::EPSILON) - self.read_value();
+    if offset != 0. {
+      self.continue_offset = Some(ContinueOffset {
+        offset,
+        start_phase: self.phase,
+        end_phase: self.steps[step_ix].x,
+      });
     }
   }
 
@@ -280,7 +695,15 @@ impl Adsr {
     }
   }
 
-  pub fn gate(&mut self, cur_beat: f32) {
+  /// Starts the envelope.  `velocity` and `key` are the MIDI velocity and note number that
+  /// triggered it, if any, and are used along with `note_scaling` to adjust its level and timing.
+  pub fn gate(&mut self, cur_beat: f32, velocity: Option<u8>, key: Option<u8>) {
+    let prev_value = self.read_value() * self.level_multiplier;
+    self.level_multiplier = self.note_scaling.level_multiplier(velocity);
+    self.length_rate_multiplier = 1. / self.note_scaling.length_multiplier(key);
+    self.attack_rate_multiplier = 1. / self.note_scaling.attack_time_multiplier(velocity);
+    self.attack_end_phase = self.compute_attack_end_phase();
+    self.loop_state = LoopState::default();
     self.phase = match self.len_beats {
       Some(len_beats) => self.compute_phase_given_beats(len_beats, cur_beat),
       None => 0.,
@@ -292,21 +715,23 @@ impl Adsr {
     );
     self.gated_beat = cur_beat;
     self.gate_status = GateStatus::Gated;
+    self.handle_retrigger(prev_value);
   }
 
   pub fn ungate(&mut self) {
+    // Sustain loops are finished; the state is re-used for the release loop
+    self.loop_state = LoopState::default();
+
     if self.phase < self.release_start_phase {
-      let cur_y = dsp::read_interpolated(
-        &*self.rendered,
-        self.phase * (RENDERED_BUFFER_SIZE - 2) as f32,
-      );
+      let cur_y = self.read_value();
+      self.continue_offset = None;
 
       'scan: {
         if self.early_release_config.strategy != EarlyReleaseStrategy::ScanToMatchThenFollow {
           break 'scan;
         }
 
-        let release_start_ix = ((self.release_start_phase * (RENDERED_BUFFER_SIZE - 2) as f32)
+        let release_start_ix = ((self.release_start_phase * (self.rendered.len() - 2) as f32)
           as usize)
           .saturating_sub(2);
 
@@ -335,7 +760,7 @@ impl Adsr {
         self.phase = dsp::clamp(
           0.,
           1.,
-          (ix + release_start_ix as f32) / ((RENDERED_BUFFER_SIZE - 2) as f32),
+          (ix + release_start_ix as f32) / ((self.rendered.len() - 2) as f32),
         );
 
         self.gate_status = GateStatus::Releasing;
@@ -347,7 +772,7 @@ impl Adsr {
         start_y: cur_y,
         release_start_point_y: dsp::read_interpolated(
           &*self.rendered,
-          self.release_start_phase * (RENDERED_BUFFER_SIZE - 2) as f32,
+          self.release_start_phase * (self.rendered.len() - 2) as f32,
         ),
         cur_progress_samples: 0,
       };
@@ -367,9 +792,11 @@ impl Adsr {
     let mut next_step_opt: Option<&AdsrStep> = self.steps.get(0);
     let mut next_step_ix = 0usize;
     let buf = unsafe { Rc::get_mut_unchecked(&mut self.rendered) };
+    buf.resize(rendered_buffer_size_for_len(self.len_samples), 0.);
+    let buf_len = buf.len();
 
-    for i in 0..RENDERED_BUFFER_SIZE {
-      let phase = i as f32 / RENDERED_BUFFER_SIZE as f32;
+    for i in 0..buf_len {
+      let phase = i as f32 / buf_len as f32;
 
       // Check to see if we've reached past the `next_step` and move through the steps if so
       while let Some(next_step) = next_step_opt.as_mut() {
@@ -412,17 +839,29 @@ impl Adsr {
     // forever instead of getting stuck a few indices before the end due to mixing/etc.
     match self.steps.last() {
       Some(step) if step.x == 1. => {
-        buf[buf.len() - 2] = step.y;
-        buf[buf.len() - 1] = step.y;
+        buf[buf_len - 2] = step.y;
+        buf[buf_len - 1] = step.y;
       },
       _ => (),
     }
   }
 
+  /// If the new length needs a larger rendered buffer than the current one, the shared buffer is
+  /// grown and re-rendered.  Other ADSRs sharing the buffer will see that it's already large
+  /// enough and skip re-rendering.
+  ///
+  /// This is called every frame, so the buffer is never shrunk here.  Otherwise lengths hovering
+  /// around a size boundary, or ADSRs sharing the buffer with different lengths, would re-render
+  /// and reallocate it repeatedly on the audio thread.  It's sized back down to fit the current
+  /// length the next time `render` is called after the steps change.
   pub fn set_len(&mut self, new_len_samples: f32, new_len_beats: Option<f32>) {
     self.len_samples = new_len_samples;
     self.len_beats = new_len_beats;
     self.cached_phase_diff_per_sample = 1. / new_len_samples;
+
+    if rendered_buffer_size_for_len(new_len_samples) > self.rendered.len() {
+      self.render();
+    }
   }
 
   /// Advance phase by one sample's worth
@@ -457,9 +896,23 @@ impl Adsr {
       }
     }
 
-    let phase_diff = self.cached_phase_diff_per_sample / oversample_factor as f32;
+    let mut phase_diff =
+      self.cached_phase_diff_per_sample * self.length_rate_multiplier / oversample_factor as f32;
+    if matches!(self.gate_status, GateStatus::Gated) && self.phase < self.attack_end_phase {
+      phase_diff *= self.attack_rate_multiplier;
+    }
+    let phase_diff = if self.loop_state.reversed {
+      -phase_diff
+    } else {
+      phase_diff
+    };
+    let prev_phase = self.phase;
 
-    if *cur_frame_start_phase > -1. && self.len_beats.is_some() && cur_frame_start_beat > 0. {
+    if *cur_frame_start_phase > -1.
+      && self.len_beats.is_some()
+      && cur_frame_start_beat > 0.
+      && self.can_beat_sync()
+    {
       let len_beats = self.len_beats.unwrap();
 
       // In order to keep our phase in sync with the global beat counter and prevent drift
@@ -488,6 +941,29 @@ impl Adsr {
       }
     }
 
+    if let Some(continue_offset) = self.continue_offset {
+      if self.phase >= continue_offset.end_phase {
+        self.continue_offset = None;
+      }
+    }
+
+    let active_loops = match self.gate_status {
+      GateStatus::Gated => self.loops.as_slice(),
+      GateStatus::Releasing => self.release_loop.as_slice(),
+      _ => &[],
+    };
+    if !active_loops.is_empty() {
+      let prev_value = self.read_value() * self.level_multiplier;
+      if apply_loops(
+        active_loops,
+        &mut self.loop_state,
+        prev_phase,
+        &mut self.phase,
+      ) {
+        self.handle_retrigger(prev_value);
+      }
+    }
+
     // We are gating and have crossed the release point
     if matches!(self.gate_status, GateStatus::Gated) && self.phase >= self.release_start_phase {
       // Disable the global beat sync if we've reset the loop since we can no longer
@@ -557,10 +1033,7 @@ impl Adsr {
           "bad phase: {}",
           self.phase
         );
-        dsp::read_interpolated(
-          &*self.rendered,
-          self.phase * (RENDERED_BUFFER_SIZE - 2) as f32,
-        )
+        self.read_value()
       },
     };
 
@@ -599,7 +1072,7 @@ impl Adsr {
         OVERSAMPLE_FACTOR,
       );
     }
-    let sample = sample / OVERSAMPLE_FACTOR as f32;
+    let sample = sample / OVERSAMPLE_FACTOR as f32 * self.level_multiplier;
     if self.log_scale {
       sample * 100.
     } else {
@@ -669,7 +1142,7 @@ impl Adsr {
       GateStatus::EarlyRelease {
         start_y, start_x, ..
       } if self.early_release_config.strategy == EarlyReleaseStrategy::Freeze => {
-        self.fill_buffer_with_value(start_y, scale, shift);
+        self.fill_buffer_with_value(start_y * self.level_multiplier, scale, shift);
         self.gate_status = GateStatus::Done;
         self.phase = start_x;
         self.maybe_write_cur_phase();
@@ -733,7 +1206,7 @@ impl Adsr {
     self.gate_status = GateStatus::Done;
     self.phase = phase;
     let new_frozen_output_value =
-      dsp::read_interpolated(&*self.rendered, phase * (RENDERED_BUFFER_SIZE - 2) as f32);
+      dsp::read_interpolated(&*self.rendered, phase * (self.rendered.len() - 2) as f32);
     self.fill_buffer_with_value(new_frozen_output_value, scale, shift);
   }
 
@@ -751,5 +1224,63 @@ impl Adsr {
       Some(loop_point) => new_release_start_phase.max(loop_point),
       _ => new_release_start_phase,
     };
+    self.loops = sanitize_loops(std::mem::take(&mut self.loops), self.release_start_phase);
+    if let Some(release_loop) = self.release_loop {
+      self.set_release_loop(Some(release_loop));
+    }
+  }
+
+  /// Sets the loops that are played while the ADSR is gated.  Loops are sorted, clamped to end
+  /// before the release point, and any that overlap a previous loop are dropped.
+  pub fn set_loops(&mut self, new_loops: Vec<AdsrLoop>) {
+    self.loops = sanitize_loops(new_loops, self.release_start_phase);
+    self.loop_state = LoopState::default();
+  }
+
+  /// Sets the loop that is played after the ADSR is released.  It's clamped to lie between the
+  /// release point and the end of the envelope.
+  pub fn set_release_loop(&mut self, new_release_loop: Option<AdsrLoop>) {
+    self.release_loop = new_release_loop.and_then(|mut lp| {
+      lp.start = dsp::clamp(self.release_start_phase, 1., lp.start);
+      lp.end = dsp::clamp(self.release_start_phase, 1., lp.end);
+      if lp.end <= lp.start {
+        None
+      } else {
+        Some(lp)
+      }
+    });
+    if matches!(self.gate_status, GateStatus::Releasing) {
+      self.loop_state = LoopState::default();
+    }
+  }
+
+  pub fn set_step_retrigger_modes(&mut self, new_step_retrigger: Vec<StepRetrigger>) {
+    self.step_retrigger = new_step_retrigger;
+  }
+
+  pub fn set_step_retrigger(&mut self, step_ix: usize, retrigger: StepRetrigger) {
+    if self.step_retrigger.len() <= step_ix {
+      self
+        .step_retrigger
+        .resize(step_ix + 1, StepRetrigger::Reset);
+    }
+    self.step_retrigger[step_ix] = retrigger;
+  }
+
+  pub fn get_loops(&self) -> &[AdsrLoop] { &self.loops }
+
+  /// Returns the id of the loop that the envelope is currently playing, if any
+  pub fn get_active_loop_id(&self) -> Option<u32> {
+    let active_loops = match self.gate_status {
+      GateStatus::Gated => self.loops.as_slice(),
+      GateStatus::Releasing => self.release_loop.as_slice(),
+      _ => return None,
+    };
+    active_loops
+      .get(self.loop_state.next_loop_ix)
+      .filter(|lp| self.phase >= lp.start && self.phase <= lp.end)
+      .map(|lp| lp.id)
   }
+
+  pub fn get_release_loop(&self) -> Option<&AdsrLoop> { self.release_loop.as_ref() }
 }
@@ -0,0 +1,125 @@
+use std::rc::Rc;
+
+use crate::{
+  rendered_buffer_size_for_len, Adsr, AdsrStep, EarlyReleaseConfig, RampFn, RENDERED_BUFFER_SIZE,
+};
+
+fn mk_single_segment_adsr(ramper: RampFn) -> Adsr {
+  let steps = vec![
+    AdsrStep {
+      x: 0.,
+      y: 0.,
+      ramper: RampFn::Instant,
+    },
+    AdsrStep {
+      x: 1.,
+      y: 1.,
+      ramper,
+    },
+  ];
+  let mut adsr = Adsr::new(
+    steps,
+    None,
+    1_000.,
+    None,
+    1.,
+    Rc::new(vec![0.; RENDERED_BUFFER_SIZE]),
+    EarlyReleaseConfig::default(),
+    false,
+  );
+  adsr.render();
+  adsr
+}
+
+fn value_at(adsr: &Adsr, phase: f32) -> f32 {
+  dsp::read_interpolated(&adsr.rendered, phase * (adsr.rendered.len() - 2) as f32)
+}
+
+#[test]
+fn sine_curve_is_symmetric_s_curve() {
+  let adsr = mk_single_segment_adsr(RampFn::Sine);
+  assert!((value_at(&adsr, 0.5) - 0.5).abs() < 1e-3);
+  // eases in, so it's below the linear ramp in the first half and above it in the second
+  assert!(value_at(&adsr, 0.2) < 0.2);
+  assert!(value_at(&adsr, 0.8) > 0.8);
+  assert!((value_at(&adsr, 0.2) + value_at(&adsr, 0.8) - 1.).abs() < 1e-3);
+}
+
+#[test]
+fn logarithmic_curve_rises_quickly() {
+  let adsr = mk_single_segment_adsr(RampFn::Logarithmic { curvature: 10. });
+  assert!(value_at(&adsr, 0.2) > 0.4);
+  assert!(value_at(&adsr, 0.99) > 0.98);
+}
+
+#[test]
+fn stairs_curve_has_step_count_levels() {
+  let adsr = mk_single_segment_adsr(RampFn::Stairs { step_count: 4 });
+  let mut levels: Vec<f32> = adsr.rendered.iter().copied().collect();
+  levels.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+  assert_eq!(levels, vec![0., 1. / 3., 2. / 3., 1.]);
+}
+
+#[test]
+fn random_curve_is_deterministic_and_pinned() {
+  let ramper = RampFn::Random {
+    point_count: 8,
+    amount: 0.2,
+    seed: 1234,
+  };
+  let a = mk_single_segment_adsr(ramper);
+  let b = mk_single_segment_adsr(ramper);
+  assert_eq!(a.rendered, b.rendered);
+  assert!(value_at(&a, 0.).abs() < 1e-3);
+  assert!((a.rendered[a.rendered.len() - 1] - 1.).abs() < 1e-3);
+
+  let max_deviation = a
+    .rendered
+    .iter()
+    .enumerate()
+    .map(|(i, &y)| (y - i as f32 / a.rendered.len() as f32).abs())
+    .fold(0f32, f32::max);
+  assert!(max_deviation > 0.01 && max_deviation <= 0.2 + 1e-3);
+
+  let c = mk_single_segment_adsr(RampFn::Random {
+    point_count: 8,
+    amount: 0.2,
+    seed: 4321,
+  });
+  assert_ne!(a.rendered, c.rendered);
+}
+
+#[test]
+fn rendered_buffer_grows_for_long_envelopes() {
+  assert_eq!(rendered_buffer_size_for_len(44_100.), RENDERED_BUFFER_SIZE);
+  // two minutes
+  let long_len = 44_100. * 120.;
+  let size = rendered_buffer_size_for_len(long_len);
+  assert!(size > RENDERED_BUFFER_SIZE);
+  assert!(long_len / size as f32 <= 4.);
+  // small length changes such as from tempo changes usually don't change the size
+  assert_eq!(rendered_buffer_size_for_len(long_len * 1.01), size);
+
+  let mut adsr = mk_single_segment_adsr(RampFn::Linear);
+  let shared = Rc::clone(&adsr.rendered);
+  adsr.set_len(long_len, None);
+  assert_eq!(adsr.rendered.len(), size);
+  // The re-render is visible to other ADSRs sharing the buffer
+  assert_eq!(shared.len(), size);
+  assert!((value_at(&adsr, 0.5) - 0.5).abs() < 1e-3);
+
+  // Going back to a shorter length keeps the larger buffer rather than re-rendering every frame
+  // when lengths jump back and forth across a size boundary
+  let buf_ptr = adsr.rendered.as_ptr();
+  for i in 0..8 {
+    let len = if i % 2 == 0 { 44_100. } else { long_len };
+    adsr.set_len(len, None);
+    assert_eq!(adsr.rendered.len(), size);
+    assert_eq!(adsr.rendered.as_ptr(), buf_ptr);
+  }
+
+  // Re-rendering after the steps change sizes it to fit the current length again
+  adsr.set_len(44_100., None);
+  adsr.render();
+  assert_eq!(adsr.rendered.len(), RENDERED_BUFFER_SIZE);
+}
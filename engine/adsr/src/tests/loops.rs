@@ -0,0 +1,234 @@
+use std::rc::Rc;
+
+use crate::{
+  Adsr, AdsrLoop, AdsrStep, EarlyReleaseConfig, GateStatus, LoopMode, RampFn, StepRetrigger,
+  RENDERED_BUFFER_SIZE,
+};
+
+/// Linear ramp from 0 to 1 over the whole envelope with a step at every 0.1 of phase
+fn mk_ramp_adsr(release_start_phase: f32) -> Adsr {
+  let steps = (0..=10)
+    .map(|i| AdsrStep {
+      x: i as f32 / 10.,
+      y: i as f32 / 10.,
+      ramper: RampFn::Linear,
+    })
+    .collect();
+  let mut adsr = Adsr::new(
+    steps,
+    None,
+    1_000.,
+    None,
+    release_start_phase,
+    Rc::new(vec![0.; RENDERED_BUFFER_SIZE]),
+    EarlyReleaseConfig::default(),
+    false,
+  );
+  adsr.render();
+  adsr
+}
+
+/// Renders frames until `pred` returns true for the current phase, returning the number of frames
+/// rendered.  Panics if it takes too long.
+fn render_until(adsr: &mut Adsr, pred: impl Fn(&Adsr) -> bool) -> usize {
+  for frame_ix in 0..1_000 {
+    if pred(adsr) {
+      return frame_ix;
+    }
+    adsr.render_frame(1., 0., 0.);
+  }
+  panic!("condition never met; phase={}", adsr.phase);
+}
+
+#[test]
+fn forward_loop_repeats_then_continues() {
+  let mut adsr = mk_ramp_adsr(0.9);
+  adsr.set_loops(vec![AdsrLoop {
+    id: 0,
+    start: 0.2,
+    end: 0.4,
+    mode: LoopMode::Forward,
+    repeat_count: Some(2),
+  }]);
+  adsr.gate(0., None, None);
+
+  let mut wraps = 0;
+  let mut last_phase = adsr.phase;
+  while adsr.phase < 0.5 {
+    adsr.render_frame(1., 0., 0.);
+    if adsr.phase < last_phase {
+      wraps += 1;
+      assert!(
+        adsr.phase >= 0.2 && adsr.phase < 0.4,
+        "bad wrap phase {}",
+        adsr.phase
+      );
+    }
+    last_phase = adsr.phase;
+  }
+  assert_eq!(wraps, 2);
+}
+
+#[test]
+fn sustain_loop_runs_until_released() {
+  let mut adsr = mk_ramp_adsr(0.9);
+  adsr.set_loops(vec![AdsrLoop {
+    id: 0,
+    start: 0.5,
+    end: 0.6,
+    mode: LoopMode::Forward,
+    repeat_count: None,
+  }]);
+  adsr.gate(0., None, None);
+
+  for _ in 0..200 {
+    adsr.render_frame(1., 0., 0.);
+    assert!(
+      adsr.phase < 0.6 + 1e-4,
+      "escaped sustain loop; phase={}",
+      adsr.phase
+    );
+  }
+
+  adsr.ungate();
+  render_until(&mut adsr, |adsr| adsr.gate_status == GateStatus::Done);
+}
+
+#[test]
+fn ping_pong_loop_reverses_direction() {
+  let mut adsr = mk_ramp_adsr(0.9);
+  adsr.set_loops(vec![AdsrLoop {
+    id: 0,
+    start: 0.2,
+    end: 0.6,
+    mode: LoopMode::PingPong,
+    repeat_count: Some(1),
+  }]);
+  adsr.gate(0., None, None);
+
+  render_until(&mut adsr, |adsr| adsr.loop_state.reversed);
+  let mut last_phase = adsr.phase;
+  let mut min_phase = adsr.phase;
+  while adsr.loop_state.reversed {
+    adsr.render_frame(1., 0., 0.);
+    assert!(adsr.phase <= last_phase || !adsr.loop_state.reversed);
+    min_phase = min_phase.min(adsr.phase);
+    last_phase = adsr.phase;
+  }
+  assert!(
+    min_phase < 0.25,
+    "should have played back to loop start; min={min_phase}"
+  );
+
+  // After one round trip, the envelope continues through to the release point
+  render_until(&mut adsr, |adsr| adsr.phase >= 0.9);
+}
+
+#[test]
+fn release_loop_keeps_looping_after_release() {
+  let mut adsr = mk_ramp_adsr(0.5);
+  adsr.set_release_loop(Some(AdsrLoop {
+    id: 0,
+    start: 0.7,
+    end: 0.8,
+    mode: LoopMode::Forward,
+    repeat_count: None,
+  }));
+  adsr.gate(0., None, None);
+  render_until(&mut adsr, |adsr| adsr.phase >= 0.5);
+  adsr.ungate();
+
+  for _ in 0..200 {
+    adsr.render_frame(1., 0., 0.);
+    assert!(adsr.phase <= 0.8 + 1e-4);
+    assert_ne!(adsr.gate_status, GateStatus::Done);
+  }
+}
+
+#[test]
+fn continue_retrigger_avoids_discontinuity() {
+  let max_delta = |retrigger: StepRetrigger| {
+    let mut adsr = mk_ramp_adsr(0.9);
+    adsr.set_loops(vec![AdsrLoop {
+      id: 0,
+      start: 0.2,
+      end: 0.6,
+      mode: LoopMode::Forward,
+      repeat_count: None,
+    }]);
+    adsr.set_step_retrigger_modes(vec![retrigger; 11]);
+    adsr.gate(0., None, None);
+
+    let mut last = adsr.get_cur_frame_output()[0];
+    let mut max_delta = 0f32;
+    for _ in 0..50 {
+      adsr.render_frame(1., 0., 0.);
+      for &sample in adsr.get_cur_frame_output() {
+        max_delta = max_delta.max((sample - last).abs());
+        last = sample;
+      }
+    }
+    max_delta
+  };
+
+  assert!(max_delta(StepRetrigger::Reset) > 0.3);
+  assert!(max_delta(StepRetrigger::Continue) < 0.05);
+}
+
+#[test]
+fn active_loop_id_follows_loops() {
+  let mut adsr = mk_ramp_adsr(0.9);
+  adsr.set_loops(vec![
+    AdsrLoop {
+      id: 3,
+      start: 0.1,
+      end: 0.2,
+      mode: LoopMode::Forward,
+      repeat_count: Some(1),
+    },
+    AdsrLoop {
+      id: 7,
+      start: 0.4,
+      end: 0.5,
+      mode: LoopMode::Forward,
+      repeat_count: None,
+    },
+  ]);
+  assert_eq!(adsr.get_active_loop_id(), None);
+  adsr.gate(0., None, None);
+
+  render_until(&mut adsr, |adsr| adsr.phase >= 0.15);
+  assert_eq!(adsr.get_active_loop_id(), Some(3));
+  render_until(&mut adsr, |adsr| adsr.phase >= 0.45);
+  assert_eq!(adsr.get_active_loop_id(), Some(7));
+  for _ in 0..200 {
+    adsr.render_frame(1., 0., 0.);
+    assert!(adsr.phase <= 0.5 + 1e-4);
+  }
+}
+
+#[test]
+fn loops_behind_an_external_phase_jump_are_skipped() {
+  let mut adsr = mk_ramp_adsr(0.9);
+  adsr.set_loops(vec![
+    AdsrLoop {
+      id: 0,
+      start: 0.1,
+      end: 0.2,
+      mode: LoopMode::Forward,
+      repeat_count: None,
+    },
+    AdsrLoop {
+      id: 1,
+      start: 0.6,
+      end: 0.7,
+      mode: LoopMode::Forward,
+      repeat_count: None,
+    },
+  ]);
+  adsr.gate(0., None, None);
+  adsr.phase = 0.5;
+
+  render_until(&mut adsr, |adsr| adsr.phase >= 0.65);
+  assert_eq!(adsr.get_active_loop_id(), Some(1));
+}
@@ -1 +1,4 @@
+mod curves;
+mod loops;
+mod note_scaling;
 mod render;
@@ -0,0 +1,128 @@
+use std::rc::Rc;
+
+use crate::{Adsr, AdsrStep, EarlyReleaseConfig, NoteScalingConfig, RampFn, RENDERED_BUFFER_SIZE};
+
+const FRAME_SIZE: usize = 128;
+
+/// Attack to 1 at 0.2, decay to 0.5 at 0.6, and hold there until release
+fn mk_adsr(note_scaling: NoteScalingConfig) -> Adsr {
+  let steps = vec![
+    AdsrStep {
+      x: 0.,
+      y: 0.,
+      ramper: RampFn::Instant,
+    },
+    AdsrStep {
+      x: 0.2,
+      y: 1.,
+      ramper: RampFn::Linear,
+    },
+    AdsrStep {
+      x: 0.6,
+      y: 0.5,
+      ramper: RampFn::Linear,
+    },
+    AdsrStep {
+      x: 1.,
+      y: 0.,
+      ramper: RampFn::Linear,
+    },
+  ];
+  let mut adsr = Adsr::new(
+    steps,
+    None,
+    10_000.,
+    None,
+    0.6,
+    Rc::new(vec![0.; RENDERED_BUFFER_SIZE]),
+    EarlyReleaseConfig::default(),
+    false,
+  );
+  adsr.note_scaling = note_scaling;
+  adsr.render();
+  adsr
+}
+
+fn frames_until_phase(adsr: &mut Adsr, phase: f32) -> usize {
+  let mut frame_count = 0;
+  while adsr.phase < phase {
+    adsr.render_frame(1., 0., 0.);
+    frame_count += 1;
+    assert!(frame_count < 10_000);
+  }
+  frame_count
+}
+
+#[test]
+fn no_scaling_by_default() {
+  let mut plain = mk_adsr(NoteScalingConfig::default());
+  let mut with_note = mk_adsr(NoteScalingConfig::default());
+  plain.gate(0., None, None);
+  with_note.gate(0., Some(20), Some(100));
+  for _ in 0..30 {
+    plain.render_frame(1., 0., 0.);
+    with_note.render_frame(1., 0., 0.);
+    assert_eq!(
+      plain.get_cur_frame_output(),
+      with_note.get_cur_frame_output()
+    );
+  }
+}
+
+#[test]
+fn velocity_scales_level() {
+  let mut adsr = mk_adsr(NoteScalingConfig {
+    velocity_to_level: 1.,
+    ..Default::default()
+  });
+  adsr.gate(0., Some(64), None);
+  frames_until_phase(&mut adsr, 0.6);
+  adsr.render_frame(1., 0., 0.);
+  let expected = 0.5 * 64. / 127.;
+  let actual = adsr.get_cur_frame_output()[FRAME_SIZE - 1];
+  assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+}
+
+#[test]
+fn velocity_shortens_attack_only() {
+  let config = NoteScalingConfig {
+    velocity_to_attack_time: 0.5,
+    ..Default::default()
+  };
+  let mut soft = mk_adsr(config);
+  let mut hard = mk_adsr(config);
+  soft.gate(0., Some(0), None);
+  hard.gate(0., Some(127), None);
+
+  let soft_attack = frames_until_phase(&mut soft, 0.2);
+  let hard_attack = frames_until_phase(&mut hard, 0.2);
+  assert!(
+    (hard_attack as f32 / soft_attack as f32 - 0.5).abs() < 0.05,
+    "soft={soft_attack}, hard={hard_attack}"
+  );
+
+  // decay runs at the normal speed for both
+  let soft_decay = frames_until_phase(&mut soft, 0.5);
+  let hard_decay = frames_until_phase(&mut hard, 0.5);
+  assert!((soft_decay as isize - hard_decay as isize).abs() <= 1);
+}
+
+#[test]
+fn key_tracking_scales_length() {
+  let config = NoteScalingConfig {
+    key_tracking: 1.,
+    key_tracking_center: 60,
+    ..Default::default()
+  };
+  let mut center = mk_adsr(config);
+  let mut octave_up = mk_adsr(config);
+  center.gate(0., None, Some(60));
+  octave_up.gate(0., None, Some(72));
+
+  let center_frames = frames_until_phase(&mut center, 0.6);
+  let octave_up_frames = frames_until_phase(&mut octave_up, 0.6);
+  assert!(
+    (octave_up_frames as f32 / center_frames as f32 - 0.5).abs() < 0.05,
+    "center={center_frames}, octave_up={octave_up_frames}"
+  );
+}
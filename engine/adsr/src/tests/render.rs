@@ -60,7 +60,7 @@ fn gain_envelope_click_debug() {
     },
   ];
 
-  let rendered = Rc::new([0.; RENDERED_BUFFER_SIZE]);
+  let rendered = Rc::new(vec![0.; RENDERED_BUFFER_SIZE]);
 
   let release_start_phase = 0.631;
   let mut adsr = Adsr::new(
@@ -122,7 +122,7 @@ fn mk_flat_adsr(log_scale: bool) -> Adsr {
     1000.,
     None,
     1.,
-    Rc::new([0.; RENDERED_BUFFER_SIZE]),
+    Rc::new(vec![0.; RENDERED_BUFFER_SIZE]),
     EarlyReleaseConfig::default(),
     log_scale,
   )
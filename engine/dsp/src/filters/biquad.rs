@@ -337,6 +337,35 @@ impl<T: Float + FloatConst + Default + MulAssign + AddAssign> BiquadFilter<T> {
     (freqs, mags, phases)
   }
 
+  /// Multiplies the magnitude response of a chain of biquad filters over the same grid as
+  /// `compute_response_grid` into `mags`, which holds one linear gain per grid point.  Nothing is
+  /// allocated, so this can be used to redesign filters on the audio thread.
+  pub fn apply_chain_magnitude_grid<O: Float + MulAssign, const LEN: usize>(
+    mode: FilterMode,
+    params: [ComputeGridFilterParams<T>; LEN],
+    start_freq: T,
+    sample_rate: T,
+    mags: &mut [O],
+  ) {
+    assert!(!mags.is_empty(), "need at least one grid point to sample");
+
+    let coefficients = params
+      .map(|params| Self::compute_coefficients(mode, params.q, params.cutoff_freq, params.gain));
+    let multiplier = if mags.len() == 1 {
+      T::one()
+    } else {
+      (T::from(NYQUIST).unwrap() / start_freq).powf(T::one() / (T::from(mags.len() - 1).unwrap()))
+    };
+    for (i, mag) in mags.iter_mut().enumerate() {
+      let freq = start_freq * multiplier.powi(i as i32);
+      let omega = T::from(2.).unwrap() * T::PI() * freq / sample_rate;
+      for &(b0, b1, b2, a1, a2) in &coefficients {
+        let (mag_db, _phase) = Self::compute_response_from_coefficients(b0, b1, b2, a1, a2, omega);
+        *mag *= db_to_gain_generic(O::from(mag_db).unwrap());
+      }
+    }
+  }
+
   /// Helper function that computes the magnitude and phase response given precomputed coefficients.
   ///
   /// `ω` is the angular frequency (in rads/sample) at which to sample the response.
@@ -540,93 +569,89 @@ impl<T: Float + FloatConst + Default + MulAssign + AddAssign> BiquadFilter<T> {
 
 /// Coefficients and state are stored as SoA.  Since applying biquad filter chains has a serial
 /// dependency on the previous output, we apply banks in parallel and store coefficients and state
-/// as bank[0][0], bank[1][0], ... bank[1][0], bank[1][1], ...
-pub struct BiquadFilterBank2D<const BANK_COUNT: usize, const BANK_LENGTH: usize> {
-  pub b0_over_a0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub b1_over_a0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub b2_over_a0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub a1_over_a0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub a2_over_a0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub x0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub x1: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub y0: [[f32; BANK_COUNT]; BANK_LENGTH],
-  pub y1: [[f32; BANK_COUNT]; BANK_LENGTH],
+/// as bank[0][0], bank[1][0], ... bank[0][1], bank[1][1], ...
+///
+/// The number of banks and the number of filters in each bank are chosen at runtime.  Each array
+/// holds `bank_length` rows of `bank_count` values.
+pub struct BiquadFilterBank2D {
+  bank_count: usize,
+  bank_length: usize,
+  pub b0_over_a0: Vec<f32>,
+  pub b1_over_a0: Vec<f32>,
+  pub b2_over_a0: Vec<f32>,
+  pub a1_over_a0: Vec<f32>,
+  pub a2_over_a0: Vec<f32>,
+  pub x0: Vec<f32>,
+  pub x1: Vec<f32>,
+  pub y0: Vec<f32>,
+  pub y1: Vec<f32>,
 }
 
-impl<const BANK_COUNT: usize, const BANK_LENGTH: usize>
-  BiquadFilterBank2D<BANK_COUNT, BANK_LENGTH>
-{
+impl BiquadFilterBank2D {
+  /// `filters[filter_ix][bank_ix]` is the `filter_ix`th filter in the chain of bank `bank_ix`.
+  /// All rows must have the same length.
   #[cold]
-  pub fn new(
-    filters: &[[BiquadFilter; BANK_COUNT]; BANK_LENGTH],
-  ) -> BiquadFilterBank2D<BANK_COUNT, BANK_LENGTH> {
-    let mut b0_over_a0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut b1_over_a0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut b2_over_a0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut a1_over_a0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut a2_over_a0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut x0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut x1 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut y0 = [[0.; BANK_COUNT]; BANK_LENGTH];
-    let mut y1 = [[0.; BANK_COUNT]; BANK_LENGTH];
-
-    for bank_ix in 0..BANK_COUNT {
-      for filter_ix in 0..BANK_LENGTH {
-        b0_over_a0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].b0_over_a0;
-        b1_over_a0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].b1_over_a0;
-        b2_over_a0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].b2_over_a0;
-        a1_over_a0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].a1_over_a0;
-        a2_over_a0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].a2_over_a0;
-        x0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].x[0];
-        x1[filter_ix][bank_ix] = filters[filter_ix][bank_ix].x[1];
-        y0[filter_ix][bank_ix] = filters[filter_ix][bank_ix].y[0];
-        y1[filter_ix][bank_ix] = filters[filter_ix][bank_ix].y[1];
-      }
-    }
+  pub fn new(filters: &[Vec<BiquadFilter>]) -> BiquadFilterBank2D {
+    let bank_length = filters.len();
+    let bank_count = filters.first().map(|row| row.len()).unwrap_or(0);
+    assert!(
+      filters.iter().all(|row| row.len() == bank_count),
+      "all banks must have the same number of filters"
+    );
+
+    let collect = |f: &dyn Fn(&BiquadFilter) -> f32| -> Vec<f32> {
+      filters.iter().flat_map(|row| row.iter().map(f)).collect()
+    };
 
     BiquadFilterBank2D {
-      b0_over_a0,
-      b1_over_a0,
-      b2_over_a0,
-      a1_over_a0,
-      a2_over_a0,
-      x0,
-      x1,
-      y0,
-      y1,
+      bank_count,
+      bank_length,
+      b0_over_a0: collect(&|filter| filter.b0_over_a0),
+      b1_over_a0: collect(&|filter| filter.b1_over_a0),
+      b2_over_a0: collect(&|filter| filter.b2_over_a0),
+      a1_over_a0: collect(&|filter| filter.a1_over_a0),
+      a2_over_a0: collect(&|filter| filter.a2_over_a0),
+      x0: collect(&|filter| filter.x[0]),
+      x1: collect(&|filter| filter.x[1]),
+      y0: collect(&|filter| filter.y[0]),
+      y1: collect(&|filter| filter.y[1]),
     }
   }
 
+  #[inline]
+  pub fn bank_count(&self) -> usize { self.bank_count }
+
+  #[inline]
+  pub fn bank_length(&self) -> usize { self.bank_length }
+
+  /// Applies the `depth`th filter of every bank to the corresponding value in `output` in place.
+  /// `output` must hold at least `bank_count` values.
   #[cfg(target_arch = "wasm32")]
   #[inline]
-  pub fn apply_simd(&mut self, output: &mut [f32; BANK_COUNT], depth: usize) {
+  pub fn apply_simd(&mut self, output: &mut [f32], depth: usize) {
     use std::arch::wasm32::*;
+    assert!(output.len() >= self.bank_count && depth < self.bank_length);
     let output_ptr = output.as_mut_ptr();
 
     const CHUNK_SIZE: usize = 4;
-    let chunk_count: usize = BANK_COUNT / CHUNK_SIZE;
-    let remainder: usize = BANK_COUNT % CHUNK_SIZE;
+    let chunk_count: usize = self.bank_count / CHUNK_SIZE;
+    let row_offset = depth * self.bank_count;
 
-    // for depth in 0..BANK_COUNT {
     for chunk_ix in 0..chunk_count {
-      let base_band_ix = chunk_ix * CHUNK_SIZE;
-
-      let b0_over_a0 =
-        unsafe { v128_load(&self.b0_over_a0[depth][base_band_ix] as *const _ as *const v128) };
-      let b1_over_a0 =
-        unsafe { v128_load(&self.b1_over_a0[depth][base_band_ix] as *const _ as *const v128) };
-      let b2_over_a0 =
-        unsafe { v128_load(&self.b2_over_a0[depth][base_band_ix] as *const _ as *const v128) };
-      let a1_over_a0 =
-        unsafe { v128_load(&self.a1_over_a0[depth][base_band_ix] as *const _ as *const v128) };
-      let a2_over_a0 =
-        unsafe { v128_load(&self.a2_over_a0[depth][base_band_ix] as *const _ as *const v128) };
-      let x0 = unsafe { v128_load(&self.x0[depth][base_band_ix] as *const _ as *const v128) };
-      let x1 = unsafe { v128_load(&self.x1[depth][base_band_ix] as *const _ as *const v128) };
-      let y0 = unsafe { v128_load(&self.y0[depth][base_band_ix] as *const _ as *const v128) };
-      let y1 = unsafe { v128_load(&self.y1[depth][base_band_ix] as *const _ as *const v128) };
-
-      let ins = unsafe { v128_load(output_ptr.add(base_band_ix) as *const v128) };
+      let base_ix = row_offset + chunk_ix * CHUNK_SIZE;
+      let load = |buf: &[f32]| unsafe { v128_load(buf.as_ptr().add(base_ix) as *const v128) };
+
+      let b0_over_a0 = load(&self.b0_over_a0);
+      let b1_over_a0 = load(&self.b1_over_a0);
+      let b2_over_a0 = load(&self.b2_over_a0);
+      let a1_over_a0 = load(&self.a1_over_a0);
+      let a2_over_a0 = load(&self.a2_over_a0);
+      let x0 = load(&self.x0);
+      let x1 = load(&self.x1);
+      let y0 = load(&self.y0);
+      let y1 = load(&self.y1);
+
+      let ins = unsafe { v128_load(output_ptr.add(chunk_ix * CHUNK_SIZE) as *const v128) };
 
       // let output =
       //   self.b0_over_a0 * input + self.b1_over_a0 * self.x[0] + self.b2_over_a0 * self.x[1]
@@ -639,38 +664,50 @@ impl<const BANK_COUNT: usize, const BANK_LENGTH: usize>
       let outs = f32x4_sub(outs, f32x4_mul(a2_over_a0, y1));
 
       unsafe {
-        v128_store(&self.x0[depth][base_band_ix] as *const _ as *mut v128, ins);
-        v128_store(&self.x1[depth][base_band_ix] as *const _ as *mut v128, x0);
-        v128_store(&self.y0[depth][base_band_ix] as *const _ as *mut v128, outs);
-        v128_store(&self.y1[depth][base_band_ix] as *const _ as *mut v128, y0);
+        v128_store(self.x0.as_mut_ptr().add(base_ix) as *mut v128, ins);
+        v128_store(self.x1.as_mut_ptr().add(base_ix) as *mut v128, x0);
+        v128_store(self.y0.as_mut_ptr().add(base_ix) as *mut v128, outs);
+        v128_store(self.y1.as_mut_ptr().add(base_ix) as *mut v128, y0);
 
-        v128_store(output_ptr.add(base_band_ix) as *mut v128, outs);
+        v128_store(output_ptr.add(chunk_ix * CHUNK_SIZE) as *mut v128, outs);
       }
     }
 
-    for band_ix in chunk_count * CHUNK_SIZE..chunk_count * CHUNK_SIZE + remainder {
-      let b0_over_a0 = self.b0_over_a0[depth][band_ix];
-      let b1_over_a0 = self.b1_over_a0[depth][band_ix];
-      let b2_over_a0 = self.b2_over_a0[depth][band_ix];
-      let a1_over_a0 = self.a1_over_a0[depth][band_ix];
-      let a2_over_a0 = self.a2_over_a0[depth][band_ix];
-      let x0 = self.x0[depth][band_ix];
-      let x1 = self.x1[depth][band_ix];
-      let y0 = self.y0[depth][band_ix];
-      let y1 = self.y1[depth][band_ix];
+    let remainder_start = chunk_count * CHUNK_SIZE;
+    for (band_ix, out) in output[remainder_start..self.bank_count]
+      .iter_mut()
+      .enumerate()
+    {
+      *out = self.apply_one(row_offset + remainder_start + band_ix, *out);
+    }
+  }
 
-      let ins = output[band_ix];
+  /// Applies the `depth`th filter of every bank to the corresponding value in `output` in place.
+  /// `output` must hold at least `bank_count` values.
+  #[cfg(not(target_arch = "wasm32"))]
+  #[inline]
+  pub fn apply_simd(&mut self, output: &mut [f32], depth: usize) {
+    assert!(output.len() >= self.bank_count && depth < self.bank_length);
+    let row_offset = depth * self.bank_count;
+    for (band_ix, out) in output.iter_mut().take(self.bank_count).enumerate() {
+      *out = self.apply_one(row_offset + band_ix, *out);
+    }
+  }
 
-      let outs =
-        b0_over_a0 * ins + b1_over_a0 * x0 + b2_over_a0 * x1 - a1_over_a0 * y0 - a2_over_a0 * y1;
+  #[inline(always)]
+  fn apply_one(&mut self, ix: usize, input: f32) -> f32 {
+    let output = self.b0_over_a0[ix] * input
+      + self.b1_over_a0[ix] * self.x0[ix]
+      + self.b2_over_a0[ix] * self.x1[ix]
+      - self.a1_over_a0[ix] * self.y0[ix]
+      - self.a2_over_a0[ix] * self.y1[ix];
 
-      self.x0[depth][band_ix] = ins;
-      self.x1[depth][band_ix] = x0;
-      self.y0[depth][band_ix] = outs;
-      self.y1[depth][band_ix] = y0;
+    self.x1[ix] = self.x0[ix];
+    self.x0[ix] = input;
+    self.y1[ix] = self.y0[ix];
+    self.y0[ix] = output;
 
-      output[band_ix] = outs;
-    }
+    output
   }
 }
 
@@ -694,15 +731,3 @@ pub fn compute_higher_order_biquad_q_factors(order: usize) -> Vec<f32> {
     })
     .collect()
 }
-
-#[cfg(target_arch = "wasm32")]
-pub extern "C" fn apply(
-  banks: &mut BiquadFilterBank2D<22, 16>,
-  outputs: &mut [f32; 22],
-  inputs: &[f32; 128],
-) {
-  outputs.fill(inputs[0]);
-  for depth in 0..22 {
-    banks.apply_simd(outputs, depth);
-  }
-}
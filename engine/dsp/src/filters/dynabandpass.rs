@@ -181,4 +181,28 @@ impl DynabandpassFilter {
 
     (freqs, mags, phases)
   }
+
+  /// Multiplies the magnitude response of the dynabandpass filter over the same grid as
+  /// `compute_response_grid` into `mags` without allocating.
+  pub fn apply_magnitude_grid<T: Float + FloatConst + Default + MulAssign + AddAssign>(
+    center_freq: f32,
+    bandwidth: f32,
+    start_freq: T,
+    sample_rate: T,
+    mags: &mut [T],
+  ) {
+    let (lowpass_freq, highpass_freq) = compute_filter_cutoff_frequencies(center_freq, bandwidth);
+    for (mode, cutoff_freq) in [
+      (FilterMode::Lowpass, lowpass_freq),
+      (FilterMode::Highpass, highpass_freq),
+    ] {
+      let params: [crate::filters::biquad::ComputeGridFilterParams<T>; 4] =
+        std::array::from_fn(|i| crate::filters::biquad::ComputeGridFilterParams {
+          q: T::from(PRECOMPUTED_BASE_Q_FACTORS[i]).unwrap(),
+          cutoff_freq: T::from(cutoff_freq).unwrap(),
+          gain: T::zero(),
+        });
+      BiquadFilter::apply_chain_magnitude_grid(mode, params, start_freq, sample_rate, mags);
+    }
+  }
 }
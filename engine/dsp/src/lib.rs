@@ -5,8 +5,12 @@ pub mod band_splitter;
 pub mod circular_buffer;
 pub mod filters;
 pub mod lookup_tables;
+pub mod loop_detection;
+pub mod onset_detection;
 pub mod oscillator;
+pub mod pitch;
 pub mod rms_level_detector;
+pub mod time_stretch;
 
 pub const SAMPLE_RATE: f32 = 44_100.;
 pub const NYQUIST: f32 = SAMPLE_RATE / 2.;
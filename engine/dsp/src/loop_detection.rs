@@ -0,0 +1,282 @@
+//! Suggests loop points for samples.
+//!
+//! Candidate loop boundaries are placed on rising zero crossings so that the jump from the loop end
+//! back to the loop start doesn't create a discontinuity.  Each start/end pair is scored by how
+//! similar the audio surrounding the two points is, using normalized cross-correlation along with
+//! how closely the levels match.  If the sample is pitched, candidates can optionally be snapped to
+//! whole numbers of periods of its fundamental as estimated by YIN.
+
+use crate::pitch::yin;
+
+/// Longest period considered when estimating the fundamental for period snapping: 20Hz at 44.1kHz
+const YIN_MAX_PERIOD: usize = 2205;
+const YIN_THRESHOLD: f32 = 0.1;
+/// Maximum number of loop starts and ends considered.  If there are more zero crossings than this,
+/// an evenly spaced subset of them is used.
+const MAX_CANDIDATES: usize = 96;
+/// Maximum number of loop lengths tried per loop start when snapping to the fundamental's period
+const MAX_PERIOD_MULTIPLES: usize = 48;
+
+#[derive(Clone, Debug)]
+pub struct LoopDetectionParams {
+  pub sample_rate: f32,
+  pub min_loop_len_samples: usize,
+  /// Region of the sample to search for loop points in
+  pub search_start_sample_ix: usize,
+  /// If `None`, the search extends to the end of the sample
+  pub search_end_sample_ix: Option<usize>,
+  /// Number of samples on either side of each candidate point compared when scoring
+  pub comparison_window_len: usize,
+  pub max_suggestions: usize,
+  /// If set, loop lengths are snapped to whole numbers of periods of the sample's estimated
+  /// fundamental
+  pub snap_to_pitch: bool,
+}
+
+impl Default for LoopDetectionParams {
+  fn default() -> Self {
+    LoopDetectionParams {
+      sample_rate: crate::SAMPLE_RATE,
+      min_loop_len_samples: 4410,
+      search_start_sample_ix: 0,
+      search_end_sample_ix: None,
+      comparison_window_len: 512,
+      max_suggestions: 8,
+      snap_to_pitch: false,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoopSuggestion {
+  pub start_sample_ix: usize,
+  pub end_sample_ix: usize,
+  /// From 0 to 1, higher is better
+  pub score: f32,
+}
+
+fn rising_zero_crossings(samples: &[f32], start: usize, end: usize) -> Vec<usize> {
+  (start.max(1)..end)
+    .filter(|&i| samples[i - 1] < 0. && samples[i] >= 0.)
+    .collect()
+}
+
+/// Evenly spaced subset of at most `max_len` elements of `vals`
+fn thin_out(vals: Vec<usize>, max_len: usize) -> Vec<usize> {
+  if vals.len() <= max_len {
+    return vals;
+  }
+
+  let step = vals.len() as f32 / max_len as f32;
+  (0..max_len)
+    .map(|i| vals[(i as f32 * step) as usize])
+    .collect()
+}
+
+/// Similarity of the audio around `a` and `b` from 0 to 1.  The windows on either side of each
+/// point are compared since that is the audio played before and after the jump.
+fn score_pair(samples: &[f32], a: usize, b: usize, window_len: usize) -> f32 {
+  let window_len = window_len.min(a).min(samples.len() - b);
+  if window_len == 0 {
+    return 0.;
+  }
+
+  let (mut dot, mut energy_a, mut energy_b) = (0., 0., 0.);
+  for offset in 0..window_len * 2 {
+    let sample_a = samples[a + offset - window_len];
+    let sample_b = samples[b + offset - window_len];
+    dot += sample_a * sample_b;
+    energy_a += sample_a * sample_a;
+    energy_b += sample_b * sample_b;
+  }
+  if energy_a == 0. || energy_b == 0. {
+    return 0.;
+  }
+
+  let correlation = dot / (energy_a * energy_b).sqrt();
+  let level_match = energy_a.min(energy_b) / energy_a.max(energy_b);
+  (correlation.max(0.) * level_match.sqrt()).min(1.)
+}
+
+/// Estimates the period of the sample's fundamental in samples using the middle of the search
+/// region, or `None` if the region is too short.
+fn estimate_period(samples: &[f32], start: usize, end: usize, sample_rate: f32) -> Option<f32> {
+  let yin_window_len = YIN_MAX_PERIOD * 2;
+  if end - start < yin_window_len {
+    return None;
+  }
+
+  let window_start = start + (end - start - yin_window_len) / 2;
+  let window = &samples[window_start..window_start + yin_window_len];
+  let f0 = yin::<YIN_MAX_PERIOD>(window, YIN_THRESHOLD, sample_rate);
+  if !f0.is_finite() || f0 <= 0. {
+    return None;
+  }
+  Some(sample_rate / f0)
+}
+
+/// Returns the zero crossing in `crossings` closest to `target` if it is within `tolerance`
+fn nearest_crossing(crossings: &[usize], target: f32, tolerance: f32) -> Option<usize> {
+  let ix = crossings.partition_point(|&c| (c as f32) < target);
+  [ix.checked_sub(1), Some(ix)]
+    .into_iter()
+    .flatten()
+    .filter_map(|ix| crossings.get(ix).copied())
+    .min_by(|&a, &b| {
+      let dist_a = (a as f32 - target).abs();
+      let dist_b = (b as f32 - target).abs();
+      dist_a.total_cmp(&dist_b)
+    })
+    .filter(|&c| (c as f32 - target).abs() <= tolerance)
+}
+
+/// Returns up to `params.max_suggestions` suggested loops sorted from best to worst.
+/// Suggestions whose start and end are both within half a comparison window of a better
+/// suggestion are dropped so that the results aren't all slight variations of the same loop.
+pub fn suggest_loop_points(samples: &[f32], params: &LoopDetectionParams) -> Vec<LoopSuggestion> {
+  let search_end = params
+    .search_end_sample_ix
+    .unwrap_or(samples.len())
+    .min(samples.len());
+  let search_start = params.search_start_sample_ix.min(search_end);
+  let window_len = params.comparison_window_len.max(1);
+
+  let crossings = rising_zero_crossings(samples, search_start, search_end);
+  let period = if params.snap_to_pitch {
+    estimate_period(samples, search_start, search_end, params.sample_rate)
+  } else {
+    None
+  };
+
+  let mut pairs: Vec<(usize, usize)> = Vec::new();
+  let starts = thin_out(crossings.clone(), MAX_CANDIDATES);
+  match period {
+    Some(period) =>
+      for &start in &starts {
+        let min_multiple = (params.min_loop_len_samples as f32 / period).ceil().max(1.) as usize;
+        let max_multiple = ((search_end - start) as f32 / period) as usize;
+        if max_multiple < min_multiple {
+          continue;
+        }
+        let multiples = thin_out(
+          (min_multiple..=max_multiple).collect(),
+          MAX_PERIOD_MULTIPLES,
+        );
+        for multiple in multiples {
+          let target = start as f32 + multiple as f32 * period;
+          if let Some(end) = nearest_crossing(&crossings, target, period / 4.) {
+            pairs.push((start, end));
+          }
+        }
+      },
+    None => {
+      let ends = thin_out(crossings.clone(), MAX_CANDIDATES);
+      for &start in &starts {
+        for &end in &ends {
+          if end >= start + params.min_loop_len_samples {
+            pairs.push((start, end));
+          }
+        }
+      }
+    },
+  }
+
+  let mut suggestions = pairs
+    .into_iter()
+    .map(|(start, end)| LoopSuggestion {
+      start_sample_ix: start,
+      end_sample_ix: end,
+      score: score_pair(samples, start, end, window_len),
+    })
+    .filter(|suggestion| suggestion.score > 0.)
+    .collect::<Vec<_>>();
+  suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+  let min_distance = window_len / 2;
+  let mut deduped: Vec<LoopSuggestion> = Vec::with_capacity(params.max_suggestions);
+  for suggestion in suggestions {
+    if deduped.len() >= params.max_suggestions {
+      break;
+    }
+    let is_duplicate = deduped.iter().any(|kept| {
+      kept.start_sample_ix.abs_diff(suggestion.start_sample_ix) < min_distance
+        && kept.end_sample_ix.abs_diff(suggestion.end_sample_ix) < min_distance
+    });
+    if !is_duplicate {
+      deduped.push(suggestion);
+    }
+  }
+  deduped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn harmonic_tone(len: usize, period: f32) -> Vec<f32> {
+    (0..len)
+      .map(|i| {
+        let phase = i as f32 / period * std::f32::consts::TAU;
+        phase.sin() * 0.6 + (phase * 2.).sin() * 0.3 + (phase * 3.).sin() * 0.1
+      })
+      .collect()
+  }
+
+  fn periods_off(suggestion: &LoopSuggestion, period: f32) -> f32 {
+    let periods = (suggestion.end_sample_ix - suggestion.start_sample_ix) as f32 / period;
+    (periods - periods.round()).abs()
+  }
+
+  #[test]
+  fn finds_whole_period_loops() {
+    let period = 123.4;
+    let samples = harmonic_tone(44_100, period);
+    let suggestions = suggest_loop_points(&samples, &LoopDetectionParams::default());
+
+    assert!(!suggestions.is_empty());
+    let best = suggestions[0];
+    assert!(best.score > 0.99);
+    assert!(best.end_sample_ix - best.start_sample_ix >= 4410);
+    assert!(periods_off(&best, period) < 0.02);
+    assert!(suggestions
+      .windows(2)
+      .all(|pair| pair[0].score >= pair[1].score));
+  }
+
+  #[test]
+  fn pitch_snapping_finds_whole_period_loops() {
+    let period = 201.7;
+    let samples = harmonic_tone(44_100, period);
+    let suggestions = suggest_loop_points(&samples, &LoopDetectionParams {
+      snap_to_pitch: true,
+      min_loop_len_samples: 2000,
+      max_suggestions: 4,
+      ..Default::default()
+    });
+
+    assert_eq!(suggestions.len(), 4);
+    for suggestion in &suggestions {
+      assert!(suggestion.score > 0.99);
+      assert!(periods_off(suggestion, period) < 0.02);
+    }
+  }
+
+  #[test]
+  fn prefers_regions_with_matching_levels() {
+    // Tone that fades out over its second half; the loop should be placed in the steady part
+    let period = 100.;
+    let mut samples = harmonic_tone(44_100, period);
+    for (i, sample) in samples.iter_mut().enumerate().skip(22_050) {
+      *sample *= 1. - (i - 22_050) as f32 / 22_050.;
+    }
+
+    let best = suggest_loop_points(&samples, &LoopDetectionParams::default())[0];
+    assert!(best.end_sample_ix < 22_050 + 1000);
+  }
+
+  #[test]
+  fn silence_has_no_suggestions() {
+    let samples = vec![0.; 10_000];
+    assert!(suggest_loop_points(&samples, &LoopDetectionParams::default()).is_empty());
+  }
+}
@@ -0,0 +1,336 @@
+//! Detects note onsets/transients in audio using spectral flux with an adaptive threshold, and
+//! estimates the tempo of loops from the detected onsets.
+//!
+//! Spectral flux measures how much the magnitude spectrum increases from one analysis frame to the
+//! next, only counting bins that get louder.  Peaks in the flux that rise sufficiently above the
+//! local average of the flux are reported as onsets.  Each onset is then refined in the time domain
+//! to the point where the signal's energy rises the most.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Number of flux values on either side of each frame that are averaged to compute its threshold
+const THRESHOLD_WINDOW_FRAMES: usize = 8;
+/// Number of flux values on either side of a peak that it must be greater than or equal to
+const PEAK_WINDOW_FRAMES: usize = 3;
+/// Length of the windows compared when refining onset positions in the time domain
+const REFINE_WINDOW_LEN: usize = 64;
+/// Intervals between onsets longer than this aren't considered when estimating tempo
+const MAX_TEMPO_INTERVAL_SECS: f32 = 4.;
+/// Estimated tempos are folded by factors of 2 into `[MIN_BPM, 2 * MIN_BPM)`
+const MIN_BPM: f32 = 80.;
+
+#[derive(Clone, Debug)]
+pub struct OnsetDetectionParams {
+  pub sample_rate: f32,
+  /// Must be a power of two
+  pub fft_size: usize,
+  pub hop_size: usize,
+  /// From 0 to 1.  Higher values detect quieter and less pronounced onsets.
+  pub sensitivity: f32,
+  /// Onsets closer than this to the previous onset are ignored
+  pub min_onset_interval_samples: usize,
+}
+
+impl Default for OnsetDetectionParams {
+  fn default() -> Self {
+    OnsetDetectionParams {
+      sample_rate: crate::SAMPLE_RATE,
+      fft_size: 1024,
+      hop_size: 256,
+      sensitivity: 0.5,
+      min_onset_interval_samples: (crate::SAMPLE_RATE * 0.05) as usize,
+    }
+  }
+}
+
+fn hann_window(len: usize) -> Vec<f32> {
+  (0..len)
+    .map(|i| 0.5 - 0.5 * (i as f32 / len as f32 * std::f32::consts::TAU).cos())
+    .collect()
+}
+
+/// Computes the spectral flux for frames centered at every multiple of `hop_size`.  Magnitudes are
+/// log-compressed so that quiet onsets following loud sounds are still detected.
+pub fn spectral_flux(samples: &[f32], fft_size: usize, hop_size: usize) -> Vec<f32> {
+  let frame_count = samples.len().div_ceil(hop_size);
+  let fft = FftPlanner::new().plan_fft_forward(fft_size);
+  let window = hann_window(fft_size);
+  let bin_count = fft_size / 2 + 1;
+
+  let mut buf = vec![Complex::new(0., 0.); fft_size];
+  let mut prev_mags = vec![0f32; bin_count];
+  let mut flux = Vec::with_capacity(frame_count);
+  for frame_ix in 0..frame_count {
+    let center = (frame_ix * hop_size) as isize;
+    for (i, (bin, &w)) in buf.iter_mut().zip(&window).enumerate() {
+      let sample_ix = center + i as isize - (fft_size / 2) as isize;
+      let sample = if sample_ix < 0 {
+        0.
+      } else {
+        samples.get(sample_ix as usize).copied().unwrap_or(0.)
+      };
+      *bin = Complex::new(sample * w, 0.);
+    }
+    fft.process(&mut buf);
+
+    let mut frame_flux = 0.;
+    for (bin, prev_mag) in buf[..bin_count].iter().zip(prev_mags.iter_mut()) {
+      let mag = (1. + 100. * bin.norm()).ln();
+      frame_flux += (mag - *prev_mag).max(0.);
+      *prev_mag = mag;
+    }
+    flux.push(frame_flux / bin_count as f32);
+  }
+  flux
+}
+
+fn window_energy(samples: &[f32], start: usize) -> f32 {
+  samples[start..(start + REFINE_WINDOW_LEN).min(samples.len())]
+    .iter()
+    .map(|s| s * s)
+    .sum()
+}
+
+/// Moves an onset detected at frame resolution to the point near it where the energy after it
+/// increases the most relative to the energy before it.  Since frames are centered on the detected
+/// position, the onset can be anywhere up to half a frame after it; the flux often peaks as soon as
+/// a transient enters the edge of the frame.
+fn refine_onset(samples: &[f32], approx_onset: usize, hop_size: usize, fft_size: usize) -> usize {
+  let search_start = approx_onset.saturating_sub(hop_size).max(REFINE_WINDOW_LEN);
+  let search_end =
+    (approx_onset + fft_size / 2).min(samples.len().saturating_sub(REFINE_WINDOW_LEN));
+  if search_start >= search_end {
+    return approx_onset.min(samples.len().saturating_sub(1));
+  }
+
+  let mut best_ix = approx_onset.clamp(search_start, search_end);
+  let mut best_rise = f32::NEG_INFINITY;
+  for ix in (search_start..search_end).step_by(4) {
+    let rise = window_energy(samples, ix) - window_energy(samples, ix - REFINE_WINDOW_LEN);
+    if rise > best_rise {
+      best_rise = rise;
+      best_ix = ix;
+    }
+  }
+  best_ix
+}
+
+/// Returns the sample indices of onsets detected in `samples` in ascending order.
+pub fn detect_onsets(samples: &[f32], params: &OnsetDetectionParams) -> Vec<usize> {
+  let hop_size = params.hop_size.max(1);
+  let flux = spectral_flux(samples, params.fft_size, hop_size);
+  if flux.is_empty() {
+    return Vec::new();
+  }
+
+  let max_flux = flux.iter().copied().fold(0., f32::max);
+  if max_flux <= 0. {
+    return Vec::new();
+  }
+  // Higher sensitivity lowers both the multiple of the local average flux that peaks must exceed
+  // and the minimum flux relative to the loudest onset
+  let sensitivity = params.sensitivity.clamp(0., 1.);
+  let threshold_multiplier = 1. + 2. * (1. - sensitivity);
+  let min_flux = max_flux * 0.2 * (1. - sensitivity).powi(2);
+
+  let mut onsets: Vec<usize> = Vec::new();
+  for (frame_ix, &frame_flux) in flux.iter().enumerate() {
+    let neighborhood = |radius: usize| {
+      &flux[frame_ix.saturating_sub(radius)..(frame_ix + radius + 1).min(flux.len())]
+    };
+
+    let is_peak = neighborhood(PEAK_WINDOW_FRAMES)
+      .iter()
+      .all(|&other| other <= frame_flux);
+    if !is_peak || frame_flux <= min_flux {
+      continue;
+    }
+    let local_flux = neighborhood(THRESHOLD_WINDOW_FRAMES);
+    let local_mean = local_flux.iter().sum::<f32>() / local_flux.len() as f32;
+    if frame_flux < local_mean * threshold_multiplier {
+      continue;
+    }
+
+    let onset = refine_onset(samples, frame_ix * hop_size, hop_size, params.fft_size);
+    match onsets.last() {
+      Some(&last_onset) if onset < last_onset + params.min_onset_interval_samples => (),
+      _ => onsets.push(onset),
+    }
+  }
+  onsets
+}
+
+/// Splits a buffer of `len_samples` samples into slices starting at each onset.  If the first
+/// onset is more than `min_leading_len_samples` from the start of the buffer, an extra slice is
+/// added covering everything before it; otherwise the first slice is extended back to the start.
+/// Returns `(start, end)` pairs of sample indices.
+pub fn onsets_to_slices(
+  onsets: &[usize],
+  len_samples: usize,
+  min_leading_len_samples: usize,
+) -> Vec<(usize, usize)> {
+  let mut starts: Vec<usize> = onsets
+    .iter()
+    .copied()
+    .filter(|&onset| onset < len_samples)
+    .collect();
+  match starts.first_mut() {
+    Some(first) if *first < min_leading_len_samples => *first = 0,
+    Some(_) => starts.insert(0, 0),
+    None if len_samples > 0 => starts.push(0),
+    None => (),
+  }
+
+  starts
+    .iter()
+    .enumerate()
+    .map(|(i, &start)| (start, starts.get(i + 1).copied().unwrap_or(len_samples)))
+    .collect()
+}
+
+/// Estimates the tempo in BPM of a loop from the intervals between its onsets.  Every interval
+/// between pairs of onsets votes for the tempo it implies after being folded into the range
+/// `[MIN_BPM, 2 * MIN_BPM)`, as does the length of the whole loop.  Returns `None` if there
+/// aren't enough onsets to make an estimate.
+pub fn estimate_tempo(onsets: &[usize], len_samples: usize, sample_rate: f32) -> Option<f32> {
+  if onsets.len() < 2 {
+    return None;
+  }
+
+  let fold_bpm = |interval_samples: usize| -> Option<f32> {
+    if interval_samples == 0 {
+      return None;
+    }
+    let mut bpm = 60. * sample_rate / interval_samples as f32;
+    while bpm < MIN_BPM {
+      bpm *= 2.;
+    }
+    while bpm >= MIN_BPM * 2. {
+      bpm /= 2.;
+    }
+    Some(bpm)
+  };
+
+  // (folded bpm, weight) votes
+  let mut votes: Vec<(f32, f32)> = Vec::new();
+  let max_interval = (MAX_TEMPO_INTERVAL_SECS * sample_rate) as usize;
+  for (i, &a) in onsets.iter().enumerate() {
+    for &b in &onsets[i + 1..] {
+      if b - a > max_interval {
+        break;
+      }
+      if let Some(bpm) = fold_bpm(b - a) {
+        votes.push((bpm, 1.));
+      }
+    }
+  }
+  // Loops are usually a whole number of bars long, so the full length is a strong hint
+  if let Some(bpm) = fold_bpm(len_samples) {
+    votes.push((bpm, onsets.len() as f32 / 2.));
+  }
+
+  // Find the tempo with the highest kernel density of votes at 0.1 BPM resolution, then refine it
+  // by taking the weighted average of nearby votes
+  const KERNEL_WIDTH_BPM: f32 = 1.;
+  let density = |bpm: f32| -> f32 {
+    votes
+      .iter()
+      .map(|&(vote, weight)| weight * (-((vote - bpm) / KERNEL_WIDTH_BPM).powi(2)).exp())
+      .sum()
+  };
+  let best_bpm = (0..(MIN_BPM * 10.) as usize)
+    .map(|i| MIN_BPM + i as f32 * 0.1)
+    .max_by(|&a, &b| density(a).total_cmp(&density(b)))?;
+
+  let (weighted_sum, total_weight) = votes
+    .iter()
+    .filter(|&&(vote, _)| (vote - best_bpm).abs() < KERNEL_WIDTH_BPM * 2.)
+    .fold((0., 0.), |(sum, total), &(vote, weight)| {
+      (sum + vote * weight, total + weight)
+    });
+  if total_weight == 0. {
+    return Some(best_bpm);
+  }
+  Some(weighted_sum / total_weight)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: f32 = 44_100.;
+
+  /// Renders decaying noise bursts starting at each of `hit_positions`
+  fn render_hits(hit_positions: &[usize], len: usize) -> Vec<f32> {
+    let mut rng_state = 1u32;
+    let mut noise = || {
+      rng_state ^= rng_state << 13;
+      rng_state ^= rng_state >> 17;
+      rng_state ^= rng_state << 5;
+      rng_state as f32 / u32::MAX as f32 * 2. - 1.
+    };
+
+    let mut samples = vec![0.; len];
+    for (hit_ix, &pos) in hit_positions.iter().enumerate() {
+      let gain = if hit_ix % 2 == 0 { 0.9 } else { 0.4 };
+      for (i, sample) in samples[pos..].iter_mut().take(8000).enumerate() {
+        *sample += noise() * gain * (-(i as f32) / 1500.).exp();
+      }
+    }
+    samples
+  }
+
+  #[test]
+  fn detects_drum_hits() {
+    let hit_positions = [2000, 13_000, 24_000, 30_000, 41_000, 60_000];
+    let samples = render_hits(&hit_positions, 70_000);
+    let onsets = detect_onsets(&samples, &OnsetDetectionParams::default());
+
+    assert_eq!(onsets.len(), hit_positions.len(), "onsets={onsets:?}");
+    for (&onset, &expected) in onsets.iter().zip(&hit_positions) {
+      assert!(
+        onset.abs_diff(expected) < 100,
+        "onset={onset}, expected={expected}"
+      );
+    }
+  }
+
+  #[test]
+  fn silence_has_no_onsets() {
+    let samples = vec![0.; 20_000];
+    assert!(detect_onsets(&samples, &OnsetDetectionParams::default()).is_empty());
+  }
+
+  #[test]
+  fn slices_cover_whole_buffer() {
+    assert_eq!(onsets_to_slices(&[100, 500], 1000, 50), vec![
+      (0, 100),
+      (100, 500),
+      (500, 1000)
+    ]);
+    assert_eq!(onsets_to_slices(&[20, 500], 1000, 50), vec![
+      (0, 500),
+      (500, 1000)
+    ]);
+    assert_eq!(onsets_to_slices(&[], 1000, 50), vec![(0, 1000)]);
+  }
+
+  #[test]
+  fn estimates_breakbeat_tempo() {
+    // Two bars at 132 BPM with hits on a subset of the sixteenth notes
+    let bpm = 132.;
+    let sixteenth_len = 60. / bpm / 4. * SAMPLE_RATE;
+    let pattern = [0, 3, 4, 6, 8, 10, 11, 14, 16, 19, 20, 22, 24, 26, 27, 30];
+    let hit_positions: Vec<usize> = pattern
+      .iter()
+      .map(|&sixteenth| (sixteenth as f32 * sixteenth_len) as usize)
+      .collect();
+    let len = (32. * sixteenth_len) as usize;
+    let samples = render_hits(&hit_positions, len);
+
+    let onsets = detect_onsets(&samples, &OnsetDetectionParams::default());
+    assert_eq!(onsets.len(), hit_positions.len());
+    let estimated = estimate_tempo(&onsets, len, SAMPLE_RATE).unwrap();
+    assert!((estimated - bpm).abs() < 1., "estimated={estimated}");
+  }
+}
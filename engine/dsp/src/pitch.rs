@@ -0,0 +1,323 @@
+//! Fundamental frequency estimation
+
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// YIN pitch detection algorithm.  Estimates fundamental frequency of a signal in Hz, or returns 0
+/// if the signal is silent.
+///
+/// `samples` must hold at least `2 * MAX_PERIOD` samples.  See [`yin_estimate`] for details.
+pub fn yin<const MAX_PERIOD: usize>(samples: &[f32], threshold: f32, sample_rate: f32) -> f32 {
+  yin_estimate::<MAX_PERIOD>(samples, threshold, 2, sample_rate).f0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YinEstimate {
+  /// Estimated fundamental frequency in Hz, or 0 if the signal is silent
+  pub f0: f32,
+  /// One minus the cumulative mean normalized difference at the detected period.  Close to 1 for
+  /// clean periodic signals and close to 0 for noise.
+  pub confidence: f32,
+}
+
+const SILENT_ESTIMATE: YinEstimate = YinEstimate {
+  f0: 0.,
+  confidence: 0.,
+};
+
+/// Turns the difference function into the cumulative mean normalized difference in place.
+/// Returns `false` if the signal is silent.
+fn cumulative_mean_normalize(diff: &mut [f32]) -> bool {
+  diff[0] = 1.;
+  let mut running_sum = 0.0;
+  for (tau, val) in diff.iter_mut().enumerate().skip(1) {
+    running_sum += *val;
+    *val = if running_sum != 0.0 {
+      *val * tau as f32 / running_sum
+    } else {
+      1.
+    };
+  }
+  running_sum != 0.0
+}
+
+/// Picks the period from the cumulative mean normalized difference of lags `0..cmnd.len()`
+fn pick_period(cmnd: &[f32], threshold: f32, min_period: usize, sample_rate: f32) -> YinEstimate {
+  let max_period = cmnd.len();
+  let min_period = min_period.clamp(2, max_period - 2);
+
+  let tau = match (min_period..max_period).find(|&tau| cmnd[tau] < threshold) {
+    Some(mut tau) => {
+      // Follow the dip down to its minimum
+      while tau + 1 < max_period && cmnd[tau + 1] < cmnd[tau] {
+        tau += 1;
+      }
+      tau
+    },
+    None => (min_period..max_period)
+      .min_by(|&a, &b| {
+        cmnd[a]
+          .partial_cmp(&cmnd[b])
+          .unwrap_or(std::cmp::Ordering::Equal)
+      })
+      .unwrap(),
+  };
+
+  let offset = if tau + 1 < max_period {
+    let (prev, cur, next) = (cmnd[tau - 1], cmnd[tau], cmnd[tau + 1]);
+    let curvature = prev - 2. * cur + next;
+    if curvature > 0. {
+      (0.5 * (prev - next) / curvature).clamp(-0.5, 0.5)
+    } else {
+      0.
+    }
+  } else {
+    0.
+  };
+
+  YinEstimate {
+    f0: sample_rate / (tau as f32 + offset),
+    confidence: (1. - cmnd[tau]).clamp(0., 1.),
+  }
+}
+
+/// YIN pitch detection with the local minimum search and parabolic interpolation steps from the
+/// paper, which make the estimate precise enough for tuning.  Also reports how periodic the signal
+/// is.
+///
+/// `samples` must hold at least `2 * MAX_PERIOD` samples.  Periods shorter than `min_period`
+/// samples are not considered.  The first period whose cumulative mean normalized difference
+/// drops below `threshold` is used, falling back to the one with the smallest difference if none
+/// do.
+///
+/// The difference function is computed directly, which takes `O(MAX_PERIOD^2)` time.  Use
+/// [`YinEstimator`] for long periods or frequent estimates.
+pub fn yin_estimate<const MAX_PERIOD: usize>(
+  samples: &[f32],
+  threshold: f32,
+  min_period: usize,
+  sample_rate: f32,
+) -> YinEstimate {
+  assert!(samples.len() >= MAX_PERIOD * 2);
+
+  let mut cmnd = [0.0_f32; MAX_PERIOD];
+  for (tau, delta) in cmnd.iter_mut().enumerate().skip(1) {
+    for i in 0..MAX_PERIOD {
+      let diff = samples[i] - samples[i + tau];
+      *delta += diff * diff;
+    }
+  }
+  if !cumulative_mean_normalize(&mut cmnd) {
+    return SILENT_ESTIMATE;
+  }
+
+  pick_period(&cmnd, threshold, min_period, sample_rate)
+}
+
+/// Same as [`yin_estimate`], but computes the difference function with an FFT cross-correlation
+/// in `O(n log n)` time and takes the maximum period at runtime.  Everything is allocated up front
+/// so that it can be used on the audio thread.
+pub struct YinEstimator {
+  max_period: usize,
+  fft: Arc<dyn Fft<f32>>,
+  ifft: Arc<dyn Fft<f32>>,
+  window_spectrum: Vec<Complex<f32>>,
+  head_spectrum: Vec<Complex<f32>>,
+  scratch: Vec<Complex<f32>>,
+  cmnd: Vec<f32>,
+}
+
+impl YinEstimator {
+  pub fn new(max_period: usize) -> Self {
+    assert!(max_period >= 4, "max period must be at least 4 samples");
+
+    // The window is `2 * max_period` long and lags are shorter than `max_period`, so the
+    // cross-correlation never wraps around at this size
+    let fft_size = (max_period * 2).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(fft_size);
+    let ifft = planner.plan_fft_inverse(fft_size);
+    let scratch_len = fft
+      .get_inplace_scratch_len()
+      .max(ifft.get_inplace_scratch_len());
+    YinEstimator {
+      max_period,
+      fft,
+      ifft,
+      window_spectrum: vec![Complex::new(0., 0.); fft_size],
+      head_spectrum: vec![Complex::new(0., 0.); fft_size],
+      scratch: vec![Complex::new(0., 0.); scratch_len],
+      cmnd: vec![0.; max_period],
+    }
+  }
+
+  pub fn max_period(&self) -> usize { self.max_period }
+
+  /// Estimates the fundamental frequency of the first `2 * max_period` samples of `samples`.  See
+  /// [`yin_estimate`] for details.
+  pub fn estimate(
+    &mut self,
+    samples: &[f32],
+    threshold: f32,
+    min_period: usize,
+    sample_rate: f32,
+  ) -> YinEstimate {
+    let max_period = self.max_period;
+    assert!(samples.len() >= max_period * 2);
+    let samples = &samples[..max_period * 2];
+
+    // The difference at lag `tau` is the energy of the first `max_period` samples plus the energy
+    // of the `max_period` samples starting at `tau`, minus twice their cross-correlation
+    for (i, dst) in self.window_spectrum.iter_mut().enumerate() {
+      *dst = Complex::new(samples.get(i).copied().unwrap_or(0.), 0.);
+    }
+    for (i, dst) in self.head_spectrum.iter_mut().enumerate() {
+      *dst = Complex::new(if i < max_period { samples[i] } else { 0. }, 0.);
+    }
+    self
+      .fft
+      .process_with_scratch(&mut self.window_spectrum, &mut self.scratch);
+    self
+      .fft
+      .process_with_scratch(&mut self.head_spectrum, &mut self.scratch);
+    for (window_bin, head_bin) in self.window_spectrum.iter_mut().zip(&self.head_spectrum) {
+      *window_bin *= head_bin.conj();
+    }
+    self
+      .ifft
+      .process_with_scratch(&mut self.window_spectrum, &mut self.scratch);
+    let norm = 1. / self.window_spectrum.len() as f32;
+
+    let head_energy: f64 = samples[..max_period]
+      .iter()
+      .map(|&s| s as f64 * s as f64)
+      .sum();
+    let mut lagged_energy = head_energy;
+    self.cmnd[0] = 0.;
+    for tau in 1..max_period {
+      let (removed, added) = (
+        samples[tau - 1] as f64,
+        samples[tau + max_period - 1] as f64,
+      );
+      lagged_energy += added * added - removed * removed;
+      let correlation = (self.window_spectrum[tau].re * norm) as f64;
+      self.cmnd[tau] = (head_energy + lagged_energy - 2. * correlation).max(0.) as f32;
+    }
+    if !cumulative_mean_normalize(&mut self.cmnd) {
+      return SILENT_ESTIMATE;
+    }
+
+    pick_period(&self.cmnd, threshold, min_period, sample_rate)
+  }
+}
+
+#[test]
+fn test_yin() {
+  use crate::{FRAME_SIZE, SAMPLE_RATE};
+
+  const YIN_FRAME_SIZE: usize = FRAME_SIZE * 16;
+  const MAX_PERIOD: usize = 44_100 / 80;
+  const THRESHOLD: f32 = 0.05;
+
+  fn snap_to_midi_pitch(f0: f32) -> f32 {
+    let mut midi_pitch = 12. * (f0 / 440.).log2();
+    midi_pitch = midi_pitch.round();
+    440. * 2_f32.powf(midi_pitch / 12.)
+  }
+
+  // 1500 Hz sine wave + 100 Hz sine wave
+  let p1 = snap_to_midi_pitch(1500.);
+  let p2 = snap_to_midi_pitch(100.);
+  dbg!(p1, p2);
+  let mut samples = [0.; YIN_FRAME_SIZE];
+  for (i, sample) in samples.iter_mut().enumerate() {
+    *sample += (i as f32 * 2. * std::f32::consts::PI * p1 / SAMPLE_RATE).sin() * 0.2;
+    *sample += (i as f32 * 2. * std::f32::consts::PI * p2 / SAMPLE_RATE).sin() * 0.9;
+  }
+
+  let estimated_f0 = yin::<MAX_PERIOD>(&samples, THRESHOLD, SAMPLE_RATE);
+  println!("{}", estimated_f0);
+  assert_eq!(snap_to_midi_pitch(estimated_f0), p2);
+
+  let mut samples = [0.; YIN_FRAME_SIZE];
+  for (i, sample) in samples.iter_mut().enumerate() {
+    *sample += (i as f32 * 2. * std::f32::consts::PI * p1 / SAMPLE_RATE).sin() * 0.9;
+    *sample += (i as f32 * 2. * std::f32::consts::PI * p2 / SAMPLE_RATE).sin() * 0.2;
+  }
+
+  let estimated_f0 = yin::<MAX_PERIOD>(&samples, THRESHOLD, SAMPLE_RATE);
+  println!("{}", estimated_f0);
+  assert_eq!(snap_to_midi_pitch(estimated_f0), p1);
+}
+
+#[test]
+fn test_yin_estimate() {
+  use crate::SAMPLE_RATE;
+
+  const MAX_PERIOD: usize = 44_100 / 50;
+
+  for f0 in [55., 196., 440., 987.77] {
+    let samples: Vec<f32> = (0..MAX_PERIOD * 2)
+      .map(|i| {
+        let phase = i as f32 * 2. * std::f32::consts::PI * f0 / SAMPLE_RATE;
+        0.6 * phase.sin() + 0.3 * (2. * phase).sin() + 0.1 * (3. * phase).sin()
+      })
+      .collect();
+    let estimate = yin_estimate::<MAX_PERIOD>(&samples, 0.1, 20, SAMPLE_RATE);
+    let cents = 1200. * (estimate.f0 / f0).log2();
+    assert!(cents.abs() < 3., "f0={f0}, estimate={estimate:?}");
+    assert!(estimate.confidence > 0.9, "f0={f0}, estimate={estimate:?}");
+  }
+
+  // white noise from an LCG isn't periodic
+  let mut state = 12345u32;
+  let noise: Vec<f32> = (0..MAX_PERIOD * 2)
+    .map(|_| {
+      state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+      (state >> 8) as f32 / (1u32 << 24) as f32 * 2. - 1.
+    })
+    .collect();
+  let estimate = yin_estimate::<MAX_PERIOD>(&noise, 0.1, 20, SAMPLE_RATE);
+  assert!(estimate.confidence < 0.6, "{estimate:?}");
+
+  let silence = [0.; MAX_PERIOD * 2];
+  assert_eq!(
+    yin_estimate::<MAX_PERIOD>(&silence, 0.1, 20, SAMPLE_RATE).confidence,
+    0.
+  );
+}
+
+#[test]
+fn test_yin_estimator_matches_direct() {
+  use crate::SAMPLE_RATE;
+
+  const MAX_PERIOD: usize = 44_100 / 50;
+
+  let mut estimator = YinEstimator::new(MAX_PERIOD);
+  let mut state = 12345u32;
+  for f0 in [55., 196., 440., 987.77] {
+    let samples: Vec<f32> = (0..MAX_PERIOD * 2)
+      .map(|i| {
+        let phase = i as f32 * 2. * std::f32::consts::PI * f0 / SAMPLE_RATE;
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let noise = (state >> 8) as f32 / (1u32 << 24) as f32 * 2. - 1.;
+        0.6 * phase.sin() + 0.3 * (2. * phase).sin() + 0.05 * noise
+      })
+      .collect();
+    let expected = yin_estimate::<MAX_PERIOD>(&samples, 0.1, 20, SAMPLE_RATE);
+    let estimate = estimator.estimate(&samples, 0.1, 20, SAMPLE_RATE);
+    let cents = 1200. * (estimate.f0 / expected.f0).log2();
+    assert!(cents.abs() < 0.1, "f0={f0}, {estimate:?} != {expected:?}");
+    assert!(
+      (estimate.confidence - expected.confidence).abs() < 1e-3,
+      "f0={f0}, {estimate:?} != {expected:?}"
+    );
+  }
+
+  let silence = [0.; MAX_PERIOD * 2];
+  assert_eq!(
+    estimator.estimate(&silence, 0.1, 20, SAMPLE_RATE),
+    SILENT_ESTIMATE
+  );
+}
@@ -0,0 +1,207 @@
+//! Offline time-stretching and pitch-shifting for clips.
+//!
+//! Time-stretching uses WSOLA (waveform similarity overlap-add): windowed frames are read from the
+//! input at a rate determined by the stretch factor and overlap-added into the output at a fixed
+//! hop.  Each frame's read position is nudged within a small tolerance to the point that best lines
+//! up with the natural continuation of the previous frame, which avoids the phasing and
+//! discontinuities that plain overlap-add would produce.
+//!
+//! Pitch-shifting is done by time-stretching by the pitch ratio and then resampling back to the
+//! target length.
+
+const FRAME_LEN: usize = 1024;
+const HOP_LEN: usize = FRAME_LEN / 2;
+/// Maximum distance in samples that a frame's read position may be moved to better line up with
+/// the previous frame
+const SEARCH_TOLERANCE: isize = 256;
+/// Only every nth sample of the overlapping region is compared when searching, which is plenty to
+/// find the best alignment and keeps the search cheap enough to re-render clips on tempo changes
+const SEARCH_DECIMATION: usize = 4;
+
+fn hann_window() -> Vec<f32> {
+  (0..FRAME_LEN)
+    .map(|i| {
+      let phase = i as f32 / FRAME_LEN as f32 * std::f32::consts::TAU;
+      0.5 - 0.5 * phase.cos()
+    })
+    .collect()
+}
+
+#[inline]
+fn read(samples: &[f32], ix: isize) -> f32 {
+  if ix < 0 {
+    return 0.;
+  }
+  samples.get(ix as usize).copied().unwrap_or(0.)
+}
+
+/// Finds the read position within `SEARCH_TOLERANCE` of `nominal_pos` whose start best matches the
+/// audio that would have followed the previously copied frame at `natural_pos`.
+fn find_best_pos(samples: &[f32], natural_pos: isize, nominal_pos: isize) -> isize {
+  let mut best_pos = nominal_pos;
+  let mut best_similarity = f32::NEG_INFINITY;
+  for offset in -SEARCH_TOLERANCE..=SEARCH_TOLERANCE {
+    let candidate_pos = nominal_pos + offset;
+    let similarity: f32 = (0..FRAME_LEN - HOP_LEN)
+      .step_by(SEARCH_DECIMATION)
+      .map(|i| read(samples, natural_pos + i as isize) * read(samples, candidate_pos + i as isize))
+      .sum();
+    if similarity > best_similarity {
+      best_similarity = similarity;
+      best_pos = candidate_pos;
+    }
+  }
+  best_pos
+}
+
+/// Changes the length of `samples` by `stretch_factor` without changing its pitch.  A factor of 2
+/// produces output twice as long as the input.
+pub fn time_stretch(samples: &[f32], stretch_factor: f64) -> Vec<f32> {
+  let out_len = (samples.len() as f64 * stretch_factor).round() as usize;
+  if samples.is_empty() || out_len == 0 {
+    return Vec::new();
+  }
+  if (stretch_factor - 1.).abs() < 1e-6 {
+    return samples.to_owned();
+  }
+
+  let window = hann_window();
+  let mut out = vec![0.; out_len + FRAME_LEN];
+  let mut window_sum = vec![0.; out_len + FRAME_LEN];
+  let mut prev_pos: isize = 0;
+  let mut out_pos = 0;
+  while out_pos < out_len {
+    let nominal_pos = (out_pos as f64 / stretch_factor).round() as isize;
+    let pos = if out_pos == 0 {
+      0
+    } else {
+      find_best_pos(samples, prev_pos + HOP_LEN as isize, nominal_pos)
+    };
+
+    for (i, &w) in window.iter().enumerate() {
+      out[out_pos + i] += read(samples, pos + i as isize) * w;
+      window_sum[out_pos + i] += w;
+    }
+    prev_pos = pos;
+    out_pos += HOP_LEN;
+  }
+
+  out.truncate(out_len);
+  for (sample, &w) in out.iter_mut().zip(&window_sum) {
+    // The very start of the output is only covered by the rising half of the first window
+    if w > 1e-3 {
+      *sample /= w;
+    }
+  }
+  out
+}
+
+/// Resamples `samples` to `out_len` samples using cubic Hermite interpolation.
+pub fn resample(samples: &[f32], out_len: usize) -> Vec<f32> {
+  if samples.is_empty() || out_len == 0 {
+    return Vec::new();
+  }
+
+  let step = samples.len() as f64 / out_len as f64;
+  (0..out_len)
+    .map(|i| {
+      let pos = i as f64 * step;
+      let base_ix = pos.trunc() as isize;
+      let t = pos.fract() as f32;
+      let y0 = read(samples, base_ix - 1);
+      let y1 = read(samples, base_ix);
+      let y2 = read(samples, base_ix + 1);
+      let y3 = read(samples, base_ix + 2);
+
+      let c1 = 0.5 * (y2 - y0);
+      let c2 = y0 - 2.5 * y1 + 2. * y2 - 0.5 * y3;
+      let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+      ((c3 * t + c2) * t + c1) * t + y1
+    })
+    .collect()
+}
+
+/// Renders `samples` so that it is `out_len` samples long and shifted in pitch by
+/// `pitch_shift_cents`.  The length and pitch are changed independently of each other.
+pub fn stretch_and_shift(samples: &[f32], out_len: usize, pitch_shift_cents: f32) -> Vec<f32> {
+  if samples.is_empty() || out_len == 0 {
+    return Vec::new();
+  }
+
+  let pitch_ratio = 2f64.powf(pitch_shift_cents as f64 / 1200.);
+  let stretch_factor = out_len as f64 / samples.len() as f64;
+  if pitch_shift_cents == 0. {
+    let mut stretched = time_stretch(samples, stretch_factor);
+    stretched.resize(out_len, 0.);
+    return stretched;
+  }
+
+  // Stretch to `pitch_ratio` times the target length, then squeeze it back down to the target
+  // length which raises the pitch by `pitch_ratio`
+  let stretched = time_stretch(samples, stretch_factor * pitch_ratio);
+  resample(&stretched, out_len)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: f32 = 44_100.;
+
+  fn sine(freq: f32, len: usize) -> Vec<f32> {
+    (0..len)
+      .map(|i| (i as f32 / SAMPLE_RATE * freq * std::f32::consts::TAU).sin() * 0.5)
+      .collect()
+  }
+
+  /// Estimates frequency by counting rising zero crossings
+  fn estimate_freq(samples: &[f32]) -> f32 {
+    let crossings: Vec<usize> = (1..samples.len())
+      .filter(|&i| samples[i - 1] < 0. && samples[i] >= 0.)
+      .collect();
+    let periods = (crossings.len() - 1) as f32;
+    periods / ((crossings[crossings.len() - 1] - crossings[0]) as f32 / SAMPLE_RATE)
+  }
+
+  fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+  }
+
+  #[test]
+  fn stretch_preserves_pitch() {
+    let input = sine(440., 44_100);
+    for stretch_factor in [0.5, 0.75, 1.5, 2.] {
+      let output = time_stretch(&input, stretch_factor);
+      assert_eq!(output.len(), (44_100. * stretch_factor) as usize);
+
+      let middle = &output[FRAME_LEN..output.len() - FRAME_LEN];
+      let freq = estimate_freq(middle);
+      assert!(
+        (freq - 440.).abs() < 2.,
+        "stretch_factor={stretch_factor}, freq={freq}"
+      );
+      assert!((rms(middle) - rms(&input)).abs() < 0.03);
+    }
+  }
+
+  #[test]
+  fn pitch_shift_preserves_length() {
+    let input = sine(440., 44_100);
+    let output = stretch_and_shift(&input, input.len(), 700.);
+    assert_eq!(output.len(), input.len());
+
+    let expected_freq = 440. * 2f32.powf(7. / 12.);
+    let freq = estimate_freq(&output[FRAME_LEN..output.len() - FRAME_LEN]);
+    assert!((freq - expected_freq).abs() < 3., "freq={freq}");
+  }
+
+  #[test]
+  fn stretch_and_shift_together() {
+    let input = sine(300., 22_050);
+    let output = stretch_and_shift(&input, 33_075, -1200.);
+    assert_eq!(output.len(), 33_075);
+
+    let freq = estimate_freq(&output[FRAME_LEN..output.len() - FRAME_LEN]);
+    assert!((freq - 150.).abs() < 2., "freq={freq}");
+  }
+}
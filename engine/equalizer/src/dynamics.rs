@@ -0,0 +1,185 @@
+//! Dynamic EQ for individual bands.
+//!
+//! A band with dynamics enabled has its gain offset based on the level of a detector signal.  When
+//! the detector level rises above the threshold, the band's gain is reduced by the amount a
+//! compressor with the configured ratio would reduce it.  Ratios below 1 expand instead, boosting
+//! the band when the detector is above the threshold.
+//!
+//! The detector is a bandpass over the band's input.  By default it follows the band's own
+//! frequency and Q, but it can be pointed at a different frequency so that the band is driven by
+//! the content of some other part of the spectrum (for example ducking the low mids whenever the
+//! kick's fundamental is loud).
+//!
+//! When a band processes both stereo channels, detection is linked: the louder of the two channels
+//! drives the gain of both so that the stereo image doesn't shift.
+
+use dsp::{
+  filters::biquad::{BiquadFilter, FilterMode},
+  linear_to_db_checked, SAMPLE_RATE,
+};
+
+/// Gain offsets are clamped to this many dB in either direction
+const MAX_GAIN_OFFSET_DB: f64 = 36.;
+/// Filter coefficients are only recomputed when the band's gain changes by more than this
+const GAIN_RECOMPUTE_THRESHOLD_DB: f64 = 0.01;
+const MIN_TIME_MS: f64 = 0.1;
+
+fn time_ms_to_coeff(time_ms: f64) -> f64 {
+  (-1. / (time_ms.max(MIN_TIME_MS) * 0.001 * SAMPLE_RATE as f64)).exp()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandDynamicsParams {
+  pub threshold_db: f64,
+  pub ratio: f64,
+  pub attack_ms: f64,
+  pub release_ms: f64,
+  /// Center frequency and Q of the detector bandpass.  If `None`, the detector uses the band's own
+  /// frequency and Q.
+  pub sidechain: Option<(f64, f64)>,
+}
+
+pub struct BandDynamics {
+  params: BandDynamicsParams,
+  attack_coeff: f64,
+  release_coeff: f64,
+  /// One detector for each channel that the band processes
+  detectors: [BiquadFilter<f64>; 2],
+  /// Frequency and Q that the detector's coefficients were last computed with
+  detector_freq_q: (f64, f64),
+  envelope_db: f64,
+  /// Band gain that the band's filter coefficients were last computed with
+  applied_gain_db: f64,
+}
+
+impl BandDynamics {
+  pub fn new(params: BandDynamicsParams) -> Self {
+    let mut dynamics = BandDynamics {
+      params,
+      attack_coeff: 0.,
+      release_coeff: 0.,
+      detectors: [BiquadFilter::default(); 2],
+      detector_freq_q: (f64::NAN, f64::NAN),
+      envelope_db: -100.,
+      applied_gain_db: f64::NAN,
+    };
+    dynamics.set_params(params);
+    dynamics
+  }
+
+  /// Updates params while keeping the envelope and detector state intact
+  pub fn set_params(&mut self, params: BandDynamicsParams) {
+    self.params = params;
+    self.attack_coeff = time_ms_to_coeff(params.attack_ms);
+    self.release_coeff = time_ms_to_coeff(params.release_ms);
+  }
+
+  /// Feeds one sample of the band's input for each channel it processes (one or two) to the
+  /// detector and returns the gain offset in dB to apply to the band.  `band_freq` and `band_q` are
+  /// the band's current, possibly automated, params.
+  #[inline]
+  pub fn process(&mut self, samples: &[f64], band_freq: f64, band_q: f64) -> f64 {
+    let freq_q = self.params.sidechain.unwrap_or((band_freq, band_q));
+    if freq_q != self.detector_freq_q {
+      self.detector_freq_q = freq_q;
+      for detector in &mut self.detectors {
+        detector.set_coefficients(FilterMode::Bandpass, freq_q.1, freq_q.0, 0.);
+      }
+    }
+
+    let detected = samples
+      .iter()
+      .zip(&mut self.detectors)
+      .fold(0., |acc: f64, (&sample, detector)| {
+        acc.max(detector.apply(sample).abs())
+      });
+    let level_db = linear_to_db_checked(detected);
+    let coeff = if level_db > self.envelope_db {
+      self.attack_coeff
+    } else {
+      self.release_coeff
+    };
+    self.envelope_db = level_db + coeff * (self.envelope_db - level_db);
+
+    let overshoot_db = self.envelope_db - self.params.threshold_db;
+    if overshoot_db > 0. {
+      (-overshoot_db * (1. - 1. / self.params.ratio.max(0.01)))
+        .clamp(-MAX_GAIN_OFFSET_DB, MAX_GAIN_OFFSET_DB)
+    } else {
+      0.
+    }
+  }
+
+  /// Returns true if the band's filter needs to be recomputed for it to have `gain_db`, recording
+  /// that it was
+  #[inline]
+  pub fn should_recompute(&mut self, gain_db: f64) -> bool {
+    if (gain_db - self.applied_gain_db).abs() < GAIN_RECOMPUTE_THRESHOLD_DB {
+      return false;
+    }
+    self.applied_gain_db = gain_db;
+    true
+  }
+
+  /// Called when the band's filter coefficients are set from outside so that they're recomputed
+  /// with the gain offset on the next sample
+  pub fn invalidate_applied_gain(&mut self) { self.applied_gain_db = f64::NAN; }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run_sine(dynamics: &mut BandDynamics, freq: f64, amplitude: f64) -> f64 {
+    let mut offset = 0.;
+    for i in 0..SAMPLE_RATE as usize / 2 {
+      let t = i as f64 / SAMPLE_RATE as f64;
+      let sample = (t * freq * std::f64::consts::TAU).sin() * amplitude;
+      offset = dynamics.process(&[sample], 1_000., 0.);
+    }
+    offset
+  }
+
+  #[test]
+  fn reduces_gain_above_threshold() {
+    let params = BandDynamicsParams {
+      threshold_db: -20.,
+      ratio: 4.,
+      attack_ms: 5.,
+      release_ms: 50.,
+      sidechain: None,
+    };
+
+    // -40dB sine at the band's frequency stays below the threshold
+    let mut dynamics = BandDynamics::new(params);
+    assert_eq!(run_sine(&mut dynamics, 1_000., 0.01), 0.);
+
+    // 0dB sine overshoots by ~20dB, so a 4:1 ratio should reduce the band by ~15dB
+    let mut dynamics = BandDynamics::new(params);
+    let offset = run_sine(&mut dynamics, 1_000., 1.);
+    assert!(offset < -12. && offset > -16., "{offset}");
+
+    // Loud content far from the band's frequency is rejected by the detector
+    let mut dynamics = BandDynamics::new(params);
+    let offset = run_sine(&mut dynamics, 8_000., 0.1);
+    assert_eq!(offset, 0.);
+
+    // ...unless the sidechain detector is pointed at it
+    let mut dynamics = BandDynamics::new(BandDynamicsParams {
+      sidechain: Some((8_000., 0.)),
+      ..params
+    });
+    let offset = run_sine(&mut dynamics, 8_000., 1.);
+    assert!(offset < -12., "{offset}");
+
+    // With linked stereo detection, a loud signal in either channel reduces the gain
+    let mut dynamics = BandDynamics::new(params);
+    let mut offset = 0.;
+    for i in 0..SAMPLE_RATE as usize / 2 {
+      let t = i as f64 / SAMPLE_RATE as f64;
+      let sample = (t * 1_000. * std::f64::consts::TAU).sin();
+      offset = dynamics.process(&[0., sample], 1_000., 0.);
+    }
+    assert!(offset < -12., "{offset}");
+  }
+}
@@ -9,6 +9,14 @@ use dsp::{
 };
 use num_traits::{Float, FloatConst};
 
+use crate::{
+  dynamics::{BandDynamics, BandDynamicsParams},
+  linear_phase::{FilterMatrix, FirDesigner, LinearPhaseConvolver, FIR_LEN, LATENCY_SAMPLES},
+};
+
+pub mod dynamics;
+pub mod linear_phase;
+
 #[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "env")]
 extern "C" {
@@ -25,6 +33,11 @@ const MAX_Q: f64 = 100.;
 const MIN_GAIN: f64 = -100.;
 const MAX_GAIN: f64 = 100.;
 
+/// Number of points in the summed band response that linear-phase FIRs are designed from
+const LINEAR_PHASE_DESIGN_GRID_SIZE: usize = 2048;
+/// In linear-phase mode, the FIR is rebuilt this often while any of its bands are automated
+const LINEAR_PHASE_AUTOMATION_REBUILD_INTERVAL_FRAMES: usize = 8;
+
 const DYNABANDPASS_MIN_BANDWIDTH: f64 = 5.;
 const DYNABANDPASS_MAX_BANDWIDTH: f64 = 10_000.;
 
@@ -117,6 +130,55 @@ impl EqualizerFilterType {
   }
 }
 
+/// Which channels of the stereo signal a band applies to.  Mid and side bands convert to mid/side,
+/// filter only that channel, and convert back.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ChannelMode {
+  #[default]
+  Stereo = 0,
+  Left = 1,
+  Right = 2,
+  Mid = 3,
+  Side = 4,
+}
+
+impl ChannelMode {
+  fn from_usize(channel_mode: usize) -> Self {
+    match channel_mode {
+      0 => ChannelMode::Stereo,
+      1 => ChannelMode::Left,
+      2 => ChannelMode::Right,
+      3 => ChannelMode::Mid,
+      4 => ChannelMode::Side,
+      _ => panic!("Invalid channel mode: {channel_mode}"),
+    }
+  }
+
+  /// Returns true if a band with this mode should be included in the response shown for `channel`.
+  /// Stereo bands affect every channel.  Left/right bands mix into both mid and side (and vice
+  /// versa) in a way that can't be shown as a single curve, so they're left out of each other's
+  /// responses.
+  fn affects(self, channel: ChannelMode) -> bool { self == channel || self == ChannelMode::Stereo }
+
+  /// Returns the `[output][input]` matrix that applies a band with this mode and a zero-phase
+  /// response of `h` to a left/right signal
+  fn stereo_matrix(self, h: f32) -> [[f32; 2]; 2] {
+    match self {
+      ChannelMode::Stereo => [[h, 0.], [0., h]],
+      ChannelMode::Left => [[h, 0.], [0., 1.]],
+      ChannelMode::Right => [[1., 0.], [0., h]],
+      ChannelMode::Mid => {
+        let (a, b) = ((1. + h) / 2., (h - 1.) / 2.);
+        [[a, b], [b, a]]
+      },
+      ChannelMode::Side => {
+        let (a, b) = ((1. + h) / 2., (1. - h) / 2.);
+        [[a, b], [b, a]]
+      },
+    }
+  }
+}
+
 #[derive(Default, Clone)]
 pub struct BiquadFilterParams<T: Float> {
   pub mode: FilterMode,
@@ -333,21 +395,29 @@ impl EqBandParamOverrides {
 
 #[derive(Default)]
 pub struct EqualizerBand<T: Float + FloatConst + Default> {
+  /// Filter for the channel the band processes, or the left channel for stereo bands
   pub inner: EqualizerBandInner<T>,
+  /// Filter for the right channel of stereo bands.  It has the same params as `inner`.
+  pub inner_right: EqualizerBandInner<T>,
+  pub channel_mode: ChannelMode,
   pub param_overrides: EqBandParamOverrides,
+  /// Only used by filter types that have a gain param
+  pub dynamics: Option<BandDynamics>,
 }
 
 impl<T: Float + FloatConst + Default + MulAssign + AddAssign> EqualizerBand<T> {
-  fn apply(
-    &mut self,
+  /// Returns true if this band has dynamics enabled and a filter type that they can be applied to
+  fn is_dynamic(&self) -> bool {
+    self.dynamics.is_some()
+      && matches!(&self.inner, EqualizerBandInner::Biquad { params, .. } if params.mode.needs_gain())
+  }
+
+  /// Returns the band's `(freq, q, gain)`, reading automated params from `automation_bufs`
+  fn get_params(
+    &self,
     automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
-    sample: T,
     sample_ix: usize,
-  ) -> T {
-    if self.param_overrides.is_empty() {
-      return self.inner.apply_static(sample);
-    }
-
+  ) -> (T, T, T) {
     let freq = if let Some(freq_ix) = self.param_overrides.freq.as_opt() {
       let raw_freq = T::from(automation_bufs[freq_ix][sample_ix]).unwrap();
       dsp::clamp(
@@ -374,7 +444,112 @@ impl<T: Float + FloatConst + Default + MulAssign + AddAssign> EqualizerBand<T> {
     } else {
       self.inner.get_params().gain
     };
-    return self.inner.apply_dynamic(freq, q, gain, sample);
+    (freq, q, gain)
+  }
+
+  /// Applies the band to one sample of each of the channels it processes.  `samples` holds the
+  /// left and right channels for stereo bands, and just the processed channel otherwise.
+  fn apply(
+    &mut self,
+    automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+    samples: &mut [T],
+    sample_ix: usize,
+  ) {
+    let is_dynamic = self.is_dynamic();
+    let is_static = self.param_overrides.is_empty();
+    if is_static && !is_dynamic {
+      for (inner, sample) in [&mut self.inner, &mut self.inner_right]
+        .into_iter()
+        .zip(samples)
+      {
+        *sample = inner.apply_static(*sample);
+      }
+      return;
+    }
+
+    let (freq, q, mut gain) = self.get_params(automation_bufs, sample_ix);
+    if is_dynamic {
+      let dynamics = self.dynamics.as_mut().unwrap();
+      let mut detector_input = [0.; 2];
+      for (dst, sample) in detector_input.iter_mut().zip(samples.iter()) {
+        *dst = sample.to_f64().unwrap();
+      }
+      let gain_offset = dynamics.process(
+        &detector_input[..samples.len()],
+        freq.to_f64().unwrap(),
+        q.to_f64().unwrap(),
+      );
+      gain += T::from(gain_offset).unwrap();
+
+      // Avoid recomputing coefficients every sample while the gain offset is holding steady
+      if is_static && dynamics.should_recompute(gain.to_f64().unwrap()) {
+        for inner in [&mut self.inner, &mut self.inner_right] {
+          if let EqualizerBandInner::Biquad { filter, params } = inner {
+            filter.set_coefficients(params.mode, q, freq, gain);
+          }
+        }
+      }
+    }
+
+    for (inner, sample) in [&mut self.inner, &mut self.inner_right]
+      .into_iter()
+      .zip(samples)
+    {
+      *sample = if is_static {
+        inner.apply_static(*sample)
+      } else {
+        inner.apply_dynamic(freq, q, gain, *sample)
+      };
+    }
+  }
+
+  /// Applies the band to one sample of a stereo signal according to its channel mode
+  fn apply_stereo(
+    &mut self,
+    automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+    (l, r): (T, T),
+    sample_ix: usize,
+  ) -> (T, T) {
+    let half = T::from(0.5).unwrap();
+    match self.channel_mode {
+      ChannelMode::Stereo => {
+        let mut samples = [l, r];
+        self.apply(automation_bufs, &mut samples, sample_ix);
+        (samples[0], samples[1])
+      },
+      ChannelMode::Left => {
+        let mut samples = [l];
+        self.apply(automation_bufs, &mut samples, sample_ix);
+        (samples[0], r)
+      },
+      ChannelMode::Right => {
+        let mut samples = [r];
+        self.apply(automation_bufs, &mut samples, sample_ix);
+        (l, samples[0])
+      },
+      ChannelMode::Mid => {
+        let side = (l - r) * half;
+        let mut samples = [(l + r) * half];
+        self.apply(automation_bufs, &mut samples, sample_ix);
+        (samples[0] + side, samples[0] - side)
+      },
+      ChannelMode::Side => {
+        let mid = (l + r) * half;
+        let mut samples = [(l - r) * half];
+        self.apply(automation_bufs, &mut samples, sample_ix);
+        (mid + samples[0], mid - samples[0])
+      },
+    }
+  }
+
+  /// Restores the band's filter to its static params.  Called after dynamics are disabled since
+  /// they may have left the filter's coefficients set with a gain offset.
+  fn reset_static_coefficients(&mut self) {
+    for inner in [&mut self.inner, &mut self.inner_right] {
+      if let EqualizerBandInner::Biquad { filter, params } = inner {
+        filter.set_coefficients(params.mode, params.q, params.freq, params.gain);
+      }
+    }
   }
 }
 
@@ -385,11 +560,57 @@ pub struct ResponseBuffers {
   pub phases_rads: Vec<f32>,
 }
 
+/// State for linear-phase mode.  Everything needed to design and apply the FIRs, including the FFT
+/// plans, is allocated when the equalizer is created so that the mode can be toggled and the FIRs
+/// rebuilt on the audio thread while bands are automated.
+pub struct LinearPhaseState {
+  pub convolver: LinearPhaseConvolver,
+  designer: FirDesigner,
+  /// Log-spaced grid from 10Hz to the nyquist that band responses are sampled on
+  design_freqs: Vec<f32>,
+  /// Linear magnitude response of a single band over `design_freqs`
+  band_mags: Vec<f64>,
+  /// Combined response of all bands from each input channel to each output channel over
+  /// `design_freqs`, indexed by `[output][input]`
+  matrix_responses: [[Vec<f32>; 2]; 2],
+  firs: [[Vec<f32>; 2]; 2],
+}
+
+impl Default for LinearPhaseState {
+  fn default() -> Self {
+    let multiplier = (NYQUIST as f64 / 10.).powf(1. / (LINEAR_PHASE_DESIGN_GRID_SIZE - 1) as f64);
+    LinearPhaseState {
+      convolver: LinearPhaseConvolver::default(),
+      designer: FirDesigner::default(),
+      design_freqs: (0..LINEAR_PHASE_DESIGN_GRID_SIZE)
+        .map(|i| (10. * multiplier.powi(i as i32)) as f32)
+        .collect(),
+      band_mags: vec![0.; LINEAR_PHASE_DESIGN_GRID_SIZE],
+      matrix_responses: std::array::from_fn(|_| {
+        std::array::from_fn(|_| vec![0.; LINEAR_PHASE_DESIGN_GRID_SIZE])
+      }),
+      firs: std::array::from_fn(|_| std::array::from_fn(|_| vec![0.; FIR_LEN])),
+    }
+  }
+}
+
 pub struct EqualizerInst<T: Float + FloatConst + Default> {
-  pub io_buf: [f32; FRAME_SIZE],
+  /// Left channel followed by right channel
+  pub io_buf: [[f32; FRAME_SIZE]; 2],
   pub bands: Vec<EqualizerBand<T>>,
   pub response_buffers: ResponseBuffers,
   pub automation_bufs: [[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+  /// Used when linear-phase mode is enabled.  Bands with dynamics enabled can't be represented by
+  /// a static FIR, so they're excluded from it and still run as IIR filters after the
+  /// convolution.
+  ///
+  /// Since mid and side bands mix the left and right channels, the convolver applies a matrix of
+  /// FIRs from each input channel to each output channel.
+  pub linear_phase: Box<LinearPhaseState>,
+  pub linear_phase_enabled: bool,
+  /// Set when the bands change so that the linear-phase FIR is rebuilt before the next frame
+  pub fir_dirty: bool,
+  frames_since_fir_rebuild: usize,
 }
 
 type EqualizerInstT = EqualizerInst<f64>;
@@ -397,14 +618,107 @@ type EqualizerInstT = EqualizerInst<f64>;
 impl<T: Float + FloatConst + Default> Default for EqualizerInst<T> {
   fn default() -> Self {
     EqualizerInst {
-      io_buf: [0.; FRAME_SIZE],
+      io_buf: [[0.; FRAME_SIZE]; 2],
       bands: Vec::new(),
       response_buffers: ResponseBuffers::default(),
       automation_bufs: [[0.; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+      linear_phase: Box::default(),
+      linear_phase_enabled: false,
+      fir_dirty: true,
+      frames_since_fir_rebuild: 0,
     }
   }
 }
 
+impl EqualizerInstT {
+  /// Applies all bands matching `include_band` to `io_buf` in order
+  fn apply_bands(&mut self, include_band: impl Fn(&EqualizerBand<f64>) -> bool) {
+    let [left, right] = &mut self.io_buf;
+    for (sample_ix, (l, r)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+      let mut samples = (*l as f64, *r as f64);
+      for band in self.bands.iter_mut().filter(|band| include_band(band)) {
+        samples = band.apply_stereo(&self.automation_bufs, samples, sample_ix);
+      }
+      *l = samples.0 as f32;
+      *r = samples.1 as f32;
+    }
+  }
+
+  /// Designs FIRs from the combined response of all non-dynamic bands and loads them into the
+  /// linear-phase convolver.  Automated params are read from the start of the current frame.
+  ///
+  /// This runs on the audio thread and doesn't allocate.
+  fn rebuild_fir(&mut self) {
+    let LinearPhaseState {
+      convolver,
+      designer,
+      design_freqs,
+      band_mags,
+      matrix_responses,
+      firs,
+    } = &mut *self.linear_phase;
+
+    // Combine the bands into a single matrix from input to output channels at each frequency
+    for (out, row) in matrix_responses.iter_mut().enumerate() {
+      for (inp, response) in row.iter_mut().enumerate() {
+        response.fill(if out == inp { 1. } else { 0. });
+      }
+    }
+    for band in self.bands.iter().filter(|band| !band.is_dynamic()) {
+      band_mags.fill(1.);
+      apply_band_magnitudes(band, &self.automation_bufs, band_mags);
+
+      let [[ll, lr], [rl, rr]] = matrix_responses;
+      for (freq_ix, &mag) in band_mags.iter().enumerate() {
+        let matrix = [[ll[freq_ix], lr[freq_ix]], [rl[freq_ix], rr[freq_ix]]];
+        let band_matrix = band.channel_mode.stereo_matrix(mag as f32);
+        let product: [[f32; 2]; 2] = std::array::from_fn(|out| {
+          std::array::from_fn(|inp| {
+            band_matrix[out][0] * matrix[0][inp] + band_matrix[out][1] * matrix[1][inp]
+          })
+        });
+        [[ll[freq_ix], lr[freq_ix]], [rl[freq_ix], rr[freq_ix]]] = product;
+      }
+    }
+
+    let mut active_firs: FilterMatrix<&[f32]> = Default::default();
+    for ((active_row, fir_row), response_row) in active_firs
+      .iter_mut()
+      .zip(firs.iter_mut())
+      .zip(&*matrix_responses)
+    {
+      for ((active_fir, fir), response) in active_row.iter_mut().zip(fir_row).zip(response_row) {
+        // Cross terms are silent unless there are mid or side bands
+        if response.iter().any(|val| val.abs() > 1e-6) {
+          designer.design(design_freqs, response, fir);
+          *active_fir = Some(fir);
+        }
+      }
+    }
+    convolver.set_firs(&active_firs);
+    self.fir_dirty = false;
+    self.frames_since_fir_rebuild = 0;
+  }
+
+  fn process_linear_phase(&mut self) {
+    let has_automated_fir_bands = self
+      .bands
+      .iter()
+      .any(|band| !band.is_dynamic() && !band.param_overrides.is_empty());
+    self.frames_since_fir_rebuild += 1;
+    if self.fir_dirty
+      || (has_automated_fir_bands
+        && self.frames_since_fir_rebuild >= LINEAR_PHASE_AUTOMATION_REBUILD_INTERVAL_FRAMES)
+    {
+      self.rebuild_fir();
+    }
+
+    self.linear_phase.convolver.process(&mut self.io_buf);
+
+    self.apply_bands(|band| band.is_dynamic());
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn equalizer_init() -> *mut EqualizerInstT {
   maybe_init();
@@ -425,40 +739,13 @@ pub extern "C" fn equalizer_get_automation_bufs_ptr(ctx: *mut EqualizerInstT) ->
   ctx.automation_bufs.as_mut_ptr() as *mut f32
 }
 
-#[no_mangle]
-pub extern "C" fn equalizer_set_band(
-  ctx: *mut EqualizerInstT,
-  band_ix: usize,
-  filter_type: usize,
+fn configure_band_inner(
+  inner: &mut EqualizerBandInner<f64>,
+  filter_type: EqualizerFilterType,
   frequency: f64,
   q: f64,
   gain: f64,
-  freq_automation_ix: usize,
-  q_automation_ix: usize,
-  gain_automation_ix: usize,
 ) {
-  let ctx = unsafe { &mut *ctx };
-  while ctx.bands.len() <= band_ix {
-    ctx.bands.push(EqualizerBand::default());
-  }
-
-  let band = &mut ctx.bands[band_ix];
-  band.param_overrides.q = if q_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
-    NonMaxUsizeOpt::none()
-  } else {
-    NonMaxUsizeOpt(q_automation_ix)
-  };
-  band.param_overrides.gain = if gain_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
-    NonMaxUsizeOpt::none()
-  } else {
-    NonMaxUsizeOpt(gain_automation_ix)
-  };
-  band.param_overrides.freq = if freq_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
-    NonMaxUsizeOpt::none()
-  } else {
-    NonMaxUsizeOpt(freq_automation_ix)
-  };
-
   fn set_chain_params<T: Float + FloatConst + Default + MulAssign + AddAssign, const LEN: usize>(
     filter_chain: &mut [BiquadFilter<T>; LEN],
     params: &mut BiquadFilterParams<T>,
@@ -479,7 +766,6 @@ pub extern "C" fn equalizer_set_band(
     params.gain = gain;
   }
 
-  let filter_type = EqualizerFilterType::from_usize(filter_type);
   match filter_type {
     EqualizerFilterType::Lowpass
     | EqualizerFilterType::Highpass
@@ -489,15 +775,14 @@ pub extern "C" fn equalizer_set_band(
     | EqualizerFilterType::Lowshelf
     | EqualizerFilterType::Highshelf
     | EqualizerFilterType::Allpass => {
-      let (filter, params) = if let EqualizerBandInner::Biquad { filter, params } = &mut band.inner
-      {
+      let (filter, params) = if let EqualizerBandInner::Biquad { filter, params } = &mut *inner {
         (filter, params)
       } else {
-        band.inner = EqualizerBandInner::Biquad {
+        *inner = EqualizerBandInner::Biquad {
           filter: BiquadFilter::default(),
           params: BiquadFilterParams::default(),
         };
-        if let EqualizerBandInner::Biquad { filter, params } = &mut band.inner {
+        if let EqualizerBandInner::Biquad { filter, params } = &mut *inner {
           (filter, params)
         } else {
           unreachable!()
@@ -513,14 +798,14 @@ pub extern "C" fn equalizer_set_band(
     },
     EqualizerFilterType::Order4Lowpass | EqualizerFilterType::Order4Highpass => {
       let (filter_chain, params) =
-        if let EqualizerBandInner::Biquad4 { filter, params } = &mut band.inner {
+        if let EqualizerBandInner::Biquad4 { filter, params } = &mut *inner {
           (filter, params)
         } else {
-          band.inner = EqualizerBandInner::Biquad4 {
+          *inner = EqualizerBandInner::Biquad4 {
             filter: [BiquadFilter::default(); 2],
             params: BiquadFilterParams::default(),
           };
-          if let EqualizerBandInner::Biquad4 { filter, params } = &mut band.inner {
+          if let EqualizerBandInner::Biquad4 { filter, params } = &mut *inner {
             (filter, params)
           } else {
             unreachable!()
@@ -539,14 +824,14 @@ pub extern "C" fn equalizer_set_band(
     },
     EqualizerFilterType::Order8Lowpass | EqualizerFilterType::Order8Highpass => {
       let (filter_chain, params) =
-        if let EqualizerBandInner::Biquad8 { filter, params } = &mut band.inner {
+        if let EqualizerBandInner::Biquad8 { filter, params } = &mut *inner {
           (filter, params)
         } else {
-          band.inner = EqualizerBandInner::Biquad8 {
+          *inner = EqualizerBandInner::Biquad8 {
             filter: [BiquadFilter::default(); 4],
             params: BiquadFilterParams::default(),
           };
-          if let EqualizerBandInner::Biquad8 { filter, params } = &mut band.inner {
+          if let EqualizerBandInner::Biquad8 { filter, params } = &mut *inner {
             (filter, params)
           } else {
             unreachable!()
@@ -565,14 +850,14 @@ pub extern "C" fn equalizer_set_band(
     },
     EqualizerFilterType::Order16Lowpass | EqualizerFilterType::Order16Highpass => {
       let (filter_chain, params) =
-        if let EqualizerBandInner::Biquad16 { filter, params } = &mut band.inner {
+        if let EqualizerBandInner::Biquad16 { filter, params } = &mut *inner {
           (filter, params)
         } else {
-          band.inner = EqualizerBandInner::Biquad16 {
+          *inner = EqualizerBandInner::Biquad16 {
             filter: [BiquadFilter::default(); 8],
             params: BiquadFilterParams::default(),
           };
-          if let EqualizerBandInner::Biquad16 { filter, params } = &mut band.inner {
+          if let EqualizerBandInner::Biquad16 { filter, params } = &mut *inner {
             (filter, params)
           } else {
             unreachable!()
@@ -592,8 +877,8 @@ pub extern "C" fn equalizer_set_band(
     EqualizerFilterType::Dynabandpass => {
       let bandwidth_hz = dynabandpass_q_to_bandwidth(q);
 
-      if !matches!(band.inner, EqualizerBandInner::Dynabandpass { .. }) {
-        band.inner = EqualizerBandInner::Dynabandpass {
+      if !matches!(inner, EqualizerBandInner::Dynabandpass { .. }) {
+        *inner = EqualizerBandInner::Dynabandpass {
           filter: DynabandpassFilter::new(bandwidth_hz as f32),
           center_freq: frequency,
           bandwidth: bandwidth_hz,
@@ -602,7 +887,7 @@ pub extern "C" fn equalizer_set_band(
         filter,
         center_freq,
         bandwidth,
-      } = &mut band.inner
+      } = &mut *inner
       {
         *center_freq = frequency;
         *bandwidth = bandwidth_hz;
@@ -612,24 +897,161 @@ pub extern "C" fn equalizer_set_band(
   }
 }
 
+#[no_mangle]
+pub extern "C" fn equalizer_set_band(
+  ctx: *mut EqualizerInstT,
+  band_ix: usize,
+  filter_type: usize,
+  frequency: f64,
+  q: f64,
+  gain: f64,
+  freq_automation_ix: usize,
+  q_automation_ix: usize,
+  gain_automation_ix: usize,
+) {
+  let ctx = unsafe { &mut *ctx };
+  while ctx.bands.len() <= band_ix {
+    ctx.bands.push(EqualizerBand::default());
+  }
+
+  let band = &mut ctx.bands[band_ix];
+  band.param_overrides.q = if q_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
+    NonMaxUsizeOpt::none()
+  } else {
+    NonMaxUsizeOpt(q_automation_ix)
+  };
+  band.param_overrides.gain = if gain_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
+    NonMaxUsizeOpt::none()
+  } else {
+    NonMaxUsizeOpt(gain_automation_ix)
+  };
+  band.param_overrides.freq = if freq_automation_ix >= MAX_AUTOMATED_PARAM_COUNT {
+    NonMaxUsizeOpt::none()
+  } else {
+    NonMaxUsizeOpt(freq_automation_ix)
+  };
+
+  let filter_type = EqualizerFilterType::from_usize(filter_type);
+  configure_band_inner(&mut band.inner, filter_type, frequency, q, gain);
+  configure_band_inner(&mut band.inner_right, filter_type, frequency, q, gain);
+
+  if let Some(dynamics) = &mut band.dynamics {
+    dynamics.invalidate_applied_gain();
+  }
+  ctx.fir_dirty = true;
+}
+
+/// Enables or disables dynamic EQ for a band.  Only filter types with a gain param are affected.
+///
+/// If `sidechain_freq` is <= 0, the detector follows the band's own frequency and Q.  Otherwise,
+/// it's a bandpass at `sidechain_freq` with `sidechain_q`.
+#[no_mangle]
+pub unsafe extern "C" fn equalizer_set_band_dynamics(
+  ctx: *mut EqualizerInstT,
+  band_ix: usize,
+  enabled: bool,
+  threshold_db: f64,
+  ratio: f64,
+  attack_ms: f64,
+  release_ms: f64,
+  sidechain_freq: f64,
+  sidechain_q: f64,
+) {
+  let ctx = &mut *ctx;
+  let Some(band) = ctx.bands.get_mut(band_ix) else {
+    return;
+  };
+
+  if !enabled {
+    if band.dynamics.take().is_some() {
+      band.reset_static_coefficients();
+      ctx.fir_dirty = true;
+    }
+    return;
+  }
+
+  let params = BandDynamicsParams {
+    threshold_db,
+    ratio,
+    attack_ms,
+    release_ms,
+    sidechain: if sidechain_freq > 0. {
+      Some((sidechain_freq.clamp(MIN_FREQ, MAX_FREQ), sidechain_q))
+    } else {
+      None
+    },
+  };
+  match &mut band.dynamics {
+    Some(dynamics) => dynamics.set_params(params),
+    None => {
+      band.dynamics = Some(BandDynamics::new(params));
+      ctx.fir_dirty = true;
+    },
+  }
+}
+
+/// Sets which channels a band applies to.  See [`ChannelMode`] for values.
+#[no_mangle]
+pub unsafe extern "C" fn equalizer_set_band_channel_mode(
+  ctx: *mut EqualizerInstT,
+  band_ix: usize,
+  channel_mode: usize,
+) {
+  let ctx = &mut *ctx;
+  let Some(band) = ctx.bands.get_mut(band_ix) else {
+    return;
+  };
+
+  let channel_mode = ChannelMode::from_usize(channel_mode);
+  if band.channel_mode != channel_mode {
+    band.channel_mode = channel_mode;
+    ctx.fir_dirty = true;
+  }
+}
+
 #[no_mangle]
 pub extern "C" fn equalizer_set_band_count(ctx: *mut EqualizerInstT, band_count: usize) {
   let ctx = unsafe { &mut *ctx };
   ctx
     .bands
     .resize_with(band_count, || EqualizerBand::default());
+  ctx.fir_dirty = true;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn equalizer_set_linear_phase(ctx: *mut EqualizerInstT, enabled: bool) {
+  let ctx = &mut *ctx;
+  if enabled == ctx.linear_phase_enabled {
+    return;
+  }
+
+  ctx.linear_phase_enabled = enabled;
+  if enabled {
+    // Input from before the mode was disabled shouldn't be played back
+    ctx.linear_phase.convolver.reset();
+    ctx.fir_dirty = true;
+  }
+}
+
+/// Returns the number of samples that the output is delayed by relative to the input
+#[no_mangle]
+pub unsafe extern "C" fn equalizer_get_latency_samples(ctx: *const EqualizerInstT) -> usize {
+  let ctx = &*ctx;
+  if ctx.linear_phase_enabled {
+    LATENCY_SAMPLES
+  } else {
+    0
+  }
 }
 
 #[no_mangle]
 pub extern "C" fn equalizer_process(ctx: *mut EqualizerInstT) {
   let ctx = unsafe { &mut *ctx };
 
-  for (sample_ix, sample_ref) in ctx.io_buf.iter_mut().enumerate() {
-    let mut sample = *sample_ref as f64;
-    for band in &mut ctx.bands {
-      sample = band.apply(&ctx.automation_bufs, sample, sample_ix);
-    }
-    *sample_ref = sample as f32;
+  if ctx.linear_phase_enabled {
+    ctx.process_linear_phase();
+  } else {
+    ctx.apply_bands(|_| true);
   }
 }
 
@@ -658,43 +1080,122 @@ fn compute_chain_response<
   )
 }
 
-#[no_mangle]
-pub extern "C" fn equalizer_compute_responses(
-  ctx: *mut EqualizerInstT,
-  grid_size: usize,
+/// Returns the value of the automated param referenced by `param_override` at the start of the
+/// current frame if there is one and `use_automated_params` is set, or `static_val` otherwise
+fn maybe_automated_param(
+  param_override: &NonMaxUsizeOpt,
+  automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
   use_automated_params: bool,
+  static_val: f64,
+) -> f64 {
+  match param_override.as_opt() {
+    Some(param_ix) if use_automated_params => automation_bufs[param_ix][0] as f64,
+    _ => static_val,
+  }
+}
+
+fn apply_chain_magnitudes<const LEN: usize>(
+  mode: FilterMode,
+  base_qs: &[f64; LEN],
+  q_offset: f64,
+  freq: f64,
+  gain: f64,
+  mags: &mut [f64],
 ) {
-  let ctx = unsafe { &mut *ctx };
+  BiquadFilter::apply_chain_magnitude_grid(
+    mode,
+    std::array::from_fn::<ComputeGridFilterParams<f64>, LEN, _>(|i| ComputeGridFilterParams {
+      q: base_qs[i] + q_offset / LEN as f64,
+      cutoff_freq: freq,
+      gain,
+    }),
+    10.,
+    SAMPLE_RATE as f64,
+    mags,
+  )
+}
 
-  if ctx.bands.is_empty() {
-    ctx.response_buffers.freqs.clear();
-    ctx.response_buffers.magnitudes_db.resize(grid_size, 0.);
-    ctx.response_buffers.magnitudes_db.fill(0.);
-    ctx.response_buffers.phases_rads.resize(grid_size, 0.);
-    ctx.response_buffers.phases_rads.fill(0.);
+/// Multiplies the linear magnitude response of a single band with automated params into `mags`,
+/// which covers the same grid as [`compute_band_response`].  Doesn't allocate.
+fn apply_band_magnitudes(
+  band: &EqualizerBand<f64>,
+  automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+  mags: &mut [f64],
+) {
+  let overrides = &band.param_overrides;
+  match &band.inner {
+    EqualizerBandInner::Dynabandpass {
+      center_freq,
+      bandwidth,
+      ..
+    } => {
+      let freq = maybe_automated_param(&overrides.freq, automation_bufs, true, *center_freq);
+      let bandwidth = match overrides.q.as_opt() {
+        Some(q_ix) => dynabandpass_q_to_bandwidth(automation_bufs[q_ix][0] as f64),
+        None => *bandwidth,
+      };
+      DynabandpassFilter::apply_magnitude_grid(
+        freq as f32,
+        bandwidth as f32,
+        10.,
+        SAMPLE_RATE as f64,
+        mags,
+      );
+    },
+    _ => {
+      let params = band.inner.get_params();
+      let q = maybe_automated_param(&overrides.q, automation_bufs, true, params.q);
+      let freq = maybe_automated_param(&overrides.freq, automation_bufs, true, params.freq);
+      let gain = maybe_automated_param(&overrides.gain, automation_bufs, true, params.gain);
 
-    let start_freq = 10.;
-    let freq_multiplier = (NYQUIST as f64 / start_freq).powf(1. / ((grid_size - 1) as f64));
-    for i in 0..grid_size {
-      let freq = start_freq * freq_multiplier.powi(i as i32);
-      ctx.response_buffers.freqs.push(freq as f32);
-    }
-    return;
+      match &band.inner {
+        EqualizerBandInner::Biquad { .. } => BiquadFilter::apply_chain_magnitude_grid(
+          params.mode,
+          [ComputeGridFilterParams {
+            q,
+            cutoff_freq: freq,
+            gain,
+          }],
+          10.,
+          SAMPLE_RATE as f64,
+          mags,
+        ),
+        EqualizerBandInner::Biquad4 { .. } =>
+          apply_chain_magnitudes(params.mode, &ORDER_4_Q_FACTORS, q, freq, gain, mags),
+        EqualizerBandInner::Biquad8 { .. } =>
+          apply_chain_magnitudes(params.mode, &ORDER_8_Q_FACTORS, q, freq, gain, mags),
+        EqualizerBandInner::Biquad16 { .. } =>
+          apply_chain_magnitudes(params.mode, &ORDER_16_Q_FACTORS, q, freq, gain, mags),
+        EqualizerBandInner::Dynabandpass { .. } => unreachable!(),
+      }
+    },
   }
+}
 
-  let mut responses = ctx.bands.iter().map(|band| match &band.inner {
+/// Computes the response of a single band over a log-spaced grid from 10Hz to the nyquist.
+///
+/// Returns (frequencies_hz, magnitude_linear, phase_rads)
+fn compute_band_response(
+  band: &EqualizerBand<f64>,
+  automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+  grid_size: usize,
+  use_automated_params: bool,
+) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+  match &band.inner {
     EqualizerBandInner::Dynabandpass {
       center_freq,
       bandwidth,
       ..
     } => {
-      let maybe_automated_freq = match band.param_overrides.freq.as_opt() {
-        Some(freq_ix) if use_automated_params => ctx.automation_bufs[freq_ix][0] as f64,
-        _ => *center_freq,
-      };
+      let maybe_automated_freq = maybe_automated_param(
+        &band.param_overrides.freq,
+        automation_bufs,
+        use_automated_params,
+        *center_freq,
+      );
       let maybe_automated_bandwidth = match band.param_overrides.q.as_opt() {
         Some(q_ix) if use_automated_params => {
-          let q_db = ctx.automation_bufs[q_ix][0] as f64;
+          let q_db = automation_bufs[q_ix][0] as f64;
           dynabandpass_q_to_bandwidth(q_db)
         },
         _ => *bandwidth,
@@ -710,18 +1211,25 @@ pub extern "C" fn equalizer_compute_responses(
     },
     _ => {
       let mode = band.inner.get_params().mode;
-      let maybe_automated_q = match band.param_overrides.q.as_opt() {
-        Some(q_ix) if use_automated_params => ctx.automation_bufs[q_ix][0] as f64,
-        _ => band.inner.get_params().q,
-      };
-      let maybe_automated_freq = match band.param_overrides.freq.as_opt() {
-        Some(freq_ix) if use_automated_params => ctx.automation_bufs[freq_ix][0] as f64,
-        _ => band.inner.get_params().freq,
-      };
-      let maybe_automated_gain = match band.param_overrides.gain.as_opt() {
-        Some(gain_ix) if use_automated_params => ctx.automation_bufs[gain_ix][0] as f64,
-        _ => band.inner.get_params().gain,
-      };
+      let params = band.inner.get_params();
+      let maybe_automated_q = maybe_automated_param(
+        &band.param_overrides.q,
+        automation_bufs,
+        use_automated_params,
+        params.q,
+      );
+      let maybe_automated_freq = maybe_automated_param(
+        &band.param_overrides.freq,
+        automation_bufs,
+        use_automated_params,
+        params.freq,
+      );
+      let maybe_automated_gain = maybe_automated_param(
+        &band.param_overrides.gain,
+        automation_bufs,
+        use_automated_params,
+        params.gain,
+      );
 
       match &band.inner {
         EqualizerBandInner::Biquad { .. } => BiquadFilter::compute_response_grid::<f32>(
@@ -760,9 +1268,26 @@ pub extern "C" fn equalizer_compute_responses(
         EqualizerBandInner::Dynabandpass { .. } => unreachable!(),
       }
     },
-  });
+  }
+}
 
-  let (freqs, mut mags, mut angles) = responses.next().unwrap();
+/// Computes the combined response of all bands matching `include_band` over a log-spaced grid from
+/// 10Hz to the nyquist.  Returns `None` if there are no such bands.
+///
+/// Returns (frequencies_hz, magnitude_linear, phase_rads)
+fn compute_summed_response(
+  bands: &[EqualizerBand<f64>],
+  automation_bufs: &[[f32; FRAME_SIZE]; MAX_AUTOMATED_PARAM_COUNT],
+  grid_size: usize,
+  use_automated_params: bool,
+  include_band: impl Fn(&EqualizerBand<f64>) -> bool,
+) -> Option<(Vec<f32>, Vec<f32>, Vec<f32>)> {
+  let mut responses = bands
+    .iter()
+    .filter(|band| include_band(band))
+    .map(|band| compute_band_response(band, automation_bufs, grid_size, use_automated_params));
+
+  let (freqs, mut mags, mut angles) = responses.next()?;
 
   for (_o_freqs, o_mags, o_angles) in responses {
     for i in 0..mags.len() {
@@ -771,6 +1296,43 @@ pub extern "C" fn equalizer_compute_responses(
     }
   }
 
+  Some((freqs, mags, angles))
+}
+
+/// Computes the response of the bands affecting `channel` (see [`ChannelMode`] for values) and
+/// stores it in the response buffers
+#[no_mangle]
+pub extern "C" fn equalizer_compute_responses(
+  ctx: *mut EqualizerInstT,
+  grid_size: usize,
+  use_automated_params: bool,
+  channel: usize,
+) {
+  let ctx = unsafe { &mut *ctx };
+
+  let channel = ChannelMode::from_usize(channel);
+  let Some((freqs, mut mags, angles)) = compute_summed_response(
+    &ctx.bands,
+    &ctx.automation_bufs,
+    grid_size,
+    use_automated_params,
+    |band| band.channel_mode.affects(channel),
+  ) else {
+    ctx.response_buffers.freqs.clear();
+    ctx.response_buffers.magnitudes_db.resize(grid_size, 0.);
+    ctx.response_buffers.magnitudes_db.fill(0.);
+    ctx.response_buffers.phases_rads.resize(grid_size, 0.);
+    ctx.response_buffers.phases_rads.fill(0.);
+
+    let start_freq = 10.;
+    let freq_multiplier = (NYQUIST as f64 / start_freq).powf(1. / ((grid_size - 1) as f64));
+    for i in 0..grid_size {
+      let freq = start_freq * freq_multiplier.powi(i as i32);
+      ctx.response_buffers.freqs.push(freq as f32);
+    }
+    return;
+  };
+
   // TODO: normalize angles
 
   for mag in &mut mags {
@@ -799,3 +1361,173 @@ pub extern "C" fn equalizer_get_response_phases_ptr(ctx: *const EqualizerInstT)
   let ctx = unsafe { &*ctx };
   ctx.response_buffers.phases_rads.as_ptr() as *const f32
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reenabling_linear_phase_clears_input_history() {
+    let ctx = equalizer_init();
+    let peak_type = EqualizerFilterType::Peak as usize;
+    equalizer_set_band(ctx, 0, peak_type, 1_000., 0., 6., 99, 99, 99);
+    unsafe { equalizer_set_linear_phase(ctx, true) };
+
+    let inst = unsafe { &mut *ctx };
+    inst.io_buf = [[1.; FRAME_SIZE]; 2];
+    equalizer_process(ctx);
+
+    unsafe {
+      equalizer_set_linear_phase(ctx, false);
+      assert_eq!(equalizer_get_latency_samples(ctx), 0);
+      equalizer_set_linear_phase(ctx, true);
+    }
+    for _ in 0..(LATENCY_SAMPLES * 2 / FRAME_SIZE + 1) {
+      let inst = unsafe { &mut *ctx };
+      inst.io_buf = [[0.; FRAME_SIZE]; 2];
+      equalizer_process(ctx);
+      assert!(inst.io_buf.iter().flatten().all(|&sample| sample == 0.));
+    }
+  }
+
+  #[test]
+  fn linear_phase_impulse_response_is_symmetric() {
+    let ctx = equalizer_init();
+    let peak_type = EqualizerFilterType::Peak as usize;
+    let shelf_type = EqualizerFilterType::Lowshelf as usize;
+    equalizer_set_band(ctx, 0, peak_type, 1_000., 0., 6., 99, 99, 99);
+    equalizer_set_band(ctx, 1, shelf_type, 200., 0., -4., 99, 99, 99);
+    unsafe {
+      equalizer_set_linear_phase(ctx, true);
+      assert_eq!(equalizer_get_latency_samples(ctx), LATENCY_SAMPLES);
+    }
+
+    let mut impulse_response = Vec::new();
+    for frame_ix in 0..(LATENCY_SAMPLES * 2 / FRAME_SIZE + 1) {
+      let inst = unsafe { &mut *ctx };
+      inst.io_buf = [[0.; FRAME_SIZE]; 2];
+      if frame_ix == 0 {
+        inst.io_buf[0][0] = 1.;
+      }
+      equalizer_process(ctx);
+      impulse_response.extend_from_slice(&inst.io_buf[0]);
+      assert!(inst.io_buf[1].iter().all(|&sample| sample == 0.));
+    }
+
+    let peak_ix = impulse_response
+      .iter()
+      .enumerate()
+      .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+      .unwrap()
+      .0;
+    assert_eq!(peak_ix, LATENCY_SAMPLES);
+    for i in 1..LATENCY_SAMPLES {
+      let (before, after) = (
+        impulse_response[LATENCY_SAMPLES - i],
+        impulse_response[LATENCY_SAMPLES + i],
+      );
+      assert!((before - after).abs() < 1e-5, "{i}: {before} != {after}");
+    }
+  }
+
+  /// A side-only band shouldn't affect a mono signal, and a mid-only band shouldn't affect a signal
+  /// with opposite polarity in each channel
+  #[test]
+  fn mid_side_bands_only_affect_their_channel() {
+    for linear_phase in [false, true] {
+      for (channel_mode, polarity) in [(ChannelMode::Side, 1.), (ChannelMode::Mid, -1.)] {
+        let ctx = equalizer_init();
+        let peak_type = EqualizerFilterType::Peak as usize;
+        equalizer_set_band(ctx, 0, peak_type, 1_000., 0., 12., 99, 99, 99);
+        unsafe {
+          equalizer_set_band_channel_mode(ctx, 0, channel_mode as usize);
+          equalizer_set_linear_phase(ctx, linear_phase);
+        }
+
+        let latency = unsafe { equalizer_get_latency_samples(ctx) };
+        let sine = |i: usize| (i as f32 / SAMPLE_RATE * 1_000. * std::f32::consts::TAU).sin();
+        let frame_count = (latency + SAMPLE_RATE as usize / 4) / FRAME_SIZE;
+        let mut max_error = 0.0f32;
+        for frame_ix in 0..frame_count {
+          let inst = unsafe { &mut *ctx };
+          for i in 0..FRAME_SIZE {
+            let sample = sine(frame_ix * FRAME_SIZE + i);
+            inst.io_buf[0][i] = sample;
+            inst.io_buf[1][i] = sample * polarity;
+          }
+          equalizer_process(ctx);
+
+          for i in 0..FRAME_SIZE {
+            let Some(src_ix) = (frame_ix * FRAME_SIZE + i).checked_sub(latency) else {
+              continue;
+            };
+            let expected = sine(src_ix);
+            max_error = max_error
+              .max((inst.io_buf[0][i] - expected).abs())
+              .max((inst.io_buf[1][i] - expected * polarity).abs());
+          }
+        }
+        assert!(
+          max_error < 1e-3,
+          "{channel_mode:?} {linear_phase}: {max_error}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn band_magnitudes_match_response_grid() {
+    let ctx = equalizer_init();
+    // Automate the frequency of the first band to make sure automated params are used
+    equalizer_set_band(
+      ctx,
+      0,
+      EqualizerFilterType::Peak as usize,
+      1_000.,
+      2.,
+      6.,
+      0,
+      99,
+      99,
+    );
+    for (band_ix, filter_type) in [
+      EqualizerFilterType::Lowshelf,
+      EqualizerFilterType::Order8Highpass,
+      EqualizerFilterType::Dynabandpass,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+      equalizer_set_band(
+        ctx,
+        band_ix + 1,
+        filter_type as usize,
+        500.,
+        1.,
+        -3.,
+        99,
+        99,
+        99,
+      );
+    }
+    let inst = unsafe { &mut *ctx };
+    inst.automation_bufs[0][0] = 3_000.;
+
+    for band in &inst.bands {
+      let (_freqs, expected_mags, _phases) = compute_band_response(
+        band,
+        &inst.automation_bufs,
+        LINEAR_PHASE_DESIGN_GRID_SIZE,
+        true,
+      );
+      let mut mags = vec![1.; LINEAR_PHASE_DESIGN_GRID_SIZE];
+      apply_band_magnitudes(band, &inst.automation_bufs, &mut mags);
+      // The reference response is computed in single precision for dynabandpass bands, so it
+      // drifts far down in the stopband
+      for (&mag, &expected) in mags.iter().zip(&expected_mags).filter(|(_, &e)| e > 1e-4) {
+        let error_db = (20. * (mag / expected as f64).log10()).abs();
+        assert!(error_db < 0.1, "{mag} != {expected}");
+      }
+    }
+  }
+}
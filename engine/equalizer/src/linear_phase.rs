@@ -0,0 +1,414 @@
+//! Linear-phase mode for the equalizer.
+//!
+//! Instead of running the bands' IIR filters, a symmetric FIR filter is designed to match the
+//! magnitude of the summed band response and applied with uniformly partitioned FFT convolution.
+//! This avoids the phase shifts that the IIR filters introduce around each band's frequency at the
+//! cost of `FIR_LEN / 2` samples of latency.  The latency is reported to the UI but isn't
+//! compensated for.
+//!
+//! The convolution is done in blocks of `FRAME_SIZE` with overlap-save.  The spectra of past input
+//! blocks are kept in a delay line so that each block only needs one forward and one inverse FFT no
+//! matter how long the filter is.
+
+use std::sync::Arc;
+
+use dsp::{FRAME_SIZE, SAMPLE_RATE};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Length of the designed FIR filter.  This determines the frequency resolution of the filter,
+/// which is `SAMPLE_RATE / FIR_LEN`, as well as the latency.
+pub const FIR_LEN: usize = 8192;
+pub const LATENCY_SAMPLES: usize = FIR_LEN / 2;
+const PARTITION_COUNT: usize = FIR_LEN / FRAME_SIZE;
+const BLOCK_FFT_SIZE: usize = FRAME_SIZE * 2;
+
+/// Designs linear-phase FIR filters of length `FIR_LEN`.  The FFT plan and buffers are created
+/// up front and reused so that filters can be redesigned on the audio thread without allocating.
+pub struct FirDesigner {
+  ifft: Arc<dyn Fft<f64>>,
+  spectrum: Vec<Complex<f64>>,
+  scratch: Vec<Complex<f64>>,
+  /// Blackman window applied to the impulse to smooth the effects of truncating it
+  window: Vec<f64>,
+}
+
+impl Default for FirDesigner {
+  fn default() -> Self {
+    let ifft = FftPlanner::new().plan_fft_inverse(FIR_LEN);
+    let scratch_len = ifft.get_inplace_scratch_len();
+    FirDesigner {
+      ifft,
+      spectrum: vec![Complex::new(0., 0.); FIR_LEN],
+      scratch: vec![Complex::new(0., 0.); scratch_len],
+      window: (0..FIR_LEN)
+        .map(|i| {
+          let phase = std::f64::consts::TAU * i as f64 / FIR_LEN as f64;
+          0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos()
+        })
+        .collect(),
+    }
+  }
+}
+
+impl FirDesigner {
+  /// Designs a linear-phase FIR filter with the given zero-phase response and writes it to `out`,
+  /// which must have length `FIR_LEN`.
+  ///
+  /// `freqs_hz` must be ascending, and `magnitudes` are linear gains at each of those frequencies.
+  /// They may be negative, which inverts the polarity of the filter at those frequencies.
+  /// The response is interpolated in log frequency onto the FFT bins and held constant outside of
+  /// the provided range.  The resulting filter's peak is at `LATENCY_SAMPLES`.
+  pub fn design(&mut self, freqs_hz: &[f32], magnitudes: &[f32], out: &mut [f32]) {
+    assert_eq!(freqs_hz.len(), magnitudes.len());
+    assert!(!freqs_hz.is_empty());
+    assert_eq!(out.len(), FIR_LEN);
+
+    // Zero-phase spectrum; the inverse FFT is real and symmetric around sample 0.  Bins are
+    // ascending, so the grid is walked alongside them rather than searched for each bin.
+    let mut grid_ix = 0;
+    for bin_ix in 0..=FIR_LEN / 2 {
+      let freq = bin_ix as f64 * SAMPLE_RATE as f64 / FIR_LEN as f64;
+      while grid_ix < freqs_hz.len() && (freqs_hz[grid_ix] as f64) < freq {
+        grid_ix += 1;
+      }
+      let mag = if grid_ix == 0 {
+        magnitudes[0] as f64
+      } else if grid_ix == freqs_hz.len() {
+        magnitudes[freqs_hz.len() - 1] as f64
+      } else {
+        let (lo, hi) = (
+          (freqs_hz[grid_ix - 1] as f64).log2(),
+          (freqs_hz[grid_ix] as f64).log2(),
+        );
+        let (lo_mag, hi_mag) = (magnitudes[grid_ix - 1] as f64, magnitudes[grid_ix] as f64);
+        lo_mag + (hi_mag - lo_mag) * (freq.log2() - lo) / (hi - lo)
+      };
+      self.spectrum[bin_ix] = Complex::new(mag, 0.);
+      self.spectrum[(FIR_LEN - bin_ix) % FIR_LEN] = Complex::new(mag, 0.);
+    }
+    self
+      .ifft
+      .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+    // Rotate the impulse to be centered at `LATENCY_SAMPLES` and window it
+    for (i, (dst, &window)) in out.iter_mut().zip(&self.window).enumerate() {
+      let h = self.spectrum[(i + LATENCY_SAMPLES) % FIR_LEN].re / FIR_LEN as f64;
+      *dst = (h * window) as f32;
+    }
+  }
+}
+
+/// Designs a single linear-phase FIR filter.  See [`FirDesigner::design`].
+pub fn design_fir(freqs_hz: &[f32], magnitudes: &[f32]) -> Vec<f32> {
+  let mut fir = vec![0.; FIR_LEN];
+  FirDesigner::default().design(freqs_hz, magnitudes, &mut fir);
+  fir
+}
+
+/// Spectra of each `FRAME_SIZE` partition of a FIR filter, zero-padded to `BLOCK_FFT_SIZE`
+type PartitionedFilter = Vec<[Complex<f32>; BLOCK_FFT_SIZE]>;
+
+/// Filters from each input channel to each output channel, indexed by `[output][input]`.  `None`
+/// entries don't pass any signal and are skipped.
+pub type FilterMatrix<F> = [[Option<F>; 2]; 2];
+
+/// Partitioned filters for each entry of a [`FilterMatrix`].  The partitions are allocated once and
+/// overwritten when the filters change; inactive entries are skipped.
+#[derive(Default)]
+struct PartitionedFilterMatrix {
+  partitions: [[PartitionedFilter; 2]; 2],
+  active: [[bool; 2]; 2],
+}
+
+impl PartitionedFilterMatrix {
+  fn new() -> Self {
+    PartitionedFilterMatrix {
+      partitions: std::array::from_fn(|_| {
+        std::array::from_fn(|_| vec![[Complex::new(0., 0.); BLOCK_FFT_SIZE]; PARTITION_COUNT])
+      }),
+      active: [[false; 2]; 2],
+    }
+  }
+}
+
+/// Convolves a stereo signal with a matrix of FIR filters.  Bands that only affect the mid or side
+/// channel mix the left and right channels together, so the combined filter for all bands needs
+/// cross terms between channels.
+pub struct LinearPhaseConvolver {
+  fft: Arc<dyn Fft<f32>>,
+  ifft: Arc<dyn Fft<f32>>,
+  filters: PartitionedFilterMatrix,
+  /// Filters that were active before the last call to `set_firs`.  If `crossfading` is set, the
+  /// output is crossfaded from them to the new filters over the next block.
+  prev_filters: PartitionedFilterMatrix,
+  crossfading: bool,
+  /// For each input channel, the previous input block followed by the current one
+  input_bufs: [[f32; BLOCK_FFT_SIZE]; 2],
+  /// For each input channel, spectra of the most recent `PARTITION_COUNT` input blocks, indexed
+  /// circularly by `fdl_pos`
+  input_spectra: [PartitionedFilter; 2],
+  fdl_pos: usize,
+  accumulator: [Complex<f32>; BLOCK_FFT_SIZE],
+  prev_output: [[f32; FRAME_SIZE]; 2],
+  scratch: Vec<Complex<f32>>,
+}
+
+impl Default for LinearPhaseConvolver {
+  fn default() -> Self {
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(BLOCK_FFT_SIZE);
+    let ifft = planner.plan_fft_inverse(BLOCK_FFT_SIZE);
+    let scratch_len = fft
+      .get_inplace_scratch_len()
+      .max(ifft.get_inplace_scratch_len());
+    LinearPhaseConvolver {
+      fft,
+      ifft,
+      filters: PartitionedFilterMatrix::new(),
+      prev_filters: PartitionedFilterMatrix::new(),
+      crossfading: false,
+      input_bufs: [[0.; BLOCK_FFT_SIZE]; 2],
+      input_spectra: std::array::from_fn(|_| {
+        vec![[Complex::new(0., 0.); BLOCK_FFT_SIZE]; PARTITION_COUNT]
+      }),
+      fdl_pos: 0,
+      accumulator: [Complex::new(0., 0.); BLOCK_FFT_SIZE],
+      prev_output: [[0.; FRAME_SIZE]; 2],
+      scratch: vec![Complex::new(0., 0.); scratch_len],
+    }
+  }
+}
+
+impl LinearPhaseConvolver {
+  /// Clears the input history so that previously processed audio isn't convolved with the filters
+  /// again.  The filters themselves are kept.
+  pub fn reset(&mut self) {
+    self.crossfading = false;
+    self.input_bufs = [[0.; BLOCK_FFT_SIZE]; 2];
+    for input_spectra in &mut self.input_spectra {
+      input_spectra.fill([Complex::new(0., 0.); BLOCK_FFT_SIZE]);
+    }
+    self.fdl_pos = 0;
+    self.prev_output = [[0.; FRAME_SIZE]; 2];
+  }
+
+  /// Replaces the filters.  All provided FIRs must have length `FIR_LEN`.
+  pub fn set_firs(&mut self, firs: &FilterMatrix<&[f32]>) {
+    // If the filters were already replaced since the last block, the output is still coming from
+    // the filters before that, so those are kept to crossfade from
+    if !self.crossfading {
+      std::mem::swap(&mut self.filters, &mut self.prev_filters);
+      self.crossfading = true;
+    }
+
+    for ((partitions_row, active_row), row) in self
+      .filters
+      .partitions
+      .iter_mut()
+      .zip(&mut self.filters.active)
+      .zip(firs)
+    {
+      for ((partitioned, active), fir) in partitions_row.iter_mut().zip(active_row).zip(row) {
+        *active = fir.is_some();
+        let Some(fir) = fir else {
+          continue;
+        };
+        assert_eq!(fir.len(), FIR_LEN);
+
+        for (partition, taps) in partitioned.iter_mut().zip(fir.as_chunks::<FRAME_SIZE>().0) {
+          for (dst, &tap) in partition.iter_mut().zip(taps) {
+            *dst = Complex::new(tap, 0.);
+          }
+          partition[FRAME_SIZE..].fill(Complex::new(0., 0.));
+          self.fft.process_with_scratch(partition, &mut self.scratch);
+        }
+      }
+    }
+  }
+
+  /// Convolves the most recent input block with `filters`, writing the result for each output
+  /// channel into `output`
+  fn render(&mut self, filters: &PartitionedFilterMatrix, output: &mut [[f32; FRAME_SIZE]; 2]) {
+    let norm = 1. / BLOCK_FFT_SIZE as f32;
+    for ((row, active_row), out_buf) in filters
+      .partitions
+      .iter()
+      .zip(&filters.active)
+      .zip(output.iter_mut())
+    {
+      if !active_row.iter().any(|&active| active) {
+        out_buf.fill(0.);
+        continue;
+      }
+
+      self.accumulator.fill(Complex::new(0., 0.));
+      for ((filter, &active), input_spectra) in row.iter().zip(active_row).zip(&self.input_spectra)
+      {
+        if !active {
+          continue;
+        }
+        for (partition_ix, partition) in filter.iter().enumerate() {
+          // Partition `i` of the filter is applied to the input block from `i` blocks ago
+          let input_ix = (self.fdl_pos + PARTITION_COUNT - partition_ix) % PARTITION_COUNT;
+          let input = &input_spectra[input_ix];
+          // Both signals are real, so only bins up to the nyquist need to be computed
+          for ((acc, &x), &h) in self.accumulator[..=FRAME_SIZE]
+            .iter_mut()
+            .zip(input)
+            .zip(partition)
+          {
+            *acc += x * h;
+          }
+        }
+      }
+      for bin_ix in 1..FRAME_SIZE {
+        self.accumulator[BLOCK_FFT_SIZE - bin_ix] = self.accumulator[bin_ix].conj();
+      }
+      self
+        .ifft
+        .process_with_scratch(&mut self.accumulator, &mut self.scratch);
+
+      // With overlap-save, the first half of the output block is corrupted by circular wraparound
+      // and discarded
+      for (dst, sample) in out_buf.iter_mut().zip(&self.accumulator[FRAME_SIZE..]) {
+        *dst = sample.re * norm;
+      }
+    }
+  }
+
+  /// Convolves one block of stereo input with the filters in place
+  pub fn process(&mut self, io_bufs: &mut [[f32; FRAME_SIZE]; 2]) {
+    self.fdl_pos = (self.fdl_pos + 1) % PARTITION_COUNT;
+    for ((input_buf, input_spectra), io_buf) in self
+      .input_bufs
+      .iter_mut()
+      .zip(&mut self.input_spectra)
+      .zip(io_bufs.iter())
+    {
+      input_buf.copy_within(FRAME_SIZE.., 0);
+      input_buf[FRAME_SIZE..].copy_from_slice(io_buf);
+
+      let input_spectrum = &mut input_spectra[self.fdl_pos];
+      for (dst, &sample) in input_spectrum.iter_mut().zip(input_buf.iter()) {
+        *dst = Complex::new(sample, 0.);
+      }
+      self
+        .fft
+        .process_with_scratch(input_spectrum, &mut self.scratch);
+    }
+
+    let filters = std::mem::take(&mut self.filters);
+    self.render(&filters, io_bufs);
+    self.filters = filters;
+
+    if self.crossfading {
+      self.crossfading = false;
+      let prev_filters = std::mem::take(&mut self.prev_filters);
+      let mut prev_output = self.prev_output;
+      self.render(&prev_filters, &mut prev_output);
+      self.prev_filters = prev_filters;
+      for (io_buf, prev_buf) in io_bufs.iter_mut().zip(&prev_output) {
+        for (i, (new, old)) in io_buf.iter_mut().zip(prev_buf).enumerate() {
+          let mix = (i + 1) as f32 / FRAME_SIZE as f32;
+          *new = old + (*new - old) * mix;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn convolution_matches_direct_fir() {
+    let mk_signal = |len: usize, seed: usize, scale: f32| -> Vec<f32> {
+      (0..len)
+        .map(|i| (((i + 1) * seed) % 997) as f32 / 997. - 0.5)
+        .map(|x| x * scale)
+        .collect()
+    };
+    let (fir_a, fir_b) = (
+      mk_signal(FIR_LEN, 7919, 0.01),
+      mk_signal(FIR_LEN, 6007, 0.01),
+    );
+    let (input_l, input_r) = (
+      mk_signal(FIR_LEN * 2, 104_729, 1.),
+      mk_signal(FIR_LEN * 2, 3331, 1.),
+    );
+
+    // The right output gets a cross term from the left input
+    let mut convolver = LinearPhaseConvolver::default();
+    convolver.set_firs(&[[Some(&fir_a[..]), None], [
+      Some(&fir_b[..]),
+      Some(&fir_a[..]),
+    ]]);
+    let (mut output_l, mut output_r) = (Vec::new(), Vec::new());
+    for (block_l, block_r) in input_l
+      .as_chunks::<FRAME_SIZE>()
+      .0
+      .iter()
+      .zip(input_r.as_chunks::<FRAME_SIZE>().0)
+    {
+      let mut io_bufs = [*block_l, *block_r];
+      convolver.process(&mut io_bufs);
+      output_l.extend_from_slice(&io_bufs[0]);
+      output_r.extend_from_slice(&io_bufs[1]);
+    }
+
+    let convolve = |fir: &[f32], input: &[f32], n: usize| -> f32 {
+      (0..=n.min(FIR_LEN - 1))
+        .map(|k| fir[k] * input[n - k])
+        .sum()
+    };
+    // The first block is crossfaded in from the silent initial filters
+    for n in (FRAME_SIZE..input_l.len()).step_by(37) {
+      let expected_l = convolve(&fir_a, &input_l, n);
+      let expected_r = convolve(&fir_b, &input_l, n) + convolve(&fir_a, &input_r, n);
+      assert!(
+        (output_l[n] - expected_l).abs() < 1e-3,
+        "sample {n}: {} != {expected_l}",
+        output_l[n]
+      );
+      assert!(
+        (output_r[n] - expected_r).abs() < 1e-3,
+        "sample {n}: {} != {expected_r}",
+        output_r[n]
+      );
+    }
+  }
+
+  #[test]
+  fn designed_fir_matches_response() {
+    // A broad +6dB bump centered on 1kHz
+    let freqs: Vec<f32> = (0..512)
+      .map(|i| 10. * (2_000f32).powf(i as f32 / 511.))
+      .collect();
+    let target_db = |f: f64| 6. * (-(f / 1_000.).log2().powi(2)).exp();
+    let mags: Vec<f32> = freqs
+      .iter()
+      .map(|&f| 10f64.powf(target_db(f as f64) / 20.) as f32)
+      .collect();
+    let fir = design_fir(&freqs, &mags);
+
+    // Symmetric around `LATENCY_SAMPLES`, so the phase is linear
+    for i in 1..LATENCY_SAMPLES {
+      assert!((fir[LATENCY_SAMPLES - i] - fir[LATENCY_SAMPLES + i]).abs() < 1e-6);
+    }
+
+    for freq in [50., 200., 1_000., 3_000., 10_000.] {
+      let w = std::f64::consts::TAU * freq / SAMPLE_RATE as f64;
+      let (re, im) = fir.iter().enumerate().fold((0., 0.), |(re, im), (n, &h)| {
+        let h = h as f64;
+        (re + h * (w * n as f64).cos(), im - h * (w * n as f64).sin())
+      });
+      let db = 20. * (re * re + im * im).sqrt().log10();
+      assert!(
+        (db - target_db(freq)).abs() < 0.1,
+        "{freq}Hz: {db} != {}",
+        target_db(freq)
+      );
+    }
+  }
+}
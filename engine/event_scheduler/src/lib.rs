@@ -4,22 +4,38 @@ use common::ref_static_mut;
 use float_ord::FloatOrd;
 use heapless::binary_heap::{BinaryHeap, Min};
 
+use crate::tempo_map::{TempoChange, TempoMap};
+
+mod tempo_map;
+
 #[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "env")]
 extern "C" {
-  fn run_callback(cb_id: i32);
+  fn run_callback(cb_id: i32, frame_offset: usize);
 
-  fn run_midi_callback(mailbox_ix: usize, event_type: u8, param_0: f32, param_1: f32);
+  fn run_midi_callback(
+    mailbox_ix: usize,
+    event_type: u8,
+    param_0: f32,
+    param_1: f32,
+    frame_offset: usize,
+  );
 
   #[allow(dead_code)]
   fn debug1(v: i32);
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-extern "C" fn run_callback(_cb_id: i32) { unimplemented!() }
+extern "C" fn run_callback(_cb_id: i32, _frame_offset: usize) { unimplemented!() }
 
 #[cfg(not(target_arch = "wasm32"))]
-extern "C" fn run_midi_callback(_mailbox_ix: usize, _event_type: u8, _param_0: f32, _param_1: f32) {
+extern "C" fn run_midi_callback(
+  _mailbox_ix: usize,
+  _event_type: u8,
+  _param_0: f32,
+  _param_1: f32,
+  _frame_offset: usize,
+) {
   unimplemented!()
 }
 
@@ -133,7 +149,7 @@ pub extern "C" fn schedule_beats(
     .expect("`SCHEDULED_BEAT_EVENTS` is full");
 }
 
-fn handle_event(evt: ScheduledEvent) {
+fn handle_event(evt: ScheduledEvent, frame_offset: usize) {
   if let Some(midi_evt) = evt.midi_evt {
     unsafe {
       run_midi_callback(
@@ -141,10 +157,11 @@ fn handle_event(evt: ScheduledEvent) {
         midi_evt.event_type,
         midi_evt.param_0,
         midi_evt.param_1,
+        frame_offset,
       )
     }
   } else {
-    unsafe { run_callback(evt.cb_id) }
+    unsafe { run_callback(evt.cb_id, frame_offset) }
   }
 }
 
@@ -158,7 +175,7 @@ pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) {
     }
 
     let evt = unsafe { scheduled_events().pop_unchecked() };
-    handle_event(evt);
+    handle_event(evt, 0);
   }
 
   loop {
@@ -169,10 +186,143 @@ pub extern "C" fn run(raw_cur_time: f64, cur_beats: f64) {
     }
 
     let evt = unsafe { scheduled_beat_events().pop_unchecked() };
-    handle_event(evt);
+    handle_event(evt, 0);
+  }
+}
+
+/// Fires every event that falls before the end of the frame starting at `frame_start_time`,
+/// converting beat-scheduled events to seconds using `tempo_map`.  Events are fired in time order
+/// along with the offset in samples into the frame at which they land.  Events that are already
+/// late fire at offset 0.
+fn drain_frame<const N: usize>(
+  events: &mut BinaryHeap<ScheduledEvent, Min, N>,
+  beat_events: &mut BinaryHeap<ScheduledEvent, Min, N>,
+  tempo_map: &TempoMap,
+  frame_start_time: f64,
+  sample_rate: f64,
+  frame_size: usize,
+  mut handle_event: impl FnMut(ScheduledEvent, usize),
+) {
+  // Positions are compared in samples with a bit of slack so that events computed to land exactly
+  // on a sample boundary aren't pushed back a sample by floating point error
+  const SAMPLE_EPSILON: f64 = 1e-6;
+  let frame_start_sample = frame_start_time * sample_rate;
+  let frame_end_sample = frame_start_sample + frame_size as f64;
+
+  loop {
+    let next_time = events.peek().map(|evt| evt.time);
+    let next_beat_time = beat_events
+      .peek()
+      .map(|evt| tempo_map.time_at_beat(evt.time));
+    let (time, is_beat_evt) = match (next_time, next_beat_time) {
+      (None, None) => break,
+      (Some(time), None) => (time, false),
+      (None, Some(beat_time)) => (beat_time, true),
+      (Some(time), Some(beat_time)) =>
+        if beat_time <= time {
+          (beat_time, true)
+        } else {
+          (time, false)
+        },
+    };
+    let evt_sample = time * sample_rate + SAMPLE_EPSILON;
+    if evt_sample >= frame_end_sample {
+      break;
+    }
+
+    let evt = if is_beat_evt {
+      unsafe { beat_events.pop_unchecked() }
+    } else {
+      unsafe { events.pop_unchecked() }
+    };
+    let frame_offset = (evt_sample - frame_start_sample).floor().max(0.) as usize;
+    handle_event(evt, frame_offset.min(frame_size - 1));
+  }
+}
+
+static mut TEMPO_MAP: *mut TempoMap = std::ptr::null_mut();
+
+fn tempo_map() -> &'static mut TempoMap {
+  unsafe {
+    if TEMPO_MAP.is_null() {
+      TEMPO_MAP = Box::into_raw(Box::new(TempoMap::constant(120., 0., 0.)));
+    }
+    &mut *TEMPO_MAP
+  }
+}
+
+/// Tempo-mapped alternative to `run`.  Rather than firing everything at or before a
+/// externally-computed current beat, beat-scheduled events are converted to seconds with the
+/// tempo map set via `set_tempo_map` so they fire at the exact sample they fall on, including
+/// during tempo ramps.
+///
+/// Returns the beat at the start of the frame.
+#[no_mangle]
+pub extern "C" fn run_frame(frame_start_time: f64, sample_rate: f64, frame_size: usize) -> f64 {
+  let tempo_map = tempo_map();
+  drain_frame(
+    scheduled_events(),
+    scheduled_beat_events(),
+    tempo_map,
+    frame_start_time,
+    sample_rate,
+    frame_size,
+    handle_event,
+  );
+  tempo_map.beat_at_time(frame_start_time)
+}
+
+static mut TEMPO_CHANGES_BUFFER: *mut Vec<f64> = std::ptr::null_mut();
+
+/// Tempo changes are encoded as `[beat, bpm, ramp]` triples where `ramp` is 1 if the tempo should
+/// ramp linearly from the previous change's tempo and 0 to jump instantly.
+#[no_mangle]
+pub unsafe extern "C" fn alloc_tempo_changes_buffer(count: usize) -> *mut f64 {
+  if !TEMPO_CHANGES_BUFFER.is_null() {
+    let buf = &mut *TEMPO_CHANGES_BUFFER;
+    buf.resize(count * 3, 0.);
+    return buf.as_mut_ptr();
   }
+
+  let mut new_buf = vec![0.; count * 3];
+  let ptr = new_buf.as_mut_ptr();
+  TEMPO_CHANGES_BUFFER = Box::into_raw(Box::new(new_buf));
+
+  ptr
 }
 
+/// Replaces the active tempo map with the tempo changes written to the buffer returned by
+/// `alloc_tempo_changes_buffer`.  The map is anchored so that `anchor_time` is at `anchor_beat`,
+/// keeping the current position continuous when tempo is edited during playback.
+#[no_mangle]
+pub unsafe extern "C" fn set_tempo_map(anchor_beat: f64, anchor_time: f64) {
+  let encoded = if TEMPO_CHANGES_BUFFER.is_null() {
+    &[]
+  } else {
+    (*TEMPO_CHANGES_BUFFER).as_slice()
+  };
+  let changes = encoded
+    .as_chunks::<3>()
+    .0
+    .iter()
+    .map(|&[beat, bpm, ramp]| TempoChange {
+      beat,
+      bpm,
+      ramp: ramp != 0.,
+    })
+    .collect::<Vec<_>>();
+  *tempo_map() = TempoMap::from_tempo_changes(&changes, anchor_beat, anchor_time);
+}
+
+#[no_mangle]
+pub extern "C" fn tempo_map_beat_at_time(time: f64) -> f64 { tempo_map().beat_at_time(time) }
+
+#[no_mangle]
+pub extern "C" fn tempo_map_time_at_beat(beat: f64) -> f64 { tempo_map().time_at_beat(beat) }
+
+#[no_mangle]
+pub extern "C" fn tempo_map_bpm_at_time(time: f64) -> f64 { tempo_map().bpm_at_time(time) }
+
 static mut IDS_BUFFER: *mut Vec<i32> = std::ptr::null_mut();
 
 #[no_mangle]
@@ -234,3 +384,177 @@ pub extern "C" fn cancel_events_by_ids() -> usize {
 
   actually_cancelled_evt_count
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: f64 = 44_100.;
+  const FRAME_SIZE: usize = 128;
+
+  type TestHeap = BinaryHeap<ScheduledEvent, Min, 64>;
+
+  fn evt(time: f64, cb_id: i32) -> ScheduledEvent {
+    ScheduledEvent {
+      time,
+      cb_id,
+      midi_evt: None,
+      event_type_hint: None,
+    }
+  }
+
+  /// Runs frames until `end_time`, returning `(cb_id, absolute sample index)` for every fired event
+  fn run_frames(
+    events: &mut TestHeap,
+    beat_events: &mut TestHeap,
+    tempo_map: &TempoMap,
+    end_time: f64,
+  ) -> Vec<(i32, usize)> {
+    let mut fired = Vec::new();
+    let mut frame_start_sample = 0;
+    while (frame_start_sample as f64) / SAMPLE_RATE < end_time {
+      drain_frame(
+        events,
+        beat_events,
+        tempo_map,
+        frame_start_sample as f64 / SAMPLE_RATE,
+        SAMPLE_RATE,
+        FRAME_SIZE,
+        |evt, offset| fired.push((evt.cb_id, frame_start_sample + offset)),
+      );
+      frame_start_sample += FRAME_SIZE;
+    }
+    fired
+  }
+
+  #[test]
+  fn beat_events_fire_at_exact_samples_during_ramp() {
+    // 60 -> 180 BPM over 8 beats, taking exactly 4 seconds
+    let tempo_map = TempoMap::from_tempo_changes(
+      &[
+        TempoChange {
+          beat: 0.,
+          bpm: 60.,
+          ramp: false,
+        },
+        TempoChange {
+          beat: 8.,
+          bpm: 180.,
+          ramp: true,
+        },
+      ],
+      0.,
+      0.,
+    );
+    let mut events = TestHeap::new();
+    let mut beat_events = TestHeap::new();
+    let beats = (1..=40).map(|i| i as f64 * 0.25).collect::<Vec<_>>();
+    for (i, &beat) in beats.iter().enumerate() {
+      beat_events.push(evt(beat, i as i32 + 1)).unwrap();
+    }
+
+    let fired = run_frames(&mut events, &mut beat_events, &tempo_map, 6.);
+    assert_eq!(fired.len(), beats.len());
+    for (i, &beat) in beats.iter().enumerate() {
+      let expected_sample = (tempo_map.time_at_beat(beat) * SAMPLE_RATE + 1e-6).floor() as usize;
+      assert_eq!(fired[i], (i as i32 + 1, expected_sample), "beat={beat}");
+    }
+    // Intervals between events must shrink while the tempo is rising
+    assert!(fired[1].1 - fired[0].1 > fired[30].1 - fired[29].1);
+  }
+
+  #[test]
+  fn event_just_past_frame_end_fires_in_next_frame() {
+    let tempo_map = TempoMap::constant(120., 0., 0.);
+    let mut events = TestHeap::new();
+    let mut beat_events = TestHeap::new();
+    // Exactly at the start of the second frame and one sample before it
+    let frame_len_beats = FRAME_SIZE as f64 / SAMPLE_RATE * 2.;
+    let sample_len_beats = 1. / SAMPLE_RATE * 2.;
+    beat_events.push(evt(frame_len_beats, 1)).unwrap();
+    beat_events
+      .push(evt(frame_len_beats - sample_len_beats, 2))
+      .unwrap();
+
+    let mut fired = Vec::new();
+    drain_frame(
+      &mut events,
+      &mut beat_events,
+      &tempo_map,
+      0.,
+      SAMPLE_RATE,
+      FRAME_SIZE,
+      |evt, offset| fired.push((evt.cb_id, offset)),
+    );
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].0, 2);
+    assert!(fired[0].1 >= FRAME_SIZE - 2);
+
+    fired.clear();
+    drain_frame(
+      &mut events,
+      &mut beat_events,
+      &tempo_map,
+      FRAME_SIZE as f64 / SAMPLE_RATE,
+      SAMPLE_RATE,
+      FRAME_SIZE,
+      |evt, offset| fired.push((evt.cb_id, offset)),
+    );
+    assert_eq!(fired, vec![(1, 0)]);
+  }
+
+  #[test]
+  fn seconds_and_beat_events_interleave_in_time_order() {
+    // Ramp from 60 to 180 BPM over beats 0-8
+    let tempo_map = TempoMap::from_tempo_changes(
+      &[
+        TempoChange {
+          beat: 0.,
+          bpm: 60.,
+          ramp: false,
+        },
+        TempoChange {
+          beat: 8.,
+          bpm: 180.,
+          ramp: true,
+        },
+      ],
+      0.,
+      0.,
+    );
+    let mut events = TestHeap::new();
+    let mut beat_events = TestHeap::new();
+    // Beat 3 lands at exactly 2 seconds
+    beat_events.push(evt(3., 1)).unwrap();
+    events.push(evt(1.999, 2)).unwrap();
+    events.push(evt(2.001, 3)).unwrap();
+
+    let fired = run_frames(&mut events, &mut beat_events, &tempo_map, 3.);
+    assert_eq!(
+      fired.iter().map(|(cb_id, _)| *cb_id).collect::<Vec<_>>(),
+      vec![2, 1, 3]
+    );
+    assert_eq!(fired[1].1, (2. * SAMPLE_RATE) as usize);
+  }
+
+  #[test]
+  fn late_events_fire_at_frame_start() {
+    let tempo_map = TempoMap::constant(120., 0., 0.);
+    let mut events = TestHeap::new();
+    let mut beat_events = TestHeap::new();
+    events.push(evt(0.25, 1)).unwrap();
+    beat_events.push(evt(1., 2)).unwrap();
+
+    let mut fired = Vec::new();
+    drain_frame(
+      &mut events,
+      &mut beat_events,
+      &tempo_map,
+      1.,
+      SAMPLE_RATE,
+      FRAME_SIZE,
+      |evt, offset| fired.push((evt.cb_id, offset)),
+    );
+    assert_eq!(fired, vec![(1, 0), (2, 0)]);
+  }
+}
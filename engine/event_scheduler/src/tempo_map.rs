@@ -0,0 +1,228 @@
+//! Piecewise map between seconds and beats supporting both instant tempo changes and linear
+//! tempo ramps.
+//!
+//! Within a ramp, tempo changes linearly with time.  That keeps both directions of the conversion
+//! in closed form: beats are the integral of a linear function of time (a quadratic) and
+//! converting back to seconds is solving that quadratic, so there is no accumulated drift no
+//! matter how long a ramp is or how many frames it spans.
+
+const MIN_BPM: f64 = 0.01;
+
+/// Composition-level tempo definition: the tempo becomes `bpm` at `beat`.  If `ramp` is set, the
+/// tempo ramps linearly from the previous change's tempo and arrives at `bpm` at `beat` rather
+/// than jumping there instantly.  `ramp` is ignored for the first change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempoChange {
+  pub beat: f64,
+  pub bpm: f64,
+  pub ramp: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TempoSegment {
+  start_beat: f64,
+  start_time: f64,
+  start_bpm: f64,
+  /// Change in BPM per second.  0 for constant-tempo segments.
+  slope: f64,
+}
+
+impl TempoSegment {
+  /// Beats elapsed `dt` seconds after the start of this segment.  Before the start of the
+  /// segment, the starting tempo is extended backwards as a constant.
+  fn beats_after(&self, dt: f64) -> f64 {
+    if dt <= 0. {
+      return self.start_bpm * dt / 60.;
+    }
+
+    (self.start_bpm * dt + 0.5 * self.slope * dt * dt) / 60.
+  }
+
+  /// Seconds needed to advance `beats` beats from the start of this segment.
+  fn seconds_for(&self, beats: f64) -> f64 {
+    if beats <= 0. {
+      return beats * 60. / self.start_bpm;
+    }
+
+    // Solves `slope/2 * dt^2 + start_bpm * dt - 60 * beats = 0` for `dt`.  This form avoids the
+    // cancellation of the textbook quadratic formula and degrades to `60 * beats / start_bpm`
+    // when `slope` is zero.
+    let discriminant = self.start_bpm * self.start_bpm + 120. * self.slope * beats;
+    120. * beats / (self.start_bpm + discriminant.max(0.).sqrt())
+  }
+
+  fn bpm_after(&self, dt: f64) -> f64 {
+    if dt <= 0. {
+      return self.start_bpm;
+    }
+    (self.start_bpm + self.slope * dt).max(MIN_BPM)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+  /// Sorted by both `start_beat` and `start_time`; never empty
+  segments: Vec<TempoSegment>,
+}
+
+impl TempoMap {
+  pub fn constant(bpm: f64, anchor_beat: f64, anchor_time: f64) -> Self {
+    Self::from_tempo_changes(
+      &[TempoChange {
+        beat: 0.,
+        bpm,
+        ramp: false,
+      }],
+      anchor_beat,
+      anchor_time,
+    )
+  }
+
+  /// Builds a map from composition-level tempo changes anchored such that
+  /// `beat_at_time(anchor_time) == anchor_beat`.  If `changes` is empty, a constant 120 BPM is
+  /// used.
+  pub fn from_tempo_changes(changes: &[TempoChange], anchor_beat: f64, anchor_time: f64) -> Self {
+    let mut changes = changes.to_vec();
+    changes.retain(|c| c.beat.is_finite() && c.bpm.is_finite());
+    if changes.is_empty() {
+      changes.push(TempoChange {
+        beat: 0.,
+        bpm: 120.,
+        ramp: false,
+      });
+    }
+    changes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+    let mut segments: Vec<TempoSegment> = Vec::with_capacity(changes.len());
+    for (i, change) in changes.iter().enumerate() {
+      let bpm = change.bpm.max(MIN_BPM);
+      let Some(prev_change) = i.checked_sub(1).map(|prev_ix| changes[prev_ix]) else {
+        segments.push(TempoSegment {
+          start_beat: change.beat,
+          start_time: 0.,
+          start_bpm: bpm,
+          slope: 0.,
+        });
+        continue;
+      };
+
+      let prev = segments.last_mut().unwrap();
+      let beat_count = change.beat - prev_change.beat;
+      let duration = if change.ramp && beat_count > 0. {
+        // Average tempo across a linear ramp is the mean of its endpoints
+        let duration = beat_count * 120. / (prev.start_bpm + bpm);
+        prev.slope = (bpm - prev.start_bpm) / duration;
+        duration
+      } else {
+        prev.seconds_for(beat_count)
+      };
+
+      let start_time = prev.start_time + duration;
+      segments.push(TempoSegment {
+        start_beat: change.beat,
+        start_time,
+        start_bpm: bpm,
+        slope: 0.,
+      });
+    }
+
+    let mut map = TempoMap { segments };
+    let offset = anchor_time - map.time_at_beat(anchor_beat);
+    for seg in &mut map.segments {
+      seg.start_time += offset;
+    }
+    map
+  }
+
+  fn segment_at_time(&self, time: f64) -> &TempoSegment {
+    let ix = self.segments.partition_point(|seg| seg.start_time <= time);
+    &self.segments[ix.saturating_sub(1)]
+  }
+
+  fn segment_at_beat(&self, beat: f64) -> &TempoSegment {
+    let ix = self.segments.partition_point(|seg| seg.start_beat <= beat);
+    &self.segments[ix.saturating_sub(1)]
+  }
+
+  pub fn beat_at_time(&self, time: f64) -> f64 {
+    let seg = self.segment_at_time(time);
+    seg.start_beat + seg.beats_after(time - seg.start_time)
+  }
+
+  pub fn time_at_beat(&self, beat: f64) -> f64 {
+    let seg = self.segment_at_beat(beat);
+    seg.start_time + seg.seconds_for(beat - seg.start_beat)
+  }
+
+  pub fn bpm_at_time(&self, time: f64) -> f64 {
+    let seg = self.segment_at_time(time);
+    seg.bpm_after(time - seg.start_time)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn change(beat: f64, bpm: f64, ramp: bool) -> TempoChange { TempoChange { beat, bpm, ramp } }
+
+  #[test]
+  fn constant_tempo_round_trips() {
+    let map = TempoMap::constant(120., 4., 10.);
+    assert_eq!(map.beat_at_time(10.), 4.);
+    assert_eq!(map.beat_at_time(11.), 6.);
+    assert_eq!(map.time_at_beat(2.), 9.);
+    assert_eq!(map.bpm_at_time(100.), 120.);
+  }
+
+  #[test]
+  fn step_change() {
+    let map =
+      TempoMap::from_tempo_changes(&[change(0., 60., false), change(4., 240., false)], 0., 0.);
+    assert_eq!(map.time_at_beat(4.), 4.);
+    assert_eq!(map.time_at_beat(8.), 5.);
+    assert_eq!(map.beat_at_time(4.5), 6.);
+    assert_eq!(map.bpm_at_time(3.9), 60.);
+    assert_eq!(map.bpm_at_time(4.), 240.);
+  }
+
+  #[test]
+  fn linear_ramp_is_exact() {
+    // 60 -> 180 BPM over 8 beats: average of 120 BPM means the ramp takes exactly 4 seconds
+    let map =
+      TempoMap::from_tempo_changes(&[change(0., 60., false), change(8., 180., true)], 0., 0.);
+    assert!((map.time_at_beat(8.) - 4.).abs() < 1e-12);
+    assert!((map.bpm_at_time(2.) - 120.).abs() < 1e-12);
+    assert!((map.bpm_at_time(4.) - 180.).abs() < 1e-12);
+    // after the ramp, the tempo stays at the target
+    assert!((map.time_at_beat(11.) - 5.).abs() < 1e-12);
+
+    // integral of 60 + 30t BPM over the first two seconds: (60*2 + 15*4) / 60 = 3 beats
+    assert!((map.beat_at_time(2.) - 3.).abs() < 1e-12);
+    for i in 0..=1000 {
+      let beat = i as f64 * 0.011;
+      assert!((map.beat_at_time(map.time_at_beat(beat)) - beat).abs() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn decelerating_ramp_is_exact() {
+    let map =
+      TempoMap::from_tempo_changes(&[change(0., 180., false), change(8., 60., true)], 0., 0.);
+    assert!((map.time_at_beat(8.) - 4.).abs() < 1e-12);
+    // integral of 180 - 30t BPM over the first two seconds: (360 - 60) / 60 = 5 beats
+    assert!((map.beat_at_time(2.) - 5.).abs() < 1e-12);
+    assert!((map.time_at_beat(5.) - 2.).abs() < 1e-12);
+  }
+
+  #[test]
+  fn anchor_keeps_position_continuous() {
+    let changes = [change(0., 60., false), change(8., 180., true)];
+    let map = TempoMap::from_tempo_changes(&changes, 2., 100.);
+    assert!((map.beat_at_time(100.) - 2.).abs() < 1e-12);
+    // beat 2 of a 60 -> 180 BPM ramp is reached after `sqrt(12) - 2` seconds
+    let beat_2_offset = 12f64.sqrt() - 2.;
+    assert!((map.time_at_beat(8.) - (100. + 4. - beat_2_offset)).abs() < 1e-9);
+    assert!((map.time_at_beat(0.) - (100. - beat_2_offset)).abs() < 1e-9);
+  }
+}
@@ -12,17 +12,72 @@ use rand::prelude::*;
 pub mod sample_recorder;
 
 const FRAME_SIZE: usize = 128;
+pub const MAX_VOICE_COUNT: usize = 16;
+pub const MAX_NOTE_CLOUD_COUNT: usize = 16;
+/// Extra note clouds kept around so that clouds stolen to make room for new notes can fade out
+/// rather than being cut off
+const MAX_FADING_NOTE_CLOUD_COUNT: usize = 4;
+const NOTE_CLOUD_POOL_SIZE: usize = MAX_NOTE_CLOUD_COUNT + MAX_FADING_NOTE_CLOUD_COUNT;
+/// ~10ms at 44.1kHz
+const STOLEN_NOTE_CLOUD_FADE_OUT_SAMPLES: usize = 441;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReadHeadMode {
+  Forward,
+  Reverse,
+  /// Moves forward until the end of the selection and then backwards until the start, repeating
+  PingPong,
+}
+
+impl ReadHeadMode {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => ReadHeadMode::Forward,
+      1 => ReadHeadMode::Reverse,
+      2 => ReadHeadMode::PingPong,
+      _ => panic!("Invalid read head mode: {}", val),
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GrainWindow {
+  /// Mix between a linear slope and a sine determined by `linear_slope_length` and
+  /// `slope_linearity`.  See `Grain::get_volume`.
+  LinearSine,
+  Hann,
+  /// Flat top with raised-cosine tapers on either side.  `linear_slope_length` controls the
+  /// fraction of the grain taken up by the tapers.
+  Tukey,
+  /// Flat top with linear ramps on either side.  `linear_slope_length` controls the fraction of
+  /// the grain taken up by the ramps.
+  Trapezoid,
+}
+
+impl GrainWindow {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => GrainWindow::LinearSine,
+      1 => GrainWindow::Hann,
+      2 => GrainWindow::Tukey,
+      3 => GrainWindow::Trapezoid,
+      _ => panic!("Invalid grain window: {}", val),
+    }
+  }
+}
 
 #[derive(Clone, Copy)]
 pub struct ReverseState {
-  pub grain_is_reversed: bool,
+  /// Probability from 0 to 1 that a newly seeded grain will play its samples backwards
+  pub grain_reverse_probability: f32,
+  /// Current direction of the read head in ping-pong mode
   pub grain_movement_is_reversed: bool,
 }
 
 impl Default for ReverseState {
   fn default() -> Self {
     ReverseState {
-      grain_is_reversed: false,
+      grain_reverse_probability: 0.,
       grain_movement_is_reversed: false,
     }
   }
@@ -45,6 +100,27 @@ pub struct GranularVoice {
   /// The index at which the current grain starts in the waveform buffer, absolute to the buffer
   pub cur_grain_start: f32,
   pub reversed: ReverseState,
+  pub read_head_mode: ReadHeadMode,
+  /// Multiplier applied to the global read head movement speed for this voice
+  pub movement_speed: f32,
+  /// Amount of random scatter applied to the start position of grains as a fraction of the
+  /// selection length
+  pub spread: f32,
+  /// -1 is hard left, 1 is hard right
+  pub pan: f32,
+  /// Position within the selection from 0 to 1 at which this voice's read head starts.  Used to
+  /// distribute voices across the selection so that they don't all play the same spot.
+  pub start_phase: f32,
+  needs_placement: bool,
+  pub window: GrainWindow,
+  /// Pitch shift applied to every grain by resampling its read rate
+  pub pitch_shift_semitones: f32,
+  /// Max amount in semitones by which each grain's pitch is randomly shifted up or down
+  pub pitch_randomization_semitones: f32,
+  /// Additional pitch shift for voices playing a MIDI note relative to the root key
+  pub transpose_semitones: f32,
+  /// If set, no new grains are seeded but existing ones play out
+  pub is_releasing: bool,
   filter: ButterworthFilter,
   filter_cutoff: f32,
   pub grains: Vec<Grain>,
@@ -56,6 +132,17 @@ impl Default for GranularVoice {
     GranularVoice {
       cur_grain_start: 0.0,
       reversed: ReverseState::default(),
+      read_head_mode: ReadHeadMode::Forward,
+      movement_speed: 1.,
+      spread: 0.,
+      pan: 0.,
+      start_phase: 0.,
+      needs_placement: true,
+      window: GrainWindow::LinearSine,
+      pitch_shift_semitones: 0.,
+      pitch_randomization_semitones: 0.,
+      transpose_semitones: 0.,
+      is_releasing: false,
       filter: ButterworthFilter::default(),
       filter_cutoff: 0.,
       grains: Vec::with_capacity(128),
@@ -73,6 +160,15 @@ pub struct Grain {
   pub sample_playback_ratio: f32,
   pub linear_slope_length: f32,
   pub slope_linearity: f32,
+  /// If `true`, this grain reads its samples from end to start
+  pub is_reversed: bool,
+  pub window: GrainWindow,
+  /// Rate at which samples are read from the buffer relative to the grain's playback.  This shifts
+  /// pitch without changing the length of the grain.
+  pub pitch_ratio: f32,
+  /// If set, reads wrap around the end of the buffer rather than being clamped to it.  Used for
+  /// grains reading from the circular live input buffer.
+  pub wraps: bool,
 }
 
 impl Grain {
@@ -99,6 +195,28 @@ impl Grain {
     mix(slope_linearity, linear_slope, sine_slope)
   }
 
+  fn get_window_volume(
+    window: GrainWindow,
+    pos_in_grain: f32,
+    linear_slope_length: f32,
+    slope_linearity: f32,
+  ) -> f32 {
+    match window {
+      GrainWindow::LinearSine =>
+        Self::get_volume(pos_in_grain, linear_slope_length, slope_linearity),
+      GrainWindow::Hann => (pos_in_grain * std::f32::consts::PI).sin().powi(2),
+      GrainWindow::Tukey => {
+        let dist_from_edge = pos_in_grain.min(1. - pos_in_grain) * 2.;
+        if dist_from_edge >= linear_slope_length {
+          return 1.;
+        }
+        0.5 * (1. - (std::f32::consts::PI * dist_from_edge / linear_slope_length).cos())
+      },
+      GrainWindow::Trapezoid =>
+        Self::compute_linear_envelope_volume(pos_in_grain, linear_slope_length),
+    }
+  }
+
   /// Returns `true` is this grain has more samples to play and `false` if it's been fully
   /// consumed
   pub fn tick(&mut self) -> bool {
@@ -106,21 +224,28 @@ impl Grain {
     self.samples_read_so_far < self.len_samples
   }
 
-  pub fn sample(
-    &self,
-    buf: &[f32],
-    is_reversed: bool,
-    linear_slope_length: f32,
-    slope_linearity: f32,
-  ) -> (f32, f32) {
+  pub fn sample(&self, buf: &[f32], linear_slope_length: f32, slope_linearity: f32) -> (f32, f32) {
     let pos_in_grain = self.samples_read_so_far / self.len_samples;
-    let sample_ix = if is_reversed {
-      self.len_samples - self.samples_read_so_far
+    let sample_ix = if self.is_reversed {
+      (self.len_samples - self.samples_read_so_far) * self.pitch_ratio
     } else {
-      self.samples_read_so_far
+      self.samples_read_so_far * self.pitch_ratio
     };
 
-    let gain = Self::get_volume(pos_in_grain, linear_slope_length, slope_linearity);
+    let gain = Self::get_window_volume(
+      self.window,
+      pos_in_grain,
+      linear_slope_length,
+      slope_linearity,
+    );
+    if self.wraps {
+      let read_ix = (self.start_sample_ix + sample_ix).rem_euclid(buf.len() as f32);
+      let base_ix = (read_ix as usize).min(buf.len() - 1);
+      let next_ix = (base_ix + 1) % buf.len();
+      let sample = mix(read_ix.fract(), buf[next_ix], buf[base_ix]);
+      return (gain, sample);
+    }
+
     // `read_interpolated` reads `buf[ix]` and `buf[ix + 1]`, so the max safe index is `len - 2`.
     let read_ix = clamp(0., buf.len() as f32 - 2., self.start_sample_ix + sample_ix);
     let sample = read_interpolated(buf, read_ix);
@@ -128,12 +253,180 @@ impl Grain {
   }
 }
 
+/// State for live mode, where `GranularCtx.waveform` is used as a circular buffer that incoming
+/// audio is continuously written into
+pub struct LiveInput {
+  /// Input samples for the current frame, written by the AWP before each render
+  pub input: [f32; FRAME_SIZE],
+  /// Index in the buffer at which the next input sample will be written.  This is also the
+  /// position of the oldest sample in the buffer.
+  pub write_ix: usize,
+  /// If set, nothing is written to the buffer and grains keep reading what is already in it
+  pub frozen: bool,
+  /// Amount of the output that is mixed back into the buffer along with the input
+  pub feedback: f32,
+  last_output: f32,
+}
+
+impl LiveInput {
+  fn new() -> Self {
+    LiveInput {
+      input: [0.; FRAME_SIZE],
+      write_ix: 0,
+      frozen: false,
+      feedback: 0.,
+      last_output: 0.,
+    }
+  }
+
+  fn record_sample(&mut self, buffer: &mut [f32], sample_ix_in_frame: usize) {
+    if self.frozen {
+      return;
+    }
+
+    // Clamped to keep high feedback amounts from running away
+    let sample = self.input[sample_ix_in_frame] + self.last_output * self.feedback;
+    buffer[self.write_ix] = clamp(-1., 1., sample);
+    self.write_ix = (self.write_ix + 1) % buffer.len();
+  }
+}
+
+/// Write head of the circular live input buffer as seen by the voices reading from it
+#[derive(Clone, Copy)]
+pub struct LiveWriteHead {
+  pub write_ix: usize,
+  /// `false` while the buffer is frozen
+  pub is_advancing: bool,
+}
+
+/// Returns the longest a grain reading from the live input buffer can play for, in output samples,
+/// before it reads across the write head.  `start` is the grain's start relative to the oldest
+/// sample in the buffer and `read_rate` is how many buffer samples it advances per output sample.
+///
+/// The valid region of the buffer runs from the oldest sample to the newest, and both ends move
+/// forward by one sample per output sample unless the buffer is frozen.
+fn max_live_grain_duration(
+  start: f32,
+  buffer_len: f32,
+  read_rate: f32,
+  write_rate: f32,
+  is_reversed: bool,
+) -> f32 {
+  let newest = buffer_len - 1.;
+  if is_reversed {
+    // Reversed grains start at their end and read back towards `start`
+    let until_past_newest = (newest - start) / read_rate;
+    let until_overwritten = if write_rate > 0. {
+      start / write_rate
+    } else {
+      f32::INFINITY
+    };
+    until_past_newest.min(until_overwritten)
+  } else if read_rate > write_rate {
+    (newest - start) / (read_rate - write_rate)
+  } else if read_rate < write_rate {
+    start / (write_rate - read_rate)
+  } else {
+    f32::INFINITY
+  }
+}
+
+/// Grain cloud spawned by a held note in MIDI mode.  It contains a copy of each of the context's
+/// voices transposed relative to the root key.
+///
+/// Clouds are pooled and re-used across notes so that playing notes doesn't allocate on the audio
+/// thread.
+pub struct NoteCloud {
+  pub midi_number: u8,
+  pub voices: Vec<GranularVoice>,
+  /// Set when the cloud was stolen to make room for a new note.  Counts down to zero while the
+  /// cloud's output fades out, at which point it's done.
+  pub fade_out_samples_remaining: Option<usize>,
+}
+
+impl NoteCloud {
+  fn new(template_voices: &[GranularVoice]) -> Self {
+    let mut cloud = NoteCloud {
+      midi_number: 0,
+      voices: Vec::with_capacity(template_voices.len()),
+      fade_out_samples_remaining: None,
+    };
+    cloud.resize_voices(template_voices, 0.);
+    cloud
+  }
+
+  /// Adds or removes voices to match the template, initializing added voices from their template
+  fn resize_voices(&mut self, template_voices: &[GranularVoice], transpose_semitones: f32) {
+    self.voices.truncate(template_voices.len());
+    while self.voices.len() < template_voices.len() {
+      let mut voice = GranularVoice::default();
+      voice.reset_for_note(&template_voices[self.voices.len()], transpose_semitones);
+      self.voices.push(voice);
+    }
+  }
+
+  /// Re-initializes this cloud from the template voices to start playing a new note
+  fn start(&mut self, midi_number: u8, template_voices: &[GranularVoice], root_key: u8) {
+    let transpose_semitones = midi_number as f32 - root_key as f32;
+    self.midi_number = midi_number;
+    self.fade_out_samples_remaining = None;
+    self.resize_voices(template_voices, transpose_semitones);
+    for (voice, template) in self.voices.iter_mut().zip(template_voices) {
+      voice.reset_for_note(template, transpose_semitones);
+    }
+  }
+
+  fn release(&mut self) {
+    for voice in &mut self.voices {
+      voice.is_releasing = true;
+    }
+  }
+
+  fn steal(&mut self) {
+    self.release();
+    self.fade_out_samples_remaining = Some(STOLEN_NOTE_CLOUD_FADE_OUT_SAMPLES);
+  }
+
+  fn next_fade_out_gain(&mut self) -> f32 {
+    match &mut self.fade_out_samples_remaining {
+      None => 1.,
+      Some(remaining) => {
+        *remaining = remaining.saturating_sub(1);
+        *remaining as f32 / STOLEN_NOTE_CLOUD_FADE_OUT_SAMPLES as f32
+      },
+    }
+  }
+
+  /// Released clouds are done once all of their grains have finished playing.  Stolen clouds are
+  /// done once they've faded out.
+  fn is_done(&self) -> bool {
+    self.fade_out_samples_remaining == Some(0)
+      || self
+        .voices
+        .iter()
+        .all(|voice| voice.is_releasing && voice.grains.is_empty())
+  }
+}
+
 pub struct GranularCtx {
   pub waveform: Vec<f32>,
   /// The offset from `cur_grain_start` at which the latest sample will be read
   pub cur_sample_offset: f32,
+  /// Left channel, or the only channel for mono consumers
   pub rendered_output: [f32; FRAME_SIZE],
-  pub voice: GranularVoice,
+  pub rendered_output_right: [f32; FRAME_SIZE],
+  pub voices: Vec<GranularVoice>,
+  /// If set, `voices` act as a template for note clouds spawned by MIDI notes rather than playing
+  /// on their own
+  pub midi_mode: bool,
+  /// MIDI number which plays grains at their un-transposed pitch
+  pub root_key: u8,
+  /// Clouds for held or releasing notes, oldest first
+  pub note_clouds: Vec<NoteCloud>,
+  /// Unused clouds ready to be started by new notes.  Filled when MIDI mode is enabled.
+  free_note_clouds: Vec<NoteCloud>,
+  /// Set when in live mode
+  pub live_input: Option<LiveInput>,
   pub last_start_sample_ix: f32,
   pub last_end_sample_ix: f32,
   pub last_grain_size: f32,
@@ -145,7 +438,13 @@ impl Default for GranularCtx {
       waveform: Vec::new(),
       cur_sample_offset: 0.0,
       rendered_output: [0.0; FRAME_SIZE],
-      voice: GranularVoice::default(),
+      rendered_output_right: [0.0; FRAME_SIZE],
+      voices: vec![GranularVoice::default()],
+      midi_mode: false,
+      root_key: 60,
+      note_clouds: Vec::new(),
+      free_note_clouds: Vec::new(),
+      live_input: None,
       last_start_sample_ix: -10.0,
       last_end_sample_ix: -10.0,
       last_grain_size: 800.,
@@ -165,7 +464,31 @@ fn normalize_gain(samples_and_gains: &[(f32, f32)], total_gain: f32) -> f32 {
   })
 }
 
+/// Returns the start phase for the voice at `voice_ix`.  Phases follow the golden ratio sequence
+/// so that they're spread evenly for any voice count and voices added by growing the voice count
+/// land in the gaps between the existing ones rather than on top of them.
+fn voice_start_phase(voice_ix: usize) -> f32 {
+  const GOLDEN_RATIO_CONJUGATE: f32 = 0.618_034;
+  (voice_ix as f32 * GOLDEN_RATIO_CONJUGATE).fract()
+}
+
 impl GranularVoice {
+  /// Copies `template`'s settings into this voice and resets its playback state so that it starts
+  /// fresh playing a note.  This voice's grain buffer is kept to avoid allocating.
+  fn reset_for_note(&mut self, template: &GranularVoice, transpose_semitones: f32) {
+    let mut grains = std::mem::take(&mut self.grains);
+    grains.clear();
+    *self = GranularVoice {
+      transpose_semitones,
+      needs_placement: true,
+      is_releasing: false,
+      grains,
+      // Seed the first grain right away rather than waiting for a full grain interval
+      samples_since_last_grain: f32::INFINITY,
+      ..template.clone()
+    };
+  }
+
   fn move_read_head(
     &mut self,
     selection_start_sample_ix: f32,
@@ -173,19 +496,58 @@ impl GranularVoice {
     grain_size: f32,
     movement_samples_per_sample: f32,
   ) {
+    // The last position at which a grain can start without overflowing the selection
+    let max_grain_start = (selection_end_sample_ix - grain_size).max(selection_start_sample_ix);
+    if self.needs_placement {
+      self.needs_placement = false;
+      self.cur_grain_start = selection_start_sample_ix
+        + self.start_phase * (max_grain_start - selection_start_sample_ix);
+    }
+
     self.cur_grain_start = clamp(
       selection_start_sample_ix,
       selection_end_sample_ix,
       self.cur_grain_start,
     );
-    // TODO: Handle reverse
-    self.cur_grain_start += movement_samples_per_sample;
-
-    if self.cur_grain_start + grain_size > selection_end_sample_ix {
-      // Grain would overflow the selection; wrap it back around to the start.  When the grain is
-      // as large as (or larger than) the selection there's no room to move, so pin to the start;
-      // otherwise `% wrap_range` would be a modulo by zero/negative and poison the read head with
-      // NaN forever.
+    let movement = movement_samples_per_sample * self.movement_speed;
+
+    if self.read_head_mode == ReadHeadMode::PingPong {
+      if self.reversed.grain_movement_is_reversed {
+        self.cur_grain_start -= movement;
+      } else {
+        self.cur_grain_start += movement;
+      }
+
+      // Bounce off the ends of the selection, reflecting any overshoot back into it
+      if self.cur_grain_start > max_grain_start {
+        self.cur_grain_start = max_grain_start - (self.cur_grain_start - max_grain_start);
+        self.reversed.grain_movement_is_reversed = true;
+      } else if self.cur_grain_start < selection_start_sample_ix {
+        self.cur_grain_start =
+          selection_start_sample_ix + (selection_start_sample_ix - self.cur_grain_start);
+        self.reversed.grain_movement_is_reversed = false;
+      }
+      self.cur_grain_start = clamp(
+        selection_start_sample_ix,
+        max_grain_start,
+        self.cur_grain_start,
+      );
+      return;
+    }
+
+    if self.read_head_mode == ReadHeadMode::Reverse {
+      self.cur_grain_start -= movement;
+    } else {
+      self.cur_grain_start += movement;
+    }
+
+    if self.cur_grain_start + grain_size > selection_end_sample_ix
+      || self.cur_grain_start < selection_start_sample_ix
+    {
+      // Grain would overflow the selection; wrap it back around to the other end.  When the grain
+      // is as large as (or larger than) the selection there's no room to move, so pin to the
+      // start; otherwise `% wrap_range` would be a modulo by zero/negative and poison the read
+      // head with NaN forever.
       let wrap_range = (selection_end_sample_ix - selection_start_sample_ix) - grain_size;
       self.cur_grain_start = if wrap_range <= 0. {
         selection_start_sample_ix
@@ -204,6 +566,7 @@ impl GranularVoice {
     sample_playback_ratio: f32,
     grain_start_randomness_samples: f32,
     sample_buffer_len: usize,
+    live_write_head: Option<LiveWriteHead>,
   ) {
     let start_offset = if grain_start_randomness_samples == 0. {
       0.
@@ -214,17 +577,58 @@ impl GranularVoice {
       )
     };
 
+    let is_reversed = self.reversed.grain_reverse_probability > 0.
+      && common::rng().gen::<f32>() < self.reversed.grain_reverse_probability;
+
+    let pitch_randomization = self.pitch_randomization_semitones.abs();
+    let pitch_offset = if pitch_randomization == 0. {
+      0.
+    } else {
+      common::rng().gen_range(-pitch_randomization, pitch_randomization)
+    };
+    let semitones = self.pitch_shift_semitones + self.transpose_semitones + pitch_offset;
+    let pitch_ratio = clamp(0.01, 100., 2f32.powf(semitones / 12.));
+    let sample_playback_ratio = clamp(0.001, 1000., sample_playback_ratio);
+
+    let start = clamp(
+      0.,
+      (sample_buffer_len - 1) as f32,
+      self.cur_grain_start + start_offset,
+    );
+    let (start_sample_ix, len_samples) = match live_write_head {
+      // For the circular live input buffer, positions are relative to the oldest sample which is
+      // the one at the write head.  Grains are shortened so that they don't read across the write
+      // head, which would play a discontinuity between the newest and oldest samples.
+      Some(LiveWriteHead {
+        write_ix,
+        is_advancing,
+      }) => {
+        let max_duration = max_live_grain_duration(
+          start,
+          sample_buffer_len as f32,
+          sample_playback_ratio * pitch_ratio,
+          if is_advancing { 1. } else { 0. },
+          is_reversed,
+        );
+        (
+          write_ix as f32 + start,
+          grain_size.min(max_duration * sample_playback_ratio),
+        )
+      },
+      None => (start, grain_size),
+    };
+
     self.grains.push(Grain {
-      len_samples: grain_size,
-      start_sample_ix: clamp(
-        0.,
-        (sample_buffer_len - 1) as f32,
-        self.cur_grain_start + start_offset,
-      ),
+      len_samples,
+      start_sample_ix,
       samples_read_so_far: 0.,
-      sample_playback_ratio: clamp(0.001, 1000., sample_playback_ratio),
+      sample_playback_ratio,
       linear_slope_length,
       slope_linearity,
+      is_reversed,
+      window: self.window,
+      pitch_ratio,
+      wraps: live_write_head.is_some(),
     });
   }
 
@@ -237,19 +641,28 @@ impl GranularVoice {
     sample_playback_ratio: f32,
     grain_start_randomness_samples: f32,
     sample_buffer_len: usize,
+    live_write_head: Option<LiveWriteHead>,
   ) {
-    if sample_playback_ratio <= 0.05 {
+    if sample_playback_ratio <= 0.05 || self.is_releasing {
       return;
     }
 
-    self.samples_since_last_grain += 1.;
+    if self.samples_since_last_grain.is_finite() {
+      self.samples_since_last_grain += 1.;
+    } else {
+      // Voices started with an infinite counter seed a single grain immediately and then pick up
+      // the regular grain spacing
+      self.samples_since_last_grain = samples_between_grains;
+    }
     if self.grains.len() >= scratch().len() {
       // Can't exceed the ridiculous max grain count
       return;
     }
 
     if self.samples_since_last_grain >= samples_between_grains {
-      self.samples_since_last_grain -= samples_between_grains;
+      // Clamped so that shrinking the grain spacing doesn't cause a burst of grains
+      self.samples_since_last_grain =
+        (self.samples_since_last_grain - samples_between_grains).min(samples_between_grains);
       self.seed_grain(
         grain_size,
         linear_slope_length,
@@ -257,6 +670,7 @@ impl GranularVoice {
         sample_playback_ratio,
         grain_start_randomness_samples,
         sample_buffer_len,
+        live_write_head,
       );
     }
   }
@@ -291,6 +705,8 @@ impl GranularVoice {
     movement_samples_per_sample: f32,
     sample_speed_ratio: f32,
     grain_start_randomness_samples: f32,
+    // Set when reading from the circular live input buffer
+    live_write_head: Option<LiveWriteHead>,
   ) -> f32 {
     self.move_read_head(
       selection_start_sample_ix,
@@ -299,14 +715,16 @@ impl GranularVoice {
       movement_samples_per_sample,
     );
 
+    let selection_len = selection_end_sample_ix - selection_start_sample_ix;
     self.maybe_seed_new_grain(
       samples_between_grains,
       grain_size,
       linear_slope_length,
       slope_linearity,
       sample_speed_ratio,
-      grain_start_randomness_samples,
+      grain_start_randomness_samples + self.spread * selection_len,
       waveform.len(),
+      live_write_head,
     );
 
     self.tick_grains();
@@ -315,12 +733,7 @@ impl GranularVoice {
     let mut total_gain = 0.;
     let mut active_grain_count = 0;
     self.grains.iter().for_each(|grain| {
-      let (gain, sample) = grain.sample(
-        waveform,
-        self.reversed.grain_is_reversed,
-        linear_slope_length,
-        slope_linearity,
-      );
+      let (gain, sample) = grain.sample(waveform, linear_slope_length, slope_linearity);
       total_gain += gain;
       samples_and_gains[active_grain_count] = (gain, sample);
       active_grain_count += 1;
@@ -344,6 +757,8 @@ impl GranularVoice {
 }
 
 impl GranularCtx {
+  /// Returns the `(left, right)` output for one sample, summing all voices panned to their
+  /// positions.
   pub fn get_sample(
     &mut self,
     selection_start_sample_ix: f32,
@@ -356,21 +771,169 @@ impl GranularCtx {
     movement_samples_per_sample: f32,
     sample_speed_ratio: f32,
     grain_start_randomness_samples: f32,
-  ) -> f32 {
-    let sample = self.voice.update_and_get_sample(
-      &self.waveform,
-      selection_start_sample_ix,
-      selection_end_sample_ix,
-      grain_size,
-      filter_cutoff,
-      linear_slope_length,
-      slope_linearity,
-      samples_between_grains,
-      movement_samples_per_sample,
-      sample_speed_ratio,
-      grain_start_randomness_samples,
-    );
-    sample * 0.5
+  ) -> (f32, f32) {
+    // Scale down by the square root of the voice count since voices are mostly uncorrelated
+    let voice_gain = 0.5 / (self.voices.len() as f32).sqrt();
+    let live_write_head = self.live_input.as_ref().map(|live_input| LiveWriteHead {
+      write_ix: live_input.write_ix,
+      is_advancing: !live_input.frozen,
+    });
+    let render_voice = |voice: &mut GranularVoice, waveform: &[f32]| -> (f32, f32) {
+      let sample = voice.update_and_get_sample(
+        waveform,
+        selection_start_sample_ix,
+        selection_end_sample_ix,
+        grain_size,
+        filter_cutoff,
+        linear_slope_length,
+        slope_linearity,
+        samples_between_grains,
+        movement_samples_per_sample,
+        sample_speed_ratio,
+        grain_start_randomness_samples,
+        live_write_head,
+      ) * voice_gain;
+
+      // Balance-style panning so that a centered voice is at full level in both channels
+      let pan = clamp(-1., 1., voice.pan);
+      (sample * (1. - pan).min(1.), sample * (1. + pan).min(1.))
+    };
+
+    let (mut left, mut right) = (0., 0.);
+    if self.midi_mode {
+      for cloud in &mut self.note_clouds {
+        let gain = cloud.next_fade_out_gain();
+        for voice in &mut cloud.voices {
+          let (l, r) = render_voice(voice, &self.waveform);
+          left += l * gain;
+          right += r * gain;
+        }
+      }
+
+      // Finished clouds go back to the pool.  Order is preserved so that the oldest clouds are the
+      // first to be stolen.
+      let mut cloud_ix = 0;
+      while cloud_ix < self.note_clouds.len() {
+        if self.note_clouds[cloud_ix].is_done() {
+          let cloud = self.note_clouds.remove(cloud_ix);
+          self.free_note_clouds.push(cloud);
+        } else {
+          cloud_ix += 1;
+        }
+      }
+    } else {
+      for voice in &mut self.voices {
+        let (l, r) = render_voice(voice, &self.waveform);
+        left += l;
+        right += r;
+      }
+    }
+    (left, right)
+  }
+
+  /// Iterates over the free-running voices as well as the voices of all note clouds
+  fn all_voices_mut(&mut self) -> impl Iterator<Item = &mut GranularVoice> {
+    self.voices.iter_mut().chain(
+      self
+        .note_clouds
+        .iter_mut()
+        .flat_map(|cloud| cloud.voices.iter_mut()),
+    )
+  }
+
+  pub fn note_on(&mut self, midi_number: u8) {
+    if !self.midi_mode {
+      return;
+    }
+
+    self.note_off(midi_number);
+
+    // The oldest cloud still playing is faded out to make room for the new note
+    let mut playing_clouds = self
+      .note_clouds
+      .iter_mut()
+      .filter(|cloud| cloud.fade_out_samples_remaining.is_none());
+    if let Some(oldest_cloud) = playing_clouds.next() {
+      if playing_clouds.count() + 1 >= MAX_NOTE_CLOUD_COUNT {
+        oldest_cloud.steal();
+      }
+    }
+
+    let mut cloud = match self.free_note_clouds.pop() {
+      Some(cloud) => cloud,
+      // Only happens if MIDI mode was enabled without filling the pool
+      None if self.note_clouds.is_empty() => NoteCloud::new(&self.voices),
+      // Every pooled cloud is in use, including ones still fading out after being stolen, so the
+      // oldest one is cut off
+      None => self.note_clouds.remove(0),
+    };
+    cloud.start(midi_number, &self.voices, self.root_key);
+    self.note_clouds.push(cloud);
+  }
+
+  /// Makes sure that enough note clouds are allocated to play `MAX_NOTE_CLOUD_COUNT` notes plus
+  /// fading stolen ones in MIDI mode and that every cloud has one voice per template voice.  Called
+  /// when MIDI mode or the voice count changes so that `note_on` doesn't need to allocate.
+  fn resize_note_cloud_pool(&mut self) {
+    if !self.midi_mode {
+      self.note_clouds.clear();
+      self.free_note_clouds.clear();
+      return;
+    }
+
+    // Template voices don't play in MIDI mode.  Their grains are cleared so that copying them into
+    // clouds doesn't allocate.
+    for voice in &mut self.voices {
+      voice.grains.clear();
+    }
+
+    for cloud in &mut self.note_clouds {
+      let transpose_semitones = cloud.midi_number as f32 - self.root_key as f32;
+      cloud.resize_voices(&self.voices, transpose_semitones);
+    }
+    for cloud in &mut self.free_note_clouds {
+      cloud.resize_voices(&self.voices, 0.);
+    }
+
+    let cloud_count = self.note_clouds.len() + self.free_note_clouds.len();
+    self
+      .free_note_clouds
+      .reserve(NOTE_CLOUD_POOL_SIZE.saturating_sub(self.free_note_clouds.len()));
+    for _ in cloud_count..NOTE_CLOUD_POOL_SIZE {
+      self.free_note_clouds.push(NoteCloud::new(&self.voices));
+    }
+    self
+      .note_clouds
+      .reserve(NOTE_CLOUD_POOL_SIZE.saturating_sub(self.note_clouds.len()));
+  }
+
+  pub fn note_off(&mut self, midi_number: u8) {
+    for cloud in &mut self.note_clouds {
+      if cloud.midi_number == midi_number {
+        cloud.release();
+      }
+    }
+  }
+
+  pub fn set_voice_count(&mut self, voice_count: usize) {
+    let voice_count = voice_count.clamp(1, MAX_VOICE_COUNT);
+    if voice_count == self.voices.len() {
+      return;
+    }
+
+    self.voices.truncate(voice_count);
+    while self.voices.len() < voice_count {
+      let start_phase = voice_start_phase(self.voices.len());
+      let first_voice = &self.voices[0];
+      self.voices.push(GranularVoice {
+        start_phase,
+        window: first_voice.window,
+        pitch_shift_semitones: first_voice.pitch_shift_semitones,
+        pitch_randomization_semitones: first_voice.pitch_randomization_semitones,
+        ..Default::default()
+      });
+    }
+    self.resize_note_cloud_pool();
   }
 }
 
@@ -385,6 +948,7 @@ pub fn create_granular_instance() -> *mut GranularCtx {
 #[no_mangle]
 pub fn get_granular_waveform_ptr(ctx: *mut GranularCtx, new_waveform_len: usize) -> *mut f32 {
   unsafe {
+    (*ctx).live_input = None;
     (*ctx).waveform = Vec::with_capacity(new_waveform_len);
     (*ctx).waveform.set_len(new_waveform_len);
     (*ctx).waveform.as_mut_ptr()
@@ -409,6 +973,7 @@ pub fn render_granular(
   // `read_interpolated` needs at least 2 samples; anything shorter is a degenerate waveform.
   if ctx.waveform.len() < 2 {
     ctx.rendered_output = [0.; FRAME_SIZE];
+    ctx.rendered_output_right = [0.; FRAME_SIZE];
     return ctx.rendered_output.as_ptr();
   }
 
@@ -454,7 +1019,11 @@ pub fn render_granular(
   let grain_size = ctx.last_grain_size.max(1.);
 
   for i in 0..FRAME_SIZE {
-    let sample = ctx.get_sample(
+    if let Some(live_input) = &mut ctx.live_input {
+      live_input.record_sample(&mut ctx.waveform, i);
+    }
+
+    let (left, right) = ctx.get_sample(
       selection_start,
       selection_end,
       grain_size,
@@ -466,12 +1035,138 @@ pub fn render_granular(
       sample_speed_ratio,
       200., // grain start randomness; no UI yet, see followups doc
     );
-    ctx.rendered_output[i] = sample;
+    ctx.rendered_output[i] = left;
+    ctx.rendered_output_right[i] = right;
+    if let Some(live_input) = &mut ctx.live_input {
+      live_input.last_output = (left + right) * 0.5;
+    }
   }
 
   ctx.rendered_output.as_ptr()
 }
 
+/// Returns a pointer to the right channel of the most recently rendered frame.  The pointer
+/// returned by `render_granular` is the left channel.
+#[no_mangle]
+pub unsafe fn granular_get_right_output_ptr(ctx: *mut GranularCtx) -> *const f32 {
+  (*ctx).rendered_output_right.as_ptr()
+}
+
+/// Switches to live mode, replacing the waveform with a silent circular buffer of
+/// `buffer_len_samples` samples that input is recorded into.  Setting a new waveform with
+/// `get_granular_waveform_ptr` switches back to playing that waveform.
+#[no_mangle]
+pub unsafe fn granular_enable_live_input(ctx: *mut GranularCtx, buffer_len_samples: usize) {
+  let ctx = &mut *ctx;
+  ctx.waveform = vec![0.; buffer_len_samples.max(2)];
+  ctx.live_input = Some(LiveInput::new());
+  for voice in ctx.all_voices_mut() {
+    voice.grains.clear();
+  }
+}
+
+/// Returns a pointer to the buffer that input samples for the next frame should be written to
+/// before calling `render_granular`, or null if not in live mode.
+#[no_mangle]
+pub unsafe fn granular_get_live_input_buf_ptr(ctx: *mut GranularCtx) -> *mut f32 {
+  match &mut (*ctx).live_input {
+    Some(live_input) => live_input.input.as_mut_ptr(),
+    None => std::ptr::null_mut(),
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_live_input_frozen(ctx: *mut GranularCtx, frozen: bool) {
+  if let Some(live_input) = &mut (*ctx).live_input {
+    live_input.frozen = frozen;
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_live_input_feedback(ctx: *mut GranularCtx, feedback: f32) {
+  if let Some(live_input) = &mut (*ctx).live_input {
+    live_input.feedback = clamp(0., 0.99, feedback);
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_voice_count(ctx: *mut GranularCtx, voice_count: usize) {
+  (*ctx).set_voice_count(voice_count);
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_voice_params(
+  ctx: *mut GranularCtx,
+  voice_ix: usize,
+  movement_speed: f32,
+  spread: f32,
+  pan: f32,
+  read_head_mode: u32,
+  grain_reverse_probability: f32,
+) {
+  let ctx = &mut *ctx;
+  let read_head_mode = ReadHeadMode::from_u32(read_head_mode);
+  let cloud_voices = ctx
+    .note_clouds
+    .iter_mut()
+    .filter_map(|cloud| cloud.voices.get_mut(voice_ix));
+  for voice in ctx.voices.get_mut(voice_ix).into_iter().chain(cloud_voices) {
+    voice.movement_speed = movement_speed;
+    voice.spread = clamp(0., 1., spread);
+    voice.pan = clamp(-1., 1., pan);
+    voice.read_head_mode = read_head_mode;
+    voice.reversed.grain_reverse_probability = clamp(0., 1., grain_reverse_probability);
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_pitch(
+  ctx: *mut GranularCtx,
+  pitch_shift_semitones: f32,
+  pitch_randomization_semitones: f32,
+) {
+  for voice in (*ctx).all_voices_mut() {
+    voice.pitch_shift_semitones = pitch_shift_semitones;
+    voice.pitch_randomization_semitones = pitch_randomization_semitones;
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_window(ctx: *mut GranularCtx, window: u32) {
+  let window = GrainWindow::from_u32(window);
+  for voice in (*ctx).all_voices_mut() {
+    voice.window = window;
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_set_midi_mode(ctx: *mut GranularCtx, enabled: bool, root_key: u8) {
+  let ctx = &mut *ctx;
+  let was_enabled = ctx.midi_mode;
+  ctx.midi_mode = enabled;
+  ctx.root_key = root_key.min(127);
+  if enabled != was_enabled {
+    ctx.resize_note_cloud_pool();
+  }
+}
+
+#[no_mangle]
+pub unsafe fn granular_note_on(ctx: *mut GranularCtx, midi_number: u8) {
+  (*ctx).note_on(midi_number);
+}
+
+#[no_mangle]
+pub unsafe fn granular_note_off(ctx: *mut GranularCtx, midi_number: u8) {
+  (*ctx).note_off(midi_number);
+}
+
+#[no_mangle]
+pub unsafe fn granular_release_all_notes(ctx: *mut GranularCtx) {
+  for cloud in &mut (*ctx).note_clouds {
+    cloud.release();
+  }
+}
+
 #[test]
 fn crossfade_correctness() {
   // setting `linear_slope_length` to 0 should cause no crossfade to be applied
@@ -481,3 +1176,351 @@ fn crossfade_correctness() {
     assert_eq!(volume, 1.);
   }
 }
+
+#[test]
+fn reverse_read_head_moves_backwards_and_wraps() {
+  let mut voice = GranularVoice {
+    read_head_mode: ReadHeadMode::Reverse,
+    start_phase: 0.5,
+    ..Default::default()
+  };
+  // Selection of 1000 samples with 200 sample grains leaves room for starts in 100..900
+  voice.move_read_head(100., 1100., 200., 10.);
+  assert_eq!(voice.cur_grain_start, 490.);
+  voice.move_read_head(100., 1100., 200., 10.);
+  assert_eq!(voice.cur_grain_start, 480.);
+
+  for _ in 0..50 {
+    voice.move_read_head(100., 1100., 200., 10.);
+    assert!(voice.cur_grain_start >= 100. && voice.cur_grain_start + 200. <= 1100.);
+  }
+  // Wrapped around from the start of the selection to the end
+  assert_eq!(voice.cur_grain_start, 780.);
+}
+
+#[test]
+fn ping_pong_read_head_bounces() {
+  let mut voice = GranularVoice {
+    read_head_mode: ReadHeadMode::PingPong,
+    ..Default::default()
+  };
+  let mut positions = Vec::new();
+  for _ in 0..20 {
+    voice.move_read_head(0., 300., 100., 30.);
+    positions.push(voice.cur_grain_start);
+  }
+  assert_eq!(&positions[..10], &[
+    30., 60., 90., 120., 150., 180., 190., 160., 130., 100.
+  ]);
+  assert!(positions.iter().all(|&pos| (0. ..=200.).contains(&pos)));
+  // Bounces back forwards off the start of the selection
+  assert_eq!(&positions[11..14], &[40., 10., 20.]);
+  assert!(!voice.reversed.grain_movement_is_reversed);
+}
+
+#[test]
+fn reversed_grain_reads_backwards() {
+  let buf: Vec<f32> = (0..100).map(|i| i as f32).collect();
+  let mut grain = Grain {
+    len_samples: 10.,
+    start_sample_ix: 20.,
+    samples_read_so_far: 0.,
+    sample_playback_ratio: 1.,
+    linear_slope_length: 0.,
+    slope_linearity: 1.,
+    is_reversed: true,
+    window: GrainWindow::LinearSine,
+    pitch_ratio: 1.,
+    wraps: false,
+  };
+  let mut samples = Vec::new();
+  for _ in 0..4 {
+    grain.tick();
+    samples.push(grain.sample(&buf, 0., 1.).1);
+  }
+  assert_eq!(samples, vec![29., 28., 27., 26.]);
+}
+
+#[test]
+fn voices_are_distributed_and_panned() {
+  let mut ctx = GranularCtx {
+    waveform: vec![0.5; 10_000],
+    ..Default::default()
+  };
+  ctx.set_voice_count(2);
+  ctx.set_voice_count(4);
+  let mut phases = ctx.voices.iter().map(|v| v.start_phase).collect::<Vec<_>>();
+  assert_eq!(phases[..2], [0., voice_start_phase(1)]);
+  // Growing the voice count places the new voices between the existing ones
+  phases.sort_by(|a, b| a.total_cmp(b));
+  assert!(phases.windows(2).all(|w| w[1] - w[0] > 0.2), "{phases:?}");
+  for voice in &mut ctx.voices {
+    voice.pan = -1.;
+  }
+
+  let mut right_sum = 0.;
+  let mut left_sum = 0.;
+  for _ in 0..2000 {
+    let (left, right) = ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 50., 1., 1., 0.);
+    left_sum += left.abs();
+    right_sum += right.abs();
+  }
+  assert!(left_sum > 0.);
+  assert_eq!(right_sum, 0.);
+}
+
+#[test]
+fn pitch_ratio_changes_read_rate_but_not_length() {
+  let buf: Vec<f32> = (0..100).map(|i| i as f32).collect();
+  let mut grain = Grain {
+    len_samples: 10.,
+    start_sample_ix: 20.,
+    samples_read_so_far: 0.,
+    sample_playback_ratio: 1.,
+    linear_slope_length: 0.,
+    slope_linearity: 1.,
+    is_reversed: false,
+    window: GrainWindow::LinearSine,
+    pitch_ratio: 2.,
+    wraps: false,
+  };
+  let mut samples = Vec::new();
+  while grain.tick() {
+    samples.push(grain.sample(&buf, 0., 1.).1);
+  }
+  assert_eq!(samples, vec![22., 24., 26., 28., 30., 32., 34., 36., 38.]);
+}
+
+#[test]
+fn grain_windows() {
+  for window in [
+    GrainWindow::Hann,
+    GrainWindow::Tukey,
+    GrainWindow::Trapezoid,
+  ] {
+    assert!(Grain::get_window_volume(window, 0., 0.5, 0.).abs() < 1e-6);
+    assert!((Grain::get_window_volume(window, 0.5, 0.5, 0.) - 1.).abs() < 1e-6);
+    let quarter = Grain::get_window_volume(window, 0.125, 0.5, 0.);
+    let mirrored = Grain::get_window_volume(window, 0.875, 0.5, 0.);
+    assert!((quarter - mirrored).abs() < 1e-5);
+  }
+
+  assert!((Grain::get_window_volume(GrainWindow::Hann, 0.25, 0., 0.) - 0.5).abs() < 1e-6);
+  // Tapers take up the outer half of the grain, so the middle half is flat
+  assert_eq!(
+    Grain::get_window_volume(GrainWindow::Tukey, 0.3, 0.5, 0.),
+    1.
+  );
+  assert!((Grain::get_window_volume(GrainWindow::Tukey, 0.125, 0.5, 0.) - 0.5).abs() < 1e-6);
+  assert_eq!(
+    Grain::get_window_volume(GrainWindow::Trapezoid, 0.125, 0.5, 0.),
+    0.5
+  );
+  // Zero-length tapers are rectangular
+  assert_eq!(
+    Grain::get_window_volume(GrainWindow::Tukey, 0.01, 0., 0.),
+    1.
+  );
+}
+
+#[test]
+fn midi_notes_spawn_transposed_clouds() {
+  let mut ctx = GranularCtx {
+    waveform: vec![0.5; 10_000],
+    midi_mode: true,
+    root_key: 60,
+    ..Default::default()
+  };
+  ctx.set_voice_count(2);
+
+  // Nothing plays without held notes in MIDI mode
+  let (left, _) = ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  assert_eq!(left, 0.);
+
+  ctx.note_on(72);
+  ctx.note_on(55);
+  assert_eq!(ctx.note_clouds.len(), 2);
+  ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  let octave_up_grain = &ctx.note_clouds[0].voices[0].grains[0];
+  assert!((octave_up_grain.pitch_ratio - 2.).abs() < 1e-6);
+  assert_eq!(ctx.note_clouds[1].voices[1].transpose_semitones, -5.);
+
+  ctx.note_off(72);
+  for _ in 0..400 {
+    ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  }
+  // Released cloud is removed once its grains have finished
+  assert_eq!(ctx.note_clouds.len(), 1);
+  assert_eq!(ctx.note_clouds[0].midi_number, 55);
+}
+
+#[test]
+fn note_on_seeds_a_single_grain_immediately() {
+  let mut ctx = GranularCtx {
+    waveform: vec![0.5; 10_000],
+    ..Default::default()
+  };
+  unsafe { granular_set_midi_mode(&mut ctx, true, 60) };
+
+  ctx.note_on(60);
+  ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  assert_eq!(ctx.note_clouds[0].voices[0].grains.len(), 1);
+  for _ in 0..99 {
+    ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  }
+  assert_eq!(ctx.note_clouds[0].voices[0].grains.len(), 1);
+  ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  assert_eq!(ctx.note_clouds[0].voices[0].grains.len(), 2);
+}
+
+#[test]
+fn stolen_note_clouds_fade_out() {
+  let mut ctx = GranularCtx {
+    waveform: vec![0.5; 10_000],
+    ..Default::default()
+  };
+  unsafe { granular_set_midi_mode(&mut ctx, true, 60) };
+  assert_eq!(ctx.free_note_clouds.len(), NOTE_CLOUD_POOL_SIZE);
+
+  for midi_number in 0..=MAX_NOTE_CLOUD_COUNT as u8 {
+    ctx.note_on(midi_number);
+  }
+  // The oldest cloud keeps playing while fading out rather than being cut off
+  assert_eq!(ctx.note_clouds.len(), MAX_NOTE_CLOUD_COUNT + 1);
+  assert_eq!(ctx.note_clouds[0].midi_number, 0);
+  assert_eq!(
+    ctx.note_clouds[0].fade_out_samples_remaining,
+    Some(STOLEN_NOTE_CLOUD_FADE_OUT_SAMPLES)
+  );
+  assert!(ctx.note_clouds[1..]
+    .iter()
+    .all(|cloud| cloud.fade_out_samples_remaining.is_none()));
+
+  for _ in 0..STOLEN_NOTE_CLOUD_FADE_OUT_SAMPLES {
+    ctx.get_sample(0., 8000., 400., 0.5, 1., 0., 100., 1., 1., 0.);
+  }
+  assert_eq!(ctx.note_clouds.len(), MAX_NOTE_CLOUD_COUNT);
+  assert_eq!(ctx.note_clouds[0].midi_number, 1);
+  // Finished clouds are returned to the pool
+  assert_eq!(
+    ctx.note_clouds.len() + ctx.free_note_clouds.len(),
+    NOTE_CLOUD_POOL_SIZE
+  );
+}
+
+#[test]
+fn live_input_records_circularly_and_freezes() {
+  let mut ctx = GranularCtx::default();
+  unsafe { granular_enable_live_input(&mut ctx, 300) };
+
+  let render_frame = |ctx: &mut GranularCtx, frame_ix: usize| {
+    let live_input = ctx.live_input.as_mut().unwrap();
+    for (i, sample) in live_input.input.iter_mut().enumerate() {
+      *sample = (frame_ix * FRAME_SIZE + i) as f32 / 1000.;
+    }
+    render_granular(ctx, 0., 299., 50., 0., 0.5, 1., 1., 1., 20.);
+  };
+
+  render_frame(&mut ctx, 0);
+  assert_eq!(ctx.live_input.as_ref().unwrap().write_ix, FRAME_SIZE);
+  render_frame(&mut ctx, 1);
+  render_frame(&mut ctx, 2);
+  // 384 samples written into a 300 sample buffer wraps around
+  assert_eq!(ctx.live_input.as_ref().unwrap().write_ix, 84);
+  assert_eq!(ctx.waveform[83], 0.383);
+  assert_eq!(ctx.waveform[84], 0.084);
+
+  unsafe { granular_set_live_input_frozen(&mut ctx, true) };
+  let frozen_waveform = ctx.waveform.clone();
+  render_frame(&mut ctx, 3);
+  assert_eq!(ctx.live_input.as_ref().unwrap().write_ix, 84);
+  assert_eq!(ctx.waveform, frozen_waveform);
+  assert!(ctx.rendered_output.iter().any(|&sample| sample != 0.));
+}
+
+#[test]
+fn live_input_feedback_is_written_to_buffer() {
+  let mut ctx = GranularCtx::default();
+  unsafe {
+    granular_enable_live_input(&mut ctx, 1000);
+    granular_set_live_input_feedback(&mut ctx, 0.5);
+  }
+
+  ctx.live_input.as_mut().unwrap().input = [0.5; FRAME_SIZE];
+  render_granular(&mut ctx, 0., 999., 100., 0., 0.5, 1., 1., 1., 10.);
+  ctx.live_input.as_mut().unwrap().input = [0.; FRAME_SIZE];
+  for _ in 0..4 {
+    render_granular(&mut ctx, 0., 999., 100., 0., 0.5, 1., 1., 1., 10.);
+  }
+  // With no input, the only thing that can end up in the buffer is fed-back output
+  assert!(ctx.waveform[FRAME_SIZE..5 * FRAME_SIZE]
+    .iter()
+    .any(|&sample| sample != 0.));
+}
+
+#[test]
+fn live_grains_dont_read_across_the_write_head() {
+  let mut ctx = GranularCtx::default();
+  unsafe {
+    granular_enable_live_input(&mut ctx, 1000);
+    granular_set_pitch(&mut ctx, 12., 0.);
+  }
+
+  // The grain starts 400 samples before the newest sample and reads 2 samples for every 1 that's
+  // written, so it can only play for 400 samples before catching up to the write head
+  let voice = &mut ctx.voices[0];
+  voice.cur_grain_start = 599.;
+  voice.seed_grain(
+    2000.,
+    0.5,
+    1.,
+    1.,
+    0.,
+    1000,
+    Some(LiveWriteHead {
+      write_ix: 0,
+      is_advancing: true,
+    }),
+  );
+  assert_eq!(voice.grains[0].len_samples, 400.);
+
+  // Frozen buffers only limit the grain to the end of the buffer
+  voice.grains.clear();
+  voice.seed_grain(
+    2000.,
+    0.5,
+    1.,
+    1.,
+    0.,
+    1000,
+    Some(LiveWriteHead {
+      write_ix: 0,
+      is_advancing: false,
+    }),
+  );
+  assert_eq!(voice.grains[0].len_samples, 200.);
+
+  // Grains from a sample buffer are never shortened
+  voice.grains.clear();
+  voice.seed_grain(2000., 0.5, 1., 1., 0., 1000, None);
+  assert_eq!(voice.grains[0].len_samples, 2000.);
+}
+
+#[test]
+fn wrapping_grain_reads_across_buffer_end() {
+  let buf: Vec<f32> = (0..10).map(|i| i as f32).collect();
+  let grain = Grain {
+    len_samples: 4.,
+    start_sample_ix: 18.5,
+    samples_read_so_far: 1.,
+    sample_playback_ratio: 1.,
+    linear_slope_length: 0.,
+    slope_linearity: 1.,
+    is_reversed: false,
+    window: GrainWindow::LinearSine,
+    pitch_ratio: 1.,
+    wraps: true,
+  };
+  // 19.5 wraps to 9.5, interpolating between the last and first samples
+  assert_eq!(grain.sample(&buf, 0., 1.).1, 4.5);
+}
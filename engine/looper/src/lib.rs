@@ -42,13 +42,61 @@ impl PartialOrd for MIDIEvent {
   fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
 }
 
+fn fire_evt(module_ix: usize, playing_notes: &mut [bool; 1024], evt: &MIDIEvent) {
+  if evt.is_gate {
+    playing_notes[evt.note as usize] = true;
+    unsafe { play_note(module_ix, evt.note) };
+  } else {
+    playing_notes[evt.note as usize] = false;
+    unsafe { release_note(module_ix, evt.note) };
+  }
+}
+
+fn release_all(module_ix: usize, playing_notes: &mut [bool; 1024]) {
+  for (note, is_playing) in playing_notes.iter_mut().enumerate() {
+    if *is_playing {
+      unsafe { release_note(module_ix, note as u8) };
+      *is_playing = false;
+    }
+  }
+}
+
+/// Playback state for a single bank when it's being played independently of the others in
+/// polymetric mode.
+struct BankPlayhead {
+  pub last_beat: f32,
+  pub next_evt_ix: Option<usize>,
+  pub playing_notes: [bool; 1024],
+}
+
+impl Default for BankPlayhead {
+  fn default() -> Self {
+    BankPlayhead {
+      last_beat: f32::INFINITY,
+      next_evt_ix: None,
+      playing_notes: [false; 1024],
+    }
+  }
+}
+
 struct LooperBank {
   pub events: Vec<MIDIEvent>,
   pub len_beats: f32,
+  /// Whether this bank plays when the module is in polymetric mode
+  pub polymetric_enabled: bool,
+  pub playhead: BankPlayhead,
 }
 
 impl LooperBank {
   pub fn clear(&mut self) { self.events.clear(); }
+
+  /// Stops the bank's independent playback, releasing any notes it's holding.  The next time it's
+  /// processed, it will pick up from the current beat without replaying earlier events.
+  pub fn reset_playhead(&mut self, module_ix: usize) {
+    release_all(module_ix, &mut self.playhead.playing_notes);
+    self.playhead.last_beat = f32::INFINITY;
+    self.playhead.next_evt_ix = None;
+  }
 }
 
 impl Default for LooperBank {
@@ -56,6 +104,8 @@ impl Default for LooperBank {
     LooperBank {
       events: Vec::new(),
       len_beats: 8.0,
+      polymetric_enabled: false,
+      playhead: BankPlayhead::default(),
     }
   }
 }
@@ -169,6 +219,14 @@ struct LooperCtx {
   pub active_bank_ix: Option<usize>,
   pub transition_algorithm: TransitionAlgorithm,
   pub banks: Vec<LooperBank>,
+  /// If true, all banks with `polymetric_enabled` set play simultaneously, each looping over its
+  /// own length with its own phase.  The transition algorithm and active bank are ignored.
+  pub polymetric: bool,
+  /// Beat at which all polymetric banks were last synced to phase 0
+  pub polymetric_origin_beat: f32,
+  pub needs_polymetric_resync: bool,
+  /// Phase of each bank as of the last call to `looper_process`, or -1 if the bank isn't playing
+  pub bank_phases: Vec<f32>,
 }
 
 impl Default for LooperCtx {
@@ -180,6 +238,19 @@ impl Default for LooperCtx {
       active_bank_ix: None,
       transition_algorithm: Default::default(),
       banks: Vec::new(),
+      polymetric: false,
+      polymetric_origin_beat: 0.,
+      needs_polymetric_resync: true,
+      bank_phases: Vec::new(),
+    }
+  }
+}
+
+impl LooperCtx {
+  pub fn release_all(&mut self, module_ix: usize) {
+    release_all(module_ix, &mut self.playing_notes);
+    for bank in &mut self.banks {
+      release_all(module_ix, &mut bank.playhead.playing_notes);
     }
   }
 }
@@ -319,6 +390,7 @@ pub extern "C" fn looper_on_playback_start() {
     };
     let new_active_bank_ix = new_active_bank_ix.filter(|&ix| ctx.banks.get(ix).is_some());
     ctx.last_beat = std::f32::INFINITY;
+    ctx.needs_polymetric_resync = true;
     ctx.active_bank_ix = new_active_bank_ix;
     unsafe {
       set_active_bank_ix(module_ix, match new_active_bank_ix {
@@ -332,12 +404,7 @@ pub extern "C" fn looper_on_playback_start() {
 #[no_mangle]
 pub extern "C" fn looper_on_playback_stop() {
   for (module_ix, ctx) in ctxs().iter_mut().enumerate() {
-    for (note, is_playing) in ctx.playing_notes.iter_mut().enumerate() {
-      if *is_playing {
-        unsafe { release_note(module_ix, note as u8) };
-        *is_playing = false;
-      }
-    }
+    ctx.release_all(module_ix);
   }
 }
 
@@ -452,12 +519,108 @@ fn process_looper_module(cur_beat: f32, module_ix: usize, ctx: &mut LooperCtx) -
   loop_beat / active_bank.len_beats
 }
 
+fn process_polymetric_bank(cur_beat: f32, module_ix: usize, bank: &mut LooperBank) -> f32 {
+  let loop_beat = cur_beat.rem_euclid(bank.len_beats);
+  let playhead = &mut bank.playhead;
+
+  if loop_beat < playhead.last_beat {
+    let is_playback_start = playhead.last_beat == f32::INFINITY;
+
+    if !is_playback_start {
+      while let Some(next_evt_ix) = playhead.next_evt_ix {
+        let evt = match bank.events.get(next_evt_ix) {
+          Some(evt) if evt.beat <= bank.len_beats => evt,
+          _ => break,
+        };
+        fire_evt(module_ix, &mut playhead.playing_notes, evt);
+        playhead.next_evt_ix = if next_evt_ix + 1 < bank.events.len() {
+          Some(next_evt_ix + 1)
+        } else {
+          None
+        };
+      }
+    }
+
+    release_all(module_ix, &mut playhead.playing_notes);
+    playhead.next_evt_ix = if bank.events.is_empty() {
+      None
+    } else {
+      Some(0)
+    };
+
+    if is_playback_start {
+      while let Some(next_evt_ix) = playhead.next_evt_ix {
+        if bank.events[next_evt_ix].beat >= loop_beat {
+          break;
+        }
+        playhead.next_evt_ix = if next_evt_ix + 1 < bank.events.len() {
+          Some(next_evt_ix + 1)
+        } else {
+          None
+        };
+      }
+    }
+  }
+
+  while let Some(next_evt_ix) = playhead.next_evt_ix {
+    let evt = &bank.events[next_evt_ix];
+    if evt.beat > loop_beat {
+      break;
+    }
+    fire_evt(module_ix, &mut playhead.playing_notes, evt);
+    playhead.next_evt_ix = if next_evt_ix + 1 < bank.events.len() {
+      Some(next_evt_ix + 1)
+    } else {
+      None
+    };
+  }
+
+  playhead.last_beat = loop_beat;
+  loop_beat / bank.len_beats
+}
+
+/// Plays all enabled banks of the module simultaneously.  Returns the phase of the first enabled
+/// bank; the phases of all banks are written into `ctx.bank_phases`.
+fn process_polymetric_looper_module(cur_beat: f32, module_ix: usize, ctx: &mut LooperCtx) -> f32 {
+  if ctx.needs_polymetric_resync {
+    ctx.needs_polymetric_resync = false;
+    ctx.polymetric_origin_beat = cur_beat;
+    for bank in &mut ctx.banks {
+      bank.reset_playhead(module_ix);
+    }
+  }
+
+  let rel_beat = cur_beat - ctx.polymetric_origin_beat;
+  let mut first_phase = None;
+  for (bank, phase) in ctx.banks.iter_mut().zip(ctx.bank_phases.iter_mut()) {
+    if !bank.polymetric_enabled || bank.len_beats <= 0. {
+      *phase = -1.;
+      continue;
+    }
+
+    *phase = process_polymetric_bank(rel_beat, module_ix, bank);
+    first_phase.get_or_insert(*phase);
+  }
+
+  first_phase.unwrap_or(0.)
+}
+
 #[no_mangle]
 pub extern "C" fn looper_process(module_ix_for_which_to_report_phase: usize, cur_beat: f32) -> f32 {
   let mut phase = 0.;
 
   for (module_ix, ctx) in ctxs().iter_mut().enumerate() {
-    let module_phase = process_looper_module(cur_beat, module_ix, ctx);
+    ctx.bank_phases.resize(ctx.banks.len(), -1.);
+    let module_phase = if ctx.polymetric {
+      process_polymetric_looper_module(cur_beat, module_ix, ctx)
+    } else {
+      ctx.bank_phases.fill(-1.);
+      let phase = process_looper_module(cur_beat, module_ix, ctx);
+      if let Some(active_bank_ix) = ctx.active_bank_ix {
+        ctx.bank_phases[active_bank_ix] = phase;
+      }
+      phase
+    };
     if module_ix == module_ix_for_which_to_report_phase {
       phase = module_phase;
     }
@@ -482,7 +645,11 @@ pub extern "C" fn looper_delete_bank(module_ix: usize, bank_ix: usize) {
   if ctx.banks.len() <= bank_ix {
     return;
   }
-  ctx.banks.remove(bank_ix);
+  let mut removed = ctx.banks.remove(bank_ix);
+  removed.reset_playhead(module_ix);
+  if bank_ix < ctx.bank_phases.len() {
+    ctx.bank_phases.remove(bank_ix);
+  }
 
   match ctx.active_bank_ix {
     Some(active) if active == bank_ix => {
@@ -512,6 +679,54 @@ pub extern "C" fn looper_set_loop_len_beats(module_ix: usize, bank_ix: usize, le
   bank.len_beats = len_beats;
 }
 
+/// Switches the module between sequential playback (one active bank at a time, chosen by the
+/// transition algorithm) and polymetric playback (all enabled banks at once).
+#[no_mangle]
+pub extern "C" fn looper_set_polymetric_mode(module_ix: usize, polymetric: bool) {
+  let ctx = ctx(module_ix);
+  if ctx.polymetric == polymetric {
+    return;
+  }
+
+  ctx.release_all(module_ix);
+  ctx.polymetric = polymetric;
+  ctx.last_beat = f32::INFINITY;
+  ctx.next_evt_ix = None;
+  for bank in &mut ctx.banks {
+    bank.reset_playhead(module_ix);
+  }
+}
+
+/// Enables or disables a bank for polymetric playback.  Newly enabled banks join in at their
+/// current phase relative to the last sync point rather than restarting from the beginning.
+#[no_mangle]
+pub extern "C" fn looper_set_bank_polymetric_enabled(
+  module_ix: usize,
+  bank_ix: usize,
+  enabled: bool,
+) {
+  let ctx = ctx(module_ix);
+  while ctx.banks.len() <= bank_ix {
+    ctx.banks.push(Default::default());
+  }
+
+  let bank = &mut ctx.banks[bank_ix];
+  if bank.polymetric_enabled != enabled {
+    bank.polymetric_enabled = enabled;
+    bank.reset_playhead(module_ix);
+  }
+}
+
+/// Returns a pointer to the per-bank phases written by the last call to `looper_process`.  There
+/// is one entry per bank; banks that aren't playing have a phase of -1.
+#[no_mangle]
+pub extern "C" fn looper_get_bank_phases_ptr(module_ix: usize) -> *const f32 {
+  ctx(module_ix).bank_phases.as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn looper_get_bank_count(module_ix: usize) -> usize { ctx(module_ix).banks.len() }
+
 static mut TRANSITION_ALGORITHM_BUFFER: *mut Vec<f32> = std::ptr::null_mut();
 
 fn transition_algorithm_buffer() -> &'static mut Vec<f32> {
@@ -557,4 +772,63 @@ mod tests {
     assert!(!evts[1].is_gate, "ungate must sort before gate at equal beat");
     assert!(evts[2].is_gate);
   }
+
+  fn polymetric_ctx() -> LooperCtx {
+    let mut ctx = LooperCtx {
+      polymetric: true,
+      ..Default::default()
+    };
+    for (len_beats, note) in [(3., 60), (4., 64)] {
+      ctx.banks.push(LooperBank {
+        events: vec![
+          MIDIEvent { is_gate: true, note, beat: 0. },
+          MIDIEvent { is_gate: false, note, beat: 1. },
+        ],
+        len_beats,
+        polymetric_enabled: true,
+        playhead: BankPlayhead::default(),
+      });
+    }
+    ctx.bank_phases = vec![-1.; ctx.banks.len()];
+    ctx
+  }
+
+  #[test]
+  fn polymetric_banks_have_independent_phases() {
+    let mut ctx = polymetric_ctx();
+
+    process_polymetric_looper_module(0., 0, &mut ctx);
+    assert_eq!(ctx.bank_phases, vec![0., 0.]);
+    assert!(ctx.banks[0].playhead.playing_notes[60]);
+    assert!(ctx.banks[1].playhead.playing_notes[64]);
+
+    process_polymetric_looper_module(1.5, 0, &mut ctx);
+    assert_eq!(ctx.bank_phases, vec![0.5, 0.375]);
+    assert!(!ctx.banks[0].playhead.playing_notes[60]);
+
+    // the 3-beat bank wraps and retriggers while the 4-beat bank keeps going
+    process_polymetric_looper_module(3.5, 0, &mut ctx);
+    assert_eq!(ctx.bank_phases, vec![0.5 / 3., 0.875]);
+    assert_eq!(ctx.banks[0].playhead.next_evt_ix, Some(1));
+    assert!(ctx.banks[0].playhead.playing_notes[60]);
+    assert!(!ctx.banks[1].playhead.playing_notes[64]);
+  }
+
+  #[test]
+  fn polymetric_banks_resync_on_playback_start() {
+    let mut ctx = polymetric_ctx();
+    ctx.banks[1].polymetric_enabled = false;
+
+    process_polymetric_looper_module(0., 0, &mut ctx);
+    process_polymetric_looper_module(5., 0, &mut ctx);
+    assert_eq!(ctx.bank_phases[1], -1.);
+
+    ctx.banks[1].polymetric_enabled = true;
+    ctx.needs_polymetric_resync = true;
+    process_polymetric_looper_module(10., 0, &mut ctx);
+    assert_eq!(ctx.polymetric_origin_beat, 10.);
+    assert_eq!(ctx.bank_phases, vec![0., 0.]);
+    assert!(ctx.banks[0].playhead.playing_notes[60]);
+    assert!(ctx.banks[1].playhead.playing_notes[64]);
+  }
 }
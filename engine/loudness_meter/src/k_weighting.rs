@@ -0,0 +1,93 @@
+//! K-weighting filter from ITU-R BS.1770: a high shelf modeling the acoustic effect of the head
+//! followed by the "RLB" highpass.  Coefficients are derived from the analog prototypes so that
+//! they're correct at any sample rate, matching the coefficients given in the spec at 48 kHz.
+
+const SHELF_FREQ: f64 = 1_681.974_450_955_533;
+const SHELF_GAIN_DB: f64 = 3.999_843_853_973_347;
+const SHELF_Q: f64 = 0.707_175_236_955_419_6;
+const HIGHPASS_FREQ: f64 = 38.135_470_876_024_44;
+const HIGHPASS_Q: f64 = 0.500_327_037_323_877_3;
+
+/// Transposed direct form II biquad
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+  b: [f64; 3],
+  a: [f64; 2],
+  z: [f64; 2],
+}
+
+impl Biquad {
+  #[inline]
+  fn apply(&mut self, input: f64) -> f64 {
+    let output = self.b[0] * input + self.z[0];
+    self.z[0] = self.b[1] * input - self.a[0] * output + self.z[1];
+    self.z[1] = self.b[2] * input - self.a[1] * output;
+    output
+  }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct KWeightingFilter {
+  shelf: Biquad,
+  highpass: Biquad,
+}
+
+impl KWeightingFilter {
+  pub fn new(sample_rate: f32) -> Self {
+    let sample_rate = sample_rate as f64;
+
+    let k = (std::f64::consts::PI * SHELF_FREQ / sample_rate).tan();
+    let vh = 10f64.powf(SHELF_GAIN_DB / 20.);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1. + k / SHELF_Q + k * k;
+    let shelf = Biquad {
+      b: [
+        (vh + vb * k / SHELF_Q + k * k) / a0,
+        2. * (k * k - vh) / a0,
+        (vh - vb * k / SHELF_Q + k * k) / a0,
+      ],
+      a: [2. * (k * k - 1.) / a0, (1. - k / SHELF_Q + k * k) / a0],
+      z: [0.; 2],
+    };
+
+    let k = (std::f64::consts::PI * HIGHPASS_FREQ / sample_rate).tan();
+    let a0 = 1. + k / HIGHPASS_Q + k * k;
+    let highpass = Biquad {
+      b: [1., -2., 1.],
+      a: [2. * (k * k - 1.) / a0, (1. - k / HIGHPASS_Q + k * k) / a0],
+      z: [0.; 2],
+    };
+
+    KWeightingFilter { shelf, highpass }
+  }
+
+  #[inline]
+  pub fn apply(&mut self, sample: f32) -> f64 { self.highpass.apply(self.shelf.apply(sample as f64)) }
+
+  pub fn reset(&mut self) {
+    self.shelf.z = [0.; 2];
+    self.highpass.z = [0.; 2];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_spec_coefficients_at_48k() {
+    let filter = KWeightingFilter::new(48_000.);
+    let expected_shelf_b = [1.535_124_859_586_97, -2.691_696_189_406_38, 1.198_392_810_852_85];
+    let expected_shelf_a = [-1.690_659_293_182_41, 0.732_480_774_215_85];
+    let expected_highpass_a = [-1.990_047_454_833_98, 0.990_072_250_366_21];
+    for (actual, expected) in filter.shelf.b.iter().zip(expected_shelf_b) {
+      assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+    for (actual, expected) in filter.shelf.a.iter().zip(expected_shelf_a) {
+      assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+    for (actual, expected) in filter.highpass.a.iter().zip(expected_highpass_a) {
+      assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+  }
+}
@@ -0,0 +1,139 @@
+use dsp::FRAME_SIZE;
+
+use crate::{
+  loudness::{LoudnessMeter, CHANNEL_COUNT},
+  true_peak::TruePeakDetector,
+};
+
+pub mod k_weighting;
+pub mod loudness;
+pub mod true_peak;
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "env")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+extern "C" fn log_err(_ptr: *const u8, _len: usize) {}
+
+/// Layout of the readings buffer exposed to JS.  All loudness values are in LUFS, loudness range
+/// is in LU, and true peak is in dBTP.  Values that aren't available yet are `-Infinity`.
+///
+/// 0: momentary loudness
+/// 1: short-term loudness
+/// 2: integrated loudness
+/// 3: loudness range
+/// 4: max momentary loudness
+/// 5: max short-term loudness
+/// 6: max true peak across all channels
+pub const READING_COUNT: usize = 7;
+
+pub struct LoudnessMeterCtx {
+  /// Planar stereo: all left samples followed by all right samples
+  pub io_buffer: [[f32; FRAME_SIZE]; CHANNEL_COUNT],
+  pub meter: LoudnessMeter,
+  pub true_peak_detectors: [TruePeakDetector; CHANNEL_COUNT],
+  pub readings: [f32; READING_COUNT],
+}
+
+impl LoudnessMeterCtx {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      io_buffer: [[0.; FRAME_SIZE]; CHANNEL_COUNT],
+      meter: LoudnessMeter::new(sample_rate),
+      true_peak_detectors: Default::default(),
+      readings: [f32::NEG_INFINITY; READING_COUNT],
+    }
+  }
+
+  /// Analyzes the contents of the IO buffer, leaving it unmodified so that audio passes through.
+  pub fn process(&mut self) {
+    let [left, right] = &self.io_buffer;
+    self.meter.process([left, right]);
+    for (detector, channel) in self.true_peak_detectors.iter_mut().zip(self.io_buffer.iter()) {
+      for &sample in channel {
+        detector.process(sample);
+      }
+    }
+
+    let true_peak = self.true_peak_detectors.iter().map(|d| d.peak()).fold(0., f32::max);
+    self.readings = [
+      self.meter.momentary_lufs as f32,
+      self.meter.short_term_lufs as f32,
+      self.meter.integrated_lufs as f32,
+      self.meter.loudness_range_lu as f32,
+      self.meter.max_momentary_lufs as f32,
+      self.meter.max_short_term_lufs as f32,
+      if true_peak > 0. {
+        dsp::gain_to_db(true_peak)
+      } else {
+        f32::NEG_INFINITY
+      },
+    ];
+  }
+
+  pub fn reset(&mut self) {
+    self.meter.reset();
+    for detector in &mut self.true_peak_detectors {
+      detector.reset();
+    }
+    self.readings = [f32::NEG_INFINITY; READING_COUNT];
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn loudness_meter_create_ctx(sample_rate: f32) -> *mut LoudnessMeterCtx {
+  common::set_raw_panic_hook(log_err);
+
+  let ctx = LoudnessMeterCtx::new(sample_rate);
+  Box::into_raw(Box::new(ctx))
+}
+
+/// Returns a pointer to the planar stereo buffer that input is written to before processing
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `loudness_meter_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn loudness_meter_get_io_buf_ptr(ctx: *mut LoudnessMeterCtx) -> *mut f32 {
+  let ctx = &mut *ctx;
+  ctx.io_buffer.as_mut_ptr() as *mut f32
+}
+
+/// Returns a pointer to the `READING_COUNT` readings, which are updated by each call to
+/// `loudness_meter_process`
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `loudness_meter_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn loudness_meter_get_readings_ptr(
+  ctx: *mut LoudnessMeterCtx,
+) -> *const f32 {
+  let ctx = &mut *ctx;
+  ctx.readings.as_ptr()
+}
+
+/// Analyzes the contents of the IO buffer and updates the readings
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `loudness_meter_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn loudness_meter_process(ctx: *mut LoudnessMeterCtx) {
+  let ctx = &mut *ctx;
+  ctx.process();
+}
+
+/// Clears all measurements, including integrated loudness, loudness range, and max values
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `loudness_meter_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn loudness_meter_reset(ctx: *mut LoudnessMeterCtx) {
+  let ctx = &mut *ctx;
+  ctx.reset();
+}
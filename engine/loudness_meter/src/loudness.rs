@@ -0,0 +1,348 @@
+//! Loudness measurement as specified by ITU-R BS.1770 and EBU R 128 / Tech 3341 / Tech 3342.
+//!
+//! K-weighted signal power is accumulated into 100ms sub-blocks.  Momentary loudness is computed
+//! over the most recent 4 sub-blocks (400ms) and short-term loudness over the most recent 30
+//! (3s), both updated every sub-block which gives the 75% overlap required for gating blocks.
+//!
+//! Integrated loudness and loudness range are computed from histograms of block loudness rather
+//! than storing every block so that memory and CPU usage stay constant no matter how long the
+//! meter runs.
+
+use crate::k_weighting::KWeightingFilter;
+
+pub const CHANNEL_COUNT: usize = 2;
+const SUB_BLOCK_DURATION_S: f32 = 0.1;
+const MOMENTARY_SUB_BLOCK_COUNT: usize = 4;
+const SHORT_TERM_SUB_BLOCK_COUNT: usize = 30;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.;
+const INTEGRATED_RELATIVE_GATE_LU: f64 = -10.;
+const LRA_RELATIVE_GATE_LU: f64 = -20.;
+const LRA_LOW_PERCENTILE: f64 = 0.1;
+const LRA_HIGH_PERCENTILE: f64 = 0.95;
+
+const HISTOGRAM_MAX_LUFS: f64 = 10.;
+const HISTOGRAM_BINS_PER_LU: f64 = 100.;
+const HISTOGRAM_BIN_COUNT: usize =
+  ((HISTOGRAM_MAX_LUFS - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU) as usize;
+
+#[inline]
+fn power_to_lufs(power: f64) -> f64 {
+  if power <= 0. {
+    return f64::NEG_INFINITY;
+  }
+  -0.691 + 10. * power.log10()
+}
+
+/// Histogram of the loudness of gating blocks which pass the absolute gate.  Each bin also tracks
+/// the summed power of the blocks in it so that gated means can be computed exactly up to the
+/// resolution of the gate threshold.
+struct GatingHistogram {
+  counts: Vec<u32>,
+  power_sums: Vec<f64>,
+  total_count: u64,
+  total_power: f64,
+}
+
+impl GatingHistogram {
+  fn new() -> Self {
+    GatingHistogram {
+      counts: vec![0; HISTOGRAM_BIN_COUNT],
+      power_sums: vec![0.; HISTOGRAM_BIN_COUNT],
+      total_count: 0,
+      total_power: 0.,
+    }
+  }
+
+  #[inline]
+  fn bin_ix(lufs: f64) -> usize {
+    let ix = ((lufs - ABSOLUTE_GATE_LUFS) * HISTOGRAM_BINS_PER_LU).floor();
+    (ix.max(0.) as usize).min(HISTOGRAM_BIN_COUNT - 1)
+  }
+
+  #[inline]
+  fn bin_center_lufs(bin_ix: usize) -> f64 {
+    ABSOLUTE_GATE_LUFS + (bin_ix as f64 + 0.5) / HISTOGRAM_BINS_PER_LU
+  }
+
+  fn add(&mut self, power: f64) {
+    let lufs = power_to_lufs(power);
+    if lufs < ABSOLUTE_GATE_LUFS {
+      return;
+    }
+
+    let bin_ix = Self::bin_ix(lufs);
+    self.counts[bin_ix] += 1;
+    self.power_sums[bin_ix] += power;
+    self.total_count += 1;
+    self.total_power += power;
+  }
+
+  /// Returns the index of the first bin which passes a gate `relative_gate_lu` below the mean
+  /// loudness of all absolute-gated blocks, or `None` if there are no such blocks.
+  fn relative_gate_start_bin(&self, relative_gate_lu: f64) -> Option<usize> {
+    if self.total_count == 0 {
+      return None;
+    }
+
+    let threshold = power_to_lufs(self.total_power / self.total_count as f64) + relative_gate_lu;
+    Some(Self::bin_ix(threshold.max(ABSOLUTE_GATE_LUFS)))
+  }
+
+  /// Mean power of all blocks passing both the absolute gate and the relative gate
+  fn gated_mean_power(&self, relative_gate_lu: f64) -> Option<f64> {
+    let start_bin_ix = self.relative_gate_start_bin(relative_gate_lu)?;
+    let count: u64 = self.counts[start_bin_ix..].iter().map(|&c| c as u64).sum();
+    if count == 0 {
+      return None;
+    }
+    let power: f64 = self.power_sums[start_bin_ix..].iter().sum();
+    Some(power / count as f64)
+  }
+
+  /// Loudness values at each of the provided percentiles (in ascending order) of the blocks
+  /// passing both the absolute gate and the relative gate
+  fn gated_percentiles<const N: usize>(
+    &self,
+    relative_gate_lu: f64,
+    percentiles: [f64; N],
+  ) -> Option<[f64; N]> {
+    let start_bin_ix = self.relative_gate_start_bin(relative_gate_lu)?;
+    let count: u64 = self.counts[start_bin_ix..].iter().map(|&c| c as u64).sum();
+    if count == 0 {
+      return None;
+    }
+
+    let mut out = [0.; N];
+    let mut cumulative_count = 0u64;
+    let mut bin_ix = start_bin_ix;
+    for (percentile, out) in percentiles.into_iter().zip(out.iter_mut()) {
+      let target_rank = (percentile * (count - 1) as f64).round() as u64;
+      while bin_ix < HISTOGRAM_BIN_COUNT - 1
+        && cumulative_count + self.counts[bin_ix] as u64 <= target_rank
+      {
+        cumulative_count += self.counts[bin_ix] as u64;
+        bin_ix += 1;
+      }
+      *out = Self::bin_center_lufs(bin_ix);
+    }
+    Some(out)
+  }
+
+  fn reset(&mut self) {
+    self.counts.fill(0);
+    self.power_sums.fill(0.);
+    self.total_count = 0;
+    self.total_power = 0.;
+  }
+}
+
+pub struct LoudnessMeter {
+  filters: [KWeightingFilter; CHANNEL_COUNT],
+  sub_block_len_samples: usize,
+  /// Number of samples accumulated into the in-progress sub-block
+  cur_sub_block_len: usize,
+  /// Sum of squared K-weighted samples across all channels for the in-progress sub-block
+  cur_sub_block_sum: f64,
+  /// Ring buffer of the mean power of the most recent completed sub-blocks
+  sub_block_powers: [f64; SHORT_TERM_SUB_BLOCK_COUNT],
+  sub_block_ix: usize,
+  completed_sub_block_count: usize,
+  momentary_histogram: GatingHistogram,
+  short_term_histogram: GatingHistogram,
+  pub momentary_lufs: f64,
+  pub short_term_lufs: f64,
+  pub integrated_lufs: f64,
+  pub loudness_range_lu: f64,
+  pub max_momentary_lufs: f64,
+  pub max_short_term_lufs: f64,
+}
+
+impl LoudnessMeter {
+  pub fn new(sample_rate: f32) -> Self {
+    LoudnessMeter {
+      filters: [KWeightingFilter::new(sample_rate); CHANNEL_COUNT],
+      sub_block_len_samples: (sample_rate * SUB_BLOCK_DURATION_S).round() as usize,
+      cur_sub_block_len: 0,
+      cur_sub_block_sum: 0.,
+      sub_block_powers: [0.; SHORT_TERM_SUB_BLOCK_COUNT],
+      sub_block_ix: 0,
+      completed_sub_block_count: 0,
+      momentary_histogram: GatingHistogram::new(),
+      short_term_histogram: GatingHistogram::new(),
+      momentary_lufs: f64::NEG_INFINITY,
+      short_term_lufs: f64::NEG_INFINITY,
+      integrated_lufs: f64::NEG_INFINITY,
+      loudness_range_lu: 0.,
+      max_momentary_lufs: f64::NEG_INFINITY,
+      max_short_term_lufs: f64::NEG_INFINITY,
+    }
+  }
+
+  /// Mean power of the most recent `count` completed sub-blocks
+  fn recent_sub_blocks_power(&self, count: usize) -> f64 {
+    let sum: f64 = (1..=count)
+      .map(|offset| {
+        let ix = (self.sub_block_ix + SHORT_TERM_SUB_BLOCK_COUNT - offset) % SHORT_TERM_SUB_BLOCK_COUNT;
+        self.sub_block_powers[ix]
+      })
+      .sum();
+    sum / count as f64
+  }
+
+  fn complete_sub_block(&mut self) {
+    self.sub_block_powers[self.sub_block_ix] =
+      self.cur_sub_block_sum / self.sub_block_len_samples as f64;
+    self.sub_block_ix = (self.sub_block_ix + 1) % SHORT_TERM_SUB_BLOCK_COUNT;
+    self.completed_sub_block_count += 1;
+    self.cur_sub_block_len = 0;
+    self.cur_sub_block_sum = 0.;
+
+    if self.completed_sub_block_count >= MOMENTARY_SUB_BLOCK_COUNT {
+      let momentary_power = self.recent_sub_blocks_power(MOMENTARY_SUB_BLOCK_COUNT);
+      self.momentary_lufs = power_to_lufs(momentary_power);
+      self.max_momentary_lufs = self.max_momentary_lufs.max(self.momentary_lufs);
+
+      self.momentary_histogram.add(momentary_power);
+      self.integrated_lufs = self
+        .momentary_histogram
+        .gated_mean_power(INTEGRATED_RELATIVE_GATE_LU)
+        .map(power_to_lufs)
+        .unwrap_or(f64::NEG_INFINITY);
+    }
+
+    if self.completed_sub_block_count >= SHORT_TERM_SUB_BLOCK_COUNT {
+      let short_term_power = self.recent_sub_blocks_power(SHORT_TERM_SUB_BLOCK_COUNT);
+      self.short_term_lufs = power_to_lufs(short_term_power);
+      self.max_short_term_lufs = self.max_short_term_lufs.max(self.short_term_lufs);
+
+      self.short_term_histogram.add(short_term_power);
+      self.loudness_range_lu = self
+        .short_term_histogram
+        .gated_percentiles(LRA_RELATIVE_GATE_LU, [LRA_LOW_PERCENTILE, LRA_HIGH_PERCENTILE])
+        .map(|[low, high]| high - low)
+        .unwrap_or(0.);
+    }
+  }
+
+  pub fn process(&mut self, channels: [&[f32]; CHANNEL_COUNT]) {
+    let len = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+    for sample_ix in 0..len {
+      // All channel weights are 1 for left/right
+      for (filter, channel) in self.filters.iter_mut().zip(channels.iter()) {
+        let weighted = filter.apply(channel[sample_ix]);
+        self.cur_sub_block_sum += weighted * weighted;
+      }
+
+      self.cur_sub_block_len += 1;
+      if self.cur_sub_block_len == self.sub_block_len_samples {
+        self.complete_sub_block();
+      }
+    }
+  }
+
+  pub fn reset(&mut self) {
+    for filter in &mut self.filters {
+      filter.reset();
+    }
+    self.cur_sub_block_len = 0;
+    self.cur_sub_block_sum = 0.;
+    self.sub_block_powers = [0.; SHORT_TERM_SUB_BLOCK_COUNT];
+    self.sub_block_ix = 0;
+    self.completed_sub_block_count = 0;
+    self.momentary_histogram.reset();
+    self.short_term_histogram.reset();
+    self.momentary_lufs = f64::NEG_INFINITY;
+    self.short_term_lufs = f64::NEG_INFINITY;
+    self.integrated_lufs = f64::NEG_INFINITY;
+    self.loudness_range_lu = 0.;
+    self.max_momentary_lufs = f64::NEG_INFINITY;
+    self.max_short_term_lufs = f64::NEG_INFINITY;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: f32 = 48_000.;
+
+  /// Feeds a stereo 1 kHz sine through the meter in segments of `(level_dbfs, duration_s)` as used
+  /// by the EBU Tech 3341 and Tech 3342 test signals, in chunks of 128 samples like the FFI does.
+  fn run_sine_segments(meter: &mut LoudnessMeter, segments: &[(f32, f32)]) {
+    let mut phase = 0f64;
+    let phase_step = std::f64::consts::TAU * 1_000. / SAMPLE_RATE as f64;
+    let mut samples = Vec::new();
+    for &(level_dbfs, duration_s) in segments {
+      let amplitude = dsp::db_to_gain(level_dbfs) as f64;
+      for _ in 0..(duration_s * SAMPLE_RATE).round() as usize {
+        samples.push((amplitude * phase.sin()) as f32);
+        phase = (phase + phase_step) % std::f64::consts::TAU;
+      }
+    }
+
+    for chunk in samples.chunks(dsp::FRAME_SIZE) {
+      meter.process([chunk, chunk]);
+    }
+  }
+
+  fn assert_close(actual: f64, expected: f64, tolerance: f64, name: &str) {
+    assert!(
+      (actual - expected).abs() <= tolerance,
+      "{name}: expected {expected} ±{tolerance}, got {actual}"
+    );
+  }
+
+  /// EBU Tech 3341 cases 1 and 2: constant stereo sine
+  #[test]
+  fn ebu_3341_constant_sine() {
+    for level in [-23., -33.] {
+      let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+      run_sine_segments(&mut meter, &[(level, 20.)]);
+      assert_close(meter.momentary_lufs, level as f64, 0.1, "momentary");
+      assert_close(meter.short_term_lufs, level as f64, 0.1, "short-term");
+      assert_close(meter.integrated_lufs, level as f64, 0.1, "integrated");
+    }
+  }
+
+  /// EBU Tech 3341 cases 3, 4 and 5: the gates must exclude the quieter segments
+  #[test]
+  fn ebu_3341_gated_integrated() {
+    let cases: [&[(f32, f32)]; 3] = [
+      &[(-36., 10.), (-23., 60.), (-36., 10.)],
+      &[(-72., 10.), (-36., 10.), (-23., 60.), (-36., 10.), (-72., 10.)],
+      &[(-26., 20.), (-20., 20.1), (-26., 20.)],
+    ];
+    for segments in cases {
+      let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+      run_sine_segments(&mut meter, segments);
+      assert_close(meter.integrated_lufs, -23., 0.1, "integrated");
+    }
+  }
+
+  /// EBU Tech 3342 cases 1, 2 and 3
+  #[test]
+  fn ebu_3342_loudness_range() {
+    let cases: [(&[(f32, f32)], f64); 3] = [
+      (&[(-20., 20.), (-30., 20.)], 10.),
+      (&[(-20., 20.), (-15., 20.)], 5.),
+      (&[(-40., 20.), (-20., 20.)], 20.),
+    ];
+    for (segments, expected_lra) in cases {
+      let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+      run_sine_segments(&mut meter, segments);
+      assert_close(meter.loudness_range_lu, expected_lra, 1., "loudness range");
+    }
+  }
+
+  #[test]
+  fn reset_clears_readings() {
+    let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+    run_sine_segments(&mut meter, &[(-20., 5.)]);
+    meter.reset();
+    assert_eq!(meter.integrated_lufs, f64::NEG_INFINITY);
+    run_sine_segments(&mut meter, &[(-30., 5.)]);
+    assert_close(meter.integrated_lufs, -30., 0.1, "integrated");
+    assert_close(meter.max_momentary_lufs, -30., 0.1, "max momentary");
+  }
+}
@@ -9,3 +9,19 @@ pub(crate) const YIN_MAX_PERIOD: usize = SAMPLE_RATE as usize / 40;
 pub(crate) const YIN_THRESHOLD: f32 = 0.05;
 
 pub(crate) const PEAK_LEVEL_PAST_WINDOW_LOOKBACK_COUNT: usize = 8;
+
+pub(crate) const MIN_VERTICAL_ZOOM: f32 = 0.25;
+pub(crate) const MAX_VERTICAL_ZOOM: f32 = 32.;
+
+pub(crate) const MAX_CHANNEL_COUNT: usize = 4;
+/// Line colors for each channel, mirrored by `OscilloscopeChannelColors` on the JS side so the
+/// readouts can be keyed to their traces
+pub(crate) const CHANNEL_COLORS: [(u8, u8, u8, u8); MAX_CHANNEL_COUNT] = [
+  (180, 0, 180, 255),
+  (0, 170, 190, 255),
+  (200, 170, 0, 255),
+  (40, 190, 60, 255),
+];
+
+/// Per-channel measurement slots written by `Viz::compute_measurements`
+pub(crate) const MEASUREMENT_COUNT: usize = 4;
@@ -7,48 +7,6 @@ use crate::{
   FRAME_SIZE,
 };
 
-/// YIN pitch detection algorithm.  Estimates fundamental frequency of a signal in Hz.
-pub(crate) fn yin<const YIN_FRAME_SIZE: usize, const MAX_PERIOD: usize>(
-  samples: &[f32; YIN_FRAME_SIZE],
-) -> f32 {
-  assert!(YIN_FRAME_SIZE >= MAX_PERIOD * 2);
-
-  let tau: usize;
-  let mut delta = [0.0_f32; MAX_PERIOD];
-  let mut running_sum = 0.0;
-
-  for tau in 1..MAX_PERIOD {
-    for i in 0..(MAX_PERIOD - 1) {
-      let diff = samples[i] - samples[i + tau];
-      delta[tau] += diff * diff;
-    }
-
-    running_sum += delta[tau];
-    if running_sum != 0.0 {
-      delta[tau] *= tau as f32 / running_sum;
-    } else {
-      delta[tau] = 1.0;
-    }
-  }
-
-  tau = 1
-    + delta[1..]
-      .iter()
-      .enumerate()
-      .find(|(_, &value)| value < YIN_THRESHOLD)
-      .map(|(i, _)| i)
-      .unwrap_or(
-        delta[1..]
-          .iter()
-          .enumerate()
-          .min_by(|(_, &a), (_, &b)| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal))
-          .unwrap()
-          .0,
-      );
-
-  SAMPLE_RATE / tau as f32
-}
-
 pub(crate) struct YinCtx {
   /// Rolling estimate of the F0 using light smoothing in between frames
   pub rolling_f0_estimate: f32,
@@ -81,9 +39,10 @@ impl YinCtx {
     self.buf_head = 0;
 
     // Estimate the F0 of the current frame
-    let f0 = yin::<YIN_FRAME_SIZE, YIN_MAX_PERIOD>(&self.samples_buf);
-    // There seems to be a bias in the YIN algorithm, and this kind of corrects it a bit
-    let f0 = f0 * 0.998;
+    let f0 = dsp::pitch::yin::<YIN_MAX_PERIOD>(&self.samples_buf, YIN_THRESHOLD, SAMPLE_RATE);
+    // Silence is treated as an estimate above the nyquist, which pauses wavelength-based windows.
+    // Otherwise, there seems to be a bias in the YIN algorithm, and this kind of corrects it a bit.
+    let f0 = if f0 == 0. { SAMPLE_RATE } else { f0 * 0.998 };
 
     // Update the rolling F0 estimate
     dsp::smooth(&mut self.rolling_f0_estimate, f0, 0.3);
@@ -125,39 +84,3 @@ pub(crate) fn freq_to_midi_note_name(frequency: f32) -> String {
   let octave = ((note_number as i32) / 12) - 1;
   format!("{}{}", note_name, octave)
 }
-
-#[test]
-fn test_yin() {
-  const YIN_FRAME_SIZE: usize = FRAME_SIZE * 16;
-  const MAX_PERIOD: usize = 44_100 / 80;
-
-  fn snap_to_midi_pitch(f0: f32) -> f32 {
-    let mut midi_pitch = 12. * (f0 / 440.).log2();
-    midi_pitch = midi_pitch.round();
-    440. * 2_f32.powf(midi_pitch / 12.)
-  }
-
-  // 1500 Hz sine wave + 100 Hz sine wave
-  let p1 = snap_to_midi_pitch(1500.);
-  let p2 = snap_to_midi_pitch(100.);
-  dbg!(p1, p2);
-  let mut samples = [0.; YIN_FRAME_SIZE];
-  for i in 0..YIN_FRAME_SIZE {
-    samples[i] += (i as f32 * 2. * std::f32::consts::PI * p1 / SAMPLE_RATE as f32).sin() * 0.2;
-    samples[i] += (i as f32 * 2. * std::f32::consts::PI * p2 / SAMPLE_RATE as f32).sin() * 0.9;
-  }
-
-  let estimated_f0 = yin::<YIN_FRAME_SIZE, MAX_PERIOD>(&samples);
-  println!("{}", estimated_f0);
-  assert_eq!(snap_to_midi_pitch(estimated_f0), p2);
-
-  let mut samples = [0.; YIN_FRAME_SIZE];
-  for i in 0..YIN_FRAME_SIZE {
-    samples[i] += (i as f32 * 2. * std::f32::consts::PI * p1 / SAMPLE_RATE as f32).sin() * 0.9;
-    samples[i] += (i as f32 * 2. * std::f32::consts::PI * p2 / SAMPLE_RATE as f32).sin() * 0.2;
-  }
-
-  let estimated_f0 = yin::<YIN_FRAME_SIZE, MAX_PERIOD>(&samples);
-  println!("{}", estimated_f0);
-  assert_eq!(snap_to_midi_pitch(estimated_f0), p1);
-}
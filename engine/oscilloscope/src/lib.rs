@@ -1,11 +1,15 @@
-use self::oscilloscope::{PreviousWindow, Viz, WindowLength};
+use self::oscilloscope::{DisplayMode, PreviousWindow, Viz, WindowLength};
 use canvas_utils::VizView;
 use common::ref_static_mut;
+use conf::{MAX_CHANNEL_COUNT, MEASUREMENT_COUNT};
 use f0_estimation::YinCtx;
+use trigger::{Trigger, TriggerConfig};
 
 pub(crate) mod conf;
 pub(crate) mod f0_estimation;
+pub(crate) mod measurements;
 pub(crate) mod oscilloscope;
+pub(crate) mod trigger;
 
 #[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "env")]
@@ -29,16 +33,22 @@ pub(crate) fn log(msg: &str) {
 
 const FRAME_SIZE: usize = 128;
 
-/// Used for receiving live samples from the audio thread
-static mut FRAME_DATA_BUFFER: [f32; FRAME_SIZE] = [0.0; FRAME_SIZE];
+/// Used for receiving live samples from the audio thread.  Holds one frame for each channel, one
+/// channel after another.
+static mut FRAME_DATA_BUFFER: [f32; FRAME_SIZE * MAX_CHANNEL_COUNT] =
+  [0.0; FRAME_SIZE * MAX_CHANNEL_COUNT];
 
 static mut VIZ: Viz = Viz {
-  samples: Vec::new(),
+  samples: [const { Vec::new() }; MAX_CHANNEL_COUNT],
+  channel_count: 1,
+  display_mode: DisplayMode::TimeDomain,
+  trigger: Trigger::new(),
   window_length: WindowLength::Beats(1.),
   snap_f0_to_midi: false,
   last_processed_sample_ix: 0,
   last_rendered_beat: 0.0,
   last_rendered_time: 0.0,
+  cur_bpm: 120.,
   image_data: Vec::new(),
   view: VizView {
     dpr: 1,
@@ -61,6 +71,8 @@ static mut VIZ: Viz = Viz {
   yin_ctx: YinCtx::new(),
   cur_frame_y_range: (-1., 1.),
   cur_window_peak_level: 0.,
+  vertical_zoom: 1.,
+  measurements: [f32::NAN; 1 + MAX_CHANNEL_COUNT * MEASUREMENT_COUNT],
 };
 
 static mut DID_SET_PANIC_HOOK: bool = false;
@@ -159,3 +171,46 @@ pub extern "C" fn oscilloscope_renderer_set_snap_f0_to_midi(snap_f0_to_midi: boo
   let viz = ref_static_mut!(VIZ);
   viz.snap_f0_to_midi = snap_f0_to_midi;
 }
+
+#[no_mangle]
+pub extern "C" fn oscilloscope_renderer_set_channel_count(channel_count: usize) {
+  let viz = ref_static_mut!(VIZ);
+  viz.set_channel_count(channel_count);
+}
+
+/// `display_mode` is 0 for time domain and 1 for XY
+#[no_mangle]
+pub extern "C" fn oscilloscope_renderer_set_display_mode(display_mode: u8) {
+  let viz = ref_static_mut!(VIZ);
+  viz.set_display_mode(DisplayMode::from_u8(display_mode));
+}
+
+/// `edge` is 0 for rising and 1 for falling.  When enabled, windows start at trigger points on the
+/// first channel instead of being locked to the clock or detected F0.
+#[no_mangle]
+pub extern "C" fn oscilloscope_renderer_set_trigger(
+  enabled: bool,
+  edge: u8,
+  level: f32,
+  holdoff_ms: f32,
+) {
+  let viz = ref_static_mut!(VIZ);
+  viz.set_trigger(TriggerConfig::from_parts(enabled, edge, level, holdoff_ms));
+}
+
+/// Magnifies the waveform vertically by `vertical_zoom`, which is clamped to
+/// `MIN_VERTICAL_ZOOM..=MAX_VERTICAL_ZOOM`
+#[no_mangle]
+pub extern "C" fn oscilloscope_renderer_set_vertical_zoom(vertical_zoom: f32) {
+  let viz = ref_static_mut!(VIZ);
+  viz.set_vertical_zoom(vertical_zoom);
+}
+
+/// Computes measurements for the displayed window and returns a pointer to them.  See
+/// `Viz::measurements` for the layout.
+#[no_mangle]
+pub extern "C" fn oscilloscope_renderer_compute_measurements(cursor_x: f32) -> *const f32 {
+  let viz = ref_static_mut!(VIZ);
+  viz.compute_measurements(cursor_x);
+  viz.measurements.as_ptr()
+}
@@ -0,0 +1,94 @@
+//! Per-channel measurements over the displayed window
+
+use crate::conf::SAMPLE_RATE;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ChannelMeasurements {
+  pub peak_to_peak: f32,
+  /// Estimated from the average spacing of rising mean crossings.  0 if fewer than two full cycles
+  /// were found.
+  pub frequency_hz: f32,
+  pub rms: f32,
+}
+
+pub(crate) fn measure(samples: &[f32]) -> ChannelMeasurements {
+  if samples.is_empty() {
+    return ChannelMeasurements::default();
+  }
+
+  let mut min = f32::INFINITY;
+  let mut max = f32::NEG_INFINITY;
+  let mut sum = 0.;
+  let mut sum_sq = 0.;
+  for &sample in samples {
+    min = min.min(sample);
+    max = max.max(sample);
+    sum += sample;
+    sum_sq += sample * sample;
+  }
+  let mean = sum / samples.len() as f32;
+
+  // Crossings are detected with a bit of hysteresis around the mean so that noise riding on a
+  // slow signal doesn't register as extra cycles
+  let hysteresis = (max - min) * 0.1;
+  let mut armed = false;
+  let mut first_crossing: Option<f32> = None;
+  let mut last_crossing = 0.;
+  let mut crossing_count = 0;
+  for i in 1..samples.len() {
+    let (last, cur) = (samples[i - 1] - mean, samples[i] - mean);
+    if cur < -hysteresis {
+      armed = true;
+    }
+    if armed && last < 0. && cur >= 0. {
+      armed = false;
+      let crossing = (i - 1) as f32 + -last / (cur - last);
+      first_crossing.get_or_insert(crossing);
+      last_crossing = crossing;
+      crossing_count += 1;
+    }
+  }
+  let frequency_hz = match first_crossing {
+    Some(first_crossing) if crossing_count >= 3 && last_crossing > first_crossing => {
+      let period_samples = (last_crossing - first_crossing) / (crossing_count - 1) as f32;
+      SAMPLE_RATE / period_samples
+    },
+    _ => 0.,
+  };
+
+  ChannelMeasurements {
+    peak_to_peak: max - min,
+    frequency_hz,
+    rms: (sum_sq / samples.len() as f32).sqrt(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_measure_sine() {
+    let freq = 441.;
+    let samples: Vec<f32> = (0..4410)
+      .map(|i| 0.5 * (std::f32::consts::TAU * freq * i as f32 / SAMPLE_RATE).sin() + 0.1)
+      .collect();
+    let res = measure(&samples);
+
+    assert!((res.peak_to_peak - 1.).abs() < 1e-3, "{res:?}");
+    assert!((res.frequency_hz - freq).abs() < 0.5, "{res:?}");
+    let expected_rms = (0.5f32.powi(2) / 2. + 0.1f32.powi(2)).sqrt();
+    assert!((res.rms - expected_rms).abs() < 1e-3, "{res:?}");
+  }
+
+  #[test]
+  fn test_measure_dc() {
+    let res = measure(&[0.25; 256]);
+    assert_eq!(res, ChannelMeasurements {
+      peak_to_peak: 0.,
+      frequency_hz: 0.,
+      rms: 0.25,
+    });
+    assert_eq!(measure(&[]), ChannelMeasurements::default());
+  }
+}
@@ -1,8 +1,13 @@
 use canvas_utils::{write_line_bilinear, VizView};
 
 use crate::{
-  conf::{PAST_WINDOW_COUNT, PEAK_LEVEL_PAST_WINDOW_LOOKBACK_COUNT, SAMPLE_RATE},
+  conf::{
+    CHANNEL_COLORS, MAX_CHANNEL_COUNT, MAX_VERTICAL_ZOOM, MEASUREMENT_COUNT, MIN_VERTICAL_ZOOM,
+    PAST_WINDOW_COUNT, PEAK_LEVEL_PAST_WINDOW_LOOKBACK_COUNT, SAMPLE_RATE,
+  },
   f0_estimation::YinCtx,
+  measurements::measure,
+  trigger::{Trigger, TriggerConfig, TriggerState, TriggeredFrame},
   FRAME_SIZE,
 };
 
@@ -41,10 +46,28 @@ impl WindowLength {
   }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum DisplayMode {
+  /// All active channels are drawn over time, overlaid on top of each other
+  TimeDomain,
+  /// Channel 1 is plotted on the x axis against channel 2 on the y axis
+  XY,
+}
+
+impl DisplayMode {
+  pub(crate) fn from_u8(display_mode: u8) -> Self {
+    match display_mode {
+      0 => DisplayMode::TimeDomain,
+      1 => DisplayMode::XY,
+      _ => panic!("Invalid display mode: {display_mode}"),
+    }
+  }
+}
+
 #[derive(Default)]
 pub(crate) struct PreviousWindow {
   pub image_data: Vec<u8>,
-  pub samples: Vec<f32>,
+  pub samples: [Vec<f32>; MAX_CHANNEL_COUNT],
   pub abs_peak_level: f32,
 }
 
@@ -52,17 +75,21 @@ impl PreviousWindow {
   pub const fn new() -> Self {
     PreviousWindow {
       image_data: Vec::new(),
-      samples: Vec::new(),
+      samples: [const { Vec::new() }; MAX_CHANNEL_COUNT],
       abs_peak_level: 0.,
     }
   }
 }
 
-unsafe fn uninit<T>() -> T { unsafe { std::mem::MaybeUninit::uninit().assume_init() } }
-
 pub(crate) struct Viz {
-  /// Stores all received samples for the currently rendered view
-  pub samples: Vec<f32>,
+  /// Stores all received samples for the currently rendered view, one buffer per channel.  All
+  /// channels are always recorded so that they can be toggled on without waiting for a new window.
+  pub samples: [Vec<f32>; MAX_CHANNEL_COUNT],
+  /// Number of channels that are drawn and measured.  Channel 0 drives window timing, F0
+  /// detection, and triggering.
+  pub channel_count: usize,
+  pub display_mode: DisplayMode,
+  pub trigger: Trigger,
   /// The user-configured length of the window
   pub window_length: WindowLength,
   /// Only used when window length is in wavelengths.  If true, the detected fundamental frequency
@@ -71,6 +98,9 @@ pub(crate) struct Viz {
   pub last_processed_sample_ix: usize,
   pub last_rendered_beat: f32,
   pub last_rendered_time: f32,
+  /// BPM as of the last call to `process`, needed to compute beat-based window lengths while
+  /// committing samples for the trigger
+  pub cur_bpm: f32,
   pub view: VizView,
   /// RGBA image data
   pub image_data: Vec<u8>,
@@ -92,6 +122,12 @@ pub(crate) struct Viz {
   /// window. Values outside of this range are clamped.
   pub cur_frame_y_range: (f32, f32),
   pub cur_window_peak_level: f32,
+  /// Divides the y range, so values above 1 magnify the waveform
+  pub vertical_zoom: f32,
+  /// Cursor position in ms from the start of the window followed by `MEASUREMENT_COUNT` readings
+  /// for each channel: peak-to-peak, frequency in Hz, RMS, and the value under the cursor.
+  /// Readings that aren't available are NaN.
+  pub measurements: [f32; 1 + MAX_CHANNEL_COUNT * MEASUREMENT_COUNT],
 }
 
 fn snap_freq_to_nearest_midi_note(freq_hz: f32) -> f32 {
@@ -109,7 +145,8 @@ impl Viz {
     }
     self.clear_image_data_buffer();
     self.view = view;
-    self.render(cur_bpm, 0, self.samples.len());
+    self.cur_bpm = cur_bpm;
+    self.render(cur_bpm, 0, self.get_renderable_sample_count(cur_bpm));
 
     // previous windows are no longer valid
     self.clear_previous_windows();
@@ -127,29 +164,63 @@ impl Viz {
     }
   }
 
-  pub fn commit_samples(&mut self, samples: &[f32; FRAME_SIZE]) {
+  fn clear_samples(&mut self) {
+    for samples in self.samples.iter_mut() {
+      samples.clear();
+    }
+  }
+
+  /// `frame` holds `FRAME_SIZE` samples for each channel, one channel after another
+  pub fn commit_samples(&mut self, frame: &[f32; FRAME_SIZE * MAX_CHANNEL_COUNT]) {
     if self.frozen && self.frozen_window_complete {
       return;
     }
 
+    let source_frame: &[f32; FRAME_SIZE] = frame[..FRAME_SIZE].try_into().unwrap();
     if matches!(self.window_length, WindowLength::Wavelengths(_)) {
-      self.yin_ctx.process_frame(samples);
+      self.yin_ctx.process_frame(source_frame);
 
       // wavelength multiplier window length depends on frequency detection, and if there is silence
       // than it's unable to do that.  So to avoid filling the buffer infinitely, we wait until
-      // there is non-silence to start recording samples again
+      // there is non-silence to start recording samples again.
+      //
+      // The trigger drops samples on its own while waiting for an edge, so this isn't needed then.
       let f0 = self.yin_ctx.rolling_f0_estimate;
-      if f0 > SAMPLE_RATE / 2.0 {
-        self.samples.clear();
+      if !self.trigger.config.enabled && f0 > SAMPLE_RATE / 2.0 {
+        self.clear_samples();
         return;
       }
     }
 
-    for &sample in samples {
-      self.cur_window_peak_level = self.cur_window_peak_level.max(sample.abs());
-    }
+    let TriggeredFrame {
+      capture_end,
+      trigger_ix,
+    } = if self.trigger.config.enabled {
+      let window_len = self.get_view_length_samples(self.cur_bpm) as usize;
+      self
+        .trigger
+        .process_frame(source_frame, self.samples[0].len(), window_len)
+    } else {
+      TriggeredFrame {
+        capture_end: FRAME_SIZE,
+        trigger_ix: None,
+      }
+    };
+
+    for (channel_ix, samples) in self.samples.iter_mut().enumerate() {
+      let channel_frame = &frame[channel_ix * FRAME_SIZE..(channel_ix + 1) * FRAME_SIZE];
 
-    self.samples.extend_from_slice(samples);
+      if channel_ix < self.channel_count {
+        for &sample in &channel_frame[..capture_end] {
+          self.cur_window_peak_level = self.cur_window_peak_level.max(sample.abs());
+        }
+      }
+
+      samples.extend_from_slice(&channel_frame[..capture_end]);
+      if let Some(trigger_ix) = trigger_ix {
+        samples.extend_from_slice(&channel_frame[trigger_ix..]);
+      }
+    }
   }
 
   fn get_view_length_samples(&self, cur_bpm: f32) -> f32 {
@@ -177,10 +248,25 @@ impl Viz {
         .max(peak);
     }
 
-    let scale = peak.max(1.);
+    let scale = peak.max(1.) / self.vertical_zoom;
     (-scale, scale)
   }
 
+  /// When triggering, samples past the end of the window are held back until the next trigger
+  /// either starts a new window with them or they're dropped, so they shouldn't be drawn.
+  fn get_renderable_sample_count(&self, cur_bpm: f32) -> usize {
+    let sample_count = self.samples[0].len();
+    if !self.trigger.config.enabled {
+      return sample_count;
+    }
+
+    let window_len = self.get_view_length_samples(cur_bpm) as usize;
+    match self.trigger.state {
+      TriggerState::Triggered { start_ix } => sample_count.min(window_len).min(start_ix),
+      _ => sample_count.min(window_len),
+    }
+  }
+
   fn compute_sample_coords(&self, cur_bpm: f32, sample_ix: usize, sample: f32) -> (f32, f32) {
     let (min_y, max_y) = self.cur_frame_y_range;
 
@@ -199,31 +285,64 @@ impl Viz {
     (x_px, y_px)
   }
 
-  fn render_one_sample(&mut self, cur_bpm: f32, sample_ix: usize) {
-    let sample = self.samples[sample_ix];
-    let (x_px, y_px) = self.compute_sample_coords(cur_bpm, sample_ix, sample);
-
-    let (mut last_x_px, mut last_y_px) = if sample_ix == 0 {
-      (x_px, y_px)
-    } else {
-      let last_sample = self.samples[sample_ix - 1];
-      self.compute_sample_coords(cur_bpm, sample_ix - 1, last_sample)
-    };
+  /// Maps a pair of samples to a point in the largest square that fits in the center of the view
+  fn compute_xy_coords(&self, x_sample: f32, y_sample: f32) -> (f32, f32) {
+    let (min_y, max_y) = self.cur_frame_y_range;
+    let size = self.view.width.min(self.view.height) as f32;
+    let x_offset = (self.view.width as f32 - size) / 2.;
+    let y_offset = (self.view.height as f32 - size) / 2.;
+    let to_px = |sample: f32| clamp(0., size - 1., (sample - min_y) / (max_y - min_y) * size);
 
-    // Avoid drawing lines backwards if we've just looped around
-    if last_x_px >= x_px {
-      last_x_px = x_px;
-      last_y_px = y_px;
-    }
+    let x_px = x_offset + to_px(x_sample);
+    // y is flipped
+    let y_px = y_offset + (size - 1.) - to_px(y_sample);
+    (x_px, y_px)
+  }
 
+  fn render_one_sample(&mut self, cur_bpm: f32, sample_ix: usize) {
     let pixels: &mut [(u8, u8, u8, u8)] = unsafe {
       std::slice::from_raw_parts_mut(
         self.image_data.as_mut_ptr() as *mut _,
         self.image_data.len() / 4,
       )
     };
-    let color = (180, 0, 180, 255);
-    write_line_bilinear(pixels, &self.view, last_x_px, last_y_px, x_px, y_px, color);
+
+    match self.display_mode {
+      DisplayMode::TimeDomain =>
+        for channel_ix in 0..self.channel_count {
+          let samples = &self.samples[channel_ix];
+          let (x_px, y_px) = self.compute_sample_coords(cur_bpm, sample_ix, samples[sample_ix]);
+
+          let (mut last_x_px, mut last_y_px) = if sample_ix == 0 {
+            (x_px, y_px)
+          } else {
+            self.compute_sample_coords(cur_bpm, sample_ix - 1, samples[sample_ix - 1])
+          };
+
+          // Avoid drawing lines backwards if we've just looped around
+          if last_x_px >= x_px {
+            last_x_px = x_px;
+            last_y_px = y_px;
+          }
+
+          let color = CHANNEL_COLORS[channel_ix];
+          write_line_bilinear(pixels, &self.view, last_x_px, last_y_px, x_px, y_px, color);
+        },
+      DisplayMode::XY => {
+        // With a single channel, it's plotted against itself
+        let y_channel_ix = if self.channel_count > 1 { 1 } else { 0 };
+        let (xs, ys) = (&self.samples[0], &self.samples[y_channel_ix]);
+        let (x_px, y_px) = self.compute_xy_coords(xs[sample_ix], ys[sample_ix]);
+        let (last_x_px, last_y_px) = if sample_ix == 0 {
+          (x_px, y_px)
+        } else {
+          self.compute_xy_coords(xs[sample_ix - 1], ys[sample_ix - 1])
+        };
+
+        let color = CHANNEL_COLORS[0];
+        write_line_bilinear(pixels, &self.view, last_x_px, last_y_px, x_px, y_px, color);
+      },
+    }
   }
 
   fn render(
@@ -241,57 +360,114 @@ impl Viz {
 
   /// We clear the viz and start drawing from the start again
   fn maybe_clear_window(&mut self, cur_bpm: f32, cur_beat: f32, cur_time: f32) {
-    let overrun_samples = match self.window_length {
-      WindowLength::Beats(beats) => {
-        let cur_beats = cur_beat % beats;
-        if cur_beats < self.last_rendered_beat % beats {
-          let phase = cur_beats / beats;
-          let samples_per_beat = self.get_view_length_samples(cur_bpm) as f32 / beats;
-          let samples = (samples_per_beat * beats * phase) as usize;
-          Some(samples)
-        } else {
-          None
-        }
-      },
-      WindowLength::Seconds(secs) => {
-        let cur_secs = cur_time % secs;
-        if cur_secs < self.last_rendered_time % secs {
-          let phase = cur_secs / secs;
-          let samples_per_sec = self.get_view_length_samples(cur_bpm) as f32 / secs;
-          let samples = (samples_per_sec * secs * phase) as usize;
-          Some(samples)
-        } else {
-          None
-        }
-      },
-      WindowLength::Samples(samples) =>
-        if self.samples.len() >= samples {
-          Some(self.samples.len() % samples)
-        } else {
-          None
+    let carry_start_ix = if self.trigger.config.enabled {
+      let TriggerState::Triggered { start_ix } = self.trigger.state else {
+        return;
+      };
+
+      // Nothing is captured while waiting for the first trigger after enabling it, so there's no
+      // window to keep.  The samples from the trigger on just continue as the current one.
+      if start_ix == 0 {
+        self.trigger.state = TriggerState::Capturing;
+        return;
+      }
+
+      // Finish drawing the current window up to where the next one was triggered so it's complete
+      // when it gets swapped into the previous windows
+      let end_ix = self.get_renderable_sample_count(cur_bpm);
+      self.render(
+        cur_bpm,
+        self.last_processed_sample_ix,
+        end_ix.max(self.last_processed_sample_ix),
+      );
+
+      if self.frozen {
+        self.frozen_window_complete = true;
+        return;
+      }
+
+      start_ix
+    } else {
+      let overrun_samples = match self.window_length {
+        WindowLength::Beats(beats) => {
+          let cur_beats = cur_beat % beats;
+          if cur_beats < self.last_rendered_beat % beats {
+            let phase = cur_beats / beats;
+            let samples_per_beat = self.get_view_length_samples(cur_bpm) as f32 / beats;
+            let samples = (samples_per_beat * beats * phase) as usize;
+            Some(samples)
+          } else {
+            None
+          }
+        },
+        WindowLength::Seconds(secs) => {
+          let cur_secs = cur_time % secs;
+          if cur_secs < self.last_rendered_time % secs {
+            let phase = cur_secs / secs;
+            let samples_per_sec = self.get_view_length_samples(cur_bpm) as f32 / secs;
+            let samples = (samples_per_sec * secs * phase) as usize;
+            Some(samples)
+          } else {
+            None
+          }
+        },
+        WindowLength::Samples(samples) =>
+          if self.samples[0].len() >= samples {
+            Some(self.samples[0].len() % samples)
+          } else {
+            None
+          },
+        WindowLength::Wavelengths(_) => {
+          let window_len = self.get_view_length_samples(cur_bpm);
+
+          if self.samples[0].len() >= window_len as usize {
+            Some(self.samples[0].len() % window_len as usize)
+          } else {
+            None
+          }
         },
-      WindowLength::Wavelengths(_) => {
-        let window_len = self.get_view_length_samples(cur_bpm);
+      };
 
-        if self.samples.len() >= window_len as usize {
-          Some(self.samples.len() % window_len as usize)
-        } else {
-          None
-        }
-      },
-    };
+      let Some(overrun_samples) = overrun_samples else {
+        return;
+      };
 
-    let needs_clear = overrun_samples.is_some();
-    if !needs_clear {
-      return;
-    }
-    let overrun_samples = overrun_samples.unwrap();
+      if self.frozen {
+        self.frozen_window_complete = true;
+        return;
+      }
 
-    if self.frozen {
-      self.frozen_window_complete = true;
-      return;
-    }
+      let carry_len = match self.window_length {
+        WindowLength::Wavelengths(_) => {
+          // We want to be as precise as possible with the window length, so we take the last
+          // `overrun_samples` samples from the previous window and concat to the front of the
+          // current window
+
+          if overrun_samples > FRAME_SIZE * 8 {
+            crate::log(&format!(
+              "overrun_samples {} > FRAME_SIZE*4; window_len={}",
+              overrun_samples,
+              self.get_view_length_samples(cur_bpm)
+            ));
+          }
+          overrun_samples.min(FRAME_SIZE * 8)
+        },
+        _ => {
+          // Retain the last `FRAME_SIZE` samples from the previous window and concat to the front
+          // so we don't miss any transients or anything
 
+          self.samples[0].len().min(FRAME_SIZE)
+        },
+      };
+      self.samples[0].len() - carry_len.min(self.samples[0].len())
+    };
+
+    self.swap_window(carry_start_ix);
+  }
+
+  /// Moves the current window into the previous windows and starts a new one with the samples
+  /// from `carry_start_ix` onwards
+  fn swap_window(&mut self, carry_start_ix: usize) {
     self.yin_ctx.cur_f0_estimate = self.yin_ctx.rolling_f0_estimate;
 
     // Swap our current image data buffer and samples into previous windows and replace with the
@@ -317,47 +493,25 @@ impl Viz {
 
     self.clear_image_data_buffer();
 
-    let mut old_samples: [f32; FRAME_SIZE * 8] = unsafe { uninit() };
-    let old_samples_len = match self.window_length {
-      WindowLength::Wavelengths(_) => {
-        // We want to be as precise as possible with the window length, so we take the last
-        // `overrun_samples` samples from the previous window and concat to the front of the
-        // current window
-
-        if overrun_samples > FRAME_SIZE * 8 {
-          crate::log(&format!(
-            "overrun_samples {} > FRAME_SIZE*4; window_len={}",
-            overrun_samples,
-            self.get_view_length_samples(cur_bpm)
-          ));
-        }
-        overrun_samples.min(FRAME_SIZE * 8)
-      },
-      _ => {
-        // Retain the last `FRAME_SIZE` samples from the previous window and concat to the front so
-        // we don't miss any transients or anything
-
-        new_prev_window.samples.len().min(FRAME_SIZE)
-      },
-    };
-
     self.last_processed_sample_ix = 0;
-    old_samples[..old_samples_len]
-      .copy_from_slice(&new_prev_window.samples[new_prev_window.samples.len() - old_samples_len..]);
-    self.samples.clear();
-    self
-      .samples
-      .extend_from_slice(&old_samples[..old_samples_len]);
+    for (samples, prev_samples) in self.samples.iter_mut().zip(&new_prev_window.samples) {
+      samples.clear();
+      samples.extend_from_slice(&prev_samples[carry_start_ix.min(prev_samples.len())..]);
+    }
+    if self.trigger.config.enabled {
+      self.trigger.state = TriggerState::Capturing;
+    }
 
     self.previous_windows[0] = new_prev_window;
   }
 
   pub fn process(&mut self, cur_bpm: f32, cur_beat: f32, cur_time: f32) {
-    if self.last_processed_sample_ix == self.samples.len() {
+    self.cur_bpm = cur_bpm;
+    if self.last_processed_sample_ix == self.samples[0].len() {
       return;
     }
 
-    if matches!(self.window_length, WindowLength::Wavelengths(_)) {
+    if matches!(self.window_length, WindowLength::Wavelengths(_)) && !self.trigger.config.enabled {
       let f0 = self.yin_ctx.rolling_f0_estimate;
       if f0 > SAMPLE_RATE / 2.0 {
         return;
@@ -370,23 +524,75 @@ impl Viz {
       return;
     }
 
-    self.render(cur_bpm, self.last_processed_sample_ix, self.samples.len());
+    let end_ix = self.get_renderable_sample_count(cur_bpm);
+    self.render(
+      cur_bpm,
+      self.last_processed_sample_ix,
+      end_ix.max(self.last_processed_sample_ix),
+    );
 
     self.last_rendered_beat = cur_beat;
     self.last_rendered_time = cur_time;
   }
 
-  pub(crate) fn set_window(&mut self, window: WindowLength) {
-    self.window_length = window;
+  /// Clears the image and re-draws the current window from its start
+  fn redraw_current_window(&mut self) {
     if !self.frozen_window_complete {
       self.last_processed_sample_ix = 0;
       self.clear_image_data_buffer();
     }
+  }
+
+  pub(crate) fn set_window(&mut self, window: WindowLength) {
+    self.window_length = window;
+    self.redraw_current_window();
 
     // previous windows are no longer valid
     self.clear_previous_windows();
   }
 
+  pub(crate) fn set_channel_count(&mut self, channel_count: usize) {
+    self.channel_count = channel_count.clamp(1, MAX_CHANNEL_COUNT);
+    self.redraw_current_window();
+  }
+
+  pub(crate) fn set_display_mode(&mut self, display_mode: DisplayMode) {
+    if self.display_mode == display_mode {
+      return;
+    }
+
+    self.display_mode = display_mode;
+    self.redraw_current_window();
+    self.clear_previous_windows();
+  }
+
+  pub(crate) fn set_trigger(&mut self, config: TriggerConfig) {
+    let enabled_changed = config.enabled != self.trigger.config.enabled;
+    self.trigger.config = config;
+    if !enabled_changed || self.frozen_window_complete {
+      return;
+    }
+
+    // Windows recorded in the other mode aren't aligned to triggers, so start over
+    self.trigger.reset();
+    self.clear_samples();
+    self.redraw_current_window();
+    self.clear_previous_windows();
+  }
+
+  pub(crate) fn set_vertical_zoom(&mut self, vertical_zoom: f32) {
+    let vertical_zoom = vertical_zoom.clamp(MIN_VERTICAL_ZOOM, MAX_VERTICAL_ZOOM);
+    if self.vertical_zoom == vertical_zoom {
+      return;
+    }
+
+    self.vertical_zoom = vertical_zoom;
+    self.cur_frame_y_range = self.compute_y_range();
+    self.redraw_current_window();
+    // previous windows were drawn at the old scale
+    self.clear_previous_windows();
+  }
+
   fn clear_previous_windows(&mut self) {
     for window in self.previous_windows.iter_mut() {
       *window = PreviousWindow::default();
@@ -403,6 +609,55 @@ impl Viz {
     self.frozen_window_complete = false;
   }
 
+  /// Samples for the window that's currently being displayed, as selected by `get_image_data`
+  fn get_displayed_samples(&self) -> &[Vec<f32>; MAX_CHANNEL_COUNT] {
+    if self.frame_by_frame {
+      return &self.samples;
+    }
+
+    &self.previous_windows[0].samples
+  }
+
+  /// Updates `measurements` for the displayed window.  `cursor_x` is in CSS pixels from the left
+  /// edge of the view; pass a negative value if there's no cursor.
+  pub(crate) fn compute_measurements(&mut self, cursor_x: f32) {
+    let window_len = self.get_view_length_samples(self.cur_bpm);
+    let cursor_ix = if cursor_x >= 0. && cursor_x < self.view.width as f32 {
+      Some((cursor_x / self.view.width as f32 * window_len) as usize)
+    } else {
+      None
+    };
+
+    let mut measurements = [f32::NAN; 1 + MAX_CHANNEL_COUNT * MEASUREMENT_COUNT];
+    measurements[0] = match cursor_ix {
+      Some(cursor_ix) => cursor_ix as f32 / SAMPLE_RATE * 1000.,
+      None => f32::NAN,
+    };
+
+    let displayed_samples = self.get_displayed_samples();
+    for (channel_ix, samples) in displayed_samples
+      .iter()
+      .take(self.channel_count)
+      .enumerate()
+    {
+      let samples = &samples[..samples.len().min(window_len as usize)];
+      let channel_measurements = measure(samples);
+      let value_at_cursor = cursor_ix
+        .and_then(|cursor_ix| samples.get(cursor_ix).copied())
+        .unwrap_or(f32::NAN);
+
+      let offset = 1 + channel_ix * MEASUREMENT_COUNT;
+      measurements[offset..offset + MEASUREMENT_COUNT].copy_from_slice(&[
+        channel_measurements.peak_to_peak,
+        channel_measurements.frequency_hz,
+        channel_measurements.rms,
+        value_at_cursor,
+      ]);
+    }
+
+    self.measurements = measurements;
+  }
+
   pub(crate) fn get_image_data(&self) -> &[u8] {
     if self.frame_by_frame {
       return &self.image_data;
@@ -0,0 +1,216 @@
+//! Edge triggering, used as an alternative to F0 locking for keeping the displayed waveform stable
+
+use crate::conf::SAMPLE_RATE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerEdge {
+  Rising,
+  Falling,
+}
+
+impl TriggerEdge {
+  pub(crate) fn from_u8(edge: u8) -> Self {
+    match edge {
+      0 => TriggerEdge::Rising,
+      1 => TriggerEdge::Falling,
+      _ => panic!("Invalid trigger edge: {edge}"),
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct TriggerConfig {
+  pub enabled: bool,
+  pub edge: TriggerEdge,
+  pub level: f32,
+  /// Minimum number of samples after a trigger before the next one is accepted
+  pub holdoff_samples: usize,
+}
+
+impl TriggerConfig {
+  pub(crate) const fn new() -> Self {
+    TriggerConfig {
+      enabled: false,
+      edge: TriggerEdge::Rising,
+      level: 0.,
+      holdoff_samples: 0,
+    }
+  }
+
+  pub(crate) fn from_parts(enabled: bool, edge: u8, level: f32, holdoff_ms: f32) -> Self {
+    TriggerConfig {
+      enabled,
+      edge: TriggerEdge::from_u8(edge),
+      level,
+      holdoff_samples: (holdoff_ms.max(0.) * 0.001 * SAMPLE_RATE) as usize,
+    }
+  }
+
+  #[inline]
+  pub(crate) fn is_crossing(&self, last_sample: f32, sample: f32) -> bool {
+    match self.edge {
+      TriggerEdge::Rising => last_sample < self.level && sample >= self.level,
+      TriggerEdge::Falling => last_sample > self.level && sample <= self.level,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TriggerState {
+  /// The current window is complete and incoming samples are dropped until the next trigger
+  Armed,
+  /// A trigger was detected at `start_ix` in the current window's samples.  Everything from there
+  /// on belongs to the next window, which is swapped in on the next call to `process`.
+  Triggered { start_ix: usize },
+  /// The current window was started by a trigger and is still being filled
+  Capturing,
+}
+
+pub(crate) struct Trigger {
+  pub config: TriggerConfig,
+  pub state: TriggerState,
+  last_sample: f32,
+  holdoff_remaining: usize,
+}
+
+/// Describes which parts of a frame should be appended to the window's samples after running it
+/// through the trigger
+#[derive(Debug, PartialEq)]
+pub(crate) struct TriggeredFrame {
+  /// Samples `[0, capture_end)` continue the current window
+  pub capture_end: usize,
+  /// Samples `[trigger_ix, len)` start the next window
+  pub trigger_ix: Option<usize>,
+}
+
+impl Trigger {
+  pub(crate) const fn new() -> Self {
+    Trigger {
+      config: TriggerConfig::new(),
+      state: TriggerState::Armed,
+      last_sample: 0.,
+      holdoff_remaining: 0,
+    }
+  }
+
+  pub(crate) fn reset(&mut self) {
+    self.state = TriggerState::Armed;
+    self.holdoff_remaining = 0;
+  }
+
+  /// Scans one frame of the trigger source channel.  `window_samples` is the number of samples
+  /// already in the current window and `window_len` is the target length of a window.
+  pub(crate) fn process_frame(
+    &mut self,
+    frame: &[f32],
+    window_samples: usize,
+    window_len: usize,
+  ) -> TriggeredFrame {
+    let mut capture_end = if matches!(self.state, TriggerState::Triggered { .. }) {
+      frame.len()
+    } else {
+      0
+    };
+    let mut trigger_ix = None;
+
+    for (i, &sample) in frame.iter().enumerate() {
+      let last_sample = std::mem::replace(&mut self.last_sample, sample);
+      self.holdoff_remaining = self.holdoff_remaining.saturating_sub(1);
+
+      match self.state {
+        TriggerState::Triggered { .. } => (),
+        TriggerState::Capturing => {
+          capture_end = i + 1;
+          if window_samples + capture_end >= window_len {
+            self.state = TriggerState::Armed;
+          }
+        },
+        TriggerState::Armed =>
+          if self.holdoff_remaining == 0 && self.config.is_crossing(last_sample, sample) {
+            trigger_ix = Some(i);
+            self.state = TriggerState::Triggered {
+              start_ix: window_samples + capture_end,
+            };
+            self.holdoff_remaining = self.config.holdoff_samples;
+          },
+      }
+    }
+
+    TriggeredFrame {
+      capture_end,
+      trigger_ix,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mk_trigger(edge: TriggerEdge, level: f32, holdoff_samples: usize) -> Trigger {
+    let mut trigger = Trigger::new();
+    trigger.config = TriggerConfig {
+      enabled: true,
+      edge,
+      level,
+      holdoff_samples,
+    };
+    trigger
+  }
+
+  #[test]
+  fn test_edge_detection() {
+    let frame = [-1., -0.5, 0.2, 0.6, 0.1, -0.4];
+
+    let mut rising = mk_trigger(TriggerEdge::Rising, 0.5, 0);
+    let res = rising.process_frame(&frame, 0, 100);
+    assert_eq!(res, TriggeredFrame {
+      capture_end: 0,
+      trigger_ix: Some(3)
+    });
+    assert_eq!(rising.state, TriggerState::Triggered { start_ix: 0 });
+
+    let mut falling = mk_trigger(TriggerEdge::Falling, 0., 0);
+    let res = falling.process_frame(&frame, 0, 100);
+    assert_eq!(res.trigger_ix, Some(5));
+  }
+
+  #[test]
+  fn test_capture_then_rearm() {
+    let mut trigger = mk_trigger(TriggerEdge::Rising, 0., 0);
+    trigger.state = TriggerState::Capturing;
+
+    // The window fills up after 2 more samples, then the next rising crossing starts a new one
+    let frame = [0.5, -0.5, -0.5, 0.5, 0.5];
+    let res = trigger.process_frame(&frame, 8, 10);
+    assert_eq!(res, TriggeredFrame {
+      capture_end: 2,
+      trigger_ix: Some(3)
+    });
+    assert_eq!(trigger.state, TriggerState::Triggered { start_ix: 10 });
+
+    // Once triggered, whole frames are kept until the window is swapped
+    let res = trigger.process_frame(&frame, 12, 10);
+    assert_eq!(res, TriggeredFrame {
+      capture_end: frame.len(),
+      trigger_ix: None
+    });
+  }
+
+  #[test]
+  fn test_holdoff() {
+    let mut trigger = mk_trigger(TriggerEdge::Rising, 0., 8);
+    let frame = [-1., 1., -1., 1., -1., 1.];
+    let res = trigger.process_frame(&frame, 0, 0);
+    assert_eq!(res.trigger_ix, Some(1));
+
+    // Simulate the window being swapped in.  Zero-length windows are complete immediately, so only
+    // the holdoff keeps the crossing at index 1 from re-triggering.
+    trigger.state = TriggerState::Capturing;
+    let res = trigger.process_frame(&frame, 0, 0);
+    assert_eq!(res, TriggeredFrame {
+      capture_end: 1,
+      trigger_ix: Some(3)
+    });
+  }
+}
@@ -0,0 +1,12 @@
+/// Lowest detectable frequency.  Each estimate analyzes two of its periods.
+pub const YIN_MIN_FREQ: f32 = 50.;
+/// Highest detectable frequency
+pub const YIN_MAX_FREQ: f32 = 2_000.;
+pub const YIN_THRESHOLD: f32 = 0.15;
+/// Number of samples between estimates
+pub const HOP_SIZE: usize = 512;
+
+/// Confidence may drop this far below the note-on threshold before an active note is released
+pub const CONFIDENCE_HYSTERESIS: f32 = 0.1;
+/// Number of consecutive estimates that must agree on a new note before it's played
+pub const STABLE_ESTIMATE_COUNT: usize = 2;
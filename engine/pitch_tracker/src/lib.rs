@@ -0,0 +1,156 @@
+use dsp::FRAME_SIZE;
+
+use crate::tracker::{NoteEvent, PitchTracker, PitchTrackerConfig};
+
+pub mod conf;
+pub mod tracker;
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "env")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+
+  fn on_note_on(note: u8, velocity: u8);
+
+  fn on_note_off(note: u8);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+extern "C" fn log_err(_ptr: *const u8, _len: usize) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe extern "C" fn on_note_on(_note: u8, _velocity: u8) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe extern "C" fn on_note_off(_note: u8) {}
+
+fn emit_note_event(evt: NoteEvent) {
+  match evt {
+    NoteEvent::On { note, velocity } => unsafe { on_note_on(note, velocity) },
+    NoteEvent::Off { note } => unsafe { on_note_off(note) },
+  }
+}
+
+/// Layout of the readings buffer exposed to JS.  Updated every `HOP_SIZE` samples.
+///
+/// 0: estimated f0 in Hz, or 0 if the input is silent
+/// 1: nearest MIDI note number to the estimated f0
+/// 2: deviation of the estimated f0 from that note in cents
+/// 3: confidence of the estimate in [0, 1]
+/// 4: input level in dBFS
+/// 5: active (gated) MIDI note number, or -1 if no note is playing
+/// 6: f0 of the last estimate that passed the gate in Hz
+/// 7: `6` as a fractional MIDI note number
+pub const READING_COUNT: usize = 8;
+
+pub struct PitchTrackerCtx {
+  pub io_buffer: [f32; FRAME_SIZE],
+  pub tracker: PitchTracker,
+  pub readings: [f32; READING_COUNT],
+}
+
+impl PitchTrackerCtx {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      io_buffer: [0.; FRAME_SIZE],
+      tracker: PitchTracker::new(sample_rate),
+      readings: [0., 0., 0., 0., f32::NEG_INFINITY, -1., 0., 0.],
+    }
+  }
+
+  fn update_readings(&mut self) {
+    let tracker = &self.tracker;
+    let nearest_note = tracker.midi_note.round();
+    self.readings = [
+      tracker.f0,
+      nearest_note,
+      (tracker.midi_note - nearest_note) * 100.,
+      tracker.confidence,
+      tracker.level_db,
+      tracker.active_note.map(|note| note as f32).unwrap_or(-1.),
+      tracker.held_f0,
+      if tracker.held_f0 > 0. {
+        tracker::freq_to_midi_note(tracker.held_f0)
+      } else {
+        0.
+      },
+    ];
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn pitch_tracker_create_ctx(sample_rate: f32) -> *mut PitchTrackerCtx {
+  common::set_raw_panic_hook(log_err);
+
+  Box::into_raw(Box::new(PitchTrackerCtx::new(sample_rate)))
+}
+
+/// Returns a pointer to the buffer that input is written to before processing
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `pitch_tracker_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_tracker_get_io_buf_ptr(ctx: *mut PitchTrackerCtx) -> *mut f32 {
+  let ctx = &mut *ctx;
+  ctx.io_buffer.as_mut_ptr()
+}
+
+/// Returns a pointer to the `READING_COUNT` readings, which are updated by each call to
+/// `pitch_tracker_process`
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `pitch_tracker_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_tracker_get_readings_ptr(ctx: *mut PitchTrackerCtx) -> *const f32 {
+  let ctx = &mut *ctx;
+  ctx.readings.as_ptr()
+}
+
+/// Sets the note gating config.  Out-of-range values are clamped.
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `pitch_tracker_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_tracker_set_config(
+  ctx: *mut PitchTrackerCtx,
+  gate_db: f32,
+  gate_hysteresis_db: f32,
+  min_confidence: f32,
+  note_hysteresis_cents: f32,
+) {
+  let ctx = &mut *ctx;
+  ctx.tracker.config = PitchTrackerConfig {
+    gate_db,
+    gate_hysteresis_db: gate_hysteresis_db.max(0.),
+    min_confidence: min_confidence.clamp(0., 1.),
+    note_hysteresis_cents: note_hysteresis_cents.clamp(0., 100.),
+  };
+}
+
+/// Analyzes the contents of the IO buffer, emitting note on/off events through the imported
+/// `on_note_on` and `on_note_off` functions
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `pitch_tracker_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_tracker_process(ctx: *mut PitchTrackerCtx) {
+  let ctx = &mut *ctx;
+  ctx.tracker.process(&ctx.io_buffer, emit_note_event);
+  ctx.update_readings();
+}
+
+/// Releases the active note and clears the analysis state
+///
+/// # Safety
+///
+/// `ctx` must be a pointer returned by `pitch_tracker_create_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn pitch_tracker_reset(ctx: *mut PitchTrackerCtx) {
+  let ctx = &mut *ctx;
+  ctx.tracker.reset(emit_note_event);
+  ctx.update_readings();
+}
@@ -0,0 +1,308 @@
+//! Monophonic pitch tracking with gated note events
+
+use dsp::pitch::YinEstimator;
+
+use crate::conf::{
+  CONFIDENCE_HYSTERESIS, HOP_SIZE, STABLE_ESTIMATE_COUNT, YIN_MAX_FREQ, YIN_MIN_FREQ, YIN_THRESHOLD,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PitchTrackerConfig {
+  /// Level in dBFS that the input must reach for a note to start
+  pub gate_db: f32,
+  /// How far below `gate_db` the level may fall before the active note is released
+  pub gate_hysteresis_db: f32,
+  /// Confidence in [0, 1] that the input must reach for a note to start
+  pub min_confidence: f32,
+  /// How far past the midpoint between two notes the pitch must move before the active note
+  /// changes
+  pub note_hysteresis_cents: f32,
+}
+
+impl Default for PitchTrackerConfig {
+  fn default() -> Self {
+    PitchTrackerConfig {
+      gate_db: -45.,
+      gate_hysteresis_db: 6.,
+      min_confidence: 0.85,
+      note_hysteresis_cents: 30.,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteEvent {
+  On { note: u8, velocity: u8 },
+  Off { note: u8 },
+}
+
+pub fn freq_to_midi_note(freq: f32) -> f32 { 69. + 12. * (freq / 440.).log2() }
+
+pub fn midi_note_to_freq(note: f32) -> f32 { 440. * 2f32.powf((note - 69.) / 12.) }
+
+pub struct PitchTracker {
+  pub config: PitchTrackerConfig,
+  sample_rate: f32,
+  yin: YinEstimator,
+  /// Shortest period considered, in samples
+  min_period: usize,
+  /// The most recent `2 * yin.max_period()` samples of the input, oldest first
+  window: Vec<f32>,
+  samples_until_estimate: usize,
+  /// Estimated fundamental frequency of the latest analysis window in Hz, or 0 if it was silent
+  pub f0: f32,
+  /// `f0` as a fractional MIDI note number
+  pub midi_note: f32,
+  /// How periodic the latest analysis window was, in [0, 1]
+  pub confidence: f32,
+  /// RMS level of the latest analysis window in dBFS
+  pub level_db: f32,
+  /// The last `f0` that passed the gate.  Holds its value while the gate is closed so that it can
+  /// drive oscillators without them jumping around between notes.
+  pub held_f0: f32,
+  pub active_note: Option<u8>,
+  candidate_note: Option<u8>,
+  candidate_count: usize,
+}
+
+impl PitchTracker {
+  pub fn new(sample_rate: f32) -> Self {
+    let max_period = (sample_rate / YIN_MIN_FREQ) as usize;
+    PitchTracker {
+      config: PitchTrackerConfig::default(),
+      sample_rate,
+      yin: YinEstimator::new(max_period),
+      min_period: (sample_rate / YIN_MAX_FREQ) as usize,
+      window: vec![0.; max_period * 2],
+      samples_until_estimate: HOP_SIZE,
+      f0: 0.,
+      midi_note: 0.,
+      confidence: 0.,
+      level_db: f32::NEG_INFINITY,
+      held_f0: 0.,
+      active_note: None,
+      candidate_note: None,
+      candidate_count: 0,
+    }
+  }
+
+  /// Consumes `samples`, re-estimating the pitch every `HOP_SIZE` samples and reporting note
+  /// changes through `on_event`
+  pub fn process(&mut self, mut samples: &[f32], mut on_event: impl FnMut(NoteEvent)) {
+    while !samples.is_empty() {
+      let chunk_len = samples.len().min(self.samples_until_estimate);
+      let (chunk, rest) = samples.split_at(chunk_len);
+      let window_len = self.window.len();
+      self.window.copy_within(chunk_len.., 0);
+      self.window[window_len - chunk_len..].copy_from_slice(chunk);
+      samples = rest;
+
+      self.samples_until_estimate -= chunk_len;
+      if self.samples_until_estimate == 0 {
+        self.samples_until_estimate = HOP_SIZE;
+        self.estimate(&mut on_event);
+      }
+    }
+  }
+
+  /// Releases the active note, if any, and clears all analysis state
+  pub fn reset(&mut self, mut on_event: impl FnMut(NoteEvent)) {
+    if let Some(note) = self.active_note.take() {
+      on_event(NoteEvent::Off { note });
+    }
+    self.window.fill(0.);
+    self.samples_until_estimate = HOP_SIZE;
+    self.f0 = 0.;
+    self.midi_note = 0.;
+    self.confidence = 0.;
+    self.level_db = f32::NEG_INFINITY;
+    self.held_f0 = 0.;
+    self.candidate_note = None;
+    self.candidate_count = 0;
+  }
+
+  fn estimate(&mut self, on_event: &mut impl FnMut(NoteEvent)) {
+    let mean_square = self.window.iter().map(|&s| s * s).sum::<f32>() / self.window.len() as f32;
+    self.level_db = 10. * mean_square.max(1e-20).log10();
+
+    let estimate = self.yin.estimate(
+      &self.window,
+      YIN_THRESHOLD,
+      self.min_period,
+      self.sample_rate,
+    );
+    self.f0 = estimate.f0;
+    self.confidence = estimate.confidence;
+    self.midi_note = if self.f0 > 0. {
+      freq_to_midi_note(self.f0)
+    } else {
+      0.
+    };
+
+    let config = &self.config;
+    let can_open = self.level_db >= config.gate_db && self.confidence >= config.min_confidence;
+    let can_stay_open = self.level_db >= config.gate_db - config.gate_hysteresis_db
+      && self.confidence >= config.min_confidence - CONFIDENCE_HYSTERESIS;
+    if can_stay_open && self.f0 > 0. {
+      self.held_f0 = self.f0;
+    }
+
+    if let Some(active_note) = self.active_note {
+      if !can_stay_open {
+        on_event(NoteEvent::Off { note: active_note });
+        self.active_note = None;
+        self.candidate_note = None;
+        return;
+      }
+
+      let max_deviation = 0.5 + config.note_hysteresis_cents / 100.;
+      if (self.midi_note - active_note as f32).abs() <= max_deviation {
+        self.candidate_note = None;
+        return;
+      }
+    } else if !can_open {
+      self.candidate_note = None;
+      return;
+    }
+
+    let note = self.midi_note.round().clamp(0., 127.) as u8;
+    if self.candidate_note == Some(note) {
+      self.candidate_count += 1;
+    } else {
+      self.candidate_note = Some(note);
+      self.candidate_count = 1;
+    }
+    if self.candidate_count < STABLE_ESTIMATE_COUNT {
+      return;
+    }
+
+    if let Some(active_note) = self.active_note {
+      on_event(NoteEvent::Off { note: active_note });
+    }
+    // map levels from the gate up to 0 dBFS onto the upper part of the velocity range
+    let velocity_pos = ((self.level_db - config.gate_db) / -config.gate_db.min(-1.)).clamp(0., 1.);
+    let velocity = (40. + velocity_pos * 87.) as u8;
+    on_event(NoteEvent::On { note, velocity });
+    self.active_note = Some(note);
+    self.candidate_note = None;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tone(freq: f32, amplitude: f32, len: usize, phase: &mut f32) -> Vec<f32> {
+    (0..len)
+      .map(|_| {
+        *phase += freq / dsp::SAMPLE_RATE;
+        let p = *phase * 2. * std::f32::consts::PI;
+        amplitude * (0.7 * p.sin() + 0.3 * (2. * p).sin())
+      })
+      .collect()
+  }
+
+  /// Peak amplitude of `tone` whose RMS level is `level_db`
+  fn amplitude_for_level(level_db: f32) -> f32 {
+    10f32.powf(level_db / 20.) / ((0.7f32.powi(2) + 0.3f32.powi(2)) / 2.).sqrt()
+  }
+
+  fn run(tracker: &mut PitchTracker, samples: &[f32]) -> Vec<NoteEvent> {
+    let mut events = Vec::new();
+    for frame in samples.chunks(dsp::FRAME_SIZE) {
+      tracker.process(frame, |evt| events.push(evt));
+    }
+    events
+  }
+
+  #[test]
+  fn tracks_notes_and_cents() {
+    let mut tracker = PitchTracker::new(dsp::SAMPLE_RATE);
+    let mut phase = 0.;
+    // A4 + 20 cents
+    let freq = midi_note_to_freq(69.2);
+    let events = run(&mut tracker, &tone(freq, 0.5, 44_100 / 4, &mut phase));
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0], NoteEvent::On { note: 69, .. }));
+    assert!((tracker.f0 - freq).abs() / freq < 0.002, "{}", tracker.f0);
+    let cents = (tracker.midi_note - tracker.midi_note.round()) * 100.;
+    assert!((cents - 20.).abs() < 3., "{cents}");
+    assert!(tracker.confidence > 0.9);
+
+    let events = run(
+      &mut tracker,
+      &tone(midi_note_to_freq(64.), 0.5, 44_100 / 4, &mut phase),
+    );
+    assert_eq!(events, vec![NoteEvent::Off { note: 69 }, events[1]]);
+    assert!(matches!(events[1], NoteEvent::On { note: 64, .. }));
+
+    // the gate closes on silence but the held frequency stays
+    let events = run(&mut tracker, &vec![0.; 44_100 / 4]);
+    assert_eq!(events, vec![NoteEvent::Off { note: 64 }]);
+    assert!((freq_to_midi_note(tracker.held_f0) - 64.).abs() < 0.1);
+  }
+
+  #[test]
+  fn hysteresis() {
+    let mut tracker = PitchTracker::new(dsp::SAMPLE_RATE);
+    let mut phase = 0.;
+    let mut events = run(
+      &mut tracker,
+      &tone(midi_note_to_freq(60.), 0.5, 11_025, &mut phase),
+    );
+    // Drifting 70 cents sharp doesn't change the note since it's within the note hysteresis
+    events.extend(run(
+      &mut tracker,
+      &tone(midi_note_to_freq(60.7), 0.5, 11_025, &mut phase),
+    ));
+    // Fading out to a little below the gate doesn't release it either
+    let quiet = amplitude_for_level(tracker.config.gate_db - 3.);
+    let fade_len = 22_050;
+    let mut fade = tone(midi_note_to_freq(60.), 1., fade_len, &mut phase);
+    for (i, sample) in fade.iter_mut().enumerate() {
+      *sample *= 0.5 * (quiet / 0.5).powf(i as f32 / fade_len as f32);
+    }
+    fade.extend(tone(midi_note_to_freq(60.), quiet, 11_025, &mut phase));
+    events.extend(run(&mut tracker, &fade));
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(tracker.active_note, Some(60));
+
+    // but a quiet note doesn't open the gate
+    let mut tracker = PitchTracker::new(dsp::SAMPLE_RATE);
+    let events = run(
+      &mut tracker,
+      &tone(midi_note_to_freq(60.), quiet, 11_025, &mut phase),
+    );
+    assert!(events.is_empty());
+    assert!(tracker.confidence > 0.9);
+  }
+
+  #[test]
+  fn tracks_notes_at_other_sample_rates() {
+    let sample_rate = 48_000.;
+    let mut tracker = PitchTracker::new(sample_rate);
+    let freq = midi_note_to_freq(57.);
+    let samples: Vec<f32> = (0..sample_rate as usize / 4)
+      .map(|i| 0.5 * (i as f32 * freq / sample_rate * std::f32::consts::TAU).sin())
+      .collect();
+    let events = run(&mut tracker, &samples);
+    assert!(
+      matches!(events[..], [NoteEvent::On { note: 57, .. }]),
+      "{events:?}"
+    );
+    assert!((tracker.f0 - freq).abs() / freq < 0.002, "{}", tracker.f0);
+  }
+
+  #[test]
+  fn noise_does_not_trigger_notes() {
+    let mut tracker = PitchTracker::new(dsp::SAMPLE_RATE);
+    let mut state = 1u32;
+    let noise: Vec<f32> = (0..44_100)
+      .map(|_| {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        ((state >> 8) as f32 / (1u32 << 24) as f32 * 2. - 1.) * 0.5
+      })
+      .collect();
+    assert!(run(&mut tracker, &noise).is_empty());
+  }
+}
@@ -0,0 +1,85 @@
+//! Fade curves and gain envelopes applied to clips as they're played.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FadeCurve {
+  #[default]
+  Linear,
+  /// Keeps the combined power of two clips constant when crossfading between them
+  EqualPower,
+  /// Starts slowly and speeds up
+  Exponential,
+  /// Starts quickly and slows down
+  Logarithmic,
+  SCurve,
+}
+
+impl FadeCurve {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => FadeCurve::Linear,
+      1 => FadeCurve::EqualPower,
+      2 => FadeCurve::Exponential,
+      3 => FadeCurve::Logarithmic,
+      4 => FadeCurve::SCurve,
+      _ => panic!("Invalid fade curve: {}", val),
+    }
+  }
+
+  /// Maps fade progress from 0 to 1 to a gain from 0 to 1.  Fade-outs use the same curve reversed.
+  pub fn apply(self, x: f32) -> f32 {
+    let x = x.clamp(0., 1.);
+    match self {
+      FadeCurve::Linear => x,
+      FadeCurve::EqualPower => (x * std::f32::consts::FRAC_PI_2).sin(),
+      FadeCurve::Exponential => x * x,
+      FadeCurve::Logarithmic => 1. - (1. - x) * (1. - x),
+      FadeCurve::SCurve => x * x * (3. - 2. * x),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Fade {
+  pub len_samples: usize,
+  pub curve: FadeCurve,
+}
+
+impl Fade {
+  pub fn new(len_samples: usize, curve: FadeCurve) -> Self { Fade { len_samples, curve } }
+
+  /// Gain for a sample `samples_from_edge` samples from the edge of the clip being faded
+  #[inline]
+  pub fn gain(&self, samples_from_edge: usize) -> f32 {
+    if samples_from_edge >= self.len_samples {
+      return 1.;
+    }
+    self
+      .curve
+      .apply(samples_from_edge as f32 / self.len_samples as f32)
+  }
+
+  /// Returns whichever of the two fades is longer
+  pub fn longest(self, other: Fade) -> Fade {
+    if other.len_samples > self.len_samples {
+      other
+    } else {
+      self
+    }
+  }
+}
+
+/// Reads the gain envelope at `progress` from 0 to 1 through the clip.  The envelope's points are
+/// spread evenly over the clip and linearly interpolated between.
+#[inline]
+pub fn read_gain_envelope(envelope: &[f32], progress: f32) -> f32 {
+  match envelope {
+    [] => 1.,
+    [gain] => *gain,
+    _ => {
+      let pos = progress.clamp(0., 1.) * (envelope.len() - 1) as f32;
+      let base_ix = (pos as usize).min(envelope.len() - 2);
+      let t = pos - base_ix as f32;
+      envelope[base_ix] + (envelope[base_ix + 1] - envelope[base_ix]) * t
+    },
+  }
+}
@@ -1,20 +1,105 @@
 use std::{
   cmp::{Eq, Ord, PartialOrd},
-  collections::BinaryHeap,
+  collections::{BinaryHeap, HashMap, HashSet},
 };
 
 use float_ord::FloatOrd;
 use slotmap::{DefaultKey, SlotMap};
 
+use crate::fades::{read_gain_envelope, Fade, FadeCurve};
+
+pub mod fades;
+
 const FRAME_SIZE: usize = 128;
 const SAMPLE_RATE: usize = 44_100;
+const DEFAULT_BPM: f64 = 120.;
+const MAX_GAIN_ENVELOPE_LEN: usize = 255;
 
 /// A "note" for the sample editor.  These are the fundamental units of the sample editor.
 #[derive(Clone, PartialEq)]
 pub struct Sample {
   pub start_pos_beats: f64,
   pub sample_data_ix: usize,
+  /// Gain multipliers spread evenly over the length of the clip
   pub gain_envelope: Option<Vec<f32>>,
+  /// If set, the clip is time-stretched to last this many beats at the current tempo without
+  /// changing its pitch
+  pub target_len_beats: Option<f64>,
+  /// Pitch shift applied independently of the clip's length
+  pub pitch_shift_cents: f32,
+  /// Offset into the source data at which the clip starts.  Time-stretching and pitch-shifting
+  /// are applied to the trimmed region.
+  pub source_offset_samples: usize,
+  /// Number of samples of the source data played starting at `source_offset_samples`.  If `None`,
+  /// the clip plays until the end of the source data.
+  pub source_len_samples: Option<usize>,
+  pub gain: f32,
+  pub fade_in: Fade,
+  pub fade_out: Fade,
+}
+
+impl Sample {
+  pub fn new(start_pos_beats: f64, sample_data_ix: usize) -> Self {
+    Sample {
+      start_pos_beats,
+      sample_data_ix,
+      gain_envelope: None,
+      target_len_beats: None,
+      pitch_shift_cents: 0.,
+      source_offset_samples: 0,
+      source_len_samples: None,
+      gain: 1.,
+      fade_in: Fade::default(),
+      fade_out: Fade::default(),
+    }
+  }
+
+  /// Range of the source data that the clip plays after trimming
+  fn source_range(&self, source_len_samples: usize) -> (usize, usize) {
+    let start = self.source_offset_samples.min(source_len_samples);
+    let end = match self.source_len_samples {
+      Some(len) => (start + len).min(source_len_samples),
+      None => source_len_samples,
+    };
+    (start, end)
+  }
+
+  fn render_key(&self, source_len_samples: usize, bpm: f64) -> Option<RenderKey> {
+    if self.target_len_beats.is_none() && self.pitch_shift_cents == 0. {
+      return None;
+    }
+
+    let (source_start, source_end) = self.source_range(source_len_samples);
+    let len_samples = match self.target_len_beats {
+      Some(target_len_beats) => compute_len_samples(target_len_beats, bpm),
+      None => source_end - source_start,
+    };
+    Some(RenderKey {
+      source_data_ix: self.sample_data_ix,
+      source_start,
+      source_end,
+      len_samples,
+      pitch_shift_cents_bits: self.pitch_shift_cents.to_bits(),
+    })
+  }
+}
+
+/// Identifies a time-stretched and/or pitch-shifted rendering of a region of a sample data buffer.
+/// Clips with the same source region and settings share a single rendering.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct RenderKey {
+  source_data_ix: usize,
+  source_start: usize,
+  source_end: usize,
+  len_samples: usize,
+  pitch_shift_cents_bits: u32,
+}
+
+/// Number of values written to `SampleEditorCtx::render_requests` for each requested rendering
+const RENDER_REQUEST_LEN: usize = 5;
+
+impl RenderKey {
+  fn pitch_shift_cents(&self) -> f32 { f32::from_bits(self.pitch_shift_cents_bits) }
 }
 
 impl Eq for Sample {}
@@ -44,13 +129,33 @@ fn compute_sample_len_beats(len_samples: usize, bpm: f64) -> f64 {
   ((len_samples as f64) / (SAMPLE_RATE as f64)) * bps
 }
 
+fn compute_len_samples(len_beats: f64, bpm: f64) -> usize {
+  let bps = bpm / 60.;
+  ((len_beats / bps) * (SAMPLE_RATE as f64)).round() as usize
+}
+
+fn key_to_u64(key: DefaultKey) -> u64 { unsafe { std::mem::transmute(key) } }
+
+fn u64_to_key(key: u64) -> DefaultKey { unsafe { std::mem::transmute(key) } }
+
 /// Represents an actively playing sample.  The sample is read out of the data buffer
 /// sample-by-sample until the whole thing has been played.  Once that point is reached, the
 /// playhead is deleted.
 pub struct Playhead {
   pub sample_key: u64,
   pub sample_data_ix: usize,
+  /// Region of `sample_data[sample_data_ix]` that is played
+  pub start_sample_ix: usize,
+  pub end_sample_ix: usize,
+  /// Number of samples played so far, counted from `start_sample_ix`
   pub played_sample_count: usize,
+  /// Number of samples into the next rendered frame at which playback starts.  Used to start
+  /// samples in the middle of frames.
+  pub start_delay_samples: usize,
+  pub gain: f32,
+  pub gain_envelope: Option<Vec<f32>>,
+  pub fade_in: Fade,
+  pub fade_out: Fade,
 }
 
 #[derive(PartialEq)]
@@ -60,26 +165,79 @@ pub enum PlayStatus {
 }
 
 impl Playhead {
+  fn len_samples(&self) -> usize { self.end_sample_ix - self.start_sample_ix }
+
+  #[inline]
+  fn gain_at(&self, played_sample_count: usize) -> f32 {
+    let len_samples = self.len_samples();
+    let mut gain = self.gain
+      * self.fade_in.gain(played_sample_count)
+      * self.fade_out.gain(len_samples - played_sample_count - 1);
+    if let Some(gain_envelope) = &self.gain_envelope {
+      gain *= read_gain_envelope(
+        gain_envelope,
+        played_sample_count as f32 / len_samples as f32,
+      );
+    }
+    gain
+  }
+
   pub fn render(&mut self, sample_data: &[Vec<f32>], output_buf: &mut [f32]) -> PlayStatus {
     let sample_data = &sample_data[self.sample_data_ix];
-    for i in 0..output_buf.len() {
-      output_buf[i] += sample_data[self.played_sample_count];
-      self.played_sample_count += 1;
-      if self.played_sample_count >= sample_data.len() {
+    let end_sample_ix = self.end_sample_ix.min(sample_data.len());
+    let start_offset = self.start_delay_samples.min(output_buf.len());
+    self.start_delay_samples -= start_offset;
+
+    for out in &mut output_buf[start_offset..] {
+      let sample_ix = self.start_sample_ix + self.played_sample_count;
+      // Renderings can be empty if the source data is empty or is stretched to zero beats
+      if sample_ix >= end_sample_ix {
         return PlayStatus::DonePlaying;
       }
+
+      *out += sample_data[sample_ix] * self.gain_at(self.played_sample_count);
+      self.played_sample_count += 1;
     }
 
-    PlayStatus::NotDonePlaying
+    if self.start_sample_ix + self.played_sample_count >= end_sample_ix {
+      PlayStatus::DonePlaying
+    } else {
+      PlayStatus::NotDonePlaying
+    }
   }
 }
 
 pub struct SampleEditorCtx {
   pub samples: SlotMap<DefaultKey, Sample>,
+  /// Holds both the sample data provided by the user and time-stretched/pitch-shifted renderings
+  /// of it
   pub sample_data: Vec<Vec<f32>>,
   pub playheads: Vec<Playhead>,
   pub output_buffer: [f32; FRAME_SIZE],
-  pub upcoming_samples: BinaryHeap<(u64, Sample)>,
+  /// Ordered by start beat, earliest first, along with the key of each sample
+  pub upcoming_samples: BinaryHeap<(Sample, u64)>,
+  pub bpm: f64,
+  /// If set, clips that overlap on the timeline are crossfaded over the overlapping region
+  pub crossfade_overlaps: bool,
+  rendered_data_ixs: HashMap<RenderKey, usize>,
+  /// Renderings returned by `collect_render_requests` that haven't been provided yet
+  requested_render_keys: HashSet<RenderKey>,
+  /// `[source_data_ix, source_start, source_end, len_samples, pitch_shift_cents]` for each
+  /// rendering found by the last call to `collect_render_requests`
+  pub render_requests: Vec<f64>,
+  /// Buffer that the main thread writes a rendering into before passing it to
+  /// `provide_rendered_data`
+  rendered_data_buf: Vec<f32>,
+  /// Indices in `sample_data` of renderings that are no longer used and can be overwritten.  Their
+  /// buffers are emptied but keep their allocations so that renderings can be swapped in and out
+  /// without allocating or freeing memory on the audio thread.
+  free_data_ixs: Vec<usize>,
+  gain_envelope_buf: Vec<f32>,
+  // Scratch buffers used by `refresh_rendered_data` and `free_rendered_data`, kept around so that
+  // they don't have to be allocated every time the tempo or a clip changes
+  used_render_keys: HashSet<RenderKey>,
+  playing_data_ixs: Vec<usize>,
+  freed_data_ixs: Vec<usize>,
 }
 
 impl Default for SampleEditorCtx {
@@ -90,23 +248,343 @@ impl Default for SampleEditorCtx {
       playheads: Vec::new(),
       output_buffer: [0.; FRAME_SIZE],
       upcoming_samples: BinaryHeap::default(),
+      bpm: DEFAULT_BPM,
+      crossfade_overlaps: true,
+      rendered_data_ixs: HashMap::new(),
+      requested_render_keys: HashSet::new(),
+      render_requests: Vec::new(),
+      rendered_data_buf: Vec::new(),
+      free_data_ixs: Vec::new(),
+      gain_envelope_buf: Vec::with_capacity(MAX_GAIN_ENVELOPE_LEN),
+      used_render_keys: HashSet::new(),
+      playing_data_ixs: Vec::new(),
+      freed_data_ixs: Vec::new(),
     }
   }
 }
 
 impl SampleEditorCtx {
+  fn render_key(&self, sample: &Sample) -> Option<RenderKey> {
+    sample.render_key(self.sample_data[sample.sample_data_ix].len(), self.bpm)
+  }
+
+  /// Finds the renderings that clips need at `bpm` which haven't been provided or requested yet and
+  /// writes them to `render_requests`.  Time-stretching is far too expensive to run on the audio
+  /// thread, so the renderings are made on the main thread and passed back in with
+  /// `provide_rendered_data`.  Returns the number of requests.
+  pub fn collect_render_requests(&mut self, bpm: f64) -> usize {
+    self.render_requests.clear();
+    for sample in self.samples.values() {
+      let Some(render_key) = sample.render_key(self.sample_data[sample.sample_data_ix].len(), bpm)
+      else {
+        continue;
+      };
+      if self.rendered_data_ixs.contains_key(&render_key)
+        || !self.requested_render_keys.insert(render_key)
+      {
+        continue;
+      }
+
+      self.render_requests.extend([
+        render_key.source_data_ix as f64,
+        render_key.source_start as f64,
+        render_key.source_end as f64,
+        render_key.len_samples as f64,
+        render_key.pitch_shift_cents() as f64,
+      ]);
+    }
+    self.render_requests.len() / RENDER_REQUEST_LEN
+  }
+
+  /// Stores a rendering requested by `collect_render_requests`.  Renderings that were never
+  /// requested, or whose source data has been overwritten since, are ignored.  Active playheads are
+  /// moved over to it by the next call to `set_bpm`.
+  ///
+  /// Returns an empty buffer holding the allocation that the rendering replaced, if any, so that it
+  /// can be reused for the next rendering rather than being freed.
+  fn provide_rendered_data(&mut self, render_key: RenderKey, mut rendered: Vec<f32>) -> Vec<f32> {
+    if !self.requested_render_keys.remove(&render_key) {
+      rendered.clear();
+      return rendered;
+    }
+
+    let data_ix = match self.rendered_data_ixs.get(&render_key) {
+      Some(&data_ix) => data_ix,
+      None => {
+        let data_ix = match self.free_data_ixs.pop() {
+          Some(data_ix) => data_ix,
+          None => {
+            self.sample_data.push(Vec::new());
+            self.sample_data.len() - 1
+          },
+        };
+        self.rendered_data_ixs.insert(render_key, data_ix);
+        data_ix
+      },
+    };
+    std::mem::swap(&mut self.sample_data[data_ix], &mut rendered);
+    rendered.clear();
+    rendered
+  }
+
+  /// Returns the buffer and the region of it played for `sample` if it has already been rendered.
+  fn get_playback_range(&self, sample: &Sample) -> Option<(usize, usize, usize)> {
+    match self.render_key(sample) {
+      Some(render_key) => {
+        let data_ix = *self.rendered_data_ixs.get(&render_key)?;
+        Some((data_ix, 0, self.sample_data[data_ix].len()))
+      },
+      None => {
+        let (start, end) = sample.source_range(self.sample_data[sample.sample_data_ix].len());
+        Some((sample.sample_data_ix, start, end))
+      },
+    }
+  }
+
+  /// Start and end of `sample` on the timeline at the current tempo
+  fn get_clip_bounds_beats(&self, sample: &Sample) -> Option<(f64, f64)> {
+    let (_data_ix, start, end) = self.get_playback_range(sample)?;
+    let len_beats = compute_sample_len_beats(end - start, self.bpm);
+    Some((sample.start_pos_beats, sample.start_pos_beats + len_beats))
+  }
+
+  /// Fade in and fade out needed to crossfade the clip with `key` into clips overlapping it on the
+  /// timeline.  Clips starting at exactly the same beat are layered rather than crossfaded.
+  fn get_overlap_fades(&self, key: DefaultKey, sample: &Sample) -> (Fade, Fade) {
+    if !self.crossfade_overlaps {
+      return (Fade::default(), Fade::default());
+    }
+    let Some((start, end)) = self.get_clip_bounds_beats(sample) else {
+      return (Fade::default(), Fade::default());
+    };
+
+    let (mut fade_in_beats, mut fade_out_beats) = (0f64, 0f64);
+    for (other_key, other) in &self.samples {
+      if other_key == key {
+        continue;
+      }
+      let Some((other_start, other_end)) = self.get_clip_bounds_beats(other) else {
+        continue;
+      };
+
+      if other_start < start && other_end > start {
+        fade_in_beats = fade_in_beats.max(other_end.min(end) - start);
+      }
+      if other_start > start && other_start < end {
+        fade_out_beats = fade_out_beats.max(end.min(other_end) - other_start);
+      }
+    }
+
+    (
+      Fade::new(
+        compute_len_samples(fade_in_beats, self.bpm),
+        FadeCurve::EqualPower,
+      ),
+      Fade::new(
+        compute_len_samples(fade_out_beats, self.bpm),
+        FadeCurve::EqualPower,
+      ),
+    )
+  }
+
+  /// Creates a playhead at the start of `sample`, or returns `None` if it has nothing to play or
+  /// its rendering hasn't been provided yet.
+  fn build_playhead(&self, key: DefaultKey, sample: &Sample) -> Option<Playhead> {
+    let (sample_data_ix, start_sample_ix, end_sample_ix) = self.get_playback_range(sample)?;
+    if end_sample_ix <= start_sample_ix {
+      return None;
+    }
+
+    let (overlap_fade_in, overlap_fade_out) = self.get_overlap_fades(key, sample);
+    Some(Playhead {
+      sample_key: key_to_u64(key),
+      sample_data_ix,
+      start_sample_ix,
+      end_sample_ix,
+      played_sample_count: 0,
+      start_delay_samples: 0,
+      gain: sample.gain,
+      gain_envelope: sample.gain_envelope.clone(),
+      fade_in: sample.fade_in.longest(overlap_fade_in),
+      fade_out: sample.fade_out.longest(overlap_fade_out),
+    })
+  }
+
+  /// Frees renderings matching `should_free` for reuse.  Their buffers are emptied rather than
+  /// dropped so that the allocations can be reused by later renderings.
+  fn free_rendered_data(&mut self, should_free: impl Fn(&RenderKey, usize) -> bool) {
+    let mut freed_data_ixs = std::mem::take(&mut self.freed_data_ixs);
+    freed_data_ixs.clear();
+    self.rendered_data_ixs.retain(|render_key, data_ix| {
+      if should_free(render_key, *data_ix) {
+        freed_data_ixs.push(*data_ix);
+        false
+      } else {
+        true
+      }
+    });
+
+    self
+      .playheads
+      .retain(|playhead| !freed_data_ixs.contains(&playhead.sample_data_ix));
+    for &data_ix in &freed_data_ixs {
+      self.sample_data[data_ix].clear();
+      self.free_data_ixs.push(data_ix);
+    }
+    self.freed_data_ixs = freed_data_ixs;
+  }
+
+  /// Moves active playheads over to the renderings or trimmed regions for the current tempo and
+  /// settings at the same relative position.  Playheads whose new rendering hasn't been provided
+  /// yet keep playing the one they started with.  Renderings that are no longer used by any clip or
+  /// playhead are freed.
+  fn refresh_rendered_data(&mut self) {
+    let mut used_render_keys = std::mem::take(&mut self.used_render_keys);
+    used_render_keys.clear();
+    used_render_keys.extend(
+      self
+        .samples
+        .values()
+        .filter_map(|sample| self.render_key(sample)),
+    );
+
+    for playhead_ix in 0..self.playheads.len() {
+      let playhead = &self.playheads[playhead_ix];
+      let Some(sample) = self.samples.get(u64_to_key(playhead.sample_key)) else {
+        continue;
+      };
+      let Some(new_range) = self.get_playback_range(sample) else {
+        continue;
+      };
+      let old_range = (
+        playhead.sample_data_ix,
+        playhead.start_sample_ix,
+        playhead.end_sample_ix,
+      );
+      if new_range == old_range {
+        continue;
+      }
+
+      let (sample_data_ix, start_sample_ix, end_sample_ix) = new_range;
+      let progress = playhead.played_sample_count as f64 / playhead.len_samples().max(1) as f64;
+      let playhead = &mut self.playheads[playhead_ix];
+      playhead.sample_data_ix = sample_data_ix;
+      playhead.start_sample_ix = start_sample_ix;
+      playhead.end_sample_ix = end_sample_ix;
+      playhead.played_sample_count =
+        (progress * end_sample_ix.saturating_sub(start_sample_ix) as f64) as usize;
+    }
+    self
+      .playheads
+      .retain(|playhead| playhead.played_sample_count < playhead.len_samples());
+
+    let mut playing_data_ixs = std::mem::take(&mut self.playing_data_ixs);
+    playing_data_ixs.clear();
+    playing_data_ixs.extend(
+      self
+        .playheads
+        .iter()
+        .map(|playhead| playhead.sample_data_ix),
+    );
+    self.free_rendered_data(|render_key, data_ix| {
+      !used_render_keys.contains(render_key) && !playing_data_ixs.contains(&data_ix)
+    });
+    self.used_render_keys = used_render_keys;
+    self.playing_data_ixs = playing_data_ixs;
+  }
+
+  /// Sets the tempo and moves playheads over to the renderings provided for it so that stretched
+  /// clips keep their length in beats.  Nothing is rendered here; renderings for the new tempo
+  /// should be requested with `collect_render_requests` and provided before calling this.
+  pub fn set_bpm(&mut self, bpm: f64) {
+    if bpm <= 0. {
+      return;
+    }
+
+    self.bpm = bpm;
+    self.refresh_rendered_data();
+  }
+
+  fn get_sample_mut(&mut self, key: DefaultKey) -> &mut Sample {
+    self
+      .samples
+      .get_mut(key)
+      .unwrap_or_else(|| panic!("No sample found with key={:?}", key))
+  }
+
+  pub fn set_sample_stretch(
+    &mut self,
+    key: DefaultKey,
+    target_len_beats: Option<f64>,
+    pitch_shift_cents: f32,
+  ) {
+    let sample = self.get_sample_mut(key);
+    sample.target_len_beats = target_len_beats;
+    sample.pitch_shift_cents = pitch_shift_cents;
+    self.refresh_rendered_data();
+  }
+
+  pub fn set_sample_trim(
+    &mut self,
+    key: DefaultKey,
+    source_offset_samples: usize,
+    source_len_samples: Option<usize>,
+  ) {
+    let sample = self.get_sample_mut(key);
+    sample.source_offset_samples = source_offset_samples;
+    sample.source_len_samples = source_len_samples;
+    self.refresh_rendered_data();
+  }
+
+  /// Fades take effect the next time the sample is played.
+  pub fn set_sample_fades(&mut self, key: DefaultKey, fade_in: Fade, fade_out: Fade) {
+    let sample = self.get_sample_mut(key);
+    sample.fade_in = fade_in;
+    sample.fade_out = fade_out;
+  }
+
+  pub fn set_sample_gain(&mut self, key: DefaultKey, gain: f32) {
+    self.get_sample_mut(key).gain = gain;
+    let key = key_to_u64(key);
+    for playhead in &mut self.playheads {
+      if playhead.sample_key == key {
+        playhead.gain = gain;
+      }
+    }
+  }
+
+  pub fn set_sample_gain_envelope(&mut self, key: DefaultKey, gain_envelope: Option<Vec<f32>>) {
+    self.get_sample_mut(key).gain_envelope = gain_envelope.clone();
+    let key = key_to_u64(key);
+    for playhead in &mut self.playheads {
+      if playhead.sample_key == key {
+        playhead.gain_envelope = gain_envelope.clone();
+      }
+    }
+  }
+
   pub fn schedule_samples(&mut self, start_beat: f64, bpm: f64) {
     self.upcoming_samples.clear();
     self.playheads.clear();
+    self.bpm = bpm;
+    self.refresh_rendered_data();
 
     // Find all samples that would be started but not finished at the start point and create
     // playheads for them
-    for (sample_key, sample) in &self.samples {
+    let samples: Vec<(DefaultKey, Sample)> = self
+      .samples
+      .iter()
+      .map(|(key, sample)| (key, sample.clone()))
+      .collect();
+    for (sample_key, sample) in samples {
       if sample.start_pos_beats > start_beat {
         continue;
       }
 
-      let sample_len_samples = self.sample_data[sample.sample_data_ix].len();
+      let Some(mut playhead) = self.build_playhead(sample_key, &sample) else {
+        continue;
+      };
+      let sample_len_samples = playhead.len_samples();
       let sample_len_beats = compute_sample_len_beats(sample_len_samples, bpm);
       let end_beat = sample.start_pos_beats + sample_len_beats;
 
@@ -118,22 +596,50 @@ impl SampleEditorCtx {
       assert!(pct_complete >= 0.);
       assert!(pct_complete <= 1.);
       let start_sample_ix = (pct_complete * (sample_len_samples as f64)).trunc() as usize;
-      assert!(start_sample_ix < sample_len_samples);
-      self.playheads.push(Playhead {
-        sample_key: unsafe { std::mem::transmute(sample_key) },
-        sample_data_ix: sample.sample_data_ix,
-        played_sample_count: start_sample_ix,
-      })
+      if start_sample_ix >= sample_len_samples {
+        continue;
+      }
+      playhead.played_sample_count = start_sample_ix;
+      self.playheads.push(playhead);
     }
 
     self.upcoming_samples.extend(
       self
         .samples
         .iter()
-        .map(|(key, sample)| -> (u64, _) { (unsafe { std::mem::transmute(key) }, sample.clone()) })
-        .filter(|(_key, sample)| sample.start_pos_beats > start_beat),
+        .map(|(key, sample)| (sample.clone(), key_to_u64(key)))
+        .filter(|(sample, _key)| sample.start_pos_beats > start_beat),
     );
   }
+
+  /// Starts playheads for all samples that start before the end of the frame starting at
+  /// `cur_beat` at the exact sample within the frame that they start at.
+  fn start_upcoming_samples(&mut self, cur_beat: f64) {
+    let beats_per_sample = self.bpm / 60. / SAMPLE_RATE as f64;
+    let frame_end_beat = cur_beat + beats_per_sample * FRAME_SIZE as f64;
+
+    loop {
+      match self.upcoming_samples.peek() {
+        Some((next_sample, _key)) if next_sample.start_pos_beats < frame_end_beat => {
+          let (_, sample_key) = self.upcoming_samples.pop().unwrap();
+          // Settings may have changed since the sample was scheduled, and it may have been removed
+          let key = u64_to_key(sample_key);
+          let Some(sample) = self.samples.get(key).cloned() else {
+            continue;
+          };
+          let Some(mut playhead) = self.build_playhead(key, &sample) else {
+            continue;
+          };
+
+          let start_offset_samples = (sample.start_pos_beats - cur_beat) / beats_per_sample;
+          playhead.start_delay_samples =
+            (start_offset_samples.round().max(0.) as usize).min(FRAME_SIZE - 1);
+          self.playheads.push(playhead);
+        },
+        _ => break,
+      }
+    }
+  }
 }
 
 #[no_mangle]
@@ -151,8 +657,15 @@ pub extern "C" fn write_sample_data(
   sample_ix: i32,
 ) -> usize {
   let ctx = unsafe { &mut *ctx };
-  if sample_ix > 0 {
+  if sample_ix >= 0 {
     let sample_ix = sample_ix as usize;
+    // Renderings of the old data are stale; they're requested again from the new data by the next
+    // call to `collect_render_requests`
+    ctx.free_rendered_data(|render_key, _data_ix| render_key.source_data_ix == sample_ix);
+    ctx
+      .requested_render_keys
+      .retain(|render_key| render_key.source_data_ix != sample_ix);
+
     // We're overwriting an existing sample data buffer
     let old_len = ctx.sample_data[sample_ix].len();
 
@@ -168,7 +681,7 @@ pub extern "C" fn write_sample_data(
           return true;
         }
 
-        if playhead.played_sample_count >= len_samples {
+        if playhead.start_sample_ix + playhead.played_sample_count >= len_samples {
           return false;
         }
         return true;
@@ -177,8 +690,9 @@ pub extern "C" fn write_sample_data(
     return sample_ix;
   }
 
-  // Create a new sample entry to hold this sample
-  ctx.sample_data.push(Vec::with_capacity(len_samples));
+  // Create a new sample entry to hold this sample.  It's zero-filled so that its length is set
+  // before the caller writes the data into it.
+  ctx.sample_data.push(vec![0.; len_samples]);
   ctx.sample_data.len() - 1
 }
 
@@ -188,23 +702,25 @@ pub extern "C" fn get_sample_data_buf_ptr(ctx: *mut SampleEditorCtx, buf_ix: usi
   ctx.sample_data[buf_ix].as_mut_ptr()
 }
 
-static mut GAIN_ENVELOPE_BUF: [f32; 255] = [0.; 255];
-static mut GAIN_ENVELOPE_LEN: usize = 0;
-
+/// Returns a pointer to a buffer that the gain envelope for the next call to `create_sample` or
+/// `set_sample_gain_envelope` can be written to.
 #[no_mangle]
-pub extern "C" fn set_gain_envelope_ptr(len: usize) -> *mut f32 {
-  if len > 255 {
-    panic!("Max gain envelope length is 255");
+pub unsafe extern "C" fn set_gain_envelope_ptr(ctx: *mut SampleEditorCtx, len: usize) -> *mut f32 {
+  if len > MAX_GAIN_ENVELOPE_LEN {
+    panic!("Max gain envelope length is {}", MAX_GAIN_ENVELOPE_LEN);
   }
 
-  unsafe {
-    GAIN_ENVELOPE_LEN = len;
-  }
-  &raw mut GAIN_ENVELOPE_BUF as *mut _
+  let ctx = &mut *ctx;
+  ctx.gain_envelope_buf.resize(len, 1.);
+  ctx.gain_envelope_buf.as_mut_ptr()
 }
 
-fn decode_gain_envelope() -> Vec<f32> {
-  unsafe { &GAIN_ENVELOPE_BUF[..GAIN_ENVELOPE_LEN] }.to_owned()
+fn decode_gain_envelope(ctx: &SampleEditorCtx, has_gain_envelope: bool) -> Option<Vec<f32>> {
+  if has_gain_envelope {
+    Some(ctx.gain_envelope_buf.clone())
+  } else {
+    None
+  }
 }
 
 #[no_mangle]
@@ -216,13 +732,8 @@ pub extern "C" fn create_sample(
 ) -> u32 {
   let ctx = unsafe { &mut *ctx };
   let sample = Sample {
-    start_pos_beats,
-    sample_data_ix,
-    gain_envelope: if has_gain_envelope {
-      Some(decode_gain_envelope())
-    } else {
-      None
-    },
+    gain_envelope: decode_gain_envelope(ctx, has_gain_envelope),
+    ..Sample::new(start_pos_beats, sample_data_ix)
   };
   let key = ctx.samples.insert(sample);
   let key: u64 = unsafe { std::mem::transmute(key) };
@@ -246,6 +757,152 @@ pub extern "C" fn remove_sample(ctx: *mut SampleEditorCtx, key: u32) {
   ctx.playheads.retain(|playhead| playhead.sample_key != key);
 }
 
+/// Sets the length in beats that the sample is time-stretched to and the number of cents it is
+/// pitch-shifted by.  If `target_len_beats` is zero or negative, the sample keeps its original
+/// length.
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_stretch(
+  ctx: *mut SampleEditorCtx,
+  key: u32,
+  target_len_beats: f64,
+  pitch_shift_cents: f32,
+) {
+  let ctx = &mut *ctx;
+  let target_len_beats = if target_len_beats > 0. {
+    Some(target_len_beats)
+  } else {
+    None
+  };
+  ctx.set_sample_stretch(u64_to_key(key as u64), target_len_beats, pitch_shift_cents);
+}
+
+/// Trims the sample to play `source_len_samples` samples of its data starting at
+/// `source_offset_samples`.  If `source_len_samples` is zero, the sample plays until the end of its
+/// data.
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_trim(
+  ctx: *mut SampleEditorCtx,
+  key: u32,
+  source_offset_samples: usize,
+  source_len_samples: usize,
+) {
+  let ctx = &mut *ctx;
+  let source_len_samples = if source_len_samples > 0 {
+    Some(source_len_samples)
+  } else {
+    None
+  };
+  ctx.set_sample_trim(
+    u64_to_key(key as u64),
+    source_offset_samples,
+    source_len_samples,
+  );
+}
+
+/// Curves are indices into `FadeCurve`: 0 = linear, 1 = equal power, 2 = exponential,
+/// 3 = logarithmic, 4 = s-curve
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_fades(
+  ctx: *mut SampleEditorCtx,
+  key: u32,
+  fade_in_len_samples: usize,
+  fade_in_curve: u32,
+  fade_out_len_samples: usize,
+  fade_out_curve: u32,
+) {
+  let ctx = &mut *ctx;
+  ctx.set_sample_fades(
+    u64_to_key(key as u64),
+    Fade::new(fade_in_len_samples, FadeCurve::from_u32(fade_in_curve)),
+    Fade::new(fade_out_len_samples, FadeCurve::from_u32(fade_out_curve)),
+  );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_gain(ctx: *mut SampleEditorCtx, key: u32, gain: f32) {
+  let ctx = &mut *ctx;
+  ctx.set_sample_gain(u64_to_key(key as u64), gain);
+}
+
+/// Sets the sample's gain envelope to the one written to the buffer returned by
+/// `set_gain_envelope_ptr`, or clears it if `has_gain_envelope` is false.
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_gain_envelope(
+  ctx: *mut SampleEditorCtx,
+  key: u32,
+  has_gain_envelope: bool,
+) {
+  let ctx = &mut *ctx;
+  let gain_envelope = decode_gain_envelope(ctx, has_gain_envelope);
+  ctx.set_sample_gain_envelope(u64_to_key(key as u64), gain_envelope);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_crossfade_overlaps(ctx: *mut SampleEditorCtx, enabled: bool) {
+  let ctx = &mut *ctx;
+  ctx.crossfade_overlaps = enabled;
+}
+
+/// Finds the time-stretched and pitch-shifted renderings that clips need at `bpm` which haven't
+/// been provided or requested yet.  Returns the number of requests; they can be read with
+/// `get_render_requests_ptr`.
+#[no_mangle]
+pub unsafe extern "C" fn collect_render_requests(ctx: *mut SampleEditorCtx, bpm: f64) -> usize {
+  let ctx = &mut *ctx;
+  ctx.collect_render_requests(bpm)
+}
+
+/// Returns a pointer to `[source_data_ix, source_start, source_end, len_samples,
+/// pitch_shift_cents]` for each rendering requested by the last call to `collect_render_requests`
+#[no_mangle]
+pub unsafe extern "C" fn get_render_requests_ptr(ctx: *mut SampleEditorCtx) -> *const f64 {
+  let ctx = &*ctx;
+  ctx.render_requests.as_ptr()
+}
+
+/// Returns a pointer to a buffer that a rendering of `len` samples can be written to before passing
+/// it to `provide_rendered_data`.  Where possible, the buffer reuses the allocation of a rendering
+/// that was replaced.
+#[no_mangle]
+pub unsafe extern "C" fn get_rendered_data_buf_ptr(
+  ctx: *mut SampleEditorCtx,
+  len: usize,
+) -> *mut f32 {
+  let ctx = &mut *ctx;
+  ctx.rendered_data_buf.resize(len, 0.);
+  ctx.rendered_data_buf.as_mut_ptr()
+}
+
+/// Stores the rendering written to the buffer returned by `get_rendered_data_buf_ptr` for the
+/// request with the provided params.  It's swapped in by the next call to `set_bpm`.
+#[no_mangle]
+pub unsafe extern "C" fn provide_rendered_data(
+  ctx: *mut SampleEditorCtx,
+  source_data_ix: usize,
+  source_start: usize,
+  source_end: usize,
+  len_samples: usize,
+  pitch_shift_cents: f32,
+) {
+  let ctx = &mut *ctx;
+  let render_key = RenderKey {
+    source_data_ix,
+    source_start,
+    source_end,
+    len_samples,
+    pitch_shift_cents_bits: pitch_shift_cents.to_bits(),
+  };
+  let rendered = std::mem::take(&mut ctx.rendered_data_buf);
+  ctx.rendered_data_buf = ctx.provide_rendered_data(render_key, rendered);
+}
+
+/// Sets the tempo and swaps in the renderings provided for it
+#[no_mangle]
+pub unsafe extern "C" fn set_bpm(ctx: *mut SampleEditorCtx, bpm: f64) {
+  let ctx = &mut *ctx;
+  ctx.set_bpm(bpm);
+}
+
 #[no_mangle]
 pub extern "C" fn start_playback(ctx: *mut SampleEditorCtx, start_beat: f64, bpm: f64) {
   let ctx = unsafe { &mut *ctx };
@@ -265,19 +922,7 @@ pub extern "C" fn process_sample_editor(ctx: *mut SampleEditorCtx, cur_beat: f64
   ctx.output_buffer.fill(0.);
 
   // Create new playheads for all notes that start in this window
-  loop {
-    match ctx.upcoming_samples.peek().cloned() {
-      Some((sample_key, next_sample)) if next_sample.start_pos_beats > cur_beat => {
-        ctx.upcoming_samples.pop();
-        ctx.playheads.push(Playhead {
-          sample_key,
-          sample_data_ix: next_sample.sample_data_ix,
-          played_sample_count: 0,
-        });
-      },
-      _ => break,
-    }
-  }
+  ctx.start_upcoming_samples(cur_beat);
 
   // Play from all playheads into the output buffer
   let mut i = 0;
@@ -294,3 +939,176 @@ pub extern "C" fn process_sample_editor(ctx: *mut SampleEditorCtx, cur_beat: f64
 
   ctx.output_buffer.as_ptr()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render_frames(ctx: &mut SampleEditorCtx, start_beat: f64, frame_count: usize) -> Vec<f32> {
+    let beats_per_frame = ctx.bpm / 60. / SAMPLE_RATE as f64 * FRAME_SIZE as f64;
+    let mut out = Vec::with_capacity(frame_count * FRAME_SIZE);
+    for frame_ix in 0..frame_count {
+      let cur_beat = start_beat + frame_ix as f64 * beats_per_frame;
+      process_sample_editor(ctx, cur_beat);
+      out.extend_from_slice(&ctx.output_buffer);
+    }
+    out
+  }
+
+  /// Renders the clips requested by the last call to `collect_render_requests` the same way that
+  /// the main thread does and passes them back in
+  fn provide_requested_renderings(ctx: &mut SampleEditorCtx) {
+    let requests = ctx.render_requests.clone();
+    for request in requests.chunks(RENDER_REQUEST_LEN) {
+      let render_key = RenderKey {
+        source_data_ix: request[0] as usize,
+        source_start: request[1] as usize,
+        source_end: request[2] as usize,
+        len_samples: request[3] as usize,
+        pitch_shift_cents_bits: (request[4] as f32).to_bits(),
+      };
+      let rendered = dsp::time_stretch::stretch_and_shift(
+        &ctx.sample_data[render_key.source_data_ix][render_key.source_start..render_key.source_end],
+        render_key.len_samples,
+        render_key.pitch_shift_cents(),
+      );
+      ctx.provide_rendered_data(render_key, rendered);
+    }
+  }
+
+  #[test]
+  fn stretched_clips_follow_tempo_changes() {
+    let mut ctx = SampleEditorCtx::default();
+    ctx.sample_data.push(
+      (0..SAMPLE_RATE)
+        .map(|i| (i as f32 / 100. * std::f32::consts::TAU).sin())
+        .collect(),
+    );
+    let key = ctx.samples.insert(Sample::new(0., 0));
+    ctx.set_sample_stretch(key, Some(4.), 0.);
+
+    // Clips don't play until their rendering has been provided
+    ctx.schedule_samples(1., 120.);
+    assert!(ctx.playheads.is_empty());
+    assert_eq!(ctx.collect_render_requests(120.), 1);
+    provide_requested_renderings(&mut ctx);
+    ctx.schedule_samples(1., 120.);
+    assert_eq!(ctx.playheads.len(), 1);
+    let data_ix = ctx.playheads[0].sample_data_ix;
+    assert_ne!(data_ix, 0);
+    assert_eq!(ctx.sample_data[data_ix].len(), SAMPLE_RATE * 2);
+    assert_eq!(ctx.playheads[0].played_sample_count, SAMPLE_RATE / 2);
+
+    // The playhead keeps playing the old rendering until the one for the new tempo is provided
+    ctx.set_bpm(60.);
+    assert_eq!(ctx.playheads[0].sample_data_ix, data_ix);
+
+    // Halving the tempo doubles the rendered length and keeps the playhead at the same beat
+    assert_eq!(ctx.collect_render_requests(60.), 1);
+    provide_requested_renderings(&mut ctx);
+    ctx.set_bpm(60.);
+    assert_eq!(ctx.playheads.len(), 1);
+    let data_ix = ctx.playheads[0].sample_data_ix;
+    assert_eq!(ctx.sample_data[data_ix].len(), SAMPLE_RATE * 4);
+    assert_eq!(ctx.playheads[0].played_sample_count, SAMPLE_RATE);
+    // The rendering for the old tempo was freed, keeping its allocation for reuse
+    assert_eq!(ctx.rendered_data_ixs.len(), 1);
+    assert_eq!(ctx.free_data_ixs.len(), 1);
+    let freed_data = &ctx.sample_data[ctx.free_data_ixs[0]];
+    assert!(freed_data.is_empty() && freed_data.capacity() >= SAMPLE_RATE * 2);
+
+    // Removing the stretch plays the original data again
+    ctx.set_sample_stretch(key, None, 0.);
+    assert_eq!(ctx.playheads[0].sample_data_ix, 0);
+    assert!(ctx.rendered_data_ixs.is_empty());
+  }
+
+  #[test]
+  fn samples_start_mid_frame() {
+    let mut ctx = SampleEditorCtx::default();
+    ctx.sample_data.push(vec![1.; 1000]);
+    // 50 samples into the first frame at 120 BPM
+    let start_beat = 50. * 2. / SAMPLE_RATE as f64;
+    ctx.samples.insert(Sample::new(start_beat, 0));
+    ctx.schedule_samples(0., 120.);
+
+    let out = render_frames(&mut ctx, 0., 10);
+    assert!(out[..50].iter().all(|&s| s == 0.));
+    assert!(out[50..1050].iter().all(|&s| s == 1.));
+    assert!(out[1050..].iter().all(|&s| s == 0.));
+  }
+
+  #[test]
+  fn upcoming_samples_start_in_order() {
+    let mut ctx = SampleEditorCtx {
+      crossfade_overlaps: false,
+      ..Default::default()
+    };
+    ctx.sample_data.push(vec![1.; 100]);
+    // Inserted latest-first so that slotmap key order is the opposite of start order
+    let beats_per_sample = 2. / SAMPLE_RATE as f64;
+    for start_sample_ix in [1000, 500, 200] {
+      ctx
+        .samples
+        .insert(Sample::new(start_sample_ix as f64 * beats_per_sample, 0));
+    }
+    ctx.schedule_samples(0., 120.);
+
+    let out = render_frames(&mut ctx, 0., 10);
+    let playing_ixs: Vec<usize> = (0..out.len()).filter(|&i| out[i] != 0.).collect();
+    let expected_ixs: Vec<usize> = [200, 500, 1000]
+      .into_iter()
+      .flat_map(|start| start..start + 100)
+      .collect();
+    assert_eq!(playing_ixs, expected_ixs);
+  }
+
+  #[test]
+  fn trim_fades_and_gain() {
+    let mut ctx = SampleEditorCtx::default();
+    ctx.sample_data.push((0..1000).map(|i| i as f32).collect());
+    let key = ctx.samples.insert(Sample::new(0., 0));
+    ctx.set_sample_trim(key, 100, Some(500));
+    ctx.set_sample_gain(key, 0.5);
+    ctx.set_sample_fades(
+      key,
+      Fade::new(100, FadeCurve::Linear),
+      Fade::new(100, FadeCurve::EqualPower),
+    );
+    ctx.schedule_samples(0., 120.);
+
+    let out = render_frames(&mut ctx, 0., 8);
+    assert_eq!(out[0], 0.);
+    assert_eq!(out[50], 150. * 0.5 * 0.5);
+    assert_eq!(out[200], 300. * 0.5);
+    assert!(out[499].abs() < 1e-6);
+    assert!(out[500..].iter().all(|&s| s == 0.));
+  }
+
+  #[test]
+  fn overlapping_clips_crossfade() {
+    let mut ctx = SampleEditorCtx::default();
+    ctx.sample_data.push(vec![1.; SAMPLE_RATE]);
+    // Two one-second clips at 60 BPM overlapping by half a beat
+    ctx.samples.insert(Sample::new(0., 0));
+    ctx.samples.insert(Sample::new(0.5, 0));
+    ctx.schedule_samples(0., 60.);
+
+    let out = render_frames(&mut ctx, 0., SAMPLE_RATE * 2 / FRAME_SIZE);
+    let half = SAMPLE_RATE / 2;
+    assert_eq!(out[half / 2], 1.);
+    // Equal-power crossfade over the overlapping region keeps the combined power constant
+    for i in (half..SAMPLE_RATE).step_by(1000) {
+      let progress = (i - half) as f32 / half as f32;
+      let fade_in = (progress * std::f32::consts::FRAC_PI_2).sin();
+      let fade_out = ((1. - progress) * std::f32::consts::FRAC_PI_2).sin();
+      assert!((out[i] - (fade_in + fade_out)).abs() < 0.01);
+    }
+    assert_eq!(out[SAMPLE_RATE + half / 2], 1.);
+
+    ctx.crossfade_overlaps = false;
+    ctx.schedule_samples(0., 60.);
+    let out = render_frames(&mut ctx, 0., SAMPLE_RATE * 2 / FRAME_SIZE);
+    assert_eq!(out[half + 100], 2.);
+  }
+}
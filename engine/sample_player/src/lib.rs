@@ -5,6 +5,20 @@ const FRAME_SIZE: usize = 128;
 pub struct Playhead {
   pub pos: f32,
   pub playback_speed: f32,
+  /// 1 when playing forwards and -1 when playing backwards through a ping-pong loop
+  pub direction: f32,
+  pub is_released: bool,
+}
+
+impl Playhead {
+  pub fn new(playback_speed: f32) -> Self {
+    Playhead {
+      pos: 0.,
+      playback_speed,
+      direction: 1.,
+      is_released: false,
+    }
+  }
 }
 
 #[derive(Default)]
@@ -13,10 +27,69 @@ pub struct CrossfadeParams {
   pub threshold: f32,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LoopMode {
+  /// Plays through the sample once.  If crossfade is enabled in `CrossfadeParams`, the whole
+  /// crossfaded buffer is looped instead.
+  #[default]
+  OneShot,
+  /// Loops between the loop points indefinitely, even after the gate is released.  Gating the
+  /// sample again restarts playback from the start.
+  LoopContinuous,
+  /// Loops between the loop points while gated, then plays through the rest of the sample once the
+  /// gate is released
+  LoopSustain,
+  /// Plays back and forth between the loop points indefinitely.  Gating the sample again restarts
+  /// playback from the start.
+  PingPong,
+}
+
+impl LoopMode {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => LoopMode::OneShot,
+      1 => LoopMode::LoopContinuous,
+      2 => LoopMode::LoopSustain,
+      3 => LoopMode::PingPong,
+      _ => panic!("Invalid loop mode: {}", val),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoopParams {
+  pub mode: LoopMode,
+  pub start_sample_ix: f32,
+  /// If not positive, the loop ends at the end of the sample
+  pub end_sample_ix: f32,
+  /// Length of the crossfade into the loop start as playback approaches the loop end.  The audio
+  /// leading up to the loop start is faded in over the end of the loop so that the jump back is
+  /// seamless.  Not used for ping-pong loops, which don't jump.
+  pub crossfade_len_samples: f32,
+}
+
+impl LoopParams {
+  /// Loop bounds clamped to the sample buffer, or `None` if the loop is empty
+  fn bounds(&self, sample_buf_len: usize) -> Option<(f32, f32)> {
+    let max_ix = sample_buf_len.saturating_sub(2) as f32;
+    let start = self.start_sample_ix.clamp(0., max_ix);
+    let end = if self.end_sample_ix > 0. {
+      self.end_sample_ix.clamp(0., max_ix)
+    } else {
+      max_ix
+    };
+    if end <= start {
+      return None;
+    }
+    Some((start, end))
+  }
+}
+
 pub struct SampleDescriptor {
   pub sample_buffer: Vec<f32>,
   pub crossfade_params: CrossfadeParams,
   pub crossfaded_sample_buffer: Vec<f32>,
+  pub loop_params: LoopParams,
   pub is_gated: bool,
   pub playheads: Vec<Playhead>,
 }
@@ -27,6 +100,7 @@ impl Default for SampleDescriptor {
       sample_buffer: Vec::new(),
       crossfade_params: CrossfadeParams::default(),
       crossfaded_sample_buffer: Vec::new(),
+      loop_params: LoopParams::default(),
       is_gated: false,
       playheads: Vec::new(),
     }
@@ -34,8 +108,30 @@ impl Default for SampleDescriptor {
 }
 
 impl SampleDescriptor {
+  pub fn gate(&mut self) {
+    self.is_gated = true;
+    if matches!(
+      self.loop_params.mode,
+      LoopMode::LoopContinuous | LoopMode::PingPong
+    ) {
+      self.playheads.clear();
+    }
+    self.playheads.push(Playhead::new(1.));
+  }
+
+  pub fn ungate(&mut self) {
+    self.is_gated = false;
+    for playhead in &mut self.playheads {
+      playhead.is_released = true;
+    }
+  }
+
   #[inline(never)]
   pub fn get_sample(&mut self) -> f32 {
+    if self.loop_params.mode != LoopMode::OneShot {
+      return self.get_looped_sample();
+    }
+
     let mut sample = 0.;
 
     let sample_buf = if self.crossfade_params.enabled {
@@ -64,6 +160,69 @@ impl SampleDescriptor {
 
     sample
   }
+
+  fn get_looped_sample(&mut self) -> f32 {
+    let sample_buf = &self.sample_buffer;
+    let LoopParams {
+      mode,
+      crossfade_len_samples,
+      ..
+    } = self.loop_params;
+    let Some((loop_start, loop_end)) = self.loop_params.bounds(sample_buf.len()) else {
+      self.playheads.clear();
+      return 0.;
+    };
+    let loop_len = loop_end - loop_start;
+    // The crossfade reads from before the loop start, so it can't be longer than the audio there
+    let crossfade_len = crossfade_len_samples.min(loop_start).min(loop_len);
+
+    let mut sample = 0.;
+    let mut i = 0;
+    while i < self.playheads.len() {
+      let mut playhead = self.playheads[i];
+      let is_looping = match mode {
+        LoopMode::OneShot => false,
+        LoopMode::LoopContinuous | LoopMode::PingPong => true,
+        LoopMode::LoopSustain => !playhead.is_released,
+      };
+
+      playhead.pos += playhead.playback_speed * playhead.direction;
+      if is_looping && mode == LoopMode::PingPong {
+        if playhead.direction > 0. && playhead.pos >= loop_end {
+          playhead.pos = (2. * loop_end - playhead.pos).max(loop_start);
+          playhead.direction = -1.;
+        } else if playhead.direction < 0. && playhead.pos < loop_start {
+          playhead.pos = (2. * loop_start - playhead.pos).min(loop_end);
+          playhead.direction = 1.;
+        }
+      } else if is_looping && playhead.pos >= loop_end {
+        playhead.pos -= loop_len;
+      }
+
+      if playhead.pos < 0. || playhead.pos > (sample_buf.len() - 2) as f32 {
+        self.playheads.swap_remove(i);
+        continue;
+      }
+      self.playheads[i] = playhead;
+
+      let mut val = dsp::read_interpolated(sample_buf, playhead.pos);
+      let crossfade_start = loop_end - crossfade_len;
+      if is_looping
+        && mode != LoopMode::PingPong
+        && crossfade_len > 0.
+        && playhead.pos >= crossfade_start
+        && playhead.pos < loop_end
+      {
+        let crossfade_phase = (playhead.pos - crossfade_start) / crossfade_len;
+        let lead_in_val = dsp::read_interpolated(sample_buf, playhead.pos - loop_len);
+        val = dsp::mix(crossfade_phase, lead_in_val, val);
+      }
+      sample += val;
+      i += 1;
+    }
+
+    sample
+  }
 }
 
 #[inline(always)]
@@ -123,13 +282,9 @@ pub extern "C" fn process_sample_player(ctx: *mut SamplePlayerCtx) {
     for sample_ix in 0..FRAME_SIZE {
       let is_gated = gate_inputs[sample_ix] > 0.;
       if is_gated && !voice.is_gated {
-        voice.is_gated = true;
-        voice.playheads.push(Playhead {
-          pos: 0.,
-          playback_speed: 1.,
-        });
+        voice.gate();
       } else if !is_gated && voice.is_gated {
-        voice.is_gated = false;
+        voice.ungate();
       }
 
       let sample = voice.get_sample();
@@ -218,6 +373,34 @@ pub extern "C" fn set_sample_crossfade_params(
   }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn set_sample_loop_params(
+  ctx: *mut SamplePlayerCtx,
+  voice_ix: usize,
+  mode: u32,
+  start_sample_ix: f32,
+  end_sample_ix: f32,
+  crossfade_len_samples: f32,
+) {
+  let ctx = &mut *ctx;
+
+  if ctx.voices.get(voice_ix).is_none() {
+    panic!(
+      "Tried to set loop params for sample at index={} but only {} samples exist",
+      voice_ix,
+      ctx.voices.len()
+    );
+  }
+
+  let voice = &mut ctx.voices[voice_ix];
+  voice.loop_params = LoopParams {
+    mode: LoopMode::from_u32(mode),
+    start_sample_ix,
+    end_sample_ix,
+    crossfade_len_samples: crossfade_len_samples.max(0.),
+  };
+}
+
 #[no_mangle]
 pub extern "C" fn get_sample_buf_ptr(
   ctx: *mut SamplePlayerCtx,
@@ -248,3 +431,107 @@ pub extern "C" fn on_sample_data_set(ctx: *mut SamplePlayerCtx, sample_ix: usize
       gen_crossfaded_sample_buffer(&sample.sample_buffer, sample.crossfade_params.threshold);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Sample whose value at each index is the index itself, making playhead positions observable
+  fn mk_ramp_sample(len: usize, loop_params: LoopParams) -> SampleDescriptor {
+    SampleDescriptor {
+      sample_buffer: (0..len).map(|i| i as f32).collect(),
+      loop_params,
+      ..Default::default()
+    }
+  }
+
+  fn render(sample: &mut SampleDescriptor, len: usize) -> Vec<f32> {
+    (0..len).map(|_| sample.get_sample()).collect()
+  }
+
+  #[test]
+  fn loop_sustain_plays_tail_after_release() {
+    let mut sample = mk_ramp_sample(1000, LoopParams {
+      mode: LoopMode::LoopSustain,
+      start_sample_ix: 100.,
+      end_sample_ix: 200.,
+      crossfade_len_samples: 0.,
+    });
+    sample.gate();
+    let held = render(&mut sample, 5000);
+    assert!(held[300..].iter().all(|&s| (100. ..200.).contains(&s)));
+
+    sample.ungate();
+    let tail = render(&mut sample, 1000);
+    assert!(sample.playheads.is_empty());
+    let max = tail.iter().copied().fold(0., f32::max);
+    assert_eq!(max, 998.);
+  }
+
+  #[test]
+  fn ping_pong_reverses_at_loop_points() {
+    let mut sample = mk_ramp_sample(1000, LoopParams {
+      mode: LoopMode::PingPong,
+      start_sample_ix: 100.,
+      end_sample_ix: 200.,
+      crossfade_len_samples: 0.,
+    });
+    sample.gate();
+    let out = render(&mut sample, 500);
+    assert_eq!(out[198], 199.);
+    assert_eq!(out[199], 200.);
+    assert_eq!(out[200], 199.);
+    assert_eq!(out[299], 100.);
+    assert_eq!(out[300], 101.);
+
+    // Keeps looping after release until gated again
+    sample.ungate();
+    render(&mut sample, 5000);
+    assert_eq!(sample.playheads.len(), 1);
+    sample.gate();
+    assert_eq!(sample.playheads.len(), 1);
+    assert_eq!(sample.get_sample(), 1.);
+  }
+
+  #[test]
+  fn crossfade_makes_loop_seamless() {
+    // The loop start lands on a trough and the loop end on a zero crossing, so jumping back
+    // without a crossfade creates a discontinuity
+    let period = 80.;
+    let sine = (0..1000)
+      .map(|i| (i as f32 / period * std::f32::consts::TAU).sin())
+      .collect::<Vec<_>>();
+    let loop_params = LoopParams {
+      mode: LoopMode::LoopContinuous,
+      start_sample_ix: 300.,
+      end_sample_ix: 600.,
+      crossfade_len_samples: 0.,
+    };
+    let max_step = |out: &[f32]| {
+      out
+        .windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .fold(0., f32::max)
+    };
+
+    let mut hard_loop = SampleDescriptor {
+      sample_buffer: sine.clone(),
+      loop_params,
+      ..Default::default()
+    };
+    hard_loop.gate();
+    assert!(max_step(&render(&mut hard_loop, 2000)) > 0.5);
+
+    let mut crossfaded_loop = SampleDescriptor {
+      sample_buffer: sine,
+      loop_params: LoopParams {
+        crossfade_len_samples: 64.,
+        ..loop_params
+      },
+      ..Default::default()
+    };
+    crossfaded_loop.gate();
+    // Bounded by the sine's own slope plus the crossfade between the two sections
+    assert!(max_step(&render(&mut crossfaded_loop, 2000)) < 0.12);
+  }
+}
@@ -0,0 +1,164 @@
+//! Delay-attack-hold-decay-sustain-release amplitude envelope applied to grains played from zones.
+//! All segments are linear.
+
+use dsp::SAMPLE_RATE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmpEnvelope {
+  pub delay_secs: f32,
+  pub attack_secs: f32,
+  pub hold_secs: f32,
+  pub decay_secs: f32,
+  /// Level held after the decay until the envelope is released, from 0 to 1
+  pub sustain_level: f32,
+  pub release_secs: f32,
+}
+
+impl Default for AmpEnvelope {
+  /// Matches the SFZ defaults: full level right away and a 1ms release
+  fn default() -> Self {
+    AmpEnvelope {
+      delay_secs: 0.,
+      attack_secs: 0.,
+      hold_secs: 0.,
+      decay_secs: 0.,
+      sustain_level: 1.,
+      release_secs: 0.001,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+  Delay,
+  Attack,
+  Hold,
+  Decay,
+  Sustain,
+  Release,
+  Done,
+}
+
+#[derive(Clone, Debug)]
+pub struct AmpEnvelopeState {
+  pub params: AmpEnvelope,
+  stage: Stage,
+  samples_in_stage: u32,
+  level: f32,
+  release_start_level: f32,
+}
+
+fn secs_to_samples(secs: f32) -> u32 { (secs.max(0.) * SAMPLE_RATE) as u32 }
+
+impl AmpEnvelopeState {
+  pub fn new(params: AmpEnvelope) -> Self {
+    AmpEnvelopeState {
+      params,
+      stage: Stage::Delay,
+      samples_in_stage: 0,
+      level: 0.,
+      release_start_level: 0.,
+    }
+  }
+
+  fn advance(&mut self, stage: Stage) {
+    self.stage = stage;
+    self.samples_in_stage = 0;
+  }
+
+  /// Returns the envelope's level for the current sample and advances it by one sample.
+  pub fn tick(&mut self) -> f32 {
+    loop {
+      let (len_samples, next_stage) = match self.stage {
+        Stage::Delay => (secs_to_samples(self.params.delay_secs), Stage::Attack),
+        Stage::Attack => (secs_to_samples(self.params.attack_secs), Stage::Hold),
+        Stage::Hold => (secs_to_samples(self.params.hold_secs), Stage::Decay),
+        Stage::Decay => (secs_to_samples(self.params.decay_secs), Stage::Sustain),
+        Stage::Release => (secs_to_samples(self.params.release_secs), Stage::Done),
+        Stage::Sustain => {
+          self.level = self.params.sustain_level;
+          return self.level;
+        },
+        Stage::Done => return 0.,
+      };
+      if self.samples_in_stage >= len_samples {
+        self.advance(next_stage);
+        continue;
+      }
+
+      let t = self.samples_in_stage as f32 / len_samples as f32;
+      self.level = match self.stage {
+        Stage::Delay => 0.,
+        Stage::Attack => t,
+        Stage::Hold => 1.,
+        Stage::Decay => dsp::mix(t, self.params.sustain_level, 1.),
+        Stage::Release => self.release_start_level * (1. - t),
+        Stage::Sustain | Stage::Done => unreachable!(),
+      };
+      self.samples_in_stage += 1;
+      return self.level;
+    }
+  }
+
+  /// Starts the release stage from the current level.  Has no effect if already released.
+  pub fn release(&mut self) {
+    if matches!(self.stage, Stage::Release | Stage::Done) {
+      return;
+    }
+    self.release_start_level = self.level;
+    self.advance(Stage::Release);
+  }
+
+  pub fn is_done(&self) -> bool { self.stage == Stage::Done }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stages_in_order() {
+    let samples_per_ms = SAMPLE_RATE / 1000.;
+    let mut env = AmpEnvelopeState::new(AmpEnvelope {
+      delay_secs: 0.001,
+      attack_secs: 0.002,
+      hold_secs: 0.001,
+      decay_secs: 0.001,
+      sustain_level: 0.5,
+      release_secs: 0.001,
+    });
+    let levels = (0..(samples_per_ms * 10.) as usize)
+      .map(|_| env.tick())
+      .collect::<Vec<_>>();
+
+    let at_ms = |ms: f32| levels[(ms * samples_per_ms) as usize];
+    assert_eq!(at_ms(0.5), 0.);
+    assert!((at_ms(2.) - 0.5).abs() < 0.02);
+    assert_eq!(at_ms(3.5), 1.);
+    assert!((at_ms(4.5) - 0.75).abs() < 0.02);
+    assert_eq!(at_ms(9.), 0.5);
+
+    env.release();
+    assert!(!env.is_done());
+    for _ in 0..(samples_per_ms as usize + 1) {
+      env.tick();
+    }
+    assert!(env.is_done());
+    assert_eq!(env.tick(), 0.);
+  }
+
+  #[test]
+  fn release_during_attack_starts_from_current_level() {
+    let mut env = AmpEnvelopeState::new(AmpEnvelope {
+      attack_secs: 1.,
+      release_secs: 1.,
+      ..Default::default()
+    });
+    for _ in 0..(SAMPLE_RATE as usize / 4) {
+      env.tick();
+    }
+    env.release();
+    let first_release_level = env.tick();
+    assert!(first_release_level <= 0.25 && first_release_level > 0.24);
+  }
+}
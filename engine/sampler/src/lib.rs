@@ -1,5 +1,15 @@
 use dsp::FRAME_SIZE;
 
+use crate::{
+  amp_envelope::AmpEnvelopeState,
+  sfz::SfzInstrument,
+  zones::{LoopMode, Zone, ZoneMap},
+};
+
+pub mod amp_envelope;
+pub mod sfz;
+pub mod zones;
+
 #[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "env")]
 extern "C" {
@@ -16,7 +26,7 @@ fn error(msg: &str) {
   unsafe { log_err(ptr, len) }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CrossfadeConfig {
   /// How many samples are in the crossfade at the start of the grain playback.  The reverse flag
   /// of the grain doesn't affect this.
@@ -59,6 +69,16 @@ pub struct GrainConfig {
   pub reverse: bool,
 }
 
+/// Loop points for a grain in sample data indices.  The grain's playback jumps back to `start`
+/// whenever it reaches `end`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrainLoop {
+  pub start_sample_ix: f32,
+  pub end_sample_ix: f32,
+  /// If set, the grain stops looping once released and plays through to its end
+  pub sustain_only: bool,
+}
+
 pub struct Grain {
   pub config: GrainConfig,
   /// Value [0, 1] representing how far through the grain we are.  This goes from 0 to 1 regardless
@@ -67,9 +87,71 @@ pub struct Grain {
   /// Sample index within the next processed frame at which this grain starts playing.  Only
   /// non-zero for the grain's first frame when it was triggered mid-frame.
   pub start_offset_samples: usize,
+  /// Index into `SamplerCtx::sample_buffers` for grains played from zones, or `None` to read from
+  /// `SamplerCtx::sample_data`
+  pub sample_buffer_ix: Option<usize>,
+  pub gain: f32,
+  pub midi_number: usize,
+  /// Only supported for forward playback
+  pub loop_config: Option<GrainLoop>,
+  /// If `None`, the grain plays at full level and ignores note-offs
+  pub amp_envelope: Option<AmpEnvelopeState>,
+  /// Sample index within the next processed frame at which to release this grain
+  pub pending_release_offset: Option<usize>,
+  pub is_released: bool,
+  pub ignores_note_off: bool,
 }
 
 impl Grain {
+  pub fn new(
+    config: GrainConfig,
+    start_offset_samples: usize,
+    sample_buffer_ix: Option<usize>,
+    gain: f32,
+    midi_number: usize,
+  ) -> Self {
+    Grain {
+      config,
+      phase: 0.,
+      start_offset_samples,
+      sample_buffer_ix,
+      gain,
+      midi_number,
+      loop_config: None,
+      amp_envelope: None,
+      pending_release_offset: None,
+      is_released: false,
+      ignores_note_off: false,
+    }
+  }
+
+  /// Schedules this grain to be released at `sample_ix_within_frame` of the next processed frame.
+  /// Grains without an amp envelope are only affected by releases if they have a sustain loop, in
+  /// which case they stop looping and play through to their end.
+  pub fn release(&mut self, sample_ix_within_frame: usize) {
+    let has_sustain_loop = self.loop_config.is_some_and(|lp| lp.sustain_only);
+    if (self.amp_envelope.is_none() && !has_sustain_loop)
+      || self.ignores_note_off
+      || self.is_released
+    {
+      return;
+    }
+    self.pending_release_offset = Some(sample_ix_within_frame.min(FRAME_SIZE - 1));
+  }
+
+  /// Loop bounds converted to grain phase if the grain is currently looping
+  fn loop_phase_bounds(&self, grain_len_samples: f32) -> Option<(f32, f32)> {
+    let loop_config = self.loop_config?;
+    if self.config.reverse || (loop_config.sustain_only && self.is_released) {
+      return None;
+    }
+
+    Some((
+      (loop_config.start_sample_ix - self.config.start_sample_ix) / grain_len_samples,
+      (loop_config.end_sample_ix - self.config.start_sample_ix) / grain_len_samples,
+    ))
+  }
+
   /// Process the grain, adding its output for the current frame to the output buffer.
   ///
   /// If the grain is done playing, returns `true`.
@@ -77,58 +159,63 @@ impl Grain {
     let GrainConfig {
       start_sample_ix: grain_start_sample_ix,
       end_sample_ix: grain_end_sample_ix,
-      ref crossfade,
+      crossfade,
       playback_rate,
       reverse,
     } = self.config;
 
     let start_offset = std::mem::take(&mut self.start_offset_samples);
-    let samples_this_frame = FRAME_SIZE - start_offset;
+    let release_offset = self.pending_release_offset.take();
 
     let grain_len_samples = grain_end_sample_ix - grain_start_sample_ix;
-    let played_samples_this_frame = playback_rate * samples_this_frame as f32;
+    // how much the grain phase is incremented for each output sample
+    let phase_step = playback_rate / grain_len_samples;
+    let mut loop_phase_bounds = self.loop_phase_bounds(grain_len_samples);
 
-    let frame_start_sample_ix = dsp::mix(
-      if !reverse {
-        1. - self.phase
-      } else {
-        self.phase
-      },
-      grain_start_sample_ix,
-      grain_end_sample_ix,
-    );
-    let frame_end_sample_ix = if reverse {
-      frame_start_sample_ix - played_samples_this_frame
-    } else {
-      frame_start_sample_ix + played_samples_this_frame
-    };
-
-    // how much the grain phase will be incremented by this frame
-    let frame_length_phase = played_samples_this_frame / grain_len_samples;
-    let (old_phase, new_phase) = (self.phase, self.phase + frame_length_phase);
-
-    let mut frame_phase = 0.;
     for sample_ix_in_frame in start_offset..FRAME_SIZE {
-      let sample_ix_in_sample_data =
-        dsp::mix(frame_phase, frame_end_sample_ix, frame_start_sample_ix);
+      if release_offset == Some(sample_ix_in_frame) {
+        self.is_released = true;
+        if let Some(env) = &mut self.amp_envelope {
+          env.release();
+        }
+        loop_phase_bounds = self.loop_phase_bounds(grain_len_samples);
+      }
+
+      if self.phase >= 1. {
+        break;
+      }
+      let sample_ix_in_sample_data = if reverse {
+        grain_end_sample_ix - self.phase * grain_len_samples
+      } else {
+        grain_start_sample_ix + self.phase * grain_len_samples
+      };
       if sample_ix_in_sample_data < 0. || sample_ix_in_sample_data >= (sample_data.len() - 1) as f32
       {
         break;
       }
 
-      let grain_phase = dsp::mix(frame_phase, new_phase, old_phase);
-
       let base_sample = dsp::read_interpolated(sample_data, sample_ix_in_sample_data);
-      let crossfade_amp_factor = crossfade.get_amp_factor(grain_phase, grain_len_samples);
-      output[sample_ix_in_frame] += base_sample * crossfade_amp_factor;
-
-      // step by 1/N so the frame covers [frame_start, frame_end): sample k lands at
-      // frame_start + k*rate and the next frame starts exactly at frame_end
-      frame_phase += 1. / samples_this_frame as f32;
+      let crossfade_amp_factor = crossfade.get_amp_factor(self.phase, grain_len_samples);
+      let envelope_level = match &mut self.amp_envelope {
+        Some(env) => env.tick(),
+        None => 1.,
+      };
+      output[sample_ix_in_frame] += base_sample * crossfade_amp_factor * envelope_level * self.gain;
+
+      self.phase += phase_step;
+      if let Some((loop_start_phase, loop_end_phase)) = loop_phase_bounds {
+        if self.phase >= loop_end_phase {
+          self.phase -= loop_end_phase - loop_start_phase;
+        }
+      }
     }
 
-    self.phase = new_phase;
-    new_phase >= 1.
+    let envelope_done = self
+      .amp_envelope
+      .as_ref()
+      .map(AmpEnvelopeState::is_done)
+      .unwrap_or(false);
+    self.phase >= 1. || envelope_done
   }
 }
 
@@ -140,6 +227,17 @@ pub struct SamplerCtx {
   pub active_grains: Vec<Grain>,
   pub output_buf: [f32; FRAME_SIZE],
   pub selections_by_midi_number: Box<[Option<GrainConfig>; 512]>,
+  /// Sustain loops for the selections in `selections_by_midi_number`.  Selections loop between
+  /// these points while their note is held and play through to their end once it's released.
+  pub selection_loops_by_midi_number: Box<[Option<GrainLoop>; 512]>,
+  /// Sample buffers referenced by zones.  If any zones are defined, they are used to handle MIDI
+  /// attacks instead of `selections_by_midi_number`.
+  pub sample_buffers: Vec<Vec<f32>>,
+  pub zones: ZoneMap,
+  /// Text of an SFZ file written from JS before calling `sampler_load_sfz`
+  pub sfz_text_buf: Vec<u8>,
+  /// Paths of the samples used by the loaded SFZ instrument, indexed by sample buffer index
+  pub sfz_sample_paths: Vec<String>,
 }
 
 impl Default for SamplerCtx {
@@ -156,6 +254,93 @@ impl Default for SamplerCtx {
       active_grains: Vec::new(),
       output_buf: uninit(),
       selections_by_midi_number,
+      selection_loops_by_midi_number: Box::new([None; 512]),
+      sample_buffers: Vec::new(),
+      zones: ZoneMap::default(),
+      sfz_text_buf: Vec::new(),
+      sfz_sample_paths: Vec::new(),
+    }
+  }
+}
+
+impl SamplerCtx {
+  pub fn handle_attack(&mut self, midi_number: usize, velocity: u8, sample_ix_within_frame: usize) {
+    let start_offset_samples = sample_ix_within_frame.min(FRAME_SIZE - 1);
+
+    if self.zones.zones().is_empty() {
+      let grain_config = self.selections_by_midi_number[midi_number].clone();
+      if let Some(grain_config) = grain_config {
+        let loop_config = self.selection_loops_by_midi_number[midi_number].map(|lp| GrainLoop {
+          start_sample_ix: lp.start_sample_ix.max(grain_config.start_sample_ix),
+          end_sample_ix: lp.end_sample_ix.min(grain_config.end_sample_ix),
+          ..lp
+        });
+        let mut grain = Grain::new(grain_config, start_offset_samples, None, 1., midi_number);
+        grain.loop_config = loop_config.filter(|lp| lp.end_sample_ix > lp.start_sample_ix);
+        self.active_grains.push(grain);
+      }
+      return;
+    }
+
+    let midi_number = midi_number.min(127) as u8;
+    for (zone, gain) in self.zones.select(midi_number, velocity) {
+      let sample_buffer_len = self
+        .sample_buffers
+        .get(zone.sample_buffer_ix)
+        .map(Vec::len)
+        .unwrap_or(0);
+      let Some(config) = zone.build_grain_config(midi_number, sample_buffer_len) else {
+        continue;
+      };
+
+      let loop_config = zone
+        .loop_bounds(&config)
+        .map(|(start_sample_ix, end_sample_ix)| GrainLoop {
+          start_sample_ix,
+          end_sample_ix,
+          sustain_only: zone.loop_mode == LoopMode::LoopSustain,
+        });
+      let mut grain = Grain::new(
+        config,
+        start_offset_samples,
+        Some(zone.sample_buffer_ix),
+        gain,
+        midi_number as usize,
+      );
+      grain.loop_config = loop_config;
+      grain.amp_envelope = Some(AmpEnvelopeState::new(zone.amp_envelope));
+      grain.ignores_note_off = zone.loop_mode == LoopMode::OneShot;
+      self.active_grains.push(grain);
+    }
+  }
+
+  /// Replaces all zones and zone sample buffers with the provided instrument.  The sample buffers
+  /// are left empty until their data is provided.
+  pub fn set_sfz_instrument(&mut self, instrument: SfzInstrument) {
+    self
+      .active_grains
+      .retain(|grain| grain.sample_buffer_ix.is_none());
+    self.zones.set_zones(instrument.zones);
+    self.sample_buffers = vec![Vec::new(); instrument.sample_paths.len()];
+    self.sfz_sample_paths = instrument.sample_paths;
+  }
+
+  /// Removes all selections and stops any grains playing them
+  pub fn clear_selections(&mut self) {
+    self
+      .active_grains
+      .retain(|grain| grain.sample_buffer_ix.is_some());
+    self.selections_by_midi_number.fill(None);
+    self.selection_loops_by_midi_number.fill(None);
+  }
+
+  /// Releases all grains playing the provided note.  Grains played from one-shot zones and from
+  /// per-key selections without a loop ignore releases.
+  pub fn handle_release(&mut self, midi_number: usize, sample_ix_within_frame: usize) {
+    for grain in &mut self.active_grains {
+      if grain.midi_number == midi_number {
+        grain.release(sample_ix_within_frame);
+      }
     }
   }
 }
@@ -201,17 +386,26 @@ pub extern "C" fn sampler_process(ctx: *mut SamplerCtx) {
   let ctx = unsafe { &mut *ctx };
   ctx.output_buf.fill(0.);
 
-  if ctx.sample_data.is_empty() {
-    return;
-  }
-
   let SamplerCtx {
     sample_data,
     active_grains,
     output_buf,
+    sample_buffers,
     ..
   } = ctx;
-  active_grains.retain_mut(|grain| !grain.process(sample_data, output_buf));
+  active_grains.retain_mut(|grain| {
+    let sample_data = match grain.sample_buffer_ix {
+      Some(buffer_ix) => sample_buffers
+        .get(buffer_ix)
+        .map(Vec::as_slice)
+        .unwrap_or(&[]),
+      None => sample_data.as_slice(),
+    };
+    if sample_data.len() < 2 {
+      return false;
+    }
+    !grain.process(sample_data, output_buf)
+  });
 }
 
 #[no_mangle]
@@ -255,6 +449,32 @@ pub extern "C" fn sampler_set_selection(
 pub extern "C" fn sampler_clear_selection(ctx: *mut SamplerCtx, selection_ix: usize) {
   let ctx = unsafe { &mut *ctx };
   ctx.selections_by_midi_number[selection_ix] = None;
+  ctx.selection_loops_by_midi_number[selection_ix] = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sampler_clear_selections(ctx: *mut SamplerCtx) {
+  let ctx = &mut *ctx;
+  ctx.clear_selections();
+}
+
+/// Sets the sustain loop for the selection mapped to `midi_number`.  The loop is removed if
+/// `loop_end_sample_ix` isn't greater than `loop_start_sample_ix`.  Loop points are clamped to the
+/// selection's bounds when it's played.
+#[no_mangle]
+pub extern "C" fn sampler_set_selection_loop(
+  ctx: *mut SamplerCtx,
+  midi_number: usize,
+  loop_start_sample_ix: f32,
+  loop_end_sample_ix: f32,
+) {
+  let ctx = unsafe { &mut *ctx };
+  ctx.selection_loops_by_midi_number[midi_number] = (loop_end_sample_ix > loop_start_sample_ix)
+    .then_some(GrainLoop {
+      start_sample_ix: loop_start_sample_ix,
+      end_sample_ix: loop_end_sample_ix,
+      sustain_only: true,
+    });
 }
 
 #[no_mangle]
@@ -264,15 +484,169 @@ pub extern "C" fn sampler_handle_midi_attack(
   sample_ix_within_frame: usize,
 ) {
   let ctx = unsafe { &mut *ctx };
-  let grain_config = ctx.selections_by_midi_number[midi_number].clone();
-  if let Some(grain_config) = grain_config {
-    let grain = Grain {
-      config: grain_config,
-      phase: 0.,
-      start_offset_samples: sample_ix_within_frame.min(FRAME_SIZE - 1),
-    };
-    ctx.active_grains.push(grain);
+  ctx.handle_attack(midi_number, 127, sample_ix_within_frame);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sampler_handle_midi_attack_with_velocity(
+  ctx: *mut SamplerCtx,
+  midi_number: usize,
+  velocity: u8,
+  sample_ix_within_frame: usize,
+) {
+  let ctx = &mut *ctx;
+  ctx.handle_attack(midi_number, velocity, sample_ix_within_frame);
+}
+
+/// Releases grains playing `midi_number` that were played from zones that respond to note-offs.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_handle_midi_release(
+  ctx: *mut SamplerCtx,
+  midi_number: usize,
+  sample_ix_within_frame: usize,
+) {
+  let ctx = &mut *ctx;
+  ctx.handle_release(midi_number, sample_ix_within_frame);
+}
+
+/// Resizes the sample buffer at `buffer_ix` used by zones, creating it if it doesn't exist, and
+/// returns a pointer to its first element.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_get_zone_sample_buffer_ptr(
+  ctx: *mut SamplerCtx,
+  buffer_ix: usize,
+  new_len: usize,
+) -> *mut f32 {
+  let ctx = &mut *ctx;
+  if ctx.sample_buffers.len() <= buffer_ix {
+    ctx.sample_buffers.resize_with(buffer_ix + 1, Vec::new);
   }
+  let buf = &mut ctx.sample_buffers[buffer_ix];
+  buf.resize(new_len, 0.);
+  buf.as_mut_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sampler_clear_zones(ctx: *mut SamplerCtx) {
+  let ctx = &mut *ctx;
+  ctx.zones.clear();
+}
+
+/// `round_robin_group` is -1 for zones that aren't part of a round-robin group.  If
+/// `end_sample_ix` is not positive, the zone plays until the end of its sample buffer.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_add_zone(
+  ctx: *mut SamplerCtx,
+  sample_buffer_ix: usize,
+  lo_key: u8,
+  hi_key: u8,
+  root_key: u8,
+  lo_vel: u8,
+  hi_vel: u8,
+  vel_crossfade_in: u8,
+  vel_crossfade_out: u8,
+  round_robin_group: i32,
+  round_robin_position: u32,
+  start_sample_ix: f32,
+  end_sample_ix: f32,
+  crossfade_start_len_samples: f32,
+  crossfade_end_len_samples: f32,
+  gain: f32,
+  tune_cents: f32,
+  pitch_keytrack_cents: f32,
+  reverse: bool,
+) {
+  let ctx = &mut *ctx;
+
+  if lo_key > hi_key || lo_vel > hi_vel {
+    error("zone key and velocity ranges must have lo <= hi");
+    return;
+  }
+
+  ctx.zones.push(Zone {
+    sample_buffer_ix,
+    lo_key,
+    hi_key,
+    root_key,
+    lo_vel,
+    hi_vel,
+    vel_crossfade_in,
+    vel_crossfade_out,
+    round_robin_group: if round_robin_group < 0 {
+      None
+    } else {
+      Some(round_robin_group as u32)
+    },
+    round_robin_position,
+    start_sample_ix: start_sample_ix.max(0.),
+    end_sample_ix,
+    crossfade: CrossfadeConfig {
+      start_len_samples: crossfade_start_len_samples,
+      end_len_samples: crossfade_end_len_samples,
+    },
+    gain,
+    tune_cents,
+    pitch_keytrack_cents,
+    reverse,
+    ..Default::default()
+  });
+}
+
+/// Resizes the buffer that SFZ file text is written into before calling `sampler_load_sfz` and
+/// returns a pointer to its first byte.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_get_sfz_text_buf_ptr(ctx: *mut SamplerCtx, len: usize) -> *mut u8 {
+  let ctx = &mut *ctx;
+  ctx.sfz_text_buf.resize(len, 0);
+  ctx.sfz_text_buf.as_mut_ptr()
+}
+
+/// Parses the SFZ file in the text buffer and replaces all zones with its regions.  Returns the
+/// number of samples it uses, which should then be provided to the zone sample buffers in the
+/// order given by `sampler_get_sfz_sample_path_ptr`.  Returns -1 if the file couldn't be parsed.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_load_sfz(ctx: *mut SamplerCtx) -> i32 {
+  let ctx = &mut *ctx;
+  let text = String::from_utf8_lossy(&ctx.sfz_text_buf);
+  let instrument = match SfzInstrument::parse(&text) {
+    Ok(instrument) => instrument,
+    Err(err) => {
+      error(&format!("Error parsing SFZ file: {err}"));
+      return -1;
+    },
+  };
+  ctx.sfz_text_buf = Vec::new();
+  ctx.set_sfz_instrument(instrument);
+  ctx.sfz_sample_paths.len() as i32
+}
+
+/// Returns a pointer to the UTF-8 path of the sample used by the loaded SFZ instrument for
+/// `sample_buffer_ix`, or null if there is no such sample.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_get_sfz_sample_path_ptr(
+  ctx: *mut SamplerCtx,
+  sample_buffer_ix: usize,
+) -> *const u8 {
+  let ctx = &*ctx;
+  match ctx.sfz_sample_paths.get(sample_buffer_ix) {
+    Some(path) => path.as_ptr(),
+    None => std::ptr::null(),
+  }
+}
+
+/// Returns the length in bytes of the path returned by `sampler_get_sfz_sample_path_ptr`, or 0 if
+/// there is no such sample.
+#[no_mangle]
+pub unsafe extern "C" fn sampler_get_sfz_sample_path_len(
+  ctx: *mut SamplerCtx,
+  sample_buffer_ix: usize,
+) -> usize {
+  let ctx = &*ctx;
+  ctx
+    .sfz_sample_paths
+    .get(sample_buffer_ix)
+    .map(String::len)
+    .unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -297,4 +671,83 @@ mod tests {
     sampler_process(ctx);
     assert!(ctx_ref.output_buf.iter().all(|&s| s != 0.));
   }
+
+  #[test]
+  fn zones_play_from_their_own_buffers() {
+    let ctx = init_sampler_ctx();
+    let ctx_ref = unsafe { &mut *ctx };
+    unsafe { sampler_get_zone_sample_buffer_ptr(ctx, 0, 1024) };
+    unsafe { sampler_get_zone_sample_buffer_ptr(ctx, 1, 1024) };
+    ctx_ref.sample_buffers[0].fill(0.25);
+    ctx_ref.sample_buffers[1].fill(1.);
+
+    // Soft layer and loud layer with no crossfade
+    unsafe {
+      sampler_add_zone(
+        ctx, 0, 0, 127, 60, 0, 63, 0, 0, -1, 0, 0., 0., 0., 0., 1., 0., 100., false,
+      );
+      sampler_add_zone(
+        ctx, 1, 0, 127, 60, 64, 127, 0, 0, -1, 0, 0., 0., 0., 0., 1., 0., 100., false,
+      );
+    }
+
+    unsafe { sampler_handle_midi_attack_with_velocity(ctx, 60, 30, 0) };
+    sampler_process(ctx);
+    assert!(ctx_ref.output_buf.iter().all(|&s| s == 0.25));
+
+    ctx_ref.active_grains.clear();
+    unsafe { sampler_handle_midi_attack_with_velocity(ctx, 60, 100, 0) };
+    sampler_process(ctx);
+    assert!(ctx_ref.output_buf.iter().all(|&s| s == 1.));
+
+    // Pitch tracking: an octave up plays through the 1024 samples in half the frames
+    ctx_ref.active_grains.clear();
+    unsafe { sampler_handle_midi_attack_with_velocity(ctx, 72, 100, 0) };
+    assert_eq!(ctx_ref.active_grains[0].config.playback_rate, 2.);
+    let mut frame_count = 0;
+    while !ctx_ref.active_grains.is_empty() {
+      sampler_process(ctx);
+      frame_count += 1;
+    }
+    assert_eq!(frame_count, 4);
+  }
+
+  #[test]
+  fn selection_loops_sustain_until_release() {
+    let ctx = init_sampler_ctx();
+    let ctx_ref = unsafe { &mut *ctx };
+    ctx_ref.sample_data = (0..4096).map(|i| i as f32).collect();
+    sampler_set_selection(ctx, 60, 0., 4000., 0., 0., 1., false);
+    sampler_set_selection_loop(ctx, 60, 100., 228.);
+
+    sampler_handle_midi_attack(ctx, 60, 0);
+    for _ in 0..8 {
+      sampler_process(ctx);
+      assert!(ctx_ref.output_buf.iter().all(|&s| s < 228.));
+    }
+
+    // Once released, the selection plays through past the loop end
+    ctx_ref.handle_release(60, 0);
+    sampler_process(ctx);
+    sampler_process(ctx);
+    assert!(ctx_ref.output_buf.iter().any(|&s| s > 228.));
+  }
+
+  #[test]
+  fn sfz_sample_paths_are_bounds_checked() {
+    let ctx = init_sampler_ctx();
+    let sfz = include_str!("../fixtures/sfz/looped_pad.sfz");
+    unsafe {
+      let text_buf_ptr = sampler_get_sfz_text_buf_ptr(ctx, sfz.len());
+      std::ptr::copy_nonoverlapping(sfz.as_ptr(), text_buf_ptr, sfz.len());
+      let sample_count = sampler_load_sfz(ctx);
+      assert!(sample_count > 0);
+
+      let last_ix = sample_count as usize - 1;
+      assert!(!sampler_get_sfz_sample_path_ptr(ctx, last_ix).is_null());
+      assert!(sampler_get_sfz_sample_path_len(ctx, last_ix) > 0);
+      assert!(sampler_get_sfz_sample_path_ptr(ctx, last_ix + 1).is_null());
+      assert_eq!(sampler_get_sfz_sample_path_len(ctx, last_ix + 1), 0);
+    }
+  }
 }
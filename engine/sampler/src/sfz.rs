@@ -0,0 +1,706 @@
+//! Parser for SFZ instrument definitions and conversion of the parsed regions into sampler zones.
+//!
+//! The `<control>`, `<global>`, `<master>`, `<group>` and `<region>` headers are supported, with
+//! each region inheriting the opcodes of the headers enclosing it.  `#define` substitutions are
+//! applied.  Opcodes that don't map onto the sampler's zone model are ignored, as are the opcodes
+//! under any other header.
+
+use std::fmt;
+
+use crate::{
+  amp_envelope::AmpEnvelope,
+  zones::{LoopMode, Zone},
+  CrossfadeConfig,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SfzError {
+  UnterminatedHeader {
+    line: usize,
+  },
+  MalformedOpcode {
+    line: usize,
+    text: String,
+  },
+  InvalidValue {
+    line: usize,
+    opcode: String,
+    value: String,
+  },
+  UnsupportedDirective {
+    line: usize,
+    directive: String,
+  },
+  MissingSample {
+    line: usize,
+  },
+  LoadSample {
+    path: String,
+    err: String,
+  },
+}
+
+impl fmt::Display for SfzError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SfzError::UnterminatedHeader { line } => write!(f, "line {line}: unterminated header"),
+      SfzError::MalformedOpcode { line, text } =>
+        write!(f, "line {line}: malformed opcode: {text}"),
+      SfzError::InvalidValue {
+        line,
+        opcode,
+        value,
+      } => write!(f, "line {line}: invalid value for `{opcode}`: {value}"),
+      SfzError::UnsupportedDirective { line, directive } =>
+        write!(f, "line {line}: unsupported directive: {directive}"),
+      SfzError::MissingSample { line } =>
+        write!(f, "line {line}: region doesn't have a `sample` opcode"),
+      SfzError::LoadSample { path, err } => write!(f, "failed to load sample {path}: {err}"),
+    }
+  }
+}
+
+/// A region with the opcodes of its enclosing headers applied
+#[derive(Clone, Debug, PartialEq)]
+pub struct SfzRegion {
+  /// Path of the sample relative to the SFZ file with `default_path` applied and `\` separators
+  /// converted to `/`
+  pub sample: String,
+  pub lokey: u8,
+  pub hikey: u8,
+  pub pitch_keycenter: u8,
+  pub lovel: u8,
+  pub hivel: u8,
+  pub offset: u32,
+  /// Index of the last sample to play, inclusive
+  pub end: Option<u32>,
+  pub loop_mode: LoopMode,
+  pub loop_start: Option<u32>,
+  /// Index of the last sample in the loop, inclusive
+  pub loop_end: Option<u32>,
+  pub ampeg: AmpEnvelope,
+  pub tune_cents: i32,
+  pub transpose: i32,
+  pub volume_db: f32,
+}
+
+impl Default for SfzRegion {
+  fn default() -> Self {
+    SfzRegion {
+      sample: String::new(),
+      lokey: 0,
+      hikey: 127,
+      pitch_keycenter: 60,
+      lovel: 1,
+      hivel: 127,
+      offset: 0,
+      end: None,
+      loop_mode: LoopMode::NoLoop,
+      loop_start: None,
+      loop_end: None,
+      ampeg: AmpEnvelope::default(),
+      tune_cents: 0,
+      transpose: 0,
+      volume_db: 0.,
+    }
+  }
+}
+
+#[derive(Clone)]
+struct Opcode {
+  name: String,
+  value: String,
+  line: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Header {
+  Control,
+  Global,
+  Master,
+  Group,
+  Region,
+  Unsupported,
+}
+
+/// Replaces block comments with spaces, preserving newlines so line numbers stay accurate.
+fn strip_block_comments(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start_ix) = rest.find("/*") {
+    out.push_str(&rest[..start_ix]);
+    let comment_len = rest[start_ix..].find("*/").map(|ix| ix + 2);
+    let comment = &rest[start_ix..start_ix + comment_len.unwrap_or(rest.len() - start_ix)];
+    out.extend(comment.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+    rest = &rest[start_ix + comment.len()..];
+  }
+  out.push_str(rest);
+  out
+}
+
+fn is_opcode_start(s: &str) -> bool {
+  let name_len = s
+    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+    .unwrap_or(s.len());
+  name_len > 0 && s[name_len..].starts_with('=')
+}
+
+/// Returns the length of the value at the start of `s`.  Sample paths can contain spaces, so they
+/// extend up to the next opcode or header on the line rather than the next whitespace.
+fn value_len(opcode: &str, s: &str) -> usize {
+  let allows_spaces = opcode == "sample" || opcode == "default_path";
+  for (ix, c) in s.char_indices() {
+    if c == '<' {
+      return ix;
+    }
+    if c.is_whitespace() && (!allows_spaces || is_opcode_start(s[ix..].trim_start())) {
+      return ix;
+    }
+  }
+  s.len()
+}
+
+fn apply_defines(line: &str, defines: &[(String, String)]) -> String {
+  let mut line = line.to_owned();
+  for (name, value) in defines {
+    line = line.replace(name.as_str(), value);
+  }
+  line
+}
+
+/// Parses the text of an SFZ file into its regions.
+pub fn parse_sfz(text: &str) -> Result<Vec<SfzRegion>, SfzError> {
+  let text = strip_block_comments(text);
+
+  let mut defines: Vec<(String, String)> = Vec::new();
+  let mut header = Header::Unsupported;
+  let mut control: Vec<Opcode> = Vec::new();
+  let mut global: Vec<Opcode> = Vec::new();
+  let mut master: Vec<Opcode> = Vec::new();
+  let mut group: Vec<Opcode> = Vec::new();
+  // Line of each region's header along with the opcodes it inherits and its own opcodes
+  let mut regions: Vec<(usize, Vec<Opcode>, Vec<Opcode>)> = Vec::new();
+
+  for (line_ix, line) in text.lines().enumerate() {
+    let line_num = line_ix + 1;
+    let line = match line.find("//") {
+      Some(comment_ix) => &line[..comment_ix],
+      None => line,
+    };
+    let line = line.trim();
+
+    if let Some(directive) = line.strip_prefix('#') {
+      let mut parts = directive.splitn(3, char::is_whitespace);
+      match (parts.next(), parts.next(), parts.next()) {
+        (Some("define"), Some(name), Some(value)) if name.starts_with('$') => {
+          defines.push((name.to_owned(), value.trim().to_owned()));
+          // Substitute longer names first so that `$AB` isn't clobbered by `$A`
+          defines.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+        },
+        _ =>
+          return Err(SfzError::UnsupportedDirective {
+            line: line_num,
+            directive: line.to_owned(),
+          }),
+      }
+      continue;
+    }
+
+    let line = apply_defines(line, &defines);
+    let mut rest = line.as_str();
+    while !rest.is_empty() {
+      if let Some(after_open) = rest.strip_prefix('<') {
+        let close_ix = after_open
+          .find('>')
+          .ok_or(SfzError::UnterminatedHeader { line: line_num })?;
+        header = match after_open[..close_ix].trim() {
+          "control" => {
+            control.clear();
+            Header::Control
+          },
+          "global" => {
+            global.clear();
+            master.clear();
+            group.clear();
+            Header::Global
+          },
+          "master" => {
+            master.clear();
+            group.clear();
+            Header::Master
+          },
+          "group" => {
+            group.clear();
+            Header::Group
+          },
+          "region" => {
+            let inherited = global
+              .iter()
+              .chain(master.iter())
+              .chain(group.iter())
+              .cloned()
+              .collect();
+            regions.push((line_num, inherited, Vec::new()));
+            Header::Region
+          },
+          _ => Header::Unsupported,
+        };
+        rest = after_open[close_ix + 1..].trim_start();
+        continue;
+      }
+
+      let malformed = || SfzError::MalformedOpcode {
+        line: line_num,
+        text: rest.to_owned(),
+      };
+      let eq_ix = rest.find('=').ok_or_else(malformed)?;
+      let name = &rest[..eq_ix];
+      if !is_opcode_start(rest) {
+        return Err(malformed());
+      }
+      let after_eq = &rest[eq_ix + 1..];
+      let value_len = value_len(name, after_eq);
+      let opcode = Opcode {
+        name: name.to_owned(),
+        value: after_eq[..value_len].trim().to_owned(),
+        line: line_num,
+      };
+      rest = after_eq[value_len..].trim_start();
+
+      match header {
+        Header::Control => control.push(opcode),
+        Header::Global => global.push(opcode),
+        Header::Master => master.push(opcode),
+        Header::Group => group.push(opcode),
+        Header::Region => regions.last_mut().unwrap().2.push(opcode),
+        Header::Unsupported => (),
+      }
+    }
+  }
+
+  let default_path = control
+    .iter()
+    .rev()
+    .find(|op| op.name == "default_path")
+    .map(|op| op.value.replace('\\', "/"))
+    .unwrap_or_default();
+
+  regions
+    .into_iter()
+    .map(|(line, inherited, own)| {
+      build_region(line, inherited.iter().chain(own.iter()), &default_path)
+    })
+    .collect()
+}
+
+/// Parses a MIDI note number or a note name like `c4`, `f#3` or `eb-1` where `c4` is 60.
+fn parse_key(value: &str) -> Option<u8> {
+  if let Ok(key) = value.parse::<u8>() {
+    return (key <= 127).then_some(key);
+  }
+
+  let value = value.to_ascii_lowercase();
+  let mut chars = value.chars();
+  let base: i32 = match chars.next()? {
+    'c' => 0,
+    'd' => 2,
+    'e' => 4,
+    'f' => 5,
+    'g' => 7,
+    'a' => 9,
+    'b' => 11,
+    _ => return None,
+  };
+  let rest = chars.as_str();
+  let (accidental, octave) = if let Some(octave) = rest.strip_prefix('#') {
+    (1, octave)
+  } else if let Some(octave) = rest.strip_prefix('b') {
+    (-1, octave)
+  } else {
+    (0, rest)
+  };
+  let octave: i32 = octave.parse().ok()?;
+  u8::try_from((octave + 1) * 12 + base + accidental)
+    .ok()
+    .filter(|&key| key <= 127)
+}
+
+fn build_region<'a>(
+  region_line: usize,
+  opcodes: impl Iterator<Item = &'a Opcode>,
+  default_path: &str,
+) -> Result<SfzRegion, SfzError> {
+  let mut region = SfzRegion::default();
+  let mut has_sample = false;
+
+  for op in opcodes {
+    let invalid = || SfzError::InvalidValue {
+      line: op.line,
+      opcode: op.name.clone(),
+      value: op.value.clone(),
+    };
+    let key = || parse_key(&op.value).ok_or_else(invalid);
+    let int = || op.value.parse::<i32>().map_err(|_| invalid());
+    let uint = || op.value.parse::<u32>().map_err(|_| invalid());
+    let vel = || {
+      op.value
+        .parse::<u8>()
+        .ok()
+        .filter(|&vel| vel <= 127)
+        .ok_or_else(invalid)
+    };
+    let secs = || {
+      op.value
+        .parse::<f32>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.)
+        .ok_or_else(invalid)
+    };
+    // Returns the value as a fraction from 0 to 1
+    let percent = || {
+      op.value
+        .parse::<f32>()
+        .ok()
+        .filter(|percent| (0. ..=100.).contains(percent))
+        .map(|percent| percent / 100.)
+        .ok_or_else(invalid)
+    };
+
+    match op.name.as_str() {
+      "sample" => {
+        region.sample = format!("{default_path}{}", op.value.replace('\\', "/"));
+        has_sample = true;
+      },
+      "key" => {
+        let key = key()?;
+        region.lokey = key;
+        region.hikey = key;
+        region.pitch_keycenter = key;
+      },
+      "lokey" => region.lokey = key()?,
+      "hikey" => region.hikey = key()?,
+      "pitch_keycenter" => region.pitch_keycenter = key()?,
+      "lovel" => region.lovel = vel()?,
+      "hivel" => region.hivel = vel()?,
+      "offset" => region.offset = uint()?,
+      "end" => region.end = Some(uint()?),
+      "loop_mode" | "loopmode" =>
+        region.loop_mode = match op.value.as_str() {
+          "no_loop" => LoopMode::NoLoop,
+          "one_shot" => LoopMode::OneShot,
+          "loop_continuous" => LoopMode::LoopContinuous,
+          "loop_sustain" => LoopMode::LoopSustain,
+          _ => return Err(invalid()),
+        },
+      "loop_start" | "loopstart" => region.loop_start = Some(uint()?),
+      "loop_end" | "loopend" => region.loop_end = Some(uint()?),
+      "ampeg_delay" => region.ampeg.delay_secs = secs()?,
+      "ampeg_attack" => region.ampeg.attack_secs = secs()?,
+      "ampeg_hold" => region.ampeg.hold_secs = secs()?,
+      "ampeg_decay" => region.ampeg.decay_secs = secs()?,
+      "ampeg_sustain" => region.ampeg.sustain_level = percent()?,
+      "ampeg_release" => region.ampeg.release_secs = secs()?,
+      "tune" => region.tune_cents = int()?,
+      "transpose" => region.transpose = int()?,
+      "volume" =>
+        region.volume_db = op
+          .value
+          .parse::<f32>()
+          .ok()
+          .filter(|db| db.is_finite())
+          .ok_or_else(invalid)?,
+      _ => (),
+    }
+  }
+
+  if !has_sample {
+    return Err(SfzError::MissingSample { line: region_line });
+  }
+  Ok(region)
+}
+
+/// Zones built from an SFZ file along with the samples they play
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SfzInstrument {
+  /// Unique sample paths referenced by the regions.  A zone's `sample_buffer_ix` indexes into
+  /// this.
+  pub sample_paths: Vec<String>,
+  pub zones: Vec<Zone>,
+}
+
+impl SfzInstrument {
+  pub fn parse(text: &str) -> Result<Self, SfzError> { Ok(Self::from_regions(&parse_sfz(text)?)) }
+
+  pub fn from_regions(regions: &[SfzRegion]) -> Self {
+    let mut instrument = SfzInstrument::default();
+
+    for region in regions {
+      let sample_buffer_ix = match instrument
+        .sample_paths
+        .iter()
+        .position(|path| *path == region.sample)
+      {
+        Some(ix) => ix,
+        None => {
+          instrument.sample_paths.push(region.sample.clone());
+          instrument.sample_paths.len() - 1
+        },
+      };
+
+      instrument.zones.push(Zone {
+        sample_buffer_ix,
+        lo_key: region.lokey,
+        hi_key: region.hikey,
+        root_key: region.pitch_keycenter,
+        lo_vel: region.lovel,
+        hi_vel: region.hivel,
+        vel_crossfade_in: 0,
+        vel_crossfade_out: 0,
+        round_robin_group: None,
+        round_robin_position: 0,
+        start_sample_ix: region.offset as f32,
+        end_sample_ix: region.end.map(|end| end as f32).unwrap_or(0.),
+        crossfade: CrossfadeConfig {
+          start_len_samples: 0.,
+          end_len_samples: 0.,
+        },
+        gain: dsp::db_to_gain(region.volume_db),
+        tune_cents: (region.tune_cents + region.transpose * 100) as f32,
+        pitch_keytrack_cents: 100.,
+        reverse: false,
+        loop_mode: region.loop_mode,
+        loop_start_sample_ix: region.loop_start.unwrap_or(0) as f32,
+        // SFZ loop ends are inclusive, but playback jumps back to the loop start upon reaching
+        // the zone's loop end
+        loop_end_sample_ix: region.loop_end.map(|end| (end + 1) as f32).unwrap_or(0.),
+        amp_envelope: region.ampeg,
+      });
+    }
+
+    instrument
+  }
+
+  /// Loads every sample referenced by the instrument, returning the sample buffers indexed by
+  /// `Zone::sample_buffer_ix`.
+  ///
+  /// `load_sample` is given each sample path and returns its mono sample data along with its
+  /// sample rate.  Zones playing samples with a sample rate different from the sampler's are
+  /// retuned so that they play back at their original pitch.
+  pub fn load_samples(
+    &mut self,
+    mut load_sample: impl FnMut(&str) -> Result<(Vec<f32>, u32), String>,
+  ) -> Result<Vec<Vec<f32>>, SfzError> {
+    let mut sample_buffers = Vec::with_capacity(self.sample_paths.len());
+    for (sample_buffer_ix, path) in self.sample_paths.iter().enumerate() {
+      let (samples, sample_rate) = load_sample(path).map_err(|err| SfzError::LoadSample {
+        path: path.clone(),
+        err,
+      })?;
+      sample_buffers.push(samples);
+
+      if sample_rate == 0 || sample_rate as f32 == dsp::SAMPLE_RATE {
+        continue;
+      }
+      let rate_correction_cents = 1200. * (sample_rate as f32 / dsp::SAMPLE_RATE).log2();
+      for zone in &mut self.zones {
+        if zone.sample_buffer_ix == sample_buffer_ix {
+          zone.tune_cents += rate_correction_cents;
+        }
+      }
+    }
+
+    Ok(sample_buffers)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+  use crate::SamplerCtx;
+
+  fn parse_fixture(text: &str) -> Vec<SfzRegion> {
+    match parse_sfz(text) {
+      Ok(regions) => regions,
+      Err(err) => panic!("failed to parse fixture: {err}"),
+    }
+  }
+
+  /// Renders a 16-bit mono WAV file containing a sine wave with the given length and period
+  fn generate_wav(len: usize, period_samples: f32, sample_rate: u32) -> Vec<u8> {
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int,
+    };
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut out, spec).unwrap();
+    for i in 0..len {
+      let val = (i as f32 / period_samples * std::f32::consts::TAU).sin();
+      writer.write_sample((val * 16_000.) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    out.into_inner()
+  }
+
+  fn decode(wav: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    let decoded = wav_decoder::try_decode_wav(wav)?;
+    Ok((decoded.to_mono(), decoded.sample_rate))
+  }
+
+  #[test]
+  fn inherits_opcodes_from_enclosing_headers() {
+    let regions = parse_fixture(include_str!("../fixtures/sfz/layered_piano.sfz"));
+    assert_eq!(regions.len(), 4);
+
+    assert_eq!(regions[0], SfzRegion {
+      sample: "samples/piano/C3 soft.wav".to_owned(),
+      lokey: 36,
+      hikey: 59,
+      pitch_keycenter: 48,
+      lovel: 1,
+      hivel: 63,
+      ampeg: AmpEnvelope {
+        attack_secs: 0.005,
+        release_secs: 0.4,
+        ..Default::default()
+      },
+      ..Default::default()
+    });
+    // Second group overrides the release and adds its own tuning
+    assert_eq!(regions[3].sample, "samples/piano/C4 loud.wav");
+    assert_eq!((regions[3].lovel, regions[3].hivel), (64, 127));
+    assert_eq!(regions[3].ampeg.release_secs, 0.8);
+    assert_eq!(regions[3].ampeg.attack_secs, 0.005);
+    assert_eq!(regions[3].tune_cents, -12);
+    assert_eq!(regions[3].offset, 100);
+    assert_eq!(regions[3].end, Some(1999));
+
+    let instrument = SfzInstrument::from_regions(&regions);
+    assert_eq!(instrument.sample_paths.len(), 4);
+    assert_eq!(instrument.zones[3].sample_buffer_ix, 3);
+    assert_eq!(instrument.zones[3].loop_mode, LoopMode::NoLoop);
+  }
+
+  #[test]
+  fn parses_loops_note_names_and_defines() {
+    let regions = parse_fixture(include_str!("../fixtures/sfz/looped_pad.sfz"));
+    assert_eq!(regions.len(), 2);
+
+    let pad = &regions[0];
+    assert_eq!(pad.sample, "pad/sustain loop.wav");
+    assert_eq!((pad.lokey, pad.hikey, pad.pitch_keycenter), (48, 71, 61));
+    assert_eq!(pad.loop_mode, LoopMode::LoopSustain);
+    assert_eq!((pad.loop_start, pad.loop_end), (Some(400), Some(1599)));
+    assert_eq!(pad.transpose, -12);
+    assert_eq!(pad.tune_cents, 5);
+    assert_eq!(pad.ampeg, AmpEnvelope {
+      delay_secs: 0.,
+      attack_secs: 0.1,
+      hold_secs: 0.05,
+      decay_secs: 0.2,
+      sustain_level: 0.6,
+      release_secs: 1.5,
+    });
+
+    let hit = &regions[1];
+    assert_eq!(hit.sample, "pad/hit.wav");
+    assert_eq!((hit.lokey, hit.hikey, hit.pitch_keycenter), (72, 72, 72));
+    assert_eq!(hit.loop_mode, LoopMode::OneShot);
+    assert_eq!(hit.volume_db, -6.);
+
+    let zone = &SfzInstrument::from_regions(&regions).zones[0];
+    assert_eq!(zone.tune_cents, -1195.);
+    assert_eq!(
+      (zone.loop_start_sample_ix, zone.loop_end_sample_ix),
+      (400., 1600.)
+    );
+  }
+
+  #[test]
+  fn reports_errors_with_line_numbers() {
+    assert_eq!(
+      parse_sfz("<region> sample=a.wav\n<region> sample=b.wav lokey=c11"),
+      Err(SfzError::InvalidValue {
+        line: 2,
+        opcode: "lokey".to_owned(),
+        value: "c11".to_owned(),
+      })
+    );
+    assert_eq!(
+      parse_sfz("<region> sample=a.wav ampeg_sustain=150"),
+      Err(SfzError::InvalidValue {
+        line: 1,
+        opcode: "ampeg_sustain".to_owned(),
+        value: "150".to_owned(),
+      })
+    );
+    assert_eq!(
+      parse_sfz("<group> lovel=10\n\n<region> lokey=10"),
+      Err(SfzError::MissingSample { line: 3 })
+    );
+    assert_eq!(
+      parse_sfz("<region sample=a.wav"),
+      Err(SfzError::UnterminatedHeader { line: 1 })
+    );
+    assert!(matches!(
+      parse_sfz("#include \"other.sfz\""),
+      Err(SfzError::UnsupportedDirective { line: 1, .. })
+    ));
+  }
+
+  #[test]
+  fn builds_playable_instrument_from_generated_wavs() {
+    let mut instrument =
+      SfzInstrument::parse(include_str!("../fixtures/sfz/looped_pad.sfz")).unwrap();
+    let sample_buffers = instrument
+      .load_samples(|path| match path {
+        // recorded at twice the sampler's sample rate, so it needs to be tuned up an octave to play
+        // back at its original pitch
+        "pad/sustain loop.wav" => decode(&generate_wav(2000, 100., 88_200)),
+        "pad/hit.wav" => decode(&generate_wav(500, 50., 44_100)),
+        _ => Err(format!("unexpected path: {path}")),
+      })
+      .unwrap();
+    assert_eq!(sample_buffers.len(), 2);
+    assert_eq!(sample_buffers[0].len(), 2000);
+    assert!((sample_buffers[0][25] - 16_000. / 32_768.).abs() < 1e-3);
+    assert_eq!(instrument.zones[0].tune_cents, -1195. + 1200.);
+
+    let mut ctx = SamplerCtx {
+      sample_buffers,
+      ..Default::default()
+    };
+    ctx.zones.set_zones(instrument.zones);
+
+    // The looped zone keeps playing while held, long after the sample would have run out
+    ctx.handle_attack(61, 100, 0);
+    assert_eq!(ctx.active_grains.len(), 1);
+    for _ in 0..200 {
+      crate::sampler_process(&mut ctx);
+    }
+    assert_eq!(ctx.active_grains.len(), 1);
+    assert!(ctx.output_buf.iter().any(|&s| s.abs() > 0.1));
+
+    // After release, it plays out of the loop and through the rest of the sample
+    ctx.handle_release(61, 0);
+    let mut frame_count = 0;
+    while !ctx.active_grains.is_empty() {
+      crate::sampler_process(&mut ctx);
+      frame_count += 1;
+      assert!(frame_count < 100);
+    }
+
+    // The one-shot zone ignores releases and stops at the end of its sample
+    ctx.handle_attack(72, 100, 0);
+    ctx.handle_release(72, 0);
+    crate::sampler_process(&mut ctx);
+    assert_eq!(ctx.active_grains.len(), 1);
+    for _ in 0..4 {
+      crate::sampler_process(&mut ctx);
+    }
+    assert!(ctx.active_grains.is_empty());
+  }
+}
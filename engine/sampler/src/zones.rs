@@ -0,0 +1,413 @@
+//! Multi-sample zone model.  Each zone maps a range of keys and velocities to a region of one of
+//! the sampler's sample buffers.  Notes between a zone's root key and the edges of its key range
+//! are pitch-tracked by resampling.
+//!
+//! Zones whose velocity ranges overlap are layered, with optional equal-power crossfades at the
+//! edges of each zone's velocity range.  Zones that share a round-robin group take turns being
+//! played rather than layering.
+
+use crate::{amp_envelope::AmpEnvelope, CrossfadeConfig, GrainConfig};
+
+/// How a zone responds to note-offs and whether it loops, mirroring SFZ's `loop_mode`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LoopMode {
+  /// Plays through to the end of the zone, releasing the amp envelope on note-off
+  NoLoop,
+  /// Plays through to the end of the zone, ignoring note-offs
+  #[default]
+  OneShot,
+  /// Loops between the loop points until the amp envelope finishes its release
+  LoopContinuous,
+  /// Loops between the loop points until note-off, then plays through to the end of the zone
+  LoopSustain,
+}
+
+impl LoopMode {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => LoopMode::NoLoop,
+      1 => LoopMode::OneShot,
+      2 => LoopMode::LoopContinuous,
+      3 => LoopMode::LoopSustain,
+      _ => {
+        crate::error(&format!("Invalid loop mode: {val}"));
+        LoopMode::OneShot
+      },
+    }
+  }
+
+  pub fn is_looping(&self) -> bool {
+    matches!(self, LoopMode::LoopContinuous | LoopMode::LoopSustain)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zone {
+  pub sample_buffer_ix: usize,
+  pub lo_key: u8,
+  pub hi_key: u8,
+  /// Key at which the sample plays at its original pitch
+  pub root_key: u8,
+  pub lo_vel: u8,
+  pub hi_vel: u8,
+  /// Width of the velocity range over which the zone fades in above `lo_vel`
+  pub vel_crossfade_in: u8,
+  /// Width of the velocity range over which the zone fades out below `hi_vel`
+  pub vel_crossfade_out: u8,
+  /// Zones with the same round-robin group that match a note take turns playing, ordered by
+  /// `round_robin_position`
+  pub round_robin_group: Option<u32>,
+  pub round_robin_position: u32,
+  pub start_sample_ix: f32,
+  /// If not positive, the zone plays until the end of its sample buffer
+  pub end_sample_ix: f32,
+  pub crossfade: CrossfadeConfig,
+  pub gain: f32,
+  pub tune_cents: f32,
+  /// How much the pitch changes per key away from `root_key`.  100 is regular chromatic tracking
+  /// and 0 plays every key at the same pitch, which is useful for drums.
+  pub pitch_keytrack_cents: f32,
+  pub reverse: bool,
+  pub loop_mode: LoopMode,
+  pub loop_start_sample_ix: f32,
+  /// If not positive, the loop ends at the end of the zone
+  pub loop_end_sample_ix: f32,
+  pub amp_envelope: AmpEnvelope,
+}
+
+impl Default for Zone {
+  fn default() -> Self {
+    Zone {
+      sample_buffer_ix: 0,
+      lo_key: 0,
+      hi_key: 127,
+      root_key: 60,
+      lo_vel: 0,
+      hi_vel: 127,
+      vel_crossfade_in: 0,
+      vel_crossfade_out: 0,
+      round_robin_group: None,
+      round_robin_position: 0,
+      start_sample_ix: 0.,
+      end_sample_ix: 0.,
+      crossfade: CrossfadeConfig {
+        start_len_samples: 0.,
+        end_len_samples: 0.,
+      },
+      gain: 1.,
+      tune_cents: 0.,
+      pitch_keytrack_cents: 100.,
+      reverse: false,
+      loop_mode: LoopMode::OneShot,
+      loop_start_sample_ix: 0.,
+      loop_end_sample_ix: 0.,
+      amp_envelope: AmpEnvelope::default(),
+    }
+  }
+}
+
+impl Zone {
+  fn matches(&self, midi_number: u8, velocity: u8) -> bool {
+    (self.lo_key..=self.hi_key).contains(&midi_number)
+      && (self.lo_vel..=self.hi_vel).contains(&velocity)
+  }
+
+  /// Equal-power gain for the velocity crossfades at either end of this zone's velocity range
+  pub fn velocity_crossfade_gain(&self, velocity: u8) -> f32 {
+    let fade_in = if self.vel_crossfade_in == 0 {
+      1.
+    } else {
+      ((velocity.saturating_sub(self.lo_vel)) as f32 / self.vel_crossfade_in as f32).min(1.)
+    };
+    let fade_out = if self.vel_crossfade_out == 0 {
+      1.
+    } else {
+      ((self.hi_vel.saturating_sub(velocity)) as f32 / self.vel_crossfade_out as f32).min(1.)
+    };
+
+    (fade_in * std::f32::consts::FRAC_PI_2).sin() * (fade_out * std::f32::consts::FRAC_PI_2).sin()
+  }
+
+  pub fn playback_rate(&self, midi_number: u8) -> f32 {
+    let cents =
+      (midi_number as f32 - self.root_key as f32) * self.pitch_keytrack_cents + self.tune_cents;
+    2f32.powf(cents / 1200.)
+  }
+
+  /// Returns the loop points to use for this zone clamped to the zone's bounds, or `None` if the
+  /// zone doesn't loop or its loop is empty.  Loops are only supported for forward playback.
+  pub fn loop_bounds(&self, grain_config: &GrainConfig) -> Option<(f32, f32)> {
+    if !self.loop_mode.is_looping() || self.reverse {
+      return None;
+    }
+
+    let loop_start = self
+      .loop_start_sample_ix
+      .clamp(grain_config.start_sample_ix, grain_config.end_sample_ix);
+    let loop_end = if self.loop_end_sample_ix > 0. {
+      self
+        .loop_end_sample_ix
+        .clamp(grain_config.start_sample_ix, grain_config.end_sample_ix)
+    } else {
+      grain_config.end_sample_ix
+    };
+    if loop_end <= loop_start {
+      return None;
+    }
+    Some((loop_start, loop_end))
+  }
+
+  /// Builds the config for a grain playing this zone for the provided note.  Returns `None` if
+  /// the zone's sample buffer is too short to be played.
+  pub fn build_grain_config(
+    &self,
+    midi_number: u8,
+    sample_buffer_len: usize,
+  ) -> Option<GrainConfig> {
+    let buffer_end = sample_buffer_len.saturating_sub(1) as f32;
+    let end_sample_ix = if self.end_sample_ix > 0. {
+      self.end_sample_ix.min(buffer_end)
+    } else {
+      buffer_end
+    };
+    if self.start_sample_ix >= end_sample_ix {
+      return None;
+    }
+
+    Some(GrainConfig {
+      start_sample_ix: self.start_sample_ix,
+      end_sample_ix,
+      crossfade: self.crossfade,
+      playback_rate: self.playback_rate(midi_number),
+      reverse: self.reverse,
+    })
+  }
+}
+
+#[derive(Default)]
+pub struct ZoneMap {
+  zones: Vec<Zone>,
+  /// Number of times each round-robin group has been triggered, keyed by group
+  round_robin_counters: Vec<(u32, usize)>,
+  // Scratch buffers used by `select`.  They're reserved whenever zones are added so that
+  // selecting zones on note-on doesn't allocate.
+  selected: Vec<(usize, f32)>,
+  triggered_groups: Vec<u32>,
+  round_robin_candidates: Vec<(u32, usize)>,
+}
+
+impl ZoneMap {
+  pub fn zones(&self) -> &[Zone] { &self.zones }
+
+  pub fn clear(&mut self) {
+    self.zones.clear();
+    self.round_robin_counters.clear();
+  }
+
+  fn reserve_scratch(&mut self) {
+    let zone_count = self.zones.len();
+    self.round_robin_counters.reserve(zone_count);
+    self.selected.reserve(zone_count);
+    self.triggered_groups.reserve(zone_count);
+    self.round_robin_candidates.reserve(zone_count);
+  }
+
+  pub fn push(&mut self, zone: Zone) {
+    self.zones.push(zone);
+    self.reserve_scratch();
+  }
+
+  /// Replaces all zones, resetting round-robin state
+  pub fn set_zones(&mut self, zones: Vec<Zone>) {
+    self.clear();
+    self.zones = zones;
+    self.reserve_scratch();
+  }
+
+  fn next_round_robin_ix(&mut self, group: u32) -> usize {
+    let counter = match self
+      .round_robin_counters
+      .iter_mut()
+      .find(|(counter_group, _)| *counter_group == group)
+    {
+      Some((_, counter)) => counter,
+      None => {
+        self.round_robin_counters.push((group, 0));
+        &mut self.round_robin_counters.last_mut().unwrap().1
+      },
+    };
+    let ix = *counter;
+    *counter += 1;
+    ix
+  }
+
+  /// Returns the zones to play for a note along with the gain to play each at.  Matching zones
+  /// without a round-robin group are all played.  For each round-robin group with matching zones,
+  /// one of them is played and the group advances to the next.
+  pub fn select(
+    &mut self,
+    midi_number: u8,
+    velocity: u8,
+  ) -> impl Iterator<Item = (&Zone, f32)> + '_ {
+    self.selected.clear();
+    self.triggered_groups.clear();
+
+    for (zone_ix, zone) in self.zones.iter().enumerate() {
+      if !zone.matches(midi_number, velocity) {
+        continue;
+      }
+
+      match zone.round_robin_group {
+        None => self
+          .selected
+          .push((zone_ix, zone.gain * zone.velocity_crossfade_gain(velocity))),
+        Some(group) =>
+          if !self.triggered_groups.contains(&group) {
+            self.triggered_groups.push(group);
+          },
+      }
+    }
+
+    for group_ix in 0..self.triggered_groups.len() {
+      let group = self.triggered_groups[group_ix];
+      self.round_robin_candidates.clear();
+      self.round_robin_candidates.extend(
+        self
+          .zones
+          .iter()
+          .enumerate()
+          .filter(|(_, zone)| {
+            zone.round_robin_group == Some(group) && zone.matches(midi_number, velocity)
+          })
+          .map(|(zone_ix, zone)| (zone.round_robin_position, zone_ix)),
+      );
+      self.round_robin_candidates.sort_unstable();
+      let rr_ix = self.next_round_robin_ix(group) % self.round_robin_candidates.len();
+      let zone_ix = self.round_robin_candidates[rr_ix].1;
+      let zone = &self.zones[zone_ix];
+      self
+        .selected
+        .push((zone_ix, zone.gain * zone.velocity_crossfade_gain(velocity)));
+    }
+
+    let zones = &self.zones;
+    self
+      .selected
+      .iter()
+      .filter(|&&(_, gain)| gain > 0.)
+      .map(move |&(zone_ix, gain)| (&zones[zone_ix], gain))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Returns the sample buffer index and gain of each selected zone
+  fn select_buffers(map: &mut ZoneMap, midi_number: u8, velocity: u8) -> Vec<(usize, f32)> {
+    map
+      .select(midi_number, velocity)
+      .map(|(zone, gain)| (zone.sample_buffer_ix, gain))
+      .collect()
+  }
+
+  #[test]
+  fn key_ranges_and_pitch_tracking() {
+    let mut map = ZoneMap::default();
+    map.push(Zone {
+      lo_key: 48,
+      hi_key: 59,
+      root_key: 54,
+      ..Default::default()
+    });
+    map.push(Zone {
+      sample_buffer_ix: 1,
+      lo_key: 60,
+      hi_key: 71,
+      root_key: 60,
+      ..Default::default()
+    });
+
+    assert_eq!(select_buffers(&mut map, 47, 100), vec![]);
+    assert_eq!(select_buffers(&mut map, 50, 100), vec![(0, 1.)]);
+    assert_eq!(select_buffers(&mut map, 71, 100), vec![(1, 1.)]);
+
+    assert_eq!(map.zones[1].playback_rate(60), 1.);
+    assert!((map.zones[1].playback_rate(67) - 1.4983).abs() < 1e-3);
+    assert!((map.zones[0].playback_rate(48) - 2f32.powf(-0.5)).abs() < 1e-6);
+  }
+
+  #[test]
+  fn velocity_layers_crossfade() {
+    let mut map = ZoneMap::default();
+    map.push(Zone {
+      lo_vel: 0,
+      hi_vel: 80,
+      vel_crossfade_out: 20,
+      ..Default::default()
+    });
+    map.push(Zone {
+      sample_buffer_ix: 1,
+      lo_vel: 60,
+      hi_vel: 127,
+      vel_crossfade_in: 20,
+      ..Default::default()
+    });
+
+    assert_eq!(select_buffers(&mut map, 60, 40), vec![(0, 1.)]);
+    assert_eq!(select_buffers(&mut map, 60, 110), vec![(1, 1.)]);
+
+    // Equal-power crossfade in the middle of the overlap
+    let layered = select_buffers(&mut map, 60, 70);
+    assert_eq!(layered.len(), 2);
+    let total_power: f32 = layered.iter().map(|(_, gain)| gain * gain).sum();
+    assert!((total_power - 1.).abs() < 1e-5);
+    assert!((layered[0].1 - layered[1].1).abs() < 1e-6);
+  }
+
+  #[test]
+  fn round_robin_cycles_in_position_order() {
+    let mut map = ZoneMap::default();
+    for (buffer_ix, position) in [(0, 2), (1, 0), (2, 1)] {
+      map.push(Zone {
+        sample_buffer_ix: buffer_ix,
+        round_robin_group: Some(7),
+        round_robin_position: position,
+        ..Default::default()
+      });
+    }
+    // Layered zone without a group always plays alongside
+    map.push(Zone {
+      sample_buffer_ix: 3,
+      ..Default::default()
+    });
+
+    let played = (0..6)
+      .map(|_| {
+        let selected = select_buffers(&mut map, 36, 100);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].0, 3);
+        selected[1].0
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(played, vec![1, 2, 0, 1, 2, 0]);
+  }
+
+  #[test]
+  fn grain_config_clamps_to_buffer() {
+    let zone = Zone {
+      start_sample_ix: 10.,
+      end_sample_ix: 5000.,
+      ..Default::default()
+    };
+    let config = zone.build_grain_config(60, 1000).unwrap();
+    assert_eq!(config.end_sample_ix, 999.);
+    assert!(zone.build_grain_config(60, 5).is_none());
+
+    let whole_buffer = Zone::default().build_grain_config(60, 1000).unwrap();
+    assert_eq!(
+      (whole_buffer.start_sample_ix, whole_buffer.end_sample_ix),
+      (0., 999.)
+    );
+  }
+}
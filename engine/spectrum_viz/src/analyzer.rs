@@ -0,0 +1,582 @@
+//! Spectrum analysis for the spectrum visualizations.
+//!
+//! Takes raw time-domain samples, windows and FFTs them, and bins the result into log-spaced
+//! output bins.  The output can be smoothed across frequency with fractional-octave smoothing,
+//! averaged over time, tilted to compensate for the natural roll-off of music (so that pink noise
+//! reads flat with a 3 dB/octave tilt), and tracked with a decaying peak hold.
+//!
+//! All levels are in dBFS with a full-scale sine reading 0 dB regardless of window or FFT size.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+pub const MIN_FFT_SIZE: usize = 256;
+pub const MAX_FFT_SIZE: usize = 32_768;
+/// Frequency at which the tilt compensation has no effect
+const TILT_PIVOT_FREQ: f32 = 1_000.;
+/// Power below this is clamped to avoid `-inf` dB values
+const MIN_POWER: f32 = 1e-20;
+pub const MIN_DB: f32 = -200.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowType {
+  Rectangular = 0,
+  Hann = 1,
+  Hamming = 2,
+  Blackman = 3,
+  BlackmanHarris = 4,
+  FlatTop = 5,
+}
+
+impl WindowType {
+  pub fn from_u32(window: u32) -> Self {
+    match window {
+      0 => WindowType::Rectangular,
+      1 => WindowType::Hann,
+      2 => WindowType::Hamming,
+      3 => WindowType::Blackman,
+      4 => WindowType::BlackmanHarris,
+      5 => WindowType::FlatTop,
+      _ => panic!("Invalid window type: {window}"),
+    }
+  }
+
+  /// Coefficients of the generalized cosine window
+  fn coefficients(self) -> &'static [f32] {
+    match self {
+      WindowType::Rectangular => &[1.],
+      WindowType::Hann => &[0.5, 0.5],
+      WindowType::Hamming => &[0.54, 0.46],
+      WindowType::Blackman => &[0.42, 0.5, 0.08],
+      WindowType::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+      WindowType::FlatTop => &[
+        0.215_578_95,
+        0.416_631_58,
+        0.277_263_16,
+        0.083_578_95,
+        0.006_947_37,
+      ],
+    }
+  }
+
+  fn build(self, size: usize) -> Vec<f32> {
+    let coefficients = self.coefficients();
+    (0..size)
+      .map(|i| {
+        let phase = std::f32::consts::TAU * i as f32 / size as f32;
+        coefficients
+          .iter()
+          .enumerate()
+          .map(|(k, &a)| {
+            let sign = if k % 2 == 0 { 1. } else { -1. };
+            sign * a * (phase * k as f32).cos()
+          })
+          .sum()
+      })
+      .collect()
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AveragingMode {
+  /// Each frame is shown as-is
+  None = 0,
+  /// Exponential moving average with a time constant of `averaging_time_ms`
+  Exponential = 1,
+  /// Equally weighted average of all frames from the last `averaging_time_ms`
+  Linear = 2,
+}
+
+impl AveragingMode {
+  pub fn from_u32(mode: u32) -> Self {
+    match mode {
+      0 => AveragingMode::None,
+      1 => AveragingMode::Exponential,
+      2 => AveragingMode::Linear,
+      _ => panic!("Invalid averaging mode: {mode}"),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpectrumAnalyzerConfig {
+  /// Must be a power of two between `MIN_FFT_SIZE` and `MAX_FFT_SIZE`
+  pub fft_size: usize,
+  pub window: WindowType,
+  /// Smooths each bin over 1/N of an octave around it.  0 disables smoothing, in which case each
+  /// output bin shows the loudest FFT bin that it covers.
+  pub octave_smoothing: u32,
+  pub averaging: AveragingMode,
+  pub averaging_time_ms: f32,
+  pub peak_hold: bool,
+  /// How long peaks are held before they start to decay
+  pub peak_hold_ms: f32,
+  pub peak_decay_db_per_s: f32,
+  /// Slope added to the spectrum, pivoting around 1 kHz.  3 dB/octave makes pink noise flat.
+  pub tilt_db_per_octave: f32,
+}
+
+impl Default for SpectrumAnalyzerConfig {
+  fn default() -> Self {
+    SpectrumAnalyzerConfig {
+      fft_size: 8192,
+      window: WindowType::BlackmanHarris,
+      octave_smoothing: 0,
+      averaging: AveragingMode::Exponential,
+      averaging_time_ms: 150.,
+      peak_hold: false,
+      peak_hold_ms: 1_000.,
+      peak_decay_db_per_s: 20.,
+      tilt_db_per_octave: 0.,
+    }
+  }
+}
+
+pub struct SpectrumAnalyzer {
+  sample_rate: f32,
+  config: SpectrumAnalyzerConfig,
+  fft_planner: FftPlanner<f32>,
+  fft: Arc<dyn Fft<f32>>,
+  window: Vec<f32>,
+  /// Scales FFT magnitudes so that a full-scale sine has an amplitude of 1
+  amplitude_scale: f32,
+  fft_buf: Vec<Complex<f32>>,
+  fft_scratch: Vec<Complex<f32>>,
+  /// Power of each FFT bin from DC to nyquist
+  power: Vec<f32>,
+  /// `power_prefix_sums[i]` is the sum of `power[..i]`
+  power_prefix_sums: Vec<f64>,
+  bin_freqs: Vec<f32>,
+  /// Power of each output bin for the current frame
+  frame_power: Vec<f32>,
+  averaged_power: Vec<f32>,
+  has_averaged_power: bool,
+  /// Frames used for linear averaging, newest first, along with their ages in seconds
+  linear_history: VecDeque<(f32, Vec<f32>)>,
+  spectrum_db: Vec<f32>,
+  peak_db: Vec<f32>,
+  peak_hold_remaining_s: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+  pub fn new(sample_rate: f32, bin_count: usize, min_freq: f32, max_freq: f32) -> Self {
+    let config = SpectrumAnalyzerConfig::default();
+    let mut fft_planner = FftPlanner::new();
+    let fft = fft_planner.plan_fft_forward(config.fft_size);
+    let mut analyzer = SpectrumAnalyzer {
+      sample_rate,
+      config,
+      fft_planner,
+      fft,
+      window: Vec::new(),
+      amplitude_scale: 1.,
+      fft_buf: Vec::new(),
+      fft_scratch: Vec::new(),
+      power: Vec::new(),
+      power_prefix_sums: Vec::new(),
+      bin_freqs: Vec::new(),
+      frame_power: Vec::new(),
+      averaged_power: Vec::new(),
+      has_averaged_power: false,
+      linear_history: VecDeque::new(),
+      spectrum_db: Vec::new(),
+      peak_db: Vec::new(),
+      peak_hold_remaining_s: Vec::new(),
+    };
+    analyzer.rebuild_fft();
+    analyzer.set_bins(bin_count, min_freq, max_freq);
+    analyzer
+  }
+
+  fn rebuild_fft(&mut self) {
+    let fft_size = self.config.fft_size;
+    self.fft = self.fft_planner.plan_fft_forward(fft_size);
+    self.window = self.config.window.build(fft_size);
+    self.amplitude_scale = 2. / self.window.iter().sum::<f32>();
+    self.fft_buf = vec![Complex::default(); fft_size];
+    self.fft_scratch = vec![Complex::default(); self.fft.get_inplace_scratch_len()];
+    self.power = vec![0.; fft_size / 2 + 1];
+    self.power_prefix_sums = vec![0.; fft_size / 2 + 2];
+  }
+
+  fn reset_averaging(&mut self) {
+    self.has_averaged_power = false;
+    self.linear_history.clear();
+  }
+
+  pub fn config(&self) -> &SpectrumAnalyzerConfig { &self.config }
+
+  pub fn set_config(&mut self, mut config: SpectrumAnalyzerConfig) {
+    config.fft_size = config
+      .fft_size
+      .clamp(MIN_FFT_SIZE, MAX_FFT_SIZE)
+      .next_power_of_two();
+    let old_config = std::mem::replace(&mut self.config, config);
+    if old_config.fft_size != config.fft_size || old_config.window != config.window {
+      self.rebuild_fft();
+    }
+    if old_config.averaging != config.averaging
+      || old_config.octave_smoothing != config.octave_smoothing
+      || old_config.fft_size != config.fft_size
+    {
+      self.reset_averaging();
+    }
+    if !config.peak_hold {
+      self.peak_db.fill(MIN_DB);
+    }
+  }
+
+  /// Sets the sample rate of the analyzed signal.  The output bins aren't changed.
+  pub fn set_sample_rate(&mut self, sample_rate: f32) {
+    if self.sample_rate != sample_rate {
+      self.sample_rate = sample_rate;
+      self.reset_averaging();
+    }
+  }
+
+  /// Sets the number of log-spaced output bins and the range of frequencies they cover
+  pub fn set_bins(&mut self, bin_count: usize, min_freq: f32, max_freq: f32) {
+    let bin_count = bin_count.max(2);
+    let ratio = max_freq / min_freq;
+    self.set_bin_freqs(
+      (0..bin_count)
+        .map(|i| min_freq * ratio.powf(i as f32 / (bin_count - 1) as f32))
+        .collect(),
+    );
+  }
+
+  /// Sets the center frequencies of the output bins directly, for non-log frequency axes.  Must
+  /// contain at least two frequencies in ascending order.  Each bin covers the range halfway (in
+  /// log space) to its neighbors.
+  pub fn set_bin_freqs(&mut self, bin_freqs: Vec<f32>) {
+    assert!(
+      bin_freqs.len() >= 2,
+      "at least two output bins are required"
+    );
+    let bin_count = bin_freqs.len();
+    self.bin_freqs = bin_freqs;
+    self.frame_power = vec![0.; bin_count];
+    self.averaged_power = vec![0.; bin_count];
+    self.spectrum_db = vec![MIN_DB; bin_count];
+    self.peak_db = vec![MIN_DB; bin_count];
+    self.peak_hold_remaining_s = vec![0.; bin_count];
+    self.reset_averaging();
+  }
+
+  pub fn bin_freqs(&self) -> &[f32] { &self.bin_freqs }
+
+  /// Level of each output bin in dB after smoothing, averaging, and tilt
+  pub fn spectrum_db(&self) -> &[f32] { &self.spectrum_db }
+
+  /// Held peak level of each output bin in dB.  All `MIN_DB` if peak hold is disabled.
+  pub fn peak_db(&self) -> &[f32] { &self.peak_db }
+
+  /// Returns the power at a fractional FFT bin index, linearly interpolating between bins
+  fn interpolated_power(&self, bin: f32) -> f32 {
+    let bin = bin.clamp(0., (self.power.len() - 1) as f32);
+    let lo = bin.floor() as usize;
+    let hi = (lo + 1).min(self.power.len() - 1);
+    let frac = bin - lo as f32;
+    self.power[lo] + (self.power[hi] - self.power[lo]) * frac
+  }
+
+  /// Returns the mean power over the range of fractional FFT bin indices, treating each bin as
+  /// covering from half a bin below its center to half a bin above it
+  fn mean_power(&self, lo: f32, hi: f32) -> f32 {
+    let max_edge = self.power.len() as f32 - 0.5;
+    let (lo, hi) = (lo.clamp(-0.5, max_edge), hi.clamp(-0.5, max_edge));
+    if hi - lo < 1. {
+      return self.interpolated_power((lo + hi) / 2.);
+    }
+
+    // Integral of the power from the lower edge of bin 0 up to `edge`
+    let integral = |edge: f32| -> f64 {
+      let shifted = (edge + 0.5) as f64;
+      let whole_bins = (shifted.floor() as usize).min(self.power.len());
+      let mut sum = self.power_prefix_sums[whole_bins];
+      if whole_bins < self.power.len() {
+        sum += self.power[whole_bins] as f64 * (shifted - whole_bins as f64);
+      }
+      sum
+    };
+    ((integral(hi) - integral(lo)) / (hi - lo) as f64) as f32
+  }
+
+  fn max_power(&self, lo: f32, hi: f32) -> f32 {
+    let first = lo.ceil().max(0.) as usize;
+    let last = (hi.floor() as usize).min(self.power.len() - 1);
+    if first > last {
+      return self.interpolated_power((lo + hi) / 2.);
+    }
+    self.power[first..=last].iter().copied().fold(0., f32::max)
+  }
+
+  /// Bins the current FFT power into the output bins
+  fn bin_power(&mut self) {
+    let hz_per_bin = self.sample_rate / self.config.fft_size as f32;
+    let smoothing_half_width = if self.config.octave_smoothing > 0 {
+      2f32.powf(1. / self.config.octave_smoothing as f32).sqrt()
+    } else {
+      1.
+    };
+
+    let bin_count = self.bin_freqs.len();
+    for bin_ix in 0..bin_count {
+      let freq = self.bin_freqs[bin_ix];
+      // The first and last bins extend as far outwards as they do inwards
+      let lower_neighbor_ratio = if bin_ix > 0 {
+        freq / self.bin_freqs[bin_ix - 1]
+      } else {
+        self.bin_freqs[1] / freq
+      };
+      let upper_neighbor_ratio = if bin_ix + 1 < bin_count {
+        self.bin_freqs[bin_ix + 1] / freq
+      } else {
+        freq / self.bin_freqs[bin_count - 2]
+      };
+      let lo = freq / lower_neighbor_ratio.sqrt().max(smoothing_half_width) / hz_per_bin;
+      let hi = freq * upper_neighbor_ratio.sqrt().max(smoothing_half_width) / hz_per_bin;
+      self.frame_power[bin_ix] = if self.config.octave_smoothing > 0 {
+        self.mean_power(lo, hi)
+      } else {
+        self.max_power(lo, hi)
+      };
+    }
+  }
+
+  fn average(&mut self, dt_s: f32) {
+    let averaging_time_s = self.config.averaging_time_ms.max(0.) * 0.001;
+    match self.config.averaging {
+      AveragingMode::None => self.averaged_power.copy_from_slice(&self.frame_power),
+      AveragingMode::Exponential =>
+        if !self.has_averaged_power || averaging_time_s <= 0. {
+          self.averaged_power.copy_from_slice(&self.frame_power);
+        } else {
+          let coeff = (-dt_s / averaging_time_s).exp();
+          for (avg, &power) in self.averaged_power.iter_mut().zip(&self.frame_power) {
+            *avg = coeff * *avg + (1. - coeff) * power;
+          }
+        },
+      AveragingMode::Linear => {
+        for (age, _) in &mut self.linear_history {
+          *age += dt_s;
+        }
+        let mut frame = match self.linear_history.back() {
+          Some((age, _)) if *age > averaging_time_s => self.linear_history.pop_back().unwrap().1,
+          _ => Vec::with_capacity(self.frame_power.len()),
+        };
+        while matches!(self.linear_history.back(), Some((age, _)) if *age > averaging_time_s) {
+          self.linear_history.pop_back();
+        }
+        frame.clear();
+        frame.extend_from_slice(&self.frame_power);
+        self.linear_history.push_front((0., frame));
+
+        self.averaged_power.fill(0.);
+        for (_, frame) in &self.linear_history {
+          for (avg, &power) in self.averaged_power.iter_mut().zip(frame) {
+            *avg += power;
+          }
+        }
+        let scale = 1. / self.linear_history.len() as f32;
+        for avg in &mut self.averaged_power {
+          *avg *= scale;
+        }
+      },
+    }
+    self.has_averaged_power = true;
+  }
+
+  fn update_peaks(&mut self, dt_s: f32) {
+    if !self.config.peak_hold {
+      return;
+    }
+
+    let hold_s = self.config.peak_hold_ms * 0.001;
+    let decay_db = self.config.peak_decay_db_per_s * dt_s;
+    for ((peak, hold_remaining), &level) in self
+      .peak_db
+      .iter_mut()
+      .zip(&mut self.peak_hold_remaining_s)
+      .zip(&self.spectrum_db)
+    {
+      if level >= *peak {
+        *peak = level;
+        *hold_remaining = hold_s;
+      } else if *hold_remaining > 0. {
+        *hold_remaining -= dt_s;
+      } else {
+        *peak = (*peak - decay_db).max(level);
+      }
+    }
+  }
+
+  /// Analyzes the last `fft_size` samples of `samples`.  `dt_s` is the time since the previous
+  /// call and is used for averaging and peak decay.
+  pub fn process(&mut self, samples: &[f32], dt_s: f32) {
+    let fft_size = self.config.fft_size;
+    let samples = &samples[samples.len().saturating_sub(fft_size)..];
+    let pad = fft_size - samples.len();
+    for (i, dst) in self.fft_buf.iter_mut().enumerate() {
+      let sample = if i < pad { 0. } else { samples[i - pad] };
+      *dst = Complex::new(sample * self.window[i], 0.);
+    }
+    self
+      .fft
+      .process_with_scratch(&mut self.fft_buf, &mut self.fft_scratch);
+
+    let scale = self.amplitude_scale;
+    for (power, bin) in self.power.iter_mut().zip(&self.fft_buf) {
+      *power = bin.norm_sqr() * scale * scale;
+    }
+    // DC and nyquist don't have a mirrored negative-frequency bin
+    self.power[0] *= 0.25;
+    self.power[fft_size / 2] *= 0.25;
+    let mut sum = 0f64;
+    for (prefix_sum, &power) in self.power_prefix_sums.iter_mut().zip(&self.power) {
+      *prefix_sum = sum;
+      sum += power as f64;
+    }
+    self.power_prefix_sums[self.power.len()] = sum;
+
+    self.bin_power();
+    self.average(dt_s);
+
+    let tilt = self.config.tilt_db_per_octave;
+    for ((db, &power), &freq) in self
+      .spectrum_db
+      .iter_mut()
+      .zip(&self.averaged_power)
+      .zip(&self.bin_freqs)
+    {
+      *db = 10. * power.max(MIN_POWER).log10() + tilt * (freq / TILT_PIVOT_FREQ).log2();
+      *db = db.max(MIN_DB);
+    }
+
+    self.update_peaks(dt_s);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE_RATE: f32 = 44_100.;
+
+  fn sine(freq: f32, amplitude: f32, len: usize) -> Vec<f32> {
+    (0..len)
+      .map(|i| (i as f32 / SAMPLE_RATE * freq * std::f32::consts::TAU).sin() * amplitude)
+      .collect()
+  }
+
+  fn level_at(analyzer: &SpectrumAnalyzer, freq: f32) -> f32 {
+    let bin_ix = analyzer
+      .bin_freqs()
+      .iter()
+      .position(|&bin_freq| bin_freq >= freq)
+      .unwrap();
+    analyzer.spectrum_db()[bin_ix]
+  }
+
+  #[test]
+  fn sine_level_is_window_independent() {
+    let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE, 1024, 20., 20_000.);
+    let samples = sine(1_000., 0.5, MAX_FFT_SIZE);
+    for window in [
+      WindowType::Hann,
+      WindowType::Blackman,
+      WindowType::BlackmanHarris,
+      WindowType::FlatTop,
+    ] {
+      for fft_size in [2048, 16_384] {
+        analyzer.set_config(SpectrumAnalyzerConfig {
+          fft_size,
+          window,
+          averaging: AveragingMode::None,
+          ..Default::default()
+        });
+        analyzer.process(&samples, 0.016);
+
+        let peak = analyzer
+          .spectrum_db()
+          .iter()
+          .copied()
+          .fold(MIN_DB, f32::max);
+        // Scalloping loss is up to ~1.5 dB for the narrower windows
+        assert!(peak < -5.5 && peak > -8., "{window:?} {fft_size}: {peak}");
+        assert!(level_at(&analyzer, 5_000.) < -60., "{window:?} {fft_size}");
+      }
+    }
+  }
+
+  #[test]
+  fn tilt_and_smoothing_flatten_pink_noise() {
+    // Equal-amplitude sines spaced evenly in log frequency have equal power per octave, like pink
+    // noise
+    let mut samples = vec![0.; MAX_FFT_SIZE];
+    let mut freq: f32 = 40.;
+    while freq < 16_000. {
+      for (dst, src) in samples.iter_mut().zip(sine(freq, 0.05, MAX_FFT_SIZE)) {
+        *dst += src;
+      }
+      freq *= 2f32.powf(1. / 12.);
+    }
+
+    let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE, 512, 20., 20_000.);
+    analyzer.set_config(SpectrumAnalyzerConfig {
+      fft_size: MAX_FFT_SIZE,
+      octave_smoothing: 3,
+      averaging: AveragingMode::None,
+      tilt_db_per_octave: 3.,
+      ..Default::default()
+    });
+    analyzer.process(&samples, 0.016);
+
+    let levels: Vec<f32> = [100., 300., 1_000., 3_000., 10_000.]
+      .iter()
+      .map(|&freq| level_at(&analyzer, freq))
+      .collect();
+    for &level in &levels {
+      assert!((level - levels[2]).abs() < 1.5, "{levels:?}");
+    }
+  }
+
+  #[test]
+  fn peaks_hold_then_decay() {
+    let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE, 256, 20., 20_000.);
+    analyzer.set_config(SpectrumAnalyzerConfig {
+      fft_size: 4096,
+      averaging: AveragingMode::None,
+      peak_hold: true,
+      peak_hold_ms: 500.,
+      peak_decay_db_per_s: 20.,
+      ..Default::default()
+    });
+    let loud = sine(1_000., 1., 4096);
+    let silence = vec![0.; 4096];
+
+    analyzer.process(&loud, 0.1);
+    let (peak_ix, &initial_peak) = analyzer
+      .peak_db()
+      .iter()
+      .enumerate()
+      .max_by(|a, b| a.1.total_cmp(b.1))
+      .unwrap();
+    assert!(initial_peak > -2., "{initial_peak}");
+
+    for _ in 0..4 {
+      analyzer.process(&silence, 0.1);
+    }
+    assert_eq!(analyzer.peak_db()[peak_ix], initial_peak);
+
+    for _ in 0..11 {
+      analyzer.process(&silence, 0.1);
+    }
+    let decayed = analyzer.peak_db()[peak_ix];
+    assert!(
+      (decayed - (initial_peak - 20.)).abs() < 2.1,
+      "{initial_peak} {decayed}"
+    );
+  }
+}
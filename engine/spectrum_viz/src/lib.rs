@@ -9,10 +9,12 @@ use palette::{
   rgb::Rgb,
 };
 
+pub mod analyzer;
 #[cfg(feature = "bindgen")]
 mod conf;
 #[cfg(feature = "line_viz")]
 mod line_viz;
+pub mod spectrogram;
 
 const BUFFER_SIZE: usize = 8192;
 
@@ -109,7 +111,7 @@ fn build_luts(gradient: &Gradient<Rgb<Linear<Srgb>>>) -> [ColorLUT; SCALER_FN_CO
   for i in 0..SCALER_FN_COUNT {
     let scaler_fn = unsafe { SCALER_FNS.get_unchecked(i) };
     let mut lut = MaybeUninit::<ColorLUT>::uninit();
-    for j in 0..255 {
+    for j in 0..=255 {
       let color = gradient.get(scaler_fn(j));
       unsafe {
         (lut.as_mut_ptr() as *mut [u8; 4]).add(j as usize).write([
@@ -1,4 +1,10 @@
-pub const FFT_SIZE: usize = 4096;
-pub const FFT_BUFFER_SIZE: usize = FFT_SIZE / 2;
+/// Sample rate of the analyzed signal until `line_spectrogram_set_sample_rate` is called
+pub const DEFAULT_SAMPLE_RATE: f32 = 44_100.;
+/// Lowest frequency shown on the x axis.  The highest is the nyquist frequency.
+pub const MIN_DISPLAY_FREQ: f32 = 10.;
+/// Number of analyzer output bins per pixel of the canvas's width
+pub const BINS_PER_PX: f32 = 0.5;
+pub const MIN_BIN_COUNT: usize = 16;
 pub const CLEAR_COLOR: (u8, u8, u8, u8) = (0, 0, 0, 255);
 pub const LINE_COLOR: (u8, u8, u8, u8) = (200, 200, 200, 255);
+pub const PEAK_COLOR: (u8, u8, u8, u8) = (110, 110, 110, 255);
@@ -1,5 +1,7 @@
 use ndarray::prelude::*;
 
+use super::conf::MIN_DISPLAY_FREQ;
+
 /// Computes the coefficients for a cubic spline interpolation.  Given the x and y values of the
 /// data points, it returns the coefficients (a, b, c, d) for the piecewise cubic functions.
 #[inline(never)]
@@ -72,25 +74,13 @@ fn clamp(x: f32, min: f32, max: f32) -> f32 {
   }
 }
 
-pub const SAMPLE_RATE: f32 = 44_100.;
-pub const NYQUIST: f32 = SAMPLE_RATE / 2.;
-
-fn frequency_bin_to_pixel(bin_ix: usize, num_bins: usize, canvas_width: f32) -> Option<f32> {
-  let min_log_freq = 10.0f32;
-
-  let bin_frequency = (bin_ix as f32) * NYQUIST / ((num_bins - 1) as f32);
-  if bin_frequency < min_log_freq {
-    return None;
-  }
-
-  let log_min_freq = min_log_freq.log10();
-  let log_max_freq = NYQUIST.log10();
-  let log_freq_range = log_max_freq - log_min_freq;
-
-  let log_bin_frequency = bin_frequency.max(min_log_freq).log10();
-  let log_position = (log_bin_frequency - log_min_freq) / log_freq_range;
-
-  Some(log_position * canvas_width)
+/// Returns the x position of `freq` on a log10 scale from `MIN_DISPLAY_FREQ` to `nyquist`
+pub(crate) fn frequency_to_pixel(freq: f32, nyquist: f32, canvas_width: f32) -> f32 {
+  let log_min_freq = MIN_DISPLAY_FREQ.log10();
+  let log_max_freq = nyquist.log10();
+  let log_position =
+    (freq.max(MIN_DISPLAY_FREQ).log10() - log_min_freq) / (log_max_freq - log_min_freq);
+  log_position * canvas_width
 }
 
 /// Estimates the length of a cubic spline segment using the trapezoidal rule.
@@ -126,30 +116,18 @@ fn compute_spline_segment_length<const NUM_INTERVALS: usize>(
   length
 }
 
-/// Assumes that frequency bins are spaced linearly, but will plot them on a log10 scale.  Assumes
-/// that `y_values` is pre-scaled to [0, (canvas_height-1)].
+/// Draws a spline through the points.  `x_values` must be increasing.  Assumes that `y_values` is
+/// pre-scaled to [0, (canvas_height-1)].
 pub(crate) fn draw_cubic_spline(
-  canvas_width: u32,
   canvas_height: u32,
+  x_values: &Array1<f32>,
   y_values: &ArrayView1<f32>,
   points_per_pixel: f32,
   mut plot_pixel: impl FnMut(f32, f32),
 ) {
-  let mut x_values = Vec::with_capacity(y_values.len());
-  for bin_ix in 0..y_values.len() {
-    let x = frequency_bin_to_pixel(bin_ix, y_values.len(), canvas_width as f32);
-    if let Some(x) = x {
-      x_values.push(x);
-    }
-  }
-  let x_values = Array1::from(x_values);
-  // Trim starting y values to match x values.
-  let trim_count = y_values.len() - x_values.len();
-  let y_values = y_values.slice(s![trim_count..]);
-
   let n = y_values.len() - 1;
 
-  let (a, b, c, d) = compute_cubic_spline_coefficients(&x_values, &y_values);
+  let (a, b, c, d) = compute_cubic_spline_coefficients(x_values, y_values);
 
   for i in 0..n {
     let x_start = x_values[i];
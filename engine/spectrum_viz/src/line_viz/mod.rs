@@ -1,6 +1,10 @@
 use canvas_utils::VizView;
 
-use self::{conf::FFT_BUFFER_SIZE, viz::LineSpectrumCtx};
+use self::viz::{DisplayMode, LineSpectrumCtx};
+use crate::{
+  analyzer::{AveragingMode, SpectrumAnalyzerConfig, WindowType},
+  spectrogram::{FrequencyScale, SpectrogramConfig},
+};
 use common::ref_static_mut;
 
 pub(self) mod conf;
@@ -42,18 +46,11 @@ fn maybe_set_panic_hook() {
   std::panic::set_hook(Box::new(hook))
 }
 
-static mut CTX: LineSpectrumCtx = LineSpectrumCtx {
-  view: VizView {
-    dpr: 1,
-    height: 0,
-    width: 0,
-  },
-  frequency_data_buf: [0; FFT_BUFFER_SIZE],
-  frequency_data_buf_f32: [0.; FFT_BUFFER_SIZE],
-  image_data_buf: Vec::new(),
-};
+static mut CTX: Option<LineSpectrumCtx> = None;
 
-fn ctx() -> &'static mut LineSpectrumCtx { ref_static_mut!(CTX) }
+fn ctx() -> &'static mut LineSpectrumCtx {
+  ref_static_mut!(CTX).get_or_insert_with(LineSpectrumCtx::new)
+}
 
 #[no_mangle]
 pub extern "C" fn line_spectrogram_set_view(width_px: usize, height_px: usize, dpr: usize) {
@@ -66,15 +63,88 @@ pub extern "C" fn line_spectrogram_set_view(width_px: usize, height_px: usize, d
   });
 }
 
+/// Sets the sample rate of the analyzed signal, which should be the sample rate of the audio
+/// context it comes from
 #[no_mangle]
-pub extern "C" fn line_spectrogram_get_frequency_data_ptr() -> *mut u8 {
-  ctx().frequency_data_buf.as_mut_ptr()
+pub extern "C" fn line_spectrogram_set_sample_rate(sample_rate: f32) {
+  maybe_set_panic_hook();
+
+  ctx().set_sample_rate(sample_rate);
+}
+
+/// Configures the analysis and the range of levels shown on the y axis.  `window` and `averaging`
+/// are the values of [`WindowType`] and [`AveragingMode`].  `octave_smoothing` smooths over 1/N
+/// octaves, or disables smoothing if 0.
+#[no_mangle]
+pub extern "C" fn line_spectrogram_set_config(
+  fft_size: usize,
+  window: u32,
+  octave_smoothing: u32,
+  averaging: u32,
+  averaging_time_ms: f32,
+  peak_hold: bool,
+  peak_hold_ms: f32,
+  peak_decay_db_per_s: f32,
+  tilt_db_per_octave: f32,
+  min_db: f32,
+  max_db: f32,
+) {
+  maybe_set_panic_hook();
+
+  let config = SpectrumAnalyzerConfig {
+    fft_size,
+    window: WindowType::from_u32(window),
+    octave_smoothing,
+    averaging: AveragingMode::from_u32(averaging),
+    averaging_time_ms,
+    peak_hold,
+    peak_hold_ms,
+    peak_decay_db_per_s,
+    tilt_db_per_octave,
+  };
+  ctx().set_config(config, min_db, max_db);
+}
+
+/// Switches between the line display (`display_mode` 0) and the scrolling spectrogram (1) and
+/// configures the latter.  `frequency_scale` is the value of [`FrequencyScale`], `color_fn` and
+/// `scaler_fn` index the palette and scaler functions, and `beats_per_bar` of 0 hides the beat
+/// grid.
+#[no_mangle]
+pub extern "C" fn line_spectrogram_set_display(
+  display_mode: u32,
+  frequency_scale: u32,
+  color_fn: usize,
+  scaler_fn: usize,
+  columns_per_second: f32,
+  beats_per_bar: u32,
+) {
+  maybe_set_panic_hook();
+
+  let config = SpectrogramConfig {
+    frequency_scale: FrequencyScale::from_u32(frequency_scale),
+    color_fn,
+    scaler_fn,
+    columns_per_second,
+    beats_per_bar,
+  };
+  ctx().set_display(DisplayMode::from_u32(display_mode), config);
+}
+
+/// Returns a pointer to the buffer that the most recent `MAX_FFT_SIZE` samples of the input
+/// signal should be written to before calling `line_spectrogram_process`
+#[no_mangle]
+pub extern "C" fn line_spectrogram_get_time_domain_data_ptr() -> *mut f32 {
+  ctx().time_domain_buf.as_mut_ptr()
 }
 
 #[no_mangle]
 pub extern "C" fn line_spectrogram_get_image_data_ptr() -> *mut u8 {
-  ctx().image_data_buf.as_mut_ptr()
+  ctx().image_data_buf().as_mut_ptr()
 }
 
+/// Analyzes the time domain data and renders it.  `cur_beat` is the current beat of the global
+/// transport.
 #[no_mangle]
-pub extern "C" fn line_spectrogram_process() { ctx().process(); }
+pub extern "C" fn line_spectrogram_process(dt_ms: f32, cur_beat: f64) {
+  ctx().process(dt_ms * 0.001, cur_beat);
+}
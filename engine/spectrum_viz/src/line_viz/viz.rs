@@ -1,20 +1,86 @@
 use canvas_utils::{write_pixel_bilinear, VizView};
-use ndarray::ArrayView1;
+use ndarray::{Array1, ArrayView1};
 
 use super::{
-  conf::{CLEAR_COLOR, FFT_BUFFER_SIZE, LINE_COLOR},
-  cubic_spline::draw_cubic_spline,
+  conf::{
+    BINS_PER_PX, CLEAR_COLOR, DEFAULT_SAMPLE_RATE, LINE_COLOR, MIN_BIN_COUNT, MIN_DISPLAY_FREQ,
+    PEAK_COLOR,
+  },
+  cubic_spline::{draw_cubic_spline, frequency_to_pixel},
 };
+use crate::{
+  analyzer::{SpectrumAnalyzer, SpectrumAnalyzerConfig, MAX_FFT_SIZE},
+  spectrogram::{Spectrogram, SpectrogramConfig},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DisplayMode {
+  /// The current spectrum as a line with frequency on the x axis
+  Line = 0,
+  /// Scrolling history of spectra with frequency on the y axis
+  Spectrogram = 1,
+}
+
+impl DisplayMode {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => DisplayMode::Line,
+      1 => DisplayMode::Spectrogram,
+      _ => panic!("Invalid display mode: {val}"),
+    }
+  }
+}
 
 pub(super) struct LineSpectrumCtx {
   pub view: VizView,
-  pub frequency_data_buf: [u8; FFT_BUFFER_SIZE],
-  pub frequency_data_buf_f32: [f32; FFT_BUFFER_SIZE],
+  /// The most recent `MAX_FFT_SIZE` samples of the input signal, oldest first
+  pub time_domain_buf: Vec<f32>,
+  sample_rate: f32,
+  analyzer: SpectrumAnalyzer,
+  display_mode: DisplayMode,
+  spectrogram: Spectrogram,
+  min_db: f32,
+  max_db: f32,
+  /// x position of each of the analyzer's output bins
+  bin_xs: Array1<f32>,
+  ys: Vec<f32>,
   /// RGBA format
-  pub image_data_buf: Vec<u8>,
+  image_data_buf: Vec<u8>,
+}
+
+fn bin_count_for_width(width: usize) -> usize {
+  ((width as f32 * BINS_PER_PX) as usize).max(MIN_BIN_COUNT)
 }
 
 impl LineSpectrumCtx {
+  pub fn new() -> Self {
+    LineSpectrumCtx {
+      view: VizView {
+        dpr: 1,
+        height: 0,
+        width: 0,
+      },
+      time_domain_buf: vec![0.; MAX_FFT_SIZE],
+      sample_rate: DEFAULT_SAMPLE_RATE,
+      analyzer: SpectrumAnalyzer::new(
+        DEFAULT_SAMPLE_RATE,
+        MIN_BIN_COUNT,
+        MIN_DISPLAY_FREQ,
+        DEFAULT_SAMPLE_RATE / 2.,
+      ),
+      display_mode: DisplayMode::Line,
+      spectrogram: Spectrogram::new(MIN_DISPLAY_FREQ, DEFAULT_SAMPLE_RATE / 2.),
+      min_db: -80.,
+      max_db: -20.,
+      bin_xs: Array1::zeros(0),
+      ys: Vec::new(),
+      image_data_buf: Vec::new(),
+    }
+  }
+
+  fn nyquist(&self) -> f32 { self.sample_rate / 2. }
+
   fn clear_image_data_buf(&mut self) {
     let pixels: &mut [(u8, u8, u8, u8)] = unsafe {
       std::slice::from_raw_parts_mut(
@@ -38,16 +104,91 @@ impl LineSpectrumCtx {
       self.image_data_buf.resize(needed_buf_size, 0);
     }
     self.clear_image_data_buf();
+    // The spectrogram is rendered at full resolution rather than in CSS pixels
+    self.spectrogram.set_size(
+      self.view.width * self.view.dpr,
+      self.view.height * self.view.dpr,
+    );
+
+    self.set_analyzer_bins();
+  }
+
+  /// Sets the analyzer's output bins to the x positions of the line or the rows of the spectrogram
+  fn set_analyzer_bins(&mut self) {
+    match self.display_mode {
+      DisplayMode::Line => {
+        self.analyzer.set_bins(
+          bin_count_for_width(self.view.width),
+          MIN_DISPLAY_FREQ,
+          self.nyquist(),
+        );
+        let (nyquist, width) = (self.nyquist(), self.view.width as f32);
+        self.bin_xs = self
+          .analyzer
+          .bin_freqs()
+          .iter()
+          .map(|&freq| frequency_to_pixel(freq, nyquist, width))
+          .collect();
+      },
+      DisplayMode::Spectrogram => self.analyzer.set_bin_freqs(self.spectrogram.row_freqs()),
+    }
   }
 
-  pub fn process(&mut self) {
+  /// Sets the sample rate of the signal written to `time_domain_buf`, which also determines the
+  /// highest frequency shown
+  pub(crate) fn set_sample_rate(&mut self, sample_rate: f32) {
+    if self.sample_rate == sample_rate {
+      return;
+    }
+
+    self.sample_rate = sample_rate;
+    self.analyzer.set_sample_rate(sample_rate);
+    self.spectrogram.set_max_freq(self.nyquist());
+    self.set_analyzer_bins();
+  }
+
+  pub(crate) fn set_config(&mut self, config: SpectrumAnalyzerConfig, min_db: f32, max_db: f32) {
+    self.analyzer.set_config(config);
+    self.min_db = min_db;
+    self.max_db = max_db;
+    self.spectrogram.set_db_range(min_db, max_db);
+  }
+
+  pub(crate) fn set_display(&mut self, display_mode: DisplayMode, config: SpectrogramConfig) {
+    let bins_changed = display_mode != self.display_mode
+      || (display_mode == DisplayMode::Spectrogram
+        && config.frequency_scale != self.spectrogram.config().frequency_scale);
+    self.display_mode = display_mode;
+    self.spectrogram.set_config(config);
+    if bins_changed {
+      self.spectrogram.clear();
+      self.set_analyzer_bins();
+    }
+  }
+
+  pub fn image_data_buf(&mut self) -> &mut [u8] {
+    match self.display_mode {
+      DisplayMode::Line => &mut self.image_data_buf,
+      DisplayMode::Spectrogram => &mut self.spectrogram.image_data,
+    }
+  }
+
+  /// Analyzes `time_domain_buf` and renders the spectrum.  `dt_s` is the time since the last
+  /// frame was processed.  `cur_beat` is the transport's current beat, which the spectrogram's
+  /// beat grid follows.
+  pub fn process(&mut self, dt_s: f32, cur_beat: f64) {
     if self.view.width == 0 || self.view.height == 0 {
       return;
     }
 
-    // Convert our u8 frequency data to f32 and pre-scale to canvas height
-    for (i, &byte) in self.frequency_data_buf.iter().enumerate() {
-      self.frequency_data_buf_f32[i] = (byte as f32 / 255.0) * (self.view.height - 1) as f32;
+    self.analyzer.process(&self.time_domain_buf, dt_s);
+
+    if self.display_mode == DisplayMode::Spectrogram {
+      self
+        .spectrogram
+        .push_spectrum(self.analyzer.spectrum_db(), dt_s, cur_beat);
+      self.spectrogram.render();
+      return;
     }
 
     self.clear_image_data_buf();
@@ -58,17 +199,30 @@ impl LineSpectrumCtx {
         self.image_data_buf.len() / 4,
       )
     };
-    let draw_point = |x: f32, y: f32| write_pixel_bilinear(pixels, &self.view, x, y, LINE_COLOR);
+    let mut draw_levels = |levels_db: &[f32], color: (u8, u8, u8, u8)| {
+      // Scale to [0, (canvas_height-1)]
+      let height = (self.view.height - 1) as f32;
+      let db_range = (self.max_db - self.min_db).max(0.0001);
+      self.ys.clear();
+      self.ys.extend(
+        levels_db
+          .iter()
+          .map(|&db| ((db - self.min_db) / db_range).clamp(0., 1.) * height),
+      );
 
-    let ys: ArrayView1<f32> = ArrayView1::from(&self.frequency_data_buf_f32);
+      let points_per_pixel = 2.5;
+      draw_cubic_spline(
+        self.view.height as u32,
+        &self.bin_xs,
+        &ArrayView1::from(&self.ys),
+        points_per_pixel,
+        |x, y| write_pixel_bilinear(pixels, &self.view, x, y, color),
+      );
+    };
 
-    let points_per_pixel = 2.5;
-    draw_cubic_spline(
-      self.view.width as u32,
-      self.view.height as u32,
-      &ys,
-      points_per_pixel,
-      draw_point,
-    );
+    if self.analyzer.config().peak_hold {
+      draw_levels(self.analyzer.peak_db(), PEAK_COLOR);
+    }
+    draw_levels(self.analyzer.spectrum_db(), LINE_COLOR);
   }
 }
@@ -0,0 +1,397 @@
+//! Scrolling time-frequency image.  Keeps a history of analyzed spectra, one per pixel column, and
+//! renders all of it to a full RGBA image each frame with the newest column on the right.  The
+//! spectra are produced by the caller, usually with a `SpectrumAnalyzer` whose output bins have
+//! been set to `Spectrogram::row_freqs`.
+
+use crate::{analyzer::MIN_DB, COLOR_FNS, SCALER_FN_COUNT};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FrequencyScale {
+  Linear = 0,
+  Log = 1,
+  Mel = 2,
+}
+
+impl FrequencyScale {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => FrequencyScale::Linear,
+      1 => FrequencyScale::Log,
+      2 => FrequencyScale::Mel,
+      _ => panic!("Invalid frequency scale: {val}"),
+    }
+  }
+}
+
+pub fn hz_to_mel(freq: f32) -> f32 { 2595. * (1. + freq / 700.).log10() }
+
+pub fn mel_to_hz(mel: f32) -> f32 { 700. * (10f32.powf(mel / 2595.) - 1.) }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpectrogramConfig {
+  pub frequency_scale: FrequencyScale,
+  /// Index into the palette color functions (pink, rd_yl_bu, radar)
+  pub color_fn: usize,
+  /// Index into the scaler functions (linear, exponential) applied before the palette
+  pub scaler_fn: usize,
+  /// How fast the image scrolls
+  pub columns_per_second: f32,
+  /// Every Nth beat line is drawn as a bar line.  0 disables the beat grid.
+  pub beats_per_bar: u32,
+}
+
+impl Default for SpectrogramConfig {
+  fn default() -> Self {
+    SpectrogramConfig {
+      frequency_scale: FrequencyScale::Log,
+      color_fn: 0,
+      scaler_fn: 0,
+      columns_per_second: 60.,
+      beats_per_bar: 0,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GridLine {
+  None,
+  Beat,
+  Bar,
+}
+
+const BEAT_LINE_OPACITY: f32 = 0.25;
+const BAR_LINE_OPACITY: f32 = 0.55;
+
+pub struct Spectrogram {
+  config: SpectrogramConfig,
+  min_freq: f32,
+  max_freq: f32,
+  min_db: f32,
+  max_db: f32,
+  width: usize,
+  height: usize,
+  /// `width` columns of `height` levels in dB, column-major with row 0 at the top (`max_freq`).
+  /// Used as a ring buffer.
+  history_db: Vec<f32>,
+  /// Beat grid line to draw over each column of `history_db`
+  grid_lines: Vec<GridLine>,
+  /// Index of the column that will be written next, which is also the oldest one
+  head: usize,
+  /// Fractional columns carried over to the next call to `push_spectrum`
+  pending_columns: f32,
+  /// Transport beat passed to the most recent call to `push_spectrum`
+  beat_position: Option<f64>,
+  /// RGBA format, `width * height` pixels
+  pub image_data: Vec<u8>,
+}
+
+impl Spectrogram {
+  pub fn new(min_freq: f32, max_freq: f32) -> Self {
+    Spectrogram {
+      config: SpectrogramConfig::default(),
+      min_freq,
+      max_freq,
+      min_db: -90.,
+      max_db: 0.,
+      width: 0,
+      height: 0,
+      history_db: Vec::new(),
+      grid_lines: Vec::new(),
+      head: 0,
+      pending_columns: 0.,
+      beat_position: None,
+      image_data: Vec::new(),
+    }
+  }
+
+  pub fn config(&self) -> &SpectrogramConfig { &self.config }
+
+  /// Sets the size of the rendered image in pixels.  Clears the history if the size changed.
+  pub fn set_size(&mut self, width: usize, height: usize) {
+    if self.width == width && self.height == height {
+      return;
+    }
+
+    self.width = width;
+    self.height = height;
+    self.image_data = vec![0; width * height * 4];
+    self.clear();
+  }
+
+  /// Clears the history if the frequency axis changed, since the stored rows would no longer line
+  /// up with it.
+  pub fn set_config(&mut self, mut config: SpectrogramConfig) {
+    config.color_fn = config.color_fn.min(COLOR_FNS.len() - 1);
+    config.scaler_fn = config.scaler_fn.min(SCALER_FN_COUNT - 1);
+    config.columns_per_second = config.columns_per_second.max(0.);
+    let axis_changed = config.frequency_scale != self.config.frequency_scale;
+    self.config = config;
+    if axis_changed {
+      self.clear();
+    }
+  }
+
+  /// Sets the frequency of the top row.  Clears the history if it changed.
+  pub fn set_max_freq(&mut self, max_freq: f32) {
+    if self.max_freq != max_freq {
+      self.max_freq = max_freq;
+      self.clear();
+    }
+  }
+
+  /// Sets the levels mapped to the lowest and highest colors of the palette
+  pub fn set_db_range(&mut self, min_db: f32, max_db: f32) {
+    self.min_db = min_db;
+    self.max_db = max_db;
+  }
+
+  pub fn clear(&mut self) {
+    self.history_db.clear();
+    self.history_db.resize(self.width * self.height, MIN_DB);
+    self.grid_lines.clear();
+    self.grid_lines.resize(self.width, GridLine::None);
+    self.head = 0;
+    self.pending_columns = 0.;
+  }
+
+  /// Center frequency of each row of the image, in ascending order (bottom row first).  The
+  /// spectra passed to `push_spectrum` must have one level per row in this order.
+  pub fn row_freqs(&self) -> Vec<f32> {
+    let row_count = self.height.max(2);
+    let pos = |row: usize| row as f32 / (row_count - 1) as f32;
+    match self.config.frequency_scale {
+      FrequencyScale::Linear => (0..row_count)
+        .map(|row| self.min_freq + pos(row) * (self.max_freq - self.min_freq))
+        .collect(),
+      FrequencyScale::Log => {
+        let ratio = self.max_freq / self.min_freq;
+        (0..row_count)
+          .map(|row| self.min_freq * ratio.powf(pos(row)))
+          .collect()
+      },
+      FrequencyScale::Mel => {
+        let (min_mel, max_mel) = (hz_to_mel(self.min_freq), hz_to_mel(self.max_freq));
+        (0..row_count)
+          .map(|row| mel_to_hz(min_mel + pos(row) * (max_mel - min_mel)))
+          .collect()
+      },
+    }
+  }
+
+  /// Scrolls in as many columns of `spectrum_db` as fit into `dt_s` seconds.
+  ///
+  /// `cur_beat` is the transport's current beat.  Beat grid lines are drawn in the columns where
+  /// its integer part changes, so the grid lines up with the transport and stops while it's
+  /// stopped.  If it moved backwards since the last call, no lines are drawn for this call.
+  pub fn push_spectrum(&mut self, spectrum_db: &[f32], dt_s: f32, cur_beat: f64) {
+    if self.width == 0 || self.height == 0 {
+      return;
+    }
+    debug_assert_eq!(spectrum_db.len(), self.height);
+
+    let beat_start = match self.beat_position {
+      Some(beat_position) if beat_position <= cur_beat => beat_position,
+      _ => cur_beat,
+    };
+    let beat_end = cur_beat;
+    self.beat_position = Some(cur_beat);
+
+    self.pending_columns += dt_s * self.config.columns_per_second;
+    let new_column_count = self.pending_columns.floor() as usize;
+    self.pending_columns -= new_column_count as f32;
+    if new_column_count == 0 {
+      return;
+    }
+
+    // Columns that would scroll off immediately are skipped, but still advance the beat grid
+    let skipped_column_count = new_column_count.saturating_sub(self.width);
+    for col_ix in skipped_column_count..new_column_count {
+      let prev_beat =
+        beat_start + (beat_end - beat_start) * col_ix as f64 / new_column_count as f64;
+      let beat =
+        beat_start + (beat_end - beat_start) * (col_ix + 1) as f64 / new_column_count as f64;
+      self.grid_lines[self.head] = if self.config.beats_per_bar == 0 {
+        GridLine::None
+      } else if beat.floor() > prev_beat.floor() {
+        if (beat.floor() as i64).rem_euclid(self.config.beats_per_bar as i64) == 0 {
+          GridLine::Bar
+        } else {
+          GridLine::Beat
+        }
+      } else {
+        GridLine::None
+      };
+
+      let column = &mut self.history_db[self.head * self.height..(self.head + 1) * self.height];
+      for (dst, &src) in column.iter_mut().zip(spectrum_db.iter().rev()) {
+        *dst = src;
+      }
+      self.head = (self.head + 1) % self.width;
+    }
+  }
+
+  /// Renders the full history into `image_data`
+  pub fn render(&mut self) {
+    let color_fn = COLOR_FNS[self.config.color_fn];
+    let scaler_fn = self.config.scaler_fn;
+    let db_range = (self.max_db - self.min_db).max(0.0001);
+    let pixels: &mut [[u8; 4]] = unsafe {
+      std::slice::from_raw_parts_mut(
+        self.image_data.as_mut_ptr() as *mut _,
+        self.image_data.len() / 4,
+      )
+    };
+
+    for x in 0..self.width {
+      let col_ix = (self.head + x) % self.width;
+      let column = &self.history_db[col_ix * self.height..(col_ix + 1) * self.height];
+      let grid_opacity = match self.grid_lines[col_ix] {
+        GridLine::None => 0.,
+        GridLine::Beat => BEAT_LINE_OPACITY,
+        GridLine::Bar => BAR_LINE_OPACITY,
+      };
+
+      for (y, &db) in column.iter().enumerate() {
+        let val = (((db - self.min_db) / db_range).clamp(0., 1.) * 255.) as u8;
+        let mut color = color_fn(scaler_fn, val);
+        if grid_opacity > 0. {
+          for channel in &mut color[..3] {
+            *channel = (*channel as f32 + (255. - *channel as f32) * grid_opacity) as u8;
+          }
+        }
+        pixels[y * self.width + x] = color;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn build_spectrogram(width: usize, height: usize, config: SpectrogramConfig) -> Spectrogram {
+    let mut spectrogram = Spectrogram::new(20., 20_000.);
+    spectrogram.set_size(width, height);
+    spectrogram.set_config(config);
+    spectrogram.set_db_range(-100., 0.);
+    spectrogram
+  }
+
+  fn pixel(spectrogram: &Spectrogram, x: usize, y: usize) -> [u8; 4] {
+    let ix = (y * spectrogram.width + x) * 4;
+    spectrogram.image_data[ix..ix + 4].try_into().unwrap()
+  }
+
+  #[test]
+  fn row_freqs_span_range() {
+    for scale in [
+      FrequencyScale::Linear,
+      FrequencyScale::Log,
+      FrequencyScale::Mel,
+    ] {
+      let spectrogram = build_spectrogram(4, 101, SpectrogramConfig {
+        frequency_scale: scale,
+        ..Default::default()
+      });
+      let freqs = spectrogram.row_freqs();
+      assert_eq!(freqs.len(), 101);
+      assert!((freqs[0] - 20.).abs() < 0.01, "{scale:?}: {}", freqs[0]);
+      assert!(
+        (freqs[100] - 20_000.).abs() < 1.,
+        "{scale:?}: {}",
+        freqs[100]
+      );
+      assert!(freqs.windows(2).all(|w| w[1] > w[0]));
+    }
+
+    let mel = build_spectrogram(4, 101, SpectrogramConfig {
+      frequency_scale: FrequencyScale::Mel,
+      ..Default::default()
+    });
+    let mid = mel.row_freqs()[50];
+    let expected = mel_to_hz((hz_to_mel(20.) + hz_to_mel(20_000.)) / 2.);
+    assert!((mid - expected).abs() < 0.5);
+    // 1000 Hz is close to 1000 mel by construction
+    assert!((hz_to_mel(1000.) - 1000.).abs() < 0.5);
+  }
+
+  #[test]
+  fn columns_scroll_from_right() {
+    let mut spectrogram = build_spectrogram(8, 4, SpectrogramConfig {
+      columns_per_second: 10.,
+      color_fn: 2,
+      ..Default::default()
+    });
+    let quiet = [-100.; 4];
+    // loud lowest-frequency row, which is drawn at the bottom
+    let loud = [0., -100., -100., -100.];
+
+    spectrogram.push_spectrum(&loud, 0.1, 0.);
+    spectrogram.render();
+    let loud_color = pixel(&spectrogram, 7, 3);
+    let quiet_color = pixel(&spectrogram, 7, 0);
+    assert_ne!(loud_color, quiet_color);
+    assert_eq!(pixel(&spectrogram, 6, 3), quiet_color);
+
+    // 0.25 s at 10 columns/s is 2.5 columns; the fraction carries over
+    spectrogram.push_spectrum(&quiet, 0.25, 0.);
+    spectrogram.render();
+    assert_eq!(pixel(&spectrogram, 5, 3), loud_color);
+    spectrogram.push_spectrum(&quiet, 0.05, 0.);
+    spectrogram.render();
+    assert_eq!(pixel(&spectrogram, 4, 3), loud_color);
+
+    // pushing more columns than fit scrolls everything out
+    spectrogram.push_spectrum(&quiet, 2., 0.);
+    spectrogram.render();
+    assert!((0..8).all(|x| pixel(&spectrogram, x, 3) == quiet_color));
+  }
+
+  #[test]
+  fn beat_grid() {
+    let width = 100;
+    let mut spectrogram = build_spectrogram(width, 2, SpectrogramConfig {
+      columns_per_second: 10.,
+      beats_per_bar: 4,
+      ..Default::default()
+    });
+    // 120 BPM at 10 columns/s is a beat every 5 columns.  The transport starts partway through a
+    // beat, and bars are counted from its beat 0.
+    let mut cur_beat = 0.4;
+    for _ in 0..40 {
+      cur_beat += 0.5;
+      spectrogram.push_spectrum(&[-100., -100.], 0.25, cur_beat);
+    }
+    let lines: Vec<usize> = (0..width)
+      .filter(|&x| spectrogram.grid_lines[(spectrogram.head + x) % width] != GridLine::None)
+      .collect();
+    assert_eq!(lines.len(), 20);
+    assert!(lines.windows(2).all(|w| w[1] - w[0] == 5));
+    let bar_count = (0..width)
+      .filter(|&x| spectrogram.grid_lines[x] == GridLine::Bar)
+      .count();
+    assert_eq!(bar_count, 5);
+    let first_bar_x = (0..width)
+      .find(|&x| spectrogram.grid_lines[(spectrogram.head + x) % width] == GridLine::Bar)
+      .unwrap();
+    // Beat 4 is reached in the 18th column
+    assert_eq!(first_bar_x, 17);
+
+    // No lines are drawn while the transport is stopped or after it jumps backwards
+    for _ in 0..8 {
+      spectrogram.push_spectrum(&[-100., -100.], 0.25, 2.);
+    }
+    let recent_lines = (width - 20..width)
+      .filter(|&x| spectrogram.grid_lines[(spectrogram.head + x) % width] != GridLine::None)
+      .count();
+    assert_eq!(recent_lines, 0);
+
+    spectrogram.render();
+    let line_x = lines[0];
+    assert_ne!(
+      pixel(&spectrogram, line_x, 0),
+      pixel(&spectrogram, line_x + 1, 0)
+    );
+  }
+}
@@ -0,0 +1,20 @@
+pub(crate) use dsp::{FRAME_SIZE, SAMPLE_RATE};
+
+/// Time constant for the running correlation/width statistics.  300ms matches the integration time
+/// used by most hardware correlation meters.
+pub(crate) const STATS_INTEGRATION_TIME_S: f32 = 0.3;
+
+/// Only every Nth sample is plotted in the goniometer
+pub(crate) const GONIOMETER_DECIMATION: usize = 2;
+/// Time constant over which plotted points fade out
+pub(crate) const GONIOMETER_PERSISTENCE_MS: f32 = 120.;
+/// How long it takes the auto-scaling peak level to fall by a factor of e
+pub(crate) const GONIOMETER_PEAK_RELEASE_S: f32 = 1.5;
+/// Smallest peak level the goniometer will scale up to fill the view; quieter signals are shown
+/// smaller rather than amplifying noise
+pub(crate) const GONIOMETER_MIN_SCALE: f32 = 0.01;
+/// Points are drawn additively, so dense regions get brighter
+pub(crate) const GONIOMETER_POINT_COLOR: (u8, u8, u8, u8) = (30, 140, 90, 255);
+
+/// Number of bands produced by `dsp::band_splitter::BandSplitter`
+pub(crate) const BAND_COUNT: usize = 3;
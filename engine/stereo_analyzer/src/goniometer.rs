@@ -0,0 +1,187 @@
+use canvas_utils::{write_line_bilinear, VizView};
+
+use crate::conf::{
+  FRAME_SIZE, GONIOMETER_DECIMATION, GONIOMETER_MIN_SCALE, GONIOMETER_PEAK_RELEASE_S,
+  GONIOMETER_PERSISTENCE_MS, GONIOMETER_POINT_COLOR, SAMPLE_RATE,
+};
+
+fn as_pixels(image_data: &mut [u8]) -> &mut [(u8, u8, u8, u8)] {
+  unsafe { std::slice::from_raw_parts_mut(image_data.as_mut_ptr() as *mut _, image_data.len() / 4) }
+}
+
+/// Renders a Lissajous/goniometer display of a stereo signal.  The signal is rotated 45° so that
+/// mono content is drawn as a vertical line, out-of-phase content as a horizontal line, and
+/// left/right-only content along the diagonals.
+///
+/// Samples are decimated into a stream of points which are drawn as connected lines on top of the
+/// previously rendered image, which fades out over time to give a phosphor-like trail.
+pub(crate) struct Goniometer {
+  pub view: VizView,
+  /// RGBA image data
+  pub image_data: Vec<u8>,
+  /// (side, mid) points which have been committed but not yet rendered
+  pending_points: Vec<(f32, f32)>,
+  decimation_counter: usize,
+  /// Pixel coordinates of the most recently rendered point, used as the start of the next line
+  last_point_px: Option<(f32, f32)>,
+  /// Auto-scaling level which maps to the edge of the plot
+  peak_level: f32,
+  peak_release_per_frame: f32,
+}
+
+impl Goniometer {
+  pub fn new() -> Self {
+    Goniometer {
+      view: VizView {
+        dpr: 1,
+        width: 0,
+        height: 0,
+      },
+      image_data: Vec::new(),
+      pending_points: Vec::new(),
+      decimation_counter: 0,
+      last_point_px: None,
+      peak_level: GONIOMETER_MIN_SCALE,
+      peak_release_per_frame: (-(FRAME_SIZE as f32) / (GONIOMETER_PEAK_RELEASE_S * SAMPLE_RATE))
+        .exp(),
+    }
+  }
+
+  pub fn set_view(&mut self, view: VizView) {
+    self.image_data.clear();
+    self.image_data.resize(view.get_image_data_buffer_size_bytes(), 0);
+    self.view = view;
+    self.last_point_px = None;
+    self.clear_image_data_buffer();
+  }
+
+  fn clear_image_data_buffer(&mut self) { as_pixels(&mut self.image_data).fill((0, 0, 0, 255)); }
+
+  pub fn commit_samples(&mut self, left: &[f32; FRAME_SIZE], right: &[f32; FRAME_SIZE]) {
+    let mut frame_peak = 0f32;
+    for (&l, &r) in left.iter().zip(right.iter()) {
+      let side = (r - l) * std::f32::consts::FRAC_1_SQRT_2;
+      let mid = (l + r) * std::f32::consts::FRAC_1_SQRT_2;
+      frame_peak = frame_peak.max(side.abs()).max(mid.abs());
+
+      self.decimation_counter += 1;
+      if self.decimation_counter >= GONIOMETER_DECIMATION {
+        self.decimation_counter = 0;
+        self.pending_points.push((side, mid));
+      }
+    }
+
+    self.peak_level = (self.peak_level * self.peak_release_per_frame)
+      .max(frame_peak)
+      .max(GONIOMETER_MIN_SCALE);
+  }
+
+  fn point_to_px(&self, (side, mid): (f32, f32)) -> (f32, f32) {
+    let center_x = self.view.width as f32 / 2.;
+    let center_y = self.view.height as f32 / 2.;
+    // Leave a small margin so that peaks aren't drawn on the very edge
+    let radius = (self.view.width.min(self.view.height) as f32 / 2.) * 0.95;
+    let x = center_x + (side / self.peak_level).clamp(-1., 1.) * radius;
+    let y = center_y - (mid / self.peak_level).clamp(-1., 1.) * radius;
+    (
+      x.clamp(0., self.view.width as f32 - 1.),
+      y.clamp(0., self.view.height as f32 - 1.),
+    )
+  }
+
+  /// Fades out the previously rendered image according to the time elapsed since the last render
+  /// and draws all pending points.
+  pub fn render(&mut self, dt_ms: f32) {
+    if self.image_data.is_empty() {
+      self.pending_points.clear();
+      return;
+    }
+
+    let fade = (-dt_ms.max(0.) / GONIOMETER_PERSISTENCE_MS).exp();
+    let fade_u16 = (fade * 256.) as u16;
+    let fade_channel = |channel: u8| ((channel as u16 * fade_u16) >> 8) as u8;
+    for px in as_pixels(&mut self.image_data) {
+      *px = (fade_channel(px.0), fade_channel(px.1), fade_channel(px.2), px.3);
+    }
+
+    let pending_points = std::mem::take(&mut self.pending_points);
+    for &point in &pending_points {
+      let (x_px, y_px) = self.point_to_px(point);
+      let (last_x_px, last_y_px) = self.last_point_px.unwrap_or((x_px, y_px));
+      write_line_bilinear(
+        as_pixels(&mut self.image_data),
+        &self.view,
+        last_x_px,
+        last_y_px,
+        x_px,
+        y_px,
+        GONIOMETER_POINT_COLOR,
+      );
+      self.last_point_px = Some((x_px, y_px));
+    }
+    // Reuse the allocation
+    self.pending_points = pending_points;
+    self.pending_points.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lit_columns(goniometer: &Goniometer) -> Vec<usize> {
+    let width = goniometer.view.width;
+    let mut columns: Vec<usize> = goniometer
+      .image_data
+      .as_chunks::<4>()
+      .0
+      .iter()
+      .enumerate()
+      .filter(|(_, px)| px[0] > 0 || px[1] > 0 || px[2] > 0)
+      .map(|(ix, _)| ix % width)
+      .collect();
+    columns.sort_unstable();
+    columns.dedup();
+    columns
+  }
+
+  #[test]
+  fn mono_signal_is_drawn_vertically() {
+    let mut goniometer = Goniometer::new();
+    goniometer.set_view(VizView {
+      dpr: 1,
+      width: 101,
+      height: 101,
+    });
+
+    let mut frame = [0.; FRAME_SIZE];
+    for (i, sample) in frame.iter_mut().enumerate() {
+      *sample = (std::f32::consts::TAU * i as f32 / 64.).sin() * 0.5;
+    }
+    goniometer.commit_samples(&frame, &frame);
+    goniometer.render(16.);
+
+    // The plot is centered at x=50.5, so bilinear filtering lights up the two center columns
+    assert_eq!(lit_columns(&goniometer), vec![50, 51]);
+  }
+
+  #[test]
+  fn image_fades_out() {
+    let mut goniometer = Goniometer::new();
+    goniometer.set_view(VizView {
+      dpr: 1,
+      width: 50,
+      height: 50,
+    });
+    let left = [0.5; FRAME_SIZE];
+    let right = [-0.5; FRAME_SIZE];
+    goniometer.commit_samples(&left, &right);
+    goniometer.render(16.);
+    assert!(!lit_columns(&goniometer).is_empty());
+
+    for _ in 0..100 {
+      goniometer.render(16.);
+    }
+    assert!(lit_columns(&goniometer).is_empty());
+  }
+}
@@ -0,0 +1,174 @@
+use canvas_utils::VizView;
+use common::ref_static_mut;
+use dsp::band_splitter::BandSplitter;
+
+use crate::{
+  conf::{BAND_COUNT, FRAME_SIZE},
+  goniometer::Goniometer,
+  stats::StereoStats,
+};
+
+pub(crate) mod conf;
+pub(crate) mod goniometer;
+pub mod stats;
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "env")]
+extern "C" {
+  fn log_err(ptr: *const u8, len: usize);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+extern "C" fn log_err(_ptr: *const u8, _len: usize) {}
+
+/// Layout of the readings buffer exposed to JS:
+///
+/// 0: broadband phase correlation in [-1, 1]
+/// 1: broadband stereo width in [0, 1]
+/// 2: level change in dB when summed to mono
+/// 3-5: phase correlation for the low, mid, and high bands
+/// 6-8: stereo width for the low, mid, and high bands
+pub const READING_COUNT: usize = 3 + BAND_COUNT * 2;
+
+pub(crate) struct StereoAnalyzer {
+  pub goniometer: Goniometer,
+  pub broadband_stats: StereoStats,
+  pub band_splitters: [BandSplitter; 2],
+  pub band_stats: [StereoStats; BAND_COUNT],
+  pub readings: [f32; READING_COUNT],
+}
+
+impl StereoAnalyzer {
+  pub fn new() -> Self {
+    StereoAnalyzer {
+      goniometer: Goniometer::new(),
+      broadband_stats: StereoStats::default(),
+      band_splitters: [BandSplitter::new(), BandSplitter::new()],
+      band_stats: [StereoStats::default(); BAND_COUNT],
+      readings: [0.; READING_COUNT],
+    }
+  }
+
+  pub fn commit_samples(&mut self, frame: &[[f32; FRAME_SIZE]; 2]) {
+    let [left, right] = frame;
+    for (&l, &r) in left.iter().zip(right.iter()) {
+      self.broadband_stats.process(l, r);
+    }
+
+    let mut bands = [[[0.; FRAME_SIZE]; BAND_COUNT]; 2];
+    for (splitter, (input, [low, mid, high])) in self
+      .band_splitters
+      .iter_mut()
+      .zip(frame.iter().zip(bands.iter_mut()))
+    {
+      splitter.apply_frame(input, low, mid, high);
+    }
+    let [left_bands, right_bands] = &bands;
+    for ((stats, left), right) in self.band_stats.iter_mut().zip(left_bands).zip(right_bands) {
+      for (&l, &r) in left.iter().zip(right.iter()) {
+        stats.process(l, r);
+      }
+    }
+
+    self.goniometer.commit_samples(left, right);
+  }
+
+  pub fn process(&mut self, dt_ms: f32) {
+    self.goniometer.render(dt_ms);
+
+    self.readings[0] = self.broadband_stats.correlation();
+    self.readings[1] = self.broadband_stats.width();
+    self.readings[2] = self.broadband_stats.mono_loss_db();
+    for (band_ix, stats) in self.band_stats.iter().enumerate() {
+      self.readings[3 + band_ix] = stats.correlation();
+      self.readings[3 + BAND_COUNT + band_ix] = stats.width();
+    }
+  }
+}
+
+/// Used for receiving live samples from the audio thread.  Planar stereo: all left samples
+/// followed by all right samples.
+static mut FRAME_DATA_BUFFER: [[f32; FRAME_SIZE]; 2] = [[0.; FRAME_SIZE]; 2];
+
+static mut ANALYZER: Option<StereoAnalyzer> = None;
+
+fn analyzer() -> &'static mut StereoAnalyzer {
+  let analyzer = ref_static_mut!(ANALYZER);
+  if analyzer.is_none() {
+    common::set_raw_panic_hook(log_err);
+    *analyzer = Some(StereoAnalyzer::new());
+  }
+  analyzer.as_mut().unwrap()
+}
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_set_view(width: usize, height: usize, dpr: usize) {
+  analyzer()
+    .goniometer
+    .set_view(VizView { width, height, dpr });
+}
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_get_frame_data_ptr() -> *mut f32 {
+  &raw mut FRAME_DATA_BUFFER as *mut f32
+}
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_commit_samples() {
+  let frame_data = ref_static_mut!(FRAME_DATA_BUFFER);
+  analyzer().commit_samples(frame_data);
+}
+
+/// Renders all committed samples into the image data buffer and updates the readings
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_process(dt_ms: f32) { analyzer().process(dt_ms); }
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_get_image_data_buf_ptr() -> *const u8 {
+  analyzer().goniometer.image_data.as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_get_image_data_buf_len() -> usize {
+  analyzer().goniometer.image_data.len()
+}
+
+#[no_mangle]
+pub extern "C" fn stereo_analyzer_get_readings_ptr() -> *const f32 {
+  analyzer().readings.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use dsp::SAMPLE_RATE;
+
+  /// Bass summed to mono with anti-phase highs is a classic mono-compatibility problem that
+  /// the broadband correlation can hide; the per-band readings should catch it.
+  #[test]
+  fn per_band_correlation() {
+    let mut analyzer = StereoAnalyzer::new();
+    let mut sample_ix = 0;
+    for _ in 0..(SAMPLE_RATE as usize * 2 / FRAME_SIZE) {
+      let mut frame = [[0.; FRAME_SIZE]; 2];
+      for i in 0..FRAME_SIZE {
+        let t = sample_ix as f32 / SAMPLE_RATE;
+        let low = (std::f32::consts::TAU * 40. * t).sin();
+        let high = 0.5 * (std::f32::consts::TAU * 8_000. * t).sin();
+        frame[0][i] = low + high;
+        frame[1][i] = low - high;
+        sample_ix += 1;
+      }
+      analyzer.commit_samples(&frame);
+    }
+    analyzer.process(16.);
+
+    let low_correlation = analyzer.readings[3];
+    let high_correlation = analyzer.readings[5];
+    let high_width = analyzer.readings[3 + BAND_COUNT + 2];
+    assert!(low_correlation > 0.95, "low band correlation: {low_correlation}");
+    assert!(high_correlation < -0.95, "high band correlation: {high_correlation}");
+    assert!(high_width > 0.95, "high band width: {high_width}");
+    assert!(analyzer.readings[0] > 0.5, "broadband correlation: {}", analyzer.readings[0]);
+  }
+}
@@ -0,0 +1,118 @@
+use crate::conf::{SAMPLE_RATE, STATS_INTEGRATION_TIME_S};
+
+/// Below this mean power, a channel is considered silent and correlation is reported as 0
+const SILENCE_THRESHOLD: f32 = 1e-10;
+
+/// Running exponentially-weighted second-order statistics of a stereo signal from which phase
+/// correlation, stereo width, and mono-compatibility can be derived.
+#[derive(Clone, Copy)]
+pub struct StereoStats {
+  coefficient: f32,
+  left_power: f32,
+  right_power: f32,
+  cross_power: f32,
+}
+
+impl Default for StereoStats {
+  fn default() -> Self {
+    StereoStats {
+      coefficient: (-1. / (STATS_INTEGRATION_TIME_S * SAMPLE_RATE)).exp(),
+      left_power: 0.,
+      right_power: 0.,
+      cross_power: 0.,
+    }
+  }
+}
+
+impl StereoStats {
+  #[inline]
+  pub fn process(&mut self, left: f32, right: f32) {
+    let c = self.coefficient;
+    self.left_power = c * self.left_power + (1. - c) * left * left;
+    self.right_power = c * self.right_power + (1. - c) * right * right;
+    self.cross_power = c * self.cross_power + (1. - c) * left * right;
+  }
+
+  /// Phase correlation in [-1, 1].  1 means the channels are identical (mono), 0 means they are
+  /// uncorrelated, and -1 means they are identical but with inverted polarity.
+  pub fn correlation(&self) -> f32 {
+    let denominator = (self.left_power * self.right_power).sqrt();
+    if denominator < SILENCE_THRESHOLD {
+      return 0.;
+    }
+    (self.cross_power / denominator).clamp(-1., 1.)
+  }
+
+  /// Fraction of the signal's power that's in the side channel, in [0, 1].  0 is mono, 0.5 is
+  /// fully decorrelated, and 1 is entirely out of phase.
+  pub fn width(&self) -> f32 {
+    // mid = (l + r) / 2, side = (l - r) / 2
+    let mid_power = (self.left_power + self.right_power + 2. * self.cross_power) / 4.;
+    let side_power = (self.left_power + self.right_power - 2. * self.cross_power) / 4.;
+    let total = mid_power + side_power;
+    if total < SILENCE_THRESHOLD {
+      return 0.;
+    }
+    (side_power / total).clamp(0., 1.)
+  }
+
+  /// Change in level in dB when the signal is summed to mono as `(l + r) / 2`, relative to the
+  /// average level of the two channels.  0 dB for mono content, -3 dB for decorrelated content, and
+  /// heading towards -inf as the channels cancel out.
+  pub fn mono_loss_db(&self) -> f32 {
+    let stereo_power = (self.left_power + self.right_power) / 2.;
+    if stereo_power < SILENCE_THRESHOLD {
+      return 0.;
+    }
+    let mono_power = (self.left_power + self.right_power + 2. * self.cross_power) / 4.;
+    (10. * (mono_power.max(0.) / stereo_power).log10()).max(-100.)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(gen: impl Fn(usize) -> (f32, f32)) -> StereoStats {
+    let mut stats = StereoStats::default();
+    for i in 0..SAMPLE_RATE as usize * 2 {
+      let (l, r) = gen(i);
+      stats.process(l, r);
+    }
+    stats
+  }
+
+  fn sine(i: usize, freq: f32) -> f32 {
+    (std::f32::consts::TAU * freq * i as f32 / SAMPLE_RATE).sin()
+  }
+
+  #[test]
+  fn mono_and_inverted() {
+    let mono = run(|i| (sine(i, 440.), sine(i, 440.)));
+    assert!((mono.correlation() - 1.).abs() < 1e-3);
+    assert!(mono.width() < 1e-3);
+    assert!(mono.mono_loss_db().abs() < 0.01);
+
+    let inverted = run(|i| (sine(i, 440.), -sine(i, 440.)));
+    assert!((inverted.correlation() + 1.).abs() < 1e-3);
+    assert!((inverted.width() - 1.).abs() < 1e-3);
+    assert!(inverted.mono_loss_db() < -60.);
+  }
+
+  #[test]
+  fn decorrelated() {
+    // Sines at different frequencies are orthogonal, much like independent noise
+    let stats = run(|i| (sine(i, 440.), sine(i, 1_013.)));
+    assert!(stats.correlation().abs() < 0.05, "{}", stats.correlation());
+    assert!((stats.width() - 0.5).abs() < 0.05, "{}", stats.width());
+    assert!((stats.mono_loss_db() + 3.01).abs() < 0.3, "{}", stats.mono_loss_db());
+  }
+
+  #[test]
+  fn silence() {
+    let stats = run(|_| (0., 0.));
+    assert_eq!(stats.correlation(), 0.);
+    assert_eq!(stats.width(), 0.);
+    assert_eq!(stats.mono_loss_db(), 0.);
+  }
+}
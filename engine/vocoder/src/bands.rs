@@ -0,0 +1,226 @@
+//! Layout and filter design for the vocoder's analysis and synthesis bands.
+//!
+//! Bands are laid out contiguously between the min and max frequency, evenly spaced on the chosen
+//! frequency scale.  Each band is a bandpass built from a Linkwitz-Riley highpass at its lower edge
+//! and a Linkwitz-Riley lowpass at its upper edge.  The magnitudes of a Linkwitz-Riley lowpass and
+//! highpass at the same frequency sum to unity at every frequency, so the magnitude responses of
+//! all bands sum to a flat response and no part of the spectrum is emphasized or lost between
+//! bands.
+
+use dsp::filters::biquad::{compute_higher_order_biquad_q_factors, BiquadFilter, FilterMode};
+
+use crate::SAMPLE_RATE;
+
+pub const MIN_BAND_COUNT: usize = 1;
+pub const MAX_BAND_COUNT: usize = 64;
+pub const MIN_FILTER_ORDER: usize = 4;
+pub const MAX_FILTER_ORDER: usize = 32;
+const MIN_FREQ_HZ: f32 = 20.;
+const MAX_FREQ_HZ: f32 = SAMPLE_RATE as f32 / 2. * 0.95;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BandSpacing {
+  /// Bands have equal width in octaves
+  Log,
+  /// Bands have equal width on the Bark scale, which follows the critical bands of hearing
+  Bark,
+  Mel,
+}
+
+impl BandSpacing {
+  pub fn from_u32(val: u32) -> Self {
+    match val {
+      0 => BandSpacing::Log,
+      1 => BandSpacing::Bark,
+      2 => BandSpacing::Mel,
+      _ => panic!("Invalid band spacing: {}", val),
+    }
+  }
+
+  fn freq_to_scale(self, freq_hz: f32) -> f32 {
+    match self {
+      BandSpacing::Log => freq_hz.log2(),
+      // Traunmüller's approximation
+      BandSpacing::Bark => 26.81 * freq_hz / (1960. + freq_hz) - 0.53,
+      BandSpacing::Mel => 2595. * (1. + freq_hz / 700.).log10(),
+    }
+  }
+
+  fn scale_to_freq(self, val: f32) -> f32 {
+    match self {
+      BandSpacing::Log => val.exp2(),
+      BandSpacing::Bark => 1960. * (val + 0.53) / (26.28 - val),
+      BandSpacing::Mel => 700. * (10f32.powf(val / 2595.) - 1.),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VocoderConfig {
+  pub band_count: usize,
+  pub min_freq_hz: f32,
+  pub max_freq_hz: f32,
+  pub spacing: BandSpacing,
+  /// Order of the highpass and lowpass at each band edge.  Each band is made of this many biquads
+  /// in series.
+  pub filter_order: usize,
+}
+
+impl Default for VocoderConfig {
+  fn default() -> Self {
+    VocoderConfig {
+      band_count: 22,
+      min_freq_hz: 80.,
+      max_freq_hz: 12_000.,
+      spacing: BandSpacing::Log,
+      filter_order: 16,
+    }
+  }
+}
+
+impl VocoderConfig {
+  /// Clamps all params to supported values.  The filter order is rounded to a multiple of 4 since
+  /// Linkwitz-Riley filters are built from pairs of even-order Butterworth filters.
+  pub fn sanitized(self) -> Self {
+    let min_freq_hz = self.min_freq_hz.clamp(MIN_FREQ_HZ, MAX_FREQ_HZ / 2.);
+    VocoderConfig {
+      band_count: self.band_count.clamp(MIN_BAND_COUNT, MAX_BAND_COUNT),
+      min_freq_hz,
+      max_freq_hz: self.max_freq_hz.clamp(min_freq_hz * 2., MAX_FREQ_HZ),
+      spacing: self.spacing,
+      filter_order: ((self.filter_order + 2) / 4 * 4).clamp(MIN_FILTER_ORDER, MAX_FILTER_ORDER),
+    }
+  }
+
+  /// Returns the `band_count + 1` frequencies in Hz that separate the bands, from low to high
+  pub fn band_edges_hz(&self) -> Vec<f32> {
+    let min = self.spacing.freq_to_scale(self.min_freq_hz);
+    let max = self.spacing.freq_to_scale(self.max_freq_hz);
+    (0..=self.band_count)
+      .map(|i| {
+        let val = min + (max - min) * i as f32 / self.band_count as f32;
+        self.spacing.scale_to_freq(val)
+      })
+      .collect()
+  }
+
+  /// Returns the geometric center frequency of each band
+  pub fn band_center_freqs_hz(&self) -> Vec<f32> {
+    self
+      .band_edges_hz()
+      .windows(2)
+      .map(|edges| (edges[0] * edges[1]).sqrt())
+      .collect()
+  }
+
+  /// Builds the chain of filters for each band.  Every band has `filter_order` filters: lowpasses
+  /// followed by highpasses.
+  pub fn build_band_filters(&self) -> Vec<Vec<BiquadFilter>> {
+    // Each half of a Linkwitz-Riley filter is a Butterworth filter of half the order
+    let butterworth_qs = compute_higher_order_biquad_q_factors(self.filter_order / 2);
+    let build_lr = |mode: FilterMode, freq: f32| {
+      butterworth_qs
+        .iter()
+        .chain(butterworth_qs.iter())
+        .map(move |&q| BiquadFilter::new(mode, q, freq, 0.))
+    };
+
+    self
+      .band_edges_hz()
+      .windows(2)
+      .map(|edges| {
+        build_lr(FilterMode::Lowpass, edges[1])
+          .chain(build_lr(FilterMode::Highpass, edges[0]))
+          .collect()
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Evaluates the complex frequency response of a chain of biquads at `freq_hz`
+  fn chain_response(filters: &[BiquadFilter], freq_hz: f32) -> (f64, f64) {
+    let w = std::f64::consts::TAU * freq_hz as f64 / SAMPLE_RATE as f64;
+    // z^-1 and z^-2
+    let (z1_re, z1_im) = (w.cos(), -w.sin());
+    let (z2_re, z2_im) = ((2. * w).cos(), -(2. * w).sin());
+    let mul = |(a_re, a_im): (f64, f64), (b_re, b_im): (f64, f64)| {
+      (a_re * b_re - a_im * b_im, a_re * b_im + a_im * b_re)
+    };
+
+    filters.iter().fold((1., 0.), |acc, filter| {
+      let (b0, b1, b2) = (
+        filter.b0_over_a0 as f64,
+        filter.b1_over_a0 as f64,
+        filter.b2_over_a0 as f64,
+      );
+      let (a1, a2) = (filter.a1_over_a0 as f64, filter.a2_over_a0 as f64);
+      let num = (b0 + b1 * z1_re + b2 * z2_re, b1 * z1_im + b2 * z2_im);
+      let den = (1. + a1 * z1_re + a2 * z2_re, a1 * z1_im + a2 * z2_im);
+      let den_mag_sq = den.0 * den.0 + den.1 * den.1;
+      let h = mul(num, (den.0 / den_mag_sq, -den.1 / den_mag_sq));
+      mul(acc, h)
+    })
+  }
+
+  #[test]
+  fn band_edges_span_range() {
+    for spacing in [BandSpacing::Log, BandSpacing::Bark, BandSpacing::Mel] {
+      let config = VocoderConfig {
+        spacing,
+        ..Default::default()
+      };
+      let edges = config.band_edges_hz();
+      assert_eq!(edges.len(), config.band_count + 1);
+      assert!((edges[0] - config.min_freq_hz).abs() < 0.01, "{spacing:?}");
+      assert!(
+        (edges[config.band_count] - config.max_freq_hz).abs() < 1.,
+        "{spacing:?}"
+      );
+      assert!(
+        edges.windows(2).all(|pair| pair[1] > pair[0]),
+        "{spacing:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn summed_band_response_is_flat() {
+    for (band_count, filter_order, spacing) in [
+      (22, 16, BandSpacing::Log),
+      (8, 8, BandSpacing::Bark),
+      (40, 24, BandSpacing::Mel),
+    ] {
+      let config = VocoderConfig {
+        band_count,
+        filter_order,
+        spacing,
+        ..Default::default()
+      }
+      .sanitized();
+      let bands = config.build_band_filters();
+
+      // Skip the outermost band on each side where the response rolls off
+      let edges = config.band_edges_hz();
+      let (lo, hi) = (edges[1], edges[band_count - 1]);
+      for i in 0..=200 {
+        let freq_hz = lo * (hi / lo).powf(i as f32 / 200.);
+        let summed_magnitude: f64 = bands
+          .iter()
+          .map(|band| {
+            let (re, im) = chain_response(band, freq_hz);
+            (re * re + im * im).sqrt()
+          })
+          .sum();
+        let db = 20. * summed_magnitude.log10();
+        assert!(
+          db.abs() < 1.5,
+          "{config:?}: summed response at {freq_hz:.1}Hz is {db:.2}dB"
+        );
+      }
+    }
+  }
+}
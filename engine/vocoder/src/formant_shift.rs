@@ -0,0 +1,103 @@
+//! Shifts the formants of the vocoded output by applying the level of each modulator band to a
+//! carrier band at a different frequency.
+
+/// For each carrier band, the position among the modulator bands that its level is read from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LevelSource {
+  /// Reads the level of the modulator band at `ix` directly
+  Band(usize),
+  /// Linearly interpolates between the modulator bands at `ix` and `ix + 1`
+  Interpolated { ix: usize, frac: f32 },
+  /// The shifted frequency is outside the range of the modulator bands
+  None,
+}
+
+pub struct FormantShifter {
+  /// Center frequencies of the bands in octaves
+  band_center_octaves: Vec<f32>,
+  shift_semitones: f32,
+  sources: Vec<LevelSource>,
+}
+
+impl FormantShifter {
+  pub fn new(band_center_freqs_hz: &[f32]) -> Self {
+    let mut shifter = FormantShifter {
+      band_center_octaves: band_center_freqs_hz.iter().map(|f| f.log2()).collect(),
+      shift_semitones: f32::NAN,
+      sources: vec![LevelSource::None; band_center_freqs_hz.len()],
+    };
+    shifter.set_shift(0.);
+    shifter
+  }
+
+  /// Positive shifts move formants up in frequency.  The mapping is only recomputed when the shift
+  /// changes.
+  pub fn set_shift(&mut self, shift_semitones: f32) {
+    if (shift_semitones - self.shift_semitones).abs() < 1e-3 {
+      return;
+    }
+    self.shift_semitones = shift_semitones;
+
+    let centers = &self.band_center_octaves;
+    let shift_octaves = shift_semitones / 12.;
+    for (carrier_ix, source) in self.sources.iter_mut().enumerate() {
+      // A carrier band takes its level from the modulator band that's shifted onto it
+      let target = centers[carrier_ix] - shift_octaves;
+      *source = if shift_octaves.abs() < 1e-4 {
+        LevelSource::Band(carrier_ix)
+      } else {
+        match centers
+          .windows(2)
+          .position(|pair| pair[0] <= target && target <= pair[1])
+        {
+          Some(ix) => LevelSource::Interpolated {
+            ix,
+            frac: (target - centers[ix]) / (centers[ix + 1] - centers[ix]),
+          },
+          None => LevelSource::None,
+        }
+      };
+    }
+  }
+
+  /// Reads the level for the carrier band at `carrier_ix` from the modulator band levels
+  #[inline]
+  pub fn get_level(&self, modulator_levels: &[f32], carrier_ix: usize) -> f32 {
+    match self.sources[carrier_ix] {
+      LevelSource::Band(ix) => modulator_levels[ix],
+      LevelSource::Interpolated { ix, frac } =>
+        modulator_levels[ix] + (modulator_levels[ix + 1] - modulator_levels[ix]) * frac,
+      LevelSource::None => 0.,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shift_by_whole_bands() {
+    // Bands spaced 2 semitones apart
+    let centers: Vec<f32> = (0..12).map(|i| 200. * 2f32.powf(i as f32 / 6.)).collect();
+    let levels: Vec<f32> = (0..12).map(|i| i as f32).collect();
+    let mut shifter = FormantShifter::new(&centers);
+
+    for ix in 0..12 {
+      assert_eq!(shifter.get_level(&levels, ix), ix as f32);
+    }
+
+    shifter.set_shift(4.);
+    assert_eq!(shifter.get_level(&levels, 0), 0.);
+    assert_eq!(shifter.get_level(&levels, 1), 0.);
+    for ix in 2..12 {
+      assert!((shifter.get_level(&levels, ix) - (ix - 2) as f32).abs() < 1e-3);
+    }
+
+    shifter.set_shift(-3.);
+    for ix in 0..10 {
+      assert!((shifter.get_level(&levels, ix) - (ix as f32 + 1.5)).abs() < 1e-3);
+    }
+    assert_eq!(shifter.get_level(&levels, 11), 0.);
+  }
+}
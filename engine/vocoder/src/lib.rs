@@ -1,7 +1,5 @@
-use std::mem::MaybeUninit;
-
 use dsp::{
-  filters::biquad::{BiquadFilter, BiquadFilterBank2D, FilterMode},
+  filters::biquad::{BiquadFilter, BiquadFilterBank2D},
   FRAME_SIZE,
 };
 
@@ -18,13 +16,18 @@ extern "C" fn log_err(ptr: *const u8, len: usize) {
   eprintln!("{}", s);
 }
 
+pub mod bands;
+pub mod formant_shift;
+pub mod sibilance;
+
+use bands::{BandSpacing, VocoderConfig};
+use formant_shift::FormantShifter;
+use sibilance::SibilanceDetector;
+
 const SAMPLE_RATE: usize = 44_100;
-const BAND_ORDER: usize = 16; // 24;
-const BAND_COUNT: usize = 22; // 36;
-const FILTERS_PER_BAND: usize = BAND_ORDER;
 
 pub struct VocoderBand {
-  pub filters: [BiquadFilter; FILTERS_PER_BAND],
+  pub filters: Vec<BiquadFilter>,
 }
 
 impl VocoderBand {
@@ -37,13 +40,14 @@ impl VocoderBand {
   }
 }
 
-fn one_pole_coeff(tau_s: f32) -> f32 { (-1. / (SAMPLE_RATE as f32 * tau_s)).exp() }
+pub(crate) fn one_pole_coeff(tau_s: f32) -> f32 { (-1. / (SAMPLE_RATE as f32 * tau_s)).exp() }
 
 /// Two-stage envelope follower: attack/release one-pole on |x|, then a short smoothing pole to
 /// suppress residual ripple at 2*f_band which would otherwise ring-modulate the carrier.  Time
 /// constants scale with band center frequency, clamped so low bands stay responsive and high
-/// bands stay smooth.
+/// bands stay smooth.  The attack and release can be overridden per band.
 pub struct LevelDetectionBand {
+  band_center_freq_hz: f32,
   attack_coeff: f32,
   release_coeff: f32,
   smooth_coeff: f32,
@@ -53,18 +57,32 @@ pub struct LevelDetectionBand {
 
 impl LevelDetectionBand {
   fn new(band_center_freq_hz: f32) -> Self {
-    let attack_tau = (0.5 / band_center_freq_hz).clamp(0.001, 0.012);
-    let release_tau = (4. / band_center_freq_hz).clamp(0.02, 0.08);
     let smooth_tau = (0.5 / band_center_freq_hz).clamp(0.0003, 0.006);
-    LevelDetectionBand {
-      attack_coeff: one_pole_coeff(attack_tau),
-      release_coeff: one_pole_coeff(release_tau),
+    let mut band = LevelDetectionBand {
+      band_center_freq_hz,
+      attack_coeff: 0.,
+      release_coeff: 0.,
       smooth_coeff: one_pole_coeff(smooth_tau),
       ar_state: 0.,
       smooth_state: 0.,
-    }
+    };
+    band.set_times(None, None);
+    band
+  }
+
+  /// Sets the attack and release time constants in seconds.  `None` uses the default for the
+  /// band's center frequency.
+  pub fn set_times(&mut self, attack_tau: Option<f32>, release_tau: Option<f32>) {
+    let attack_tau = attack_tau.unwrap_or((0.5 / self.band_center_freq_hz).clamp(0.001, 0.012));
+    let release_tau = release_tau.unwrap_or((4. / self.band_center_freq_hz).clamp(0.02, 0.08));
+    self.attack_coeff = one_pole_coeff(attack_tau);
+    self.release_coeff = one_pole_coeff(release_tau);
   }
 
+  /// Returns the current level without updating it
+  #[inline]
+  pub fn level(&self) -> f32 { self.smooth_state }
+
   pub fn process(&mut self, sample: f32) -> f32 {
     let x = sample.abs();
     let coeff = if x > self.ar_state {
@@ -84,197 +102,198 @@ impl LevelDetectionBand {
 }
 
 pub struct LevelDetectionCtx {
-  pub bands: [LevelDetectionBand; BAND_COUNT],
+  pub bands: Vec<LevelDetectionBand>,
 }
 
 impl LevelDetectionCtx {
   fn new(band_center_freqs_hz: &[f32]) -> Self {
     Self {
-      bands: std::array::from_fn(|band_ix| LevelDetectionBand::new(band_center_freqs_hz[band_ix])),
+      bands: band_center_freqs_hz
+        .iter()
+        .map(|&freq| LevelDetectionBand::new(freq))
+        .collect(),
     }
   }
 }
 
 pub struct VocoderCtx {
+  pub config: VocoderConfig,
   pub carrier_input_buf: [f32; FRAME_SIZE],
-  pub carrier_filter_bands: Box<[VocoderBand; BAND_COUNT]>,
-  pub carrier_filter_bands_simd: Box<BiquadFilterBank2D<BAND_COUNT, FILTERS_PER_BAND>>,
+  pub carrier_filter_bands: Vec<VocoderBand>,
+  pub carrier_filter_bands_simd: BiquadFilterBank2D,
   pub modulator_input_buf: [f32; FRAME_SIZE],
-  pub modulator_filter_bands: Box<[VocoderBand; BAND_COUNT]>,
-  pub modulator_filter_bands_simd: Box<BiquadFilterBank2D<BAND_COUNT, FILTERS_PER_BAND>>,
+  pub modulator_filter_bands: Vec<VocoderBand>,
+  pub modulator_filter_bands_simd: BiquadFilterBank2D,
   pub modulator_level_detection_ctx: LevelDetectionCtx,
+  pub sibilance_detector: SibilanceDetector,
+  pub formant_shifter: FormantShifter,
+  /// Scratch buffers holding the current sample's output for each band
+  pub modulator_outputs: Vec<f32>,
+  pub carrier_outputs: Vec<f32>,
+  pub modulator_levels: Vec<f32>,
   pub output_buf: [f32; FRAME_SIZE],
   pub carrier_gain: f32,
   pub modulator_gain: f32,
   pub output_gain: f32,
+  pub sibilance_gain: f32,
 }
 
-// Need two params per filter: cutoff and Q
-const FILTER_PARAMS_BUF_LEN: usize = BAND_COUNT * FILTERS_PER_BAND * 2;
-pub static mut FILTER_PARAMS_BUF: [f32; FILTER_PARAMS_BUF_LEN] = [0.; FILTER_PARAMS_BUF_LEN];
-
-#[no_mangle]
-pub extern "C" fn get_filter_params_buf_ptr() -> *mut f32 { &raw mut FILTER_PARAMS_BUF as *mut _ }
-
-#[inline(always)]
-fn uninit<T>() -> T { unsafe { MaybeUninit::uninit().assume_init() } }
-
 impl VocoderCtx {
+  /// Lays out the filters so that each row holds the filter at the same depth for every band
   #[cold]
-  fn build_filter_bands() -> Box<[VocoderBand; BAND_COUNT]> {
-    let mut bands: Box<MaybeUninit<[VocoderBand; BAND_COUNT]>> = Box::new_uninit();
-    let bands_ptr = bands.as_mut_ptr() as *mut VocoderBand;
-    for band_ix in 0..BAND_COUNT {
-      let mut band = MaybeUninit::<VocoderBand>::uninit();
-      let band_ptr = band.as_mut_ptr() as *mut BiquadFilter;
-      for filter_ix in 0..FILTERS_PER_BAND {
-        let filter_type = if filter_ix < FILTERS_PER_BAND / 2 {
-          FilterMode::Lowpass
-        } else {
-          FilterMode::Highpass
-        };
-        unsafe {
-          let cutoff_freq = FILTER_PARAMS_BUF[(FILTERS_PER_BAND * band_ix + filter_ix) * 2];
-          let q = FILTER_PARAMS_BUF[(FILTERS_PER_BAND * band_ix + filter_ix) * 2 + 1];
-          let filter = BiquadFilter::new(filter_type, q, cutoff_freq, 0.);
-          band_ptr.add(filter_ix).write(filter)
-        };
-      }
-      unsafe { bands_ptr.add(band_ix).write(band.assume_init()) };
-    }
-    unsafe { bands.assume_init() }
+  fn build_filter_bank_simd(
+    bands: &[Vec<BiquadFilter>],
+    filter_order: usize,
+  ) -> BiquadFilterBank2D {
+    let rows: Vec<Vec<BiquadFilter>> = (0..filter_order)
+      .map(|filter_ix| bands.iter().map(|band| band[filter_ix]).collect())
+      .collect();
+    BiquadFilterBank2D::new(&rows)
   }
 
-  pub fn new() -> Self {
-    let mut band_center_freqs_hz = [0.; BAND_COUNT];
-    for band_ix in 0..BAND_COUNT {
-      let lowpass_freq =
-        unsafe { FILTER_PARAMS_BUF[(FILTERS_PER_BAND * band_ix + FILTERS_PER_BAND / 2 - 1) * 2] };
-      let highpass_freq =
-        unsafe { FILTER_PARAMS_BUF[(FILTERS_PER_BAND * band_ix + FILTERS_PER_BAND / 2) * 2] };
-      band_center_freqs_hz[band_ix] = (lowpass_freq + highpass_freq) / 2.;
-    }
-
-    let carrier_filter_bands = Self::build_filter_bands();
-    let modulator_filter_bands = Self::build_filter_bands();
-
-    let mut filter_bands_for_simd: [[BiquadFilter; BAND_COUNT]; FILTERS_PER_BAND] = uninit();
-
-    for filter_ix in 0..FILTERS_PER_BAND {
-      for band_ix in 0..BAND_COUNT {
-        unsafe {
-          std::ptr::write(
-            &mut filter_bands_for_simd[filter_ix][band_ix],
-            carrier_filter_bands[band_ix].filters[filter_ix],
-          )
-        }
-      }
-    }
-    let carrier_filter_bands_simd = Box::new(BiquadFilterBank2D::new(&filter_bands_for_simd));
-
-    for filter_ix in 0..FILTERS_PER_BAND {
-      for band_ix in 0..BAND_COUNT {
-        unsafe {
-          std::ptr::write(
-            &mut filter_bands_for_simd[filter_ix][band_ix],
-            modulator_filter_bands[band_ix].filters[filter_ix],
-          )
-        }
-      }
-    }
-    let modulator_filter_bands_simd = Box::new(BiquadFilterBank2D::new(&filter_bands_for_simd));
+  pub fn new(config: VocoderConfig) -> Self {
+    let config = config.sanitized();
+    let band_filters = config.build_band_filters();
+    let band_center_freqs_hz = config.band_center_freqs_hz();
+    let build_bands = || -> Vec<VocoderBand> {
+      band_filters
+        .iter()
+        .map(|filters| VocoderBand {
+          filters: filters.clone(),
+        })
+        .collect()
+    };
 
     Self {
+      config,
       carrier_input_buf: [0.; FRAME_SIZE],
-      carrier_filter_bands,
-      carrier_filter_bands_simd,
+      carrier_filter_bands: build_bands(),
+      carrier_filter_bands_simd: Self::build_filter_bank_simd(&band_filters, config.filter_order),
       modulator_input_buf: [0.; FRAME_SIZE],
-      modulator_filter_bands,
+      modulator_filter_bands: build_bands(),
+      modulator_filter_bands_simd: Self::build_filter_bank_simd(&band_filters, config.filter_order),
       modulator_level_detection_ctx: LevelDetectionCtx::new(&band_center_freqs_hz),
-      modulator_filter_bands_simd,
+      sibilance_detector: SibilanceDetector::default(),
+      formant_shifter: FormantShifter::new(&band_center_freqs_hz),
+      modulator_outputs: vec![0.; config.band_count],
+      carrier_outputs: vec![0.; config.band_count],
+      modulator_levels: vec![0.; config.band_count],
       output_buf: [0.; FRAME_SIZE],
       carrier_gain: 1.,
       modulator_gain: 1.,
       output_gain: 1.,
+      sibilance_gain: 0.,
     }
   }
 
-  /// First, we pass the modulator through the modulator bands and detect the level of each band.
-  ///
-  /// Then, we pass the carrier through the carrier bands and multiply the output of each band by
-  /// the level of the corresponding modulator band.
-  ///
-  /// Filters in each band are processed in series.  Bands are processed in parallel.
-  #[cfg(not(target_arch = "wasm32"))]
-  pub fn process(&mut self, carrier_gain: f32, modulator_gain: f32, post_gain: f32) {
-    let carrier_gain = dsp::smooth(&mut self.carrier_gain, carrier_gain, 0.5);
-    let modulator_gain = dsp::smooth(&mut self.modulator_gain, modulator_gain, 0.5);
-    let post_gain = dsp::smooth(&mut self.output_gain, post_gain, 0.5);
-
-    self.output_buf.fill(0.);
-
-    for band_ix in 0..BAND_COUNT {
-      let level_detector = &mut self.modulator_level_detection_ctx.bands[band_ix];
-      let carrier_band = &mut self.carrier_filter_bands[band_ix];
-      let modulator_band = &mut self.modulator_filter_bands[band_ix];
-      for sample_ix in 0..FRAME_SIZE {
-        let modulator_band_output =
-          modulator_band.process(self.modulator_input_buf[sample_ix] * modulator_gain);
-        let level = level_detector.process(modulator_band_output);
-        if cfg!(debug_assertions) && (level.is_infinite() || level.is_nan()) {
-          panic!("{}", level);
-        }
-
-        let carrier_band_output =
-          carrier_band.process(self.carrier_input_buf[sample_ix] * carrier_gain);
-
-        self.output_buf[sample_ix] += carrier_band_output * level;
-      }
+  /// Sets the attack and release times in milliseconds of the envelope follower for one band.
+  /// Values <= 0 use the default for the band's center frequency.
+  pub fn set_band_envelope(&mut self, band_ix: usize, attack_ms: f32, release_ms: f32) {
+    let to_tau = |ms: f32| if ms > 0. { Some(ms / 1000.) } else { None };
+    if let Some(band) = self.modulator_level_detection_ctx.bands.get_mut(band_ix) {
+      band.set_times(to_tau(attack_ms), to_tau(release_ms));
     }
+  }
 
-    for sample_ix in 0..FRAME_SIZE {
-      self.output_buf[sample_ix] *= post_gain;
+  /// Passes one sample of the modulator and carrier through the filters of every band, leaving the
+  /// output of each band in `modulator_outputs` and `carrier_outputs`.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn filter_bands(&mut self, modulator_sample: f32, carrier_sample: f32) {
+    for (band, output) in self
+      .modulator_filter_bands
+      .iter_mut()
+      .zip(&mut self.modulator_outputs)
+    {
+      *output = band.process(modulator_sample);
+    }
+    for (band, output) in self
+      .carrier_filter_bands
+      .iter_mut()
+      .zip(&mut self.carrier_outputs)
+    {
+      *output = band.process(carrier_sample);
     }
   }
 
   #[cfg(target_arch = "wasm32")]
-  pub fn process(&mut self, carrier_gain: f32, modulator_gain: f32, post_gain: f32) {
+  fn filter_bands(&mut self, modulator_sample: f32, carrier_sample: f32) {
+    self.modulator_outputs.fill(modulator_sample);
+    self.carrier_outputs.fill(carrier_sample);
+
+    for depth in 0..self.config.filter_order {
+      self
+        .modulator_filter_bands_simd
+        .apply_simd(&mut self.modulator_outputs, depth);
+
+      self
+        .carrier_filter_bands_simd
+        .apply_simd(&mut self.carrier_outputs, depth);
+    }
+  }
+
+  /// First, we pass the modulator through the modulator bands and detect the level of each band.
+  /// While frozen, the levels are held at their current values instead.
+  ///
+  /// Then, we pass the carrier through the carrier bands and multiply the output of each band by
+  /// the level of the modulator band that the formant shift maps onto it.
+  ///
+  /// Finally, the high-frequency content of unvoiced modulator sounds is mixed in directly so that
+  /// consonants stay intelligible.
+  ///
+  /// Filters in each band are processed in series.  Bands are processed in parallel.
+  pub fn process(
+    &mut self,
+    carrier_gain: f32,
+    modulator_gain: f32,
+    post_gain: f32,
+    sibilance_gain: f32,
+    formant_shift_semitones: f32,
+    freeze: bool,
+  ) {
     let carrier_gain = dsp::smooth(&mut self.carrier_gain, carrier_gain, 0.5);
     let modulator_gain = dsp::smooth(&mut self.modulator_gain, modulator_gain, 0.5);
     let post_gain = dsp::smooth(&mut self.output_gain, post_gain, 0.5);
+    let sibilance_gain = dsp::smooth(&mut self.sibilance_gain, sibilance_gain, 0.5);
+    self.formant_shifter.set_shift(formant_shift_semitones);
 
     self.output_buf.fill(0.);
 
-    let mut modulator_outputs: [f32; BAND_COUNT] = [0.; BAND_COUNT];
-    let mut carrier_outputs: [f32; BAND_COUNT] = [0.; BAND_COUNT];
-
     for sample_ix in 0..FRAME_SIZE {
-      modulator_outputs.fill(self.modulator_input_buf[sample_ix] * modulator_gain);
-      carrier_outputs.fill(self.carrier_input_buf[sample_ix] * carrier_gain);
-
-      for depth in 0..FILTERS_PER_BAND {
-        self
-          .modulator_filter_bands_simd
-          .apply_simd(&mut modulator_outputs, depth);
-
-        self
-          .carrier_filter_bands_simd
-          .apply_simd(&mut carrier_outputs, depth);
-      }
-
-      for band_ix in 0..BAND_COUNT {
-        let sample = modulator_outputs[band_ix];
-        let level = self.modulator_level_detection_ctx.bands[band_ix].process(sample);
+      let modulator_sample = self.modulator_input_buf[sample_ix] * modulator_gain;
+      self.filter_bands(
+        modulator_sample,
+        self.carrier_input_buf[sample_ix] * carrier_gain,
+      );
+
+      for (band_ix, level_detector) in self
+        .modulator_level_detection_ctx
+        .bands
+        .iter_mut()
+        .enumerate()
+      {
+        let level = if freeze {
+          level_detector.level()
+        } else {
+          level_detector.process(self.modulator_outputs[band_ix])
+        };
         if cfg!(debug_assertions) && (level.is_infinite() || level.is_nan()) {
           panic!("{}", level);
         }
+        self.modulator_levels[band_ix] = level;
+      }
 
-        self.output_buf[sample_ix] += carrier_outputs[band_ix] * level;
+      let mut output = 0.;
+      for (band_ix, carrier_output) in self.carrier_outputs.iter().enumerate() {
+        output += carrier_output
+          * self
+            .formant_shifter
+            .get_level(&self.modulator_levels, band_ix);
       }
-    }
 
-    for sample_ix in 0..FRAME_SIZE {
-      self.output_buf[sample_ix] *= post_gain;
+      let (sibilance_sample, unvoiced_amount) = self.sibilance_detector.process(modulator_sample);
+      output += sibilance_sample * unvoiced_amount * sibilance_gain;
+
+      self.output_buf[sample_ix] = output * post_gain;
     }
   }
 }
@@ -293,13 +312,33 @@ fn maybe_init() {
   common::set_raw_panic_hook(log_err);
 }
 
+/// Creates a vocoder with `band_count` bands between `min_freq_hz` and `max_freq_hz`.
+/// `band_spacing` is 0 for log, 1 for Bark, and 2 for mel spacing.  `filter_order` is the order of
+/// the filters at each band edge; higher orders separate bands more sharply but cost more CPU.
+/// All params are clamped to supported values.
 #[no_mangle]
-pub extern "C" fn vocoder_create_ctx() -> *mut VocoderCtx {
+pub extern "C" fn vocoder_create_ctx(
+  band_count: usize,
+  min_freq_hz: f32,
+  max_freq_hz: f32,
+  band_spacing: u32,
+  filter_order: usize,
+) -> *mut VocoderCtx {
   maybe_init();
 
-  Box::into_raw(Box::new(VocoderCtx::new()))
+  let config = VocoderConfig {
+    band_count,
+    min_freq_hz,
+    max_freq_hz,
+    spacing: BandSpacing::from_u32(band_spacing),
+    filter_order,
+  };
+  Box::into_raw(Box::new(VocoderCtx::new(config)))
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn vocoder_free_ctx(ctx: *mut VocoderCtx) { drop(Box::from_raw(ctx)) }
+
 #[no_mangle]
 pub extern "C" fn vocoder_get_carrier_input_buf_ptr(ctx: *mut VocoderCtx) -> *mut f32 {
   unsafe { (*ctx).carrier_input_buf.as_mut_ptr() }
@@ -315,15 +354,40 @@ pub extern "C" fn vocoder_get_output_buf_ptr(ctx: *mut VocoderCtx) -> *mut f32 {
   unsafe { (*ctx).output_buf.as_mut_ptr() }
 }
 
+/// `sibilance_gain` scales the high-frequency content of unvoiced modulator sounds that's passed
+/// through to the output.  While `freeze` is set, the band levels are held at their current values.
 #[no_mangle]
 pub extern "C" fn vocoder_process(
   ctx: *mut VocoderCtx,
   carrier_gain: f32,
   modulator_gain: f32,
   output_gain: f32,
+  sibilance_gain: f32,
+  formant_shift_semitones: f32,
+  freeze: bool,
 ) {
   let ctx = unsafe { &mut *ctx };
-  ctx.process(carrier_gain, modulator_gain, output_gain);
+  ctx.process(
+    carrier_gain,
+    modulator_gain,
+    output_gain,
+    sibilance_gain,
+    formant_shift_semitones,
+    freeze,
+  );
+}
+
+/// Sets the attack and release times in milliseconds of the envelope follower for the band at
+/// `band_ix`.  Values <= 0 use the default for the band's center frequency.
+#[no_mangle]
+pub unsafe extern "C" fn vocoder_set_band_envelope(
+  ctx: *mut VocoderCtx,
+  band_ix: usize,
+  attack_ms: f32,
+  release_ms: f32,
+) {
+  let ctx = &mut *ctx;
+  ctx.set_band_envelope(band_ix, attack_ms, release_ms);
 }
 
 #[test]
@@ -361,3 +425,59 @@ fn envelope_follower_ripple() {
   }
   assert!((max - min) / max < 0.01, "ripple {:.4}", (max - min) / max);
 }
+
+#[test]
+fn filter_bank_matches_per_band_filters() {
+  let mut ctx = VocoderCtx::new(VocoderConfig {
+    band_count: 13,
+    filter_order: 8,
+    ..Default::default()
+  });
+  let mut band_filters = ctx.config.build_band_filters();
+  let mut bank_outputs = vec![0.; ctx.config.band_count];
+  for i in 0..2048 {
+    let input = ((i * 7919) % 200) as f32 / 100. - 1.;
+    bank_outputs.fill(input);
+    for depth in 0..ctx.config.filter_order {
+      ctx
+        .carrier_filter_bands_simd
+        .apply_simd(&mut bank_outputs, depth);
+    }
+
+    for (filters, &bank_output) in band_filters.iter_mut().zip(&bank_outputs) {
+      let band_output = filters
+        .iter_mut()
+        .fold(input, |sample, filter| filter.apply(sample));
+      assert!((band_output - bank_output).abs() < 1e-5);
+    }
+  }
+}
+
+#[test]
+fn freeze_holds_band_levels() {
+  let mut ctx = VocoderCtx::new(VocoderConfig::default());
+  let sine = |i: usize| (i as f32 / SAMPLE_RATE as f32 * 440. * std::f32::consts::TAU).sin();
+  let mut sample_ix = 0;
+  let mut run = |ctx: &mut VocoderCtx, modulator_on: bool, freeze: bool| {
+    for _ in 0..100 {
+      for i in 0..FRAME_SIZE {
+        ctx.carrier_input_buf[i] = sine(sample_ix);
+        ctx.modulator_input_buf[i] = if modulator_on { sine(sample_ix) } else { 0. };
+        sample_ix += 1;
+      }
+      ctx.process(1., 1., 1., 0., 0., freeze);
+    }
+    ctx.output_buf.iter().map(|s| s.abs()).fold(0., f32::max)
+  };
+
+  let playing_level = run(&mut ctx, true, false);
+  assert!(playing_level > 0.1, "{playing_level}");
+  // The modulator stops but the frozen levels keep the carrier sounding
+  let frozen_level = run(&mut ctx, false, true);
+  assert!(
+    (frozen_level - playing_level).abs() / playing_level < 0.05,
+    "{frozen_level} vs {playing_level}"
+  );
+  let released_level = run(&mut ctx, false, false);
+  assert!(released_level < 1e-3, "{released_level}");
+}
@@ -0,0 +1,115 @@
+//! Passes the high-frequency content of unvoiced modulator sounds (sibilants like "s", "sh", and
+//! "t") straight through to the output.
+//!
+//! These sounds are mostly noise in the upper vocoder bands, which usually have little carrier
+//! energy to shape, so they get lost when the modulator is only used to shape the carrier.  The
+//! modulator is high-passed at `SIBILANCE_CUTOFF_HZ`, and sounds are classified as unvoiced when
+//! most of its energy is in the high-passed signal.
+
+use dsp::filters::biquad::{compute_higher_order_biquad_q_factors, BiquadFilter, FilterMode};
+
+use crate::one_pole_coeff;
+
+const SIBILANCE_CUTOFF_HZ: f32 = 4_000.;
+const SIBILANCE_FILTER_ORDER: usize = 4;
+/// Time constant for tracking the energy of the modulator and its high-passed signal
+const ENERGY_TAU_S: f32 = 0.01;
+/// Time constant for smoothing the voiced/unvoiced decision to avoid zipper noise
+const UNVOICED_SMOOTH_TAU_S: f32 = 0.005;
+/// Below this ratio of high-frequency to total energy, sounds are considered fully voiced
+const UNVOICED_RATIO_MIN: f32 = 0.25;
+/// Above this ratio of high-frequency to total energy, sounds are considered fully unvoiced
+const UNVOICED_RATIO_MAX: f32 = 0.6;
+/// Modulator energy below this is treated as silence and never passed through
+const MIN_ENERGY: f32 = 1e-7;
+
+pub struct SibilanceDetector {
+  highpass_filters: Vec<BiquadFilter>,
+  energy_coeff: f32,
+  unvoiced_smooth_coeff: f32,
+  hf_energy: f32,
+  total_energy: f32,
+  unvoiced_amount: f32,
+}
+
+impl Default for SibilanceDetector {
+  fn default() -> Self {
+    SibilanceDetector {
+      highpass_filters: compute_higher_order_biquad_q_factors(SIBILANCE_FILTER_ORDER)
+        .into_iter()
+        .map(|q| BiquadFilter::new(FilterMode::Highpass, q, SIBILANCE_CUTOFF_HZ, 0.))
+        .collect(),
+      energy_coeff: one_pole_coeff(ENERGY_TAU_S),
+      unvoiced_smooth_coeff: one_pole_coeff(UNVOICED_SMOOTH_TAU_S),
+      hf_energy: 0.,
+      total_energy: 0.,
+      unvoiced_amount: 0.,
+    }
+  }
+}
+
+impl SibilanceDetector {
+  /// Returns the high-passed modulator sample and how unvoiced the modulator currently is from 0
+  /// to 1.
+  #[inline]
+  pub fn process(&mut self, modulator_sample: f32) -> (f32, f32) {
+    let mut hf_sample = modulator_sample;
+    for filter in &mut self.highpass_filters {
+      hf_sample = filter.apply(hf_sample);
+    }
+
+    let c = self.energy_coeff;
+    self.hf_energy = c * self.hf_energy + (1. - c) * hf_sample * hf_sample;
+    self.total_energy = c * self.total_energy + (1. - c) * modulator_sample * modulator_sample;
+
+    let target_unvoiced_amount = if self.total_energy < MIN_ENERGY {
+      0.
+    } else {
+      let ratio = self.hf_energy / self.total_energy;
+      ((ratio - UNVOICED_RATIO_MIN) / (UNVOICED_RATIO_MAX - UNVOICED_RATIO_MIN)).clamp(0., 1.)
+    };
+    let c = self.unvoiced_smooth_coeff;
+    self.unvoiced_amount = c * self.unvoiced_amount + (1. - c) * target_unvoiced_amount;
+
+    (hf_sample, self.unvoiced_amount)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::SAMPLE_RATE;
+
+  fn final_unvoiced_amount(samples: impl Iterator<Item = f32>) -> f32 {
+    let mut detector = SibilanceDetector::default();
+    samples.fold(0., |_, sample| detector.process(sample).1)
+  }
+
+  #[test]
+  fn classifies_voiced_and_unvoiced() {
+    let voiced = (0..SAMPLE_RATE / 2).map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      (t * 220. * std::f32::consts::TAU).sin() * 0.5
+        + (t * 440. * std::f32::consts::TAU).sin() * 0.25
+    });
+    let amount = final_unvoiced_amount(voiced);
+    assert!(amount < 0.05, "{amount}");
+
+    // Crude high-passed noise: differentiated pseudo-random samples
+    let mut state = 0x1234_5678u32;
+    let mut prev = 0.;
+    let unvoiced = (0..SAMPLE_RATE / 2).map(|_| {
+      state ^= state << 13;
+      state ^= state >> 17;
+      state ^= state << 5;
+      let noise = state as f32 / u32::MAX as f32 - 0.5;
+      let out = noise - prev;
+      prev = noise;
+      out
+    });
+    let amount = final_unvoiced_amount(unvoiced);
+    assert!(amount > 0.9, "{amount}");
+
+    assert_eq!(final_unvoiced_amount(std::iter::repeat_n(0., 4096)), 0.);
+  }
+}
@@ -9,21 +9,28 @@ static mut ERROR_MESSAGE: String = String::new();
 #[wasm_bindgen]
 pub fn get_error_message() -> String { ref_static_mut!(ERROR_MESSAGE).clone() }
 
-#[wasm_bindgen]
-pub fn decode_wav(data: Vec<u8>) -> Vec<f32> {
-  common::maybe_init(None);
-  wbg_logging::maybe_init();
+/// Decoded contents of a WAV file.  Samples are interleaved if there are multiple channels.
+pub struct DecodedWav {
+  pub sample_rate: u32,
+  pub channel_count: u16,
+  pub samples: Vec<f32>,
+}
 
-  let mut reader = match hound::WavReader::new(data.as_slice()) {
-    Ok(r) => r,
-    Err(e) => {
-      error!("Error parsing wav file: {e}");
-      unsafe {
-        ERROR_MESSAGE = format!("Error parsing wav file: {e}");
-      }
-      return Vec::new();
-    },
-  };
+impl DecodedWav {
+  /// Mixes all channels down to one by averaging them
+  pub fn to_mono(&self) -> Vec<f32> {
+    let channel_count = self.channel_count.max(1) as usize;
+    self
+      .samples
+      .chunks_exact(channel_count)
+      .map(|frame| frame.iter().sum::<f32>() / channel_count as f32)
+      .collect()
+  }
+}
+
+pub fn try_decode_wav(data: &[u8]) -> Result<DecodedWav, String> {
+  let mut reader =
+    hound::WavReader::new(data).map_err(|e| format!("Error parsing wav file: {e}"))?;
 
   let spec = reader.spec();
   info!("{:?}", spec);
@@ -39,14 +46,126 @@ pub fn decode_wav(data: Vec<u8>) -> Vec<f32> {
       .into_samples::<f32>()
       .collect::<Result<Vec<f32>, _>>(),
   };
-  match res {
-    Ok(samples) => samples,
+  let samples = res.map_err(|err| format!("Error decoding wav file: {err:?}"))?;
+
+  Ok(DecodedWav {
+    sample_rate: spec.sample_rate,
+    channel_count: spec.channels,
+    samples,
+  })
+}
+
+#[wasm_bindgen]
+pub fn decode_wav(data: Vec<u8>) -> Vec<f32> {
+  common::maybe_init(None);
+  wbg_logging::maybe_init();
+
+  match try_decode_wav(data.as_slice()) {
+    Ok(decoded) => decoded.samples,
     Err(err) => {
-      error!("Error decoding wav file: {err:?}");
+      error!("{err}");
       unsafe {
-        ERROR_MESSAGE = format!("Error decoding wav file: {err:?}");
+        ERROR_MESSAGE = err;
       }
       Vec::new()
     },
   }
 }
+
+/// Suggests loop points for mono sample data.  Returns `[start_sample_ix, end_sample_ix, score]`
+/// triples sorted from best to worst, with scores from 0 to 1.
+#[wasm_bindgen]
+pub fn suggest_loop_points(
+  samples: &[f32],
+  sample_rate: f32,
+  min_loop_len_secs: f32,
+  max_suggestions: usize,
+  snap_to_pitch: bool,
+) -> Vec<f64> {
+  let params = dsp::loop_detection::LoopDetectionParams {
+    sample_rate,
+    min_loop_len_samples: (min_loop_len_secs.max(0.) * sample_rate) as usize,
+    max_suggestions,
+    snap_to_pitch,
+    ..Default::default()
+  };
+  dsp::loop_detection::suggest_loop_points(samples, &params)
+    .into_iter()
+    .flat_map(|suggestion| {
+      [
+        suggestion.start_sample_ix as f64,
+        suggestion.end_sample_ix as f64,
+        suggestion.score as f64,
+      ]
+    })
+    .collect()
+}
+
+/// Renders mono sample data so that it is `out_len` samples long and shifted in pitch by
+/// `pitch_shift_cents`.  Used to render time-stretched sample editor clips off the audio thread.
+#[wasm_bindgen]
+pub fn stretch_and_shift(samples: &[f32], out_len: usize, pitch_shift_cents: f32) -> Vec<f32> {
+  dsp::time_stretch::stretch_and_shift(samples, out_len, pitch_shift_cents)
+}
+
+/// Splits mono sample data into slices at detected onsets.  `sensitivity` is from 0 to 1; higher
+/// values produce more slices.  Returns the tempo of the sample estimated from the onsets, or 0 if
+/// it couldn't be estimated, followed by `[start_sample_ix, end_sample_ix]` pairs for each slice.
+#[wasm_bindgen]
+pub fn detect_slices(
+  samples: &[f32],
+  sample_rate: f32,
+  sensitivity: f32,
+  min_slice_len_samples: usize,
+) -> Vec<f64> {
+  let params = dsp::onset_detection::OnsetDetectionParams {
+    sample_rate,
+    sensitivity,
+    min_onset_interval_samples: min_slice_len_samples,
+    ..Default::default()
+  };
+  let onsets = dsp::onset_detection::detect_onsets(samples, &params);
+  let slices =
+    dsp::onset_detection::onsets_to_slices(&onsets, samples.len(), min_slice_len_samples);
+  let estimated_bpm =
+    dsp::onset_detection::estimate_tempo(&onsets, samples.len(), sample_rate).unwrap_or(0.);
+
+  std::iter::once(estimated_bpm as f64)
+    .chain(
+      slices
+        .into_iter()
+        .flat_map(|(start, end)| [start as f64, end as f64]),
+    )
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detect_slices_at_sample_rate() {
+    // Decaying tone bursts at 0.5s intervals, the first one a bit after the start of the sample
+    let sample_rate = 48_000;
+    let hit_positions = [4800, 28_800, 52_800, 76_800];
+    let mut samples = vec![0.; 96_000];
+    for &pos in &hit_positions {
+      for (i, sample) in samples[pos..].iter_mut().take(22_000).enumerate() {
+        *sample += (i as f32 * 0.07).sin() * (-(i as f32) / 1500.).exp();
+      }
+    }
+
+    let res = detect_slices(&samples, sample_rate as f32, 0.5, sample_rate / 20);
+    let (estimated_bpm, bounds) = (res[0], &res[1..]);
+    // A slice for the silence before the first hit plus one for each hit
+    assert_eq!(bounds.len(), (hit_positions.len() + 1) * 2);
+    for (i, &hit_pos) in hit_positions.iter().enumerate() {
+      let (start, end) = (bounds[(i + 1) * 2], bounds[(i + 1) * 2 + 1]);
+      assert!((start - hit_pos as f64).abs() < 100.);
+      let expected_end = hit_positions.get(i + 1).copied().unwrap_or(samples.len());
+      assert!((end - expected_end as f64).abs() < 100.);
+    }
+    // 0.5s between hits is 120 BPM
+    assert!((estimated_bpm - 120.).abs() < 1., "{estimated_bpm}");
+  }
+}
@@ -4,7 +4,7 @@ use std::{mem::MaybeUninit, rc::Rc};
 
 use adsr::{
   managed_adsr::ManagedAdsr, Adsr, AdsrLengthMode, AdsrStep, EarlyReleaseConfig,
-  EarlyReleaseStrategy, GateStatus, RampFn, RENDERED_BUFFER_SIZE,
+  EarlyReleaseStrategy, GateStatus, NoteScalingConfig, RampFn, RENDERED_BUFFER_SIZE,
 };
 use dsp::{
   midi_number_to_frequency, oscillator::PhasedOscillator, uninit, FRAME_SIZE, SAMPLE_RATE,
@@ -992,8 +992,8 @@ fn build_default_gain_adsr_steps() -> Vec<AdsrStep> {
 impl FMSynthVoice {
   #[cold]
   fn new(
-    shared_gain_adsr_rendered_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]>,
-    shared_filter_adsr_rendered_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]>,
+    shared_gain_adsr_rendered_buffer: Rc<Vec<f32>>,
+    shared_filter_adsr_rendered_buffer: Rc<Vec<f32>>,
   ) -> Self {
     FMSynthVoice {
       output: 0.,
@@ -1638,19 +1638,9 @@ pub unsafe extern "C" fn init_fm_synth_ctx() -> *mut FMSynthContext {
         offset_hz: 0.,
       });
   }
-  // let shared_gain_adsr_rendered_buffer: Box<[f32; RENDERED_BUFFER_SIZE]> =
-  //   Box::new([0.242424; RENDERED_BUFFER_SIZE]);
-  let shared_gain_adsr_rendered_buffer: Box<[f32; RENDERED_BUFFER_SIZE]> =
-    Box::new_uninit().assume_init();
-  let shared_gain_adsr_rendered_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]> =
-    shared_gain_adsr_rendered_buffer.into();
-
-  // let shared_filter_adsr_rendered_buffer: Box<[f32; RENDERED_BUFFER_SIZE]> =
-  //   Box::new([0.424242; RENDERED_BUFFER_SIZE]);
-  let shared_filter_adsr_rendered_buffer: Box<[f32; RENDERED_BUFFER_SIZE]> =
-    Box::new_uninit().assume_init();
-  let shared_filter_adsr_rendered_buffer: Rc<[f32; RENDERED_BUFFER_SIZE]> =
-    shared_filter_adsr_rendered_buffer.into();
+  let shared_gain_adsr_rendered_buffer: Rc<Vec<f32>> = Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
+  let shared_filter_adsr_rendered_buffer: Rc<Vec<f32>> =
+    Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
 
   for i in 0..VOICE_COUNT {
     std::ptr::write(
@@ -1662,7 +1652,7 @@ pub unsafe extern "C" fn init_fm_synth_ctx() -> *mut FMSynthContext {
     );
   }
   // Render the default gain and filter envelope for all voices
-  let shared_buffer = Rc::new([0.0f32; RENDERED_BUFFER_SIZE]);
+  let shared_buffer = Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
   (*ctx).voices[0].gain_envelope_generator.adsr.rendered = shared_buffer;
   (*ctx).voices[0].gain_envelope_generator.render();
   for voice_ix in 1..VOICE_COUNT {
@@ -1671,7 +1661,7 @@ pub unsafe extern "C" fn init_fm_synth_ctx() -> *mut FMSynthContext {
       .adsr
       .rendered = Rc::clone(&(*ctx).voices[0].gain_envelope_generator.adsr.rendered);
   }
-  let shared_buffer = Rc::new([0.0f32; RENDERED_BUFFER_SIZE]);
+  let shared_buffer = Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
   (*ctx).voices[0].filter_envelope_generator.adsr.rendered = shared_buffer;
   (*ctx).voices[0].filter_envelope_generator.render();
   for voice_ix in 1..VOICE_COUNT {
@@ -2205,16 +2195,23 @@ unsafe fn gate_voice_inner(
   (*ctx).most_recent_gated_voice_ix = voice_ix;
 
   let voice = &mut (*ctx).voices[voice_ix];
+  let (velocity_opt, key_opt) = (Some(velocity), Some(midi_number.min(127) as u8));
   for (i, adsr) in voice.adsrs.iter_mut().enumerate() {
     adsr.store_phase_to = Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(i));
-    adsr.gate(0.);
+    adsr.gate(0., velocity_opt, key_opt);
   }
 
   voice.last_gated_midi_number = midi_number;
-  voice.gain_envelope_generator.adsr.gate(0.);
+  voice
+    .gain_envelope_generator
+    .adsr
+    .gate(0., velocity_opt, key_opt);
   voice.gain_envelope_generator.adsr.store_phase_to =
     Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(GAIN_ENVELOPE_PHASE_BUF_INDEX));
-  voice.filter_envelope_generator.adsr.gate(0.);
+  voice
+    .filter_envelope_generator
+    .adsr
+    .gate(0., velocity_opt, key_opt);
   voice.filter_envelope_generator.adsr.store_phase_to =
     Some(((*ctx).adsr_phase_buf.as_mut_ptr() as *mut f32).add(FILTER_ENVELOPE_PHASE_BUF_INDEX));
   voice.velocity_gain_multiplier = midi_velocity_to_gain(velocity);
@@ -2324,7 +2321,7 @@ pub unsafe extern "C" fn set_adsr(
     0.,   // This will be overridden when ADSRs are rendered
     None, // Maybe we want to set this later?
     release_start_phase,
-    Rc::new([0.0f32; RENDERED_BUFFER_SIZE]), // temp
+    Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]), // temp
     EarlyReleaseConfig::default(),
     log_scale,
   );
@@ -2370,6 +2367,7 @@ pub unsafe extern "C" fn set_adsr(
       let store_phase_to = old_adsr.store_phase_to;
 
       new_adsr.early_release_config = old_adsr.early_release_config.clone();
+      new_adsr.note_scaling = old_adsr.note_scaling;
       new_adsr.phase = match gate_status {
         GateStatus::GatedFrozen => release_start_phase,
         GateStatus::Done => 1.,
@@ -2407,7 +2405,7 @@ pub unsafe extern "C" fn set_adsr(
   }
 
   // Render the ADSR's shared buffer
-  let shared_buffer = Rc::new([0.0f32; RENDERED_BUFFER_SIZE]);
+  let shared_buffer = Rc::new(vec![0.0f32; RENDERED_BUFFER_SIZE]);
   if adsr_ix == -1 {
     ctx.voices[0].gain_envelope_generator.adsr.rendered = shared_buffer;
     ctx.voices[0].gain_envelope_generator.render();
@@ -2432,6 +2430,37 @@ pub unsafe extern "C" fn set_adsr(
   }
 }
 
+/// Sets how note velocity and key affect the level and timing of an ADSR.  `adsr_ix` is -1 for the
+/// gain envelope, -2 for the filter envelope, or the index of a modulation ADSR.
+#[no_mangle]
+pub unsafe extern "C" fn fm_synth_set_adsr_note_scaling(
+  ctx: *mut FMSynthContext,
+  adsr_ix: isize,
+  velocity_to_level: f32,
+  velocity_to_attack_time: f32,
+  key_tracking: f32,
+  key_tracking_center: u8,
+) {
+  let note_scaling = NoteScalingConfig {
+    velocity_to_level,
+    velocity_to_attack_time,
+    key_tracking,
+    key_tracking_center,
+  };
+
+  for voice in &mut *(*ctx).voices {
+    let adsr = match adsr_ix {
+      -1 => &mut voice.gain_envelope_generator.adsr,
+      -2 => &mut voice.filter_envelope_generator.adsr,
+      _ => match voice.adsrs.get_mut(adsr_ix as usize) {
+        Some(adsr) => adsr,
+        None => return,
+      },
+    };
+    adsr.note_scaling = note_scaling;
+  }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn set_adsr_length(
   ctx: *mut FMSynthContext,
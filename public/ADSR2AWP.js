@@ -69,6 +69,64 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
           this.wasmInstance.exports.adsr_set_loop_point(this.ctxPtr, evt.data.loopPoint ?? -1);
           break;
         }
+        case 'setLoops': {
+          if (!this.wasmInstance) {
+            console.error('Tried to set ADSR2 loops before initialization');
+            break;
+          }
+          // Encoded as `[id, start, end, mode, repeatCount]` tuples; negative `repeatCount` loops
+          // until released
+          const encodedLoops = evt.data.encodedLoops;
+          const loopBufPtr = this.wasmInstance.exports.get_encoded_adsr_loop_buf_ptr(
+            encodedLoops.length / 5
+          );
+          this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+          this.wasmMemoryBuffer.set(encodedLoops, loopBufPtr / BYTES_PER_F32);
+          this.wasmInstance.exports.adsr_set_loops(this.ctxPtr);
+          break;
+        }
+        case 'setReleaseLoop': {
+          if (!this.wasmInstance) {
+            console.error('Tried to set ADSR2 release loop before initialization');
+            break;
+          }
+          const releaseLoop = evt.data.releaseLoop;
+          this.wasmInstance.exports.adsr_set_release_loop(
+            this.ctxPtr,
+            releaseLoop?.id ?? 0,
+            releaseLoop?.start ?? -1,
+            releaseLoop?.end ?? -1,
+            releaseLoop?.mode ?? 0,
+            releaseLoop?.repeatCount ?? -1
+          );
+          break;
+        }
+        case 'setStepRetrigger': {
+          if (!this.wasmInstance) {
+            console.error('Tried to set ADSR2 step retrigger before initialization');
+            break;
+          }
+          this.wasmInstance.exports.adsr_set_step_retrigger(
+            this.ctxPtr,
+            evt.data.stepIx,
+            evt.data.continueFromCurrent
+          );
+          break;
+        }
+        case 'setNoteScaling': {
+          if (!this.wasmInstance) {
+            console.error('Tried to set ADSR2 note scaling before initialization');
+            break;
+          }
+          this.wasmInstance.exports.adsr_set_note_scaling(
+            this.ctxPtr,
+            evt.data.velocityToLevel ?? 0,
+            evt.data.velocityToAttackTime ?? 0,
+            evt.data.keyTracking ?? 0,
+            evt.data.keyTrackingCenter ?? 60
+          );
+          break;
+        }
         case 'setReleaseStartPhase': {
           if (!this.wasmInstance) {
             console.error('Tried to set ADSR2 release start phase before initialization');
@@ -93,7 +151,17 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
             console.warn('Tried to gate before wasm inst initialize in ADSR2 AWP');
             break;
           }
-          this.wasmInstance.exports.gate_adsr(this.ctxPtr, evt.data.index, getCurBeat());
+          if (typeof evt.data.velocity === 'number' && typeof evt.data.midiNumber === 'number') {
+            this.wasmInstance.exports.gate_adsr_with_note(
+              this.ctxPtr,
+              evt.data.index,
+              getCurBeat(),
+              evt.data.velocity,
+              evt.data.midiNumber
+            );
+          } else {
+            this.wasmInstance.exports.gate_adsr(this.ctxPtr, evt.data.index, getCurBeat());
+          }
           break;
         }
         case 'ungate': {
@@ -208,12 +276,13 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
       .fill(null)
       .map((_, i) => this.wasmInstance.exports.adsr_get_output_buf_ptr(this.ctxPtr, i));
 
-    // Create `SharedArrayBuffer` to synchronize ADSR phase between the AWP and the UI
+    // Create `SharedArrayBuffer` to synchronize ADSR phase between the AWP and the UI.  Index 1
+    // holds the id of the loop being played or -1 if none.
     if (typeof SharedArrayBuffer !== 'undefined') {
-      this.audioThreadDataBufferInner = new SharedArrayBuffer(1 * BYTES_PER_F32);
+      this.audioThreadDataBufferInner = new SharedArrayBuffer(2 * BYTES_PER_F32);
       this.audioThreadDataBuffer = new Float32Array(this.audioThreadDataBufferInner);
     } else {
-      this.audioThreadDataBuffer = new Float32Array(1);
+      this.audioThreadDataBuffer = new Float32Array(2);
     }
     this.port.postMessage({
       type: 'phaseDataBuffer',
@@ -246,6 +315,7 @@ class MultiADSR2AWP extends AudioWorkletProcessor {
     // Record the current phase of the most recently gated ADSR which will be displayed
     // in the UI as an indicator on the ADSR UI
     this.audioThreadDataBuffer[0] = curPhase;
+    this.audioThreadDataBuffer[1] = this.wasmInstance.exports.adsr_get_active_loop_id(this.ctxPtr);
     for (let i = 0; i < this.adsrInstanceCount; i++) {
       const output = outputs[i]?.[0];
       if (!output) {
@@ -16,7 +16,7 @@ class EqualizerAWP extends AudioWorkletProcessor {
   }
 
   constructor(_options) {
-    super({ numberOfInputs: 1, numberOfOutputs: 1, outputChannelCount: [1] });
+    super({ numberOfInputs: 1, numberOfOutputs: 1, outputChannelCount: [2] });
 
     this.isShutdown = false;
     this.ctxPtr = 0;
@@ -86,6 +86,14 @@ class EqualizerAWP extends AudioWorkletProcessor {
           this.isBypassed = evt.data.isBypassed;
           break;
         }
+        case 'setLinearPhase': {
+          this.wasmInstance.exports.equalizer_set_linear_phase(this.ctxPtr, evt.data.linearPhase);
+          this.port.postMessage({
+            type: 'latency',
+            latencySamples: this.wasmInstance.exports.equalizer_get_latency_samples(this.ctxPtr),
+          });
+          break;
+        }
         default:
           console.error('Unknown message type in EqualizerAWP', evt.data.type);
       }
@@ -94,7 +102,17 @@ class EqualizerAWP extends AudioWorkletProcessor {
 
   commitBand(
     bandIx,
-    { filterType, frequency, q, gain, freqAutomationBufIx, qAutomationBufIx, gainAutomationBufIx }
+    {
+      filterType,
+      frequency,
+      q,
+      gain,
+      dynamics,
+      channelModeIx,
+      freqAutomationBufIx,
+      qAutomationBufIx,
+      gainAutomationBufIx,
+    }
   ) {
     this.wasmInstance.exports.equalizer_set_band(
       this.ctxPtr,
@@ -111,6 +129,22 @@ class EqualizerAWP extends AudioWorkletProcessor {
         ? gainAutomationBufIx
         : 99999
     );
+    this.wasmInstance.exports.equalizer_set_band_dynamics(
+      this.ctxPtr,
+      bandIx,
+      !!dynamics,
+      dynamics?.thresholdDb ?? 0,
+      dynamics?.ratio ?? 1,
+      dynamics?.attackMs ?? 0,
+      dynamics?.releaseMs ?? 0,
+      dynamics?.sidechainFrequency ?? 0,
+      dynamics?.sidechainQ ?? 0
+    );
+    this.wasmInstance.exports.equalizer_set_band_channel_mode(
+      this.ctxPtr,
+      bandIx,
+      channelModeIx ?? 0
+    );
   }
 
   logWasmErr = (ptr, len) => {
@@ -143,12 +177,15 @@ class EqualizerAWP extends AudioWorkletProcessor {
    * @returns {boolean}
    */
   process(inputs, outputs, params) {
-    const input = inputs[0]?.[0];
-    if (!input) {
+    const inputL = inputs[0]?.[0];
+    if (!inputL) {
       return true;
     }
-    const output = outputs[0]?.[0];
-    if (!output) {
+    // mono inputs are processed as stereo with the same signal in both channels
+    const inputR = inputs[0][1] ?? inputL;
+    const outputL = outputs[0]?.[0];
+    const outputR = outputs[0]?.[1];
+    if (!outputL || !outputR) {
       return true;
     }
     if (this.isShutdown) {
@@ -160,8 +197,11 @@ class EqualizerAWP extends AudioWorkletProcessor {
     }
 
     const wasmMemory = this.getWasmMemoryBuffer();
-    const inputPtr = this.wasmInstance.exports.equalizer_get_io_buf_ptr(this.ctxPtr);
-    wasmMemory.set(input, inputPtr / Float32Array.BYTES_PER_ELEMENT);
+    const ioBufPtr = this.wasmInstance.exports.equalizer_get_io_buf_ptr(this.ctxPtr);
+    const ioBufIxL = ioBufPtr / Float32Array.BYTES_PER_ELEMENT;
+    const ioBufIxR = ioBufIxL + FRAME_SIZE;
+    wasmMemory.set(inputL, ioBufIxL);
+    wasmMemory.set(inputR, ioBufIxR);
 
     for (let i = 0; i < AutomationParamKeys.length; i++) {
       const paramKey = AutomationParamKeys[i];
@@ -177,16 +217,13 @@ class EqualizerAWP extends AudioWorkletProcessor {
     }
 
     if (this.isBypassed) {
-      output.set(input);
+      outputL.set(inputL);
+      outputR.set(inputR);
     } else {
       this.wasmInstance.exports.equalizer_process(this.ctxPtr);
 
-      output.set(
-        wasmMemory.subarray(
-          inputPtr / Float32Array.BYTES_PER_ELEMENT,
-          inputPtr / Float32Array.BYTES_PER_ELEMENT + FRAME_SIZE
-        )
-      );
+      outputL.set(wasmMemory.subarray(ioBufIxL, ioBufIxL + FRAME_SIZE));
+      outputR.set(wasmMemory.subarray(ioBufIxR, ioBufIxR + FRAME_SIZE));
     }
 
     return true;
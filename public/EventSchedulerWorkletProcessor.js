@@ -52,6 +52,7 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
               anchorBeat,
               currentFrame
             );
+            this.setWasmTempoMap(anchorBeat, currentTime);
           } else {
             globalThis.transport.tempoMap = TempoMap.constant(sampleRate, this.baseBpm);
           }
@@ -72,6 +73,7 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
             this.startBeat,
             this.startFrame
           );
+          this.setWasmTempoMap(this.startBeat, currentTime);
           globalThis.curBeat = this.startBeat;
           globalThis.globalBeatCounterStarted = true;
           globalThis.playbackSeq += 1;
@@ -156,7 +158,15 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
     this.wasmInstance = new WebAssembly.Instance(compiledModule, {
       env: {
         debug1: v => console.log(v),
-        run_callback: val => this.port.postMessage(val),
+        // `frameOffset` is the sample offset into the current frame at which the event landed.
+        // It's forwarded along with the frame's start time so consumers can schedule precisely.
+        run_callback: (cbId, frameOffset) =>
+          this.port.postMessage({
+            type: 'callback',
+            cbId,
+            frameOffset,
+            frameStartTime: currentTime,
+          }),
         // MIDI no longer routes through the Rust heap, but the import must still be satisfied.
         run_midi_callback: () => {
           console.error('run_midi_callback invoked but MIDI no longer routes through the scheduler');
@@ -173,6 +183,23 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
     this.pendingEvents = null;
   }
 
+  /**
+   * Mirrors the active tempo changes into the Rust scheduler's tempo map so that beat-scheduled
+   * callback events fire at the exact sample they land on, including during tempo ramps.  Changes
+   * with `ramp: true` ramp linearly from the previous change's tempo.
+   */
+  setWasmTempoMap(anchorBeat, anchorTime) {
+    const changes = this.tempoChanges;
+    const bufPtr = this.wasmInstance.exports.alloc_tempo_changes_buffer(changes.length);
+    const buf = new Float64Array(this.wasmInstance.exports.memory.buffer, bufPtr, changes.length * 3);
+    changes.forEach((change, i) => {
+      buf[i * 3] = change.beat;
+      buf[i * 3 + 1] = change.bpm;
+      buf[i * 3 + 2] = change.ramp ? 1 : 0;
+    });
+    this.wasmInstance.exports.set_tempo_map(anchorBeat, anchorTime);
+  }
+
   /**
    * Clamps late-arriving events up to the current beat while running so they fire at the next
    * window's offset 0 instead of falling below consumer poll cursors and being silently dropped.
@@ -235,7 +262,9 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
 
   process(_inputs, _outputs, _params) {
     if (this.isStarted) {
-      const curBeat = globalThis.transport.beatAt(currentFrame);
+      // Beat-scheduled callbacks fire against the Rust tempo map, so the beat it reports is the
+      // authoritative one.  The JS transport integrates ramps identically and drives MIDI polling.
+      const curBeat = this.wasmInstance.exports.run_frame(currentTime, sampleRate, FRAME_SIZE);
       const bpm = globalThis.transport.bpmAt(currentFrame);
       globalThis.curBeat = curBeat;
       globalThis.globalTempoBPM = bpm;
@@ -251,7 +280,7 @@ class EventSchedulerWorkletProcessor extends AudioWorkletProcessor {
       }
     }
 
-    if (this.wasmInstance) {
+    if (this.wasmInstance && !this.isStarted) {
       this.wasmInstance.exports.run(currentTime, globalThis.curBeat);
     }
 
@@ -209,7 +209,8 @@ class FMSynthAWP extends AudioWorkletProcessor {
             return;
           }
 
-          const { adsrIx, steps, lenSamples, releasePoint, loopPoint, logScale } = evt.data;
+          const { adsrIx, steps, lenSamples, releasePoint, loopPoint, logScale, noteScaling } =
+            evt.data;
           steps.forEach(({ x, y, ramper, params }, stepIx) =>
             this.wasmInstance.exports.set_adsr_step_buffer(stepIx, x, y, ramper, ...params)
           );
@@ -226,6 +227,7 @@ class FMSynthAWP extends AudioWorkletProcessor {
             loopPoint ?? -1.0,
             logScale ?? false
           );
+          this.setAdsrNoteScaling(adsrIx, noteScaling);
           break;
         }
         case 'setAdsrLength': {
@@ -538,6 +540,20 @@ class FMSynthAWP extends AudioWorkletProcessor {
     });
   }
 
+  /**
+   * `set_adsr` rebuilds the ADSR, so this must be called after it to restore the note scaling
+   */
+  setAdsrNoteScaling(adsrIx, noteScaling) {
+    this.wasmInstance.exports.fm_synth_set_adsr_note_scaling(
+      this.ctxPtr,
+      adsrIx,
+      noteScaling?.velocityToLevel ?? 0,
+      noteScaling?.velocityToAttackTime ?? 0,
+      noteScaling?.keyTracking ?? 0,
+      noteScaling?.keyTrackingCenter ?? 60
+    );
+  }
+
   setSampleMappingState(stateByOperatorIx) {
     const entries = Object.entries(stateByOperatorIx);
     for (const [operatorIxStr, { mappedSamplesByMIDINumber }] of entries) {
@@ -577,24 +593,27 @@ class FMSynthAWP extends AudioWorkletProcessor {
         paramSource.valParamFloat3
       )
     );
-    adsrs.forEach(({ steps, lenSamples, releasePoint, loopPoint, logScale, adsrIx }) => {
-      steps.forEach(({ x, y, ramper, params }, stepIx) =>
-        this.wasmInstance.exports.set_adsr_step_buffer(stepIx, x, y, ramper, ...params)
-      );
-      this.wasmInstance.exports.set_adsr(
-        this.ctxPtr,
-        adsrIx,
-        steps.length,
-        lenSamples.valueType,
-        lenSamples.valParamInt,
-        lenSamples.valParamFloat,
-        lenSamples.valParamFloat2,
-        lenSamples.valParamFloat3,
-        releasePoint,
-        loopPoint ?? -1.0,
-        logScale ?? false
-      );
-    });
+    adsrs.forEach(
+      ({ steps, lenSamples, releasePoint, loopPoint, logScale, noteScaling, adsrIx }) => {
+        steps.forEach(({ x, y, ramper, params }, stepIx) =>
+          this.wasmInstance.exports.set_adsr_step_buffer(stepIx, x, y, ramper, ...params)
+        );
+        this.wasmInstance.exports.set_adsr(
+          this.ctxPtr,
+          adsrIx,
+          steps.length,
+          lenSamples.valueType,
+          lenSamples.valParamInt,
+          lenSamples.valParamFloat,
+          lenSamples.valParamFloat2,
+          lenSamples.valParamFloat3,
+          releasePoint,
+          loopPoint ?? -1.0,
+          logScale ?? false
+        );
+        this.setAdsrNoteScaling(adsrIx, noteScaling);
+      }
+    );
     modulationMatrix.forEach((indices, srcOperatorIx) =>
       indices.forEach((paramSource, dstOperatorIx) =>
         this.wasmInstance.exports.fm_synth_set_modulation_index(
@@ -3,6 +3,19 @@ const clamp = (min, max, val) => Math.min(Math.max(min, val), max);
 const BYTES_PER_F32 = 4;
 const FRAME_SIZE = 128;
 const RECORDING_BLOCK_SIZE = 44100 / 3; // 3 blocks/second
+/**
+ * Settings messages that are queued until the Wasm instance is ready rather than dropped
+ */
+const DEFERRED_MESSAGE_TYPES = new Set([
+  'enableLiveInput',
+  'setLiveInputFrozen',
+  'setLiveInputFeedback',
+  'setVoiceCount',
+  'setVoiceParams',
+  'setPitch',
+  'setWindow',
+  'setMIDIMode',
+]);
 
 class GranulatorWorkletProcessor extends AudioWorkletProcessor {
   static get parameterDescriptors() {
@@ -81,16 +94,25 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
     // We'll set the samples when we receive them if we haven't already
 
     this.wasmMemory = new Float32Array(this.wasmInstance.exports.memory.buffer);
+
+    this.pendingMessages.forEach(evt => this.port.onmessage(evt));
+    this.pendingMessages = [];
   }
 
-  initGranularCtx() {
-    // `get_granular_waveform_ptr` reallocates the waveform, so the context is created once and
-    // reused across sample changes.
+  /**
+   * `get_granular_waveform_ptr` reallocates the waveform, so the context is created once and reused
+   * across sample changes.  Settings can be applied to it before any samples are set.
+   */
+  getGranularCtxPtr() {
     if (!this.granularInstCtxPtr) {
       this.granularInstCtxPtr = this.wasmInstance.exports.create_granular_instance();
     }
+    return this.granularInstCtxPtr;
+  }
+
+  initGranularCtx() {
     const waveformPtr = this.wasmInstance.exports.get_granular_waveform_ptr(
-      this.granularInstCtxPtr,
+      this.getGranularCtxPtr(),
       this.samples.length
     );
     new Float32Array(this.wasmInstance.exports.memory.buffer).set(
@@ -103,6 +125,8 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
     super();
 
     this.samples = null;
+    // Length of the circular buffer that input is recorded into when in live mode; 0 if not live
+    this.liveInputBufferLen = 0;
     this.i = 0;
     this.isRecording = false;
     this.isShutdown = false;
@@ -114,10 +138,17 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
     this.recordingBlockIndex = 0;
     // The absolute index of the last sample sent to the main thread; next recording block should start here.
     this.lastSentRecordingBlockEndIx = 0;
+    this.pendingMessages = [];
 
     this.port.onmessage = evt => {
+      if (!this.wasmInstance && DEFERRED_MESSAGE_TYPES.has(evt.data.type)) {
+        this.pendingMessages.push(evt);
+        return;
+      }
+
       switch (evt.data.type) {
         case 'setSamples': {
+          this.liveInputBufferLen = 0;
           this.samples = evt.data.samples;
           if (this.wasmInstance && this.samples) {
             this.initGranularCtx();
@@ -131,6 +162,91 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
           });
           break;
         }
+        case 'enableLiveInput': {
+          this.liveInputBufferLen = evt.data.bufferLenSamples;
+          this.wasmInstance.exports.granular_enable_live_input(
+            this.getGranularCtxPtr(),
+            this.liveInputBufferLen
+          );
+          break;
+        }
+        case 'setLiveInputFrozen': {
+          if (this.liveInputBufferLen) {
+            this.wasmInstance.exports.granular_set_live_input_frozen(
+              this.granularInstCtxPtr,
+              evt.data.frozen
+            );
+          }
+          break;
+        }
+        case 'setLiveInputFeedback': {
+          if (this.liveInputBufferLen) {
+            this.wasmInstance.exports.granular_set_live_input_feedback(
+              this.granularInstCtxPtr,
+              evt.data.feedback
+            );
+          }
+          break;
+        }
+        case 'setVoiceCount': {
+          this.wasmInstance.exports.granular_set_voice_count(
+            this.getGranularCtxPtr(),
+            evt.data.voiceCount
+          );
+          break;
+        }
+        case 'setVoiceParams': {
+          // `readHeadMode`: 0 = forward, 1 = reverse, 2 = ping-pong
+          this.wasmInstance.exports.granular_set_voice_params(
+            this.getGranularCtxPtr(),
+            evt.data.voiceIx,
+            evt.data.movementSpeed ?? 1,
+            evt.data.spread ?? 0,
+            evt.data.pan ?? 0,
+            evt.data.readHeadMode ?? 0,
+            evt.data.grainReverseProbability ?? 0
+          );
+          break;
+        }
+        case 'setPitch': {
+          this.wasmInstance.exports.granular_set_pitch(
+            this.getGranularCtxPtr(),
+            evt.data.pitchShiftSemitones ?? 0,
+            evt.data.pitchRandomizationSemitones ?? 0
+          );
+          break;
+        }
+        case 'setWindow': {
+          // 0 = linear/sine mix, 1 = Hann, 2 = Tukey, 3 = trapezoid
+          this.wasmInstance.exports.granular_set_window(this.getGranularCtxPtr(), evt.data.window);
+          break;
+        }
+        case 'setMIDIMode': {
+          this.wasmInstance.exports.granular_set_midi_mode(
+            this.getGranularCtxPtr(),
+            evt.data.enabled,
+            evt.data.rootKey ?? 60
+          );
+          break;
+        }
+        case 'noteOn': {
+          if (this.granularInstCtxPtr) {
+            this.wasmInstance.exports.granular_note_on(this.granularInstCtxPtr, evt.data.midiNumber);
+          }
+          break;
+        }
+        case 'noteOff': {
+          if (this.granularInstCtxPtr) {
+            this.wasmInstance.exports.granular_note_off(this.granularInstCtxPtr, evt.data.midiNumber);
+          }
+          break;
+        }
+        case 'releaseAllNotes': {
+          if (this.granularInstCtxPtr) {
+            this.wasmInstance.exports.granular_release_all_notes(this.granularInstCtxPtr);
+          }
+          break;
+        }
         case 'shutdown': {
           this.isShutdown = true;
           break;
@@ -266,20 +382,44 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
       this.updateRecording(inputs);
     }
 
-    if (outputs.length === 0 || !this.samples || !this.wasmInstance || !this.granularInstCtxPtr) {
+    const isLive = this.liveInputBufferLen > 0;
+    if (
+      outputs.length === 0 ||
+      (!this.samples && !isLive) ||
+      !this.wasmInstance ||
+      !this.granularInstCtxPtr
+    ) {
       return true;
     } else if (outputs[0].length === 0) {
       throw new Error('Output 0 must have at least one channel for impl detail reasons');
     }
 
-    const selectionStartSampleIx = clamp(0, this.samples.length, params['start_sample'][0]);
-    const selectionEndSampleIx = clamp(
-      selectionStartSampleIx,
-      this.samples.length,
-      params['end_sample'][0]
-    );
+    const bufferLen = isLive ? this.liveInputBufferLen : this.samples.length;
+    let selectionStartSampleIx = clamp(0, bufferLen, params['start_sample'][0]);
+    let selectionEndSampleIx = clamp(selectionStartSampleIx, bufferLen, params['end_sample'][0]);
     if (selectionEndSampleIx <= selectionStartSampleIx) {
-      return true;
+      if (!isLive) {
+        return true;
+      }
+      // Default to granulating the whole live buffer if no selection has been made
+      selectionStartSampleIx = 0;
+      selectionEndSampleIx = bufferLen - 1;
+    }
+
+    if (isLive) {
+      const inputBufPtr = this.wasmInstance.exports.granular_get_live_input_buf_ptr(
+        this.granularInstCtxPtr
+      );
+      const inputBuf = this.getWasmMemory().subarray(
+        inputBufPtr / BYTES_PER_F32,
+        inputBufPtr / BYTES_PER_F32 + FRAME_SIZE
+      );
+      const input = inputs[0]?.[0];
+      if (input) {
+        inputBuf.set(input);
+      } else {
+        inputBuf.fill(0);
+      }
     }
 
     const grainSize = params['grain_size'][0];
@@ -304,19 +444,18 @@ class GranulatorWorkletProcessor extends AudioWorkletProcessor {
       samplesBetweenGrains
     );
 
-    // Fill the first output buffer and then copy them to all other outputs
-    const dstBuffer = outputs[0][0];
-    const outStartIx = outputBufPtr / BYTES_PER_F32;
-    const output = this.getWasmMemory().subarray(outStartIx, outStartIx + FRAME_SIZE);
-    dstBuffer.set(output);
+    // Fill the first output's left and right channels and then copy them to all other outputs
+    const wasmMemory = this.getWasmMemory();
+    const leftStartIx = outputBufPtr / BYTES_PER_F32;
+    const rightStartIx =
+      this.wasmInstance.exports.granular_get_right_output_ptr(this.granularInstCtxPtr) /
+      BYTES_PER_F32;
+    const left = wasmMemory.subarray(leftStartIx, leftStartIx + FRAME_SIZE);
+    const right = wasmMemory.subarray(rightStartIx, rightStartIx + FRAME_SIZE);
 
     for (let outputIx = 0; outputIx < outputs.length; outputIx++) {
       for (let channelIx = 0; channelIx < outputs[outputIx].length; channelIx++) {
-        if (outputIx === 0 && channelIx === 0) {
-          continue;
-        }
-
-        outputs[outputIx][channelIx].set(dstBuffer);
+        outputs[outputIx][channelIx].set(channelIx % 2 === 0 ? left : right);
       }
     }
 
@@ -1,4 +1,9 @@
 const BYTES_PER_F32 = 4;
+/**
+ * Layout of the phase SAB: [active bank phase, playing bank ix, bank count, ...per-bank phases]
+ */
+const BANK_PHASES_OFFSET = 3;
+const MAX_REPORTED_BANK_COUNT = 64;
 
 class LooperAWP extends AudioWorkletProcessor {
   constructor() {
@@ -9,7 +14,9 @@ class LooperAWP extends AudioWorkletProcessor {
     this.moduleIxForWhichToReportPhase = 0;
     this.pendingEvents = [];
     const curPhaseSAB =
-      typeof SharedArrayBuffer !== 'undefined' ? new SharedArrayBuffer(8) : undefined;
+      typeof SharedArrayBuffer !== 'undefined'
+        ? new SharedArrayBuffer((BANK_PHASES_OFFSET + MAX_REPORTED_BANK_COUNT) * BYTES_PER_F32)
+        : undefined;
     this.curPhaseBuffer = curPhaseSAB ? new Float32Array(curPhaseSAB) : undefined;
     this.mailboxIDsByModuleIx = new Array(64).fill(null);
     this.needsUIThreadSchedulingByModuleIX = new Array(64).fill(true);
@@ -103,6 +110,18 @@ class LooperAWP extends AudioWorkletProcessor {
         );
         break;
       }
+      case 'setPolymetricMode': {
+        this.wasmInstance.exports.looper_set_polymetric_mode(data.moduleIx, data.polymetric);
+        break;
+      }
+      case 'setBankPolymetricEnabled': {
+        this.wasmInstance.exports.looper_set_bank_polymetric_enabled(
+          data.moduleIx,
+          data.bankIx,
+          data.enabled
+        );
+        break;
+      }
       case 'updateMIDISchedulingInfoForModule': {
         const { moduleIx, mailboxIDs, needsUIThreadScheduling } = data;
         this.mailboxIDsByModuleIx[moduleIx] = mailboxIDs;
@@ -164,6 +183,25 @@ class LooperAWP extends AudioWorkletProcessor {
     this.pendingEvents = [];
   }
 
+  writeBankPhases() {
+    const moduleIx = this.moduleIxForWhichToReportPhase;
+    const bankCount = Math.min(
+      this.wasmInstance.exports.looper_get_bank_count(moduleIx),
+      MAX_REPORTED_BANK_COUNT
+    );
+    const bankPhasesPtr = this.wasmInstance.exports.looper_get_bank_phases_ptr(moduleIx);
+    // Memory growth detaches old views of the wasm memory
+    if (this.wasmMemoryF32?.buffer !== this.wasmInstance.exports.memory.buffer) {
+      this.wasmMemoryF32 = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    }
+
+    this.curPhaseBuffer[2] = bankCount;
+    const srcOffset = bankPhasesPtr / BYTES_PER_F32;
+    for (let bankIx = 0; bankIx < bankCount; bankIx++) {
+      this.curPhaseBuffer[BANK_PHASES_OFFSET + bankIx] = this.wasmMemoryF32[srcOffset + bankIx];
+    }
+  }
+
   process(_inputs, _outputs, _params) {
     if (!this.wasmInstance || !globalThis.globalBeatCounterStarted) {
       if (!globalThis.globalBeatCounterStarted) {
@@ -173,6 +211,7 @@ class LooperAWP extends AudioWorkletProcessor {
         this.wasmInstance.exports.looper_on_playback_stop();
         if (this.curPhaseBuffer) {
           this.curPhaseBuffer[1] = -1;
+          this.curPhaseBuffer[2] = 0;
         }
         this.didReleaseAfterStop = true;
       }
@@ -198,6 +237,7 @@ class LooperAWP extends AudioWorkletProcessor {
       this.curPhaseBuffer[1] = this.wasmInstance.exports.looper_get_playing_bank_ix(
         this.moduleIxForWhichToReportPhase
       );
+      this.writeBankPhases();
     }
 
     return true;
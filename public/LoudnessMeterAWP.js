@@ -0,0 +1,102 @@
+const FRAME_SIZE = 128;
+const BYTES_PER_F32 = 32 / 8;
+// momentary, short-term, integrated, LRA, max momentary, max short-term, max true peak
+const READING_COUNT = 7;
+
+class LoudnessMeterWorkletProcessor extends AudioWorkletProcessor {
+  constructor() {
+    super();
+
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.wasmMemoryBuffer = null;
+    this.readingsSAB =
+      typeof SharedArrayBuffer === 'undefined'
+        ? null
+        : new SharedArrayBuffer(READING_COUNT * BYTES_PER_F32);
+    this.readingsBufF32 = this.readingsSAB ? new Float32Array(this.readingsSAB) : null;
+    this.readingsBufF32?.fill(-Infinity);
+
+    this.port.onmessage = evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        case 'reset': {
+          if (this.wasmInstance) {
+            this.wasmInstance.exports.loudness_meter_reset(this.ctxPtr);
+          }
+          this.readingsBufF32?.fill(-Infinity);
+          break;
+        }
+        default: {
+          console.warn('Unhandled message type in loudness meter AWP: ', evt.data.type);
+        }
+      }
+    };
+
+    if (this.readingsBufF32) {
+      this.port.postMessage({ type: 'readingsSAB', sab: this.readingsSAB });
+    }
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`LoudnessMeterAWP Wasm panic: ${str}`);
+  };
+
+  async initWasm(wasmBytes) {
+    const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.ctxPtr = this.wasmInstance.exports.loudness_meter_create_ctx(sampleRate);
+    this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @returns {boolean}
+   */
+  process(inputs, outputs) {
+    const input = inputs[0];
+    const output = outputs[0];
+
+    if (!input?.[0] || !output?.[0] || !this.wasmInstance) {
+      return true;
+    }
+
+    const ioBufPtr = this.wasmInstance.exports.loudness_meter_get_io_buf_ptr(this.ctxPtr);
+    const ioBuf = this.wasmMemoryBuffer.subarray(
+      ioBufPtr / BYTES_PER_F32,
+      ioBufPtr / BYTES_PER_F32 + FRAME_SIZE * 2
+    );
+    // Mono inputs are measured as dual-mono
+    const left = input[0];
+    const right = input[1] ?? input[0];
+    ioBuf.set(left, 0);
+    ioBuf.set(right, FRAME_SIZE);
+
+    this.wasmInstance.exports.loudness_meter_process(this.ctxPtr);
+
+    if (this.readingsBufF32) {
+      const readingsPtr = this.wasmInstance.exports.loudness_meter_get_readings_ptr(this.ctxPtr);
+      this.readingsBufF32.set(
+        this.wasmMemoryBuffer.subarray(
+          readingsPtr / BYTES_PER_F32,
+          readingsPtr / BYTES_PER_F32 + READING_COUNT
+        )
+      );
+    }
+
+    output[0]?.set(left);
+    output[1]?.set(right);
+
+    return true;
+  }
+}
+
+registerProcessor('loudness-meter-awp', LoudnessMeterWorkletProcessor);
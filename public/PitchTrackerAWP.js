@@ -0,0 +1,132 @@
+const FRAME_SIZE = 128;
+const BYTES_PER_F32 = 32 / 8;
+// f0, nearest note, cents, confidence, level dB, active note, held f0, held note
+const READING_COUNT = 8;
+const READING_CONFIDENCE = 3;
+const READING_HELD_F0 = 6;
+const READING_HELD_NOTE = 7;
+
+class PitchTrackerWorkletProcessor extends AudioWorkletProcessor {
+  constructor() {
+    super();
+
+    this.wasmInstance = null;
+    this.ctxPtr = 0;
+    this.wasmMemoryBuffer = null;
+    this.pendingConfig = null;
+    this.readingsSAB =
+      typeof SharedArrayBuffer === 'undefined'
+        ? null
+        : new SharedArrayBuffer(READING_COUNT * BYTES_PER_F32);
+    this.readingsBufF32 = this.readingsSAB ? new Float32Array(this.readingsSAB) : null;
+
+    this.port.onmessage = evt => {
+      switch (evt.data.type) {
+        case 'setWasmBytes': {
+          this.initWasm(evt.data.wasmBytes);
+          break;
+        }
+        case 'setConfig': {
+          this.pendingConfig = evt.data.config;
+          this.maybeApplyConfig();
+          break;
+        }
+        case 'reset': {
+          if (this.wasmInstance) {
+            this.wasmInstance.exports.pitch_tracker_reset(this.ctxPtr);
+          }
+          break;
+        }
+        default: {
+          console.warn('Unhandled message type in pitch tracker AWP: ', evt.data.type);
+        }
+      }
+    };
+
+    if (this.readingsBufF32) {
+      this.port.postMessage({ type: 'readingsSAB', sab: this.readingsSAB });
+    }
+  }
+
+  handleWasmPanic = (ptr, len) => {
+    const mem = new Uint8Array(this.wasmInstance.exports.memory.buffer);
+    const slice = mem.subarray(ptr, ptr + len);
+    const str = String.fromCharCode(...slice);
+    console.error(`PitchTrackerAWP Wasm panic: ${str}`);
+  };
+
+  maybeApplyConfig() {
+    if (!this.wasmInstance || !this.pendingConfig) {
+      return;
+    }
+
+    const { gateDb, gateHysteresisDb, minConfidence, noteHysteresisCents } = this.pendingConfig;
+    this.wasmInstance.exports.pitch_tracker_set_config(
+      this.ctxPtr,
+      gateDb,
+      gateHysteresisDb,
+      minConfidence,
+      noteHysteresisCents
+    );
+    this.pendingConfig = null;
+  }
+
+  async initWasm(wasmBytes) {
+    const importObject = {
+      env: {
+        log_err: (ptr, len) => this.handleWasmPanic(ptr, len),
+        on_note_on: (note, velocity) => this.port.postMessage({ type: 'noteOn', note, velocity }),
+        on_note_off: note => this.port.postMessage({ type: 'noteOff', note }),
+      },
+    };
+    const compiledModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
+    this.ctxPtr = this.wasmInstance.exports.pitch_tracker_create_ctx(sampleRate);
+    this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    this.maybeApplyConfig();
+  }
+
+  /**
+   * @param {Float32Array[][]} inputs
+   * @param {Float32Array[][]} outputs
+   * @returns {boolean}
+   */
+  process(inputs, outputs) {
+    const input = inputs[0]?.[0];
+    const output = outputs[0];
+
+    if (!this.wasmInstance) {
+      return true;
+    }
+
+    const ioBufPtr = this.wasmInstance.exports.pitch_tracker_get_io_buf_ptr(this.ctxPtr);
+    const ioBuf = this.wasmMemoryBuffer.subarray(
+      ioBufPtr / BYTES_PER_F32,
+      ioBufPtr / BYTES_PER_F32 + FRAME_SIZE
+    );
+    // keep analyzing silence while disconnected so that active notes get released
+    if (input) {
+      ioBuf.set(input);
+    } else {
+      ioBuf.fill(0);
+    }
+
+    this.wasmInstance.exports.pitch_tracker_process(this.ctxPtr);
+
+    const readingsPtr = this.wasmInstance.exports.pitch_tracker_get_readings_ptr(this.ctxPtr);
+    const readings = this.wasmMemoryBuffer.subarray(
+      readingsPtr / BYTES_PER_F32,
+      readingsPtr / BYTES_PER_F32 + READING_COUNT
+    );
+    this.readingsBufF32?.set(readings);
+
+    // control outputs: held f0 in Hz, held fractional MIDI note number, confidence
+    output?.[0]?.fill(readings[READING_HELD_F0]);
+    output?.[1]?.fill(readings[READING_HELD_NOTE]);
+    output?.[2]?.fill(readings[READING_CONFIDENCE]);
+
+    return true;
+  }
+}
+
+registerProcessor('pitch-tracker-awp', PitchTrackerWorkletProcessor);
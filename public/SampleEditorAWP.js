@@ -1,4 +1,14 @@
 const BYTES_PER_F32 = 32 / 8;
+const FADE_CURVE_IXS = { linear: 0, equalPower: 1, exponential: 2, logarithmic: 3, sCurve: 4 };
+/**
+ * Number of values in each render request read from Wasm memory
+ */
+const RENDER_REQUEST_LEN = 5;
+/**
+ * Stretched clips are only re-rendered once the tempo has stopped changing for this long so that
+ * ramps and tempo drags don't request a rendering for every intermediate tempo
+ */
+const TEMPO_CHANGE_DEBOUNCE_SECS = 0.25;
 
 class SampleEditorAWP extends AudioWorkletProcessor {
   static get parameterDescriptors() {
@@ -19,6 +29,18 @@ class SampleEditorAWP extends AudioWorkletProcessor {
      * Mapping from sample ID to the key of the sample in Wasm memory that can be used to remove it.
      */
     this.sampleKeyMapping = new Map();
+    /**
+     * Latest tempo read from the global tempo
+     */
+    this.lastBPM = 0;
+    /**
+     * Tempo that the Wasm is currently playing clips at
+     */
+    this.ctxBPM = 0;
+    /**
+     * Frame at which the tempo last changed, or `null` if renderings have been requested for it
+     */
+    this.tempoChangedFrame = null;
 
     this.port.onmessage = evt => {
       switch (evt.data.type) {
@@ -34,6 +56,22 @@ class SampleEditorAWP extends AudioWorkletProcessor {
           this.removeSample(evt.data.sampleID);
           break;
         }
+        case 'setSampleStretch': {
+          this.setSampleStretch(evt.data.sampleID, evt.data);
+          break;
+        }
+        case 'setSampleClipParams': {
+          this.setSampleClipParams(evt.data.sampleID, evt.data);
+          break;
+        }
+        case 'renderedData': {
+          this.provideRenderedData(evt.data.bpm, evt.data.renderings);
+          break;
+        }
+        case 'setCrossfadeOverlaps': {
+          this.wasmInst.exports.set_crossfade_overlaps(this.ctxPtr, evt.data.enabled);
+          break;
+        }
         default: {
           console.warn('Unhandled event type received over port: ' + evt.data.type);
         }
@@ -52,16 +90,42 @@ class SampleEditorAWP extends AudioWorkletProcessor {
     let index = this.sampleDataMapping.get(sampleData.id) ?? -1;
     index = this.wasmInst.exports.write_sample_data(this.ctxPtr, sampleData.samples.length, index);
     const sampleDataBufPtr = this.wasmInst.exports.get_sample_data_buf_ptr(this.ctxPtr, index);
+    // Allocating sample data can grow Wasm memory
+    if (this.wasmMemoryF32View.buffer !== this.wasmInst.exports.memory.buffer) {
+      this.wasmMemoryF32View = new Float32Array(this.wasmInst.exports.memory.buffer);
+    }
     const f32Index = sampleDataBufPtr / BYTES_PER_F32;
     this.wasmMemoryF32View.set(sampleData.samples, f32Index);
     return index;
   }
 
+  /**
+   * Writes the gain envelope into Wasm memory to be used by the next `create_sample` or
+   * `set_sample_gain_envelope` call.
+   *
+   * @param {number[]} gainEnvelope
+   */
+  writeGainEnvelope(gainEnvelope) {
+    const ptr = this.wasmInst.exports.set_gain_envelope_ptr(this.ctxPtr, gainEnvelope.length);
+    if (this.wasmMemoryF32View.buffer !== this.wasmInst.exports.memory.buffer) {
+      this.wasmMemoryF32View = new Float32Array(this.wasmInst.exports.memory.buffer);
+    }
+    this.wasmMemoryF32View.set(gainEnvelope, ptr / BYTES_PER_F32);
+  }
+
+  getSampleKey(sampleID) {
+    const key = this.sampleKeyMapping.get(sampleID);
+    if (key === null || key === undefined) {
+      throw new Error(`No entry exists for sample with id=${sampleID} in \`sampleKeyMapping\`.`);
+    }
+    return key;
+  }
+
   /**
    * Schedules a sample in the Wasm.  The sample's data must have already been written into Wasm
    * memory with the `setSampleData` function.
    *
-   * @param {{ id: string|number; startBeat: number }} sample
+   * @param {{ id: string|number; startBeat: number; gainEnvelope?: number[]; targetLenBeats?: number; pitchShiftCents?: number } & ClipParams} sample
    */
   createSample(sample) {
     const dataIndex = this.sampleDataMapping.get(sample.id);
@@ -71,14 +135,179 @@ class SampleEditorAWP extends AudioWorkletProcessor {
       );
     }
 
-    // TODO: Gain envelopes
+    if (sample.gainEnvelope) {
+      this.writeGainEnvelope(sample.gainEnvelope);
+    }
     const key = this.wasmInst.exports.create_sample(
       this.ctxPtr,
       sample.startBeat,
       dataIndex,
-      false
+      !!sample.gainEnvelope
     );
     this.sampleKeyMapping.set(sample.id, key);
+
+    // The gain envelope was already set when creating the sample
+    this.setSampleClipParams(sample.id, { ...sample, gainEnvelope: undefined });
+    if (sample.targetLenBeats || sample.pitchShiftCents) {
+      this.setSampleStretch(sample.id, sample);
+    }
+  }
+
+  getSampleDataID(dataIndex) {
+    for (const [id, index] of this.sampleDataMapping) {
+      if (index === dataIndex) {
+        return id;
+      }
+    }
+    return undefined;
+  }
+
+  /**
+   * Posts the time-stretched and pitch-shifted renderings that clips need at `bpm` to the main
+   * thread, which renders them and sends them back in a `renderedData` message.  Rendering is far
+   * too expensive to do on the audio thread.  If nothing needs rendering, the tempo is applied
+   * right away; otherwise, it's applied once the reply comes back.
+   *
+   * @param {number} bpm
+   */
+  requestRenders(bpm) {
+    const exports = this.wasmInst.exports;
+    const requestCount = exports.collect_render_requests(this.ctxPtr, bpm);
+    if (requestCount === 0) {
+      if (bpm !== this.ctxBPM) {
+        this.ctxBPM = bpm;
+        exports.set_bpm(this.ctxPtr, bpm);
+      }
+      return;
+    }
+
+    const requestsPtr = exports.get_render_requests_ptr(this.ctxPtr);
+    const requestsF64 = new Float64Array(
+      exports.memory.buffer,
+      requestsPtr,
+      requestCount * RENDER_REQUEST_LEN
+    );
+    const requests = [];
+    for (let i = 0; i < requestCount; i++) {
+      const [sourceDataIx, sourceStart, sourceEnd, lenSamples, pitchShiftCents] =
+        requestsF64.subarray(i * RENDER_REQUEST_LEN, (i + 1) * RENDER_REQUEST_LEN);
+      requests.push({
+        sampleDataID: this.getSampleDataID(sourceDataIx),
+        sourceStart,
+        sourceEnd,
+        lenSamples,
+        pitchShiftCents,
+      });
+    }
+    this.port.postMessage({ type: 'renderRequests', bpm, requests });
+  }
+
+  /**
+   * Writes renderings made on the main thread into Wasm memory and switches playback over to them.
+   * Replies for a tempo that has since changed again are stored but don't change the tempo.
+   *
+   * @param {number} bpm
+   * @param {{ sampleDataID: string|number; sourceStart: number; sourceEnd: number; lenSamples: number; pitchShiftCents: number; samples: Float32Array }[]} renderings
+   */
+  provideRenderedData(bpm, renderings) {
+    const exports = this.wasmInst.exports;
+    for (const rendering of renderings) {
+      const dataIndex = this.sampleDataMapping.get(rendering.sampleDataID);
+      if (dataIndex === undefined || dataIndex === null) {
+        continue;
+      }
+
+      const ptr = exports.get_rendered_data_buf_ptr(this.ctxPtr, rendering.samples.length);
+      if (this.wasmMemoryF32View.buffer !== exports.memory.buffer) {
+        this.wasmMemoryF32View = new Float32Array(exports.memory.buffer);
+      }
+      this.wasmMemoryF32View.set(rendering.samples, ptr / BYTES_PER_F32);
+      exports.provide_rendered_data(
+        this.ctxPtr,
+        dataIndex,
+        rendering.sourceStart,
+        rendering.sourceEnd,
+        rendering.lenSamples,
+        rendering.pitchShiftCents
+      );
+    }
+
+    if (bpm === this.lastBPM) {
+      this.ctxBPM = bpm;
+    }
+    exports.set_bpm(this.ctxPtr, this.ctxBPM);
+  }
+
+  /**
+   * @typedef {'linear' | 'equalPower' | 'exponential' | 'logarithmic' | 'sCurve'} FadeCurve
+   * @typedef {{ lenSamples: number; curve?: FadeCurve }} Fade
+   * @typedef {{ sourceOffsetSamples?: number; sourceLenSamples?: number; gain?: number; fadeIn?: Fade; fadeOut?: Fade }} ClipParams
+   */
+
+  /**
+   * Sets trim, gain, and fades for the scheduled sample with the provided ID.  Only the params that
+   * are provided are updated.  Trim and fade changes take effect the next time the sample starts
+   * playing; gain changes are applied immediately.  If `sourceLenSamples` is 0, the sample plays
+   * until the end of its data.
+   *
+   * @param {string|number} sampleID
+   * @param {ClipParams & { gainEnvelope?: number[] | null }} params
+   */
+  setSampleClipParams(sampleID, params) {
+    const key = this.getSampleKey(sampleID);
+    const exports = this.wasmInst.exports;
+
+    if (params.sourceOffsetSamples !== undefined || params.sourceLenSamples !== undefined) {
+      exports.set_sample_trim(
+        this.ctxPtr,
+        key,
+        params.sourceOffsetSamples ?? 0,
+        params.sourceLenSamples ?? 0
+      );
+      this.requestRenders(this.ctxBPM);
+    }
+    if (params.gain !== undefined) {
+      exports.set_sample_gain(this.ctxPtr, key, params.gain);
+    }
+    if (params.fadeIn || params.fadeOut) {
+      exports.set_sample_fades(
+        this.ctxPtr,
+        key,
+        params.fadeIn?.lenSamples ?? 0,
+        FADE_CURVE_IXS[params.fadeIn?.curve ?? 'linear'],
+        params.fadeOut?.lenSamples ?? 0,
+        FADE_CURVE_IXS[params.fadeOut?.curve ?? 'linear']
+      );
+    }
+    // `null` clears the gain envelope while `undefined` leaves it unchanged
+    if (params.gainEnvelope !== undefined) {
+      if (params.gainEnvelope) {
+        this.writeGainEnvelope(params.gainEnvelope);
+      }
+      exports.set_sample_gain_envelope(this.ctxPtr, key, !!params.gainEnvelope);
+    }
+  }
+
+  /**
+   * Time-stretches the scheduled sample with the provided ID to last `targetLenBeats` beats at the
+   * current tempo and pitch-shifts it by `pitchShiftCents`.  Both are rendered ahead of time on the
+   * main thread, and stretched samples are re-rendered when the tempo changes.  The sample doesn't
+   * play until its rendering arrives.  If `targetLenBeats` is not provided, the sample keeps its
+   * original length.
+   *
+   * @param {string|number} sampleID
+   * @param {{ targetLenBeats?: number; pitchShiftCents?: number }} params
+   */
+  setSampleStretch(sampleID, { targetLenBeats, pitchShiftCents }) {
+    const key = this.getSampleKey(sampleID);
+
+    this.wasmInst.exports.set_sample_stretch(
+      this.ctxPtr,
+      key,
+      targetLenBeats ?? 0,
+      pitchShiftCents ?? 0
+    );
+    this.requestRenders(this.ctxBPM);
   }
 
   /**
@@ -102,6 +331,9 @@ class SampleEditorAWP extends AudioWorkletProcessor {
     const compiledModule = await WebAssembly.compile(wasmBytes);
     this.wasmInst = new WebAssembly.Instance(compiledModule, importObject);
     this.ctxPtr = this.wasmInst.exports.init_sample_editor_ctx();
+    this.lastBPM = globalThis.globalTempoBPM || 120;
+    this.ctxBPM = this.lastBPM;
+    this.wasmInst.exports.set_bpm(this.ctxPtr, this.ctxBPM);
     this.wasmMemoryF32View = new Float32Array(this.wasmInst.exports.memory.buffer);
 
     // Copy the actual sample data into Wasm memory
@@ -122,6 +354,20 @@ class SampleEditorAWP extends AudioWorkletProcessor {
       globalThis.globalBeatCounterStarted && globalThis.transport
         ? globalThis.transport.beatAt(currentFrame)
         : globalThis.curBeat;
+    // Stretched samples are re-rendered on the main thread to keep their length in beats at the new
+    // tempo.  The new tempo is applied along with the renderings once they come back, or
+    // immediately if nothing needs to be re-rendered.
+    const bpm = globalThis.globalTempoBPM || 120;
+    if (bpm !== this.lastBPM) {
+      this.lastBPM = bpm;
+      this.tempoChangedFrame = currentFrame;
+    } else if (
+      this.tempoChangedFrame !== null &&
+      currentFrame - this.tempoChangedFrame >= TEMPO_CHANGE_DEBOUNCE_SECS * sampleRate
+    ) {
+      this.tempoChangedFrame = null;
+      this.requestRenders(bpm);
+    }
     this.wasmInst.exports.process_sample_editor(this.ctxPtr, curBeat);
 
     return true;
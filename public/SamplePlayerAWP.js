@@ -110,6 +110,17 @@ class SamplePlayerAWP extends AudioWorkletProcessor {
     this.wasmInstance.exports.set_sample_crossfade_params(this.ctxPtr, voiceIx, enabled, threshold);
   }
 
+  setSampleLoopParams(voiceIx, mode, startSampleIx, endSampleIx, crossfadeLenSamples) {
+    this.wasmInstance.exports.set_sample_loop_params(
+      this.ctxPtr,
+      voiceIx,
+      mode,
+      startSampleIx,
+      endSampleIx,
+      crossfadeLenSamples
+    );
+  }
+
   handleMessage(data) {
     // Store all events other than the initialization event until after Wasm is loaded and they can be handled.
     //
@@ -144,6 +155,16 @@ class SamplePlayerAWP extends AudioWorkletProcessor {
         this.setSampleCrossfadeParams(data.voiceIx, data.enabled, data.threshold);
         break;
       }
+      case 'setSampleLoopParams': {
+        this.setSampleLoopParams(
+          data.voiceIx,
+          data.mode,
+          data.startSampleIx,
+          data.endSampleIx,
+          data.crossfadeLenSamples
+        );
+        break;
+      }
       default: {
         console.error('Unhandled message type in sample player AWP: ', data.type);
       }
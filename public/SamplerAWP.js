@@ -116,6 +116,8 @@ class SamplerAWP extends AudioWorkletProcessor {
           crossfadeEndLenSamples,
           playbackRate,
           reverse,
+          loopStartSampleIx,
+          loopEndSampleIx,
         } = data.selection;
         this.wasmInstance.exports.sampler_set_selection(
           this.ctxPtr,
@@ -127,6 +129,13 @@ class SamplerAWP extends AudioWorkletProcessor {
           playbackRate,
           reverse
         );
+        // A zero-length loop removes it
+        this.wasmInstance.exports.sampler_set_selection_loop(
+          this.ctxPtr,
+          midiNumber,
+          loopStartSampleIx ?? 0,
+          loopEndSampleIx ?? 0
+        );
         break;
       }
       case 'clearSelection': {
@@ -134,6 +143,83 @@ class SamplerAWP extends AudioWorkletProcessor {
         this.wasmInstance.exports.sampler_clear_selection(this.ctxPtr, midiNumber);
         break;
       }
+      case 'clearSelections': {
+        this.wasmInstance.exports.sampler_clear_selections(this.ctxPtr);
+        break;
+      }
+      case 'setZoneSampleData': {
+        const { bufferIx, sampleData } = data;
+        const sampleDataPtr = this.wasmInstance.exports.sampler_get_zone_sample_buffer_ptr(
+          this.ctxPtr,
+          bufferIx,
+          sampleData.length
+        );
+        this.getWasmMemoryBuffer().set(sampleData, sampleDataPtr / BYTES_PER_F32);
+        break;
+      }
+      case 'setZones': {
+        // Replaces all zones.  While any zones are defined, they take over from the per-key
+        // selections when handling MIDI attacks.
+        this.wasmInstance.exports.sampler_clear_zones(this.ctxPtr);
+        for (const zone of data.zones) {
+          this.wasmInstance.exports.sampler_add_zone(
+            this.ctxPtr,
+            zone.sampleBufferIx,
+            zone.loKey ?? 0,
+            zone.hiKey ?? 127,
+            zone.rootKey ?? 60,
+            zone.loVel ?? 0,
+            zone.hiVel ?? 127,
+            zone.velCrossfadeIn ?? 0,
+            zone.velCrossfadeOut ?? 0,
+            zone.roundRobinGroup ?? -1,
+            zone.roundRobinPosition ?? 0,
+            zone.startSampleIx ?? 0,
+            zone.endSampleIx ?? 0,
+            zone.crossfadeStartLenSamples ?? 0,
+            zone.crossfadeEndLenSamples ?? 0,
+            zone.gain ?? 1,
+            zone.tuneCents ?? 0,
+            zone.pitchKeytrackCents ?? 100,
+            zone.reverse ?? false
+          );
+        }
+        break;
+      }
+      case 'loadSFZ': {
+        // `sfzBytes` is the UTF-8 encoded SFZ file since `TextEncoder` isn't available in all
+        // worklet scopes.  `samplesByPath` maps the sample paths referenced by the SFZ file
+        // (relative to it, with `default_path` applied and `/` separators) to mono sample data
+        // already resampled to the context's sample rate.
+        const { sfzBytes, samplesByPath } = data;
+        const textBufPtr = this.wasmInstance.exports.sampler_get_sfz_text_buf_ptr(
+          this.ctxPtr,
+          sfzBytes.length
+        );
+        new Uint8Array(this.wasmInstance.exports.memory.buffer).set(sfzBytes, textBufPtr);
+
+        const sampleCount = this.wasmInstance.exports.sampler_load_sfz(this.ctxPtr);
+        if (sampleCount < 0) {
+          break;
+        }
+
+        for (let bufferIx = 0; bufferIx < sampleCount; bufferIx++) {
+          const path = this.readSFZSamplePath(bufferIx);
+          const sampleData = samplesByPath[path];
+          if (!sampleData) {
+            console.warn(`SamplerAWP: no sample data provided for SFZ sample "${path}"`);
+            continue;
+          }
+
+          const sampleDataPtr = this.wasmInstance.exports.sampler_get_zone_sample_buffer_ptr(
+            this.ctxPtr,
+            bufferIx,
+            sampleData.length
+          );
+          this.getWasmMemoryBuffer().set(sampleData, sampleDataPtr / BYTES_PER_F32);
+        }
+        break;
+      }
       case 'captureNextMIDIAttack': {
         this.transmitMIDIAttack = true;
         break;
@@ -144,6 +230,14 @@ class SamplerAWP extends AudioWorkletProcessor {
     }
   }
 
+  readSFZSamplePath(bufferIx) {
+    const ptr = this.wasmInstance.exports.sampler_get_sfz_sample_path_ptr(this.ctxPtr, bufferIx);
+    const len = this.wasmInstance.exports.sampler_get_sfz_sample_path_len(this.ctxPtr, bufferIx);
+    const bytes = new Uint8Array(this.wasmInstance.exports.memory.buffer).subarray(ptr, ptr + len);
+    // Paths are UTF-8; this round-trip decodes multi-byte characters without `TextDecoder`
+    return decodeURIComponent(escape(String.fromCharCode(...bytes)));
+  }
+
   checkMailbox() {
     if (!this.mailboxID || !globalThis.transport) {
       return;
@@ -156,7 +250,12 @@ class SamplerAWP extends AudioWorkletProcessor {
       const note = e.param0;
       switch (e.type) {
         case 0: {
-          this.wasmInstance.exports.sampler_handle_midi_attack(this.ctxPtr, note, e.sampleOffset);
+          this.wasmInstance.exports.sampler_handle_midi_attack_with_velocity(
+            this.ctxPtr,
+            note,
+            e.param1 ?? 127,
+            e.sampleOffset
+          );
           if (this.transmitMIDIAttack) {
             this.port.postMessage({ type: 'midiAttack', midiNumber: note });
             this.transmitMIDIAttack = false;
@@ -168,6 +267,7 @@ class SamplerAWP extends AudioWorkletProcessor {
           break;
         }
         case 1: {
+          this.wasmInstance.exports.sampler_handle_midi_release(this.ctxPtr, note, e.sampleOffset);
           if (this.midiGateStatusF32) {
             this.midiGateStatusF32[note] = 0;
             Atomics.notify(this.midiGateStatusI32, MIDI_GATE_STATUS_BUFFER_UPDATED_IX);
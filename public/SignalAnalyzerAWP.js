@@ -2,7 +2,14 @@ const FRAME_SIZE = 128;
 const BYTES_PER_F32 = 4;
 // ~2 seconds of audio
 const CIRCULAR_BUFFER_LEN_SAMPLES = 345 * 2 * FRAME_SIZE;
-const SAB_SIZE = (8 + CIRCULAR_BUFFER_LEN_SAMPLES) * BYTES_PER_F32;
+// Oscilloscope channels 1 and 2 are the left and right channels of the main input and channels 3
+// and 4 are the auxiliary inputs
+const SCOPE_CHANNEL_COUNT = 4;
+// Each frame is written as all samples for channel 1 followed by all samples for channel 2, etc.
+const SAB_SIZE = (8 + CIRCULAR_BUFFER_LEN_SAMPLES * SCOPE_CHANNEL_COUNT) * BYTES_PER_F32;
+// Same layout as the mono SAB, but each frame is written as all left samples followed by all
+// right samples
+const STEREO_SAB_SIZE = (8 + CIRCULAR_BUFFER_LEN_SAMPLES * 2) * BYTES_PER_F32;
 
 class SignalAnalyzerAWP extends AudioWorkletProcessor {
   constructor() {
@@ -12,16 +19,22 @@ class SignalAnalyzerAWP extends AudioWorkletProcessor {
     this.sabF32 = new Float32Array(sab);
     this.sabI32 = new Int32Array(sab);
     this.samplesCircularBuffer = this.sabF32.subarray(8);
-    if (this.samplesCircularBuffer.length % FRAME_SIZE !== 0) {
+    if (this.samplesCircularBuffer.length % (FRAME_SIZE * SCOPE_CHANNEL_COUNT) !== 0) {
       throw new Error(
-        `Circular buffer length (${this.samplesCircularBuffer.length}) is not divisible by frame size (${FRAME_SIZE})`
+        `Circular buffer length (${this.samplesCircularBuffer.length}) is not divisible by frame size (${FRAME_SIZE}) * channel count (${SCOPE_CHANNEL_COUNT})`
       );
     }
 
+    const stereoSab = new SharedArrayBuffer(STEREO_SAB_SIZE);
+    this.stereoSabF32 = new Float32Array(stereoSab);
+    this.stereoSabI32 = new Int32Array(stereoSab);
+    this.stereoSamplesCircularBuffer = this.stereoSabF32.subarray(8);
+
     this.port.onmessage = evt => {
       switch (evt.data.type) {
         case 'sendSAB':
           this.port.postMessage({ type: 'setSAB', sab });
+          this.port.postMessage({ type: 'setStereoSAB', sab: stereoSab });
           break;
         default:
           console.error('Unknown message type', evt.data.type);
@@ -34,6 +47,8 @@ class SignalAnalyzerAWP extends AudioWorkletProcessor {
     if (!input) {
       return true;
     }
+    // Mono inputs are treated as dual-mono
+    const rightInput = inputs[0][1] ?? input;
 
     const sampleCount = input.length;
     // SAB Layout (32-bit increments):
@@ -50,11 +65,18 @@ class SignalAnalyzerAWP extends AudioWorkletProcessor {
     //                 extends to the end of the SAB.
 
     let head = Atomics.load(this.sabI32, 7);
-    const bufferLen = this.samplesCircularBuffer.length;
-    for (let i = 0; i < sampleCount; i++) {
-      this.samplesCircularBuffer[head] = input[i];
-      head = (head + 1) % bufferLen;
+    const scopeChannels = [input, rightInput, inputs[1]?.[0], inputs[2]?.[0]];
+    for (let channelIx = 0; channelIx < SCOPE_CHANNEL_COUNT; channelIx++) {
+      const channelStart = head + channelIx * sampleCount;
+      const channel = scopeChannels[channelIx];
+      if (channel) {
+        this.samplesCircularBuffer.set(channel, channelStart);
+      } else {
+        // Disconnected auxiliary inputs have no channels
+        this.samplesCircularBuffer.fill(0, channelStart, channelStart + sampleCount);
+      }
     }
+    head = (head + sampleCount * SCOPE_CHANNEL_COUNT) % this.samplesCircularBuffer.length;
     Atomics.store(this.sabI32, 7, head);
     Atomics.notify(this.sabI32, 7);
     Atomics.add(this.sabI32, 2, 1);
@@ -63,6 +85,13 @@ class SignalAnalyzerAWP extends AudioWorkletProcessor {
     this.sabF32[4] = globalThis.currentTime;
     this.sabF32[5] = globalThis.globalTempoBPM;
 
+    let stereoHead = Atomics.load(this.stereoSabI32, 7);
+    this.stereoSamplesCircularBuffer.set(input, stereoHead);
+    this.stereoSamplesCircularBuffer.set(rightInput, stereoHead + sampleCount);
+    stereoHead = (stereoHead + sampleCount * 2) % this.stereoSamplesCircularBuffer.length;
+    Atomics.store(this.stereoSabI32, 7, stereoHead);
+    Atomics.add(this.stereoSabI32, 2, 1);
+
     return true;
   }
 }
@@ -1,4 +1,5 @@
 const FRAME_SIZE = 128;
+const BAND_SPACING_IXS = { log: 0, bark: 1, mel: 2 };
 
 class VocoderAWP extends AudioWorkletProcessor {
   static get parameterDescriptors() {
@@ -15,6 +16,21 @@ class VocoderAWP extends AudioWorkletProcessor {
         name: 'post_gain',
         automationRate: 'k-rate',
       },
+      {
+        name: 'sibilance_gain',
+        automationRate: 'k-rate',
+        defaultValue: 0,
+      },
+      {
+        name: 'formant_shift',
+        automationRate: 'k-rate',
+        defaultValue: 0,
+      },
+      {
+        name: 'freeze',
+        automationRate: 'k-rate',
+        defaultValue: 0,
+      },
     ];
   }
 
@@ -24,6 +40,8 @@ class VocoderAWP extends AudioWorkletProcessor {
     this.wasmInstance = null;
     this.wasmMemoryBuffer = null;
     this.ctxPtr = 0;
+    this.bandConfig = null;
+    this.bandEnvelopes = [];
 
     this.port.onmessage = event => this.handleMessage(event.data);
   }
@@ -35,28 +53,71 @@ class VocoderAWP extends AudioWorkletProcessor {
     console.error(str);
   };
 
-  async initWasm(wasmBytes, filterParams) {
+  async initWasm(wasmBytes) {
     const importObject = { env: { log_err: (ptr, len) => this.handleWasmPanic(ptr, len) } };
     const compiledModule = await WebAssembly.compile(wasmBytes);
     this.wasmInstance = await WebAssembly.instantiate(compiledModule, importObject);
     // this.wasmInstance.exports.memory.grow(1024 * 4);
 
-    const filterParamsBufPtr = this.wasmInstance.exports.get_filter_params_buf_ptr();
-    const paramsBuf = new Float32Array(this.wasmInstance.exports.memory.buffer).subarray(
-      filterParamsBufPtr / 4,
-      filterParamsBufPtr / 4 + filterParams.length
-    );
-    paramsBuf.set(filterParams);
+    // The band config may have been updated while the module was compiling
+    this.createCtx(this.bandConfig);
+  }
 
-    this.ctxPtr = this.wasmInstance.exports.vocoder_create_ctx();
+  /**
+   * Creates a new vocoder context with the provided band layout, replacing the existing one if
+   * there is one.  Filter and envelope state is reset.
+   *
+   * @param {{ bandCount: number; minFreqHz: number; maxFreqHz: number; bandSpacing: 'log' | 'bark' | 'mel'; filterOrder: number }} bandConfig
+   */
+  createCtx({ bandCount, minFreqHz, maxFreqHz, bandSpacing, filterOrder }) {
+    if (this.ctxPtr) {
+      this.wasmInstance.exports.vocoder_free_ctx(this.ctxPtr);
+    }
+    this.ctxPtr = this.wasmInstance.exports.vocoder_create_ctx(
+      bandCount,
+      minFreqHz,
+      maxFreqHz,
+      BAND_SPACING_IXS[bandSpacing] ?? BAND_SPACING_IXS.log,
+      filterOrder
+    );
     this.wasmMemoryBuffer = new Float32Array(this.wasmInstance.exports.memory.buffer);
+    this.applyBandEnvelopes();
+  }
+
+  /**
+   * Sets the attack and release of each band's envelope follower.  Bands without an entry or with
+   * times <= 0 use defaults based on their center frequency.
+   */
+  applyBandEnvelopes() {
+    const bandCount = this.bandConfig?.bandCount ?? 0;
+    for (let bandIx = 0; bandIx < bandCount; bandIx++) {
+      const envelope = this.bandEnvelopes[bandIx];
+      this.wasmInstance.exports.vocoder_set_band_envelope(
+        this.ctxPtr,
+        bandIx,
+        envelope?.attackMs ?? 0,
+        envelope?.releaseMs ?? 0
+      );
+    }
   }
 
   handleMessage(msg) {
     switch (msg.type) {
       case 'setWasmBytes':
-        const { wasmBytes, filterParams } = msg;
-        this.initWasm(wasmBytes, filterParams);
+        this.bandConfig = msg.bandConfig;
+        this.initWasm(msg.wasmBytes);
+        break;
+      case 'setBandEnvelopes':
+        this.bandEnvelopes = msg.bandEnvelopes;
+        if (this.ctxPtr) {
+          this.applyBandEnvelopes();
+        }
+        break;
+      case 'setBandConfig':
+        this.bandConfig = msg.bandConfig;
+        if (this.wasmInstance) {
+          this.createCtx(this.bandConfig);
+        }
         break;
       default:
         console.warn('Unhandled message type in vocoder AWP: ', msg.type);
@@ -111,8 +172,19 @@ class VocoderAWP extends AudioWorkletProcessor {
     const carrierGain = params.carrier_gain[0];
     const modulatorGain = params.modulator_gain[0];
     const postGain = params.post_gain[0];
-
-    this.wasmInstance.exports.vocoder_process(this.ctxPtr, carrierGain, modulatorGain, postGain);
+    const sibilanceGain = params.sibilance_gain[0];
+    const formantShift = params.formant_shift[0];
+    const freeze = params.freeze[0] > 0.5;
+
+    this.wasmInstance.exports.vocoder_process(
+      this.ctxPtr,
+      carrierGain,
+      modulatorGain,
+      postGain,
+      sibilanceGain,
+      formantShift,
+      freeze
+    );
 
     const outputBufPtr = this.wasmInstance.exports.vocoder_get_output_buf_ptr(this.ctxPtr);
     const outputBuf = this.wasmMemoryBuffer.subarray(
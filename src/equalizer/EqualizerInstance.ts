@@ -15,7 +15,12 @@ import {
 } from 'src/equalizer/conf';
 import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { OverridableAudioNode } from 'src/graphEditor/nodes/util';
-import { EqualizerFilterType, getValidParamsForFilterType } from 'src/equalizer/eqHelpers';
+import {
+  EQ_CHANNEL_MODES,
+  EqualizerFilterType,
+  getChannelModeIx,
+  getValidParamsForFilterType,
+} from 'src/equalizer/eqHelpers';
 import { LineSpectrogram } from 'src/visualizations/LineSpectrogram/LineSpectrogram';
 import { LineSpectrogramFFTSize } from 'src/visualizations/LineSpectrogram/conf';
 import { buildDefaultLineSpecrogramUIState } from 'src/visualizations/LineSpectrogram/types';
@@ -62,6 +67,7 @@ export const buildDefaultEqualizerState = (): EqualizerState => ({
   },
   isBypassed: false,
   animateAutomatedParams: true,
+  linearPhase: false,
 });
 
 /**
@@ -81,6 +87,10 @@ export class EqualizerInstance {
    * Holds the last seen value from the audio thread for each automation slot
    */
   public automationValsSAB: TransparentWritable<Float32Array | null> = rwritable(null);
+  /**
+   * Latency added by the equalizer as reported by the audio thread.  Non-zero in linear-phase mode.
+   */
+  public latencySamples: TransparentWritable<number> = rwritable(0);
   private bandOANs: {
     freq: OverridableAudioNode;
     q: OverridableAudioNode;
@@ -130,9 +140,6 @@ export class EqualizerInstance {
     }
     this.analyzerNode = ctx.createAnalyser();
     this.analyzerNode.fftSize = LineSpectrogramFFTSize;
-    this.analyzerNode.minDecibels = initialState.lineSpectrogramUIState.rangeDb[0];
-    this.analyzerNode.maxDecibels = initialState.lineSpectrogramUIState.rangeDb[1];
-    this.analyzerNode.smoothingTimeConstant = initialState.lineSpectrogramUIState.smoothingCoeff;
     this.lineSpectrogram = isHeadless
       ? null
       : new LineSpectrogram(initialState.lineSpectrogramUIState, this.analyzerNode);
@@ -157,9 +164,10 @@ export class EqualizerInstance {
     this.awpHandle = new AudioWorkletNode(this.ctx, 'equalizer-awp', {
       numberOfInputs: 1,
       numberOfOutputs: 1,
-      channelCount: 1,
-      channelInterpretation: 'discrete',
+      channelCount: 2,
+      channelInterpretation: 'speakers',
       channelCountMode: 'explicit',
+      outputChannelCount: [2],
     });
     this.awpHandle.connect(this.analyzerNode);
 
@@ -284,6 +292,7 @@ export class EqualizerInstance {
 
     return {
       ...band,
+      channelModeIx: getChannelModeIx(band),
       freqAutomationBufIx,
       qAutomationBufIx,
       gainAutomationBufIx,
@@ -303,6 +312,10 @@ export class EqualizerInstance {
           type: 'setInitialState',
           state: { bands: bandsWithAutomationBufIxs },
         });
+        awpHandle.port.postMessage({
+          type: 'setLinearPhase',
+          linearPhase: this.state.current.linearPhase ?? false,
+        });
         this.worker.setInitialState({
           ...this.state.current,
           bands: bandsWithAutomationBufIxs,
@@ -323,6 +336,10 @@ export class EqualizerInstance {
         }
         break;
       }
+      case 'latency': {
+        this.latencySamples.set(evt.data.latencySamples);
+        break;
+      }
       default:
         console.warn('Unknown message type from Equalizer AWP: ', evt.data.type);
     }
@@ -371,8 +388,10 @@ export class EqualizerInstance {
     }
 
     const svg: SVGSVGElement = bgContainer.getElementsByClassName('eq-mag-response-plot')[0] as any;
-    const path: SVGPathElement = svg.getElementsByClassName('eq-mag-response-plot-path')[0] as any;
-    path.setAttribute('d', responses.magResponsePath);
+    for (const channel of EQ_CHANNEL_MODES) {
+      const path = svg.querySelector(`.eq-mag-response-plot-path[data-channel="${channel}"]`);
+      path?.setAttribute('d', responses.magResponsePaths[channel] ?? '');
+    }
   };
 
   public setBand(bandIx: number, newBand: EqualizerBand) {
@@ -481,12 +500,20 @@ export class EqualizerInstance {
     }
   }
 
+  public setLinearPhase(linearPhase: boolean) {
+    this.state.update(state => ({ ...state, linearPhase }));
+    if (this.ready) {
+      (this.awpHandle as AudioWorkletNode).port.postMessage({ type: 'setLinearPhase', linearPhase });
+    }
+  }
+
   public reset = () => {
     const newState = buildDefaultEqualizerState();
     this.state.set(newState);
     this.bandOANs = newState.bands.map((_band, bandIx) => this.buildOANsForBand(bandIx));
     this.automatedParams.set(new Array(EQ_MAX_AUTOMATED_PARAM_COUNT).fill(null));
     this.setBypassed(newState.isBypassed ?? false);
+    this.setLinearPhase(newState.linearPhase ?? false);
     if (this.ready && this.awpHandle instanceof AudioWorkletNode) {
       const encodedState = {
         bands: this.state.current.bands.map((_band, bandIx) => this.buildAWPBandState(bandIx)),
@@ -1,4 +1,5 @@
 import { EQ_Q_DOMAIN, EQ_GAIN_DOMAIN } from 'src/equalizer/conf';
+import type { EqualizerBandDynamics, EqualizerChannelMode } from 'src/equalizer/equalizer';
 
 export enum EqualizerFilterType {
   Lowpass = 0,
@@ -46,6 +47,31 @@ export const getValidParamsForFilterType = (
   }
 };
 
+/**
+ * Indices match the values of `ChannelMode` in the Rust code
+ */
+export const EQ_CHANNEL_MODES: EqualizerChannelMode[] = ['stereo', 'left', 'right', 'mid', 'side'];
+
+export const EQ_CHANNEL_MODE_COLORS: Record<EqualizerChannelMode, string> = {
+  stereo: '#cccccccc',
+  left: '#4fc3f7cc',
+  right: '#ef5350cc',
+  mid: '#ffd54fcc',
+  side: '#ba68c8cc',
+};
+
+export const getChannelModeIx = (band: { channelMode?: EqualizerChannelMode }): number =>
+  EQ_CHANNEL_MODES.indexOf(band.channelMode ?? 'stereo');
+
+export const buildDefaultBandDynamics = (): EqualizerBandDynamics => ({
+  thresholdDb: -24,
+  ratio: 3,
+  attackMs: 10,
+  releaseMs: 120,
+  sidechainFrequency: null,
+  sidechainQ: 0,
+});
+
 export const getEqAxes = (
   filterType: EqualizerFilterType
 ): { yParam: 'gain' | 'q'; yDomain: [number, number] } & (
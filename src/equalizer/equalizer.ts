@@ -6,11 +6,40 @@ import { rwritable, type TransparentWritable } from 'src/util';
 import type { EqualizerFilterType } from 'src/equalizer/eqHelpers';
 import { type LineSpectrogramUIState } from 'src/visualizations/LineSpectrogram/types';
 
+/**
+ * Dynamic EQ settings for a band.  The band's gain is reduced by the amount a compressor with
+ * these settings would reduce the level of the detector signal.  Ratios below 1 boost the band
+ * instead.  Only filter types with a gain param support dynamics.
+ */
+export interface EqualizerBandDynamics {
+  thresholdDb: number;
+  ratio: number;
+  attackMs: number;
+  releaseMs: number;
+  /**
+   * If set, the detector is a bandpass at this frequency rather than following the band's own
+   * frequency and Q
+   */
+  sidechainFrequency: number | null;
+  sidechainQ: number;
+}
+
+/**
+ * Which channels of the stereo signal a band applies to.  Mid and side bands convert the signal
+ * to mid/side, filter only that channel, and convert back.
+ */
+export type EqualizerChannelMode = 'stereo' | 'left' | 'right' | 'mid' | 'side';
+
 export interface EqualizerBand {
   filterType: EqualizerFilterType;
   frequency: number;
   q: number;
   gain: number;
+  dynamics?: EqualizerBandDynamics | null;
+  /**
+   * Defaults to 'stereo' if not set
+   */
+  channelMode?: EqualizerChannelMode;
 }
 
 export interface EqualizerState {
@@ -19,6 +48,12 @@ export interface EqualizerState {
   lineSpectrogramUIState: LineSpectrogramUIState;
   isBypassed?: boolean;
   animateAutomatedParams: boolean;
+  /**
+   * If set, the bands are applied as a single linear-phase FIR filter rather than as IIR filters.
+   * This adds latency, which is reported in the UI but not compensated for, so the output is
+   * delayed relative to signals that don't pass through the equalizer.
+   */
+  linearPhase?: boolean;
 }
 
 const EqualizerCtxById = new Map<
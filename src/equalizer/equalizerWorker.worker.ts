@@ -1,12 +1,14 @@
 import * as Comlink from 'comlink';
 
-import type { EqualizerBand, EqualizerState } from 'src/equalizer/equalizer';
+import type { EqualizerBand, EqualizerChannelMode, EqualizerState } from 'src/equalizer/equalizer';
 import d3 from './d3';
 import { EQ_X_DOMAIN, EQ_GAIN_DOMAIN } from 'src/equalizer/conf';
+import { EQ_CHANNEL_MODES } from 'src/equalizer/eqHelpers';
 
 const FRAME_SIZE = 128;
 
 interface EqualizerBandWithAutomationBufIxs extends EqualizerBand {
+  channelModeIx: number;
   freqAutomationBufIx: number | null;
   qAutomationBufIx: number | null;
   gainAutomationBufIx: number | null;
@@ -18,6 +20,7 @@ export class EqualizerWorker {
   private ctxPtr = 0;
   private wasmMemoryBuffer: Float32Array = new Float32Array(0);
   private automationValsSAB: Float32Array | null = null;
+  private bandChannelModes: EqualizerChannelMode[] = [];
 
   private getWasmMemoryBuffer() {
     if (
@@ -60,6 +63,7 @@ export class EqualizerWorker {
 
   public setState = ({ bands }: { bands: EqualizerBandWithAutomationBufIxs[] }) => {
     (this.wasmInstance.exports.equalizer_set_band_count as Function)(this.ctxPtr, bands.length);
+    this.bandChannelModes.length = bands.length;
     for (let bandIx = 0; bandIx < bands.length; bandIx++) {
       const band = bands[bandIx];
       this.setBand(bandIx, band);
@@ -73,11 +77,13 @@ export class EqualizerWorker {
       frequency,
       q,
       gain,
+      channelModeIx,
       freqAutomationBufIx,
       qAutomationBufIx,
       gainAutomationBufIx,
     }: EqualizerBandWithAutomationBufIxs
   ) => {
+    this.bandChannelModes[bandIx] = EQ_CHANNEL_MODES[channelModeIx] ?? 'stereo';
     (this.wasmInstance.exports.equalizer_set_band as Function)(
       this.ctxPtr,
       bandIx,
@@ -99,6 +105,29 @@ export class EqualizerWorker {
         ? gainAutomationBufIx
         : 99999
     );
+    (this.wasmInstance.exports.equalizer_set_band_channel_mode as Function)(
+      this.ctxPtr,
+      bandIx,
+      channelModeIx
+    );
+  };
+
+  /**
+   * Returns the channels that should have their responses plotted.  Left and right are only
+   * plotted separately if some band applies to just one of them; otherwise they're the same and
+   * plotted as a single stereo response.
+   */
+  private getPlottedChannels = (): EqualizerChannelMode[] => {
+    const modes = new Set(this.bandChannelModes);
+    const channels: EqualizerChannelMode[] =
+      modes.has('left') || modes.has('right') ? ['left', 'right'] : ['stereo'];
+    if (modes.has('mid')) {
+      channels.push('mid');
+    }
+    if (modes.has('side')) {
+      channels.push('side');
+    }
+    return channels;
   };
 
   public computeResponses = (
@@ -129,39 +158,46 @@ export class EqualizerWorker {
       }
     }
 
-    (this.wasmInstance.exports.equalizer_compute_responses as Function)(
-      this.ctxPtr,
-      gridSize,
-      animateAutomatedParams
-    );
-
-    const freqsPtr = (this.wasmInstance.exports.equalizer_get_response_freqs_ptr as Function)(
-      this.ctxPtr
-    );
-    const magsPtr = (this.wasmInstance.exports.equalizer_get_response_mags_ptr as Function)(
-      this.ctxPtr
-    );
-    // const phasesPtr = (this.wasmInstance.exports.equalizer_get_response_phases_ptr as Function)(
-    //   this.ctxPtr
-    // );
-
-    // have to re-acquire memory in case computing the responses caused it to grow
-    memory = this.getWasmMemoryBuffer();
-
-    const freqs = new Float32Array(memory.buffer, freqsPtr, gridSize);
-    const mags = new Float32Array(memory.buffer, magsPtr, gridSize);
-    // const phases = new Float32Array(memory.buffer, phasesPtr, gridSize);
-
     const xScale = d3.scaleLog().domain(xDomain).range([0, widthPx]);
     // TODO: Verify if this domain is good for response plot and unify with FFT viz
     const yScale = d3.scaleLinear([heightPx, 0]).domain(yDomain);
-    const magResponsePath = d3
-      .line<number>()
-      .x((_d, i) => xScale(freqs[i]))
-      .y(d => yScale(d))
-      .curve(d3.curveMonotoneX)(mags)!;
 
-    return { magResponsePath };
+    const magResponsePaths: Partial<Record<EqualizerChannelMode, string>> = {};
+    for (const channel of this.getPlottedChannels()) {
+      // the stereo response is the same as the left channel's when there are no left/right bands
+      const channelIx = EQ_CHANNEL_MODES.indexOf(channel === 'stereo' ? 'left' : channel);
+      (this.wasmInstance.exports.equalizer_compute_responses as Function)(
+        this.ctxPtr,
+        gridSize,
+        animateAutomatedParams,
+        channelIx
+      );
+
+      const freqsPtr = (this.wasmInstance.exports.equalizer_get_response_freqs_ptr as Function)(
+        this.ctxPtr
+      );
+      const magsPtr = (this.wasmInstance.exports.equalizer_get_response_mags_ptr as Function)(
+        this.ctxPtr
+      );
+      // const phasesPtr = (this.wasmInstance.exports.equalizer_get_response_phases_ptr as Function)(
+      //   this.ctxPtr
+      // );
+
+      // have to re-acquire memory in case computing the responses caused it to grow
+      memory = this.getWasmMemoryBuffer();
+
+      const freqs = new Float32Array(memory.buffer, freqsPtr, gridSize);
+      const mags = new Float32Array(memory.buffer, magsPtr, gridSize);
+      // const phases = new Float32Array(memory.buffer, phasesPtr, gridSize);
+
+      magResponsePaths[channel] = d3
+        .line<number>()
+        .x((_d, i) => xScale(freqs[i]))
+        .y(d => yScale(d))
+        .curve(d3.curveMonotoneX)(mags)!;
+    }
+
+    return { magResponsePaths };
   };
 }
 
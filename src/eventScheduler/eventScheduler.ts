@@ -41,12 +41,18 @@ let PendingTempoChanges: TempoChange[] | null = null;
 const ctx = new AudioContext();
 let SchedulerHandle: AudioWorkletNode | null = null;
 
+/**
+ * Called when a scheduled event fires.  `eventTime` is the audio context time of the exact sample
+ * the event landed on, which may be slightly ahead of `currentTime` on the main thread.
+ */
+export type ScheduledCallback = (eventTime: number) => void;
+
 let cbIdCounter = 1;
-const RegisteredCbs: Map<number, () => void> = new Map();
+const RegisteredCbs: Map<number, ScheduledCallback> = new Map();
 
 export const getUniqueCBID = () => cbIdCounter++;
 
-const registerCb = (cb: () => void): number => {
+const registerCb = (cb: ScheduledCallback): number => {
   const cbId = cbIdCounter++;
   RegisteredCbs.set(cbId, cb);
   return cbId;
@@ -56,7 +62,7 @@ const registerCb = (cb: () => void): number => {
  * Registers a callback under a pre-allocated ID from `getUniqueCBID`.  Needed when scheduling
  * through paths like `cancelAndRescheduleManyEvents` that take already-built event descriptors.
  */
-export const registerCbWithID = (cbId: number, cb: () => void) => {
+export const registerCbWithID = (cbId: number, cb: ScheduledCallback) => {
   RegisteredCbs.set(cbId, cb);
 };
 
@@ -136,14 +142,14 @@ export const stopAll = () => {
   StopCBs.forEach(cb => cb());
 };
 
-const callCb = (cbId: number) => {
+const callCb = (cbId: number, eventTime: number) => {
   const cb = RegisteredCbs.get(cbId);
   if (!cb) {
     // Cancelled?
     return;
   }
   RegisteredCbs.delete(cbId);
-  cb();
+  cb(eventTime);
 };
 
 export const BookmarkPosBeats: Writable<number | null> = writable(
@@ -230,8 +236,9 @@ export const EventSchedulerInitialized = Promise.all([
       channelCountMode: 'explicit',
     });
     SchedulerHandle.port.onmessage = evt => {
-      if (typeof evt.data === 'number') {
-        callCb(evt.data);
+      if (evt.data.type === 'callback') {
+        const { cbId, frameOffset, frameStartTime } = evt.data;
+        callCb(cbId, frameStartTime + frameOffset / ctx.sampleRate);
       } else if (evt.data.type === 'beatManagerSAB') {
         beatManagerSAB = evt.data.beatManagerSAB;
       } else {
@@ -298,7 +305,7 @@ export const EventSchedulerInitialized = Promise.all([
 /**
  * Schedules `cb` to be run when the global audio context `currentTime` reaches `time`.
  */
-export const scheduleEventTimeAbsolute = (time: number, cb: () => void): number => {
+export const scheduleEventTimeAbsolute = (time: number, cb: ScheduledCallback): number => {
   const cbId = registerCb(cb);
   if (!SchedulerHandle) {
     PendingEvents.push({ type: 'schedule', time, beats: null, payload: { type: 'cbId', cbId } });
@@ -312,7 +319,7 @@ export const scheduleEventTimeAbsolute = (time: number, cb: () => void): number
 /**
  * Schedules `cb` to be run `time` seconds after the time the global beat counter was last started
  */
-export const scheduleEventTimeRelativeToStart = (time: number, cb: () => void): number =>
+export const scheduleEventTimeRelativeToStart = (time: number, cb: ScheduledCallback): number =>
   scheduleEventTimeAbsolute(lastStartTime + time, cb);
 
 /**
@@ -320,7 +327,7 @@ export const scheduleEventTimeRelativeToStart = (time: number, cb: () => void):
  */
 export const scheduleEventTimeRelativeToCurTime = (
   secondsFromNow: number,
-  cb: () => void
+  cb: ScheduledCallback
 ): number => scheduleEventTimeAbsolute(ctx.currentTime + secondsFromNow, cb);
 
 /**
@@ -329,7 +336,7 @@ export const scheduleEventTimeRelativeToCurTime = (
  */
 export const scheduleEventBeats = (
   beats: number,
-  cb: () => void,
+  cb: ScheduledCallback,
   midiEventType?: MIDIEventType
 ): number => {
   const cbId = registerCb(cb);
@@ -380,7 +387,7 @@ export const scheduleMIDIEventBeats = (
 /**
  * @param beatsFromNow When to start the even, in beats, from the current beat
  */
-export const scheduleEventBeatsRelative = (beatsFromNow: number, cb: () => void): number => {
+export const scheduleEventBeatsRelative = (beatsFromNow: number, cb: ScheduledCallback): number => {
   const cbId = registerCb(cb);
   if (!SchedulerHandle) {
     PendingEvents.push({
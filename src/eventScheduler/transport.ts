@@ -8,9 +8,37 @@
 export interface TempoSegment {
   startBeat: number;
   startFrame: number;
+  /** Tempo at `startFrame` */
   bpm: number;
+  /** Change in BPM per frame while inside this segment.  0 or unset for constant-tempo segments. */
+  slope?: number;
 }
 
+/** Floor applied to every tempo, matching `MIN_BPM` in the Rust event scheduler's tempo map. */
+const MIN_BPM = 0.01;
+
+/**
+ * Beats elapsed `frames` frames after the start of `seg`.  Before the start of the segment, the
+ * starting tempo is extended backwards as a constant.
+ */
+const beatsAfter = (seg: TempoSegment, frames: number, sampleRate: number): number => {
+  const slope = frames > 0 ? seg.slope ?? 0 : 0;
+  return (seg.bpm * frames + 0.5 * slope * frames * frames) / (60 * sampleRate);
+};
+
+/** Frames needed to advance `beats` beats from the start of `seg`. */
+const framesFor = (seg: TempoSegment, beats: number, sampleRate: number): number => {
+  if (beats <= 0) {
+    return (beats * 60 * sampleRate) / seg.bpm;
+  }
+
+  // Solves `slope/2 * f^2 + bpm * f - 60 * sampleRate * beats = 0` for `f` in the same
+  // cancellation-free form used by the Rust tempo map
+  const slope = seg.slope ?? 0;
+  const discriminant = seg.bpm * seg.bpm + 120 * sampleRate * slope * beats;
+  return (120 * sampleRate * beats) / (seg.bpm + Math.sqrt(Math.max(discriminant, 0)));
+};
+
 /**
  * Composition-level tempo definition: tempo becomes `bpm` at absolute `beat`.  This is the
  * canonical, frame-agnostic form authored on the UI side; the clock owner anchors it to actual
@@ -19,6 +47,12 @@ export interface TempoSegment {
 export interface TempoChange {
   beat: number;
   bpm: number;
+  /**
+   * If set, tempo ramps linearly (in time) from the previous change's tempo and arrives at `bpm`
+   * at `beat`.  Integrated identically here and in the Rust event scheduler's tempo map so that
+   * both agree on the beat position throughout the ramp.
+   */
+  ramp?: boolean;
 }
 
 /**
@@ -59,28 +93,37 @@ export class TempoMap {
     const sorted = [...changes].sort((a, b) => a.beat - b.beat);
 
     // Provisional frames with the first change at frame 0, then shift so the anchor lands on `anchorFrame`.
-    const provFrames = new Array<number>(sorted.length);
-    provFrames[0] = 0;
-    for (let i = 1; i < sorted.length; i++) {
-      const prev = sorted[i - 1];
-      provFrames[i] = provFrames[i - 1] + ((sorted[i].beat - prev.beat) * 60 * sampleRate) / prev.bpm;
-    }
+    const segments: TempoSegment[] = [];
+    for (let i = 0; i < sorted.length; i++) {
+      const change = sorted[i];
+      const bpm = Math.max(change.bpm, MIN_BPM);
+      if (i === 0) {
+        segments.push({ startBeat: change.beat, startFrame: 0, bpm, slope: 0 });
+        continue;
+      }
 
-    let k = 0;
-    for (let i = sorted.length - 1; i > 0; i--) {
-      if (anchorBeat >= sorted[i].beat) {
-        k = i;
-        break;
+      const prev = segments[i - 1];
+      const beatCount = change.beat - prev.startBeat;
+      let duration: number;
+      if (change.ramp && beatCount > 0) {
+        // Average tempo across a linear ramp is the mean of its endpoints
+        duration = (beatCount * 120 * sampleRate) / (prev.bpm + bpm);
+        prev.slope = (bpm - prev.bpm) / duration;
+      } else {
+        duration = framesFor(prev, beatCount, sampleRate);
       }
+      segments.push({
+        startBeat: change.beat,
+        startFrame: prev.startFrame + duration,
+        bpm,
+        slope: 0,
+      });
+    }
+
+    const delta = anchorFrame - new TempoMap(sampleRate, segments).frameAt(anchorBeat);
+    for (const seg of segments) {
+      seg.startFrame += delta;
     }
-    const provAnchorFrame = provFrames[k] + ((anchorBeat - sorted[k].beat) * 60 * sampleRate) / sorted[k].bpm;
-    const delta = anchorFrame - provAnchorFrame;
-
-    const segments = sorted.map((c, i) => ({
-      startBeat: c.beat,
-      startFrame: provFrames[i] + delta,
-      bpm: c.bpm,
-    }));
     return new TempoMap(sampleRate, segments);
   }
 
@@ -106,16 +149,21 @@ export class TempoMap {
 
   beatAt(frame: number): number {
     const seg = this.segAtFrame(frame);
-    return seg.startBeat + ((frame - seg.startFrame) * seg.bpm) / (60 * this.sampleRate);
+    return seg.startBeat + beatsAfter(seg, frame - seg.startFrame, this.sampleRate);
   }
 
   frameAt(beat: number): number {
     const seg = this.segAtBeat(beat);
-    return seg.startFrame + ((beat - seg.startBeat) * 60 * this.sampleRate) / seg.bpm;
+    return seg.startFrame + framesFor(seg, beat - seg.startBeat, this.sampleRate);
   }
 
   bpmAt(frame: number): number {
-    return this.segAtFrame(frame).bpm;
+    const seg = this.segAtFrame(frame);
+    const frames = frame - seg.startFrame;
+    if (frames <= 0) {
+      return seg.bpm;
+    }
+    return Math.max(seg.bpm + (seg.slope ?? 0) * frames, MIN_BPM);
   }
 
   /**
@@ -125,7 +173,10 @@ export class TempoMap {
   withManualChange(atFrame: number, bpm: number): TempoMap {
     const startBeat = this.beatAt(atFrame);
     const kept = this.segments.filter(s => s.startFrame < atFrame);
-    return new TempoMap(this.sampleRate, [...kept, { startBeat, startFrame: atFrame, bpm }]);
+    return new TempoMap(this.sampleRate, [
+      ...kept,
+      { startBeat, startFrame: atFrame, bpm, slope: 0 },
+    ]);
   }
 }
 
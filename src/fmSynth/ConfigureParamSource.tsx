@@ -4,6 +4,8 @@ import ControlPanel from 'react-control-panel';
 
 import type { AdsrChangeHandler } from 'src/fmSynth/ConfigureEffects';
 import {
+  applyAdsrNoteScalingChange,
+  buildAdsrNoteScalingControlPanelState,
   buildConfigureParamSourceSettings,
   buildDefaultParamSource,
   type ParamSource,
@@ -214,6 +216,7 @@ const ConfigureParamSourceInnerInner: React.FC<ConfigureParamSourceInnerProps> =
                 : undefined,
             adsr: adsr ? adsr : undefined,
             'log scale': adsr ? (adsr.logScale ?? false) : undefined,
+            ...(adsr ? buildAdsrNoteScalingControlPanelState(adsr.noteScaling) : {}),
             range: state.type === 'random' ? [state.min, state.max] : undefined,
             'update interval samples':
               state.type === 'random' ? state.updateIntervalSamples : undefined,
@@ -294,6 +297,19 @@ const ConfigureParamSourceInnerInner: React.FC<ConfigureParamSourceInnerProps> =
               break;
             }
             default: {
+              if (state.type === 'adsr') {
+                const adsrIx = state['adsr index'];
+                const noteScaling = applyAdsrNoteScalingChange(
+                  adsrs[adsrIx]?.noteScaling,
+                  key,
+                  value
+                );
+                if (noteScaling) {
+                  onAdsrChange(adsrIx, { ...adsrs[adsrIx], noteScaling });
+                  break;
+                }
+              }
+
               console.error('Unhandled param value configurator key: ', key);
             }
           }
@@ -349,6 +365,7 @@ const ConfigureParamSourceInnerInner: React.FC<ConfigureParamSourceInnerProps> =
                 onAdsrChange(adsrIx, {
                   ...newAdsr,
                   lenSamples: adsrs[state['adsr index']].lenSamples,
+                  noteScaling: adsrs[state['adsr index']].noteScaling,
                 });
               }}
               vcId={vcId}
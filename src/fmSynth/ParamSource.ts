@@ -1,6 +1,10 @@
 import type { AudioThreadData } from 'src/controls/adsr2/adsr2';
 import type { ConfigureParamSourceInnerProps } from 'src/fmSynth/ConfigureParamSource';
 import type { AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth';
+import {
+  buildDefaultAdsrNoteScaling,
+  type AdsrNoteScaling,
+} from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import { MIDINode, type MIDIInputCbs } from 'src/patchNetwork/midiNode';
 import { UnimplementedError, UnreachableError, filterNils } from 'src/util';
 
@@ -231,6 +235,54 @@ export const buildDefaultAdsr = (audioThreadData?: AudioThreadData): AdsrParams
   logScale: false,
 });
 
+/**
+ * Control panel settings for the velocity and key scaling of an ADSR, each tagged with the field of
+ * `AdsrNoteScaling` that it controls
+ */
+export const ADSR_NOTE_SCALING_SETTINGS: {
+  type: 'range';
+  label: string;
+  min: number;
+  max: number;
+  step?: number;
+  field: keyof AdsrNoteScaling;
+}[] = [
+  { type: 'range', label: 'velocity to level', min: 0, max: 1, field: 'velocityToLevel' },
+  { type: 'range', label: 'velocity to attack', min: 0, max: 1, field: 'velocityToAttackTime' },
+  { type: 'range', label: 'key tracking', min: -1, max: 1, field: 'keyTracking' },
+  {
+    type: 'range',
+    label: 'key tracking center',
+    min: 0,
+    max: 127,
+    step: 1,
+    field: 'keyTrackingCenter',
+  },
+];
+
+export const buildAdsrNoteScalingControlPanelState = (noteScaling: AdsrNoteScaling | undefined) => {
+  const resolved = noteScaling ?? buildDefaultAdsrNoteScaling();
+  return Object.fromEntries(
+    ADSR_NOTE_SCALING_SETTINGS.map(setting => [setting.label, resolved[setting.field]])
+  );
+};
+
+/**
+ * Returns the updated note scaling if `key` is the label of one of `ADSR_NOTE_SCALING_SETTINGS` or
+ * `null` otherwise
+ */
+export const applyAdsrNoteScalingChange = (
+  noteScaling: AdsrNoteScaling | undefined,
+  key: string,
+  value: number
+): AdsrNoteScaling | null => {
+  const setting = ADSR_NOTE_SCALING_SETTINGS.find(setting => setting.label === key);
+  if (!setting) {
+    return null;
+  }
+  return { ...(noteScaling ?? buildDefaultAdsrNoteScaling()), [setting.field]: value };
+};
+
 interface BuildConfigureParamSourceSettingsArgs extends Pick<
   ConfigureParamSourceInnerProps,
   | 'state'
@@ -289,6 +341,7 @@ export const buildConfigureParamSourceSettings = ({
           type: 'checkbox',
           label: 'log scale',
         },
+        ...ADSR_NOTE_SCALING_SETTINGS,
         {
           type: 'button',
           label: 'add envelope generator',
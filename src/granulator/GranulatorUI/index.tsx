@@ -4,7 +4,19 @@ import ControlPanel from 'react-control-panel';
 
 import './Granulator.css';
 
-import { type GranulatorInstance, GranulatorInstancesById } from 'src/granulator/granulator';
+import {
+  buildDefaultGranulatorVoiceParams,
+  GRANULATOR_MAX_VOICE_COUNT,
+  GranulatorGrainWindow,
+  type GranulatorInstance,
+  GranulatorInstancesById,
+  GranulatorReadHeadMode,
+  setGranulatorLiveInput,
+  setGranulatorPlaybackSettings,
+  setGranulatorSamples,
+  setGranulatorVoiceParams,
+  setGranulatorVoices,
+} from 'src/granulator/granulator';
 import SampleEditor from 'src/granulator/GranulatorUI/SampleEditor';
 import SampleRecorder from 'src/granulator/GranulatorUI/SampleRecorder';
 import type { WaveformRenderer } from 'src/granulator/GranulatorUI/WaveformRenderer';
@@ -144,6 +156,270 @@ const GranularControlPanel: React.FC<GranularControlPanelProps> = ({ initialStat
   );
 };
 
+const READ_HEAD_MODE_NAMES: Record<GranulatorReadHeadMode, string> = {
+  [GranulatorReadHeadMode.Forward]: 'forward',
+  [GranulatorReadHeadMode.Reverse]: 'reverse',
+  [GranulatorReadHeadMode.PingPong]: 'ping-pong',
+};
+
+const READ_HEAD_MODE_BY_NAME: Record<string, GranulatorReadHeadMode> = {
+  forward: GranulatorReadHeadMode.Forward,
+  reverse: GranulatorReadHeadMode.Reverse,
+  'ping-pong': GranulatorReadHeadMode.PingPong,
+};
+
+interface GranularVoicesControlPanelProps {
+  inst: GranulatorInstance;
+}
+
+const GranularVoicesControlPanel: React.FC<GranularVoicesControlPanelProps> = ({ inst }) => {
+  const [voices, setVoices] = useState(inst.voices);
+  const [editedVoiceIx, setEditedVoiceIx] = useState(0);
+
+  const settings = useMemo(
+    () => [
+      { label: 'voice_count', type: 'range', min: 1, max: GRANULATOR_MAX_VOICE_COUNT, step: 1 },
+      {
+        label: 'edited_voice',
+        type: 'select',
+        options: voices.map((_voice, voiceIx) => `${voiceIx + 1}`),
+      },
+      { label: 'movement_speed', type: 'range', min: 0, max: 4 },
+      { label: 'spread', type: 'range', min: 0, max: 1, step: 0.01 },
+      { label: 'pan', type: 'range', min: -1, max: 1, step: 0.01 },
+      { label: 'read_head_mode', type: 'select', options: Object.keys(READ_HEAD_MODE_BY_NAME) },
+      { label: 'grain_reverse_probability', type: 'range', min: 0, max: 1, step: 0.01 },
+    ],
+    [voices]
+  );
+
+  const editedVoice = voices[editedVoiceIx];
+  const state = useMemo(
+    () => ({
+      voice_count: voices.length,
+      edited_voice: `${editedVoiceIx + 1}`,
+      movement_speed: editedVoice.movementSpeed,
+      spread: editedVoice.spread,
+      pan: editedVoice.pan,
+      read_head_mode: READ_HEAD_MODE_NAMES[editedVoice.readHeadMode],
+      grain_reverse_probability: editedVoice.grainReverseProbability,
+    }),
+    [editedVoice, editedVoiceIx, voices.length]
+  );
+
+  const onChange = useCallback(
+    (key: string, value: any) => {
+      switch (key) {
+        case 'voice_count': {
+          const newVoices = voices.slice(0, value);
+          while (newVoices.length < value) {
+            newVoices.push(buildDefaultGranulatorVoiceParams());
+          }
+          setGranulatorVoices(inst, newVoices);
+          setVoices(newVoices);
+          setEditedVoiceIx(Math.min(editedVoiceIx, newVoices.length - 1));
+          return;
+        }
+        case 'edited_voice': {
+          setEditedVoiceIx(+value - 1);
+          return;
+        }
+      }
+
+      const newParams = { ...editedVoice };
+      switch (key) {
+        case 'movement_speed': {
+          newParams.movementSpeed = value;
+          break;
+        }
+        case 'spread': {
+          newParams.spread = value;
+          break;
+        }
+        case 'pan': {
+          newParams.pan = value;
+          break;
+        }
+        case 'read_head_mode': {
+          newParams.readHeadMode = READ_HEAD_MODE_BY_NAME[value];
+          break;
+        }
+        case 'grain_reverse_probability': {
+          newParams.grainReverseProbability = value;
+          break;
+        }
+        default: {
+          console.error(`Unhandled key in granular voices control panel: "${key}"`);
+          return;
+        }
+      }
+
+      setGranulatorVoiceParams(inst, editedVoiceIx, newParams);
+      setVoices([...inst.voices]);
+    },
+    [editedVoice, editedVoiceIx, inst, voices]
+  );
+
+  return (
+    <ControlPanel
+      title='voices'
+      style={GRANULATOR_CONTROL_PANEL_STYLE}
+      settings={settings}
+      state={state}
+      onChange={onChange}
+    />
+  );
+};
+
+const GRAIN_WINDOW_BY_NAME: Record<string, GranulatorGrainWindow> = {
+  'linear/sine': GranulatorGrainWindow.LinearSine,
+  hann: GranulatorGrainWindow.Hann,
+  tukey: GranulatorGrainWindow.Tukey,
+  trapezoid: GranulatorGrainWindow.Trapezoid,
+};
+
+const GRANULAR_PLAYBACK_SETTINGS = [
+  { label: 'midi_mode', type: 'checkbox' },
+  { label: 'root_key', type: 'range', min: 0, max: 127, step: 1 },
+  { label: 'pitch_shift_semitones', type: 'range', min: -24, max: 24, step: 0.1 },
+  { label: 'pitch_randomization_semitones', type: 'range', min: 0, max: 12, step: 0.1 },
+  { label: 'grain_window', type: 'select', options: Object.keys(GRAIN_WINDOW_BY_NAME) },
+];
+
+interface GranularPlaybackControlPanelProps {
+  inst: GranulatorInstance;
+}
+
+const GranularPlaybackControlPanel: React.FC<GranularPlaybackControlPanelProps> = ({ inst }) => {
+  const [playback, setPlayback] = useState(inst.playback);
+
+  const state = useMemo(
+    () => ({
+      midi_mode: playback.midiMode,
+      root_key: playback.rootKey,
+      pitch_shift_semitones: playback.pitchShiftSemitones,
+      pitch_randomization_semitones: playback.pitchRandomizationSemitones,
+      grain_window: Object.keys(GRAIN_WINDOW_BY_NAME).find(
+        name => GRAIN_WINDOW_BY_NAME[name] === playback.window
+      ),
+    }),
+    [playback]
+  );
+
+  const onChange = useCallback(
+    (key: string, value: any) => {
+      const newPlayback = { ...playback };
+      switch (key) {
+        case 'midi_mode': {
+          newPlayback.midiMode = value;
+          break;
+        }
+        case 'root_key': {
+          newPlayback.rootKey = value;
+          break;
+        }
+        case 'pitch_shift_semitones': {
+          newPlayback.pitchShiftSemitones = value;
+          break;
+        }
+        case 'pitch_randomization_semitones': {
+          newPlayback.pitchRandomizationSemitones = value;
+          break;
+        }
+        case 'grain_window': {
+          newPlayback.window = GRAIN_WINDOW_BY_NAME[value];
+          break;
+        }
+        default: {
+          console.error(`Unhandled key in granular playback control panel: "${key}"`);
+          return;
+        }
+      }
+
+      setGranulatorPlaybackSettings(inst, newPlayback);
+      setPlayback(newPlayback);
+    },
+    [inst, playback]
+  );
+
+  return (
+    <ControlPanel
+      title='playback'
+      style={GRANULATOR_CONTROL_PANEL_STYLE}
+      settings={GRANULAR_PLAYBACK_SETTINGS}
+      state={state}
+      onChange={onChange}
+    />
+  );
+};
+
+const GRANULAR_LIVE_INPUT_SETTINGS = [
+  { label: 'source', type: 'select', options: ['sample', 'live input'] },
+  { label: 'buffer_length_seconds', type: 'range', min: 0.5, max: 30, step: 0.1 },
+  { label: 'frozen', type: 'checkbox' },
+  { label: 'feedback', type: 'range', min: 0, max: 0.99, step: 0.01 },
+];
+
+interface GranularLiveInputControlPanelProps {
+  inst: GranulatorInstance;
+}
+
+const GranularLiveInputControlPanel: React.FC<GranularLiveInputControlPanelProps> = ({ inst }) => {
+  const [liveInput, setLiveInput] = useState(inst.liveInput);
+
+  const state = useMemo(
+    () => ({
+      source: liveInput.enabled ? 'live input' : 'sample',
+      buffer_length_seconds: liveInput.bufferLenSeconds,
+      frozen: liveInput.frozen,
+      feedback: liveInput.feedback,
+    }),
+    [liveInput]
+  );
+
+  const onChange = useCallback(
+    (key: string, value: any) => {
+      const newLiveInput = { ...liveInput };
+      switch (key) {
+        case 'source': {
+          newLiveInput.enabled = value === 'live input';
+          break;
+        }
+        case 'buffer_length_seconds': {
+          newLiveInput.bufferLenSeconds = value;
+          break;
+        }
+        case 'frozen': {
+          newLiveInput.frozen = value;
+          break;
+        }
+        case 'feedback': {
+          newLiveInput.feedback = value;
+          break;
+        }
+        default: {
+          console.error(`Unhandled key in granular live input control panel: "${key}"`);
+          return;
+        }
+      }
+
+      setGranulatorLiveInput(inst, newLiveInput);
+      setLiveInput(newLiveInput);
+    },
+    [inst, liveInput]
+  );
+
+  return (
+    <ControlPanel
+      title='live input'
+      style={GRANULATOR_CONTROL_PANEL_STYLE}
+      settings={GRANULAR_LIVE_INPUT_SETTINGS}
+      state={state}
+      onChange={onChange}
+    />
+  );
+};
+
 const msToSamples = (ms: number | null, sampleRate: number): number | null => {
   if (ms === null) {
     return null;
@@ -172,14 +448,11 @@ const GranulatorUI: React.FC<GranulatorUIProps> = ({
 }) => {
   const [activeSample, setActiveSample] = useState<ActiveSample | null>(null);
   const inst = useMappedWritableValue(GranulatorInstancesById, map => map.get(vcId));
-  useEffect(
-    () =>
-      void inst?.node.port.postMessage({
-        type: 'setSamples',
-        samples: activeSample?.sampleData.getChannelData(0),
-      }),
-    [inst, activeSample, vcId]
-  );
+  useEffect(() => {
+    if (inst) {
+      setGranulatorSamples(inst, activeSample?.sampleData.getChannelData(0) ?? null);
+    }
+  }, [inst, activeSample]);
 
   useEffect(() => {
     if (activeSample) {
@@ -272,6 +545,9 @@ const GranulatorUI: React.FC<GranulatorUIProps> = ({
       </div>
 
       <GranularControlPanel initialState={initialState} inst={inst} />
+      {inst ? <GranularVoicesControlPanel inst={inst} /> : null}
+      {inst ? <GranularPlaybackControlPanel inst={inst} /> : null}
+      {inst ? <GranularLiveInputControlPanel inst={inst} /> : null}
 
       {activeSample ? <SampleEditor waveformRenderer={waveformRenderer} /> : null}
 
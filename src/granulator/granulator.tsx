@@ -13,6 +13,7 @@ import { OverridableAudioParam } from 'src/graphEditor/nodes/util';
 import Loading from 'src/misc/Loading';
 import type { AudioConnectables, ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
 import { updateConnectables } from 'src/patchNetwork/interface';
+import { MIDINode } from 'src/patchNetwork/midiNode';
 import {
   mkContainerCleanupHelper,
   mkContainerHider,
@@ -35,6 +36,112 @@ const GranulatorAWPRegistered = new AsyncOnce(
   true
 );
 
+/**
+ * Mirrors the max voice count of the Wasm engine
+ */
+export const GRANULATOR_MAX_VOICE_COUNT = 16;
+
+/**
+ * Corresponds to `ReadHeadMode` in the Wasm engine
+ */
+export enum GranulatorReadHeadMode {
+  Forward = 0,
+  Reverse = 1,
+  /**
+   * Moves forward until the end of the selection and then backwards until the start, repeating
+   */
+  PingPong = 2,
+}
+
+export interface GranulatorVoiceParams {
+  /**
+   * Multiplier applied to the read head movement speed for this voice
+   */
+  movementSpeed: number;
+  /**
+   * Random scatter applied to the start position of grains as a fraction of the selection length
+   */
+  spread: number;
+  /**
+   * -1 is hard left, 1 is hard right
+   */
+  pan: number;
+  readHeadMode: GranulatorReadHeadMode;
+  /**
+   * Probability from 0 to 1 that each grain plays its samples backwards
+   */
+  grainReverseProbability: number;
+}
+
+export const buildDefaultGranulatorVoiceParams = (): GranulatorVoiceParams => ({
+  movementSpeed: 1,
+  spread: 0,
+  pan: 0,
+  readHeadMode: GranulatorReadHeadMode.Forward,
+  grainReverseProbability: 0,
+});
+
+/**
+ * Corresponds to `GrainWindow` in the Wasm engine
+ */
+export enum GranulatorGrainWindow {
+  /**
+   * Mix between a linear slope and a sine controlled by `linear_slope_length` and
+   * `slope_linearity`
+   */
+  LinearSine = 0,
+  Hann = 1,
+  Tukey = 2,
+  Trapezoid = 3,
+}
+
+export interface GranulatorPlaybackSettings {
+  /**
+   * If enabled, notes received by the MIDI input spawn grain clouds transposed relative to
+   * `rootKey` instead of the voices playing continuously
+   */
+  midiMode: boolean;
+  rootKey: number;
+  pitchShiftSemitones: number;
+  /**
+   * Max amount by which each grain's pitch is randomly shifted up or down
+   */
+  pitchRandomizationSemitones: number;
+  window: GranulatorGrainWindow;
+}
+
+export const buildDefaultGranulatorPlaybackSettings = (): GranulatorPlaybackSettings => ({
+  midiMode: false,
+  rootKey: 60,
+  pitchShiftSemitones: 0,
+  pitchRandomizationSemitones: 0,
+  window: GranulatorGrainWindow.LinearSine,
+});
+
+export interface GranulatorLiveInputSettings {
+  /**
+   * If enabled, grains are read out of a circular buffer that audio from the recording input is
+   * continuously written into instead of the selected sample
+   */
+  enabled: boolean;
+  bufferLenSeconds: number;
+  /**
+   * Stops writing new input into the buffer so that the current contents can be granulated
+   */
+  frozen: boolean;
+  /**
+   * Amount of the existing buffer contents mixed back in when writing new input, from 0 to 0.99
+   */
+  feedback: number;
+}
+
+export const buildDefaultGranulatorLiveInputSettings = (): GranulatorLiveInputSettings => ({
+  enabled: false,
+  bufferLenSeconds: 4,
+  frozen: false,
+  feedback: 0,
+});
+
 export interface GranulatorInstance {
   node: AudioWorkletNode;
   startSample: OverridableAudioParam;
@@ -48,8 +155,109 @@ export interface GranulatorInstance {
   movementSamplesPerSample: OverridableAudioParam;
   selectedSample: SampleDescriptor | null;
   waveformRenderer: WaveformRenderer;
+  /**
+   * Notes received here spawn grain clouds transposed relative to the root key when MIDI mode is
+   * enabled in `playback`
+   */
+  midiNode: MIDINode;
+  playback: GranulatorPlaybackSettings;
+  liveInput: GranulatorLiveInputSettings;
+  /**
+   * Samples of the selected sample's first channel, kept so that they can be re-sent to the AWP
+   * when switching out of live input mode
+   */
+  samples: Float32Array | null;
+  /**
+   * Params for each voice; the number of entries is the voice count
+   */
+  voices: GranulatorVoiceParams[];
 }
 
+export const setGranulatorVoiceParams = (
+  inst: GranulatorInstance,
+  voiceIx: number,
+  params: GranulatorVoiceParams
+) => {
+  inst.voices[voiceIx] = params;
+  inst.node.port.postMessage({ type: 'setVoiceParams', voiceIx, ...params });
+};
+
+export const setGranulatorPlaybackSettings = (
+  inst: GranulatorInstance,
+  playback: GranulatorPlaybackSettings
+) => {
+  inst.playback = playback;
+  inst.node.port.postMessage({
+    type: 'setMIDIMode',
+    enabled: playback.midiMode,
+    rootKey: playback.rootKey,
+  });
+  inst.node.port.postMessage({
+    type: 'setPitch',
+    pitchShiftSemitones: playback.pitchShiftSemitones,
+    pitchRandomizationSemitones: playback.pitchRandomizationSemitones,
+  });
+  inst.node.port.postMessage({ type: 'setWindow', window: playback.window });
+};
+
+export const setGranulatorSamples = (inst: GranulatorInstance, samples: Float32Array | null) => {
+  inst.samples = samples;
+  if (!inst.liveInput.enabled) {
+    inst.node.port.postMessage({ type: 'setSamples', samples });
+  }
+};
+
+const postGranulatorLiveInput = (inst: GranulatorInstance) => {
+  const { bufferLenSeconds, frozen, feedback } = inst.liveInput;
+  inst.node.port.postMessage({
+    type: 'enableLiveInput',
+    bufferLenSamples: Math.round(bufferLenSeconds * ctx.sampleRate),
+  });
+  inst.node.port.postMessage({ type: 'setLiveInputFrozen', frozen });
+  inst.node.port.postMessage({ type: 'setLiveInputFeedback', feedback });
+};
+
+/**
+ * Enabling live input or changing the buffer length re-creates the buffer, clearing it.  Disabling
+ * it switches back to granulating the selected sample.
+ */
+export const setGranulatorLiveInput = (
+  inst: GranulatorInstance,
+  liveInput: GranulatorLiveInputSettings
+) => {
+  const prevLiveInput = inst.liveInput;
+  inst.liveInput = liveInput;
+
+  if (!liveInput.enabled) {
+    if (prevLiveInput.enabled) {
+      inst.node.port.postMessage({ type: 'setSamples', samples: inst.samples });
+    }
+    return;
+  }
+
+  if (!prevLiveInput.enabled || prevLiveInput.bufferLenSeconds !== liveInput.bufferLenSeconds) {
+    postGranulatorLiveInput(inst);
+    return;
+  }
+
+  if (prevLiveInput.frozen !== liveInput.frozen) {
+    inst.node.port.postMessage({ type: 'setLiveInputFrozen', frozen: liveInput.frozen });
+  }
+  if (prevLiveInput.feedback !== liveInput.feedback) {
+    inst.node.port.postMessage({ type: 'setLiveInputFeedback', feedback: liveInput.feedback });
+  }
+};
+
+/**
+ * Voices added by the engine start out with default params, so all voices' params are re-sent
+ * after the count changes.
+ */
+export const setGranulatorVoices = (inst: GranulatorInstance, voices: GranulatorVoiceParams[]) => {
+  inst.voices = voices;
+  inst.node.port.postMessage({ type: 'setVoiceCount', voiceCount: voices.length });
+  voices.forEach((params, voiceIx) => setGranulatorVoiceParams(inst, voiceIx, params));
+};
+
 export const GranulatorInstancesById = writable(ImmMap<string, GranulatorInstance>());
 
 const GranulatorUI = React.lazy(() => import('./GranulatorUI'));
@@ -61,6 +269,9 @@ interface SerializedGranulator {
   selectedSample: SampleDescriptor | null;
   startSample: number | null;
   endSample: number | null;
+  voices: GranulatorVoiceParams[];
+  playback: GranulatorPlaybackSettings;
+  liveInput: GranulatorLiveInputSettings;
 }
 
 const serializeGranulator = (vcId: string): string => {
@@ -82,6 +293,9 @@ const serializeGranulator = (vcId: string): string => {
     selectedSample: inst.selectedSample,
     startSample: inst.startSample.manualControl.offset.value,
     endSample: inst.endSample.manualControl.offset.value,
+    voices: inst.voices,
+    playback: inst.playback,
+    liveInput: inst.liveInput,
   };
 
   return JSON.stringify(serialized);
@@ -100,6 +314,9 @@ const buildDefaultGranulatorState = (): SerializedGranulator => ({
   selectedSample: null,
   startSample: null,
   endSample: null,
+  voices: [buildDefaultGranulatorVoiceParams()],
+  playback: buildDefaultGranulatorPlaybackSettings(),
+  liveInput: buildDefaultGranulatorLiveInputSettings(),
 });
 
 // Maps the old per-voice control keys onto the single-voice keys, falling back to defaults for
@@ -132,6 +349,14 @@ const deserializeGranulator = (stateKey: string, serialized: string): Serialized
     return {
       ...deserialized,
       controlPanelState: migrateControlPanelState(deserialized.controlPanelState),
+      voices: deserialized.voices?.length
+        ? deserialized.voices.map((voice: Partial<GranulatorVoiceParams>) => ({
+            ...buildDefaultGranulatorVoiceParams(),
+            ...voice,
+          }))
+        : [buildDefaultGranulatorVoiceParams()],
+      playback: { ...buildDefaultGranulatorPlaybackSettings(), ...deserialized.playback },
+      liveInput: { ...buildDefaultGranulatorLiveInputSettings(), ...deserialized.liveInput },
     };
   } catch (err) {
     console.warn('Error deserializing granulator state: ', err);
@@ -158,7 +383,8 @@ export const build_granulator_audio_connectables = (vcId: string): AudioConnecta
         .set('filter cutoff', { type: 'number', node: new DummyNode() })
         .set('sample speed ratio', { type: 'number', node: new DummyNode() })
         .set('playhead movement speed ratio', { type: 'number', node: new DummyNode() })
-        .set('recording_input', { type: 'customAudio', node: new DummyNode() }),
+        .set('recording_input', { type: 'customAudio', node: new DummyNode() })
+        .set('midi', { type: 'midi', node: new MIDINode() }),
       outputs: ImmMap<string, ConnectableOutput>().set('output', {
         type: 'customAudio',
         node: new DummyNode(),
@@ -177,7 +403,8 @@ export const build_granulator_audio_connectables = (vcId: string): AudioConnecta
         type: 'number',
         node: inst.movementSamplesPerSample,
       })
-      .set('recording_input', { type: 'customAudio', node: inst.node }),
+      .set('recording_input', { type: 'customAudio', node: inst.node })
+      .set('midi', { type: 'midi', node: inst.midiNode }),
     outputs: ImmMap<string, ConnectableOutput>().set('output', {
       type: 'customAudio',
       node: inst.node,
@@ -206,11 +433,15 @@ export const init_granulator = async (stateKey: string) => {
   const granularWasmPromise = GranularWasm.get();
   const waveformRenderer = new WaveformRenderer();
   GranulatorAWPRegistered.get().then(async () => {
+    // Input is only used for recording and live input, which are mono.  The speakers
+    // interpretation downmixes stereo sources rather than dropping their right channel.  Output is
+    // stereo so that panned voices are audible on both sides.
     const node = new AudioWorkletNode(ctx, 'granulator-audio-worklet-processor', {
       channelCount: 1,
       numberOfInputs: 1,
       numberOfOutputs: 1,
-      channelInterpretation: 'discrete',
+      outputChannelCount: [2],
+      channelInterpretation: 'speakers',
       channelCountMode: 'explicit',
     });
 
@@ -231,6 +462,16 @@ export const init_granulator = async (stateKey: string) => {
       ),
       selectedSample: initialState.selectedSample,
       waveformRenderer,
+      midiNode: new MIDINode(() => ({
+        onAttack: (midiNumber: number) => node.port.postMessage({ type: 'noteOn', midiNumber }),
+        onRelease: (midiNumber: number) => node.port.postMessage({ type: 'noteOff', midiNumber }),
+        onPitchBend: () => {},
+        onClearAll: () => node.port.postMessage({ type: 'releaseAllNotes' }),
+      })),
+      voices: initialState.voices,
+      playback: initialState.playback,
+      liveInput: initialState.liveInput,
+      samples: null,
     };
     if (initialState.startSample !== null) {
       inst.startSample.manualControl.offset.value = initialState.startSample;
@@ -247,6 +488,11 @@ export const init_granulator = async (stateKey: string) => {
     // Once we've fetched the Wasm bytes for the granular's DSP instance, we send them to the AWP
     // to be instantiated and start.
     node.port.postMessage({ type: 'setWasmBytes', wasmBytes: granularWasm });
+    setGranulatorVoices(inst, initialState.voices);
+    setGranulatorPlaybackSettings(inst, initialState.playback);
+    if (inst.liveInput.enabled) {
+      postGranulatorLiveInput(inst);
+    }
 
     GranulatorInstancesById.update(map => map.set(vcId, inst));
     updateConnectables(vcId, build_granulator_audio_connectables(vcId));
@@ -303,6 +549,7 @@ export const cleanup_granulator = (stateKey: string) => {
     inst.slopeLinearity.dispose();
     inst.movementSamplesPerSample.dispose();
     inst.waveformRenderer.dispose();
+    inst.midiNode.dispose();
     GranulatorInstancesById.update(map => map.remove(vcId));
   }
 
@@ -21,6 +21,7 @@ import { EnvelopeGenerator } from 'src/graphEditor/nodes/CustomAudio/EnvelopeGen
 import FMSynth from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import { LevelDetectorNode } from 'src/graphEditor/nodes/CustomAudio/LevelDetectorNode/LevelDetectorNode';
 import { LFONode } from 'src/graphEditor/nodes/CustomAudio/LFONode';
+import { LoudnessMeterNode } from 'src/graphEditor/nodes/CustomAudio/LoudnessMeterNode/LoudnessMeterNode';
 import MIDIQuantizerNode from 'src/graphEditor/nodes/CustomAudio/MIDIQuantizer/MIDIQuantizerNode';
 import { MIDIToFrequencyNode } from 'src/graphEditor/nodes/CustomAudio/MIDIToFrequency/MIDIToFrequency';
 import { MixerNode } from 'src/graphEditor/nodes/CustomAudio/mixer/mixer';
@@ -28,6 +29,7 @@ import { MBDLDNode } from 'src/graphEditor/nodes/CustomAudio/MultibandDiodeLadde
 import { MultiplyNode } from 'src/graphEditor/nodes/CustomAudio/MultiplyNode/MultiplyNode';
 import { NativeCompressorSmallViewShim } from 'src/graphEditor/nodes/CustomAudio/NativeCompressor/NativeCompressorSmallViewShim';
 import { NoiseGenNode } from 'src/graphEditor/nodes/CustomAudio/NoiseGen';
+import { PitchTrackerNode } from 'src/graphEditor/nodes/CustomAudio/PitchTrackerNode/PitchTrackerNode';
 import QuantizerNode from 'src/graphEditor/nodes/CustomAudio/Quantizer/QuantizerNode';
 import SamplePlayerNode from 'src/graphEditor/nodes/CustomAudio/SamplePlayer/SamplePlayer';
 import { ScaleAndShiftNode } from 'src/graphEditor/nodes/CustomAudio/ScaleAndShift';
@@ -495,6 +497,12 @@ export const audioNodeGetters: {
   'customAudio/levelDetector': {
     nodeGetter: LevelDetectorNode,
   },
+  'customAudio/loudnessMeter': {
+    nodeGetter: LoudnessMeterNode,
+  },
+  'customAudio/pitchTracker': {
+    nodeGetter: PitchTrackerNode,
+  },
   'customAudio/nativeCompressor': {
     nodeGetter: NativeCompressorNode,
   },
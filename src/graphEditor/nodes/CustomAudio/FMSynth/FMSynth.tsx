@@ -139,6 +139,59 @@ export enum AdsrLengthMode {
   Beats,
 }
 
+export enum AdsrLoopMode {
+  Forward = 0,
+  PingPong = 1,
+}
+
+/**
+ * Corresponds to `AdsrLoop` in the Wasm engine.  The name is only used by the UI; the engine
+ * identifies loops by `id`.
+ */
+export interface AdsrLoopRegion {
+  id: number;
+  name: string;
+  /**
+   * Start and end of the loop in phase, from 0 to 1
+   */
+  start: number;
+  end: number;
+  mode: AdsrLoopMode;
+  /**
+   * Number of times the loop is repeated.  If `null`, the loop repeats until the ADSR is released
+   * (or forever for the release loop).
+   */
+  repeatCount: number | null;
+}
+
+/**
+ * Corresponds to `NoteScalingConfig` in the Wasm engine.  Controls how the velocity and key of the
+ * note that gated an ADSR affect its level and timing.
+ */
+export interface AdsrNoteScaling {
+  /**
+   * From 0 to 1.  At 1, the envelope's level is scaled linearly by velocity.
+   */
+  velocityToLevel: number;
+  /**
+   * From 0 to 1.  Higher velocities shorten the attack.
+   */
+  velocityToAttackTime: number;
+  /**
+   * Octaves of envelope length per octave of pitch relative to `keyTrackingCenter`.  Positive
+   * values shorten envelopes for higher notes.
+   */
+  keyTracking: number;
+  keyTrackingCenter: number;
+}
+
+export const buildDefaultAdsrNoteScaling = (): AdsrNoteScaling => ({
+  velocityToLevel: 0,
+  velocityToAttackTime: 0,
+  keyTracking: 0,
+  keyTrackingCenter: 60,
+});
+
 /**
  * Corresponds to `Adsr` in the Wasm engine
  */
@@ -152,8 +205,17 @@ export interface Adsr {
   lengthMode?: AdsrLengthMode;
   loopPoint: number | null;
   releasePoint: number;
+  /**
+   * Loops played while the ADSR is gated.  They must lie before `releasePoint`.
+   */
+  loops?: AdsrLoopRegion[];
+  /**
+   * Loop played after the ADSR is released.  It must lie after `releasePoint`.
+   */
+  releaseLoop?: AdsrLoopRegion | null;
   audioThreadData: AudioThreadData;
   logScale?: boolean;
+  noteScaling?: AdsrNoteScaling;
 }
 
 export interface AdsrParams {
@@ -162,6 +224,7 @@ export interface AdsrParams {
   loopPoint: number | null; // TODO: ParamSource
   releasePoint: number; // TODO: ParamSource
   logScale?: boolean;
+  noteScaling?: AdsrNoteScaling;
   audioThreadData: AudioThreadData;
 }
 
@@ -415,6 +478,7 @@ export default class FMSynth implements ForeignNode {
       releasePoint: adsr.releasePoint,
       loopPoint: adsr.loopPoint,
       logScale: adsr.logScale ?? false,
+      noteScaling: adsr.noteScaling ?? null,
     };
   }
 
@@ -873,7 +937,8 @@ export default class FMSynth implements ForeignNode {
       R.equals(oldAdsr.steps, newAdsrRaw.steps) &&
       oldAdsr.releasePoint === newAdsrRaw.releasePoint &&
       oldAdsr.loopPoint === newAdsrRaw.loopPoint &&
-      oldAdsr.logScale === newAdsrRaw.logScale;
+      oldAdsr.logScale === newAdsrRaw.logScale &&
+      R.equals(oldAdsr.noteScaling, newAdsrRaw.noteScaling);
     const newAdsr = {
       ...R.clone({ ...newAdsrRaw, audioThreadData: undefined }),
       audioThreadData: {
@@ -929,6 +994,7 @@ export default class FMSynth implements ForeignNode {
         releasePoint: newAdsr.releasePoint,
         loopPoint: newAdsr.loopPoint,
         logScale: newAdsr.logScale ?? false,
+        noteScaling: newAdsr.noteScaling ?? null,
       });
     }
   }
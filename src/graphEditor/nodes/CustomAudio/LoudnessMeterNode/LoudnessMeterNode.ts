@@ -0,0 +1,141 @@
+import { Map as ImmMap } from 'immutable';
+import { writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils.svelte';
+import { AsyncOnce } from 'src/util';
+import LoudnessMeterNodeSmallView from './LoudnessMeterNodeSmallView.svelte';
+
+const LoudnessMeterWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'loudness_meter.wasm?cacheBust=' +
+        (window.location.host.includes('localhost') ? '' : genRandomStringID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+const LoudnessMeterAWPRegistered = new AsyncOnce(
+  () =>
+    new AudioContext().audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'LoudnessMeterAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : genRandomStringID())
+    ),
+  true
+);
+
+/**
+ * Indices into the readings SAB populated by the AWP.  Loudness values are in LUFS, loudness range
+ * is in LU, and true peak is in dBTP.
+ */
+export enum LoudnessReading {
+  Momentary = 0,
+  ShortTerm = 1,
+  Integrated = 2,
+  LoudnessRange = 3,
+  MaxMomentary = 4,
+  MaxShortTerm = 5,
+  TruePeak = 6,
+}
+
+/**
+ * Measures loudness according to ITU-R BS.1770 / EBU R 128, passing audio through unmodified.
+ */
+export class LoudnessMeterNode implements ForeignNode {
+  private vcId: string | undefined;
+  private ctx: AudioContext;
+  private awpHandle: AudioWorkletNode | undefined;
+  private readingsSAB: Writable<Float32Array | null> = writable(null);
+  private dummyInput = new DummyNode();
+
+  static typeName = 'Loudness Meter';
+  public nodeType = 'customAudio/loudnessMeter';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, _params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    this.vcId = vcId;
+
+    this.init().catch(err => {
+      console.error(`Error initializing ${LoudnessMeterNode.typeName} node:`, err);
+      getSentry()?.captureException(err);
+    });
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: LoudnessMeterNodeSmallView,
+      getProps: () => ({
+        readingsSAB: this.readingsSAB,
+        reset: () => this.awpHandle?.port.postMessage({ type: 'reset' }),
+      }),
+    });
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({ preserveRoot: true });
+  }
+
+  private async init() {
+    const [wasmBytes] = await Promise.all([
+      LoudnessMeterWasmBytes.get(),
+      LoudnessMeterAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(this.ctx, 'loudness-meter-awp', {
+      numberOfInputs: 1,
+      numberOfOutputs: 1,
+      channelCount: 2,
+      channelInterpretation: 'speakers',
+      channelCountMode: 'explicit',
+      outputChannelCount: [2],
+    });
+    if (this.vcId) {
+      updateConnectables(this.vcId, this.buildConnectables());
+    }
+
+    this.awpHandle.port.postMessage({
+      type: 'setWasmBytes',
+      wasmBytes,
+    });
+    this.awpHandle.port.onmessage = e => {
+      if (typeof e.data !== 'object') {
+        console.error('Received non-object message from LoudnessMeterAWP:', e.data);
+        return;
+      }
+      switch (e.data.type) {
+        case 'readingsSAB':
+          this.readingsSAB.set(new Float32Array(e.data.sab as SharedArrayBuffer));
+          break;
+        default:
+          console.error('Received unknown message type from LoudnessMeterAWP:', e.data.type);
+      }
+    };
+  }
+
+  public serialize() {
+    return {};
+  }
+
+  public buildConnectables() {
+    return {
+      inputs: ImmMap<string, ConnectableInput>().set('input', {
+        type: 'customAudio',
+        node: this.awpHandle ?? this.dummyInput,
+      }),
+      outputs: ImmMap<string, ConnectableOutput>().set('output', {
+        type: 'customAudio',
+        node: this.awpHandle ?? this.dummyInput,
+      }),
+      vcId: this.vcId!,
+      node: this,
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
@@ -0,0 +1,194 @@
+import { Map as ImmMap } from 'immutable';
+import { get, writable, type Writable } from 'svelte/store';
+
+import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio/CustomAudio';
+import DummyNode from 'src/graphEditor/nodes/DummyNode';
+import type { OverridableAudioParam } from 'src/graphEditor/nodes/util';
+import type { ConnectableInput, ConnectableOutput } from 'src/patchNetwork';
+import { updateConnectables } from 'src/patchNetwork/interface';
+import { MIDINode } from 'src/patchNetwork/midiNode';
+import { getSentry } from 'src/sentry';
+import { mkSvelteContainerCleanupHelper, mkSvelteContainerRenderHelper } from 'src/svelteUtils.svelte';
+import { AsyncOnce } from 'src/util';
+import PitchTrackerNodeSmallView from './PitchTrackerNodeSmallView.svelte';
+
+const PitchTrackerWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'pitch_tracker.wasm?cacheBust=' +
+        (window.location.host.includes('localhost') ? '' : genRandomStringID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+const PitchTrackerAWPRegistered = new AsyncOnce(
+  () =>
+    new AudioContext().audioWorklet.addModule(
+      process.env.ASSET_PATH +
+        'PitchTrackerAWP.js?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : genRandomStringID())
+    ),
+  true
+);
+
+/**
+ * Indices into the readings SAB populated by the AWP
+ */
+export enum PitchReading {
+  /** Hz, or 0 if the input is silent */
+  F0 = 0,
+  NearestNote = 1,
+  /** Deviation of `F0` from `NearestNote` */
+  Cents = 2,
+  /** In [0, 1] */
+  Confidence = 3,
+  LevelDb = 4,
+  /** -1 if no note is playing */
+  ActiveNote = 5,
+  /** The last f0 that passed the gate, in Hz */
+  HeldF0 = 6,
+  /** `HeldF0` as a fractional MIDI note number */
+  HeldNote = 7,
+}
+
+export interface PitchTrackerNodeState {
+  /** Level in dBFS that the input must reach to start a note */
+  gateDb: number;
+  /** How far below `gateDb` the level may fall before the note is released */
+  gateHysteresisDb: number;
+  /** Confidence in [0, 1] that the input must reach to start a note */
+  minConfidence: number;
+  /** How far past the midpoint between two notes the pitch must move to change notes */
+  noteHysteresisCents: number;
+}
+
+const buildDefaultState = (): PitchTrackerNodeState => ({
+  gateDb: -45,
+  gateHysteresisDb: 6,
+  minConfidence: 0.85,
+  noteHysteresisCents: 30,
+});
+
+/**
+ * Tracks the pitch of a monophonic input signal.  Outputs the detected frequency, fractional MIDI
+ * note number, and detection confidence as control signals, and plays gated MIDI notes following
+ * the input.
+ */
+export class PitchTrackerNode implements ForeignNode {
+  private vcId: string | undefined;
+  private ctx: AudioContext;
+  private awpHandle: AudioWorkletNode | undefined;
+  private readingsSAB: Writable<Float32Array | null> = writable(null);
+  private state: Writable<PitchTrackerNodeState>;
+  private dummyNode = new DummyNode();
+  private midiNode = new MIDINode();
+  private splitter: ChannelSplitterNode;
+  private frequencyOutput: GainNode;
+  private noteOutput: GainNode;
+  private confidenceOutput: GainNode;
+
+  static typeName = 'Pitch Tracker';
+  public nodeType = 'customAudio/pitchTracker';
+
+  public paramOverrides: {
+    [name: string]: { param: OverridableAudioParam; override: ConstantSourceNode };
+  } = {};
+
+  constructor(ctx: AudioContext, vcId?: string, params?: { [key: string]: any } | null) {
+    this.ctx = ctx;
+    this.vcId = vcId;
+    this.state = writable(this.deserialize(params));
+
+    this.splitter = ctx.createChannelSplitter(3);
+    this.frequencyOutput = ctx.createGain();
+    this.noteOutput = ctx.createGain();
+    this.confidenceOutput = ctx.createGain();
+    this.splitter.connect(this.frequencyOutput, 0);
+    this.splitter.connect(this.noteOutput, 1);
+    this.splitter.connect(this.confidenceOutput, 2);
+
+    this.init().catch(err => {
+      console.error(`Error initializing ${PitchTrackerNode.typeName} node:`, err);
+      getSentry()?.captureException(err);
+    });
+
+    this.renderSmallView = mkSvelteContainerRenderHelper({
+      Comp: PitchTrackerNodeSmallView,
+      getProps: () => ({
+        state: this.state,
+        readingsSAB: this.readingsSAB,
+        reset: () => this.awpHandle?.port.postMessage({ type: 'reset' }),
+      }),
+    });
+    this.cleanupSmallView = mkSvelteContainerCleanupHelper({ preserveRoot: true });
+  }
+
+  private async init() {
+    const [wasmBytes] = await Promise.all([
+      PitchTrackerWasmBytes.get(),
+      PitchTrackerAWPRegistered.get(),
+    ] as const);
+    this.awpHandle = new AudioWorkletNode(this.ctx, 'pitch-tracker-awp', {
+      numberOfInputs: 1,
+      numberOfOutputs: 1,
+      channelCount: 1,
+      channelInterpretation: 'speakers',
+      channelCountMode: 'explicit',
+      outputChannelCount: [3],
+    });
+    this.awpHandle.connect(this.splitter);
+    if (this.vcId) {
+      updateConnectables(this.vcId, this.buildConnectables());
+    }
+
+    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes });
+    this.awpHandle.port.onmessage = e => {
+      if (typeof e.data !== 'object') {
+        console.error('Received non-object message from PitchTrackerAWP:', e.data);
+        return;
+      }
+      switch (e.data.type) {
+        case 'readingsSAB':
+          this.readingsSAB.set(new Float32Array(e.data.sab as SharedArrayBuffer));
+          break;
+        case 'noteOn':
+          this.midiNode.onAttack(e.data.note, e.data.velocity);
+          break;
+        case 'noteOff':
+          this.midiNode.onRelease(e.data.note, 0);
+          break;
+        default:
+          console.error('Received unknown message type from PitchTrackerAWP:', e.data.type);
+      }
+    };
+    this.state.subscribe(config => this.awpHandle?.port.postMessage({ type: 'setConfig', config }));
+  }
+
+  public serialize(): PitchTrackerNodeState {
+    return get(this.state);
+  }
+
+  public deserialize(params: Record<string, any> | null | undefined): PitchTrackerNodeState {
+    return { ...buildDefaultState(), ...params };
+  }
+
+  public buildConnectables() {
+    return {
+      inputs: ImmMap<string, ConnectableInput>().set('input', {
+        type: 'customAudio',
+        node: this.awpHandle ?? this.dummyNode,
+      }),
+      outputs: ImmMap<string, ConnectableOutput>()
+        .set('midi', { type: 'midi', node: this.midiNode })
+        .set('frequency', { type: 'number', node: this.frequencyOutput })
+        .set('note', { type: 'number', node: this.noteOutput })
+        .set('confidence', { type: 'number', node: this.confidenceOutput }),
+      vcId: this.vcId!,
+      node: this,
+    };
+  }
+
+  // These are set dynamically at initialization time in the constructor
+  public renderSmallView: ForeignNode['renderSmallView'];
+  public cleanupSmallView: ForeignNode['cleanupSmallView'];
+}
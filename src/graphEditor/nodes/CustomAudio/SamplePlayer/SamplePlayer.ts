@@ -31,16 +31,61 @@ const SamplePlayerAWPRegistered = new AsyncOnce(
   true
 );
 
+const WavDecoder = new AsyncOnce(async () => {
+  const mod = await import('src/wav_decoder');
+  await mod.default();
+  return mod;
+}, true);
+
+/**
+ * Shortest loop that will be suggested by `suggestLoopParams`
+ */
+const MIN_SUGGESTED_LOOP_LEN_SECS = 0.1;
+
 export interface SampleCrossfadeParams {
   enabled: boolean;
   threshold: number;
 }
 
+export type SampleLoopMode = 'oneShot' | 'loopContinuous' | 'loopSustain' | 'pingPong';
+
+const LOOP_MODE_IXS: Record<SampleLoopMode, number> = {
+  oneShot: 0,
+  loopContinuous: 1,
+  loopSustain: 2,
+  pingPong: 3,
+};
+
+export interface SampleLoopParams {
+  mode: SampleLoopMode;
+  /**
+   * Loop start as a fraction of the sample's length
+   */
+  start: number;
+  /**
+   * Loop end as a fraction of the sample's length
+   */
+  end: number;
+  /**
+   * Length of the crossfade into the loop start as playback approaches the loop end.  Not used for
+   * ping-pong loops.
+   */
+  crossfadeMs: number;
+}
+
+export const buildDefaultSampleLoopParams = (): SampleLoopParams => ({
+  mode: 'oneShot',
+  start: 0,
+  end: 1,
+  crossfadeMs: 0,
+});
+
 export interface SamplePlayerSampleDescriptor {
   id: string;
   descriptor: SampleDescriptor;
   sample: AudioBuffer | null;
   crossfadeParams: SampleCrossfadeParams;
+  loopParams: SampleLoopParams;
   gain: number;
 }
 
@@ -90,6 +135,8 @@ export default class SamplePlayerNode implements ForeignNode {
         removeSample: this.removeSample.bind(this),
         setSampleGain: this.setSampleGain.bind(this),
         setSampleCrossfadeParams: this.setSampleCrossfadeParams.bind(this),
+        setSampleLoopParams: this.setSampleLoopParams.bind(this),
+        suggestLoopParams: this.suggestLoopParams.bind(this),
         setSampleDescriptor: this.setSampleDescriptor.bind(this),
         initialState: [...this.sampleDescriptors],
       }),
@@ -139,6 +186,7 @@ export default class SamplePlayerNode implements ForeignNode {
         enabled: desc.crossfadeParams.enabled,
         threshold: desc.crossfadeParams.threshold,
       });
+      this.postLoopParams(i);
     });
 
     if (!R.isNil(this.vcId)) {
@@ -159,14 +207,34 @@ export default class SamplePlayerNode implements ForeignNode {
           voiceIx: slotIx,
           sampleData: sample.getChannelData(0),
         });
+        // Loop points are converted to sample indices, so they need to be re-sent for new samples
+        this.postLoopParams(slotIx);
       });
   }
 
+  private postLoopParams(voiceIx: number) {
+    const { sample, loopParams } = this.sampleDescriptors[voiceIx];
+    if (!sample) {
+      return;
+    }
+
+    const lastSampleIx = sample.length - 1;
+    this.awpHandle?.port.postMessage({
+      type: 'setSampleLoopParams',
+      voiceIx,
+      mode: LOOP_MODE_IXS[loopParams.mode],
+      startSampleIx: loopParams.start * lastSampleIx,
+      endSampleIx: loopParams.end * lastSampleIx,
+      crossfadeLenSamples: (loopParams.crossfadeMs / 1000) * sample.sampleRate,
+    });
+  }
+
   private addSample(
     descriptor: SampleDescriptor,
     id: string,
     gain?: number,
-    crossfadeParams?: SampleCrossfadeParams
+    crossfadeParams?: SampleCrossfadeParams,
+    loopParams?: SampleLoopParams
   ) {
     this.sampleDescriptors.push({
       id,
@@ -177,6 +245,7 @@ export default class SamplePlayerNode implements ForeignNode {
         enabled: false,
         threshold: 0,
       },
+      loopParams: loopParams ?? buildDefaultSampleLoopParams(),
     });
 
     if (this.awpHandle) {
@@ -209,6 +278,42 @@ export default class SamplePlayerNode implements ForeignNode {
     });
   }
 
+  private setSampleLoopParams(voiceIx: number, loopParams: SampleLoopParams) {
+    this.sampleDescriptors[voiceIx].loopParams = loopParams;
+    this.postLoopParams(voiceIx);
+  }
+
+  /**
+   * Finds the best loop points for the sample in the given slot, snapped to whole periods of its
+   * pitch.  Returns `null` if the sample isn't loaded yet or no good loop points were found.
+   */
+  private async suggestLoopParams(voiceIx: number): Promise<SampleLoopParams | null> {
+    const { sample, loopParams } = this.sampleDescriptors[voiceIx];
+    if (!sample) {
+      return null;
+    }
+
+    const wavDecoder = await WavDecoder.get();
+    const [startSampleIx, endSampleIx] = wavDecoder.suggest_loop_points(
+      sample.getChannelData(0),
+      sample.sampleRate,
+      MIN_SUGGESTED_LOOP_LEN_SECS,
+      1,
+      true
+    );
+    if (startSampleIx === undefined || endSampleIx === undefined) {
+      return null;
+    }
+
+    const lastSampleIx = sample.length - 1;
+    return {
+      ...loopParams,
+      mode: loopParams.mode === 'oneShot' ? 'loopSustain' : loopParams.mode,
+      start: startSampleIx / lastSampleIx,
+      end: endSampleIx / lastSampleIx,
+    };
+  }
+
   private removeSample(index: number) {
     this.sampleDescriptors = R.remove(index, 1, this.sampleDescriptors);
 
@@ -252,7 +357,8 @@ export default class SamplePlayerNode implements ForeignNode {
             descriptor.descriptor,
             btoa(Math.random().toString()),
             descriptor.gain,
-            descriptor.crossfadeParams
+            descriptor.crossfadeParams,
+            descriptor.loopParams
           );
         }
       );
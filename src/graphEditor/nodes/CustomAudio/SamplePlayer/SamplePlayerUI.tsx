@@ -1,10 +1,13 @@
 import * as R from 'ramda';
-import React, { useCallback, useMemo, useReducer, useRef } from 'react';
+import React, { useCallback, useMemo, useReducer } from 'react';
 import ControlPanel from 'src/controls/LazyControlPanel';
 
-import type {
-  SampleCrossfadeParams,
-  SamplePlayerSampleDescriptor,
+import {
+  buildDefaultSampleLoopParams,
+  type SampleCrossfadeParams,
+  type SampleLoopMode,
+  type SampleLoopParams,
+  type SamplePlayerSampleDescriptor,
 } from 'src/graphEditor/nodes/CustomAudio/SamplePlayer/SamplePlayer';
 import type { SampleDescriptor } from 'src/sampleLibrary/sampleLibrary';
 import { selectSample } from 'src/sampleLibrary/SampleLibraryUI/SelectSample';
@@ -12,12 +15,25 @@ import { filterNils } from 'src/util';
 
 const MAX_SAMPLE_COUNT = 8;
 
+const LOOP_MODE_LABELS: Record<SampleLoopMode, string> = {
+  oneShot: 'one shot',
+  loopContinuous: 'loop continuous',
+  loopSustain: 'loop sustain',
+  pingPong: 'ping pong',
+};
+const loopModeFromLabel = (label: string): SampleLoopMode =>
+  (Object.keys(LOOP_MODE_LABELS) as SampleLoopMode[]).find(
+    mode => LOOP_MODE_LABELS[mode] === label
+  ) ?? 'oneShot';
+
 interface SamplePlayerUIProps {
   initialState: SamplePlayerSampleDescriptor[];
   addSample: (descriptor: SampleDescriptor, id: string, gain?: number | undefined) => void;
   removeSample: (index: number) => void;
   setSampleGain: (index: number, gain: number) => void;
   setSampleCrossfadeParams: (index: number, crossfadeParams: SampleCrossfadeParams) => void;
+  setSampleLoopParams: (index: number, loopParams: SampleLoopParams) => void;
+  suggestLoopParams: (index: number) => Promise<SampleLoopParams | null>;
   setSampleDescriptor: (index: number, descriptor: SampleDescriptor) => void;
 }
 
@@ -26,6 +42,7 @@ type SamplePlayerUIState = SamplePlayerSampleDescriptor[];
 type SamplePlayerUIAction =
   | { type: 'SET_GAIN'; index: number; gain: number }
   | { type: 'SET_CROSSFADE_PARAMS'; index: number; crossfadeParams: SampleCrossfadeParams }
+  | { type: 'SET_LOOP_PARAMS'; index: number; loopParams: SampleLoopParams }
   | { type: 'SET_SAMPLE'; index: number; descriptor: SampleDescriptor }
   | { type: 'ADD_SAMPLE'; descriptor: SampleDescriptor }
   | { type: 'REMOVE_SAMPLE'; index: number };
@@ -36,6 +53,7 @@ const buildDefaultSlot = (descriptor: SampleDescriptor): SamplePlayerSampleDescr
   sample: null,
   descriptor,
   crossfadeParams: { enabled: false, threshold: 0 },
+  loopParams: buildDefaultSampleLoopParams(),
 });
 
 const mkSamplePlayerUIReducer =
@@ -44,6 +62,7 @@ const mkSamplePlayerUIReducer =
     removeSample,
     setSampleGain,
     setSampleCrossfadeParams,
+    setSampleLoopParams,
     setSampleDescriptor,
   }: Pick<
     SamplePlayerUIProps,
@@ -51,6 +70,7 @@ const mkSamplePlayerUIReducer =
     | 'removeSample'
     | 'setSampleGain'
     | 'setSampleCrossfadeParams'
+    | 'setSampleLoopParams'
     | 'setSampleDescriptor'
   >) =>
   (state: SamplePlayerUIState, action: SamplePlayerUIAction): SamplePlayerUIState => {
@@ -69,6 +89,11 @@ const mkSamplePlayerUIReducer =
           state
         );
       }
+      case 'SET_LOOP_PARAMS': {
+        const slot = state[action.index];
+        setSampleLoopParams(action.index, action.loopParams);
+        return R.set(R.lensIndex(action.index), { ...slot, loopParams: action.loopParams }, state);
+      }
       case 'SET_SAMPLE': {
         const slot = state[action.index] ?? buildDefaultSlot(action.descriptor);
         setSampleDescriptor(action.index, action.descriptor);
@@ -85,6 +110,7 @@ const mkSamplePlayerUIReducer =
             gain: 1,
             sample: null,
             crossfadeParams: { enabled: false, threshold: 0 },
+            loopParams: buildDefaultSampleLoopParams(),
           },
         ];
       }
@@ -104,14 +130,29 @@ interface ConfigureSampleProps {
   index: number;
   descriptor: SamplePlayerSampleDescriptor;
   dispatch: (action: SamplePlayerUIAction) => void;
+  suggestLoopParams: (index: number) => Promise<SampleLoopParams | null>;
 }
 
-const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, dispatch }) => {
-  const initialState = useRef({
-    gain: descriptor.gain,
-    'enable crossfade': descriptor.crossfadeParams.enabled,
-    'crossfade threshold': descriptor.crossfadeParams.threshold,
-  });
+const ConfigureSample: React.FC<ConfigureSampleProps> = ({
+  index,
+  descriptor,
+  dispatch,
+  suggestLoopParams,
+}) => {
+  // Controlled so that auto-detected loop points show up in the sliders
+  const controlPanelState = useMemo(
+    () => ({
+      gain: descriptor.gain,
+      'enable crossfade': descriptor.crossfadeParams.enabled,
+      'crossfade threshold': descriptor.crossfadeParams.threshold,
+      'loop mode': LOOP_MODE_LABELS[descriptor.loopParams.mode],
+      'loop start': descriptor.loopParams.start,
+      'loop end': descriptor.loopParams.end,
+      'loop crossfade ms': descriptor.loopParams.crossfadeMs,
+    }),
+    [descriptor.crossfadeParams, descriptor.gain, descriptor.loopParams]
+  );
+  const loopMode = descriptor.loopParams.mode;
   const settings = useMemo(
     () =>
       filterNils([
@@ -128,13 +169,31 @@ const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, di
         descriptor.crossfadeParams.enabled
           ? { type: 'range', label: 'crossfade threshold', min: 0, max: 1 }
           : null,
+        { type: 'select', label: 'loop mode', options: Object.values(LOOP_MODE_LABELS) },
+        {
+          type: 'button',
+          label: 'auto-detect loop',
+          action: async () => {
+            const loopParams = await suggestLoopParams(index);
+            if (!loopParams) {
+              toastError('No good loop points found; make sure the sample has loaded');
+              return;
+            }
+            dispatch({ type: 'SET_LOOP_PARAMS', index, loopParams });
+          },
+        },
+        loopMode !== 'oneShot' ? { type: 'range', label: 'loop start', min: 0, max: 1 } : null,
+        loopMode !== 'oneShot' ? { type: 'range', label: 'loop end', min: 0, max: 1 } : null,
+        loopMode !== 'oneShot' && loopMode !== 'pingPong'
+          ? { type: 'range', label: 'loop crossfade ms', min: 0, max: 500 }
+          : null,
         {
           type: 'button',
           label: 'delete',
           action: () => dispatch({ type: 'REMOVE_SAMPLE', index }),
         },
       ]),
-    [dispatch, index, descriptor.crossfadeParams.enabled]
+    [dispatch, index, descriptor.crossfadeParams.enabled, loopMode, suggestLoopParams]
   );
 
   const handleChange = useCallback(
@@ -160,19 +219,51 @@ const ConfigureSample: React.FC<ConfigureSampleProps> = ({ index, descriptor, di
           });
           break;
         }
+        case 'loop mode': {
+          dispatch({
+            type: 'SET_LOOP_PARAMS',
+            index,
+            loopParams: { ...descriptor.loopParams, mode: loopModeFromLabel(value) },
+          });
+          break;
+        }
+        case 'loop start': {
+          dispatch({
+            type: 'SET_LOOP_PARAMS',
+            index,
+            loopParams: { ...descriptor.loopParams, start: value },
+          });
+          break;
+        }
+        case 'loop end': {
+          dispatch({
+            type: 'SET_LOOP_PARAMS',
+            index,
+            loopParams: { ...descriptor.loopParams, end: value },
+          });
+          break;
+        }
+        case 'loop crossfade ms': {
+          dispatch({
+            type: 'SET_LOOP_PARAMS',
+            index,
+            loopParams: { ...descriptor.loopParams, crossfadeMs: value },
+          });
+          break;
+        }
         default: {
           console.error('Unhandled key in `ConfigureSample`: ' + key);
         }
       }
     },
-    [descriptor.crossfadeParams.threshold, dispatch, index]
+    [descriptor.crossfadeParams.threshold, descriptor.loopParams, dispatch, index]
   );
 
   return (
     <div style={{ width: '100%' }}>
       Selected Sample: {descriptor.descriptor.name}
       <ControlPanel
-        initialState={initialState.current}
+        state={controlPanelState}
         settings={settings}
         onChange={handleChange}
         style={{ width: '100%' }}
@@ -209,6 +300,8 @@ const SamplePlayerUI: React.FC<SamplePlayerUIProps> = ({
   removeSample,
   setSampleGain,
   setSampleCrossfadeParams,
+  setSampleLoopParams,
+  suggestLoopParams,
   setSampleDescriptor,
 }) => {
   const reducer = useMemo(
@@ -218,16 +311,32 @@ const SamplePlayerUI: React.FC<SamplePlayerUIProps> = ({
         removeSample,
         setSampleGain,
         setSampleCrossfadeParams,
+        setSampleLoopParams,
         setSampleDescriptor,
       }),
-    [addSample, removeSample, setSampleDescriptor, setSampleGain, setSampleCrossfadeParams]
+    [
+      addSample,
+      removeSample,
+      setSampleDescriptor,
+      setSampleGain,
+      setSampleCrossfadeParams,
+      setSampleLoopParams,
+    ]
   );
   const [state, dispatch] = useReducer(reducer, initialState);
 
   return (
     <div className='sample-player-small-view' style={{ width: '100%' }}>
       {state.map((slot, i) => {
-        return <ConfigureSample key={slot.id} index={i} descriptor={slot} dispatch={dispatch} />;
+        return (
+          <ConfigureSample
+            key={slot.id}
+            index={i}
+            descriptor={slot}
+            dispatch={dispatch}
+            suggestLoopParams={suggestLoopParams}
+          />
+        );
       })}
       {state.length < MAX_SAMPLE_COUNT ? <AddNewSample dispatch={dispatch} /> : null}
     </div>
@@ -2,7 +2,6 @@ import { Map as ImmMap } from 'immutable';
 import * as R from 'ramda';
 import { get, writable, type Writable } from 'svelte/store';
 
-import { buildVocoderBandpassChainPreset } from 'src/filterDesigner/vocoder';
 import type { ForeignNode } from 'src/graphEditor/nodes/CustomAudio';
 import VocoderSmallView from 'src/graphEditor/nodes/CustomAudio/Vocoder/VocoderSmallView.svelte';
 import DummyNode from 'src/graphEditor/nodes/DummyNode';
@@ -34,18 +33,81 @@ const VocoderAWPRegistered = new AsyncOnce(
   true
 );
 
-export interface VocoderState {
+export type VocoderBandSpacing = 'log' | 'bark' | 'mel';
+
+/**
+ * Layout of the vocoder's bands.  Changing any of these rebuilds the vocoder's filters, so they
+ * can't be modulated.  More bands and higher filter orders improve intelligibility at the cost of
+ * CPU.
+ */
+export interface VocoderBandConfig {
+  bandCount: number;
+  minFreqHz: number;
+  maxFreqHz: number;
+  bandSpacing: VocoderBandSpacing;
+  /**
+   * Order of the filters at each band edge.  Must be a multiple of 4.
+   */
+  filterOrder: number;
+}
+
+/**
+ * Attack and release times for a band's envelope follower.  Times <= 0 use a default based on the
+ * band's center frequency.
+ */
+export interface VocoderBandEnvelope {
+  attackMs: number;
+  releaseMs: number;
+}
+
+export interface VocoderState extends VocoderBandConfig {
   carrierGainDb: number;
   modulatorGainDb: number;
   postGainDb: number;
+  /**
+   * Level of the high-frequency content of unvoiced modulator sounds like sibilants that is passed
+   * directly to the output, from 0 to 1
+   */
+  sibilanceLevel: number;
+  formantShiftSemitones: number;
+  /**
+   * If set, band levels are held at their current values and changes to the modulator are ignored
+   */
+  freeze: boolean;
+  /**
+   * Envelope used for all bands that don't have an override
+   */
+  envelope: VocoderBandEnvelope;
+  bandEnvelopeOverrides: { [bandIx: number]: VocoderBandEnvelope };
 }
 
 const buildDefaultVocoderState = (): VocoderState => ({
   carrierGainDb: 0,
   modulatorGainDb: 0,
   postGainDb: 0,
+  bandCount: 22,
+  minFreqHz: 80,
+  maxFreqHz: 12_000,
+  bandSpacing: 'log',
+  filterOrder: 16,
+  sibilanceLevel: 0,
+  formantShiftSemitones: 0,
+  freeze: false,
+  envelope: { attackMs: 0, releaseMs: 0 },
+  bandEnvelopeOverrides: {},
 });
 
+const getBandConfig = (state: VocoderState): VocoderBandConfig => ({
+  bandCount: state.bandCount,
+  minFreqHz: state.minFreqHz,
+  maxFreqHz: state.maxFreqHz,
+  bandSpacing: state.bandSpacing,
+  filterOrder: state.filterOrder,
+});
+
+const getBandEnvelopes = (state: VocoderState): VocoderBandEnvelope[] =>
+  R.times(bandIx => state.bandEnvelopeOverrides[bandIx] ?? state.envelope, state.bandCount);
+
 export class VocoderNode implements ForeignNode {
   private ctx: AudioContext;
   private vcId: string | undefined;
@@ -56,6 +118,11 @@ export class VocoderNode implements ForeignNode {
   private carrierGain: OverridableAudioParam;
   private modulatorGain: OverridableAudioParam;
   private postGain: OverridableAudioParam;
+  private sibilanceGain: OverridableAudioParam;
+  private formantShift: OverridableAudioParam;
+  private freeze: OverridableAudioParam;
+  private lastBandConfig: VocoderBandConfig;
+  private lastBandEnvelopes: VocoderBandEnvelope[];
 
   static typeName = 'Vocoder';
   public nodeType = 'customAudio/vocoder';
@@ -72,10 +139,15 @@ export class VocoderNode implements ForeignNode {
     this.modulatorInput = new GainNode(ctx);
 
     this.store = writable(this.deserialize(params));
+    this.lastBandConfig = getBandConfig(get(this.store));
+    this.lastBandEnvelopes = getBandEnvelopes(get(this.store));
 
     this.carrierGain = new OverridableAudioParam(ctx);
     this.modulatorGain = new OverridableAudioParam(ctx);
     this.postGain = new OverridableAudioParam(ctx);
+    this.sibilanceGain = new OverridableAudioParam(ctx);
+    this.formantShift = new OverridableAudioParam(ctx);
+    this.freeze = new OverridableAudioParam(ctx);
 
     this.handleStateChange(get(this.store));
 
@@ -103,23 +175,21 @@ export class VocoderNode implements ForeignNode {
     this.carrierGain.manualControl.offset.value = dbToLinear(newState.carrierGainDb);
     this.modulatorGain.manualControl.offset.value = dbToLinear(newState.modulatorGainDb);
     this.postGain.manualControl.offset.value = dbToLinear(newState.postGainDb);
-  }
+    this.sibilanceGain.manualControl.offset.value = newState.sibilanceLevel;
+    this.formantShift.manualControl.offset.value = newState.formantShiftSemitones;
+    this.freeze.manualControl.offset.value = newState.freeze ? 1 : 0;
+
+    const bandConfig = getBandConfig(newState);
+    if (!R.equals(bandConfig, this.lastBandConfig)) {
+      this.lastBandConfig = bandConfig;
+      this.awpHandle?.port.postMessage({ type: 'setBandConfig', bandConfig });
+    }
 
-  private buildAndEncoderFilterParams(): Float32Array {
-    const params = buildVocoderBandpassChainPreset(16);
-    const groupCount = params.filterGroups.length;
-    const filtersPerGroup = params.filterGroups[0].length;
-    // buf format: [cutoffFreq, Q]
-    const buf = new Float32Array(groupCount * filtersPerGroup * 2);
-    for (let groupIx = 0; groupIx < groupCount; groupIx++) {
-      for (let filterIx = 0; filterIx < filtersPerGroup; filterIx++) {
-        const filter = params.filterGroups[groupIx][filterIx];
-        const i = groupIx * filtersPerGroup * 2 + filterIx * 2;
-        buf[i] = filter.frequency;
-        buf[i + 1] = filter.Q ?? 1;
-      }
+    const bandEnvelopes = getBandEnvelopes(newState);
+    if (!R.equals(bandEnvelopes, this.lastBandEnvelopes)) {
+      this.lastBandEnvelopes = bandEnvelopes;
+      this.awpHandle?.port.postMessage({ type: 'setBandEnvelopes', bandEnvelopes });
     }
-    return buf;
   }
 
   private async init() {
@@ -154,13 +224,33 @@ export class VocoderNode implements ForeignNode {
       undefined,
       true
     );
+    this.sibilanceGain = new OverridableAudioParam(
+      this.ctx,
+      awpParams.get('sibilance_gain')!,
+      undefined,
+      true
+    );
+    this.formantShift = new OverridableAudioParam(
+      this.ctx,
+      awpParams.get('formant_shift')!,
+      undefined,
+      true
+    );
+    this.freeze = new OverridableAudioParam(this.ctx, awpParams.get('freeze')!, undefined, true);
     this.handleStateChange(get(this.store));
 
     this.carrierInput.connect(this.awpHandle, 0, 0);
     this.modulatorInput.connect(this.awpHandle, 0, 1);
 
-    const filterParams = this.buildAndEncoderFilterParams();
-    this.awpHandle.port.postMessage({ type: 'setWasmBytes', wasmBytes, filterParams });
+    this.awpHandle.port.postMessage({
+      type: 'setWasmBytes',
+      wasmBytes,
+      bandConfig: this.lastBandConfig,
+    });
+    this.awpHandle.port.postMessage({
+      type: 'setBandEnvelopes',
+      bandEnvelopes: this.lastBandEnvelopes,
+    });
 
     if (this.vcId) {
       updateConnectables(this.vcId, this.buildConnectables());
@@ -177,6 +267,16 @@ export class VocoderNode implements ForeignNode {
       carrierGainDb: params.carrierGainDb ?? defaults.carrierGainDb,
       modulatorGainDb: params.modulatorGainDb ?? defaults.modulatorGainDb,
       postGainDb: params.postGainDb ?? defaults.postGainDb,
+      bandCount: params.bandCount ?? defaults.bandCount,
+      minFreqHz: params.minFreqHz ?? defaults.minFreqHz,
+      maxFreqHz: params.maxFreqHz ?? defaults.maxFreqHz,
+      bandSpacing: params.bandSpacing ?? defaults.bandSpacing,
+      filterOrder: params.filterOrder ?? defaults.filterOrder,
+      sibilanceLevel: params.sibilanceLevel ?? defaults.sibilanceLevel,
+      formantShiftSemitones: params.formantShiftSemitones ?? defaults.formantShiftSemitones,
+      freeze: params.freeze ?? defaults.freeze,
+      envelope: params.envelope ?? defaults.envelope,
+      bandEnvelopeOverrides: params.bandEnvelopeOverrides ?? defaults.bandEnvelopeOverrides,
     };
   }
 
@@ -194,7 +294,9 @@ export class VocoderNode implements ForeignNode {
         .set('modulator', {
           type: 'customAudio',
           node: this.modulatorInput,
-        }),
+        })
+        .set('formant shift', { type: 'number', node: this.formantShift })
+        .set('freeze', { type: 'number', node: this.freeze }),
       outputs: ImmMap<string, ConnectableOutput>().set('output', {
         type: 'customAudio',
         node: this.awpHandle ? this.awpHandle : new DummyNode(),
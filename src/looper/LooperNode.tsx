@@ -75,8 +75,16 @@ export class LooperNode {
 
         this.setCompositionForBank(moduleIx, bankIx, bank.loadedComposition, bank.lenBeats);
       });
+      module.banks?.forEach((bank, bankIx) => {
+        if (bank.polymetricEnabled) {
+          this.setBankPolymetricEnabled(moduleIx, bankIx, true);
+        }
+      });
 
       this.setActiveBankIx(moduleIx, module.activeBankIx);
+      if (module.polymetric) {
+        this.setPolymetricMode(moduleIx, true);
+      }
 
       // Try to parse + set the UI transition algorithm, falling back to the last good applied algorithm otherwise
       const parsed = parseLooperTransitionAlgorithmUIState(
@@ -156,6 +164,18 @@ export class LooperNode {
     this.postMessage({ type: 'setNextBankIx', moduleIx, nextBankIx });
   }
 
+  /**
+   * In polymetric mode, all banks with polymetric playback enabled play at once, each looping over
+   * its own length.
+   */
+  public setPolymetricMode(moduleIx: number, polymetric: boolean) {
+    this.postMessage({ type: 'setPolymetricMode', moduleIx, polymetric });
+  }
+
+  public setBankPolymetricEnabled(moduleIx: number, bankIx: number, enabled: boolean) {
+    this.postMessage({ type: 'setBankPolymetricEnabled', moduleIx, bankIx, enabled });
+  }
+
   private getMIDISchedulingInfoForModule(
     moduleIx: number
   ): { mailboxIDs: string[] | null; needsUIThreadScheduling: boolean } | null {
@@ -113,6 +113,7 @@ interface LooperBankCompProps {
   isLast: boolean;
   phaseSAB: Float32Array | null;
   moduleIx: number;
+  polymetric: boolean;
 }
 
 const LooperBankCompInner: React.FC<LooperBankCompProps> = ({
@@ -123,6 +124,7 @@ const LooperBankCompInner: React.FC<LooperBankCompProps> = ({
   bankIx,
   phaseSAB,
   moduleIx,
+  polymetric,
 }) => {
   const settings = useMemo(
     () => [
@@ -151,8 +153,13 @@ const LooperBankCompInner: React.FC<LooperBankCompProps> = ({
         label: 'activate bank',
         action: () => looperDispatch(looperActions.setActiveBankIx({ moduleIx, vcId, bankIx })),
       },
+      ...(polymetric ? [{ type: 'checkbox', label: 'play in polymetric mode' }] : []),
     ],
-    [bankIx, moduleIx, vcId]
+    [bankIx, moduleIx, polymetric, vcId]
+  );
+  const controlPanelState = useMemo(
+    () => ({ 'play in polymetric mode': bank.polymetricEnabled }),
+    [bank.polymetricEnabled]
   );
 
   return (
@@ -208,7 +215,18 @@ const LooperBankCompInner: React.FC<LooperBankCompProps> = ({
       <div style={{ position: 'relative' }}>
         <div className='looper-bank-number'>{bankIx}</div>
       </div>
-      <ControlPanel width={400} settings={settings} />
+      <ControlPanel
+        width={400}
+        settings={settings}
+        state={controlPanelState}
+        onChange={(key: string, val: any) => {
+          if (key === 'play in polymetric mode') {
+            looperDispatch(
+              looperActions.setBankPolymetricEnabled({ vcId, moduleIx, bankIx, enabled: val })
+            );
+          }
+        }}
+      />
       <div className='active-composition-name'>
         <div className='click-to-activate'>Loaded Sequence:</div>
         <b>{bank.loadedComposition ? bank.loadedComposition.name : 'NONE'}</b>
@@ -222,7 +240,14 @@ const LooperBankCompInner: React.FC<LooperBankCompProps> = ({
       />
       <div>
         {phaseSAB ? (
-          <LooperViz vcId={vcId} bankIx={bankIx} phaseSAB={phaseSAB} width={500} height={100} />
+          <LooperViz
+            vcId={vcId}
+            bankIx={bankIx}
+            phaseSAB={phaseSAB}
+            width={500}
+            height={100}
+            polymetric={polymetric}
+          />
         ) : null}
       </div>
     </div>
@@ -234,9 +259,14 @@ const LooperBankComp = React.memo(LooperBankCompInner);
 interface LooperMainControlPanelProps {
   vcId: string;
   moduleIx: number;
+  polymetric: boolean;
 }
 
-const AddBankControlPanel: React.FC<LooperMainControlPanelProps> = ({ vcId, moduleIx }) => {
+const AddBankControlPanel: React.FC<LooperMainControlPanelProps> = ({
+  vcId,
+  moduleIx,
+  polymetric,
+}) => {
   const settings = useMemo(
     () => [
       {
@@ -244,11 +274,25 @@ const AddBankControlPanel: React.FC<LooperMainControlPanelProps> = ({ vcId, modu
         label: 'add midi bank',
         action: () => looperDispatch(looperActions.addBank({ vcId, moduleIx })),
       },
+      { type: 'checkbox', label: 'polymetric mode' },
     ],
     [moduleIx, vcId]
   );
+  const controlPanelState = useMemo(() => ({ 'polymetric mode': polymetric }), [polymetric]);
 
-  return <ControlPanel settings={settings} width={400} className='looper-main-control-panel' />;
+  return (
+    <ControlPanel
+      settings={settings}
+      state={controlPanelState}
+      onChange={(key: string, val: any) => {
+        if (key === 'polymetric mode') {
+          looperDispatch(looperActions.setPolymetricMode({ vcId, moduleIx, polymetric: val }));
+        }
+      }}
+      width={400}
+      className='looper-main-control-panel'
+    />
+  );
 };
 
 interface LooperTabProps {
@@ -298,6 +342,11 @@ const loadLooperPreset = (vcId: string, preset: SerializedLooperInstState) => {
     looperDispatch(looperActions.setModuleName({ vcId, moduleIx, name: mod.name }));
     mod.banks.forEach((bank, bankIx) => {
       looperDispatch(looperActions.addBank({ vcId, moduleIx }));
+      if (bank.polymetricEnabled) {
+        looperDispatch(
+          looperActions.setBankPolymetricEnabled({ vcId, moduleIx, bankIx, enabled: true })
+        );
+      }
       if (bank.loadedComposition) {
         looperDispatch(
           looperActions.setLoadedComposition({
@@ -310,6 +359,9 @@ const loadLooperPreset = (vcId: string, preset: SerializedLooperInstState) => {
       }
     });
     looperDispatch(looperActions.setActiveBankIx({ vcId, moduleIx, bankIx: mod.activeBankIx }));
+    if (mod.polymetric) {
+      looperDispatch(looperActions.setPolymetricMode({ vcId, moduleIx, polymetric: true }));
+    }
   });
   looperDispatch(looperActions.setActiveModuleIx({ vcId, moduleIx: preset.activeModuleIx }));
 };
@@ -516,9 +568,16 @@ const LooperUI: React.FC<LooperUIProps> = ({ vcId }) => {
               isLast={bankIx === activeModule.banks.length - 1}
               phaseSAB={phaseSAB}
               moduleIx={activeModuleIx}
+              polymetric={activeModule.polymetric}
             />
           )) ?? null}
-          {activeModule ? <AddBankControlPanel moduleIx={activeModuleIx} vcId={vcId} /> : null}
+          {activeModule ? (
+            <AddBankControlPanel
+              moduleIx={activeModuleIx}
+              vcId={vcId}
+              polymetric={activeModule.polymetric}
+            />
+          ) : null}
         </div>
         {activeModule ? <ConfigureTransitionAlgorithm vcId={vcId} /> : null}
       </div>
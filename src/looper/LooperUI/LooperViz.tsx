@@ -9,6 +9,12 @@ const Conf = {
   phaseIndicatorColor: '#19d4d1',
 };
 
+/**
+ * Offset of the per-bank phases in the phase SAB, which is laid out as
+ * [active bank phase, playing bank ix, bank count, ...per-bank phases]
+ */
+const BANK_PHASES_OFFSET = 3;
+
 class LooperVizInst {
   private bankIx: number;
   private phaseSAB: Float32Array;
@@ -17,16 +23,19 @@ class LooperVizInst {
   private width: number;
   private height: number;
   private lastRenderedPhase: number | null = 0;
+  private polymetric: boolean;
 
   constructor(
     bankIx: number,
     phaseSAB: Float32Array,
     ctx: CanvasRenderingContext2D,
     width: number,
-    height: number
+    height: number,
+    polymetric: boolean
   ) {
     this.bankIx = bankIx;
     this.phaseSAB = phaseSAB;
+    this.polymetric = polymetric;
     this.ctx = ctx;
     this.width = width;
     this.height = height;
@@ -38,13 +47,33 @@ class LooperVizInst {
     this.ctx = ctx;
   }
 
+  public setPolymetric(polymetric: boolean) {
+    this.polymetric = polymetric;
+  }
+
+  /**
+   * Returns the current phase of this bank or `null` if it isn't playing.  In polymetric mode,
+   * every enabled bank plays with its own phase.
+   */
+  private getPhase(): number | null {
+    if (!this.polymetric) {
+      return this.phaseSAB[1] === this.bankIx ? this.phaseSAB[0] : null;
+    }
+
+    if (this.bankIx >= this.phaseSAB[2]) {
+      return null;
+    }
+    const phase = this.phaseSAB[BANK_PHASES_OFFSET + this.bankIx];
+    return phase < 0 ? null : phase;
+  }
+
   public draw() {
     if (!this.isRunning) {
       return;
     }
 
-    const isActive = this.phaseSAB[1] === this.bankIx;
-    if (!isActive) {
+    const phase = this.getPhase();
+    if (phase === null) {
       if (this.lastRenderedPhase === null) {
         requestAnimationFrame(() => this.draw());
         return;
@@ -60,7 +89,6 @@ class LooperVizInst {
     this.ctx.fillStyle = Conf.backgroundColor;
     this.ctx.fillRect(0, 0, this.width, this.height);
 
-    const phase = this.phaseSAB[0];
     this.lastRenderedPhase = phase;
     const x = phase * this.width;
     this.ctx.strokeStyle = Conf.phaseIndicatorColor;
@@ -88,9 +116,17 @@ interface LooperVizProps {
   phaseSAB: Float32Array;
   width: number;
   height: number;
+  polymetric: boolean;
 }
 
-const LooperViz: React.FC<LooperVizProps> = ({ vcId, bankIx, phaseSAB, width, height }) => {
+const LooperViz: React.FC<LooperVizProps> = ({
+  vcId,
+  bankIx,
+  phaseSAB,
+  width,
+  height,
+  polymetric,
+}) => {
   const isHidden = useSelector((state: ReduxStore) => state.looper.stateByVcId[vcId].isHidden);
   const inst = useRef<LooperVizInst | null>(null);
 
@@ -106,6 +142,8 @@ const LooperViz: React.FC<LooperVizProps> = ({ vcId, bankIx, phaseSAB, width, he
     }
   }, [isHidden]);
 
+  useEffect(() => inst.current?.setPolymetric(polymetric), [polymetric]);
+
   useEffect(() => () => inst.current?.stop(), []);
 
   return (
@@ -128,7 +166,7 @@ const LooperViz: React.FC<LooperVizProps> = ({ vcId, bankIx, phaseSAB, width, he
         if (inst.current) {
           inst.current.setCtx(ctx);
         } else {
-          inst.current = new LooperVizInst(bankIx, phaseSAB, ctx, width, height);
+          inst.current = new LooperVizInst(bankIx, phaseSAB, ctx, width, height, polymetric);
         }
       }}
     />
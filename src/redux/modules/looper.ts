@@ -11,6 +11,10 @@ export interface LooperBank {
   loadedComposition: SavedMIDIComposition | null;
   lenBeats: number;
   compositionLenBeats: number | null;
+  /**
+   * Whether this bank plays when its module is in polymetric mode
+   */
+  polymetricEnabled: boolean;
 }
 
 export type LooperTransitionAlgorithm =
@@ -34,6 +38,11 @@ export interface LooperModule {
   activeBankIx: number | null;
   banks: LooperBank[];
   transitionAlgorithm: LooperTransitionAlgorithmState;
+  /**
+   * If true, all banks with `polymetricEnabled` set play simultaneously with independent lengths
+   * and phases rather than one at a time according to the transition algorithm.
+   */
+  polymetric: boolean;
 }
 
 export interface LooperInstState {
@@ -62,6 +71,11 @@ export const deserializeLooper = (serialized: string): Omit<LooperInstState, 'lo
     modules: parsed.modules.map(mod => ({
       ...mod,
       transitionAlgorithm: mod.transitionAlgorithm ?? buildDefaultLooperTransitionAlgorithmState(),
+      polymetric: mod.polymetric ?? false,
+      banks: mod.banks.map(bank => ({
+        ...bank,
+        polymetricEnabled: bank.polymetricEnabled ?? false,
+      })),
     })),
     phaseSAB: null,
     isHidden: true,
@@ -82,6 +96,7 @@ const buildDefaultLooperBank = (): LooperBank => ({
   loadedComposition: null,
   lenBeats: 8,
   compositionLenBeats: null,
+  polymetricEnabled: false,
 });
 
 const buildDefaultLooperTransitionAlgorithmState = (): LooperTransitionAlgorithmState => ({
@@ -94,6 +109,7 @@ const buildDefaultLooperModule = (moduleIx: number): LooperModule => ({
   activeBankIx: null,
   banks: [buildDefaultLooperBank()],
   transitionAlgorithm: buildDefaultLooperTransitionAlgorithmState(),
+  polymetric: false,
 });
 
 export const buildDefaultLooperInstState = (): Omit<LooperInstState, 'looperNode'> => ({
@@ -252,6 +268,26 @@ const looperSlice = createSlice({
         }
       }
     },
+    setPolymetricMode: (
+      state,
+      {
+        payload: { vcId, moduleIx, polymetric },
+      }: PayloadAction<{ vcId: string; moduleIx: number; polymetric: boolean }>
+    ) => {
+      const instState = state.stateByVcId[vcId];
+      instState.modules[moduleIx].polymetric = polymetric;
+      instState.looperNode.setPolymetricMode(moduleIx, polymetric);
+    },
+    setBankPolymetricEnabled: (
+      state,
+      {
+        payload: { vcId, moduleIx, bankIx, enabled },
+      }: PayloadAction<{ vcId: string; moduleIx: number; bankIx: number; enabled: boolean }>
+    ) => {
+      const instState = state.stateByVcId[vcId];
+      instState.modules[moduleIx].banks[bankIx].polymetricEnabled = enabled;
+      instState.looperNode.setBankPolymetricEnabled(moduleIx, bankIx, enabled);
+    },
     setPhaseSAB: (
       state,
       { payload: { vcId, phaseSAB } }: PayloadAction<{ vcId: string; phaseSAB: Float32Array }>
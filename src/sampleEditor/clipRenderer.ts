@@ -0,0 +1,55 @@
+import { AsyncOnce } from 'src/util';
+
+const WavDecoder = new AsyncOnce(async () => {
+  const mod = await import('src/wav_decoder');
+  await mod.default();
+  return mod;
+}, true);
+
+interface RenderRequest {
+  sampleDataID: string | number;
+  sourceStart: number;
+  sourceEnd: number;
+  lenSamples: number;
+  pitchShiftCents: number;
+}
+
+/**
+ * Renders the time-stretched and pitch-shifted clips requested by a sample editor AWP and posts
+ * them back to it.  Rendering takes far too long to do on the audio thread, so it's done here on
+ * the main thread instead.
+ *
+ * @param getSampleData returns the sample data that was passed to the AWP with the provided ID
+ */
+export const connectClipRenderer = (
+  node: AudioWorkletNode,
+  getSampleData: (sampleDataID: string | number) => Float32Array | undefined
+) => {
+  node.port.addEventListener('message', async evt => {
+    if (evt.data.type !== 'renderRequests') {
+      return;
+    }
+
+    const wavDecoder = await WavDecoder.get();
+    const renderings = [];
+    for (const request of evt.data.requests as RenderRequest[]) {
+      const samples = getSampleData(request.sampleDataID);
+      if (!samples) {
+        continue;
+      }
+
+      const rendered = wavDecoder.stretch_and_shift(
+        samples.subarray(request.sourceStart, request.sourceEnd),
+        request.lenSamples,
+        request.pitchShiftCents
+      );
+      renderings.push({ ...request, samples: rendered });
+    }
+
+    node.port.postMessage(
+      { type: 'renderedData', bpm: evt.data.bpm, renderings },
+      renderings.map(rendering => rendering.samples.buffer)
+    );
+  });
+  node.port.start();
+};
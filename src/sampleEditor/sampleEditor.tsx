@@ -0,0 +1,74 @@
+import { connectClipRenderer } from 'src/sampleEditor/clipRenderer';
+import { AsyncOnce } from 'src/util';
+
+const cacheBust = () =>
+  window.location.host.includes('localhost') ? '' : btoa(Math.random().toString());
+
+const ctx = new AudioContext();
+
+const SampleEditorAWPRegistered = new AsyncOnce(
+  () =>
+    ctx.audioWorklet.addModule(
+      process.env.ASSET_PATH + 'SampleEditorAWP.js?cacheBust=' + cacheBust()
+    ),
+  true
+);
+
+const SampleEditorWasm = new AsyncOnce(
+  () =>
+    fetch(process.env.ASSET_PATH + 'sample_editor.wasm?cacheBust=' + cacheBust()).then(res =>
+      res.arrayBuffer()
+    ),
+  true
+);
+
+export interface SampleEditorSampleData {
+  id: string | number;
+  samples: Float32Array;
+}
+
+type FadeCurve = 'linear' | 'equalPower' | 'exponential' | 'logarithmic' | 'sCurve';
+
+interface Fade {
+  lenSamples: number;
+  curve?: FadeCurve;
+}
+
+/**
+ * A clip scheduled on the timeline playing the sample data with the same ID
+ */
+export interface SampleEditorClip {
+  id: string | number;
+  startBeat: number;
+  gainEnvelope?: number[];
+  targetLenBeats?: number;
+  pitchShiftCents?: number;
+  sourceOffsetSamples?: number;
+  sourceLenSamples?: number;
+  gain?: number;
+  fadeIn?: Fade;
+  fadeOut?: Fade;
+}
+
+/**
+ * Creates a sample editor AWP that plays `initialClips` and connects it to a clip renderer so
+ * that time-stretched and pitch-shifted clips are rendered and re-rendered when the tempo changes.
+ * All sample editor nodes should be created with this so that their renderings are never left
+ * unanswered.
+ */
+export const createSampleEditorNode = async (
+  sampleData: SampleEditorSampleData[],
+  initialClips: SampleEditorClip[]
+): Promise<AudioWorkletNode> => {
+  const [wasmBytes] = await Promise.all([SampleEditorWasm.get(), SampleEditorAWPRegistered.get()]);
+
+  const node = new AudioWorkletNode(ctx, 'sample-editor-awp', {
+    numberOfInputs: 0,
+    numberOfOutputs: 1,
+  });
+  const samplesByID = new Map(sampleData.map(datum => [datum.id, datum.samples]));
+  connectClipRenderer(node, sampleDataID => samplesByID.get(sampleDataID));
+  node.port.postMessage({ type: 'init', wasmBytes, sampleData, initialSamples: initialClips });
+
+  return node;
+};
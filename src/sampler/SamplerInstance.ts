@@ -26,6 +26,36 @@ const SamplerWasm = new AsyncOnce(
   true
 );
 
+/**
+ * Slices shorter than this are merged into the previous slice when auto-slicing
+ */
+const MIN_AUTO_SLICE_LEN_SECS = 0.05;
+/**
+ * Slices are faded out over this many samples at their end to avoid clicks where the following
+ * slice's transient is cut off
+ */
+const AUTO_SLICE_END_CROSSFADE_LEN_SAMPLES = 64;
+/**
+ * Slices that would be mapped past the last MIDI note when auto-slicing are dropped
+ */
+const MIDI_NUMBER_COUNT = 128;
+
+const WavDecoder = new AsyncOnce(async () => {
+  const mod = await import('src/wav_decoder');
+  await mod.default();
+  return mod;
+}, true);
+
+/**
+ * Shortest loop that will be suggested by `suggestSelectionLoop`
+ */
+const MIN_SUGGESTED_LOOP_LEN_SECS = 0.1;
+
+/**
+ * Audio files in an imported SFZ folder that are decoded and made available to the instrument
+ */
+const SFZ_SAMPLE_FILE_RE = /\.(wav|flac|ogg|mp3|aif|aiff)$/i;
+
 interface ActiveSampleData {
   descriptor: SampleDescriptor;
   sampleData: AudioBuffer | null;
@@ -43,6 +73,11 @@ export class SamplerInstance {
   public midiNode: MIDINode;
   public awpHandle: AudioWorkletNode | null = null;
   public activeSample: Writable<ActiveSampleData | null> = writable(null);
+  /**
+   * Name of the SFZ file of the imported SFZ instrument, if one is loaded.  While it's loaded, its
+   * zones take over from the selections.
+   */
+  public loadedSFZName: Writable<string | null> = writable(null);
   public selections: Writable<SamplerSelection[]>;
   public activeSelectionIx: Writable<number | null>;
   public midiGateStatusBufferF32: Float32Array | null = null;
@@ -208,6 +243,8 @@ export class SamplerInstance {
         crossfadeEndLenSamples: selection.endCrossfadeLenSamples,
         playbackRate: selection.playbackRate,
         reverse: selection.reverse ?? false,
+        loopStartSampleIx: selection.loop?.startSampleIx ?? 0,
+        loopEndSampleIx: selection.loop?.endSampleIx ?? 0,
       },
     });
   }
@@ -273,6 +310,43 @@ export class SamplerInstance {
     this.midiNode.metadata.update(metadata => ({ ...metadata, noteMetadata: newNoteMetadata }));
   }
 
+  /**
+   * Finds the best loop points within the selection at `ix`, snapped to whole periods of its pitch,
+   * and sets them as its loop.  Returns `false` if no good loop points were found.
+   */
+  public async suggestSelectionLoop(ix: number): Promise<boolean> {
+    const sampleData = get(this.activeSample)?.sampleData;
+    const selection = get(this.selections)[ix];
+    if (!sampleData || selection?.startSampleIx == null || selection.endSampleIx == null) {
+      return false;
+    }
+
+    const wavDecoder = await WavDecoder.get();
+    const startSampleIx = Math.max(Math.round(selection.startSampleIx), 0);
+    const endSampleIx = Math.min(Math.round(selection.endSampleIx), sampleData.length);
+    const [loopStart, loopEnd] = wavDecoder.suggest_loop_points(
+      sampleData.getChannelData(0).subarray(startSampleIx, endSampleIx),
+      sampleData.sampleRate,
+      MIN_SUGGESTED_LOOP_LEN_SECS,
+      1,
+      true
+    );
+    if (loopStart === undefined || loopEnd === undefined) {
+      return false;
+    }
+
+    // The selection may have changed while the loop points were being computed
+    const curSelection = get(this.selections)[ix];
+    if (!curSelection) {
+      return false;
+    }
+    this.setSelection(ix, {
+      ...curSelection,
+      loop: { startSampleIx: startSampleIx + loopStart, endSampleIx: startSampleIx + loopEnd },
+    });
+    return true;
+  }
+
   /**
    * Listens for incoming MIDI events and returns the MIDI number of the first attack event received.
    */
@@ -288,6 +362,114 @@ export class SamplerInstance {
     });
   }
 
+  /**
+   * Splits the active sample into slices at detected onsets and replaces all existing selections
+   * with one selection per slice, mapped to consecutive MIDI notes starting at `firstMIDINumber`.
+   *
+   * @param sensitivity from 0 to 1; higher values produce more slices
+   * @returns the estimated tempo of the sample if it's treated as a loop, or `null` if it couldn't
+   * be estimated
+   */
+  public async autoSlice(firstMIDINumber: number, sensitivity: number): Promise<number | null> {
+    const sampleData = get(this.activeSample)?.sampleData;
+    if (!this.awpHandle || !sampleData) {
+      throw new Error('Cannot auto-slice before a sample is loaded');
+    }
+
+    // Onset detection runs over the whole sample, which is far too slow for the audio thread
+    const wavDecoder = await WavDecoder.get();
+    const [estimatedBPM, ...bounds] = wavDecoder.detect_slices(
+      sampleData.getChannelData(0),
+      sampleData.sampleRate,
+      sensitivity,
+      Math.round(MIN_AUTO_SLICE_LEN_SECS * sampleData.sampleRate)
+    );
+    const sliceCount = Math.min(bounds.length / 2, MIDI_NUMBER_COUNT - firstMIDINumber);
+
+    const newSelections: SamplerSelection[] = [];
+    for (let i = 0; i < sliceCount; i++) {
+      const [startSampleIx, endSampleIx] = [bounds[i * 2], bounds[i * 2 + 1]];
+      newSelections.push({
+        name: `slice ${i + 1}`,
+        startSampleIx,
+        endSampleIx,
+        startCrossfadeLenSamples: 0,
+        endCrossfadeLenSamples: Math.min(
+          AUTO_SLICE_END_CROSSFADE_LEN_SAMPLES,
+          (endSampleIx - startSampleIx) / 2
+        ),
+        midiNumber: firstMIDINumber + i,
+        playbackRate: 1,
+        reverse: false,
+      });
+    }
+    this.awpHandle.port.postMessage({ type: 'clearSelections' });
+    this.selections.set(newSelections);
+    this.activeSelectionIx.set(newSelections.length > 0 ? 0 : null);
+    for (const selection of newSelections) {
+      this.commitSelection(selection);
+    }
+    this.updateMIDINodeMetadata();
+
+    return estimatedBPM > 0 ? estimatedBPM : null;
+  }
+
+  /**
+   * Loads an SFZ instrument from the files of a folder containing an `.sfz` file along with the
+   * samples it references, replacing the active sample and selections.  Samples are decoded and
+   * resampled to the audio context's sample rate here; the SFZ file is parsed on the audio thread.
+   *
+   * SFZ instruments aren't persisted with the sampler's state and need to be re-imported after
+   * reloading.
+   */
+  public async importSFZ(files: File[]) {
+    if (!this.awpHandle) {
+      throw new Error('Cannot import SFZ before AWP initialized');
+    }
+    const sfzFile = files.find(file => file.name.toLowerCase().endsWith('.sfz'));
+    if (!sfzFile) {
+      throw new Error('No .sfz file found in the selected folder');
+    }
+
+    // Sample paths in the SFZ file are relative to the directory containing it
+    const getPath = (file: File) => file.webkitRelativePath || file.name;
+    const sfzDirPrefix = getPath(sfzFile).split('/').slice(0, -1).join('/') + '/';
+    const samplesByPath: Record<string, Float32Array> = {};
+    await Promise.all(
+      files.map(async file => {
+        const path = getPath(file);
+        if (!SFZ_SAMPLE_FILE_RE.test(file.name) || !path.startsWith(sfzDirPrefix)) {
+          return;
+        }
+
+        try {
+          const decoded = await ctx.decodeAudioData(await file.arrayBuffer());
+          const mono = new Float32Array(decoded.length);
+          for (let channelIx = 0; channelIx < decoded.numberOfChannels; channelIx++) {
+            const channel = decoded.getChannelData(channelIx);
+            for (let i = 0; i < mono.length; i++) {
+              mono[i] += channel[i] / decoded.numberOfChannels;
+            }
+          }
+          samplesByPath[path.slice(sfzDirPrefix.length)] = mono;
+        } catch (err) {
+          console.warn(`Error decoding SFZ sample "${path}": `, err);
+        }
+      })
+    );
+
+    await this.setSelectedSample(null);
+    // `TextEncoder` isn't available in all worklet scopes, so the SFZ file is sent as UTF-8 bytes
+    const sfzBytes = new TextEncoder().encode(await sfzFile.text());
+    this.awpHandle.port.postMessage({ type: 'loadSFZ', sfzBytes, samplesByPath });
+    this.loadedSFZName.set(sfzFile.name);
+  }
+
+  public unloadSFZ() {
+    this.awpHandle?.port.postMessage({ type: 'setZones', zones: [] });
+    this.loadedSFZName.set(null);
+  }
+
   public serialize(): SerializedSampler {
     return {
       activeSample: get(this.activeSample)?.descriptor || null,
@@ -21,6 +21,11 @@ export interface SamplerSelection {
   midiNumber?: number;
   playbackRate: number;
   reverse?: boolean;
+  /**
+   * Sustain loop in absolute sample indices.  The selection loops between these points while its
+   * note is held and plays through to its end once released.
+   */
+  loop?: { startSampleIx: number; endSampleIx: number } | null;
 }
 
 export const buildDefaultSamplerSelection = (): SamplerSelection => ({
@@ -11,8 +11,10 @@ import {
 import { Oscilloscope } from 'src/visualizations/Oscilloscope/Oscilloscope';
 import {
   buildDefaultOscilloscopeUIState,
+  normalizeOscilloscopeUIState,
   type OscilloscopeUIState,
 } from 'src/visualizations/Oscilloscope/types';
+import { StereoAnalyzer } from 'src/visualizations/StereoAnalyzer/StereoAnalyzer';
 
 const ctx = new AudioContext();
 const SignalAnalyzerAWPRegistered = new AsyncOnce(
@@ -38,24 +40,29 @@ export const buildDefaultSignalAnalyzerInstState = (): SerializedSignalAnalyzerI
 export class SignalAnalyzerInst {
   private destroyed = false;
   public input: AnalyserNode;
+  /**
+   * Mono inputs feeding oscilloscope channels 3 and 4
+   */
+  public auxInputs: GainNode[];
   private awpHandle: AudioWorkletNode | null = null;
   public oscilloscope: Oscilloscope;
   public lineSpectrogram: LineSpectrogram;
+  public stereoAnalyzer: StereoAnalyzer;
   // Need to connect the analyzer AWP to the audio graph so it gets driven
   private silentGain: GainNode;
   public oscilloscopeUIState: Writable<OscilloscopeUIState>;
 
   constructor(ctx: AudioContext, initialState: SerializedSignalAnalyzerInst) {
-    this.oscilloscopeUIState = writable(initialState.oscilloscopeUIState);
+    const oscilloscopeUIState = normalizeOscilloscopeUIState(initialState.oscilloscopeUIState);
+    this.oscilloscopeUIState = writable(oscilloscopeUIState);
     this.input = ctx.createAnalyser();
     this.input.fftSize = LineSpectrogramFFTSize;
-    this.input.minDecibels = initialState.lineSpectrogramUIState.rangeDb[0];
-    this.input.maxDecibels = initialState.lineSpectrogramUIState.rangeDb[1];
-    this.input.smoothingTimeConstant = initialState.lineSpectrogramUIState.smoothingCoeff;
+    this.auxInputs = [ctx.createGain(), ctx.createGain()];
     this.silentGain = ctx.createGain();
     this.silentGain.gain.value = 0;
     this.silentGain.connect(ctx.destination);
-    this.oscilloscope = new Oscilloscope(initialState.oscilloscopeUIState);
+    this.oscilloscope = new Oscilloscope(oscilloscopeUIState);
+    this.stereoAnalyzer = new StereoAnalyzer();
 
     this.lineSpectrogram = new LineSpectrogram(initialState.lineSpectrogramUIState, this.input);
 
@@ -69,6 +76,9 @@ export class SignalAnalyzerInst {
       case 'setSAB':
         this.oscilloscope.setSAB(e.data.sab);
         break;
+      case 'setStereoSAB':
+        this.stereoAnalyzer.setSAB(e.data.sab);
+        break;
       default:
         console.warn(`Unknown message type from signal analyzer AWP: ${(e.data as any).type}`);
     }
@@ -82,14 +92,15 @@ export class SignalAnalyzerInst {
     }
 
     this.awpHandle = new AudioWorkletNode(ctx, 'signal-analyzer-awp', {
-      numberOfInputs: 1,
-      channelCount: 1,
+      numberOfInputs: 1 + this.auxInputs.length,
+      channelCount: 2,
       numberOfOutputs: 1,
-      channelInterpretation: 'discrete',
+      channelInterpretation: 'speakers',
       channelCountMode: 'explicit',
     });
     this.awpHandle.port.onmessage = this.handleAWPMessage;
     this.input.connect(this.awpHandle);
+    this.auxInputs.forEach((auxInput, i) => auxInput.connect(this.awpHandle!, 0, i + 1));
     this.awpHandle.connect(this.silentGain);
 
     // We need to make sure the AWP doesn't send the SAB before we register our even listener
@@ -98,11 +109,13 @@ export class SignalAnalyzerInst {
 
   public pause() {
     this.oscilloscope.pause();
+    this.stereoAnalyzer.pause();
     this.lineSpectrogram.stop();
   }
 
   public resume() {
     this.oscilloscope.resume();
+    this.stereoAnalyzer.resume();
     this.lineSpectrogram.start();
   }
 
@@ -122,12 +135,14 @@ export class SignalAnalyzerInst {
 
     console.warn('DESTROYING SIGNAL ANALYZER');
     this.oscilloscope.destroy();
+    this.stereoAnalyzer.destroy();
     this.lineSpectrogram.destroy();
     if (this.awpHandle) {
       this.awpHandle.port.close();
       this.awpHandle.disconnect();
     }
     this.input.disconnect();
+    this.auxInputs.forEach(auxInput => auxInput.disconnect());
     this.silentGain.disconnect();
   }
 }
@@ -11,6 +11,7 @@ import { noop, tryParseJson } from 'src/util';
 
 interface SignalAnalyzerHandle {
   input: AnalyserNode;
+  auxInputs: GainNode[];
   pause: () => void;
   resume: () => void;
   serialize: () => SerializedSignalAnalyzerInst;
@@ -41,6 +42,7 @@ export const init_signal_analyzer = (stateKey: string) => {
     // all skipped.
     SignalAnalyzerInstsByStateKey.set(stateKey, {
       input: ctx.createAnalyser(),
+      auxInputs: [ctx.createGain(), ctx.createGain()],
       pause: noop,
       resume: noop,
       serialize: () => initialState,
@@ -128,12 +130,17 @@ export const get_signal_analyzer_audio_connectables = (stateKey: string): AudioC
   }
 
   const vcId = stateKey.split('_')[1];
-  return {
-    vcId,
-    inputs: ImmMap<string, ConnectableInput>().set('input', {
+  // The main input provides oscilloscope channels 1 and 2 (left and right)
+  const inputs = inst.auxInputs.reduce(
+    (acc, auxInput, i) => acc.set(`channel ${i + 3}`, { type: 'customAudio', node: auxInput }),
+    ImmMap<string, ConnectableInput>().set('input', {
       type: 'customAudio',
       node: inst.input,
-    }),
+    })
+  );
+  return {
+    vcId,
+    inputs,
     outputs: ImmMap<string, ConnectableOutput>(),
   };
 };
@@ -7,6 +7,7 @@ import {
   encodeRampFnParams,
   encodeRampFnType,
   type Adsr,
+  type AdsrLoopRegion,
   type AdsrStep,
 } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import { getSentry } from 'src/sentry';
@@ -42,6 +43,8 @@ export interface ADSR2Params {
   releaseStartPhase: number;
   steps: AdsrStep[];
   logScale?: boolean;
+  loops?: AdsrLoopRegion[];
+  releaseLoop?: AdsrLoopRegion | null;
 }
 
 export enum EarlyReleaseModeType {
@@ -109,6 +112,20 @@ export class ADSR2Module {
     return encoded;
   }
 
+  private static encodeLoops(loops: AdsrLoopRegion[]): Float32Array {
+    const ENCODED_LOOP_SIZE = 5;
+
+    const encoded = new Float32Array(loops.length * ENCODED_LOOP_SIZE);
+    loops.forEach((loop, i) => {
+      encoded[i * ENCODED_LOOP_SIZE] = loop.id;
+      encoded[i * ENCODED_LOOP_SIZE + 1] = loop.start;
+      encoded[i * ENCODED_LOOP_SIZE + 2] = loop.end;
+      encoded[i * ENCODED_LOOP_SIZE + 3] = loop.mode;
+      encoded[i * ENCODED_LOOP_SIZE + 4] = loop.repeatCount ?? -1;
+    });
+    return encoded;
+  }
+
   private async init(instanceCount: number) {
     const [wasmBytes] = await Promise.all([ADSRWasm.get(), ADSR2AWPRegistered.get()] as const);
     this.awp = new AudioWorkletNode(this.ctx, 'multi-adsr2-awp', {
@@ -131,6 +148,12 @@ export class ADSR2Module {
           this.audioThreadData.buffer = new Float32Array(
             evt.data.phaseDataBuffer as SharedArrayBuffer
           );
+          if (this.params.loops) {
+            this.setLoops(this.params.loops);
+          }
+          if (this.params.releaseLoop) {
+            this.setReleaseLoop(this.params.releaseLoop);
+          }
           this.onInitializedCbs.forEach(cb => cb());
           this.onInitializedCbs = [];
           break;
@@ -196,6 +219,8 @@ export class ADSR2Module {
     this.setSteps(newState.steps);
     this.setLoopPoint(newState.loopPoint);
     this.setReleaseStartPhase(newState.releasePoint);
+    this.setLoops(newState.loops ?? []);
+    this.setReleaseLoop(newState.releaseLoop ?? null);
     // this.setLength(newState.lengthMode, newState.lenSamples);
     this.setLogScale(newState.logScale ?? false);
   }
@@ -217,6 +242,32 @@ export class ADSR2Module {
     this.awp.port.postMessage({ type: 'setLoopPoint', loopPoint: newLoopPoint });
   }
 
+  public setLoops(newLoops: AdsrLoopRegion[]) {
+    this.params.loops = newLoops;
+    if (!this.awp) {
+      return;
+    }
+    const encodedLoops = ADSR2Module.encodeLoops(newLoops);
+    this.awp.port.postMessage({ type: 'setLoops', encodedLoops });
+  }
+
+  public setReleaseLoop(newReleaseLoop: AdsrLoopRegion | null) {
+    this.params.releaseLoop = newReleaseLoop;
+    if (!this.awp) {
+      return;
+    }
+    this.awp.port.postMessage({ type: 'setReleaseLoop', releaseLoop: newReleaseLoop });
+  }
+
+  /**
+   * Returns the id of the loop that the most recently gated ADSR is playing, or `null` if it isn't
+   * in a loop
+   */
+  public getActiveLoopId(): number | null {
+    const activeLoopId = this.audioThreadData.buffer?.[1];
+    return activeLoopId === undefined || activeLoopId < 0 ? null : activeLoopId;
+  }
+
   public setReleaseStartPhase(newReleaseStartPhase: number) {
     this.params.releaseStartPhase = newReleaseStartPhase;
     if (!this.awp) {
@@ -328,6 +379,8 @@ export class ADSR2Module {
       lenSamples: this.params.length,
       loopPoint: this.params.loopPoint ?? null,
       releasePoint: this.params.releaseStartPhase ?? null,
+      loops: R.clone(this.params.loops ?? []),
+      releaseLoop: this.params.releaseLoop ? { ...this.params.releaseLoop } : null,
       audioThreadData: { phaseIndex: 0, debugName: 'ADSR2Module.serialize()' },
       logScale: this.params.logScale ?? false,
     };
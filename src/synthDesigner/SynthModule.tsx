@@ -10,6 +10,11 @@ import {
 } from 'src/controls/adsr2/ControlPanelADSR2';
 import { renderGenericPresetSaverWithModal } from 'src/controls/GenericPresetPicker/GenericPresetSaver';
 import { ConnectedFMSynthUI } from 'src/fmSynth/FMSynthUI';
+import {
+  ADSR_NOTE_SCALING_SETTINGS,
+  applyAdsrNoteScalingChange,
+  buildAdsrNoteScalingControlPanelState,
+} from 'src/fmSynth/ParamSource';
 import type { Adsr, AdsrParams } from 'src/graphEditor/nodes/CustomAudio/FMSynth/FMSynth';
 import { updateConnectables } from 'src/patchNetwork/interface';
 import { store, type ReduxStore } from 'src/redux';
@@ -147,6 +152,7 @@ const buildSynthControlPanelSettings = (vcId: string) => [
     type: 'checkbox',
     label: 'log scale',
   },
+  ...ADSR_NOTE_SCALING_SETTINGS,
   {
     type: 'custom',
     label: 'gain envelope',
@@ -171,11 +177,12 @@ const SynthControlPanelInner: React.FC<SynthControlPanelProps> = props => {
           return;
         }
         case 'gain envelope': {
-          setGainEnvelope(val);
           const fmSynth = getState().synthDesigner.synths[props.index].fmSynth;
+          setGainEnvelope({ ...val, noteScaling: fmSynth.gainEnvelope.noteScaling });
           fmSynth.handleAdsrChange(-1, {
             ...val,
             lenSamples: { type: 'constant', value: msToSamples(gainADSRLengthMs) },
+            noteScaling: fmSynth.gainEnvelope.noteScaling,
           });
           return;
         }
@@ -208,6 +215,18 @@ const SynthControlPanelInner: React.FC<SynthControlPanelProps> = props => {
           return;
         }
         default: {
+          const fmSynth = getState().synthDesigner.synths[props.index].fmSynth;
+          const noteScaling = applyAdsrNoteScalingChange(
+            fmSynth.gainEnvelope.noteScaling,
+            key,
+            val
+          );
+          if (noteScaling) {
+            fmSynth.handleAdsrChange(-1, { ...fmSynth.gainEnvelope, noteScaling });
+            setGainEnvelope({ ...gainEnvelope, noteScaling });
+            return;
+          }
+
           console.warn('Unhandled key in synth control panel: ', key);
         }
       }
@@ -228,6 +247,7 @@ const SynthControlPanelInner: React.FC<SynthControlPanelProps> = props => {
       'gain envelope': gainEnvelopeState,
       'pitch multiplier': localPitchMultiplier ?? props.pitchMultiplier?.toString() ?? 1,
       'log scale': gainEnvelope.logScale,
+      ...buildAdsrNoteScalingControlPanelState(gainEnvelope.noteScaling),
     };
   }, [
     props.masterGain,
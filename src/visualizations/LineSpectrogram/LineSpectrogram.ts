@@ -1,11 +1,13 @@
 import { get, type Writable, writable } from 'svelte/store';
 
+import { getCurBeat } from 'src/eventScheduler';
 import { logError } from 'src/sentry';
 import { AsyncOnce } from 'src/util';
 import { LineSpectrogramFFTSize } from 'src/visualizations/LineSpectrogram/conf';
-import type {
-  LineSpectrogramUIState,
-  LineSpectrogramWorkerMessage,
+import {
+  normalizeLineSpectrogramUIState,
+  type LineSpectrogramUIState,
+  type LineSpectrogramWorkerMessage,
 } from 'src/visualizations/LineSpectrogram/types';
 
 const LineSpectrogramWasmBytes = new AsyncOnce(
@@ -19,9 +21,13 @@ const LineSpectrogramWasmBytes = new AsyncOnce(
 );
 
 /**
- * Visualization of the immediate spectrum of audio input signal.  Uses `AnalyserNode` to perform STFFT and
- * `OffscreenCanvas` to render the spectrogram.  The spectrogram is rendered as a smooth line with the line's
- * Y position at each point representing the amplitude of the frequency at that point.
+ * Visualization of the immediate spectrum of audio input signal.  Uses `AnalyserNode` to capture the
+ * input signal, performs the FFT and analysis in Wasm, and uses `OffscreenCanvas` to render the
+ * spectrogram.  The spectrogram is rendered as a smooth line with the line's Y position at each
+ * point representing the amplitude of the frequency at that point.
+ *
+ * It can alternatively render a scrolling spectrogram with time on the x axis and frequency on the
+ * y axis, optionally overlaid with a beat grid that follows the global transport.
  */
 export class LineSpectrogram {
   public store: Writable<LineSpectrogramUIState>;
@@ -32,14 +38,17 @@ export class LineSpectrogram {
   private renderWorker!: Worker;
   private notifySAB!: SharedArrayBuffer;
   private notifySABI32!: Int32Array;
-  private frequencyDataSAB!: SharedArrayBuffer;
-  private frequencyDataSABU8!: Uint8Array;
-  private frequencyDataBufTemp!: Uint8Array<ArrayBuffer>;
+  private timeDomainDataSAB!: SharedArrayBuffer;
+  private timeDomainDataSABF32!: Float32Array;
+  private timeDomainDataBufTemp!: Float32Array<ArrayBuffer>;
+  private curBeatSAB!: SharedArrayBuffer;
+  private curBeatSABF64!: Float64Array;
+  private unsubscribeStore: (() => void) | null = null;
   private running = false;
   private frameIx = 0;
 
   constructor(initialState: LineSpectrogramUIState, analyserNode: AnalyserNode) {
-    this.store = writable(initialState);
+    this.store = writable(normalizeLineSpectrogramUIState(initialState));
     if (analyserNode.fftSize !== LineSpectrogramFFTSize) {
       throw new Error(
         `LineSpectrogram requires analyserNode.fftSize to be ${LineSpectrogramFFTSize}, but it was ${analyserNode.fftSize}`
@@ -53,9 +62,13 @@ export class LineSpectrogram {
 
     this.notifySAB = new SharedArrayBuffer(4);
     this.notifySABI32 = new Int32Array(this.notifySAB);
-    this.frequencyDataSAB = new SharedArrayBuffer(LineSpectrogramFFTSize / 2);
-    this.frequencyDataSABU8 = new Uint8Array(this.frequencyDataSAB);
-    this.frequencyDataBufTemp = new Uint8Array(LineSpectrogramFFTSize / 2);
+    this.timeDomainDataSAB = new SharedArrayBuffer(
+      LineSpectrogramFFTSize * Float32Array.BYTES_PER_ELEMENT
+    );
+    this.timeDomainDataSABF32 = new Float32Array(this.timeDomainDataSAB);
+    this.timeDomainDataBufTemp = new Float32Array(LineSpectrogramFFTSize);
+    this.curBeatSAB = new SharedArrayBuffer(Float64Array.BYTES_PER_ELEMENT);
+    this.curBeatSABF64 = new Float64Array(this.curBeatSAB);
 
     this.init().catch(err => {
       logError('Error initializing oscilloscope', err);
@@ -67,13 +80,20 @@ export class LineSpectrogram {
     const msg: LineSpectrogramWorkerMessage = {
       type: 'setWasmBytes',
       wasmBytes,
-      frequencyDataSAB: this.frequencyDataSAB,
+      timeDomainDataSAB: this.timeDomainDataSAB,
       notifySAB: this.notifySAB,
+      curBeatSAB: this.curBeatSAB,
+      sampleRate: this.analyserNode.context.sampleRate,
     };
     this.renderWorker.postMessage(msg);
+
+    this.unsubscribeStore = this.store.subscribe(state => {
+      const msg: LineSpectrogramWorkerMessage = { type: 'setConfig', state };
+      this.renderWorker.postMessage(msg);
+    });
   }
 
-  // We need to drive animation from the main thread because getting the frequency data from the
+  // We need to drive animation from the main thread because getting the time domain data from the
   // analyser node can only be done on the main thread.
   private animate = () => {
     if (!this.running) {
@@ -84,8 +104,9 @@ export class LineSpectrogram {
     this.frameIx = frameIx;
 
     // Browser is hilarious and doesn't let us write to shared buffer directly, so we have to waste a copy.
-    this.analyserNode.getByteFrequencyData(this.frequencyDataBufTemp);
-    this.frequencyDataSABU8.set(this.frequencyDataBufTemp);
+    this.analyserNode.getFloatTimeDomainData(this.timeDomainDataBufTemp);
+    this.timeDomainDataSABF32.set(this.timeDomainDataBufTemp);
+    this.curBeatSABF64[0] = getCurBeat();
     Atomics.store(this.notifySABI32, 0, frameIx);
     Atomics.notify(this.notifySABI32, 0);
 
@@ -126,6 +147,7 @@ export class LineSpectrogram {
   }
 
   public destroy() {
+    this.unsubscribeStore?.();
     this.renderWorker?.terminate();
   }
 
@@ -1,10 +1,22 @@
-import type { LineSpectrogramWorkerMessage } from 'src/visualizations/LineSpectrogram/types';
+import {
+  SpectrogramColorScalings,
+  SpectrogramColorSchemes,
+  SpectrogramFrequencyScales,
+  SpectrumAveragingModes,
+  SpectrumDisplayModes,
+  SpectrumWindows,
+  type LineSpectrogramUIState,
+  type LineSpectrogramWorkerMessage,
+} from 'src/visualizations/LineSpectrogram/types';
 
 class LineSpectrogramWorker {
   private wasmInstance: WebAssembly.Instance | null = null;
   private wasmMemoryBufferU8Clamped: Uint8ClampedArray<ArrayBuffer> = new Uint8ClampedArray(0);
   private notifySABI32: Int32Array | null = null;
-  private frequencyDataSABU8: Uint8Array | null = null;
+  private timeDomainDataSABF32: Float32Array | null = null;
+  private curBeatSABF64: Float64Array | null = null;
+  private sampleRate = 44_100;
+  private config: LineSpectrogramUIState | null = null;
   private canvas: OffscreenCanvas | null = null;
   private ctx: OffscreenCanvasRenderingContext2D | null = null;
   private running = true;
@@ -19,9 +31,15 @@ class LineSpectrogramWorker {
     switch (evt.data.type) {
       case 'setWasmBytes':
         this.notifySABI32 = new Int32Array(evt.data.notifySAB);
-        this.frequencyDataSABU8 = new Uint8Array(evt.data.frequencyDataSAB);
+        this.timeDomainDataSABF32 = new Float32Array(evt.data.timeDomainDataSAB);
+        this.curBeatSABF64 = new Float64Array(evt.data.curBeatSAB);
+        this.sampleRate = evt.data.sampleRate;
         this.setWasmBytes(evt.data.wasmBytes);
         break;
+      case 'setConfig':
+        this.config = evt.data.state;
+        this.maybeSetConfigToWasm();
+        break;
       case 'setCanvas':
         this.canvas = evt.data.canvas;
         this.dpr = evt.data.dpr;
@@ -69,11 +87,44 @@ class LineSpectrogramWorker {
         log_info: (ptr: number, len: number) => console.log(decodeStr(ptr, len)),
       },
     });
+    (this.wasmInstance.exports.line_spectrogram_set_sample_rate as (sampleRate: number) => void)(
+      this.sampleRate
+    );
 
     this.maybeSetViewToWasm();
+    this.maybeSetConfigToWasm();
     this.maybeStartAnimationLoop();
   }
 
+  private maybeSetConfigToWasm() {
+    if (!this.wasmInstance || !this.config) {
+      return;
+    }
+
+    const config = this.config;
+    (this.wasmInstance.exports.line_spectrogram_set_config as Function)(
+      config.fftSize,
+      Math.max(SpectrumWindows.indexOf(config.window), 0),
+      config.octaveSmoothing,
+      Math.max(SpectrumAveragingModes.indexOf(config.averaging), 0),
+      config.averagingTimeMs,
+      config.peakHold,
+      config.peakHoldMs,
+      config.peakDecayDbPerSecond,
+      config.tiltDbPerOctave,
+      config.rangeDb[0],
+      config.rangeDb[1]
+    );
+    (this.wasmInstance.exports.line_spectrogram_set_display as Function)(
+      Math.max(SpectrumDisplayModes.indexOf(config.display), 0),
+      Math.max(SpectrogramFrequencyScales.indexOf(config.spectrogram.frequencyScale), 0),
+      Math.max(SpectrogramColorSchemes.indexOf(config.spectrogram.colorScheme), 0),
+      Math.max(SpectrogramColorScalings.indexOf(config.spectrogram.colorScaling), 0),
+      config.spectrogram.columnsPerSecond,
+      config.spectrogram.beatsPerBar
+    );
+  }
+
   private resizeView(width: number, height: number) {
     if (!this.canvas || !this.ctx) {
       console.error('Tried to resize view before canvas was set');
@@ -103,7 +154,8 @@ class LineSpectrogramWorker {
       !this.running ||
       !this.wasmInstance ||
       !this.notifySABI32 ||
-      !this.frequencyDataSABU8 ||
+      !this.timeDomainDataSABF32 ||
+      !this.curBeatSABF64 ||
       !this.canvas ||
       !this.ctx
     ) {
@@ -120,15 +172,20 @@ class LineSpectrogramWorker {
       );
     }
 
-    const frequencyDataU8 = this.frequencyDataSABU8;
-    const frequencyDataBufPtr = (
-      this.wasmInstance.exports.line_spectrogram_get_frequency_data_ptr as () => number
+    const timeDomainDataF32 = this.timeDomainDataSABF32;
+    const curBeatF64 = this.curBeatSABF64;
+    const timeDomainDataBufPtr = (
+      this.wasmInstance.exports.line_spectrogram_get_time_domain_data_ptr as () => number
     )();
-    const process = this.wasmInstance.exports.line_spectrogram_process as () => void;
+    const process = this.wasmInstance.exports.line_spectrogram_process as (
+      dtMs: number,
+      curBeat: number
+    ) => void;
     const getImageDataPtr = this.wasmInstance.exports
       .line_spectrogram_get_image_data_ptr as () => number;
 
     let lastRenderedFrameIx = -1;
+    let lastProcessTime = performance.now();
 
     while (true) {
       if (this.runToken !== runToken) {
@@ -150,10 +207,15 @@ class LineSpectrogramWorker {
       }
       lastRenderedFrameIx = Atomics.load(this.notifySABI32, 0);
 
-      // We have fresh frequency data to process
+      // We have fresh time domain data to process
       let memoryU8 = this.getWasmMemoryBufferU8Clamped();
-      memoryU8.set(frequencyDataU8, frequencyDataBufPtr);
-      process();
+      new Float32Array(memoryU8.buffer, timeDomainDataBufPtr, timeDomainDataF32.length).set(
+        timeDomainDataF32
+      );
+      const now = performance.now();
+      // cap the step so that averaging and peak decay don't jump after the tab was backgrounded
+      process(Math.min(now - lastProcessTime, 250), curBeatF64[0]);
+      lastProcessTime = now;
 
       memoryU8 = this.getWasmMemoryBufferU8Clamped();
       const imageDataPtr = getImageDataPtr();
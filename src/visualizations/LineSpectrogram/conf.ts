@@ -1 +1,7 @@
-export const LineSpectrogramFFTSize = 4096;
+/**
+ * Size of the `AnalyserNode`'s time-domain buffer.  The spectrum is computed in Wasm from the most
+ * recent `fftSize` samples of it, so this must be at least the largest selectable FFT size.
+ */
+export const LineSpectrogramFFTSize = 32_768;
+
+export const LineSpectrogramFFTSizes = [1024, 2048, 4096, 8192, 16_384, 32_768];
@@ -2,8 +2,13 @@ export type LineSpectrogramWorkerMessage =
   | {
       type: 'setWasmBytes';
       wasmBytes: ArrayBuffer;
-      frequencyDataSAB: SharedArrayBuffer;
+      timeDomainDataSAB: SharedArrayBuffer;
       notifySAB: SharedArrayBuffer;
+      curBeatSAB: SharedArrayBuffer;
+      /**
+       * Sample rate of the audio context that the analyzed signal comes from
+       */
+      sampleRate: number;
     }
   | {
       type: 'setCanvas';
@@ -11,15 +16,131 @@ export type LineSpectrogramWorkerMessage =
       dpr: number;
     }
   | { type: 'resizeCanvas'; width: number; height: number }
+  | { type: 'setConfig'; state: LineSpectrogramUIState }
   | { type: 'start' }
   | { type: 'stop' };
 
+/**
+ * Indices match the values of `WindowType` in the Rust code
+ */
+export const SpectrumWindows = [
+  'rectangular',
+  'hann',
+  'hamming',
+  'blackman',
+  'blackman-harris',
+  'flat-top',
+] as const;
+export type SpectrumWindow = (typeof SpectrumWindows)[number];
+
+/**
+ * Indices match the values of `AveragingMode` in the Rust code
+ */
+export const SpectrumAveragingModes = ['none', 'exponential', 'linear'] as const;
+export type SpectrumAveragingMode = (typeof SpectrumAveragingModes)[number];
+
+/**
+ * Indices match the values of `DisplayMode` in the Rust code
+ */
+export const SpectrumDisplayModes = ['line', 'spectrogram'] as const;
+export type SpectrumDisplayMode = (typeof SpectrumDisplayModes)[number];
+
+/**
+ * Indices match the values of `FrequencyScale` in the Rust code
+ */
+export const SpectrogramFrequencyScales = ['linear', 'log', 'mel'] as const;
+export type SpectrogramFrequencyScale = (typeof SpectrogramFrequencyScales)[number];
+
+/**
+ * Indices match the palette color functions in the Rust code
+ */
+export const SpectrogramColorSchemes = ['pink', 'red-yellow-blue', 'radar'] as const;
+export type SpectrogramColorScheme = (typeof SpectrogramColorSchemes)[number];
+
+/**
+ * Indices match the scaler functions in the Rust code
+ */
+export const SpectrogramColorScalings = ['linear', 'exponential'] as const;
+export type SpectrogramColorScaling = (typeof SpectrogramColorScalings)[number];
+
+export interface SpectrogramUIState {
+  frequencyScale: SpectrogramFrequencyScale;
+  colorScheme: SpectrogramColorScheme;
+  colorScaling: SpectrogramColorScaling;
+  columnsPerSecond: number;
+  /**
+   * Every Nth line of the beat grid is drawn brighter.  0 hides the grid.
+   */
+  beatsPerBar: number;
+}
+
 export interface LineSpectrogramUIState {
+  display: SpectrumDisplayMode;
+  /**
+   * Only used when `display` is 'spectrogram'.  `rangeDb` sets the range of levels mapped to the
+   * color scheme.
+   */
+  spectrogram: SpectrogramUIState;
   rangeDb: [number, number];
-  smoothingCoeff: number;
+  fftSize: number;
+  window: SpectrumWindow;
+  /**
+   * Smooths each point over 1/N octaves.  0 disables smoothing.
+   */
+  octaveSmoothing: number;
+  averaging: SpectrumAveragingMode;
+  averagingTimeMs: number;
+  peakHold: boolean;
+  peakHoldMs: number;
+  peakDecayDbPerSecond: number;
+  /**
+   * Slope added to the spectrum, pivoting at 1 kHz.  3 dB/octave shows pink noise as flat.
+   */
+  tiltDbPerOctave: number;
 }
 
+export const buildDefaultSpectrogramUIState = (): SpectrogramUIState => ({
+  frequencyScale: 'log',
+  colorScheme: 'radar',
+  colorScaling: 'linear',
+  columnsPerSecond: 60,
+  beatsPerBar: 0,
+});
+
 export const buildDefaultLineSpecrogramUIState = (): LineSpectrogramUIState => ({
-  rangeDb: [-80, -20],
-  smoothingCoeff: 0.9,
+  display: 'line',
+  spectrogram: buildDefaultSpectrogramUIState(),
+  rangeDb: [-90, 0],
+  fftSize: 8192,
+  window: 'blackman-harris',
+  octaveSmoothing: 0,
+  averaging: 'exponential',
+  averagingTimeMs: 150,
+  peakHold: false,
+  peakHoldMs: 1000,
+  peakDecayDbPerSecond: 20,
+  tiltDbPerOctave: 0,
 });
+
+/**
+ * Fills in settings missing from states serialized before they were added
+ */
+export const normalizeLineSpectrogramUIState = (
+  state: Partial<LineSpectrogramUIState>
+): LineSpectrogramUIState => {
+  const defaults = buildDefaultLineSpecrogramUIState();
+  return {
+    display: state.display ?? defaults.display,
+    spectrogram: { ...defaults.spectrogram, ...state.spectrogram },
+    rangeDb: state.rangeDb ?? defaults.rangeDb,
+    fftSize: state.fftSize ?? defaults.fftSize,
+    window: state.window ?? defaults.window,
+    octaveSmoothing: state.octaveSmoothing ?? defaults.octaveSmoothing,
+    averaging: state.averaging ?? defaults.averaging,
+    averagingTimeMs: state.averagingTimeMs ?? defaults.averagingTimeMs,
+    peakHold: state.peakHold ?? defaults.peakHold,
+    peakHoldMs: state.peakHoldMs ?? defaults.peakHoldMs,
+    peakDecayDbPerSecond: state.peakDecayDbPerSecond ?? defaults.peakDecayDbPerSecond,
+    tiltDbPerOctave: state.tiltDbPerOctave ?? defaults.tiltDbPerOctave,
+  };
+};
@@ -1,6 +1,8 @@
 import { logError } from 'src/sentry';
 import { AsyncOnce } from 'src/util';
 import type {
+  OscilloscopeDisplayMode,
+  OscilloscopeTrigger,
   OscilloscopeUIState,
   OscilloscopeWindowType,
   OscilloscopeWorkerMessage,
@@ -29,6 +31,11 @@ export class Oscilloscope {
     this.setWindow(initialState.window.type, initialState.window.value);
     this.setFrozen(initialState.frozen);
     this.setFrameByFrame(initialState.frameByFrame);
+    this.setChannelCount(initialState.channelCount);
+    this.setDisplayMode(initialState.displayMode);
+    this.setVerticalZoom(initialState.verticalZoom);
+    this.setTrigger(initialState.trigger);
+    this.setShowMeasurements(initialState.showMeasurements);
   }
 
   private async init() {
@@ -79,6 +86,40 @@ export class Oscilloscope {
     this.renderWorker.postMessage(msg);
   }
 
+  public setChannelCount(channelCount: number) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setChannelCount', channelCount };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setDisplayMode(displayMode: OscilloscopeDisplayMode) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setDisplayMode', displayMode };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setVerticalZoom(verticalZoom: number) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setVerticalZoom', verticalZoom };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setTrigger(trigger: OscilloscopeTrigger) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setTrigger', trigger };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setShowMeasurements(showMeasurements: boolean) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setShowMeasurements', showMeasurements };
+    this.renderWorker.postMessage(msg);
+  }
+
+  /**
+   * Sets the measurement cursor position in CSS pixels from the left edge of the view, or `null`
+   * to hide it
+   */
+  public setCursor(cursorX: number | null) {
+    const msg: OscilloscopeWorkerMessage = { type: 'setCursor', cursorX };
+    this.renderWorker.postMessage(msg);
+  }
+
   public pause() {
     Atomics.store(this.sab, 0, 1);
   }
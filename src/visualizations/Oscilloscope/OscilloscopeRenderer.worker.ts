@@ -9,9 +9,15 @@
 //             so you read sabF32[7] to get the first sample in the buffer, sabF32[8] to get the second, etc.
 //             It is incremented by the realtime audio rendering thread each time it renders a frame
 // 8-: [float32][] circular buffer containing raw samples from the realtime audio rendering thread.  It
-//                 extends to the end of the SAB.
+//                 extends to the end of the SAB.  Each frame is stored as `FRAME_SIZE` samples for
+//                 each of the `OSCILLOSCOPE_MAX_CHANNEL_COUNT` channels, one channel after another.
 
 import {
+  OSCILLOSCOPE_MAX_CHANNEL_COUNT,
+  OscilloscopeChannelColors,
+  OscilloscopeDisplayMode,
+  type OscilloscopeTrigger,
+  OscilloscopeTriggerEdge,
   type OscilloscopeWindow,
   OscilloscopeWindowType,
   type OscilloscopeWorkerMessage,
@@ -24,6 +30,12 @@ enum RendererStatusFlag {
 }
 
 const FRAME_SIZE = 128;
+const MULTI_CHANNEL_FRAME_SIZE = FRAME_SIZE * OSCILLOSCOPE_MAX_CHANNEL_COUNT;
+/**
+ * Per-channel readings returned by `oscilloscope_renderer_compute_measurements` after the leading
+ * cursor time: peak-to-peak, frequency, RMS, value at cursor
+ */
+const MEASUREMENT_COUNT = 4;
 
 class OscilloscopeRendererWorker {
   private sabF32: Float32Array | null = null;
@@ -40,6 +52,17 @@ class OscilloscopeRendererWorker {
   private frozen = false;
   private frameByFrame = true;
   private snapF0ToMIDI = false;
+  private channelCount = 1;
+  private displayMode = OscilloscopeDisplayMode.TimeDomain;
+  private verticalZoom = 1;
+  private trigger: OscilloscopeTrigger = {
+    enabled: false,
+    edge: OscilloscopeTriggerEdge.Rising,
+    level: 0,
+    holdoffMs: 0,
+  };
+  private showMeasurements = false;
+  private cursorX: number | null = null;
   private dpr = 1;
   private runToken = 0;
 
@@ -74,6 +97,10 @@ class OscilloscopeRendererWorker {
     const imageDataObj = new ImageData(imageData, this.view.width, this.view.height);
     this.ctx.putImageData(imageDataObj, 0, 0);
 
+    if (this.showMeasurements) {
+      this.drawMeasurements();
+    }
+
     if (this.window.type === OscilloscopeWindowType.Wavelengths) {
       // Print estimated F0
       const strPtr = (
@@ -97,6 +124,61 @@ class OscilloscopeRendererWorker {
     }
   };
 
+  private drawMeasurements() {
+    const ctx = this.ctx!;
+    const view = this.view!;
+    const computeMeasurements = this.wasmInstance!.exports
+      .oscilloscope_renderer_compute_measurements as (cursorX: number) => number;
+    const measurementsPtr = computeMeasurements(this.cursorX ?? -1);
+    const memoryF32 = this.getWasmMemoryBufferF32();
+    const measurements = memoryF32.slice(
+      measurementsPtr / 4,
+      measurementsPtr / 4 + 1 + OSCILLOSCOPE_MAX_CHANNEL_COUNT * MEASUREMENT_COUNT
+    );
+
+    // The cursor maps to a position in time, which doesn't exist in XY mode
+    const showCursor =
+      this.cursorX !== null && this.displayMode === OscilloscopeDisplayMode.TimeDomain;
+    if (showCursor) {
+      const x = Math.round(this.cursorX! * this.dpr) + 0.5;
+      ctx.strokeStyle = 'rgba(255, 255, 255, 0.5)';
+      ctx.lineWidth = 1;
+      ctx.beginPath();
+      ctx.moveTo(x, 0);
+      ctx.lineTo(x, view.height);
+      ctx.stroke();
+    }
+
+    const fmt = (val: number, digits = 3) => (Number.isFinite(val) ? val.toFixed(digits) : '---');
+    const lineHeight = 14 * this.dpr;
+    ctx.font = `${
+      12 * this.dpr
+    }px Hack, "Oxygen Mono", "Ubuntu Mono", "Lucida Console", monospace`;
+    let y = lineHeight;
+    if (showCursor) {
+      ctx.fillStyle = 'white';
+      ctx.fillText(`cursor: ${fmt(measurements[0], 2)}ms`, 4, y);
+      y += lineHeight;
+    }
+
+    for (let channelIx = 0; channelIx < this.channelCount; channelIx++) {
+      const offset = 1 + channelIx * MEASUREMENT_COUNT;
+      const [peakToPeak, frequency, rms, valueAtCursor] = measurements.subarray(
+        offset,
+        offset + MEASUREMENT_COUNT
+      );
+      let line = `ch${channelIx + 1}  p-p: ${fmt(peakToPeak)}  freq: ${
+        frequency > 0 ? `${frequency.toFixed(2)}Hz` : '---'
+      }  rms: ${fmt(rms)}`;
+      if (showCursor) {
+        line += `  cursor: ${fmt(valueAtCursor)}`;
+      }
+      ctx.fillStyle = OscilloscopeChannelColors[channelIx];
+      ctx.fillText(line, 4, y);
+      y += lineHeight;
+    }
+  }
+
   private startRenderLoop = () => {
     this.renderFrame();
     requestAnimationFrame(this.startRenderLoop);
@@ -149,6 +231,24 @@ class OscilloscopeRendererWorker {
       case 'setSnapF0ToMIDI':
         this.setSnapF0ToMIDI(message.snapF0ToMIDI);
         break;
+      case 'setChannelCount':
+        this.setChannelCount(message.channelCount);
+        break;
+      case 'setDisplayMode':
+        this.setDisplayMode(message.displayMode);
+        break;
+      case 'setVerticalZoom':
+        this.setVerticalZoom(message.verticalZoom);
+        break;
+      case 'setTrigger':
+        this.setTrigger(message.trigger);
+        break;
+      case 'setShowMeasurements':
+        this.showMeasurements = message.showMeasurements;
+        break;
+      case 'setCursor':
+        this.cursorX = message.cursorX;
+        break;
       default:
         console.warn(
           `Unknown message type in \`OscilloscopeRendererWorker\`: ${(message as any).type}`
@@ -165,6 +265,46 @@ class OscilloscopeRendererWorker {
     }
   }
 
+  private setChannelCount(channelCount: number) {
+    this.channelCount = channelCount;
+    if (this.wasmInstance) {
+      const setChannelCount = this.wasmInstance.exports
+        .oscilloscope_renderer_set_channel_count as (channelCount: number) => void;
+      setChannelCount(channelCount);
+    }
+  }
+
+  private setDisplayMode(displayMode: OscilloscopeDisplayMode) {
+    this.displayMode = displayMode;
+    if (this.wasmInstance) {
+      const setDisplayMode = this.wasmInstance.exports
+        .oscilloscope_renderer_set_display_mode as (displayMode: OscilloscopeDisplayMode) => void;
+      setDisplayMode(displayMode);
+    }
+  }
+
+  private setVerticalZoom(verticalZoom: number) {
+    this.verticalZoom = verticalZoom;
+    if (this.wasmInstance) {
+      const setVerticalZoom = this.wasmInstance.exports
+        .oscilloscope_renderer_set_vertical_zoom as (verticalZoom: number) => void;
+      setVerticalZoom(verticalZoom);
+    }
+  }
+
+  private setTrigger(trigger: OscilloscopeTrigger) {
+    this.trigger = trigger;
+    if (this.wasmInstance) {
+      const setTrigger = this.wasmInstance.exports.oscilloscope_renderer_set_trigger as (
+        enabled: boolean,
+        edge: OscilloscopeTriggerEdge,
+        level: number,
+        holdoffMs: number
+      ) => void;
+      setTrigger(trigger.enabled, trigger.edge, trigger.level, trigger.holdoffMs);
+    }
+  }
+
   private maybeSetViewToWasm() {
     if (!this.sabF32 || !this.view || !this.wasmInstance) {
       return;
@@ -218,9 +358,9 @@ class OscilloscopeRendererWorker {
 
     this.sabF32 = new Float32Array(sab);
     this.samplesCircularBuffer = this.sabF32.subarray(8);
-    if (this.samplesCircularBuffer.length % FRAME_SIZE !== 0) {
+    if (this.samplesCircularBuffer.length % MULTI_CHANNEL_FRAME_SIZE !== 0) {
       throw new Error(
-        `Invalid SAB size: ${this.samplesCircularBuffer.length} bytes.  Must be a multiple of ${FRAME_SIZE} (frame size) bytes.`
+        `Invalid SAB size: ${this.samplesCircularBuffer.length} samples.  Must be a multiple of ${MULTI_CHANNEL_FRAME_SIZE} (frame size * channel count) samples.`
       );
     }
     this.sabI32 = new Int32Array(sab);
@@ -243,6 +383,10 @@ class OscilloscopeRendererWorker {
     this.setFrozen(this.frozen);
     this.setFrameByFrame(this.frameByFrame);
     this.setSnapF0ToMIDI(this.snapF0ToMIDI);
+    this.setChannelCount(this.channelCount);
+    this.setDisplayMode(this.displayMode);
+    this.setVerticalZoom(this.verticalZoom);
+    this.setTrigger(this.trigger);
     this.checkAndStart();
   }
 
@@ -315,7 +459,7 @@ class OscilloscopeRendererWorker {
           return;
         }
 
-        if (bufferHeadIx % FRAME_SIZE !== 0) {
+        if (bufferHeadIx % MULTI_CHANNEL_FRAME_SIZE !== 0) {
           throw new Error(`Buffer head index is not a multiple of frame size: ${bufferHeadIx}`);
         }
 
@@ -335,15 +479,18 @@ class OscilloscopeRendererWorker {
         // until we've caught up to the current buffer head index
         while (this.lastProcessedBufferHeadIx !== bufferHeadIx) {
           const memory = this.getWasmMemoryBufferF32();
-          const frameData = memory.subarray(frameDataPtr / 4, frameDataPtr / 4 + FRAME_SIZE);
+          const frameData = memory.subarray(
+            frameDataPtr / 4,
+            frameDataPtr / 4 + MULTI_CHANNEL_FRAME_SIZE
+          );
 
           // Buffer head index has not wrapped around
           const freshSamples = this.samplesCircularBuffer.subarray(
             this.lastProcessedBufferHeadIx,
-            this.lastProcessedBufferHeadIx + FRAME_SIZE
+            this.lastProcessedBufferHeadIx + MULTI_CHANNEL_FRAME_SIZE
           );
           frameData.set(freshSamples);
-          this.lastProcessedBufferHeadIx += FRAME_SIZE;
+          this.lastProcessedBufferHeadIx += MULTI_CHANNEL_FRAME_SIZE;
 
           commitSamples();
 
@@ -10,6 +10,35 @@ export interface OscilloscopeWindow {
   value: number;
 }
 
+export const OSCILLOSCOPE_MAX_CHANNEL_COUNT = 4;
+
+/**
+ * Line colors for each channel; must match `CHANNEL_COLORS` in the Rust `oscilloscope` crate
+ */
+export const OscilloscopeChannelColors = [
+  'rgb(180, 0, 180)',
+  'rgb(0, 170, 190)',
+  'rgb(200, 170, 0)',
+  'rgb(40, 190, 60)',
+];
+
+export enum OscilloscopeDisplayMode {
+  TimeDomain = 0,
+  XY = 1,
+}
+
+export enum OscilloscopeTriggerEdge {
+  Rising = 0,
+  Falling = 1,
+}
+
+export interface OscilloscopeTrigger {
+  enabled: boolean;
+  edge: OscilloscopeTriggerEdge;
+  level: number;
+  holdoffMs: number;
+}
+
 export type OscilloscopeWorkerMessage =
   | { type: 'setSAB'; sab: SharedArrayBuffer }
   | { type: 'setWasmBytes'; wasmBytes: ArrayBuffer }
@@ -18,7 +47,13 @@ export type OscilloscopeWorkerMessage =
   | { type: 'setFrozen'; frozen: boolean }
   | { type: 'setFrameByFrame'; frameByFrame: boolean }
   | { type: 'resizeView'; newWidth: number; newHeight: number }
-  | { type: 'setSnapF0ToMIDI'; snapF0ToMIDI: boolean };
+  | { type: 'setSnapF0ToMIDI'; snapF0ToMIDI: boolean }
+  | { type: 'setChannelCount'; channelCount: number }
+  | { type: 'setDisplayMode'; displayMode: OscilloscopeDisplayMode }
+  | { type: 'setVerticalZoom'; verticalZoom: number }
+  | { type: 'setTrigger'; trigger: OscilloscopeTrigger }
+  | { type: 'setShowMeasurements'; showMeasurements: boolean }
+  | { type: 'setCursor'; cursorX: number | null };
 
 export interface OscilloscopeUIState {
   window: OscilloscopeWindow;
@@ -26,6 +61,18 @@ export interface OscilloscopeUIState {
   frozen: boolean;
   frameByFrame: boolean;
   snapF0ToMIDI: boolean;
+  /**
+   * Number of overlaid channels.  Channels 1 and 2 are the left and right channels of the main
+   * input and channels 3 and 4 come from the auxiliary inputs.
+   */
+  channelCount: number;
+  displayMode: OscilloscopeDisplayMode;
+  /**
+   * Vertical magnification applied on top of the auto-scaled y range.  Values above 1 zoom in.
+   */
+  verticalZoom: number;
+  trigger: OscilloscopeTrigger;
+  showMeasurements: boolean;
 }
 
 export const buildDefaultOscilloscopeUIState = (): OscilloscopeUIState => ({
@@ -42,4 +89,35 @@ export const buildDefaultOscilloscopeUIState = (): OscilloscopeUIState => ({
   frozen: false,
   frameByFrame: true,
   snapF0ToMIDI: false,
+  channelCount: 1,
+  displayMode: OscilloscopeDisplayMode.TimeDomain,
+  verticalZoom: 1,
+  trigger: {
+    enabled: false,
+    edge: OscilloscopeTriggerEdge.Rising,
+    level: 0,
+    holdoffMs: 0,
+  },
+  showMeasurements: false,
 });
+
+/**
+ * Fills in settings missing from states serialized before they were added
+ */
+export const normalizeOscilloscopeUIState = (
+  state: Partial<OscilloscopeUIState>
+): OscilloscopeUIState => {
+  const defaults = buildDefaultOscilloscopeUIState();
+  return {
+    window: state.window ?? defaults.window,
+    lastValueByWindowType: { ...defaults.lastValueByWindowType, ...state.lastValueByWindowType },
+    frozen: state.frozen ?? defaults.frozen,
+    frameByFrame: state.frameByFrame ?? defaults.frameByFrame,
+    snapF0ToMIDI: state.snapF0ToMIDI ?? defaults.snapF0ToMIDI,
+    channelCount: state.channelCount ?? defaults.channelCount,
+    displayMode: state.displayMode ?? defaults.displayMode,
+    verticalZoom: state.verticalZoom ?? defaults.verticalZoom,
+    trigger: { ...defaults.trigger, ...state.trigger },
+    showMeasurements: state.showMeasurements ?? defaults.showMeasurements,
+  };
+};
@@ -0,0 +1,68 @@
+import { logError } from 'src/sentry';
+import { AsyncOnce } from 'src/util';
+import type { StereoAnalyzerWorkerMessage } from 'src/visualizations/StereoAnalyzer/types';
+
+const StereoAnalyzerWasmBytes = new AsyncOnce(
+  () =>
+    fetch(
+      process.env.ASSET_PATH +
+        'stereo_analyzer.wasm?cacheBust=' +
+        (window.location.href.includes('localhost') ? '' : genRandomStringID())
+    ).then(res => res.arrayBuffer()),
+  true
+);
+
+/**
+ * Phase correlation meter, per-band stereo width, and goniometer.  Rendering happens in a worker
+ * which reads stereo samples from a SAB written by the signal analyzer AWP.
+ */
+export class StereoAnalyzer {
+  private renderWorker: Worker;
+  private sab: Int32Array = new Int32Array(8);
+
+  constructor() {
+    this.renderWorker = new Worker(new URL('./StereoAnalyzerRenderer.worker.ts', import.meta.url), {
+      type: 'module',
+    });
+    this.init().catch(err => {
+      logError('Error initializing stereo analyzer', err);
+    });
+  }
+
+  private async init() {
+    const wasmBytes = await StereoAnalyzerWasmBytes.get();
+    const msg: StereoAnalyzerWorkerMessage = { type: 'setWasmBytes', wasmBytes };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setSAB(sab: SharedArrayBuffer) {
+    this.sab = new Int32Array(sab);
+    const msg: StereoAnalyzerWorkerMessage = { type: 'setSAB', sab };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public setView(view: OffscreenCanvas, dpr: number) {
+    // dpr must be an integer
+    if (dpr !== Math.floor(dpr)) {
+      throw new Error('dpr must be an integer for stereo analyzer');
+    }
+    this.renderWorker.postMessage({ type: 'setView', view, dpr }, [view]);
+  }
+
+  public resizeView(newWidth: number, newHeight: number) {
+    const msg: StereoAnalyzerWorkerMessage = { type: 'resizeView', newWidth, newHeight };
+    this.renderWorker.postMessage(msg);
+  }
+
+  public pause() {
+    Atomics.store(this.sab, 0, 1);
+  }
+
+  public resume() {
+    Atomics.store(this.sab, 0, 0);
+  }
+
+  public destroy() {
+    this.renderWorker.terminate();
+  }
+}
@@ -0,0 +1,272 @@
+// SAB Layout (32-bit increments):
+// 0: [int32] shutdown / pause flag.  0 = running, 1 = paused, 2 = shutdown
+// 1: [int32] reserved
+// 2: [int32] incremented by realtime audio rendering thread each time it renders a frame into the buffer
+// 7: [int32] circular buffer head index.  This is zero-indexed relative to the start of the buffer.
+// 8-: [float32][] circular buffer containing raw samples from the realtime audio rendering thread.
+//                 Each frame is stored as `FRAME_SIZE` left samples followed by `FRAME_SIZE` right
+//                 samples.  It extends to the end of the SAB.
+
+import {
+  StereoAnalyzerBandNames,
+  StereoAnalyzerReading,
+  StereoAnalyzerReadingCount,
+  type StereoAnalyzerWorkerMessage,
+} from 'src/visualizations/StereoAnalyzer/types';
+
+enum RendererStatusFlag {
+  Running = 0,
+  Paused = 1,
+  Shutdown = 2,
+}
+
+const FRAME_SIZE = 128;
+const CORRELATION_METER_HEIGHT_PX = 14;
+const FONT = 'Hack, "Oxygen Mono", "Ubuntu Mono", "Lucida Console", monospace';
+
+class StereoAnalyzerRendererWorker {
+  private sabI32: Int32Array | null = null;
+  private samplesCircularBuffer: Float32Array = new Float32Array(0);
+  private wasmInstance: WebAssembly.Instance | null = null;
+  private lastProcessedBufferHeadIx = 0;
+  private view: OffscreenCanvas | null = null;
+  private ctx: OffscreenCanvasRenderingContext2D | null = null;
+  private dpr = 1;
+  private lastRenderTime: number | null = null;
+
+  constructor() {
+    this.startRenderLoop();
+  }
+
+  private get exports() {
+    return this.wasmInstance!.exports as {
+      memory: WebAssembly.Memory;
+      stereo_analyzer_set_view: (width: number, height: number, dpr: number) => void;
+      stereo_analyzer_get_frame_data_ptr: () => number;
+      stereo_analyzer_commit_samples: () => void;
+      stereo_analyzer_process: (dtMs: number) => void;
+      stereo_analyzer_get_image_data_buf_ptr: () => number;
+      stereo_analyzer_get_image_data_buf_len: () => number;
+      stereo_analyzer_get_readings_ptr: () => number;
+    };
+  }
+
+  private startRenderLoop = () => {
+    this.renderFrame();
+    requestAnimationFrame(this.startRenderLoop);
+  };
+
+  private renderFrame = () => {
+    const now = performance.now();
+    const dtMs = this.lastRenderTime === null ? 0 : now - this.lastRenderTime;
+    this.lastRenderTime = now;
+
+    if (!this.view || !this.ctx || !this.wasmInstance || !this.sabI32) {
+      return;
+    }
+
+    const status = Atomics.load(this.sabI32, 0);
+    if (status === RendererStatusFlag.Shutdown) {
+      self.close();
+      return;
+    } else if (status === RendererStatusFlag.Paused) {
+      // Skip over anything written while paused
+      this.lastProcessedBufferHeadIx = Atomics.load(this.sabI32, 7);
+      return;
+    }
+
+    this.consumeBuffer();
+    this.exports.stereo_analyzer_process(dtMs);
+
+    const imageDataPtr = this.exports.stereo_analyzer_get_image_data_buf_ptr();
+    const imageDataLenBytes = this.exports.stereo_analyzer_get_image_data_buf_len();
+    if (imageDataLenBytes === 0) {
+      return;
+    }
+    const imageData = new Uint8ClampedArray(
+      this.exports.memory.buffer,
+      imageDataPtr,
+      imageDataLenBytes
+    );
+    this.ctx.putImageData(new ImageData(imageData, this.view.width, this.view.height), 0, 0);
+
+    const readingsPtr = this.exports.stereo_analyzer_get_readings_ptr();
+    const readings = new Float32Array(
+      this.exports.memory.buffer,
+      readingsPtr,
+      StereoAnalyzerReadingCount
+    );
+    this.drawOverlay(readings);
+  };
+
+  private drawOverlay(readings: Float32Array) {
+    const ctx = this.ctx!;
+    const { width, height } = this.view!;
+    const dpr = this.dpr;
+
+    // Guides for mono (vertical), out-of-phase (horizontal), and left/right (diagonals)
+    // Matches the plot radius used by the Wasm renderer
+    const size = Math.min(width, height) * 0.95;
+    const cx = width / 2;
+    const cy = height / 2;
+    ctx.strokeStyle = 'rgba(255, 255, 255, 0.15)';
+    ctx.lineWidth = dpr;
+    ctx.beginPath();
+    ctx.moveTo(cx, cy - size / 2);
+    ctx.lineTo(cx, cy + size / 2);
+    ctx.moveTo(cx - size / 2, cy);
+    ctx.lineTo(cx + size / 2, cy);
+    ctx.moveTo(cx - size / 2.8, cy - size / 2.8);
+    ctx.lineTo(cx + size / 2.8, cy + size / 2.8);
+    ctx.moveTo(cx + size / 2.8, cy - size / 2.8);
+    ctx.lineTo(cx - size / 2.8, cy + size / 2.8);
+    ctx.stroke();
+
+    ctx.font = `${11 * dpr}px ${FONT}`;
+    ctx.fillStyle = 'rgba(255, 255, 255, 0.5)';
+    ctx.fillText('L', cx - size / 2.8 - 10 * dpr, cy - size / 2.8);
+    ctx.fillText('R', cx + size / 2.8 + 4 * dpr, cy - size / 2.8);
+    ctx.fillText('M', cx + 4 * dpr, cy - size / 2 + 12 * dpr);
+    ctx.fillText('S', cx + size / 2 - 10 * dpr, cy - 4 * dpr);
+
+    // Correlation meter along the bottom, from -1 on the left to +1 on the right
+    const correlation = readings[StereoAnalyzerReading.Correlation];
+    const meterHeight = CORRELATION_METER_HEIGHT_PX * dpr;
+    const meterY = height - meterHeight;
+    ctx.fillStyle = '#111';
+    ctx.fillRect(0, meterY, width, meterHeight);
+    const meterCenterX = width / 2;
+    const meterX = meterCenterX + (correlation * width) / 2;
+    ctx.fillStyle = correlation < 0 ? '#e04040' : '#40c080';
+    ctx.fillRect(
+      Math.min(meterCenterX, meterX),
+      meterY,
+      Math.abs(meterX - meterCenterX),
+      meterHeight
+    );
+    ctx.fillStyle = '#888';
+    ctx.fillRect(meterCenterX - dpr / 2, meterY, dpr, meterHeight);
+
+    const lines = [
+      `corr ${correlation.toFixed(2)}`,
+      `width ${(readings[StereoAnalyzerReading.Width] * 100).toFixed(0)}%`,
+      `mono ${readings[StereoAnalyzerReading.MonoLossDb].toFixed(1)}dB`,
+      ...StereoAnalyzerBandNames.map((name, bandIx) => {
+        const bandCorrelation = readings[StereoAnalyzerReading.BandCorrelationStart + bandIx];
+        const bandWidth = readings[StereoAnalyzerReading.BandWidthStart + bandIx];
+        return `${name.padEnd(4)} ${bandCorrelation.toFixed(2)} ${(bandWidth * 100).toFixed(0)}%`;
+      }),
+    ];
+    ctx.fillStyle = 'white';
+    lines.forEach((line, i) => ctx.fillText(line, 4 * dpr, (i + 1) * 13 * dpr));
+
+    const hasMonoCompatibilityProblem = StereoAnalyzerBandNames.some(
+      (_name, bandIx) => readings[StereoAnalyzerReading.BandCorrelationStart + bandIx] < -0.2
+    );
+    if (hasMonoCompatibilityProblem || correlation < 0) {
+      ctx.fillStyle = '#e04040';
+      ctx.fillText('MONO COMPATIBILITY', 4 * dpr, meterY - 4 * dpr);
+    }
+  }
+
+  /**
+   * Copies all frames written by the audio thread since the last render into Wasm and commits them
+   */
+  private consumeBuffer() {
+    const bufferHeadIx = Atomics.load(this.sabI32!, 7);
+    if (bufferHeadIx % (FRAME_SIZE * 2) !== 0) {
+      throw new Error(`Buffer head index is not a multiple of frame size: ${bufferHeadIx}`);
+    }
+
+    const frameDataPtr = this.exports.stereo_analyzer_get_frame_data_ptr();
+    while (this.lastProcessedBufferHeadIx !== bufferHeadIx) {
+      const frameData = new Float32Array(
+        this.exports.memory.buffer,
+        frameDataPtr,
+        FRAME_SIZE * 2
+      );
+      frameData.set(
+        this.samplesCircularBuffer.subarray(
+          this.lastProcessedBufferHeadIx,
+          this.lastProcessedBufferHeadIx + FRAME_SIZE * 2
+        )
+      );
+      this.exports.stereo_analyzer_commit_samples();
+
+      this.lastProcessedBufferHeadIx += FRAME_SIZE * 2;
+      if (this.lastProcessedBufferHeadIx >= this.samplesCircularBuffer.length) {
+        this.lastProcessedBufferHeadIx = 0;
+      }
+    }
+  }
+
+  public handleMessage(event: MessageEvent) {
+    const message: StereoAnalyzerWorkerMessage = event.data;
+
+    if (!message || typeof message !== 'object' || !message.type) {
+      console.warn(`Invalid message received in stereo analyzer renderer worker: ${message}`);
+      return;
+    }
+
+    switch (message.type) {
+      case 'setSAB':
+        this.sabI32 = new Int32Array(message.sab);
+        this.samplesCircularBuffer = new Float32Array(message.sab).subarray(8);
+        this.lastProcessedBufferHeadIx = Atomics.load(this.sabI32, 7);
+        break;
+      case 'setWasmBytes':
+        this.setWasmBytes(message.wasmBytes);
+        break;
+      case 'setView': {
+        this.view = message.view;
+        const ctx = this.view.getContext('2d');
+        if (!ctx) {
+          throw new Error('Could not get 2d context from offscreen canvas');
+        }
+        this.ctx = ctx;
+        this.dpr = message.dpr;
+        this.maybeSetViewToWasm();
+        break;
+      }
+      case 'resizeView':
+        if (this.view) {
+          this.view.width = message.newWidth * this.dpr;
+          this.view.height = message.newHeight * this.dpr;
+        }
+        this.maybeSetViewToWasm();
+        break;
+      default:
+        console.warn(
+          `Unknown message type in \`StereoAnalyzerRendererWorker\`: ${(message as any).type}`
+        );
+    }
+  }
+
+  private maybeSetViewToWasm() {
+    if (!this.view || !this.wasmInstance) {
+      return;
+    }
+
+    this.exports.stereo_analyzer_set_view(
+      this.view.width / this.dpr,
+      this.view.height / this.dpr,
+      this.dpr
+    );
+  }
+
+  private async setWasmBytes(wasmBytes: ArrayBuffer) {
+    const wasmModule = await WebAssembly.compile(wasmBytes);
+    this.wasmInstance = await WebAssembly.instantiate(wasmModule, {
+      env: {
+        log_err: (ptr: number, len: number) =>
+          console.error(
+            new TextDecoder().decode(new Uint8Array(this.exports.memory.buffer, ptr, len))
+          ),
+      },
+    });
+    this.maybeSetViewToWasm();
+  }
+}
+
+const stereoAnalyzerRendererWorker = new StereoAnalyzerRendererWorker();
+self.addEventListener('message', event => stereoAnalyzerRendererWorker.handleMessage(event));
@@ -0,0 +1,19 @@
+export type StereoAnalyzerWorkerMessage =
+  | { type: 'setSAB'; sab: SharedArrayBuffer }
+  | { type: 'setWasmBytes'; wasmBytes: ArrayBuffer }
+  | { type: 'setView'; view: OffscreenCanvas; dpr: number }
+  | { type: 'resizeView'; newWidth: number; newHeight: number };
+
+/**
+ * Indices into the readings buffer exposed by the `stereo_analyzer` Wasm module
+ */
+export enum StereoAnalyzerReading {
+  Correlation = 0,
+  Width = 1,
+  MonoLossDb = 2,
+  BandCorrelationStart = 3,
+  BandWidthStart = 6,
+}
+
+export const StereoAnalyzerReadingCount = 9;
+export const StereoAnalyzerBandNames = ['low', 'mid', 'high'] as const;
@@ -71,6 +71,44 @@ test('fromTempoChanges: anchoring keeps the current beat continuous (mid-playbac
   assert.ok(Math.abs(tm.beatAt(tm.frameAt(8)) - 8) < 1e-9);
 });
 
+test('fromTempoChanges: linear ramps integrate exactly like the Rust tempo map', () => {
+  // 60 -> 180bpm over 8 beats: average of 120bpm means the ramp takes exactly 4 seconds
+  const tm = TempoMap.fromTempoChanges(
+    SR,
+    [
+      { beat: 0, bpm: 60 },
+      { beat: 8, bpm: 180, ramp: true },
+    ],
+    0,
+    0
+  );
+  assert.ok(Math.abs(tm.frameAt(8) - 4 * SR) < 1e-6);
+  assert.ok(Math.abs(tm.bpmAt(2 * SR) - 120) < 1e-9);
+  assert.ok(Math.abs(tm.bpmAt(4 * SR) - 180) < 1e-9);
+  // integral of 60 + 30t bpm over the first two seconds: (60*2 + 15*4) / 60 = 3 beats
+  assert.ok(Math.abs(tm.beatAt(2 * SR) - 3) < 1e-9);
+  // after the ramp, the tempo stays at the target
+  assert.ok(Math.abs(tm.frameAt(11) - 5 * SR) < 1e-6);
+  for (let i = 0; i <= 1000; i++) {
+    const beat = i * 0.011;
+    assert.ok(Math.abs(tm.beatAt(tm.frameAt(beat)) - beat) < 1e-9);
+  }
+
+  // Anchoring mid-ramp keeps the position continuous: beat 2 is reached `sqrt(12) - 2` seconds in
+  const anchored = TempoMap.fromTempoChanges(
+    SR,
+    [
+      { beat: 0, bpm: 60 },
+      { beat: 8, bpm: 180, ramp: true },
+    ],
+    2,
+    100 * SR
+  );
+  const beat2Offset = (Math.sqrt(12) - 2) * SR;
+  assert.ok(Math.abs(anchored.beatAt(100 * SR) - 2) < 1e-9);
+  assert.ok(Math.abs(anchored.frameAt(0) - (100 * SR - beat2Offset)) < 1e-4);
+});
+
 test('pollMIDI: each event delivered exactly once, in the quantum containing its beat, at the right offset', () => {
   const tm = TempoMap.constant(SR, 120);
   const t = new Transport(tm, FRAME);